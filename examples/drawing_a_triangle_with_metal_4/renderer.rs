@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use metal::{prelude::*, *};
 use objc2::{msg_send, rc::Retained, runtime::ProtocolObject};
 use objc2_core_foundation::CGSize;
@@ -6,6 +8,7 @@ use objc2_quartz_core::CAMetalLayer;
 use crate::vertex_data::{VertexData, write_rotating_triangle_vertices};
 
 const MAX_FRAMES_IN_FLIGHT: usize = 3;
+const FRAME_TIMEOUT: Duration = Duration::from_secs(1);
 
 const SHADER_SOURCE: &str = r#"
 #include <metal_stdlib>
@@ -49,7 +52,7 @@ pub struct TriangleRenderer {
     vertex_buffers: Box<[Retained<ProtocolObject<dyn MTLBuffer>>]>,
     viewport_size_buffer: Retained<ProtocolObject<dyn MTLBuffer>>,
     shared_event: Retained<ProtocolObject<dyn MTLSharedEvent>>,
-    frame_number: u64,
+    timeline: Timeline<SharedEventNotifier>,
     viewport_size: [u32; 2],
 }
 
@@ -98,6 +101,7 @@ impl TriangleRenderer {
 
         let shared_event = device.new_shared_event().ok_or("Failed to create shared event")?;
         shared_event.set_signaled_value(0);
+        let timeline = Timeline::with_shared_event(&shared_event, MAX_FRAMES_IN_FLIGHT);
 
         Ok(Self {
             _device: device,
@@ -107,7 +111,7 @@ impl TriangleRenderer {
             vertex_buffers,
             viewport_size_buffer,
             shared_event,
-            frame_number: 0,
+            timeline,
             viewport_size,
         })
     }
@@ -127,30 +131,51 @@ impl TriangleRenderer {
     }
 
     pub fn render(&mut self) {
-        self.frame_number += 1;
-        let frame_index = (self.frame_number as usize) % MAX_FRAMES_IN_FLIGHT;
+        // Get the command buffer first: once a frame begins, its value must
+        // be signaled, or the next frame to reuse its slot waits forever.
+        let command_buffer = match self.command_queue.command_buffer() {
+            Some(command_buffer) => command_buffer,
+            None => return,
+        };
 
-        if self.frame_number > MAX_FRAMES_IN_FLIGHT as u64 {
-            let earlier_frame = self.frame_number - MAX_FRAMES_IN_FLIGHT as u64;
-            if self.shared_event.signaled_value() < earlier_frame {
-                self.shared_event.wait_until_signaled_value_timeout_ms(earlier_frame, 1000);
+        // Pace before acquiring a drawable so a slow GPU doesn't hold one of
+        // the layer's few drawables while we wait. Waits until the frame that
+        // last used this slot's vertex buffer is done; a timeout only means the
+        // GPU is behind, so keep waiting rather than drop the frame.
+        let frame = loop {
+            match self.timeline.begin_frame(FRAME_TIMEOUT) {
+                Ok(frame) => break frame,
+                Err(error) => eprintln!("still waiting for a frame slot: {error}"),
             }
-        }
-
-        let vertex_buffer = &self.vertex_buffers[frame_index];
-        let rotation_degrees = (self.frame_number % 360) as f32;
-        write_rotating_triangle_vertices(rotation_degrees, vertex_buffer.contents().as_ptr() as *mut VertexData);
+        };
 
         // CAMetalLayer/CAMetalDrawable return objc2-metal types; use msg_send
         // to stay in mtl-rs's type system.
         let drawable: Option<Retained<ProtocolObject<dyn MTLDrawable>>> =
             unsafe { msg_send![&*self.metal_layer, nextDrawable] };
-        let drawable = match drawable {
-            Some(drawable) => drawable,
-            None => return,
-        };
+        if let Some(drawable) = drawable {
+            self.encode_triangle(&command_buffer, &drawable, frame.slot, frame.value);
+            command_buffer.present_drawable(&drawable);
+        }
 
-        let drawable_texture: Retained<ProtocolObject<dyn MTLTexture>> = unsafe { msg_send![&*drawable, texture] };
+        // The frame's value is signaled even without a drawable, otherwise the
+        // next frame to reuse this slot would wait on it forever.
+        command_buffer.encode_signal_event_value(ProtocolObject::from_ref(&*self.shared_event), frame.value);
+        command_buffer.commit();
+    }
+
+    fn encode_triangle(
+        &self,
+        command_buffer: &ProtocolObject<dyn MTLCommandBuffer>,
+        drawable: &ProtocolObject<dyn MTLDrawable>,
+        slot: usize,
+        frame_value: u64,
+    ) {
+        let vertex_buffer = &self.vertex_buffers[slot];
+        let rotation_degrees = (frame_value % 360) as f32;
+        write_rotating_triangle_vertices(rotation_degrees, vertex_buffer.contents().as_ptr() as *mut VertexData);
+
+        let drawable_texture: Retained<ProtocolObject<dyn MTLTexture>> = unsafe { msg_send![drawable, texture] };
 
         let render_pass_descriptor = MTLRenderPassDescriptor::render_pass_descriptor();
         let color_attachment = render_pass_descriptor.color_attachments().object_at_indexed_subscript(0);
@@ -165,11 +190,6 @@ impl TriangleRenderer {
         });
         color_attachment.set_store_action(MTLStoreAction::Store);
 
-        let command_buffer = match self.command_queue.command_buffer() {
-            Some(command_buffer) => command_buffer,
-            None => return,
-        };
-
         let render_command_encoder =
             match command_buffer.render_command_encoder_with_descriptor(&render_pass_descriptor) {
                 Some(encoder) => encoder,
@@ -189,9 +209,5 @@ impl TriangleRenderer {
         render_command_encoder.set_vertex_buffer(Some(&self.viewport_size_buffer), 0, 1);
         render_command_encoder.draw_primitives(MTLPrimitiveType::Triangle, 0, 3);
         render_command_encoder.end_encoding();
-
-        command_buffer.present_drawable(&drawable);
-        command_buffer.encode_signal_event_value(ProtocolObject::from_ref(&*self.shared_event), self.frame_number);
-        command_buffer.commit();
     }
 }
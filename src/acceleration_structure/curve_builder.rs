@@ -0,0 +1,954 @@
+use core::{fmt, ops::Range};
+
+use objc2::runtime::ProtocolObject;
+
+use crate::{
+    MTLAccelerationStructureCurveGeometryDescriptor, MTLAttributeFormat, MTLBuffer, MTLCurveBasis, MTLIndexType,
+};
+
+/// A control point and its radius packed into one lane so that basis conversions
+/// transform both with the same weights.
+type Lane = [f32; 4];
+
+/// Errors produced while assembling or converting curve geometry.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CurveGeometryError {
+    /// The basis does not accept the requested number of control points per segment.
+    UnsupportedControlPointCount {
+        basis: MTLCurveBasis,
+        count: usize,
+    },
+    /// A strand needs at least two points.
+    StrandTooShort {
+        points: usize,
+    },
+    /// The radius slice must hold either one radius or one radius per point.
+    RadiusCountMismatch {
+        points: usize,
+        radii: usize,
+    },
+    /// A radius is negative, NaN or infinite.
+    InvalidRadius {
+        index: usize,
+        radius: f32,
+    },
+    /// A control point coordinate is NaN or infinite.
+    NonFiniteControlPoint {
+        index: usize,
+    },
+    /// The conversion cannot be expressed exactly in the target basis.
+    UnsupportedConversion {
+        from: MTLCurveBasis,
+        to: MTLCurveBasis,
+    },
+    /// A segment index refers to control points past the end of the buffer.
+    IndexOutOfBounds {
+        segment: usize,
+        index: u32,
+    },
+    /// The geometry does not fit 32-bit indices.
+    TooManyControlPoints,
+}
+
+impl fmt::Display for CurveGeometryError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::UnsupportedControlPointCount {
+                basis,
+                count,
+            } => {
+                write!(f, "{basis:?} curves do not support {count} control points per segment")
+            },
+            Self::StrandTooShort {
+                points,
+            } => write!(f, "a strand needs at least 2 points, got {points}"),
+            Self::RadiusCountMismatch {
+                points,
+                radii,
+            } => {
+                write!(f, "expected 1 or {points} radii, got {radii}")
+            },
+            Self::InvalidRadius {
+                index,
+                radius,
+            } => write!(f, "radius {index} is invalid: {radius}"),
+            Self::NonFiniteControlPoint {
+                index,
+            } => write!(f, "control point {index} is not finite"),
+            Self::UnsupportedConversion {
+                from,
+                to,
+            } => {
+                write!(f, "cannot convert {from:?} curve segments to {to:?}")
+            },
+            Self::IndexOutOfBounds {
+                segment,
+                index,
+            } => {
+                write!(f, "segment {segment} starts at control point {index}, which is out of bounds")
+            },
+            Self::TooManyControlPoints => f.write_str("control point count exceeds the 32-bit index range"),
+        }
+    }
+}
+
+impl std::error::Error for CurveGeometryError {}
+
+/// Returns whether `basis` accepts `count` control points per segment.
+///
+/// Linear curves use 2, Catmull-Rom curves use 4, and B-spline and Bézier
+/// curves use 3 (quadratic) or 4 (cubic).
+pub fn curve_basis_supports_control_point_count(
+    basis: MTLCurveBasis,
+    count: usize,
+) -> bool {
+    match basis {
+        MTLCurveBasis::Linear => count == 2,
+        MTLCurveBasis::CatmullRom => count == 4,
+        MTLCurveBasis::BSpline | MTLCurveBasis::Bezier => count == 3 || count == 4,
+    }
+}
+
+/// Axis-aligned bounds of a set of curve segments, including their radii.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CurveBounds {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl CurveBounds {
+    /// Bounds that contain nothing; the identity for [`union`][Self::union].
+    pub const EMPTY: Self = Self {
+        min: [f32::INFINITY; 3],
+        max: [f32::NEG_INFINITY; 3],
+    };
+
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|axis| self.min[axis] > self.max[axis])
+    }
+
+    pub fn union(
+        &self,
+        other: &Self,
+    ) -> Self {
+        let mut result = *self;
+        for axis in 0..3 {
+            result.min[axis] = result.min[axis].min(other.min[axis]);
+            result.max[axis] = result.max[axis].max(other.max[axis]);
+        }
+        result
+    }
+
+    pub fn contains(
+        &self,
+        point: [f32; 3],
+    ) -> bool {
+        (0..3).all(|axis| self.min[axis] <= point[axis] && point[axis] <= self.max[axis])
+    }
+}
+
+/// Control points, radii and segment indices laid out the way
+/// `MTLAccelerationStructureCurveGeometryDescriptor` expects them.
+///
+/// Each index points at the first of `segment_control_point_count` consecutive
+/// control points. Control points are packed `float3` values and radii are
+/// `float` values, so the buffers can be bound with a zero stride.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CurveGeometry {
+    basis: MTLCurveBasis,
+    segment_control_point_count: usize,
+    control_points: Vec<[f32; 3]>,
+    radii: Vec<f32>,
+    indices: Vec<u32>,
+}
+
+impl CurveGeometry {
+    pub fn basis(&self) -> MTLCurveBasis {
+        self.basis
+    }
+
+    pub fn segment_control_point_count(&self) -> usize {
+        self.segment_control_point_count
+    }
+
+    pub fn control_points(&self) -> &[[f32; 3]] {
+        &self.control_points
+    }
+
+    pub fn radii(&self) -> &[f32] {
+        &self.radii
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Control point data as packed `float3` bytes, ready for `new_buffer_with_data`.
+    pub fn control_point_bytes(&self) -> &[u8] {
+        // SAFETY: `[f32; 3]` has no padding and every byte of an `f32` is
+        // initialized, so the vector's storage is a valid `u8` slice of
+        // `size_of_val` bytes that lives as long as `self`.
+        unsafe {
+            core::slice::from_raw_parts(
+                self.control_points.as_ptr().cast(),
+                size_of_val(self.control_points.as_slice()),
+            )
+        }
+    }
+
+    /// Radius data as `float` bytes, ready for `new_buffer_with_data`.
+    pub fn radius_bytes(&self) -> &[u8] {
+        // SAFETY: `f32` has no padding or invalid bit patterns as bytes, and
+        // the slice borrows `self.radii` for its full length.
+        unsafe { core::slice::from_raw_parts(self.radii.as_ptr().cast(), size_of_val(self.radii.as_slice())) }
+    }
+
+    /// Segment index data as `uint32` bytes, ready for `new_buffer_with_data`.
+    pub fn index_bytes(&self) -> &[u8] {
+        // SAFETY: `u32` has no padding or invalid bit patterns as bytes, and
+        // the slice borrows `self.indices` for its full length.
+        unsafe { core::slice::from_raw_parts(self.indices.as_ptr().cast(), size_of_val(self.indices.as_slice())) }
+    }
+
+    /// Checks that every segment references control points inside the buffer and
+    /// that every radius is non-negative.
+    pub fn validate(&self) -> Result<(), CurveGeometryError> {
+        for (index, radius) in self.radii.iter().enumerate() {
+            if !(radius.is_finite() && *radius >= 0.0) {
+                return Err(CurveGeometryError::InvalidRadius {
+                    index,
+                    radius: *radius,
+                });
+            }
+        }
+        for (index, point) in self.control_points.iter().enumerate() {
+            if !point.iter().all(|c| c.is_finite()) {
+                return Err(CurveGeometryError::NonFiniteControlPoint {
+                    index,
+                });
+            }
+        }
+        for (segment, &index) in self.indices.iter().enumerate() {
+            if index as usize + self.segment_control_point_count > self.control_points.len() {
+                return Err(CurveGeometryError::IndexOutOfBounds {
+                    segment,
+                    index,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Evaluates the centerline position and radius of `segment` at `t` in `[0, 1]`.
+    pub fn evaluate(
+        &self,
+        segment: usize,
+        t: f32,
+    ) -> ([f32; 3], f32) {
+        let bezier = to_cubic_bezier(self.basis, &self.segment_lanes(segment));
+        let lane = eval_cubic_bezier(&bezier, t);
+        ([lane[0], lane[1], lane[2]], lane[3])
+    }
+
+    /// Conservative bounds of one segment: the exact bounds of its centerline
+    /// grown by the largest radius reached along the segment.
+    pub fn segment_bounds(
+        &self,
+        segment: usize,
+    ) -> CurveBounds {
+        let bezier = to_cubic_bezier(self.basis, &self.segment_lanes(segment));
+        let mut bounds = CurveBounds::EMPTY;
+        let (_, max_radius) = cubic_extrema(&bezier, 3);
+        let max_radius = max_radius.max(0.0);
+        for axis in 0..3 {
+            let (lo, hi) = cubic_extrema(&bezier, axis);
+            bounds.min[axis] = lo - max_radius;
+            bounds.max[axis] = hi + max_radius;
+        }
+        bounds
+    }
+
+    /// Bounds of the whole geometry, as the union of [`segment_bounds`][Self::segment_bounds].
+    pub fn bounds(&self) -> CurveBounds {
+        (0..self.segment_count()).fold(CurveBounds::EMPTY, |acc, segment| acc.union(&self.segment_bounds(segment)))
+    }
+
+    /// Re-expresses every segment in `basis` using four control points per segment.
+    ///
+    /// Segments no longer share control points afterwards. Converting into the
+    /// B-spline or Catmull-Rom basis can produce negative radii for strongly
+    /// varying radius profiles; [`validate`][Self::validate] reports those.
+    /// Converting to the linear basis is only possible from linear geometry.
+    pub fn convert_basis(
+        &self,
+        basis: MTLCurveBasis,
+    ) -> Result<Self, CurveGeometryError> {
+        if basis == MTLCurveBasis::Linear {
+            return if self.basis == MTLCurveBasis::Linear {
+                Ok(self.clone())
+            } else {
+                Err(CurveGeometryError::UnsupportedConversion {
+                    from: self.basis,
+                    to: basis,
+                })
+            };
+        }
+        let segment_count = self.segment_count();
+        let mut converted = Self {
+            basis,
+            segment_control_point_count: 4,
+            control_points: Vec::with_capacity(segment_count * 4),
+            radii: Vec::with_capacity(segment_count * 4),
+            indices: Vec::with_capacity(segment_count),
+        };
+        for segment in 0..segment_count {
+            let bezier = to_cubic_bezier(self.basis, &self.segment_lanes(segment));
+            let lanes = from_cubic_bezier(basis, &bezier);
+            converted.indices.push(index_u32(converted.control_points.len())?);
+            for lane in lanes {
+                converted.control_points.push([lane[0], lane[1], lane[2]]);
+                converted.radii.push(lane[3]);
+            }
+        }
+        Ok(converted)
+    }
+
+    /// Points `descriptor` at the given buffers and configures counts, formats
+    /// and the basis for this geometry.
+    ///
+    /// The buffers must hold [`control_point_bytes`][Self::control_point_bytes],
+    /// [`radius_bytes`][Self::radius_bytes] and [`index_bytes`][Self::index_bytes]
+    /// at offset zero. Curve type and end caps are left for the caller to choose.
+    pub fn configure_descriptor(
+        &self,
+        descriptor: &MTLAccelerationStructureCurveGeometryDescriptor,
+        control_point_buffer: &ProtocolObject<dyn MTLBuffer>,
+        radius_buffer: &ProtocolObject<dyn MTLBuffer>,
+        index_buffer: &ProtocolObject<dyn MTLBuffer>,
+    ) {
+        descriptor.set_control_point_buffer(Some(control_point_buffer));
+        descriptor.set_control_point_buffer_offset(0);
+        descriptor.set_control_point_count(self.control_points.len());
+        descriptor.set_control_point_stride(0);
+        descriptor.set_control_point_format(MTLAttributeFormat::Float3);
+        descriptor.set_radius_buffer(Some(radius_buffer));
+        descriptor.set_radius_buffer_offset(0);
+        descriptor.set_radius_stride(0);
+        descriptor.set_radius_format(MTLAttributeFormat::Float);
+        descriptor.set_index_buffer(Some(index_buffer));
+        descriptor.set_index_buffer_offset(0);
+        descriptor.set_index_type(MTLIndexType::UInt32);
+        descriptor.set_segment_count(self.indices.len());
+        descriptor.set_segment_control_point_count(self.segment_control_point_count);
+        descriptor.set_curve_basis(self.basis);
+    }
+
+    fn segment_lanes(
+        &self,
+        segment: usize,
+    ) -> Vec<Lane> {
+        let start = self.indices[segment] as usize;
+        (start..start + self.segment_control_point_count)
+            .map(|i| {
+                let [x, y, z] = self.control_points[i];
+                [x, y, z, self.radii[i]]
+            })
+            .collect()
+    }
+}
+
+/// Converts polylines (hair strands, fur guides) into [`CurveGeometry`] for a
+/// given basis.
+///
+/// Every strand is turned into a curve that starts at its first point and ends
+/// at its last point:
+/// - Linear: the points become the control points, one segment per edge.
+/// - Catmull-Rom: mirrored phantom points are added at both ends so the curve
+///   passes through every point.
+/// - B-spline: the end points are repeated so the clamped curve reaches them;
+///   interior points act as a control polygon.
+/// - Bézier: cubic segments are derived from the Catmull-Rom interpolant and
+///   share their end points; quadratic segments join edge midpoints.
+#[derive(Clone, Debug)]
+pub struct CurveGeometryBuilder {
+    geometry: CurveGeometry,
+}
+
+impl CurveGeometryBuilder {
+    /// Creates a builder for `basis` with its default segment size: 2 control
+    /// points for linear curves and 4 for the others.
+    pub fn new(basis: MTLCurveBasis) -> Self {
+        let segment_control_point_count = match basis {
+            MTLCurveBasis::Linear => 2,
+            _ => 4,
+        };
+        Self {
+            geometry: CurveGeometry {
+                basis,
+                segment_control_point_count,
+                control_points: Vec::new(),
+                radii: Vec::new(),
+                indices: Vec::new(),
+            },
+        }
+    }
+
+    /// Creates a builder for `basis` with an explicit segment size.
+    pub fn with_segment_control_point_count(
+        basis: MTLCurveBasis,
+        count: usize,
+    ) -> Result<Self, CurveGeometryError> {
+        if !curve_basis_supports_control_point_count(basis, count) {
+            return Err(CurveGeometryError::UnsupportedControlPointCount {
+                basis,
+                count,
+            });
+        }
+        let mut builder = Self::new(basis);
+        builder.geometry.segment_control_point_count = count;
+        Ok(builder)
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.geometry.segment_count()
+    }
+
+    /// Appends a strand with a constant radius.
+    pub fn add_strand_with_radius(
+        &mut self,
+        points: &[[f32; 3]],
+        radius: f32,
+    ) -> Result<Range<usize>, CurveGeometryError> {
+        self.add_strand(points, &[radius])
+    }
+
+    /// Appends a strand and returns the range of segments it occupies.
+    ///
+    /// `radii` holds either a single radius for the whole strand or one radius
+    /// per point.
+    pub fn add_strand(
+        &mut self,
+        points: &[[f32; 3]],
+        radii: &[f32],
+    ) -> Result<Range<usize>, CurveGeometryError> {
+        if points.len() < 2 {
+            return Err(CurveGeometryError::StrandTooShort {
+                points: points.len(),
+            });
+        }
+        if radii.len() != 1 && radii.len() != points.len() {
+            return Err(CurveGeometryError::RadiusCountMismatch {
+                points: points.len(),
+                radii: radii.len(),
+            });
+        }
+        for (index, radius) in radii.iter().enumerate() {
+            if !(radius.is_finite() && *radius >= 0.0) {
+                return Err(CurveGeometryError::InvalidRadius {
+                    index,
+                    radius: *radius,
+                });
+            }
+        }
+        if let Some(index) = points.iter().position(|p| !p.iter().all(|c| c.is_finite())) {
+            return Err(CurveGeometryError::NonFiniteControlPoint {
+                index,
+            });
+        }
+
+        let lanes: Vec<Lane> = points
+            .iter()
+            .enumerate()
+            .map(|(i, &[x, y, z])| {
+                [
+                    x,
+                    y,
+                    z,
+                    if radii.len() == 1 {
+                        radii[0]
+                    } else {
+                        radii[i]
+                    },
+                ]
+            })
+            .collect();
+
+        let geometry = &mut self.geometry;
+        let first_segment = geometry.indices.len();
+        let base = geometry.control_points.len();
+        let k = geometry.segment_control_point_count;
+        let (control_points, step) = match (geometry.basis, k) {
+            (MTLCurveBasis::Linear, _) => (lanes, 1),
+            (MTLCurveBasis::CatmullRom, _) => {
+                let n = lanes.len();
+                let mut padded = Vec::with_capacity(n + 2);
+                padded.push(sub(scale(lanes[0], 2.0), lanes[1]));
+                padded.extend_from_slice(&lanes);
+                padded.push(sub(scale(lanes[n - 1], 2.0), lanes[n - 2]));
+                (padded, 1)
+            },
+            (MTLCurveBasis::BSpline, _) => (clamp_ends(&lanes, k - 2), 1),
+            (MTLCurveBasis::Bezier, 4) => {
+                let n = lanes.len();
+                let mut bezier = Vec::with_capacity(3 * (n - 1) + 1);
+                bezier.push(lanes[0]);
+                for i in 0..n - 1 {
+                    let prev = if i == 0 {
+                        sub(scale(lanes[0], 2.0), lanes[1])
+                    } else {
+                        lanes[i - 1]
+                    };
+                    let next = if i + 2 < n {
+                        lanes[i + 2]
+                    } else {
+                        sub(scale(lanes[n - 1], 2.0), lanes[n - 2])
+                    };
+                    let segment = to_cubic_bezier(MTLCurveBasis::CatmullRom, &[prev, lanes[i], lanes[i + 1], next]);
+                    bezier.extend_from_slice(&segment[1..]);
+                }
+                (bezier, 3)
+            },
+            (MTLCurveBasis::Bezier, _) => {
+                let padded = clamp_ends(&lanes, 1);
+                let mut bezier = vec![padded[0]];
+                for window in padded.windows(3) {
+                    bezier.push(window[1]);
+                    bezier.push(scale(add(window[1], window[2]), 0.5));
+                }
+                (bezier, 2)
+            },
+        };
+
+        let segments = (control_points.len() - k) / step + 1;
+        index_u32(base + control_points.len())?;
+        for segment in 0..segments {
+            geometry.indices.push((base + segment * step) as u32);
+        }
+        for lane in control_points {
+            geometry.control_points.push([lane[0], lane[1], lane[2]]);
+            geometry.radii.push(lane[3]);
+        }
+        Ok(first_segment..geometry.indices.len())
+    }
+
+    pub fn build(self) -> CurveGeometry {
+        self.geometry
+    }
+}
+
+/// Converts one curve segment from `from` to the cubic form of `to`.
+///
+/// The segment may have 2, 3 or 4 control points as allowed by `from`; lower
+/// degree segments are elevated exactly. Converting to the linear basis is only
+/// possible for linear segments.
+pub fn convert_curve_segment(
+    from: MTLCurveBasis,
+    to: MTLCurveBasis,
+    control_points: &[[f32; 3]],
+) -> Result<Vec<[f32; 3]>, CurveGeometryError> {
+    if !curve_basis_supports_control_point_count(from, control_points.len()) {
+        return Err(CurveGeometryError::UnsupportedControlPointCount {
+            basis: from,
+            count: control_points.len(),
+        });
+    }
+    if to == MTLCurveBasis::Linear {
+        return if from == MTLCurveBasis::Linear {
+            Ok(control_points.to_vec())
+        } else {
+            Err(CurveGeometryError::UnsupportedConversion {
+                from,
+                to,
+            })
+        };
+    }
+    let lanes: Vec<Lane> = control_points.iter().map(|&[x, y, z]| [x, y, z, 0.0]).collect();
+    let converted = from_cubic_bezier(to, &to_cubic_bezier(from, &lanes));
+    Ok(converted.iter().map(|l| [l[0], l[1], l[2]]).collect())
+}
+
+fn index_u32(value: usize) -> Result<u32, CurveGeometryError> {
+    u32::try_from(value).map_err(|_| CurveGeometryError::TooManyControlPoints)
+}
+
+/// Repeats the first and last lane `extra` more times.
+fn clamp_ends(
+    lanes: &[Lane],
+    extra: usize,
+) -> Vec<Lane> {
+    let mut padded = Vec::with_capacity(lanes.len() + 2 * extra);
+    padded.extend(core::iter::repeat_n(lanes[0], extra));
+    padded.extend_from_slice(lanes);
+    padded.extend(core::iter::repeat_n(lanes[lanes.len() - 1], extra));
+    padded
+}
+
+fn add(
+    a: Lane,
+    b: Lane,
+) -> Lane {
+    core::array::from_fn(|i| a[i] + b[i])
+}
+
+fn sub(
+    a: Lane,
+    b: Lane,
+) -> Lane {
+    core::array::from_fn(|i| a[i] - b[i])
+}
+
+fn scale(
+    a: Lane,
+    s: f32,
+) -> Lane {
+    a.map(|v| v * s)
+}
+
+fn combine(
+    points: &[Lane],
+    weights: &[f32],
+) -> Lane {
+    points.iter().zip(weights).fold([0.0; 4], |acc, (p, w)| add(acc, scale(*p, *w)))
+}
+
+/// Expresses a segment of any basis as an equivalent cubic Bézier segment.
+fn to_cubic_bezier(
+    basis: MTLCurveBasis,
+    p: &[Lane],
+) -> [Lane; 4] {
+    match (basis, p.len()) {
+        (MTLCurveBasis::Linear, _) => {
+            [p[0], combine(p, &[2.0 / 3.0, 1.0 / 3.0]), combine(p, &[1.0 / 3.0, 2.0 / 3.0]), p[1]]
+        },
+        (MTLCurveBasis::BSpline, 3) => {
+            elevate_quadratic([combine(p, &[0.5, 0.5, 0.0]), p[1], combine(p, &[0.0, 0.5, 0.5])])
+        },
+        (MTLCurveBasis::Bezier, 3) => elevate_quadratic([p[0], p[1], p[2]]),
+        (MTLCurveBasis::BSpline, _) => [
+            combine(p, &[1.0 / 6.0, 4.0 / 6.0, 1.0 / 6.0, 0.0]),
+            combine(p, &[0.0, 4.0 / 6.0, 2.0 / 6.0, 0.0]),
+            combine(p, &[0.0, 2.0 / 6.0, 4.0 / 6.0, 0.0]),
+            combine(p, &[0.0, 1.0 / 6.0, 4.0 / 6.0, 1.0 / 6.0]),
+        ],
+        (MTLCurveBasis::CatmullRom, _) => {
+            [p[1], combine(p, &[-1.0 / 6.0, 1.0, 1.0 / 6.0, 0.0]), combine(p, &[0.0, 1.0 / 6.0, 1.0, -1.0 / 6.0]), p[2]]
+        },
+        (MTLCurveBasis::Bezier, _) => [p[0], p[1], p[2], p[3]],
+    }
+}
+
+fn elevate_quadratic(q: [Lane; 3]) -> [Lane; 4] {
+    [q[0], combine(&q, &[1.0 / 3.0, 2.0 / 3.0, 0.0]), combine(&q, &[0.0, 2.0 / 3.0, 1.0 / 3.0]), q[2]]
+}
+
+/// Expresses a cubic Bézier segment in the cubic form of `basis`.
+fn from_cubic_bezier(
+    basis: MTLCurveBasis,
+    b: &[Lane; 4],
+) -> [Lane; 4] {
+    match basis {
+        MTLCurveBasis::BSpline => [
+            combine(b, &[6.0, -7.0, 2.0, 0.0]),
+            combine(b, &[0.0, 2.0, -1.0, 0.0]),
+            combine(b, &[0.0, -1.0, 2.0, 0.0]),
+            combine(b, &[0.0, 2.0, -7.0, 6.0]),
+        ],
+        MTLCurveBasis::CatmullRom => {
+            [combine(b, &[6.0, -6.0, 0.0, 1.0]), b[0], b[3], combine(b, &[1.0, 0.0, -6.0, 6.0])]
+        },
+        MTLCurveBasis::Bezier | MTLCurveBasis::Linear => *b,
+    }
+}
+
+fn eval_cubic_bezier(
+    b: &[Lane; 4],
+    t: f32,
+) -> Lane {
+    let s = 1.0 - t;
+    combine(b, &[s * s * s, 3.0 * s * s * t, 3.0 * s * t * t, t * t * t])
+}
+
+/// Minimum and maximum of lane `axis` of a cubic Bézier segment over `[0, 1]`.
+fn cubic_extrema(
+    b: &[Lane; 4],
+    axis: usize,
+) -> (f32, f32) {
+    let [p0, p1, p2, p3] = [b[0][axis], b[1][axis], b[2][axis], b[3][axis]];
+    let mut lo = p0.min(p3);
+    let mut hi = p0.max(p3);
+    // The derivative is a quadratic a t^2 + b t + c.
+    let a = 3.0 * (-p0 + 3.0 * p1 - 3.0 * p2 + p3);
+    let bq = 6.0 * (p0 - 2.0 * p1 + p2);
+    let c = 3.0 * (p1 - p0);
+    let mut roots = [f32::NAN; 2];
+    if a.abs() < 1e-12 {
+        if bq.abs() > 1e-12 {
+            roots[0] = -c / bq;
+        }
+    } else {
+        let discriminant = bq * bq - 4.0 * a * c;
+        if discriminant >= 0.0 {
+            let sqrt = discriminant.sqrt();
+            roots = [(-bq + sqrt) / (2.0 * a), (-bq - sqrt) / (2.0 * a)];
+        }
+    }
+    for t in roots {
+        if t > 0.0 && t < 1.0 {
+            let value = eval_cubic_bezier(b, t)[axis];
+            lo = lo.min(value);
+            hi = hi.max(value);
+        }
+    }
+    (lo, hi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STRAND: [[f32; 3]; 4] = [[0.0, 0.0, 0.0], [1.0, 2.0, 0.0], [3.0, 2.0, 1.0], [4.0, 0.0, 1.0]];
+
+    fn strand(
+        basis: MTLCurveBasis,
+        count: usize,
+    ) -> CurveGeometry {
+        let mut builder = CurveGeometryBuilder::with_segment_control_point_count(basis, count).unwrap();
+        builder.add_strand(&STRAND, &[0.1, 0.2, 0.3, 0.4]).unwrap();
+        builder.build()
+    }
+
+    fn assert_close(
+        actual: [f32; 3],
+        expected: [f32; 3],
+    ) {
+        assert!(actual.iter().zip(expected).all(|(a, e)| (a - e).abs() < 1e-4), "{actual:?} != {expected:?}");
+    }
+
+    fn floats(bytes: &[u8]) -> Vec<f32> {
+        bytes.chunks_exact(4).map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap())).collect()
+    }
+
+    #[test]
+    fn buffer_layout() {
+        let mut builder = CurveGeometryBuilder::new(MTLCurveBasis::Linear);
+        assert_eq!(builder.add_strand_with_radius(&STRAND[..3], 0.5).unwrap(), 0..2);
+        assert_eq!(builder.add_strand(&STRAND[2..], &[1.0, 2.0]).unwrap(), 2..3);
+        let geometry = builder.build();
+
+        assert_eq!(geometry.segment_control_point_count(), 2);
+        assert_eq!(geometry.control_points(), [STRAND[0], STRAND[1], STRAND[2], STRAND[2], STRAND[3]]);
+        assert_eq!(geometry.radii(), [0.5, 0.5, 0.5, 1.0, 2.0]);
+        // The second strand starts after the first one's control points.
+        assert_eq!(geometry.indices(), [0, 1, 3]);
+
+        // Packed float3, float and uint32 with no stride padding.
+        assert_eq!(geometry.control_point_bytes().len(), 5 * 12);
+        assert_eq!(floats(&geometry.control_point_bytes()[12..24]), [1.0, 2.0, 0.0]);
+        assert_eq!(floats(geometry.radius_bytes()), geometry.radii());
+        assert_eq!(geometry.index_bytes(), [0, 0, 0, 0, 1, 0, 0, 0, 3, 0, 0, 0]);
+        assert_eq!(geometry.validate(), Ok(()));
+    }
+
+    #[test]
+    fn segment_counts() {
+        // Catmull-Rom adds a phantom point at each end.
+        let catmull_rom = strand(MTLCurveBasis::CatmullRom, 4);
+        assert_eq!(catmull_rom.control_points().len(), 6);
+        assert_eq!(catmull_rom.indices(), [0, 1, 2]);
+        assert_eq!(catmull_rom.control_points()[0], [-1.0, -2.0, 0.0]);
+        assert_eq!(catmull_rom.control_points()[5], [5.0, -2.0, 1.0]);
+        assert_eq!(catmull_rom.radii()[0], 0.0);
+
+        // B-splines repeat the end points so the clamped curve reaches them.
+        let cubic_b_spline = strand(MTLCurveBasis::BSpline, 4);
+        assert_eq!(cubic_b_spline.control_points().len(), 8);
+        assert_eq!(cubic_b_spline.indices(), [0, 1, 2, 3, 4]);
+        let quadratic_b_spline = strand(MTLCurveBasis::BSpline, 3);
+        assert_eq!(quadratic_b_spline.control_points().len(), 6);
+        assert_eq!(quadratic_b_spline.indices(), [0, 1, 2, 3]);
+
+        // Cubic Bézier segments share their end points.
+        let cubic_bezier = strand(MTLCurveBasis::Bezier, 4);
+        assert_eq!(cubic_bezier.control_points().len(), 10);
+        assert_eq!(cubic_bezier.indices(), [0, 3, 6]);
+        let quadratic_bezier = strand(MTLCurveBasis::Bezier, 3);
+        assert_eq!(quadratic_bezier.control_points().len(), 9);
+        assert_eq!(quadratic_bezier.indices(), [0, 2, 4, 6]);
+
+        for geometry in [catmull_rom, cubic_b_spline, quadratic_b_spline, cubic_bezier, quadratic_bezier] {
+            let last = geometry.segment_count() - 1;
+            let (start, start_radius) = geometry.evaluate(0, 0.0);
+            let (end, end_radius) = geometry.evaluate(last, 1.0);
+            assert_close(start, STRAND[0]);
+            assert_close(end, STRAND[3]);
+            assert!((start_radius - 0.1).abs() < 1e-4 && (end_radius - 0.4).abs() < 1e-4);
+            assert_eq!(geometry.validate(), Ok(()), "{:?}", geometry.basis());
+        }
+    }
+
+    #[test]
+    fn interpolating_bases_pass_through_points() {
+        for geometry in [strand(MTLCurveBasis::CatmullRom, 4), strand(MTLCurveBasis::Bezier, 4)] {
+            for (segment, point) in STRAND[1..].iter().enumerate() {
+                assert_close(geometry.evaluate(segment, 1.0).0, *point);
+            }
+        }
+    }
+
+    #[test]
+    fn segment_conversion() {
+        let bezier = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [2.0, 0.0, 0.0], [3.0, 0.0, 0.0]];
+        let b_spline = convert_curve_segment(MTLCurveBasis::Bezier, MTLCurveBasis::BSpline, &bezier).unwrap();
+        assert_eq!(b_spline, [[-3.0, 0.0, 0.0], [0.0, 0.0, 0.0], [3.0, 0.0, 0.0], [6.0, 0.0, 0.0]]);
+        let back = convert_curve_segment(MTLCurveBasis::BSpline, MTLCurveBasis::Bezier, &b_spline).unwrap();
+        for (actual, expected) in back.into_iter().zip(bezier) {
+            assert_close(actual, expected);
+        }
+
+        let catmull_rom = [[-1.0, 1.0, 0.0], [0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [2.0, 1.0, 0.0]];
+        let bezier = convert_curve_segment(MTLCurveBasis::CatmullRom, MTLCurveBasis::Bezier, &catmull_rom).unwrap();
+        let third = 1.0 / 3.0;
+        let expected = [[0.0, 0.0, 0.0], [third, -1.0 / 6.0, 0.0], [2.0 * third, -1.0 / 6.0, 0.0], [1.0, 0.0, 0.0]];
+        for (actual, expected) in bezier.into_iter().zip(expected) {
+            assert_close(actual, expected);
+        }
+
+        // A quadratic Bézier is elevated exactly.
+        let quadratic = [[0.0, 0.0, 0.0], [3.0, 3.0, 0.0], [6.0, 0.0, 0.0]];
+        let cubic = convert_curve_segment(MTLCurveBasis::Bezier, MTLCurveBasis::Bezier, &quadratic).unwrap();
+        assert_eq!(cubic, [[0.0, 0.0, 0.0], [2.0, 2.0, 0.0], [4.0, 2.0, 0.0], [6.0, 0.0, 0.0]]);
+
+        assert_eq!(
+            convert_curve_segment(MTLCurveBasis::CatmullRom, MTLCurveBasis::Linear, &catmull_rom),
+            Err(CurveGeometryError::UnsupportedConversion {
+                from: MTLCurveBasis::CatmullRom,
+                to: MTLCurveBasis::Linear,
+            })
+        );
+        assert_eq!(
+            convert_curve_segment(MTLCurveBasis::CatmullRom, MTLCurveBasis::BSpline, &quadratic),
+            Err(CurveGeometryError::UnsupportedControlPointCount {
+                basis: MTLCurveBasis::CatmullRom,
+                count: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn geometry_conversion_preserves_curve() {
+        for (basis, count) in [(MTLCurveBasis::CatmullRom, 4), (MTLCurveBasis::Bezier, 4), (MTLCurveBasis::Bezier, 3)] {
+            let source = strand(basis, count);
+            let converted = source.convert_basis(MTLCurveBasis::BSpline).unwrap();
+            assert_eq!(converted.basis(), MTLCurveBasis::BSpline);
+            assert_eq!(converted.segment_control_point_count(), 4);
+            assert_eq!(converted.segment_count(), source.segment_count());
+            assert_eq!(converted.control_points().len(), 4 * source.segment_count());
+            assert_eq!(converted.indices()[1], 4);
+            for segment in 0..source.segment_count() {
+                for t in [0.0, 0.3, 0.5, 1.0] {
+                    let (expected, expected_radius) = source.evaluate(segment, t);
+                    let (actual, actual_radius) = converted.evaluate(segment, t);
+                    assert_close(actual, expected);
+                    assert!((actual_radius - expected_radius).abs() < 1e-4);
+                }
+            }
+        }
+        assert!(matches!(
+            strand(MTLCurveBasis::BSpline, 4).convert_basis(MTLCurveBasis::Linear),
+            Err(CurveGeometryError::UnsupportedConversion { .. })
+        ));
+    }
+
+    #[test]
+    fn bounds() {
+        let mut builder = CurveGeometryBuilder::new(MTLCurveBasis::Linear);
+        builder.add_strand_with_radius(&[[0.0, 0.0, 0.0], [2.0, 0.0, 0.0]], 0.5).unwrap();
+        let linear = builder.build();
+        assert_eq!(
+            linear.bounds(),
+            CurveBounds {
+                min: [-0.5, -0.5, -0.5],
+                max: [2.5, 0.5, 0.5],
+            }
+        );
+
+        // A Bézier arch peaks at 0.75 between its end points, above neither
+        // of them and below its control polygon.
+        let geometry = CurveGeometry {
+            basis: MTLCurveBasis::Bezier,
+            segment_control_point_count: 4,
+            control_points: vec![[0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 1.0, 0.0], [1.0, 0.0, 0.0]],
+            radii: vec![0.0, 0.0, 0.0, 0.0],
+            indices: vec![0],
+        };
+        let bounds = geometry.bounds();
+        assert_close(bounds.min, [0.0, 0.0, 0.0]);
+        assert_close(bounds.max, [1.0, 0.75, 0.0]);
+
+        let geometry = strand(MTLCurveBasis::CatmullRom, 4);
+        let bounds = geometry.bounds();
+        for segment in 0..geometry.segment_count() {
+            for step in 0..=16 {
+                assert!(bounds.contains(geometry.evaluate(segment, step as f32 / 16.0).0));
+            }
+        }
+        assert!(CurveBounds::EMPTY.is_empty());
+        assert_eq!(CurveBounds::EMPTY.union(&bounds), bounds);
+    }
+
+    #[test]
+    fn errors() {
+        let mut builder = CurveGeometryBuilder::new(MTLCurveBasis::CatmullRom);
+        assert_eq!(
+            builder.add_strand_with_radius(&STRAND[..1], 1.0),
+            Err(CurveGeometryError::StrandTooShort {
+                points: 1
+            })
+        );
+        assert_eq!(
+            builder.add_strand(&STRAND, &[1.0, 1.0]),
+            Err(CurveGeometryError::RadiusCountMismatch {
+                points: 4,
+                radii: 2,
+            })
+        );
+        assert!(matches!(
+            builder.add_strand(&STRAND, &[1.0, f32::NAN, 1.0, 1.0]),
+            Err(CurveGeometryError::InvalidRadius {
+                index: 1,
+                ..
+            })
+        ));
+        assert_eq!(
+            builder.add_strand_with_radius(&[[0.0; 3], [f32::INFINITY, 0.0, 0.0]], 1.0),
+            Err(CurveGeometryError::NonFiniteControlPoint {
+                index: 1
+            })
+        );
+        assert_eq!(builder.segment_count(), 0);
+        assert!(matches!(
+            CurveGeometryBuilder::with_segment_control_point_count(MTLCurveBasis::Linear, 3),
+            Err(CurveGeometryError::UnsupportedControlPointCount {
+                count: 3,
+                ..
+            })
+        ));
+
+        let mut geometry = strand(MTLCurveBasis::Bezier, 4);
+        geometry.indices.push(7);
+        assert_eq!(
+            geometry.validate(),
+            Err(CurveGeometryError::IndexOutOfBounds {
+                segment: 3,
+                index: 7,
+            })
+        );
+    }
+}
@@ -1,6 +1,7 @@
 mod acceleration_structure;
 mod bounding_box_geometry_descriptor;
 mod curve_basis;
+mod curve_builder;
 mod curve_end_caps;
 mod curve_geometry_descriptor;
 mod curve_type;
@@ -31,6 +32,10 @@ mod user_id_instance_descriptor;
 pub use acceleration_structure::MTLAccelerationStructure;
 pub use bounding_box_geometry_descriptor::MTLAccelerationStructureBoundingBoxGeometryDescriptor;
 pub use curve_basis::MTLCurveBasis;
+pub use curve_builder::{
+    CurveBounds, CurveGeometry, CurveGeometryBuilder, CurveGeometryError, convert_curve_segment,
+    curve_basis_supports_control_point_count,
+};
 pub use curve_end_caps::MTLCurveEndCaps;
 pub use curve_geometry_descriptor::MTLAccelerationStructureCurveGeometryDescriptor;
 pub use curve_type::MTLCurveType;
@@ -1,6 +1,7 @@
 use objc2::{Message, extern_protocol, msg_send, rc::Retained, runtime::ProtocolObject};
 use objc2_foundation::{NSError, NSObjectProtocol, NSString};
 
+use super::{CommandBufferCompletion, completion::CompletionStart};
 use crate::{
     MTLAccelerationStructureCommandEncoder, MTLAccelerationStructurePassDescriptor, MTLBlitCommandEncoder,
     MTLBlitPassDescriptor, MTLCommandBufferHandler, MTLCommandBufferStatus, MTLCommandQueue, MTLComputeCommandEncoder,
@@ -199,6 +200,14 @@ pub trait MTLCommandBufferExt: MTLCommandBuffer + Message {
         &self,
         handler: &MTLCommandBufferHandler,
     );
+    /// A future resolving when this command buffer has completed execution,
+    /// with its error if it failed.
+    ///
+    /// Registers a completed handler, so call this before `commit`. Metal
+    /// rejects handlers added after `commit`, so this returns `None` for a
+    /// command buffer that is committed but still running. One that already
+    /// finished gives a future that is ready on first poll.
+    fn completed(&self) -> Option<CommandBufferCompletion>;
     /// Push a new named string onto a stack of string labels.
     fn push_debug_group(
         &self,
@@ -261,6 +270,16 @@ impl MTLCommandBufferExt for ProtocolObject<dyn MTLCommandBuffer> {
         }
     }
 
+    fn completed(&self) -> Option<CommandBufferCompletion> {
+        match CompletionStart::for_status(self.status()) {
+            CompletionStart::Handler => {
+                Some(CommandBufferCompletion::new(self.retain(), |handler| self.add_completed_handler(handler)))
+            },
+            CompletionStart::Finished => Some(CommandBufferCompletion::resolved(self.retain())),
+            CompletionStart::Running => None,
+        }
+    }
+
     fn push_debug_group(
         &self,
         string: &str,
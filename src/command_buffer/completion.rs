@@ -0,0 +1,111 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use std::sync::Arc;
+
+use objc2::{rc::Retained, runtime::ProtocolObject};
+use objc2_foundation::NSError;
+
+use super::{MTLCommandBuffer, MTLCommandBufferHandler, MTLCommandBufferStatus};
+use crate::Notification;
+
+/// How a completion future starts for a command buffer in a status.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum CompletionStart {
+    /// Not committed yet, so a completed handler can still be added.
+    Handler,
+    /// Completed or failed; nothing to wait for.
+    Finished,
+    /// Committed but not finished. Metal rejects new completed handlers, so
+    /// there is no way to be notified.
+    Running,
+}
+
+impl CompletionStart {
+    pub(crate) fn for_status(status: MTLCommandBufferStatus) -> Self {
+        match status {
+            MTLCommandBufferStatus::NotEnqueued | MTLCommandBufferStatus::Enqueued => Self::Handler,
+            MTLCommandBufferStatus::Completed | MTLCommandBufferStatus::Error => Self::Finished,
+            MTLCommandBufferStatus::Committed | MTLCommandBufferStatus::Scheduled => Self::Running,
+        }
+    }
+}
+
+/// Resolves when a command buffer completes, with its error if execution
+/// failed. Created by [`MTLCommandBufferExt::completed`][super::MTLCommandBufferExt::completed].
+#[must_use = "futures do nothing unless polled"]
+pub struct CommandBufferCompletion {
+    command_buffer: Retained<ProtocolObject<dyn MTLCommandBuffer>>,
+    notification: Arc<Notification>,
+}
+
+impl CommandBufferCompletion {
+    /// Registers a completed handler on `command_buffer`, which must not be
+    /// committed yet.
+    pub(crate) fn new(
+        command_buffer: Retained<ProtocolObject<dyn MTLCommandBuffer>>,
+        add_completed_handler: impl FnOnce(&MTLCommandBufferHandler),
+    ) -> Self {
+        let notification = Arc::new(Notification::default());
+        let notify = Arc::clone(&notification);
+        add_completed_handler(&MTLCommandBufferHandler::new(move |_| notify.notify()));
+        Self {
+            command_buffer,
+            notification,
+        }
+    }
+
+    /// A completion that is ready on first poll, for command buffers that
+    /// already finished.
+    pub(crate) fn resolved(command_buffer: Retained<ProtocolObject<dyn MTLCommandBuffer>>) -> Self {
+        let notification = Arc::new(Notification::default());
+        notification.notify();
+        Self {
+            command_buffer,
+            notification,
+        }
+    }
+
+    pub fn command_buffer(&self) -> &ProtocolObject<dyn MTLCommandBuffer> {
+        &self.command_buffer
+    }
+}
+
+impl Future for CommandBufferCompletion {
+    type Output = Result<(), Retained<NSError>>;
+
+    fn poll(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Self::Output> {
+        let finished =
+            matches!(self.command_buffer.status(), MTLCommandBufferStatus::Completed | MTLCommandBufferStatus::Error);
+        if !finished && self.notification.poll_notified(cx).is_pending() {
+            return Poll::Pending;
+        }
+        Poll::Ready(match self.command_buffer.error() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completion_start() {
+        use MTLCommandBufferStatus as S;
+
+        assert_eq!(CompletionStart::for_status(S::NotEnqueued), CompletionStart::Handler);
+        assert_eq!(CompletionStart::for_status(S::Enqueued), CompletionStart::Handler);
+        // Committed buffers may still be running, so they must not resolve.
+        assert_eq!(CompletionStart::for_status(S::Committed), CompletionStart::Running);
+        assert_eq!(CompletionStart::for_status(S::Scheduled), CompletionStart::Running);
+        assert_eq!(CompletionStart::for_status(S::Completed), CompletionStart::Finished);
+        assert_eq!(CompletionStart::for_status(S::Error), CompletionStart::Finished);
+    }
+}
@@ -1,10 +1,12 @@
 mod command_buffer;
+mod completion;
 mod descriptor;
 mod error;
 mod handler;
 mod status;
 
 pub use command_buffer::{MTLCommandBuffer, MTLCommandBufferExt};
+pub use completion::CommandBufferCompletion;
 pub use descriptor::MTLCommandBufferDescriptor;
 pub use error::{MTLCommandBufferError, MTLCommandBufferErrorOption, command_buffer_error_domain};
 pub use handler::MTLCommandBufferHandler;
@@ -3,10 +3,15 @@ use core::{ffi::c_void, ops::Range, ptr::NonNull};
 use objc2::{Message, extern_protocol, msg_send, runtime::ProtocolObject};
 use objc2_foundation::NSRange;
 
+use super::{
+    DispatchLimits, DispatchMode, DispatchPlan, DispatchPlanError, DispatchStrategy,
+    MTLDispatchThreadgroupsIndirectArguments, MTLStageInRegionIndirectArguments, plan_dispatch,
+};
 use crate::util::{opt_ref_slice_as_ptr, ref_slice_as_ptr};
 use crate::{
-    MTLAccelerationStructure, MTLBarrierScope, MTLBuffer, MTLCommandEncoder, MTLCounterSampleBuffer, MTLFence, MTLHeap,
-    MTLIndirectCommandBuffer, MTLResource, MTLResourceUsage, MTLSamplerState, MTLTexture,
+    IndirectArgumentsOffset, MTLAccelerationStructure, MTLBarrierScope, MTLBuffer, MTLCommandEncoder,
+    MTLCounterSampleBuffer, MTLFence, MTLHeap, MTLIndirectCommandBuffer, MTLIndirectCommandBufferExecutionRange,
+    MTLResource, MTLResourceUsage, MTLSamplerState, MTLTexture,
     compute_pipeline::MTLComputePipelineState,
     intersection_function_table::MTLIntersectionFunctionTable,
     types::{MTLRegion, MTLSize},
@@ -369,6 +374,84 @@ pub trait MTLComputeCommandEncoderExt: MTLComputeCommandEncoder + Message {
         }
     }
 
+    /// Dispatches threadgroups whose count is read from the typed `offset` in
+    /// `indirect_buffer`, panicking if the arguments overrun the buffer.
+    fn encode_dispatch_threadgroups_indirect(
+        &self,
+        indirect_buffer: &ProtocolObject<dyn MTLBuffer>,
+        offset: IndirectArgumentsOffset<MTLDispatchThreadgroupsIndirectArguments>,
+        threads_per_threadgroup: MTLSize,
+    ) where
+        Self: Sized,
+    {
+        offset.check_bounds(indirect_buffer);
+        self.dispatch_threadgroups_indirect(indirect_buffer, offset.bytes(), threads_per_threadgroup);
+    }
+
+    /// Sets the stage-in region from the typed `offset` in `indirect_buffer`.
+    fn encode_stage_in_region_indirect(
+        &self,
+        indirect_buffer: &ProtocolObject<dyn MTLBuffer>,
+        offset: IndirectArgumentsOffset<MTLStageInRegionIndirectArguments>,
+    ) where
+        Self: Sized,
+    {
+        offset.check_bounds(indirect_buffer);
+        self.set_stage_in_region_indirect(indirect_buffer, offset.bytes());
+    }
+
+    /// Executes `icb` over the range read from the typed `offset` in
+    /// `indirect_range_buffer`.
+    fn encode_execute_commands_in_buffer_indirect(
+        &self,
+        icb: &ProtocolObject<dyn MTLIndirectCommandBuffer>,
+        indirect_range_buffer: &ProtocolObject<dyn MTLBuffer>,
+        offset: IndirectArgumentsOffset<MTLIndirectCommandBufferExecutionRange>,
+    ) where
+        Self: Sized,
+    {
+        offset.check_bounds(indirect_range_buffer);
+        self.execute_commands_in_buffer_indirect(icb, indirect_range_buffer, offset.bytes());
+    }
+
+    /// Sets the plan's threadgroup memory and encodes its dispatch, with
+    /// `dispatch_threads` for [`DispatchMode::ExactFit`] and
+    /// `dispatch_threadgroups` for [`DispatchMode::Padded`].
+    fn dispatch_with_plan(
+        &self,
+        plan: &DispatchPlan,
+    ) where
+        Self: Sized,
+    {
+        if let Some((index, length)) = plan.threadgroup_memory {
+            self.set_threadgroup_memory_length(length, index);
+        }
+        match plan.mode {
+            DispatchMode::ExactFit => self.dispatch_threads(plan.threads_per_grid, plan.threads_per_threadgroup),
+            DispatchMode::Padded => {
+                self.dispatch_threadgroups(plan.threadgroups_per_grid, plan.threads_per_threadgroup)
+            },
+        }
+    }
+
+    /// Binds `pipeline`, plans a dispatch of `grid` threads against its limits
+    /// and encodes it. Nothing is dispatched if planning fails.
+    fn dispatch_planned(
+        &self,
+        pipeline: &ProtocolObject<dyn MTLComputePipelineState>,
+        grid: MTLSize,
+        strategy: &DispatchStrategy,
+    ) -> Result<DispatchPlan, DispatchPlanError>
+    where
+        Self: Sized,
+    {
+        let limits = DispatchLimits::from_pipeline(pipeline, &pipeline.device());
+        let plan = plan_dispatch(grid, &limits, strategy)?;
+        self.set_compute_pipeline_state(pipeline);
+        self.dispatch_with_plan(&plan);
+        Ok(plan)
+    }
+
     fn use_resources(
         &self,
         resources: &[&ProtocolObject<dyn MTLResource>],
@@ -0,0 +1,437 @@
+use core::fmt;
+
+use objc2::runtime::ProtocolObject;
+
+use crate::{MTLComputePipelineState, MTLDevice, MTLDeviceExt, MTLGPUFamily, MTLSize};
+
+/// Threadgroup memory lengths passed to `setThreadgroupMemoryLength:atIndex:`
+/// must be a multiple of this.
+pub const THREADGROUP_MEMORY_LENGTH_ALIGNMENT: usize = 16;
+
+/// Pipeline and device limits that bound a compute dispatch.
+///
+/// Usually read with [`from_pipeline`][Self::from_pipeline]; constructing it by
+/// hand lets dispatches be planned without a device.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DispatchLimits {
+    /// `MTLComputePipelineState::thread_execution_width`.
+    pub thread_execution_width: usize,
+    /// `MTLComputePipelineState::max_total_threads_per_threadgroup`.
+    pub max_total_threads_per_threadgroup: usize,
+    /// `MTLDevice::max_threads_per_threadgroup`.
+    pub max_threads_per_threadgroup: MTLSize,
+    /// `MTLComputePipelineState::static_threadgroup_memory_length`.
+    pub static_threadgroup_memory_length: usize,
+    /// `MTLDevice::max_threadgroup_memory_length`.
+    pub max_threadgroup_memory_length: usize,
+    /// Whether `dispatch_threads` may launch partial threadgroups, which
+    /// requires the Apple4 or Mac2 GPU family.
+    pub supports_non_uniform_threadgroups: bool,
+}
+
+impl DispatchLimits {
+    pub fn from_pipeline(
+        pipeline: &ProtocolObject<dyn MTLComputePipelineState>,
+        device: &ProtocolObject<dyn MTLDevice>,
+    ) -> Self {
+        Self {
+            thread_execution_width: pipeline.thread_execution_width(),
+            max_total_threads_per_threadgroup: pipeline.max_total_threads_per_threadgroup(),
+            max_threads_per_threadgroup: device.max_threads_per_threadgroup(),
+            static_threadgroup_memory_length: pipeline.static_threadgroup_memory_length(),
+            max_threadgroup_memory_length: device.max_threadgroup_memory_length(),
+            supports_non_uniform_threadgroups: device.supports_family(MTLGPUFamily::Apple4)
+                || device.supports_family(MTLGPUFamily::Mac2),
+        }
+    }
+
+    /// Threadgroup memory left for dynamic allocations.
+    pub fn available_threadgroup_memory_length(&self) -> usize {
+        self.max_threadgroup_memory_length.saturating_sub(self.static_threadgroup_memory_length)
+    }
+}
+
+/// How threads are arranged within a threadgroup.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DispatchShape {
+    /// Threadgroups are a single row; the grid's height and depth are covered
+    /// by threadgroups alone.
+    Linear,
+    /// Threadgroups are `thread_execution_width` wide and as tall as the limits
+    /// allow, for image-like grids.
+    Tiled2D,
+}
+
+/// How the grid is covered by threadgroups.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DispatchMode {
+    /// Dispatch exactly the grid with `dispatch_threads`; edge threadgroups are
+    /// smaller. Plans fall back to [`Padded`][Self::Padded] when the limits
+    /// lack non-uniform threadgroup support.
+    ExactFit,
+    /// Dispatch whole threadgroups with `dispatch_threadgroups`; the kernel must
+    /// discard threads past the grid.
+    Padded,
+}
+
+/// Dynamic threadgroup memory a dispatch allocates, used to limit the
+/// threadgroup size so the allocation fits.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ThreadgroupMemoryRequirement {
+    /// Binding index passed to `set_threadgroup_memory_length`.
+    pub index: usize,
+    /// Bytes needed regardless of the threadgroup size.
+    pub fixed_length: usize,
+    /// Bytes needed per thread in the threadgroup.
+    pub length_per_thread: usize,
+}
+
+/// Options for [`plan_dispatch`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DispatchStrategy {
+    pub shape: DispatchShape,
+    pub mode: DispatchMode,
+    pub threadgroup_memory: Option<ThreadgroupMemoryRequirement>,
+}
+
+impl DispatchStrategy {
+    pub const fn new(
+        shape: DispatchShape,
+        mode: DispatchMode,
+    ) -> Self {
+        Self {
+            shape,
+            mode,
+            threadgroup_memory: None,
+        }
+    }
+
+    /// Limits the threadgroup size so `requirement` fits in the available
+    /// threadgroup memory.
+    pub const fn with_threadgroup_memory(
+        mut self,
+        requirement: ThreadgroupMemoryRequirement,
+    ) -> Self {
+        self.threadgroup_memory = Some(requirement);
+        self
+    }
+}
+
+/// Errors raised by [`plan_dispatch`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DispatchPlanError {
+    /// The grid has a zero dimension.
+    EmptyGrid(MTLSize),
+    /// Covering the grid with whole threadgroups overflows `usize`.
+    GridTooLarge(MTLSize),
+    /// The threadgroup memory requirement leaves no room for a single thread.
+    ThreadgroupMemoryExceeded {
+        required: usize,
+        available: usize,
+    },
+}
+
+impl fmt::Display for DispatchPlanError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::EmptyGrid(grid) => {
+                write!(f, "cannot dispatch an empty grid of {}x{}x{}", grid.width, grid.height, grid.depth)
+            },
+            Self::GridTooLarge(grid) => {
+                write!(f, "a grid of {}x{}x{} is too large to dispatch", grid.width, grid.height, grid.depth)
+            },
+            Self::ThreadgroupMemoryExceeded {
+                required,
+                available,
+            } => {
+                write!(f, "threadgroup memory requires {required} bytes but only {available} are available")
+            },
+        }
+    }
+}
+
+impl std::error::Error for DispatchPlanError {}
+
+/// Threadgroup and grid sizes for one compute dispatch.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DispatchPlan {
+    /// The strategy's mode, or [`DispatchMode::Padded`] when exact fit is
+    /// unsupported.
+    pub mode: DispatchMode,
+    /// The requested grid, in threads.
+    pub threads_per_grid: MTLSize,
+    pub threads_per_threadgroup: MTLSize,
+    /// Threadgroups needed to cover the grid, rounding up.
+    pub threadgroups_per_grid: MTLSize,
+    /// Threads in the last threadgroup along each dimension when the grid
+    /// doesn't divide evenly, otherwise zero.
+    pub remainder: MTLSize,
+    /// Binding index and 16-byte-aligned length of the dynamic threadgroup
+    /// memory allocation.
+    pub threadgroup_memory: Option<(usize, usize)>,
+}
+
+impl DispatchPlan {
+    /// Whether every threadgroup is full.
+    pub fn is_uniform(&self) -> bool {
+        self.remainder == MTLSize::new(0, 0, 0)
+    }
+
+    /// Threads launched by a padded dispatch, including those past the grid.
+    pub fn padded_threads_per_grid(&self) -> MTLSize {
+        MTLSize::new(
+            self.threadgroups_per_grid.width * self.threads_per_threadgroup.width,
+            self.threadgroups_per_grid.height * self.threads_per_threadgroup.height,
+            self.threadgroups_per_grid.depth * self.threads_per_threadgroup.depth,
+        )
+    }
+}
+
+/// Plans a dispatch of `grid` threads under `limits`.
+///
+/// Threadgroup widths are kept to multiples of `thread_execution_width` where
+/// the limits allow, and shrink to the grid when it is smaller than a full
+/// threadgroup.
+pub fn plan_dispatch(
+    grid: MTLSize,
+    limits: &DispatchLimits,
+    strategy: &DispatchStrategy,
+) -> Result<DispatchPlan, DispatchPlanError> {
+    if grid.width == 0 || grid.height == 0 || grid.depth == 0 {
+        return Err(DispatchPlanError::EmptyGrid(grid));
+    }
+
+    let simd_width = limits.thread_execution_width.max(1);
+    let max_size = MTLSize::new(
+        limits.max_threads_per_threadgroup.width.max(1),
+        limits.max_threads_per_threadgroup.height.max(1),
+        limits.max_threads_per_threadgroup.depth.max(1),
+    );
+    let mut max_threads = limits.max_total_threads_per_threadgroup.max(1);
+
+    let mut threadgroup_memory = None;
+    if let Some(requirement) = strategy.threadgroup_memory {
+        let available = limits.available_threadgroup_memory_length();
+        let required = requirement
+            .fixed_length
+            .checked_add(requirement.length_per_thread)
+            .and_then(checked_align_memory_length)
+            .unwrap_or(usize::MAX);
+        if required > available {
+            return Err(DispatchPlanError::ThreadgroupMemoryExceeded {
+                required,
+                available,
+            });
+        }
+        if let Some(mut threads) = (available - requirement.fixed_length).checked_div(requirement.length_per_thread) {
+            while threads > 1
+                && align_memory_length(requirement.fixed_length + threads * requirement.length_per_thread) > available
+            {
+                threads -= 1;
+            }
+            max_threads = max_threads.min(threads);
+        }
+        threadgroup_memory = Some(requirement);
+    }
+    max_threads = round_down_to_simd(max_threads, simd_width);
+
+    let threads_per_threadgroup = match strategy.shape {
+        DispatchShape::Linear => {
+            let width = max_threads.min(max_size.width);
+            let width = round_down_to_simd(width, simd_width).min(round_up_to_simd(grid.width, simd_width));
+            MTLSize::new(width.min(max_size.width).max(1), 1, 1)
+        },
+        DispatchShape::Tiled2D => {
+            let width = simd_width.min(max_threads).min(max_size.width);
+            let height = (max_threads / width).min(max_size.height).min(grid.height).max(1);
+            // Give threads freed up by a short grid back to the width.
+            let width = round_down_to_simd((max_threads / height).min(max_size.width), simd_width)
+                .min(round_up_to_simd(grid.width, simd_width))
+                .min(max_size.width)
+                .max(1);
+            MTLSize::new(width, height, 1)
+        },
+    };
+
+    let threadgroups_per_grid = MTLSize::new(
+        grid.width.div_ceil(threads_per_threadgroup.width),
+        grid.height.div_ceil(threads_per_threadgroup.height),
+        grid.depth.div_ceil(threads_per_threadgroup.depth),
+    );
+    // Keeps `padded_threads_per_grid` from overflowing.
+    let covered = |groups: usize, threads: usize| groups.checked_mul(threads);
+    if covered(threadgroups_per_grid.width, threads_per_threadgroup.width).is_none()
+        || covered(threadgroups_per_grid.height, threads_per_threadgroup.height).is_none()
+        || covered(threadgroups_per_grid.depth, threads_per_threadgroup.depth).is_none()
+    {
+        return Err(DispatchPlanError::GridTooLarge(grid));
+    }
+    let remainder = MTLSize::new(
+        grid.width % threads_per_threadgroup.width,
+        grid.height % threads_per_threadgroup.height,
+        grid.depth % threads_per_threadgroup.depth,
+    );
+    let total_threads = threads_per_threadgroup.width * threads_per_threadgroup.height * threads_per_threadgroup.depth;
+
+    let mode = match strategy.mode {
+        DispatchMode::ExactFit if !limits.supports_non_uniform_threadgroups => DispatchMode::Padded,
+        mode => mode,
+    };
+
+    Ok(DispatchPlan {
+        mode,
+        threads_per_grid: grid,
+        threads_per_threadgroup,
+        threadgroups_per_grid,
+        remainder,
+        threadgroup_memory: threadgroup_memory.map(|requirement| {
+            let length = requirement.fixed_length + total_threads * requirement.length_per_thread;
+            (requirement.index, align_memory_length(length))
+        }),
+    })
+}
+
+fn round_down_to_simd(
+    threads: usize,
+    simd_width: usize,
+) -> usize {
+    if threads >= simd_width {
+        threads / simd_width * simd_width
+    } else {
+        threads
+    }
+}
+
+fn round_up_to_simd(
+    threads: usize,
+    simd_width: usize,
+) -> usize {
+    threads.checked_next_multiple_of(simd_width).unwrap_or(usize::MAX)
+}
+
+fn align_memory_length(length: usize) -> usize {
+    length.next_multiple_of(THREADGROUP_MEMORY_LENGTH_ALIGNMENT)
+}
+
+fn checked_align_memory_length(length: usize) -> Option<usize> {
+    length.checked_next_multiple_of(THREADGROUP_MEMORY_LENGTH_ALIGNMENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: DispatchLimits = DispatchLimits {
+        thread_execution_width: 32,
+        max_total_threads_per_threadgroup: 1024,
+        max_threads_per_threadgroup: MTLSize {
+            width: 1024,
+            height: 1024,
+            depth: 1024,
+        },
+        static_threadgroup_memory_length: 0,
+        max_threadgroup_memory_length: 32768,
+        supports_non_uniform_threadgroups: true,
+    };
+
+    const LINEAR: DispatchStrategy = DispatchStrategy::new(DispatchShape::Linear, DispatchMode::Padded);
+    const TILED: DispatchStrategy = DispatchStrategy::new(DispatchShape::Tiled2D, DispatchMode::Padded);
+
+    fn plan(
+        grid: (usize, usize, usize),
+        strategy: &DispatchStrategy,
+    ) -> DispatchPlan {
+        plan_dispatch(MTLSize::new(grid.0, grid.1, grid.2), &LIMITS, strategy).unwrap()
+    }
+
+    #[test]
+    fn linear() {
+        let plan = plan((5000, 2, 1), &LINEAR);
+        assert_eq!(plan.threads_per_threadgroup, MTLSize::new(1024, 1, 1));
+        assert_eq!(plan.threadgroups_per_grid, MTLSize::new(5, 2, 1));
+        assert_eq!(plan.remainder, MTLSize::new(904, 0, 0));
+        assert_eq!(plan.padded_threads_per_grid(), MTLSize::new(5120, 2, 1));
+        assert!(!plan.is_uniform());
+        assert!(plan_dispatch(MTLSize::new(4096, 1, 1), &LIMITS, &LINEAR).unwrap().is_uniform());
+    }
+
+    #[test]
+    fn small_grids_shrink_to_simd_multiple() {
+        assert_eq!(plan((10, 1, 1), &LINEAR).threads_per_threadgroup, MTLSize::new(32, 1, 1));
+        assert_eq!(plan((1000, 4, 1), &TILED).threads_per_threadgroup, MTLSize::new(256, 4, 1));
+    }
+
+    #[test]
+    fn tiled() {
+        let plan = plan((1920, 1080, 1), &TILED);
+        assert_eq!(plan.threads_per_threadgroup, MTLSize::new(32, 32, 1));
+        assert_eq!(plan.threadgroups_per_grid, MTLSize::new(60, 34, 1));
+        assert_eq!(plan.remainder, MTLSize::new(0, 24, 0));
+    }
+
+    #[test]
+    fn threadgroup_memory_limits_size() {
+        let strategy = LINEAR.with_threadgroup_memory(ThreadgroupMemoryRequirement {
+            index: 1,
+            fixed_length: 1024,
+            length_per_thread: 64,
+        });
+        let plan = plan((4096, 1, 1), &strategy);
+        assert_eq!(plan.threads_per_threadgroup, MTLSize::new(480, 1, 1));
+        assert_eq!(plan.threadgroup_memory, Some((1, 31744)));
+    }
+
+    #[test]
+    fn threadgroup_memory_exceeded() {
+        let grid = MTLSize::new(64, 1, 1);
+        let requirement = ThreadgroupMemoryRequirement {
+            index: 0,
+            fixed_length: 32768,
+            length_per_thread: 4,
+        };
+        let strategy = LINEAR.with_threadgroup_memory(requirement);
+        assert_eq!(
+            plan_dispatch(grid, &LIMITS, &strategy),
+            Err(DispatchPlanError::ThreadgroupMemoryExceeded {
+                required: 32784,
+                available: 32768,
+            })
+        );
+        let strategy = LINEAR.with_threadgroup_memory(ThreadgroupMemoryRequirement {
+            fixed_length: usize::MAX,
+            ..requirement
+        });
+        assert_eq!(
+            plan_dispatch(grid, &LIMITS, &strategy),
+            Err(DispatchPlanError::ThreadgroupMemoryExceeded {
+                required: usize::MAX,
+                available: 32768,
+            })
+        );
+    }
+
+    #[test]
+    fn exact_fit_falls_back_to_padded() {
+        let strategy = DispatchStrategy::new(DispatchShape::Linear, DispatchMode::ExactFit);
+        let grid = MTLSize::new(1000, 1, 1);
+        assert_eq!(plan_dispatch(grid, &LIMITS, &strategy).unwrap().mode, DispatchMode::ExactFit);
+        let limits = DispatchLimits {
+            supports_non_uniform_threadgroups: false,
+            ..LIMITS
+        };
+        assert_eq!(plan_dispatch(grid, &limits, &strategy).unwrap().mode, DispatchMode::Padded);
+    }
+
+    #[test]
+    fn invalid_grids() {
+        let empty = MTLSize::new(16, 0, 1);
+        assert_eq!(plan_dispatch(empty, &LIMITS, &LINEAR), Err(DispatchPlanError::EmptyGrid(empty)));
+        let huge = MTLSize::new(usize::MAX, 1, 1);
+        assert_eq!(plan_dispatch(huge, &LIMITS, &LINEAR), Err(DispatchPlanError::GridTooLarge(huge)));
+        let huge = MTLSize::new(1, usize::MAX, 1);
+        assert_eq!(plan_dispatch(huge, &LIMITS, &TILED), Err(DispatchPlanError::GridTooLarge(huge)));
+    }
+}
@@ -2,7 +2,7 @@ use objc2::{Encode, Encoding, RefEncode};
 
 /// Indirect arguments for `dispatchThreadgroups` (from `MTLDispatchThreadgroupsIndirectArguments`).
 #[repr(C)]
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 pub struct MTLDispatchThreadgroupsIndirectArguments {
     pub threadgroups_per_grid: [u32; 3],
 }
@@ -15,9 +15,12 @@ unsafe impl RefEncode for MTLDispatchThreadgroupsIndirectArguments {
     const ENCODING_REF: Encoding = Encoding::Pointer(&Self::ENCODING);
 }
 
+const _: () = assert!(size_of::<MTLDispatchThreadgroupsIndirectArguments>() == 12);
+const _: () = assert!(align_of::<MTLDispatchThreadgroupsIndirectArguments>() == 4);
+
 /// Indirect arguments for `dispatchThreads` (from `MTLDispatchThreadsIndirectArguments`).
 #[repr(C)]
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 pub struct MTLDispatchThreadsIndirectArguments {
     pub threads_per_grid: [u32; 3],
     pub threads_per_threadgroup: [u32; 3],
@@ -31,11 +34,14 @@ unsafe impl RefEncode for MTLDispatchThreadsIndirectArguments {
     const ENCODING_REF: Encoding = Encoding::Pointer(&Self::ENCODING);
 }
 
+const _: () = assert!(size_of::<MTLDispatchThreadsIndirectArguments>() == 24);
+const _: () = assert!(align_of::<MTLDispatchThreadsIndirectArguments>() == 4);
+
 /// Indirect arguments for stage-in region (from `MTLStageInRegionIndirectArguments`).
 ///
 /// Availability: macOS 10.14+, iOS 12.0+
 #[repr(C)]
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 pub struct MTLStageInRegionIndirectArguments {
     pub stage_in_origin: [u32; 3],
     pub stage_in_size: [u32; 3],
@@ -48,3 +54,6 @@ unsafe impl Encode for MTLStageInRegionIndirectArguments {
 unsafe impl RefEncode for MTLStageInRegionIndirectArguments {
     const ENCODING_REF: Encoding = Encoding::Pointer(&Self::ENCODING);
 }
+
+const _: () = assert!(size_of::<MTLStageInRegionIndirectArguments>() == 24);
+const _: () = assert!(align_of::<MTLStageInRegionIndirectArguments>() == 4);
@@ -1,8 +1,13 @@
 mod compute_command_encoder;
+mod dispatch_plan;
 mod dispatch_type;
 mod indirect;
 
 pub use compute_command_encoder::{MTLComputeCommandEncoder, MTLComputeCommandEncoderExt};
+pub use dispatch_plan::{
+    DispatchLimits, DispatchMode, DispatchPlan, DispatchPlanError, DispatchShape, DispatchStrategy,
+    THREADGROUP_MEMORY_LENGTH_ALIGNMENT, ThreadgroupMemoryRequirement, plan_dispatch,
+};
 pub use dispatch_type::MTLDispatchType;
 pub use indirect::{
     MTLDispatchThreadgroupsIndirectArguments, MTLDispatchThreadsIndirectArguments, MTLStageInRegionIndirectArguments,
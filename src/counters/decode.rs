@@ -0,0 +1,390 @@
+use core::{fmt, slice};
+
+use objc2::runtime::ProtocolObject;
+
+use super::{
+    MTL_COUNTER_ERROR_VALUE, MTLCommonCounterSet, MTLCounterResultStageUtilization, MTLCounterResultStatistic,
+    MTLCounterResultTimestamp, MTLCounterSet, MTLCounterSetExt,
+    common::{MTLCommonCounterSetStageUtilization, MTLCommonCounterSetStatistic, MTLCommonCounterSetTimestamp},
+};
+
+/// Errors raised while decoding resolved counter data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CounterDecodeError {
+    /// The counter set is not one of the `MTLCommonCounterSet` sets.
+    UnknownCounterSet(String),
+    /// The data is not a whole number of results.
+    InvalidLength {
+        length: usize,
+        result_size: usize,
+    },
+    /// The data is not aligned for a zero-copy view.
+    Misaligned {
+        alignment: usize,
+    },
+    /// Paired deltas need an even number of samples.
+    UnpairedSample {
+        sample_count: usize,
+    },
+}
+
+impl fmt::Display for CounterDecodeError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::UnknownCounterSet(name) => write!(f, "unknown counter set {name:?}"),
+            Self::InvalidLength {
+                length,
+                result_size,
+            } => {
+                write!(f, "{length} bytes is not a whole number of {result_size}-byte counter results")
+            },
+            Self::Misaligned {
+                alignment,
+            } => write!(f, "counter data is not {alignment}-byte aligned"),
+            Self::UnpairedSample {
+                sample_count,
+            } => {
+                write!(f, "{sample_count} samples cannot be split into begin/end pairs")
+            },
+        }
+    }
+}
+
+impl std::error::Error for CounterDecodeError {}
+
+/// The `MTLCommonCounterSet` a sample buffer was created with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CounterSetKind {
+    Timestamp,
+    StageUtilization,
+    Statistic,
+}
+
+impl CounterSetKind {
+    const ALL: [Self; 3] = [Self::Timestamp, Self::StageUtilization, Self::Statistic];
+
+    /// Matches the value of `MTLCommonCounterSetTimestamp`,
+    /// `MTLCommonCounterSetStageUtilization` or `MTLCommonCounterSetStatistic`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name().to_string() == name)
+    }
+
+    pub fn from_counter_set(counter_set: &ProtocolObject<dyn MTLCounterSet>) -> Result<Self, CounterDecodeError> {
+        let name = counter_set.name();
+        Self::from_name(&name).ok_or(CounterDecodeError::UnknownCounterSet(name))
+    }
+
+    /// The `MTLCommonCounterSet` constant naming this set.
+    pub fn name(self) -> &'static MTLCommonCounterSet {
+        unsafe {
+            match self {
+                Self::Timestamp => MTLCommonCounterSetTimestamp,
+                Self::StageUtilization => MTLCommonCounterSetStageUtilization,
+                Self::Statistic => MTLCommonCounterSetStatistic,
+            }
+        }
+    }
+
+    /// Size in bytes of one resolved sample.
+    pub const fn result_size(self) -> usize {
+        match self {
+            Self::Timestamp => size_of::<MTLCounterResultTimestamp>(),
+            Self::StageUtilization => size_of::<MTLCounterResultStageUtilization>(),
+            Self::Statistic => size_of::<MTLCounterResultStatistic>(),
+        }
+    }
+}
+
+/// A resolved counter sample struct: a `#[repr(C)]` run of `u64` counters.
+///
+/// # Safety
+///
+/// Implementors must consist of exactly `size_of::<Self>() / 8` `u64` fields.
+pub unsafe trait CounterResult: Copy + 'static {
+    const KIND: CounterSetKind;
+
+    fn counters(&self) -> &[u64] {
+        unsafe { slice::from_raw_parts((self as *const Self).cast(), size_of::<Self>() / size_of::<u64>()) }
+    }
+
+    fn counters_mut(&mut self) -> &mut [u64] {
+        unsafe { slice::from_raw_parts_mut((self as *mut Self).cast(), size_of::<Self>() / size_of::<u64>()) }
+    }
+
+    /// Whether every counter was resolved, i.e. none is `MTLCounterErrorValue`.
+    fn is_valid(&self) -> bool {
+        !self.counters().contains(&MTL_COUNTER_ERROR_VALUE)
+    }
+
+    /// Per-counter difference from `self` to the later sample `end`, or `None`
+    /// if either is invalid or a counter went backwards.
+    fn delta(
+        &self,
+        end: &Self,
+    ) -> Option<Self> {
+        if !self.is_valid() || !end.is_valid() {
+            return None;
+        }
+        let mut delta = *end;
+        for (counter, begin) in delta.counters_mut().iter_mut().zip(self.counters()) {
+            *counter = counter.checked_sub(*begin)?;
+        }
+        Some(delta)
+    }
+}
+
+unsafe impl CounterResult for MTLCounterResultTimestamp {
+    const KIND: CounterSetKind = CounterSetKind::Timestamp;
+}
+
+unsafe impl CounterResult for MTLCounterResultStageUtilization {
+    const KIND: CounterSetKind = CounterSetKind::StageUtilization;
+}
+
+unsafe impl CounterResult for MTLCounterResultStatistic {
+    const KIND: CounterSetKind = CounterSetKind::Statistic;
+}
+
+/// Decodes resolved counter data into one result per sample, with `None` for
+/// samples containing `MTLCounterErrorValue`.
+///
+/// The data may have any alignment.
+pub fn decode_counter_results<T: CounterResult>(bytes: &[u8]) -> Result<Vec<Option<T>>, CounterDecodeError> {
+    check_length::<T>(bytes)?;
+    Ok(bytes
+        .chunks_exact(size_of::<T>())
+        .map(|chunk| {
+            let result = unsafe { chunk.as_ptr().cast::<T>().read_unaligned() };
+            result.is_valid().then_some(result)
+        })
+        .collect())
+}
+
+/// Reinterprets resolved counter data in place. Invalid samples are not
+/// filtered; check them with [`CounterResult::is_valid`].
+pub fn view_counter_results<T: CounterResult>(bytes: &[u8]) -> Result<&[T], CounterDecodeError> {
+    check_length::<T>(bytes)?;
+    if !bytes.as_ptr().cast::<T>().is_aligned() {
+        return Err(CounterDecodeError::Misaligned {
+            alignment: align_of::<T>(),
+        });
+    }
+    Ok(unsafe { slice::from_raw_parts(bytes.as_ptr().cast(), bytes.len() / size_of::<T>()) })
+}
+
+/// Differences between consecutive begin/end samples: `(0, 1)`, `(2, 3)`, ...
+pub fn paired_counter_deltas<T: CounterResult>(samples: &[Option<T>]) -> Result<Vec<Option<T>>, CounterDecodeError> {
+    if !samples.len().is_multiple_of(2) {
+        return Err(CounterDecodeError::UnpairedSample {
+            sample_count: samples.len(),
+        });
+    }
+    Ok(samples
+        .chunks_exact(2)
+        .map(|pair| match (&pair[0], &pair[1]) {
+            (Some(begin), Some(end)) => begin.delta(end),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Resolved samples of any common counter set.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CounterResults {
+    Timestamp(Vec<Option<MTLCounterResultTimestamp>>),
+    StageUtilization(Vec<Option<MTLCounterResultStageUtilization>>),
+    Statistic(Vec<Option<MTLCounterResultStatistic>>),
+}
+
+impl CounterResults {
+    /// Decodes `bytes` as results of the `kind` counter set.
+    pub fn decode(
+        kind: CounterSetKind,
+        bytes: &[u8],
+    ) -> Result<Self, CounterDecodeError> {
+        Ok(match kind {
+            CounterSetKind::Timestamp => Self::Timestamp(decode_counter_results(bytes)?),
+            CounterSetKind::StageUtilization => Self::StageUtilization(decode_counter_results(bytes)?),
+            CounterSetKind::Statistic => Self::Statistic(decode_counter_results(bytes)?),
+        })
+    }
+
+    /// Decodes `bytes` as results of the set named `counter_set_name`.
+    pub fn decode_named(
+        counter_set_name: &str,
+        bytes: &[u8],
+    ) -> Result<Self, CounterDecodeError> {
+        let kind = CounterSetKind::from_name(counter_set_name)
+            .ok_or_else(|| CounterDecodeError::UnknownCounterSet(counter_set_name.to_owned()))?;
+        Self::decode(kind, bytes)
+    }
+
+    pub fn kind(&self) -> CounterSetKind {
+        match self {
+            Self::Timestamp(_) => CounterSetKind::Timestamp,
+            Self::StageUtilization(_) => CounterSetKind::StageUtilization,
+            Self::Statistic(_) => CounterSetKind::Statistic,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Timestamp(samples) => samples.len(),
+            Self::StageUtilization(samples) => samples.len(),
+            Self::Statistic(samples) => samples.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of samples that contained `MTLCounterErrorValue`.
+    pub fn invalid_count(&self) -> usize {
+        match self {
+            Self::Timestamp(samples) => samples.iter().filter(|s| s.is_none()).count(),
+            Self::StageUtilization(samples) => samples.iter().filter(|s| s.is_none()).count(),
+            Self::Statistic(samples) => samples.iter().filter(|s| s.is_none()).count(),
+        }
+    }
+
+    /// Differences between consecutive begin/end samples.
+    pub fn paired_deltas(&self) -> Result<Self, CounterDecodeError> {
+        Ok(match self {
+            Self::Timestamp(samples) => Self::Timestamp(paired_counter_deltas(samples)?),
+            Self::StageUtilization(samples) => Self::StageUtilization(paired_counter_deltas(samples)?),
+            Self::Statistic(samples) => Self::Statistic(paired_counter_deltas(samples)?),
+        })
+    }
+}
+
+fn check_length<T: CounterResult>(bytes: &[u8]) -> Result<(), CounterDecodeError> {
+    if !bytes.len().is_multiple_of(size_of::<T>()) {
+        return Err(CounterDecodeError::InvalidLength {
+            length: bytes.len(),
+            result_size: size_of::<T>(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ERR: u64 = MTL_COUNTER_ERROR_VALUE;
+
+    fn resolved(counters: &[u64]) -> Vec<u8> {
+        counters.iter().flat_map(|counter| counter.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn decodes_timestamps_with_error_values() {
+        let bytes = resolved(&[10, ERR, 30]);
+        let samples = decode_counter_results::<MTLCounterResultTimestamp>(&bytes).unwrap();
+        assert_eq!(
+            samples,
+            [
+                Some(MTLCounterResultTimestamp {
+                    timestamp: 10
+                }),
+                None,
+                Some(MTLCounterResultTimestamp {
+                    timestamp: 30
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_unaligned_data() {
+        let mut bytes = vec![0xAA];
+        bytes.extend(resolved(&[1, 2, 3, 4, 5, 6]));
+        let samples = decode_counter_results::<MTLCounterResultStageUtilization>(&bytes[1..]).unwrap();
+        assert_eq!(
+            samples,
+            [Some(MTLCounterResultStageUtilization {
+                total_cycles: 1,
+                vertex_cycles: 2,
+                tessellation_cycles: 3,
+                post_tessellation_vertex_cycles: 4,
+                fragment_cycles: 5,
+                render_target_cycles: 6,
+            })]
+        );
+    }
+
+    #[test]
+    fn rejects_partial_results() {
+        let bytes = resolved(&[0; 9]);
+        assert_eq!(
+            decode_counter_results::<MTLCounterResultStatistic>(&bytes),
+            Err(CounterDecodeError::InvalidLength {
+                length: 72,
+                result_size: 64,
+            })
+        );
+        assert_eq!(
+            CounterResults::decode(CounterSetKind::StageUtilization, &bytes[..50]),
+            Err(CounterDecodeError::InvalidLength {
+                length: 50,
+                result_size: 48,
+            })
+        );
+    }
+
+    #[test]
+    fn views_aligned_data() {
+        let counters = [7u64, 8];
+        let bytes = unsafe { slice::from_raw_parts(counters.as_ptr().cast::<u8>(), 16) };
+        let view = view_counter_results::<MTLCounterResultTimestamp>(bytes).unwrap();
+        assert_eq!(view.iter().map(|sample| sample.timestamp).collect::<Vec<_>>(), [7, 8]);
+        assert_eq!(
+            view_counter_results::<MTLCounterResultTimestamp>(&bytes[1..9]),
+            Err(CounterDecodeError::Misaligned {
+                alignment: 8
+            })
+        );
+    }
+
+    #[test]
+    fn statistic_deltas() {
+        let begin = [0, 100, 0, 50, 50, 1000, 900, 0];
+        let end = [0, 160, 0, 80, 70, 2500, 2000, 0];
+        let backwards = [0, 99, 0, 50, 50, 1000, 900, 0];
+        let mut counters = Vec::new();
+        for sample in [&begin, &end, &begin, &[ERR; 8], &begin, &backwards] {
+            counters.extend_from_slice(sample);
+        }
+        let results = CounterResults::decode(CounterSetKind::Statistic, &resolved(&counters)).unwrap();
+        assert_eq!((results.kind(), results.len(), results.invalid_count()), (CounterSetKind::Statistic, 6, 1));
+
+        let CounterResults::Statistic(deltas) = results.paired_deltas().unwrap() else {
+            panic!("wrong kind");
+        };
+        let delta = deltas[0].unwrap();
+        assert_eq!((delta.vertex_invocations, delta.fragment_invocations, delta.fragments_passed), (60, 1500, 1100));
+        assert_eq!(deltas[1..], [None, None]);
+    }
+
+    #[test]
+    fn unpaired_samples() {
+        let results = CounterResults::decode(CounterSetKind::Timestamp, &resolved(&[1, 2, 3])).unwrap();
+        assert_eq!(
+            results.paired_deltas(),
+            Err(CounterDecodeError::UnpairedSample {
+                sample_count: 3
+            })
+        );
+        let empty = CounterResults::decode(CounterSetKind::Timestamp, &[]).unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn result_sizes() {
+        assert_eq!(CounterSetKind::ALL.map(CounterSetKind::result_size), [8, 48, 64]);
+    }
+}
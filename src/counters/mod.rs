@@ -1,15 +1,21 @@
 mod common;
 mod counter;
 mod counter_set;
+mod decode;
 mod descriptor;
 mod error;
 mod results;
 mod sample_buffer;
 mod types;
 
+pub(crate) use common::MTLCommonCounterSetTimestamp;
 pub use common::{MTLCommonCounter, MTLCommonCounterSet};
 pub use counter::{MTLCounter, MTLCounterExt};
 pub use counter_set::{MTLCounterSet, MTLCounterSetExt};
+pub use decode::{
+    CounterDecodeError, CounterResult, CounterResults, CounterSetKind, decode_counter_results, paired_counter_deltas,
+    view_counter_results,
+};
 pub use descriptor::MTLCounterSampleBufferDescriptor;
 pub use error::{MTL_COUNTER_DONT_SAMPLE, MTL_COUNTER_ERROR_VALUE, MTLCounterSampleBufferError, counter_error_domain};
 pub use results::{MTLCounterResultStageUtilization, MTLCounterResultStatistic, MTLCounterResultTimestamp};
@@ -1,7 +1,7 @@
 use objc2::{Encode, Encoding, RefEncode};
 
 #[repr(C)]
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
 pub struct MTLCounterResultTimestamp {
     pub timestamp: u64,
 }
@@ -14,8 +14,10 @@ unsafe impl RefEncode for MTLCounterResultTimestamp {
     const ENCODING_REF: Encoding = Encoding::Pointer(&Self::ENCODING);
 }
 
+const _: () = assert!(size_of::<MTLCounterResultTimestamp>() == 8);
+
 #[repr(C)]
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
 pub struct MTLCounterResultStageUtilization {
     pub total_cycles: u64,
     pub vertex_cycles: u64,
@@ -36,8 +38,10 @@ unsafe impl RefEncode for MTLCounterResultStageUtilization {
     const ENCODING_REF: Encoding = Encoding::Pointer(&Self::ENCODING);
 }
 
+const _: () = assert!(size_of::<MTLCounterResultStageUtilization>() == 48);
+
 #[repr(C)]
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
 pub struct MTLCounterResultStatistic {
     pub tessellation_input_patches: u64,
     pub vertex_invocations: u64,
@@ -68,3 +72,5 @@ unsafe impl Encode for MTLCounterResultStatistic {
 unsafe impl RefEncode for MTLCounterResultStatistic {
     const ENCODING_REF: Encoding = Encoding::Pointer(&Self::ENCODING);
 }
+
+const _: () = assert!(size_of::<MTLCounterResultStatistic>() == 64);
@@ -0,0 +1,209 @@
+use core::{fmt, ptr::NonNull};
+
+use objc2::{rc::Retained, runtime::ProtocolObject};
+use objc2_foundation::NSError;
+
+use super::{
+    CULLING_SHADER_SOURCE, CullingBufferLayout, CullingUniforms, FRUSTUM_CULL_KERNEL, ICB_CULL_KERNEL,
+    OCCLUSION_CULL_KERNEL, RESET_COUNTER_KERNEL, WRITE_EXECUTION_RANGE_KERNEL, culling_dispatch_size,
+};
+use crate::{
+    MTLArgumentDescriptor, MTLArgumentEncoder, MTLBuffer, MTLComputeCommandEncoder, MTLComputePipelineState,
+    MTLDataType, MTLDevice, MTLDeviceExt, MTLIndirectCommandBuffer, MTLLibraryExt, MTLSize, MTLTexture,
+};
+
+/// Errors raised while compiling the culling kernels.
+#[derive(Debug)]
+pub enum GpuCullingError {
+    /// `new_library_with_source` rejected [`CULLING_SHADER_SOURCE`].
+    Compilation(Retained<NSError>),
+    /// The compiled library does not contain the named kernel.
+    MissingFunction(&'static str),
+    /// Creating the compute pipeline for the named kernel failed.
+    PipelineCreation(&'static str, Retained<NSError>),
+    /// The device could not create the argument encoder for the ICB container.
+    ArgumentEncoderCreation,
+}
+
+impl fmt::Display for GpuCullingError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::Compilation(error) => write!(f, "failed to compile the culling kernels: {error}"),
+            Self::MissingFunction(name) => write!(f, "the culling library has no function named {name}"),
+            Self::PipelineCreation(name, error) => write!(f, "failed to create the {name} pipeline: {error}"),
+            Self::ArgumentEncoderCreation => {
+                f.write_str("failed to create the indirect command buffer argument encoder")
+            },
+        }
+    }
+}
+
+impl std::error::Error for GpuCullingError {}
+
+/// Compiled culling pipelines plus the Rust-side encoding for each kernel in
+/// [`CULLING_SHADER_SOURCE`].
+///
+/// The frustum and occlusion paths write one
+/// `MTLDrawIndexedPrimitivesIndirectArguments` per input draw, which the render
+/// pass consumes with one indexed indirect draw per slot. The ICB path encodes
+/// only visible draws, compacted, into an indirect command buffer created with
+/// `MTLIndirectCommandType::DrawIndexed`, `inherit_pipeline_state` and
+/// `inherit_buffers`; the render pass then executes it through the range written
+/// at [`CullingBufferLayout::execution_range_offset`]. Its three dispatches rely
+/// on the encoder's serial dispatch type for ordering.
+///
+/// Callers make the draw, index and indirect command buffers resident with
+/// `use_resource`.
+#[derive(Debug)]
+pub struct GpuCuller {
+    frustum: Retained<ProtocolObject<dyn MTLComputePipelineState>>,
+    occlusion: Retained<ProtocolObject<dyn MTLComputePipelineState>>,
+    icb: Retained<ProtocolObject<dyn MTLComputePipelineState>>,
+    reset_counter: Retained<ProtocolObject<dyn MTLComputePipelineState>>,
+    write_execution_range: Retained<ProtocolObject<dyn MTLComputePipelineState>>,
+    icb_argument_encoder: Retained<ProtocolObject<dyn MTLArgumentEncoder>>,
+}
+
+impl GpuCuller {
+    /// Compiles the culling kernels for `device`.
+    pub fn new(device: &ProtocolObject<dyn MTLDevice>) -> Result<Self, GpuCullingError> {
+        let library =
+            device.new_library_with_source(CULLING_SHADER_SOURCE, None).map_err(GpuCullingError::Compilation)?;
+        let pipeline = |name: &'static str| {
+            let function = library.new_function_with_name(name).ok_or(GpuCullingError::MissingFunction(name))?;
+            device
+                .new_compute_pipeline_state_with_function(&function)
+                .map_err(|error| GpuCullingError::PipelineCreation(name, error))
+        };
+
+        let container = MTLArgumentDescriptor::new();
+        container.set_data_type(MTLDataType::IndirectCommandBuffer);
+        container.set_index(0);
+        let icb_argument_encoder = device
+            .new_argument_encoder_with_arguments(&[&*container])
+            .ok_or(GpuCullingError::ArgumentEncoderCreation)?;
+
+        Ok(Self {
+            frustum: pipeline(FRUSTUM_CULL_KERNEL)?,
+            occlusion: pipeline(OCCLUSION_CULL_KERNEL)?,
+            icb: pipeline(ICB_CULL_KERNEL)?,
+            reset_counter: pipeline(RESET_COUNTER_KERNEL)?,
+            write_execution_range: pipeline(WRITE_EXECUTION_RANGE_KERNEL)?,
+            icb_argument_encoder,
+        })
+    }
+
+    /// Bytes needed for the argument buffer that holds the indirect command buffer.
+    pub fn icb_argument_buffer_length(&self) -> usize {
+        self.icb_argument_encoder.encoded_length()
+    }
+
+    /// Writes `icb` into `argument_buffer` at `offset` so [`encode_icb_cull`][Self::encode_icb_cull]
+    /// can pass it to the kernel.
+    pub fn encode_icb_argument(
+        &self,
+        argument_buffer: &ProtocolObject<dyn MTLBuffer>,
+        offset: usize,
+        icb: &ProtocolObject<dyn MTLIndirectCommandBuffer>,
+    ) {
+        self.icb_argument_encoder.set_argument_buffer(Some(argument_buffer), offset);
+        self.icb_argument_encoder.set_indirect_command_buffer(Some(icb), 0);
+    }
+
+    /// Frustum-culls `uniforms.draw_count` draws from `draws` into `arguments`.
+    pub fn encode_frustum_cull(
+        &self,
+        encoder: &ProtocolObject<dyn MTLComputeCommandEncoder>,
+        uniforms: &CullingUniforms,
+        draws: (&ProtocolObject<dyn MTLBuffer>, usize),
+        arguments: (&ProtocolObject<dyn MTLBuffer>, usize),
+    ) {
+        encoder.set_compute_pipeline_state(&self.frustum);
+        set_uniforms(encoder, uniforms, 0);
+        encoder.set_buffer(Some(draws.0), draws.1, 1);
+        encoder.set_buffer(Some(arguments.0), arguments.1, 2);
+        self.dispatch(encoder, &self.frustum, uniforms.draw_count as usize);
+    }
+
+    /// Frustum- and occlusion-culls draws against `hiz`, a max-depth pyramid
+    /// whose size and mip count are set with [`CullingUniforms::with_hiz`].
+    pub fn encode_occlusion_cull(
+        &self,
+        encoder: &ProtocolObject<dyn MTLComputeCommandEncoder>,
+        uniforms: &CullingUniforms,
+        draws: (&ProtocolObject<dyn MTLBuffer>, usize),
+        arguments: (&ProtocolObject<dyn MTLBuffer>, usize),
+        hiz: &ProtocolObject<dyn MTLTexture>,
+    ) {
+        encoder.set_compute_pipeline_state(&self.occlusion);
+        set_uniforms(encoder, uniforms, 0);
+        encoder.set_buffer(Some(draws.0), draws.1, 1);
+        encoder.set_buffer(Some(arguments.0), arguments.1, 2);
+        encoder.set_texture(Some(hiz), 0);
+        self.dispatch(encoder, &self.occlusion, uniforms.draw_count as usize);
+    }
+
+    /// Encodes visible draws into the indirect command buffer referenced by
+    /// `icb_argument_buffer` and writes the resulting execution range.
+    ///
+    /// At most `uniforms.command_capacity` draws are encoded; set it with
+    /// [`CullingUniforms::with_command_capacity`] when the indirect command
+    /// buffer holds fewer commands than there are draws.
+    ///
+    /// `buffer` is laid out as described by `layout`; `index_buffer` holds the
+    /// 32-bit indices every draw's `index_start` refers to.
+    pub fn encode_icb_cull(
+        &self,
+        encoder: &ProtocolObject<dyn MTLComputeCommandEncoder>,
+        uniforms: &CullingUniforms,
+        buffer: &ProtocolObject<dyn MTLBuffer>,
+        layout: &CullingBufferLayout,
+        icb_argument_buffer: (&ProtocolObject<dyn MTLBuffer>, usize),
+        index_buffer: &ProtocolObject<dyn MTLBuffer>,
+    ) {
+        let single = MTLSize::new(1, 1, 1);
+
+        encoder.set_compute_pipeline_state(&self.reset_counter);
+        encoder.set_buffer(Some(buffer), layout.counter_offset, 0);
+        encoder.dispatch_threadgroups(single, single);
+
+        encoder.set_compute_pipeline_state(&self.icb);
+        set_uniforms(encoder, uniforms, 0);
+        encoder.set_buffer(Some(buffer), layout.draws_offset, 1);
+        encoder.set_buffer(Some(icb_argument_buffer.0), icb_argument_buffer.1, 2);
+        encoder.set_buffer(Some(index_buffer), 0, 3);
+        encoder.set_buffer(Some(buffer), layout.counter_offset, 4);
+        self.dispatch(encoder, &self.icb, uniforms.draw_count as usize);
+
+        encoder.set_compute_pipeline_state(&self.write_execution_range);
+        encoder.set_buffer(Some(buffer), layout.counter_offset, 0);
+        encoder.set_buffer(Some(buffer), layout.execution_range_offset, 1);
+        set_uniforms(encoder, uniforms, 2);
+        encoder.dispatch_threadgroups(single, single);
+    }
+
+    fn dispatch(
+        &self,
+        encoder: &ProtocolObject<dyn MTLComputeCommandEncoder>,
+        pipeline: &ProtocolObject<dyn MTLComputePipelineState>,
+        draw_count: usize,
+    ) {
+        let (threadgroups, threads_per_threadgroup) = culling_dispatch_size(
+            draw_count,
+            pipeline.thread_execution_width(),
+            pipeline.max_total_threads_per_threadgroup(),
+        );
+        encoder.dispatch_threadgroups(threadgroups, threads_per_threadgroup);
+    }
+}
+
+fn set_uniforms(
+    encoder: &ProtocolObject<dyn MTLComputeCommandEncoder>,
+    uniforms: &CullingUniforms,
+    index: usize,
+) {
+    encoder.set_bytes(NonNull::from(uniforms).cast(), size_of::<CullingUniforms>(), index);
+}
@@ -0,0 +1,75 @@
+/// Extracts the six inward-facing frustum planes from a column-major
+/// view-projection matrix with Metal's `[0, 1]` clip-space depth range.
+///
+/// Planes are returned as `(normal, distance)` with unit-length normals, in
+/// the order left, right, bottom, top, near, far. A point `p` is inside a plane
+/// when `dot(normal, p) + distance >= 0`.
+pub fn frustum_planes(view_projection: &[[f32; 4]; 4]) -> [[f32; 4]; 6] {
+    let row = |r: usize| -> [f32; 4] { core::array::from_fn(|c| view_projection[c][r]) };
+    let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+    let add = |a: [f32; 4], b: [f32; 4]| -> [f32; 4] { core::array::from_fn(|i| a[i] + b[i]) };
+    let sub = |a: [f32; 4], b: [f32; 4]| -> [f32; 4] { core::array::from_fn(|i| a[i] - b[i]) };
+    [add(r3, r0), sub(r3, r0), add(r3, r1), sub(r3, r1), r2, sub(r3, r2)].map(normalize_plane)
+}
+
+fn normalize_plane(plane: [f32; 4]) -> [f32; 4] {
+    let length = (plane[0] * plane[0] + plane[1] * plane[1] + plane[2] * plane[2]).sqrt();
+    if length > 0.0 {
+        plane.map(|v| v / length)
+    } else {
+        plane
+    }
+}
+
+/// CPU reference for the kernels' frustum test: whether a sphere (center in
+/// `xyz`, radius in `w`) intersects the frustum described by `planes`.
+pub fn sphere_in_frustum(
+    planes: &[[f32; 4]; 6],
+    sphere: [f32; 4],
+) -> bool {
+    planes
+        .iter()
+        .all(|plane| plane[0] * sphere[0] + plane[1] * sphere[1] + plane[2] * sphere[2] + plane[3] >= -sphere[3])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDENTITY: [[f32; 4]; 4] =
+        [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+
+    #[test]
+    fn identity_planes() {
+        assert_eq!(
+            frustum_planes(&IDENTITY),
+            [
+                [1.0, 0.0, 0.0, 1.0],
+                [-1.0, 0.0, 0.0, 1.0],
+                [0.0, 1.0, 0.0, 1.0],
+                [0.0, -1.0, 0.0, 1.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [0.0, 0.0, -1.0, 1.0],
+            ]
+        );
+    }
+
+    #[test]
+    fn planes_are_normalized() {
+        let mut scaled = IDENTITY;
+        scaled[0][0] = 2.0;
+        let planes = frustum_planes(&scaled);
+        assert_eq!(planes[0], [1.0, 0.0, 0.0, 0.5]);
+        assert_eq!(planes[1], [-1.0, 0.0, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn sphere_tests() {
+        let planes = frustum_planes(&IDENTITY);
+        assert!(sphere_in_frustum(&planes, [0.0, 0.0, 0.5, 0.0]));
+        assert!(!sphere_in_frustum(&planes, [2.0, 0.0, 0.5, 0.5]));
+        assert!(sphere_in_frustum(&planes, [2.0, 0.0, 0.5, 1.5]));
+        assert!(!sphere_in_frustum(&planes, [0.0, 0.0, -1.0, 0.5]));
+        assert!(!sphere_in_frustum(&planes, [0.0, 0.0, 2.0, 0.5]));
+    }
+}
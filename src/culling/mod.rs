@@ -0,0 +1,12 @@
+mod culler;
+mod frustum;
+mod shaders;
+mod types;
+
+pub use culler::{GpuCuller, GpuCullingError};
+pub use frustum::{frustum_planes, sphere_in_frustum};
+pub use shaders::{
+    CULLING_SHADER_SOURCE, FRUSTUM_CULL_KERNEL, ICB_CULL_KERNEL, OCCLUSION_CULL_KERNEL, RESET_COUNTER_KERNEL,
+    WRITE_EXECUTION_RANGE_KERNEL,
+};
+pub use types::{CullingBufferLayout, CullingDrawInput, CullingUniforms, culling_dispatch_size};
@@ -0,0 +1,200 @@
+/// Metal Shading Language source for the GPU culling kernels.
+///
+/// Compile it with `MTLDeviceExt::new_library_with_source`. The struct layouts
+/// match [`CullingUniforms`][crate::CullingUniforms],
+/// [`CullingDrawInput`][crate::CullingDrawInput] and
+/// [`MTLDrawIndexedPrimitivesIndirectArguments`][crate::MTLDrawIndexedPrimitivesIndirectArguments].
+///
+/// Kernels:
+/// - [`FRUSTUM_CULL_KERNEL`]: writes one indexed indirect draw per input draw,
+///   with `instance_count` zeroed for draws outside the frustum.
+/// - [`OCCLUSION_CULL_KERNEL`]: like the frustum kernel, and additionally tests
+///   the draw's bounds against a max-depth (Hi-Z) pyramid. Draws are never
+///   occluded while `hiz_mip_count` is zero.
+/// - [`ICB_CULL_KERNEL`]: encodes visible draws, compacted, into an indirect
+///   command buffer that inherits its pipeline state and buffers. Visible draws
+///   beyond `command_capacity` are dropped.
+/// - [`RESET_COUNTER_KERNEL`] and [`WRITE_EXECUTION_RANGE_KERNEL`]: clear the
+///   compaction counter before culling and turn it into an
+///   `MTLIndirectCommandBufferExecutionRange` afterwards.
+pub const CULLING_SHADER_SOURCE: &str = r#"
+#include <metal_stdlib>
+using namespace metal;
+
+struct DrawIndexedArguments {
+    uint index_count;
+    uint instance_count;
+    uint index_start;
+    int base_vertex;
+    uint base_instance;
+};
+
+struct CullingUniforms {
+    float4 frustum_planes[6];
+    float4x4 view_projection;
+    float2 hiz_size;
+    uint draw_count;
+    uint hiz_mip_count;
+    uint command_capacity;
+    uint padding[3];
+};
+
+struct CullingDrawInput {
+    float4 bounding_sphere;
+    DrawIndexedArguments arguments;
+    uint padding[3];
+};
+
+struct ExecutionRange {
+    uint location;
+    uint length;
+};
+
+struct IndirectCommandBufferContainer {
+    command_buffer commands [[id(0)]];
+};
+
+static bool sphere_in_frustum(constant CullingUniforms &uniforms, float4 sphere) {
+    for (uint i = 0; i < 6; ++i) {
+        float4 plane = uniforms.frustum_planes[i];
+        if (dot(plane.xyz, sphere.xyz) + plane.w < -sphere.w) {
+            return false;
+        }
+    }
+    return true;
+}
+
+static bool sphere_occluded(
+    constant CullingUniforms &uniforms,
+    texture2d<float, access::read> hiz,
+    float4 sphere
+) {
+    if (uniforms.hiz_mip_count == 0) {
+        return false;
+    }
+    float2 uv_min = float2(1.0);
+    float2 uv_max = float2(0.0);
+    float nearest_depth = 1.0;
+    for (uint corner = 0; corner < 8; ++corner) {
+        float3 offset = float3(
+            (corner & 1) ? sphere.w : -sphere.w,
+            (corner & 2) ? sphere.w : -sphere.w,
+            (corner & 4) ? sphere.w : -sphere.w
+        );
+        float4 clip = uniforms.view_projection * float4(sphere.xyz + offset, 1.0);
+        if (clip.w <= 0.0) {
+            return false;
+        }
+        float3 ndc = clip.xyz / clip.w;
+        float2 uv = float2(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+        uv_min = min(uv_min, uv);
+        uv_max = max(uv_max, uv);
+        nearest_depth = min(nearest_depth, ndc.z);
+    }
+    uv_min = saturate(uv_min);
+    uv_max = saturate(uv_max);
+
+    float2 extent = (uv_max - uv_min) * uniforms.hiz_size;
+    uint mip = uint(ceil(log2(max(max(extent.x, extent.y), 1.0))));
+    mip = min(mip, uniforms.hiz_mip_count - 1);
+
+    uint2 mip_size = uint2(hiz.get_width(mip), hiz.get_height(mip));
+    uint2 lo = min(uint2(uv_min * float2(mip_size)), mip_size - 1);
+    uint2 hi = min(uint2(uv_max * float2(mip_size)), mip_size - 1);
+    float farthest = max(
+        max(hiz.read(lo, mip).r, hiz.read(uint2(hi.x, lo.y), mip).r),
+        max(hiz.read(uint2(lo.x, hi.y), mip).r, hiz.read(hi, mip).r)
+    );
+    return nearest_depth > farthest;
+}
+
+static DrawIndexedArguments culled_arguments(DrawIndexedArguments arguments, bool visible) {
+    if (!visible) {
+        arguments.instance_count = 0;
+    }
+    return arguments;
+}
+
+kernel void cull_draws_frustum(
+    constant CullingUniforms &uniforms [[buffer(0)]],
+    device const CullingDrawInput *draws [[buffer(1)]],
+    device DrawIndexedArguments *arguments [[buffer(2)]],
+    uint index [[thread_position_in_grid]]
+) {
+    if (index >= uniforms.draw_count) {
+        return;
+    }
+    CullingDrawInput draw = draws[index];
+    arguments[index] = culled_arguments(draw.arguments, sphere_in_frustum(uniforms, draw.bounding_sphere));
+}
+
+kernel void cull_draws_occlusion(
+    constant CullingUniforms &uniforms [[buffer(0)]],
+    device const CullingDrawInput *draws [[buffer(1)]],
+    device DrawIndexedArguments *arguments [[buffer(2)]],
+    texture2d<float, access::read> hiz [[texture(0)]],
+    uint index [[thread_position_in_grid]]
+) {
+    if (index >= uniforms.draw_count) {
+        return;
+    }
+    CullingDrawInput draw = draws[index];
+    bool visible = sphere_in_frustum(uniforms, draw.bounding_sphere)
+        && !sphere_occluded(uniforms, hiz, draw.bounding_sphere);
+    arguments[index] = culled_arguments(draw.arguments, visible);
+}
+
+kernel void cull_draws_to_icb(
+    constant CullingUniforms &uniforms [[buffer(0)]],
+    device const CullingDrawInput *draws [[buffer(1)]],
+    device IndirectCommandBufferContainer &container [[buffer(2)]],
+    device const uint *indices [[buffer(3)]],
+    device atomic_uint *counter [[buffer(4)]],
+    uint index [[thread_position_in_grid]]
+) {
+    if (index >= uniforms.draw_count) {
+        return;
+    }
+    CullingDrawInput draw = draws[index];
+    if (!sphere_in_frustum(uniforms, draw.bounding_sphere) || draw.arguments.instance_count == 0) {
+        return;
+    }
+    uint slot = atomic_fetch_add_explicit(counter, 1, memory_order_relaxed);
+    if (slot >= uniforms.command_capacity) {
+        return;
+    }
+    render_command command(container.commands, slot);
+    command.draw_indexed_primitives(
+        primitive_type::triangle,
+        draw.arguments.index_count,
+        indices + draw.arguments.index_start,
+        draw.arguments.instance_count,
+        draw.arguments.base_vertex,
+        draw.arguments.base_instance
+    );
+}
+
+kernel void reset_culling_counter(device atomic_uint *counter [[buffer(0)]]) {
+    atomic_store_explicit(counter, 0, memory_order_relaxed);
+}
+
+kernel void write_icb_execution_range(
+    device atomic_uint *counter [[buffer(0)]],
+    device ExecutionRange *range [[buffer(1)]],
+    constant CullingUniforms &uniforms [[buffer(2)]]
+) {
+    range->location = 0;
+    range->length = min(atomic_load_explicit(counter, memory_order_relaxed), uniforms.command_capacity);
+}
+"#;
+
+/// Kernel that frustum-culls draws into per-draw indexed indirect arguments.
+pub const FRUSTUM_CULL_KERNEL: &str = "cull_draws_frustum";
+/// Kernel that frustum- and Hi-Z-culls draws into per-draw indexed indirect arguments.
+pub const OCCLUSION_CULL_KERNEL: &str = "cull_draws_occlusion";
+/// Kernel that encodes visible draws into an indirect command buffer.
+pub const ICB_CULL_KERNEL: &str = "cull_draws_to_icb";
+/// Single-thread kernel that zeroes the compaction counter.
+pub const RESET_COUNTER_KERNEL: &str = "reset_culling_counter";
+/// Single-thread kernel that writes the compaction counter as an execution range.
+pub const WRITE_EXECUTION_RANGE_KERNEL: &str = "write_icb_execution_range";
@@ -0,0 +1,224 @@
+use crate::{MTLDrawIndexedPrimitivesIndirectArguments, MTLSize};
+
+/// Per-frame parameters shared by all culling kernels (`CullingUniforms` in
+/// [`CULLING_SHADER_SOURCE`][crate::CULLING_SHADER_SOURCE]).
+///
+/// Matrices are column-major, as in Metal Shading Language.
+#[repr(C, align(16))]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct CullingUniforms {
+    /// Inward-facing planes `(normal, distance)`: left, right, bottom, top, near, far.
+    pub frustum_planes: [[f32; 4]; 6],
+    pub view_projection: [[f32; 4]; 4],
+    /// Size in texels of mip 0 of the Hi-Z pyramid; only read by the occlusion kernel.
+    pub hiz_size: [f32; 2],
+    pub draw_count: u32,
+    /// Number of mips in the Hi-Z pyramid; only read by the occlusion kernel,
+    /// which treats every draw as unoccluded while it is zero.
+    pub hiz_mip_count: u32,
+    /// Number of commands in the indirect command buffer; only read by the ICB
+    /// kernel, which drops visible draws past it.
+    pub command_capacity: u32,
+    pub padding: [u32; 3],
+}
+
+const _: () = assert!(size_of::<CullingUniforms>() == 192);
+const _: () = assert!(align_of::<CullingUniforms>() == 16);
+
+impl CullingUniforms {
+    /// Builds uniforms for `draw_count` draws, deriving the frustum planes from
+    /// `view_projection`.
+    ///
+    /// The command capacity defaults to `draw_count`, matching an indirect
+    /// command buffer with one command per draw.
+    pub fn new(
+        view_projection: [[f32; 4]; 4],
+        draw_count: u32,
+    ) -> Self {
+        Self {
+            frustum_planes: super::frustum_planes(&view_projection),
+            view_projection,
+            hiz_size: [0.0; 2],
+            draw_count,
+            hiz_mip_count: 0,
+            command_capacity: draw_count,
+            padding: [0; 3],
+        }
+    }
+
+    /// Sets the Hi-Z pyramid size used by the occlusion kernel.
+    pub fn with_hiz(
+        mut self,
+        width: usize,
+        height: usize,
+        mip_count: usize,
+    ) -> Self {
+        self.hiz_size = [width as f32, height as f32];
+        self.hiz_mip_count = mip_count as u32;
+        self
+    }
+
+    /// Sets the number of commands the ICB kernel may encode.
+    pub fn with_command_capacity(
+        mut self,
+        command_capacity: usize,
+    ) -> Self {
+        self.command_capacity = command_capacity as u32;
+        self
+    }
+}
+
+/// One draw to cull (`CullingDrawInput` in
+/// [`CULLING_SHADER_SOURCE`][crate::CULLING_SHADER_SOURCE]).
+#[repr(C, align(16))]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct CullingDrawInput {
+    /// World-space bounding sphere: center in `xyz`, radius in `w`.
+    pub bounding_sphere: [f32; 4],
+    /// Arguments written out unchanged when the draw is visible.
+    pub arguments: MTLDrawIndexedPrimitivesIndirectArguments,
+    pub padding: [u32; 3],
+}
+
+const _: () = assert!(size_of::<MTLDrawIndexedPrimitivesIndirectArguments>() == 20);
+const _: () = assert!(size_of::<CullingDrawInput>() == 48);
+const _: () = assert!(align_of::<CullingDrawInput>() == 16);
+
+impl CullingDrawInput {
+    pub fn new(
+        center: [f32; 3],
+        radius: f32,
+        arguments: MTLDrawIndexedPrimitivesIndirectArguments,
+    ) -> Self {
+        Self {
+            bounding_sphere: [center[0], center[1], center[2], radius],
+            arguments,
+            padding: [0; 3],
+        }
+    }
+}
+
+/// Buffer offsets used by the ICB culling path when everything lives in a
+/// single `MTLBuffer`.
+///
+/// Every section starts on a 256-byte boundary, which satisfies the buffer
+/// offset alignment of all Metal GPU families.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CullingBufferLayout {
+    pub draw_count: usize,
+    /// Offset of the `CullingDrawInput` array.
+    pub draws_offset: usize,
+    /// Offset of the `MTLDrawIndexedPrimitivesIndirectArguments` array.
+    pub arguments_offset: usize,
+    /// Offset of the `atomic_uint` compaction counter.
+    pub counter_offset: usize,
+    /// Offset of the `MTLIndirectCommandBufferExecutionRange`.
+    pub execution_range_offset: usize,
+    pub length: usize,
+}
+
+impl CullingBufferLayout {
+    pub const OFFSET_ALIGNMENT: usize = 256;
+
+    pub fn new(draw_count: usize) -> Self {
+        let align = |offset: usize| offset.next_multiple_of(Self::OFFSET_ALIGNMENT);
+        let draws_offset = 0;
+        let arguments_offset = align(draws_offset + draw_count * size_of::<CullingDrawInput>());
+        let counter_offset =
+            align(arguments_offset + draw_count * size_of::<MTLDrawIndexedPrimitivesIndirectArguments>());
+        let execution_range_offset = align(counter_offset + size_of::<u32>());
+        let length = align(execution_range_offset + 2 * size_of::<u32>());
+        Self {
+            draw_count,
+            draws_offset,
+            arguments_offset,
+            counter_offset,
+            execution_range_offset,
+            length,
+        }
+    }
+
+    /// Offset of the indirect arguments for draw `index`, as passed to an
+    /// indexed indirect draw call.
+    pub fn arguments_offset_for_draw(
+        &self,
+        index: usize,
+    ) -> usize {
+        self.arguments_offset + index * size_of::<MTLDrawIndexedPrimitivesIndirectArguments>()
+    }
+}
+
+/// Grid and threadgroup sizes for a one-thread-per-draw culling dispatch.
+///
+/// The threadgroup width is the largest multiple of `thread_execution_width`
+/// not exceeding `max_total_threads_per_threadgroup` (capped at 256), so the
+/// kernels' bounds check discards the padding threads.
+pub fn culling_dispatch_size(
+    draw_count: usize,
+    thread_execution_width: usize,
+    max_total_threads_per_threadgroup: usize,
+) -> (MTLSize, MTLSize) {
+    let width = thread_execution_width.max(1);
+    let limit = max_total_threads_per_threadgroup.clamp(1, 256);
+    let threads = if limit >= width {
+        limit / width * width
+    } else {
+        limit
+    };
+    let threadgroups = draw_count.div_ceil(threads).max(1);
+    (MTLSize::new(threadgroups, 1, 1), MTLSize::new(threads, 1, 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use core::mem::offset_of;
+
+    use super::*;
+
+    #[test]
+    fn uniform_offsets_match_shader() {
+        assert_eq!(offset_of!(CullingUniforms, frustum_planes), 0);
+        assert_eq!(offset_of!(CullingUniforms, view_projection), 96);
+        assert_eq!(offset_of!(CullingUniforms, hiz_size), 160);
+        assert_eq!(offset_of!(CullingUniforms, draw_count), 168);
+        assert_eq!(offset_of!(CullingUniforms, hiz_mip_count), 172);
+        assert_eq!(offset_of!(CullingUniforms, command_capacity), 176);
+
+        assert_eq!(offset_of!(CullingDrawInput, bounding_sphere), 0);
+        assert_eq!(offset_of!(CullingDrawInput, arguments), 16);
+        assert_eq!(offset_of!(CullingDrawInput, padding), 36);
+    }
+
+    #[test]
+    fn uniforms_builders() {
+        let uniforms = CullingUniforms::new([[0.0; 4]; 4], 10);
+        assert_eq!((uniforms.draw_count, uniforms.command_capacity, uniforms.hiz_mip_count), (10, 10, 0));
+        let uniforms = uniforms.with_hiz(1920, 1080, 11).with_command_capacity(4);
+        assert_eq!(uniforms.hiz_size, [1920.0, 1080.0]);
+        assert_eq!((uniforms.hiz_mip_count, uniforms.command_capacity), (11, 4));
+    }
+
+    #[test]
+    fn buffer_layout_alignment() {
+        let layout = CullingBufferLayout::new(10);
+        assert_eq!(layout.draws_offset, 0);
+        assert_eq!(layout.arguments_offset, 512);
+        assert_eq!(layout.counter_offset, 768);
+        assert_eq!(layout.execution_range_offset, 1024);
+        assert_eq!(layout.length, 1280);
+        assert_eq!(layout.arguments_offset_for_draw(3), 572);
+    }
+
+    #[test]
+    fn dispatch_sizing() {
+        let size = |draws, width, max| {
+            let (groups, threads) = culling_dispatch_size(draws, width, max);
+            (groups.width, threads.width)
+        };
+        assert_eq!(size(1000, 32, 1024), (4, 256));
+        assert_eq!(size(1000, 32, 200), (6, 192));
+        assert_eq!(size(0, 32, 1024), (1, 256));
+        assert_eq!(size(10, 64, 48), (1, 48));
+        assert_eq!(size(10, 0, 0), (10, 1));
+    }
+}
@@ -3,6 +3,7 @@ use std::{ffi::c_void, path::Path, ptr::NonNull};
 use dispatch2::DispatchData;
 use objc2::{Message, extern_protocol, msg_send, rc::Retained, runtime::ProtocolObject};
 use objc2_foundation::{NSArray, NSError, NSObjectProtocol, NSString, NSURL};
+use objc2_io_surface::IOSurfaceRef;
 
 use super::{MTLArchitecture, MTLSizeAndAlign};
 use crate::{
@@ -411,6 +412,16 @@ extern_protocol!(
             shared_handle: &MTLSharedTextureHandle,
         ) -> Option<Retained<ProtocolObject<dyn MTLTexture>>>;
 
+        /// Creates a texture that shares storage with a plane of an IOSurface.
+        #[unsafe(method(newTextureWithDescriptor:iosurface:plane:))]
+        #[unsafe(method_family = new)]
+        fn new_texture_with_descriptor_iosurface_plane(
+            &self,
+            descriptor: &MTLTextureDescriptor,
+            iosurface: &IOSurfaceRef,
+            plane: usize,
+        ) -> Option<Retained<ProtocolObject<dyn MTLTexture>>>;
+
         /// Creates a new variable rasterization rate map with the given descriptor.
         #[unsafe(method(newRasterizationRateMapWithDescriptor:))]
         #[unsafe(method_family = new)]
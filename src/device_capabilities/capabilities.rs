@@ -0,0 +1,273 @@
+use objc2::runtime::ProtocolObject;
+
+use super::{FamilyCapabilities, PixelFormatCapabilities, compressed_format_supported};
+use crate::{
+    MTLArgumentBuffersTier, MTLDevice, MTLDeviceExt, MTLDeviceLocation, MTLGPUFamily, MTLPixelFormat,
+    MTLReadWriteTextureTier, MTLSize, MTLSparsePageSize,
+};
+
+/// GPU families queried by [`DeviceCapabilities::new`].
+pub(crate) const FAMILIES: [MTLGPUFamily; 15] = [
+    MTLGPUFamily::Apple1,
+    MTLGPUFamily::Apple2,
+    MTLGPUFamily::Apple3,
+    MTLGPUFamily::Apple4,
+    MTLGPUFamily::Apple5,
+    MTLGPUFamily::Apple6,
+    MTLGPUFamily::Apple7,
+    MTLGPUFamily::Apple8,
+    MTLGPUFamily::Apple9,
+    MTLGPUFamily::Mac2,
+    MTLGPUFamily::Common1,
+    MTLGPUFamily::Common2,
+    MTLGPUFamily::Common3,
+    MTLGPUFamily::Metal3,
+    MTLGPUFamily::Metal4,
+];
+
+/// Texture sample counts queried by [`DeviceCapabilities::new`].
+const SAMPLE_COUNTS: [usize; 5] = [1, 2, 4, 8, 16];
+
+/// Sparse page sizes queried by [`DeviceCapabilities::new`].
+pub(crate) const SPARSE_PAGE_SIZES: [MTLSparsePageSize; 3] =
+    [MTLSparsePageSize::KB16, MTLSparsePageSize::KB64, MTLSparsePageSize::KB256];
+
+/// The largest vertex amplification and rasterization rate map layer counts
+/// queried by [`DeviceCapabilities::new`].
+const MAX_QUERIED_COUNT: usize = 16;
+
+/// A snapshot of a device's capabilities, so resources can be validated
+/// without querying Metal, or against a recorded device.
+///
+/// Snapshots can be saved as JSON with [`to_json`](Self::to_json) for bug
+/// reports and test fixtures, and loaded on any platform with
+/// [`from_json`](Self::from_json).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DeviceCapabilities {
+    pub name: String,
+    /// The GPU architecture, such as `applegpu_g13s`.
+    pub architecture: String,
+    pub location: MTLDeviceLocation,
+    pub location_number: usize,
+    pub is_low_power: bool,
+    pub is_headless: bool,
+    pub is_removable: bool,
+    pub has_unified_memory: bool,
+    pub recommended_max_working_set_size: u64,
+    /// Bytes per second between system and GPU memory; 0 with unified
+    /// memory.
+    pub max_transfer_rate: u64,
+    pub peer_group_id: u64,
+    pub peer_index: u32,
+    pub peer_count: u32,
+    /// The GPU families the device supports.
+    pub families: Vec<MTLGPUFamily>,
+    /// The texture sample counts the device supports.
+    pub texture_sample_counts: Vec<usize>,
+    pub read_write_texture_tier: MTLReadWriteTextureTier,
+    pub argument_buffers_tier: MTLArgumentBuffersTier,
+    pub max_threads_per_threadgroup: MTLSize,
+    pub max_threadgroup_memory_length: usize,
+    pub max_buffer_length: usize,
+    pub max_argument_buffer_sampler_count: usize,
+    /// The largest vertex amplification count supported, up to 16; 1 means
+    /// amplification isn't supported.
+    pub max_vertex_amplification_count: usize,
+    /// The most layers a rasterization rate map supports, up to 16; 0 if
+    /// rate maps aren't supported.
+    pub max_rasterization_rate_map_layer_count: usize,
+    pub sparse_tile_size_in_bytes: usize,
+    /// Bytes per sparse tile for each placement sparse page size; empty
+    /// without placement sparse support.
+    pub placement_sparse_tile_sizes_in_bytes: Vec<(MTLSparsePageSize, usize)>,
+    pub supports_bc_texture_compression: bool,
+    pub supports_32bit_float_filtering: bool,
+    pub supports_32bit_msaa: bool,
+    pub supports_depth24_stencil8: bool,
+    pub supports_query_texture_lod: bool,
+    pub supports_pull_model_interpolation: bool,
+    pub supports_shader_barycentric_coordinates: bool,
+    pub supports_programmable_sample_positions: bool,
+    pub supports_raster_order_groups: bool,
+    pub supports_dynamic_libraries: bool,
+    pub supports_render_dynamic_libraries: bool,
+    pub supports_function_pointers: bool,
+    pub supports_function_pointers_from_render: bool,
+    pub supports_raytracing: bool,
+    pub supports_raytracing_from_render: bool,
+    pub supports_primitive_motion_blur: bool,
+    pub supports_placement_sparse: bool,
+}
+
+impl DeviceCapabilities {
+    /// Queries `device`'s capabilities.
+    pub fn new(device: &ProtocolObject<dyn MTLDevice>) -> Self {
+        let max_count = |supported: &dyn Fn(usize) -> bool| {
+            (1..=MAX_QUERIED_COUNT).take_while(|&count| supported(count)).last().unwrap_or(0)
+        };
+        let supports_placement_sparse = device.supports_placement_sparse();
+        let placement_sparse_tile_sizes_in_bytes = if supports_placement_sparse {
+            let tile_size = |page_size| (page_size, device.sparse_tile_size_in_bytes_for_sparse_page_size(page_size));
+            SPARSE_PAGE_SIZES.into_iter().map(tile_size).collect()
+        } else {
+            Vec::new()
+        };
+        Self {
+            name: device.name(),
+            architecture: device.architecture().name(),
+            location: device.location(),
+            location_number: device.location_number(),
+            is_low_power: device.is_low_power(),
+            is_headless: device.is_headless(),
+            is_removable: device.is_removable(),
+            has_unified_memory: device.has_unified_memory(),
+            recommended_max_working_set_size: device.recommended_max_working_set_size(),
+            max_transfer_rate: device.max_transfer_rate(),
+            peer_group_id: device.peer_group_id(),
+            peer_index: device.peer_index(),
+            peer_count: device.peer_count(),
+            families: FAMILIES.into_iter().filter(|&family| device.supports_family(family)).collect(),
+            texture_sample_counts: SAMPLE_COUNTS
+                .into_iter()
+                .filter(|&count| device.supports_texture_sample_count(count))
+                .collect(),
+            read_write_texture_tier: device.read_write_texture_support(),
+            argument_buffers_tier: device.argument_buffers_support(),
+            max_threads_per_threadgroup: device.max_threads_per_threadgroup(),
+            max_threadgroup_memory_length: device.max_threadgroup_memory_length(),
+            max_buffer_length: device.max_buffer_length(),
+            max_argument_buffer_sampler_count: device.max_argument_buffer_sampler_count(),
+            max_vertex_amplification_count: max_count(&|count| device.supports_vertex_amplification_count(count)),
+            max_rasterization_rate_map_layer_count: max_count(&|count| {
+                device.supports_rasterization_rate_map_with_layer_count(count)
+            }),
+            sparse_tile_size_in_bytes: device.sparse_tile_size_in_bytes(),
+            placement_sparse_tile_sizes_in_bytes,
+            supports_bc_texture_compression: device.supports_bc_texture_compression(),
+            supports_32bit_float_filtering: device.supports_32bit_float_filtering(),
+            supports_32bit_msaa: device.supports_32bit_msaa(),
+            supports_depth24_stencil8: device.is_depth24_stencil8_pixel_format_supported(),
+            supports_query_texture_lod: device.supports_query_texture_lod(),
+            supports_pull_model_interpolation: device.supports_pull_model_interpolation(),
+            supports_shader_barycentric_coordinates: device.supports_shader_barycentric_coordinates(),
+            supports_programmable_sample_positions: device.are_programmable_sample_positions_supported(),
+            supports_raster_order_groups: device.raster_order_groups_supported(),
+            supports_dynamic_libraries: device.supports_dynamic_libraries(),
+            supports_render_dynamic_libraries: device.supports_render_dynamic_libraries(),
+            supports_function_pointers: device.supports_function_pointers(),
+            supports_function_pointers_from_render: device.supports_function_pointers_from_render(),
+            supports_raytracing: device.supports_raytracing(),
+            supports_raytracing_from_render: device.supports_raytracing_from_render(),
+            supports_primitive_motion_blur: device.supports_primitive_motion_blur(),
+            supports_placement_sparse,
+        }
+    }
+
+    pub fn supports_family(
+        &self,
+        family: MTLGPUFamily,
+    ) -> bool {
+        self.families.contains(&family)
+    }
+
+    pub fn supports_texture_sample_count(
+        &self,
+        sample_count: usize,
+    ) -> bool {
+        self.texture_sample_counts.contains(&sample_count)
+    }
+
+    /// The documented capabilities of the device's GPU families: the largest
+    /// limit any of them guarantees, and every feature. `None` if the device
+    /// only reports families without tables, such as `Common1`.
+    pub fn family_capabilities(&self) -> Option<FamilyCapabilities> {
+        self.families
+            .iter()
+            .filter_map(|&family| FamilyCapabilities::for_family(family))
+            .reduce(FamilyCapabilities::union)
+    }
+
+    /// Bytes per sparse tile for placement sparse textures with `page_size`.
+    pub fn placement_sparse_tile_size_in_bytes(
+        &self,
+        page_size: MTLSparsePageSize,
+    ) -> Option<usize> {
+        let sizes = self.placement_sparse_tile_sizes_in_bytes.iter();
+        sizes.copied().find(|&(size, _)| size == page_size).map(|(_, bytes)| bytes)
+    }
+
+    /// Whether textures of `format` can be created: block-compressed formats
+    /// need BC support or an Apple GPU, and `Depth24Unorm_Stencil8` is only
+    /// available on some Macs.
+    pub fn supports_pixel_format(
+        &self,
+        format: MTLPixelFormat,
+    ) -> bool {
+        match format {
+            MTLPixelFormat::Invalid => false,
+            MTLPixelFormat::Depth24Unorm_Stencil8 => self.supports_depth24_stencil8,
+            _ => compressed_format_supported(
+                self.supports_bc_texture_compression,
+                |family| self.supports_family(family),
+                format,
+            ),
+        }
+    }
+
+    /// What textures of `format` can be used for: the capabilities of every
+    /// family the device supports, narrowed by the device's own 32-bit float,
+    /// BC, depth and read-write support.
+    pub fn pixel_format_capabilities(
+        &self,
+        format: MTLPixelFormat,
+    ) -> PixelFormatCapabilities {
+        if !self.supports_pixel_format(format) {
+            return PixelFormatCapabilities::empty();
+        }
+        let mut caps = self
+            .families
+            .iter()
+            .fold(PixelFormatCapabilities::empty(), |caps, &family| caps | format.capabilities(family));
+        if format.is_compressed() {
+            caps |= PixelFormatCapabilities::FILTER;
+        }
+        if matches!(format, MTLPixelFormat::R32Float | MTLPixelFormat::RG32Float | MTLPixelFormat::RGBA32Float) {
+            caps.set(PixelFormatCapabilities::FILTER, self.supports_32bit_float_filtering);
+            if !self.supports_32bit_msaa {
+                caps -= PixelFormatCapabilities::MSAA | PixelFormatCapabilities::RESOLVE;
+            }
+        }
+        caps.set(PixelFormatCapabilities::READ_WRITE, self.supports_read_write_texture(format));
+        caps
+    }
+
+    /// Whether shaders can read and write textures of `format` in the same
+    /// function.
+    pub fn supports_read_write_texture(
+        &self,
+        format: MTLPixelFormat,
+    ) -> bool {
+        use MTLPixelFormat as F;
+
+        let tier = match format {
+            F::R32Float | F::R32Uint | F::R32Sint => MTLReadWriteTextureTier::Tier1,
+            F::RGBA32Float
+            | F::RGBA32Uint
+            | F::RGBA32Sint
+            | F::RGBA16Float
+            | F::RGBA16Uint
+            | F::RGBA16Sint
+            | F::RGBA8Unorm
+            | F::RGBA8Uint
+            | F::RGBA8Sint
+            | F::R16Float
+            | F::R16Uint
+            | F::R16Sint
+            | F::R8Unorm
+            | F::R8Uint
+            | F::R8Sint => MTLReadWriteTextureTier::Tier2,
+            _ => return false,
+        };
+        self.read_write_texture_tier >= tier
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{MTLGPUFamily, MTLPixelFormat};
+
+/// Limits and features Apple's Metal feature set tables document for a GPU
+/// family. Any device supporting the family meets them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FamilyCapabilities {
+    /// Largest width of 1D textures, and width and height of 2D and cube
+    /// textures.
+    pub max_texture_size: usize,
+    /// Largest width, height and depth of 3D textures.
+    pub max_3d_texture_size: usize,
+    pub max_texture_array_length: usize,
+    /// Buffer entries in a function's argument table.
+    pub max_buffer_arguments: usize,
+    /// Texture entries in a function's argument table.
+    pub max_texture_arguments: usize,
+    /// Sampler state entries in a function's argument table.
+    pub max_sampler_arguments: usize,
+    pub max_threads_per_threadgroup: usize,
+    pub max_threadgroup_memory_length: usize,
+    pub max_color_render_targets: usize,
+    pub max_vertex_attributes: usize,
+    pub supports_astc: bool,
+    pub supports_astc_hdr: bool,
+    pub supports_etc2: bool,
+    pub supports_pvrtc: bool,
+    /// BC is also available on some Apple GPUs; see
+    /// [`DeviceCapabilities::supports_bc_texture_compression`](crate::DeviceCapabilities::supports_bc_texture_compression).
+    pub supports_bc: bool,
+    pub supports_memoryless: bool,
+    pub supports_cube_arrays: bool,
+}
+
+const APPLE1: FamilyCapabilities = FamilyCapabilities {
+    max_texture_size: 8192,
+    max_3d_texture_size: 2048,
+    max_texture_array_length: 2048,
+    max_buffer_arguments: 31,
+    max_texture_arguments: 31,
+    max_sampler_arguments: 16,
+    max_threads_per_threadgroup: 512,
+    max_threadgroup_memory_length: 16352,
+    max_color_render_targets: 4,
+    max_vertex_attributes: 31,
+    supports_astc: false,
+    supports_astc_hdr: false,
+    supports_etc2: true,
+    supports_pvrtc: true,
+    supports_bc: false,
+    supports_memoryless: true,
+    supports_cube_arrays: false,
+};
+
+const APPLE2: FamilyCapabilities = FamilyCapabilities {
+    max_color_render_targets: 8,
+    supports_astc: true,
+    ..APPLE1
+};
+
+const APPLE3: FamilyCapabilities = FamilyCapabilities {
+    max_texture_size: 16384,
+    max_threadgroup_memory_length: 16384,
+    ..APPLE2
+};
+
+const APPLE4: FamilyCapabilities = FamilyCapabilities {
+    max_threads_per_threadgroup: 1024,
+    max_threadgroup_memory_length: 32768,
+    supports_cube_arrays: true,
+    ..APPLE3
+};
+
+const APPLE6: FamilyCapabilities = FamilyCapabilities {
+    max_texture_arguments: 128,
+    supports_astc_hdr: true,
+    ..APPLE4
+};
+
+const MAC2: FamilyCapabilities = FamilyCapabilities {
+    max_texture_size: 16384,
+    max_3d_texture_size: 2048,
+    max_texture_array_length: 2048,
+    max_buffer_arguments: 31,
+    max_texture_arguments: 128,
+    max_sampler_arguments: 16,
+    max_threads_per_threadgroup: 1024,
+    max_threadgroup_memory_length: 32768,
+    max_color_render_targets: 8,
+    max_vertex_attributes: 31,
+    supports_astc: false,
+    supports_astc_hdr: false,
+    supports_etc2: false,
+    supports_pvrtc: false,
+    supports_bc: true,
+    supports_memoryless: false,
+    supports_cube_arrays: true,
+};
+
+impl FamilyCapabilities {
+    /// The table for `family`. `None` for the `Common`, `Metal` and
+    /// deprecated families, which Apple documents as feature sets shared
+    /// across the others rather than with limits of their own.
+    pub const fn for_family(family: MTLGPUFamily) -> Option<Self> {
+        let capabilities = match family {
+            MTLGPUFamily::Apple1 => APPLE1,
+            MTLGPUFamily::Apple2 => APPLE2,
+            MTLGPUFamily::Apple3 => APPLE3,
+            MTLGPUFamily::Apple4 | MTLGPUFamily::Apple5 => APPLE4,
+            MTLGPUFamily::Apple6 | MTLGPUFamily::Apple7 | MTLGPUFamily::Apple8 | MTLGPUFamily::Apple9 => APPLE6,
+            MTLGPUFamily::Mac2 => MAC2,
+            _ => return None,
+        };
+        Some(capabilities)
+    }
+
+    /// The larger of each limit, and the features either supports.
+    pub fn union(
+        self,
+        other: Self,
+    ) -> Self {
+        Self {
+            max_texture_size: self.max_texture_size.max(other.max_texture_size),
+            max_3d_texture_size: self.max_3d_texture_size.max(other.max_3d_texture_size),
+            max_texture_array_length: self.max_texture_array_length.max(other.max_texture_array_length),
+            max_buffer_arguments: self.max_buffer_arguments.max(other.max_buffer_arguments),
+            max_texture_arguments: self.max_texture_arguments.max(other.max_texture_arguments),
+            max_sampler_arguments: self.max_sampler_arguments.max(other.max_sampler_arguments),
+            max_threads_per_threadgroup: self.max_threads_per_threadgroup.max(other.max_threads_per_threadgroup),
+            max_threadgroup_memory_length: self.max_threadgroup_memory_length.max(other.max_threadgroup_memory_length),
+            max_color_render_targets: self.max_color_render_targets.max(other.max_color_render_targets),
+            max_vertex_attributes: self.max_vertex_attributes.max(other.max_vertex_attributes),
+            supports_astc: self.supports_astc || other.supports_astc,
+            supports_astc_hdr: self.supports_astc_hdr || other.supports_astc_hdr,
+            supports_etc2: self.supports_etc2 || other.supports_etc2,
+            supports_pvrtc: self.supports_pvrtc || other.supports_pvrtc,
+            supports_bc: self.supports_bc || other.supports_bc,
+            supports_memoryless: self.supports_memoryless || other.supports_memoryless,
+            supports_cube_arrays: self.supports_cube_arrays || other.supports_cube_arrays,
+        }
+    }
+}
+
+/// Whether a device with `supports_bc` BC support, in the families
+/// `supports_family` reports, can sample `format`. Uncompressed formats are
+/// always supported.
+pub(crate) fn compressed_format_supported(
+    supports_bc: bool,
+    supports_family: impl Fn(MTLGPUFamily) -> bool,
+    format: MTLPixelFormat,
+) -> bool {
+    if format.is_bc() {
+        supports_bc
+    } else if format.is_astc_hdr() {
+        supports_family(MTLGPUFamily::Apple6)
+    } else if format.is_astc() {
+        supports_family(MTLGPUFamily::Apple2)
+    } else if format.is_pvrtc() || format.is_eac_etc2() {
+        supports_family(MTLGPUFamily::Apple1)
+    } else {
+        true
+    }
+}
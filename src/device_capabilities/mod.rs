@@ -0,0 +1,10 @@
+mod capabilities;
+mod families;
+mod pixel_format_capabilities;
+mod snapshot;
+
+pub use capabilities::DeviceCapabilities;
+pub use families::FamilyCapabilities;
+pub(crate) use families::compressed_format_supported;
+pub use pixel_format_capabilities::PixelFormatCapabilities;
+pub use snapshot::DeviceCapabilitiesError;
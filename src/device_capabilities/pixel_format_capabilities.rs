@@ -0,0 +1,303 @@
+use bitflags::bitflags;
+
+use crate::{MTLGPUFamily, MTLPixelFormat};
+
+bitflags! {
+    /// What a pixel format can be used for, from the pixel format
+    /// capabilities in Apple's Metal feature set tables.
+    #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+    pub struct PixelFormatCapabilities: u32 {
+        /// Sampled with linear filtering.
+        const FILTER = 1 << 0;
+        /// Written by shaders.
+        const WRITE = 1 << 1;
+        /// Used as a color render target.
+        const COLOR = 1 << 2;
+        /// Blended as a color render target.
+        const BLEND = 1 << 3;
+        /// Used for multisample textures.
+        const MSAA = 1 << 4;
+        /// Used as a multisample resolve target.
+        const RESOLVE = 1 << 5;
+        /// Used with `MTLTextureUsage::SHADER_ATOMIC`.
+        const ATOMIC = 1 << 6;
+        /// Read and written by the same shader function.
+        const READ_WRITE = 1 << 7;
+    }
+}
+
+type Caps = PixelFormatCapabilities;
+
+/// What the tables call "All": every capability but atomics and read-write
+/// access, which are listed separately.
+const ALL: Caps =
+    Caps::FILTER.union(Caps::WRITE).union(Caps::COLOR).union(Caps::BLEND).union(Caps::MSAA).union(Caps::RESOLVE);
+
+/// Unsigned and signed normalized formats Apple GPUs can't resolve to.
+const ALL_BUT_RESOLVE: Caps = ALL.difference(Caps::RESOLVE);
+
+/// sRGB formats Apple3 and earlier GPUs can't write.
+const ALL_BUT_WRITE: Caps = ALL.difference(Caps::WRITE);
+
+/// Integer formats.
+const INTEGER: Caps = Caps::WRITE.union(Caps::COLOR).union(Caps::MSAA);
+
+/// The columns of the pixel format tables.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Column {
+    /// An Apple GPU family, by number.
+    Apple(u8),
+    Mac2,
+}
+
+impl Column {
+    const fn for_family(family: MTLGPUFamily) -> Option<Self> {
+        let column = match family {
+            MTLGPUFamily::Apple2 => Self::Apple(2),
+            MTLGPUFamily::Apple3 => Self::Apple(3),
+            MTLGPUFamily::Apple4 => Self::Apple(4),
+            MTLGPUFamily::Apple5 => Self::Apple(5),
+            MTLGPUFamily::Apple6 => Self::Apple(6),
+            MTLGPUFamily::Apple7 => Self::Apple(7),
+            MTLGPUFamily::Apple8 => Self::Apple(8),
+            MTLGPUFamily::Apple9 => Self::Apple(9),
+            MTLGPUFamily::Mac2 => Self::Mac2,
+            _ => return None,
+        };
+        Some(column)
+    }
+
+    /// Whether the column is an Apple family of at least `apple`.
+    const fn apple(
+        self,
+        apple: u8,
+    ) -> bool {
+        match self {
+            Self::Apple(family) => family >= apple,
+            Self::Mac2 => false,
+        }
+    }
+
+    /// `apple_caps` for Apple families, `mac_caps` for `Mac2`.
+    const fn pick(
+        self,
+        apple_caps: Caps,
+        mac_caps: Caps,
+    ) -> Caps {
+        match self {
+            Self::Apple(_) => apple_caps,
+            Self::Mac2 => mac_caps,
+        }
+    }
+}
+
+impl MTLPixelFormat {
+    /// What textures of this format can be used for on GPUs of `family`.
+    ///
+    /// Empty for families the tables have no column for: `Apple1`, `Mac1`,
+    /// and the `Common`, `MacCatalyst` and `Metal` families. Filtering and
+    /// multisampling 32-bit float formats, BC formats on Apple GPUs and
+    /// `Depth24Unorm_Stencil8` also depend on the device; see
+    /// [`DeviceCapabilities::pixel_format_capabilities`](crate::DeviceCapabilities::pixel_format_capabilities).
+    pub fn capabilities(
+        self,
+        family: MTLGPUFamily,
+    ) -> PixelFormatCapabilities {
+        use MTLPixelFormat as F;
+
+        let Some(column) = Column::for_family(family) else {
+            return Caps::empty();
+        };
+        let caps = match self {
+            F::Invalid | F::Unspecialized => Caps::empty(),
+            F::A8Unorm | F::GBGR422 | F::BGRG422 => Caps::FILTER,
+            F::R8Unorm
+            | F::R8Snorm
+            | F::RG8Unorm
+            | F::RG8Snorm
+            | F::R16Float
+            | F::RG16Float
+            | F::RGBA16Float
+            | F::RGBA8Unorm
+            | F::RGBA8Snorm
+            | F::BGRA8Unorm
+            | F::RGB10A2Unorm
+            | F::BGR10A2Unorm
+            | F::RG11B10Float => ALL,
+            F::R8UnormSrgb | F::RG8UnormSrgb => match column {
+                Column::Mac2 => Caps::empty(),
+                _ if column.apple(4) => ALL,
+                _ => ALL_BUT_WRITE,
+            },
+            F::RGBA8UnormSrgb | F::BGRA8UnormSrgb => {
+                if column.apple(4) {
+                    ALL
+                } else {
+                    ALL_BUT_WRITE
+                }
+            },
+            F::R16Unorm | F::R16Snorm | F::RG16Unorm | F::RG16Snorm | F::RGBA16Unorm | F::RGBA16Snorm => {
+                column.pick(ALL_BUT_RESOLVE, ALL)
+            },
+            F::R8Uint
+            | F::R8Sint
+            | F::R16Uint
+            | F::R16Sint
+            | F::RG8Uint
+            | F::RG8Sint
+            | F::R32Uint
+            | F::R32Sint
+            | F::RG16Uint
+            | F::RG16Sint
+            | F::RGBA8Uint
+            | F::RGBA8Sint
+            | F::RGB10A2Uint
+            | F::RGBA16Uint
+            | F::RGBA16Sint => INTEGER,
+            F::RG32Uint | F::RG32Sint | F::RGBA32Uint | F::RGBA32Sint => {
+                column.pick(Caps::WRITE.union(Caps::COLOR), INTEGER)
+            },
+            F::B5G6R5Unorm | F::A1BGR5Unorm | F::ABGR4Unorm | F::BGR5A1Unorm => {
+                column.pick(ALL_BUT_WRITE, Caps::empty())
+            },
+            F::RGB9E5Float => column.pick(ALL, Caps::FILTER),
+            F::R32Float => match column {
+                Column::Mac2 => ALL,
+                _ if column.apple(7) => ALL,
+                _ => Caps::WRITE.union(Caps::COLOR).union(Caps::MSAA).union(Caps::BLEND),
+            },
+            F::RG32Float | F::RGBA32Float => match column {
+                Column::Mac2 => ALL,
+                _ if column.apple(7) => ALL,
+                _ => Caps::WRITE.union(Caps::COLOR).union(Caps::BLEND),
+            },
+            F::BGRA10_XR if column.apple(3) => ALL,
+            F::BGRA10_XR_sRGB if column.apple(3) => ALL_BUT_WRITE,
+            F::Depth16Unorm | F::Depth32Float | F::Depth32Float_Stencil8 => {
+                column.pick(Caps::MSAA.union(Caps::RESOLVE), Caps::FILTER.union(Caps::MSAA).union(Caps::RESOLVE))
+            },
+            F::Stencil8 | F::X32_Stencil8 => Caps::MSAA,
+            F::Depth24Unorm_Stencil8 => column.pick(Caps::empty(), Caps::FILTER.union(Caps::MSAA).union(Caps::RESOLVE)),
+            F::X24_Stencil8 => column.pick(Caps::empty(), Caps::MSAA),
+            _ if self.is_bc() => column.pick(Caps::empty(), Caps::FILTER),
+            _ if self.is_astc_hdr() => {
+                if column.apple(6) {
+                    Caps::FILTER
+                } else {
+                    Caps::empty()
+                }
+            },
+            _ if self.is_compressed() => column.pick(Caps::FILTER, Caps::empty()),
+            _ => Caps::empty(),
+        };
+        caps | self.access_capabilities(column)
+    }
+
+    /// Atomic and read-write access, which the tables list by family rather
+    /// than by format.
+    fn access_capabilities(
+        self,
+        column: Column,
+    ) -> PixelFormatCapabilities {
+        use MTLPixelFormat as F;
+
+        let atomic = column == Column::Mac2 || column.apple(6);
+        let tier1 = column == Column::Mac2 || column.apple(3);
+        let tier2 = column == Column::Mac2 || column.apple(4);
+        match self {
+            F::R32Uint | F::R32Sint if atomic => Caps::ATOMIC | Caps::READ_WRITE,
+            F::R32Uint | F::R32Sint | F::R32Float if tier1 => Caps::READ_WRITE,
+            F::RGBA32Float
+            | F::RGBA32Uint
+            | F::RGBA32Sint
+            | F::RGBA16Float
+            | F::RGBA16Uint
+            | F::RGBA16Sint
+            | F::RGBA8Unorm
+            | F::RGBA8Uint
+            | F::RGBA8Sint
+            | F::R16Float
+            | F::R16Uint
+            | F::R16Sint
+            | F::R8Unorm
+            | F::R8Uint
+            | F::R8Sint
+                if tier2 =>
+            {
+                Caps::READ_WRITE
+            },
+            _ => Caps::empty(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renderable_formats() {
+        for family in [MTLGPUFamily::Apple2, MTLGPUFamily::Apple7, MTLGPUFamily::Mac2] {
+            assert!(MTLPixelFormat::RGBA8Unorm.capabilities(family).contains(ALL));
+            assert!(MTLPixelFormat::RGBA16Float.capabilities(family).contains(ALL));
+            assert_eq!(MTLPixelFormat::RGBA8Uint.capabilities(family) & ALL, INTEGER);
+        }
+        assert_eq!(MTLPixelFormat::R16Unorm.capabilities(MTLGPUFamily::Apple9), ALL_BUT_RESOLVE);
+        assert_eq!(MTLPixelFormat::R16Unorm.capabilities(MTLGPUFamily::Mac2), ALL);
+    }
+
+    #[test]
+    fn srgb_writes() {
+        let caps = MTLPixelFormat::RGBA8UnormSrgb.capabilities(MTLGPUFamily::Apple3);
+        assert_eq!(caps, ALL_BUT_WRITE);
+        assert!(MTLPixelFormat::RGBA8UnormSrgb.capabilities(MTLGPUFamily::Apple4).contains(Caps::WRITE));
+        assert!(!MTLPixelFormat::BGRA8UnormSrgb.capabilities(MTLGPUFamily::Mac2).contains(Caps::WRITE));
+        assert!(MTLPixelFormat::R8UnormSrgb.capabilities(MTLGPUFamily::Mac2).is_empty());
+    }
+
+    #[test]
+    fn float32_formats() {
+        let caps = MTLPixelFormat::RGBA32Float.capabilities(MTLGPUFamily::Apple6);
+        assert_eq!(caps, Caps::WRITE | Caps::COLOR | Caps::BLEND | Caps::READ_WRITE);
+        assert!(MTLPixelFormat::RGBA32Float.capabilities(MTLGPUFamily::Apple7).contains(Caps::FILTER | Caps::MSAA));
+        assert!(!MTLPixelFormat::R32Float.capabilities(MTLGPUFamily::Apple2).contains(Caps::FILTER));
+        assert!(MTLPixelFormat::R32Float.capabilities(MTLGPUFamily::Apple2).contains(Caps::MSAA | Caps::BLEND));
+    }
+
+    #[test]
+    fn compressed_formats() {
+        assert_eq!(MTLPixelFormat::BC7_RGBAUnorm.capabilities(MTLGPUFamily::Mac2), Caps::FILTER);
+        assert!(MTLPixelFormat::BC7_RGBAUnorm.capabilities(MTLGPUFamily::Apple9).is_empty());
+        assert_eq!(MTLPixelFormat::ASTC_4x4_LDR.capabilities(MTLGPUFamily::Apple2), Caps::FILTER);
+        assert!(MTLPixelFormat::ASTC_4x4_HDR.capabilities(MTLGPUFamily::Apple5).is_empty());
+        assert_eq!(MTLPixelFormat::ASTC_4x4_HDR.capabilities(MTLGPUFamily::Apple6), Caps::FILTER);
+        assert_eq!(MTLPixelFormat::ETC2_RGB8.capabilities(MTLGPUFamily::Apple3), Caps::FILTER);
+        assert!(MTLPixelFormat::ETC2_RGB8.capabilities(MTLGPUFamily::Mac2).is_empty());
+    }
+
+    #[test]
+    fn depth_stencil_formats() {
+        let caps = MTLPixelFormat::Depth32Float.capabilities(MTLGPUFamily::Apple4);
+        assert_eq!(caps, Caps::MSAA | Caps::RESOLVE);
+        assert!(MTLPixelFormat::Depth32Float.capabilities(MTLGPUFamily::Mac2).contains(Caps::FILTER));
+        assert!(MTLPixelFormat::Depth24Unorm_Stencil8.capabilities(MTLGPUFamily::Apple9).is_empty());
+        assert_eq!(MTLPixelFormat::Stencil8.capabilities(MTLGPUFamily::Apple2), Caps::MSAA);
+    }
+
+    #[test]
+    fn access() {
+        assert!(MTLPixelFormat::R32Uint.capabilities(MTLGPUFamily::Apple6).contains(Caps::ATOMIC));
+        assert!(!MTLPixelFormat::R32Uint.capabilities(MTLGPUFamily::Apple5).contains(Caps::ATOMIC));
+        assert!(MTLPixelFormat::R32Float.capabilities(MTLGPUFamily::Apple3).contains(Caps::READ_WRITE));
+        assert!(!MTLPixelFormat::RGBA8Unorm.capabilities(MTLGPUFamily::Apple3).contains(Caps::READ_WRITE));
+        assert!(MTLPixelFormat::RGBA8Unorm.capabilities(MTLGPUFamily::Mac2).contains(Caps::READ_WRITE));
+        assert!(!MTLPixelFormat::RGBA8Snorm.capabilities(MTLGPUFamily::Mac2).contains(Caps::READ_WRITE));
+    }
+
+    #[test]
+    fn families_without_tables() {
+        for family in [MTLGPUFamily::Apple1, MTLGPUFamily::Common1, MTLGPUFamily::Metal3] {
+            assert!(MTLPixelFormat::RGBA8Unorm.capabilities(family).is_empty());
+        }
+    }
+}
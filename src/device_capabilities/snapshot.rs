@@ -0,0 +1,464 @@
+use std::{
+    fmt::{self, Debug},
+    io,
+};
+
+use super::capabilities::{FAMILIES, SPARSE_PAGE_SIZES};
+use crate::{
+    DeviceCapabilities, MTLArgumentBuffersTier, MTLDeviceLocation, MTLReadWriteTextureTier, MTLSize,
+    json::{JsonValue, write_json_string},
+};
+
+/// The snapshot format version written by [`DeviceCapabilities::write_json`].
+const VERSION: u64 = 1;
+
+const LOCATIONS: [MTLDeviceLocation; 4] =
+    [MTLDeviceLocation::BuiltIn, MTLDeviceLocation::Slot, MTLDeviceLocation::External, MTLDeviceLocation::Unspecified];
+
+const READ_WRITE_TEXTURE_TIERS: [MTLReadWriteTextureTier; 3] =
+    [MTLReadWriteTextureTier::None, MTLReadWriteTextureTier::Tier1, MTLReadWriteTextureTier::Tier2];
+
+const ARGUMENT_BUFFERS_TIERS: [MTLArgumentBuffersTier; 2] =
+    [MTLArgumentBuffersTier::Tier1, MTLArgumentBuffersTier::Tier2];
+
+#[derive(Debug)]
+pub enum DeviceCapabilitiesError {
+    /// The snapshot isn't valid JSON.
+    Json(String),
+    /// A field is missing or has the wrong type or an unknown value.
+    InvalidField(String),
+    UnsupportedVersion(u64),
+}
+
+impl fmt::Display for DeviceCapabilitiesError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::Json(message) => write!(f, "invalid device capability snapshot: {message}"),
+            Self::InvalidField(name) => write!(f, "device capability snapshot has a missing or invalid {name:?}"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported device capability snapshot version {version}")
+            },
+        }
+    }
+}
+
+impl std::error::Error for DeviceCapabilitiesError {}
+
+impl DeviceCapabilities {
+    /// Writes the snapshot as JSON. Enum values are written by name, so
+    /// snapshots stay readable when Metal renumbers them.
+    pub fn write_json<W: io::Write>(
+        &self,
+        mut writer: W,
+    ) -> io::Result<()> {
+        write!(writer, "{{\n  \"version\": {VERSION}")?;
+        key(&mut writer, "name")?;
+        write_json_string(&mut writer, &self.name)?;
+        key(&mut writer, "architecture")?;
+        write_json_string(&mut writer, &self.architecture)?;
+        key(&mut writer, "location")?;
+        write_variant(&mut writer, self.location)?;
+        key(&mut writer, "location_number")?;
+        write!(writer, "{}", self.location_number)?;
+        key(&mut writer, "is_low_power")?;
+        write!(writer, "{}", self.is_low_power)?;
+        key(&mut writer, "is_headless")?;
+        write!(writer, "{}", self.is_headless)?;
+        key(&mut writer, "is_removable")?;
+        write!(writer, "{}", self.is_removable)?;
+        key(&mut writer, "has_unified_memory")?;
+        write!(writer, "{}", self.has_unified_memory)?;
+        key(&mut writer, "recommended_max_working_set_size")?;
+        write!(writer, "{}", self.recommended_max_working_set_size)?;
+        key(&mut writer, "max_transfer_rate")?;
+        write!(writer, "{}", self.max_transfer_rate)?;
+        key(&mut writer, "peer_group_id")?;
+        write!(writer, "{}", self.peer_group_id)?;
+        key(&mut writer, "peer_index")?;
+        write!(writer, "{}", self.peer_index)?;
+        key(&mut writer, "peer_count")?;
+        write!(writer, "{}", self.peer_count)?;
+
+        key(&mut writer, "families")?;
+        writer.write_all(b"[")?;
+        for (index, &family) in self.families.iter().enumerate() {
+            if index > 0 {
+                writer.write_all(b", ")?;
+            }
+            write_variant(&mut writer, family)?;
+        }
+        writer.write_all(b"]")?;
+        key(&mut writer, "texture_sample_counts")?;
+        let sample_counts: Vec<String> = self.texture_sample_counts.iter().map(usize::to_string).collect();
+        write!(writer, "[{}]", sample_counts.join(", "))?;
+
+        key(&mut writer, "read_write_texture_tier")?;
+        write_variant(&mut writer, self.read_write_texture_tier)?;
+        key(&mut writer, "argument_buffers_tier")?;
+        write_variant(&mut writer, self.argument_buffers_tier)?;
+        key(&mut writer, "max_threads_per_threadgroup")?;
+        let MTLSize {
+            width,
+            height,
+            depth,
+        } = self.max_threads_per_threadgroup;
+        write!(writer, "{{\"width\": {width}, \"height\": {height}, \"depth\": {depth}}}")?;
+        key(&mut writer, "max_threadgroup_memory_length")?;
+        write!(writer, "{}", self.max_threadgroup_memory_length)?;
+        key(&mut writer, "max_buffer_length")?;
+        write!(writer, "{}", self.max_buffer_length)?;
+        key(&mut writer, "max_argument_buffer_sampler_count")?;
+        write!(writer, "{}", self.max_argument_buffer_sampler_count)?;
+        key(&mut writer, "max_vertex_amplification_count")?;
+        write!(writer, "{}", self.max_vertex_amplification_count)?;
+        key(&mut writer, "max_rasterization_rate_map_layer_count")?;
+        write!(writer, "{}", self.max_rasterization_rate_map_layer_count)?;
+        key(&mut writer, "sparse_tile_size_in_bytes")?;
+        write!(writer, "{}", self.sparse_tile_size_in_bytes)?;
+
+        key(&mut writer, "placement_sparse_tile_sizes_in_bytes")?;
+        writer.write_all(b"{")?;
+        for (index, &(page_size, bytes)) in self.placement_sparse_tile_sizes_in_bytes.iter().enumerate() {
+            if index > 0 {
+                writer.write_all(b", ")?;
+            }
+            write_variant(&mut writer, page_size)?;
+            write!(writer, ": {bytes}")?;
+        }
+        writer.write_all(b"}")?;
+
+        for (name, supported) in self.supports() {
+            key(&mut writer, name)?;
+            write!(writer, "{supported}")?;
+        }
+        writer.write_all(b"\n}\n")
+    }
+
+    /// [`write_json`](Self::write_json) into a string.
+    pub fn to_json(&self) -> String {
+        let mut bytes = Vec::new();
+        self.write_json(&mut bytes).expect("writing to a Vec cannot fail");
+        String::from_utf8(bytes).expect("the snapshot writer only emits UTF-8")
+    }
+
+    /// Reads a snapshot written by [`write_json`](Self::write_json).
+    pub fn from_json(text: &str) -> Result<Self, DeviceCapabilitiesError> {
+        let root = JsonValue::parse(text).map_err(DeviceCapabilitiesError::Json)?;
+        let snapshot = Snapshot {
+            root: &root,
+        };
+        let version = snapshot.u64("version")?;
+        if version != VERSION {
+            return Err(DeviceCapabilitiesError::UnsupportedVersion(version));
+        }
+
+        let families = snapshot.array("families")?;
+        let families = families.iter().map(|family| variant(family, &FAMILIES));
+        let families = families.collect::<Option<_>>().ok_or_else(|| invalid_field("families"))?;
+        let sample_counts = snapshot.array("texture_sample_counts")?;
+        let sample_counts = sample_counts.iter().map(|count| count.as_u64().and_then(|count| count.try_into().ok()));
+        let texture_sample_counts =
+            sample_counts.collect::<Option<_>>().ok_or_else(|| invalid_field("texture_sample_counts"))?;
+
+        let threads = snapshot.get("max_threads_per_threadgroup")?;
+        let dimension = |name| threads.get(name).and_then(JsonValue::as_u64).and_then(|value| value.try_into().ok());
+        let max_threads_per_threadgroup = match (dimension("width"), dimension("height"), dimension("depth")) {
+            (Some(width), Some(height), Some(depth)) => MTLSize {
+                width,
+                height,
+                depth,
+            },
+            _ => return Err(invalid_field("max_threads_per_threadgroup")),
+        };
+
+        let tile_sizes = snapshot.get("placement_sparse_tile_sizes_in_bytes")?;
+        let tile_sizes = tile_sizes.as_object().ok_or_else(|| invalid_field("placement_sparse_tile_sizes_in_bytes"))?;
+        let tile_sizes = tile_sizes.iter().map(|(page_size, bytes)| {
+            let page_size = SPARSE_PAGE_SIZES.into_iter().find(|size| format!("{size:?}") == *page_size)?;
+            Some((page_size, bytes.as_u64()?.try_into().ok()?))
+        });
+        let placement_sparse_tile_sizes_in_bytes =
+            tile_sizes.collect::<Option<_>>().ok_or_else(|| invalid_field("placement_sparse_tile_sizes_in_bytes"))?;
+
+        Ok(Self {
+            name: snapshot.string("name")?,
+            architecture: snapshot.string("architecture")?,
+            location: snapshot.variant("location", &LOCATIONS)?,
+            location_number: snapshot.usize("location_number")?,
+            is_low_power: snapshot.bool("is_low_power")?,
+            is_headless: snapshot.bool("is_headless")?,
+            is_removable: snapshot.bool("is_removable")?,
+            has_unified_memory: snapshot.bool("has_unified_memory")?,
+            recommended_max_working_set_size: snapshot.u64("recommended_max_working_set_size")?,
+            max_transfer_rate: snapshot.u64("max_transfer_rate")?,
+            peer_group_id: snapshot.u64("peer_group_id")?,
+            peer_index: snapshot.u32("peer_index")?,
+            peer_count: snapshot.u32("peer_count")?,
+            families,
+            texture_sample_counts,
+            read_write_texture_tier: snapshot.variant("read_write_texture_tier", &READ_WRITE_TEXTURE_TIERS)?,
+            argument_buffers_tier: snapshot.variant("argument_buffers_tier", &ARGUMENT_BUFFERS_TIERS)?,
+            max_threads_per_threadgroup,
+            max_threadgroup_memory_length: snapshot.usize("max_threadgroup_memory_length")?,
+            max_buffer_length: snapshot.usize("max_buffer_length")?,
+            max_argument_buffer_sampler_count: snapshot.usize("max_argument_buffer_sampler_count")?,
+            max_vertex_amplification_count: snapshot.usize("max_vertex_amplification_count")?,
+            max_rasterization_rate_map_layer_count: snapshot.usize("max_rasterization_rate_map_layer_count")?,
+            sparse_tile_size_in_bytes: snapshot.usize("sparse_tile_size_in_bytes")?,
+            placement_sparse_tile_sizes_in_bytes,
+            supports_bc_texture_compression: snapshot.bool("supports_bc_texture_compression")?,
+            supports_32bit_float_filtering: snapshot.bool("supports_32bit_float_filtering")?,
+            supports_32bit_msaa: snapshot.bool("supports_32bit_msaa")?,
+            supports_depth24_stencil8: snapshot.bool("supports_depth24_stencil8")?,
+            supports_query_texture_lod: snapshot.bool("supports_query_texture_lod")?,
+            supports_pull_model_interpolation: snapshot.bool("supports_pull_model_interpolation")?,
+            supports_shader_barycentric_coordinates: snapshot.bool("supports_shader_barycentric_coordinates")?,
+            supports_programmable_sample_positions: snapshot.bool("supports_programmable_sample_positions")?,
+            supports_raster_order_groups: snapshot.bool("supports_raster_order_groups")?,
+            supports_dynamic_libraries: snapshot.bool("supports_dynamic_libraries")?,
+            supports_render_dynamic_libraries: snapshot.bool("supports_render_dynamic_libraries")?,
+            supports_function_pointers: snapshot.bool("supports_function_pointers")?,
+            supports_function_pointers_from_render: snapshot.bool("supports_function_pointers_from_render")?,
+            supports_raytracing: snapshot.bool("supports_raytracing")?,
+            supports_raytracing_from_render: snapshot.bool("supports_raytracing_from_render")?,
+            supports_primitive_motion_blur: snapshot.bool("supports_primitive_motion_blur")?,
+            supports_placement_sparse: snapshot.bool("supports_placement_sparse")?,
+        })
+    }
+
+    /// The `supports_*` fields by name, in declaration order.
+    fn supports(&self) -> [(&'static str, bool); 17] {
+        [
+            ("supports_bc_texture_compression", self.supports_bc_texture_compression),
+            ("supports_32bit_float_filtering", self.supports_32bit_float_filtering),
+            ("supports_32bit_msaa", self.supports_32bit_msaa),
+            ("supports_depth24_stencil8", self.supports_depth24_stencil8),
+            ("supports_query_texture_lod", self.supports_query_texture_lod),
+            ("supports_pull_model_interpolation", self.supports_pull_model_interpolation),
+            ("supports_shader_barycentric_coordinates", self.supports_shader_barycentric_coordinates),
+            ("supports_programmable_sample_positions", self.supports_programmable_sample_positions),
+            ("supports_raster_order_groups", self.supports_raster_order_groups),
+            ("supports_dynamic_libraries", self.supports_dynamic_libraries),
+            ("supports_render_dynamic_libraries", self.supports_render_dynamic_libraries),
+            ("supports_function_pointers", self.supports_function_pointers),
+            ("supports_function_pointers_from_render", self.supports_function_pointers_from_render),
+            ("supports_raytracing", self.supports_raytracing),
+            ("supports_raytracing_from_render", self.supports_raytracing_from_render),
+            ("supports_primitive_motion_blur", self.supports_primitive_motion_blur),
+            ("supports_placement_sparse", self.supports_placement_sparse),
+        ]
+    }
+}
+
+/// Starts a new member of the top-level object.
+fn key(
+    writer: &mut impl io::Write,
+    name: &str,
+) -> io::Result<()> {
+    writer.write_all(b",\n  ")?;
+    write_json_string(writer, name)?;
+    writer.write_all(b": ")
+}
+
+fn write_variant(
+    writer: &mut impl io::Write,
+    value: impl Debug,
+) -> io::Result<()> {
+    write_json_string(writer, &format!("{value:?}"))
+}
+
+/// The variant of `variants` named by `value`.
+fn variant<T: Copy + Debug>(
+    value: &JsonValue,
+    variants: &[T],
+) -> Option<T> {
+    let name = value.as_str()?;
+    variants.iter().copied().find(|variant| format!("{variant:?}") == name)
+}
+
+fn invalid_field(name: &str) -> DeviceCapabilitiesError {
+    DeviceCapabilitiesError::InvalidField(name.to_owned())
+}
+
+/// Typed access to the members of a snapshot.
+struct Snapshot<'a> {
+    root: &'a JsonValue,
+}
+
+impl<'a> Snapshot<'a> {
+    fn get(
+        &self,
+        name: &str,
+    ) -> Result<&'a JsonValue, DeviceCapabilitiesError> {
+        self.root.get(name).ok_or_else(|| invalid_field(name))
+    }
+
+    fn string(
+        &self,
+        name: &str,
+    ) -> Result<String, DeviceCapabilitiesError> {
+        self.get(name)?.as_str().map(str::to_owned).ok_or_else(|| invalid_field(name))
+    }
+
+    fn bool(
+        &self,
+        name: &str,
+    ) -> Result<bool, DeviceCapabilitiesError> {
+        self.get(name)?.as_bool().ok_or_else(|| invalid_field(name))
+    }
+
+    fn u64(
+        &self,
+        name: &str,
+    ) -> Result<u64, DeviceCapabilitiesError> {
+        self.get(name)?.as_u64().ok_or_else(|| invalid_field(name))
+    }
+
+    fn u32(
+        &self,
+        name: &str,
+    ) -> Result<u32, DeviceCapabilitiesError> {
+        self.u64(name)?.try_into().map_err(|_| invalid_field(name))
+    }
+
+    fn usize(
+        &self,
+        name: &str,
+    ) -> Result<usize, DeviceCapabilitiesError> {
+        self.u64(name)?.try_into().map_err(|_| invalid_field(name))
+    }
+
+    fn array(
+        &self,
+        name: &str,
+    ) -> Result<&'a [JsonValue], DeviceCapabilitiesError> {
+        self.get(name)?.as_array().ok_or_else(|| invalid_field(name))
+    }
+
+    fn variant<T: Copy + Debug>(
+        &self,
+        name: &str,
+        variants: &[T],
+    ) -> Result<T, DeviceCapabilitiesError> {
+        variant(self.get(name)?, variants).ok_or_else(|| invalid_field(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MTLGPUFamily, MTLSparsePageSize};
+
+    const SNAPSHOT: &str =
+        include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/device_capabilities/snapshot.json"));
+
+    /// The device in `snapshot.json`.
+    fn fixture_capabilities() -> DeviceCapabilities {
+        DeviceCapabilities {
+            name: "Test \"GPU\"\n\u{1}é".to_owned(),
+            architecture: "applegpu_g13g".to_owned(),
+            location: MTLDeviceLocation::BuiltIn,
+            location_number: 0,
+            is_low_power: false,
+            is_headless: false,
+            is_removable: false,
+            has_unified_memory: true,
+            recommended_max_working_set_size: 11_453_251_584,
+            max_transfer_rate: 0,
+            peer_group_id: u64::MAX,
+            peer_index: 0,
+            peer_count: 0,
+            families: vec![
+                MTLGPUFamily::Apple1,
+                MTLGPUFamily::Apple7,
+                MTLGPUFamily::Mac2,
+                MTLGPUFamily::Common3,
+                MTLGPUFamily::Metal3,
+            ],
+            texture_sample_counts: vec![1, 2, 4],
+            read_write_texture_tier: MTLReadWriteTextureTier::Tier2,
+            argument_buffers_tier: MTLArgumentBuffersTier::Tier2,
+            max_threads_per_threadgroup: MTLSize {
+                width: 1024,
+                height: 1024,
+                depth: 1024,
+            },
+            max_threadgroup_memory_length: 32768,
+            max_buffer_length: 9_663_676_416,
+            max_argument_buffer_sampler_count: 1024,
+            max_vertex_amplification_count: 8,
+            max_rasterization_rate_map_layer_count: 2,
+            sparse_tile_size_in_bytes: 16384,
+            placement_sparse_tile_sizes_in_bytes: vec![
+                (MTLSparsePageSize::KB16, 16384),
+                (MTLSparsePageSize::KB64, 65536),
+            ],
+            supports_bc_texture_compression: true,
+            supports_32bit_float_filtering: true,
+            supports_32bit_msaa: true,
+            supports_depth24_stencil8: false,
+            supports_query_texture_lod: true,
+            supports_pull_model_interpolation: true,
+            supports_shader_barycentric_coordinates: true,
+            supports_programmable_sample_positions: true,
+            supports_raster_order_groups: true,
+            supports_dynamic_libraries: true,
+            supports_render_dynamic_libraries: true,
+            supports_function_pointers: true,
+            supports_function_pointers_from_render: true,
+            supports_raytracing: true,
+            supports_raytracing_from_render: true,
+            supports_primitive_motion_blur: true,
+            supports_placement_sparse: true,
+        }
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let capabilities = fixture_capabilities();
+        assert_eq!(capabilities.to_json(), SNAPSHOT);
+        assert_eq!(DeviceCapabilities::from_json(SNAPSHOT).unwrap(), capabilities);
+        let mut written = Vec::new();
+        capabilities.write_json(&mut written).unwrap();
+        assert_eq!(written, SNAPSHOT.as_bytes());
+    }
+
+    #[test]
+    fn reads_compact_snapshots() {
+        let compact = SNAPSHOT.split('\n').map(str::trim).collect::<String>();
+        assert_eq!(DeviceCapabilities::from_json(&compact).unwrap(), fixture_capabilities());
+    }
+
+    #[test]
+    fn rejects_invalid_snapshots() {
+        let replaced = |from: &str, to: &str| {
+            assert!(SNAPSHOT.contains(from), "{from}");
+            DeviceCapabilities::from_json(&SNAPSHOT.replacen(from, to, 1))
+        };
+        assert!(matches!(DeviceCapabilities::from_json("{"), Err(DeviceCapabilitiesError::Json(_))));
+        assert!(matches!(
+            replaced("\"version\": 1", "\"version\": 2"),
+            Err(DeviceCapabilitiesError::UnsupportedVersion(2))
+        ));
+        let invalid = [
+            ("\"version\": 1", "\"version\": \"1\"", "version"),
+            ("\"location\": \"BuiltIn\"", "\"location\": \"Internal\"", "location"),
+            ("\"is_headless\": false", "\"is_headless\": 0", "is_headless"),
+            ("\"peer_index\": 0", "\"peer_index\": 4294967296", "peer_index"),
+            ("\"Metal3\"", "\"Metal9\"", "families"),
+            ("[1, 2, 4]", "[1, -2, 4]", "texture_sample_counts"),
+            ("\"depth\": 1024", "\"depth\": null", "max_threads_per_threadgroup"),
+            ("\"KB64\"", "\"KB32\"", "placement_sparse_tile_sizes_in_bytes"),
+            ("  \"supports_raytracing\": true,\n", "", "supports_raytracing"),
+        ];
+        for (from, to, field) in invalid {
+            match replaced(from, to) {
+                Err(DeviceCapabilitiesError::InvalidField(name)) => assert_eq!(name, field),
+                result => panic!("{from} -> {to}: {result:?}"),
+            }
+        }
+    }
+}
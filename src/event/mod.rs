@@ -1,9 +1,14 @@
 mod event;
+mod notification;
 mod shared_event;
 mod shared_event_handle;
 mod shared_event_listener;
+mod timeline;
 
 pub use event::{MTLEvent, MTLEventExt};
-pub use shared_event::MTLSharedEvent;
+pub(crate) use notification::Notification;
+pub use notification::{SignalFuture, SignalNotifier, SignalTimeline};
+pub use shared_event::{MTLSharedEvent, MTLSharedEventExt, SharedEventNotifier};
 pub use shared_event_handle::MTLSharedEventHandle;
 pub use shared_event_listener::MTLSharedEventListener;
+pub use timeline::{Timeline, TimelineError, TimelineEvent, TimelineFrame, TimelineState};
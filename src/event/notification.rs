@@ -0,0 +1,360 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use std::sync::{Arc, Mutex};
+
+/// A monotonically increasing signal value that can call back once it
+/// reaches a target, such as an `MTLSharedEvent`.
+///
+/// [`SignalFuture`] and [`SignalTimeline`] only depend on this trait, so they
+/// work with any notification source, including fakes driven by hand.
+pub trait SignalNotifier {
+    fn signaled_value(&self) -> u64;
+
+    /// Arranges for `notify` to be called once the signaled value reaches
+    /// `value`. It may be called immediately, and on any thread.
+    fn notify_at(
+        &self,
+        value: u64,
+        notify: Box<dyn FnOnce() + Send>,
+    );
+}
+
+impl<N: SignalNotifier + ?Sized> SignalNotifier for &N {
+    fn signaled_value(&self) -> u64 {
+        (**self).signaled_value()
+    }
+
+    fn notify_at(
+        &self,
+        value: u64,
+        notify: Box<dyn FnOnce() + Send>,
+    ) {
+        (**self).notify_at(value, notify)
+    }
+}
+
+impl<N: SignalNotifier + ?Sized> SignalNotifier for Arc<N> {
+    fn signaled_value(&self) -> u64 {
+        (**self).signaled_value()
+    }
+
+    fn notify_at(
+        &self,
+        value: u64,
+        notify: Box<dyn FnOnce() + Send>,
+    ) {
+        (**self).notify_at(value, notify)
+    }
+}
+
+/// One-shot flag set from a callback and awaited by a future.
+#[derive(Default)]
+pub(crate) struct Notification {
+    state: Mutex<NotificationState>,
+}
+
+#[derive(Default)]
+struct NotificationState {
+    notified: bool,
+    waker: Option<Waker>,
+}
+
+impl Notification {
+    pub(crate) fn notify(&self) {
+        let waker = {
+            let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            state.notified = true;
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    pub(crate) fn poll_notified(
+        &self,
+        cx: &mut Context<'_>,
+    ) -> Poll<()> {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if state.notified {
+            return Poll::Ready(());
+        }
+        match &mut state.waker {
+            Some(waker) => waker.clone_from(cx.waker()),
+            waker @ None => *waker = Some(cx.waker().clone()),
+        }
+        Poll::Pending
+    }
+}
+
+/// Resolves to the signaled value once it reaches the target value.
+///
+/// The notification is registered on first poll, so an unpolled future costs
+/// nothing. Works with any executor.
+#[must_use = "futures do nothing unless polled"]
+pub struct SignalFuture<N: SignalNotifier> {
+    notifier: N,
+    value: u64,
+    notification: Option<Arc<Notification>>,
+}
+
+impl<N: SignalNotifier> SignalFuture<N> {
+    pub fn new(
+        notifier: N,
+        value: u64,
+    ) -> Self {
+        Self {
+            notifier,
+            value,
+            notification: None,
+        }
+    }
+
+    /// The value this future waits for.
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+
+    pub fn notifier(&self) -> &N {
+        &self.notifier
+    }
+}
+
+impl<N: SignalNotifier + Unpin> Future for SignalFuture<N> {
+    type Output = u64;
+
+    fn poll(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<u64> {
+        let this = self.get_mut();
+        let signaled = this.notifier.signaled_value();
+        if signaled >= this.value {
+            return Poll::Ready(signaled);
+        }
+        let notification = this.notification.get_or_insert_with(|| {
+            let notification = Arc::new(Notification::default());
+            let notify = Arc::clone(&notification);
+            this.notifier.notify_at(this.value, Box::new(move || notify.notify()));
+            notification
+        });
+        match notification.poll_notified(cx) {
+            Poll::Ready(()) => Poll::Ready(this.notifier.signaled_value()),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Hands out increasing signal values on one notifier and waits for them.
+///
+/// Each unit of GPU work takes a value from [`next_value`](Self::next_value)
+/// and signals it when done; [`wait_for`](Self::wait_for) then waits for that
+/// work.
+pub struct SignalTimeline<N: SignalNotifier> {
+    notifier: N,
+    last_value: u64,
+}
+
+impl<N: SignalNotifier> SignalTimeline<N> {
+    /// Starts handing out values after the notifier's current value.
+    pub fn new(notifier: N) -> Self {
+        let last_value = notifier.signaled_value();
+        Self {
+            notifier,
+            last_value,
+        }
+    }
+
+    pub fn notifier(&self) -> &N {
+        &self.notifier
+    }
+
+    /// Allocates the next signal value.
+    pub fn next_value(&mut self) -> u64 {
+        self.last_value += 1;
+        self.last_value
+    }
+
+    /// The most recently allocated value.
+    pub fn last_value(&self) -> u64 {
+        self.last_value
+    }
+
+    /// The value signaled so far.
+    pub fn completed_value(&self) -> u64 {
+        self.notifier.signaled_value()
+    }
+
+    pub fn is_complete(
+        &self,
+        value: u64,
+    ) -> bool {
+        self.completed_value() >= value
+    }
+
+    pub fn wait_for(
+        &self,
+        value: u64,
+    ) -> SignalFuture<&N> {
+        SignalFuture::new(&self.notifier, value)
+    }
+
+    /// Waits for all allocated values.
+    pub fn wait_for_last(&self) -> SignalFuture<&N> {
+        self.wait_for(self.last_value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::Wake;
+
+    use super::*;
+
+    type Callback = Box<dyn FnOnce() + Send>;
+
+    /// A hand-driven notifier that, like `MTLSharedEvent`, calls back
+    /// immediately for values already reached.
+    #[derive(Default)]
+    struct FakeNotifier {
+        value: Mutex<u64>,
+        pending: Mutex<Vec<(u64, Callback)>>,
+        /// Signaled from inside `notify_at`, after the future's first check
+        /// of the value but before its callback is registered.
+        signal_on_register: Mutex<Option<u64>>,
+    }
+
+    impl FakeNotifier {
+        fn signal(
+            &self,
+            value: u64,
+        ) {
+            *self.value.lock().unwrap() = value;
+            let ready: Vec<_> = {
+                let mut pending = self.pending.lock().unwrap();
+                let (ready, waiting) = pending.drain(..).partition(|(target, _)| *target <= value);
+                *pending = waiting;
+                ready
+            };
+            for (_, notify) in ready {
+                notify();
+            }
+        }
+    }
+
+    impl SignalNotifier for FakeNotifier {
+        fn signaled_value(&self) -> u64 {
+            *self.value.lock().unwrap()
+        }
+
+        fn notify_at(
+            &self,
+            value: u64,
+            notify: Callback,
+        ) {
+            if let Some(signal) = self.signal_on_register.lock().unwrap().take() {
+                *self.value.lock().unwrap() = signal;
+            }
+            if self.signaled_value() >= value {
+                notify();
+            } else {
+                self.pending.lock().unwrap().push((value, notify));
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn poll<F: Future + Unpin>(
+        future: &mut F,
+        waker: &Arc<CountingWaker>,
+    ) -> Poll<F::Output> {
+        let waker = Waker::from(Arc::clone(waker));
+        Pin::new(future).poll(&mut Context::from_waker(&waker))
+    }
+
+    #[test]
+    fn immediately_ready() {
+        let notifier = FakeNotifier::default();
+        notifier.signal(3);
+        let waker = Arc::new(CountingWaker::default());
+        let mut future = SignalFuture::new(&notifier, 2);
+        assert_eq!(poll(&mut future, &waker), Poll::Ready(3));
+        assert!(notifier.pending.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn wakes_on_notify() {
+        let notifier = Arc::new(FakeNotifier::default());
+        let waker = Arc::new(CountingWaker::default());
+        let mut future = SignalFuture::new(Arc::clone(&notifier), 2);
+        assert_eq!(poll(&mut future, &waker), Poll::Pending);
+        assert_eq!(poll(&mut future, &waker), Poll::Pending);
+        assert_eq!(notifier.pending.lock().unwrap().len(), 1, "the callback is registered once");
+
+        notifier.signal(1);
+        assert_eq!(waker.0.load(Ordering::SeqCst), 0);
+        std::thread::spawn({
+            let notifier = Arc::clone(&notifier);
+            move || notifier.signal(2)
+        })
+        .join()
+        .unwrap();
+        assert_eq!(waker.0.load(Ordering::SeqCst), 1);
+        assert_eq!(poll(&mut future, &waker), Poll::Ready(2));
+    }
+
+    #[test]
+    fn notify_before_poll_registers() {
+        let notifier = FakeNotifier::default();
+        *notifier.signal_on_register.lock().unwrap() = Some(5);
+        let waker = Arc::new(CountingWaker::default());
+        let mut future = SignalFuture::new(&notifier, 4);
+        // The value was 0 when first read, but the callback ran before the
+        // waker was stored; the future must not wait for a second callback.
+        assert_eq!(poll(&mut future, &waker), Poll::Ready(5));
+        assert_eq!(waker.0.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn notification_keeps_latest_waker() {
+        let notification = Notification::default();
+        let (first, second) = (Arc::new(CountingWaker::default()), Arc::new(CountingWaker::default()));
+        for waker in [&first, &second] {
+            let waker = Waker::from(Arc::clone(waker));
+            assert!(notification.poll_notified(&mut Context::from_waker(&waker)).is_pending());
+        }
+        notification.notify();
+        assert_eq!((first.0.load(Ordering::SeqCst), second.0.load(Ordering::SeqCst)), (0, 1));
+        let waker = Waker::from(first);
+        assert!(notification.poll_notified(&mut Context::from_waker(&waker)).is_ready());
+    }
+
+    #[test]
+    fn timeline_values() {
+        let notifier = FakeNotifier::default();
+        notifier.signal(10);
+        let mut timeline = SignalTimeline::new(&notifier);
+        assert_eq!((timeline.last_value(), timeline.next_value(), timeline.next_value()), (10, 11, 12));
+        assert!(timeline.is_complete(10) && !timeline.is_complete(11));
+
+        let waker = Arc::new(CountingWaker::default());
+        let mut last = timeline.wait_for_last();
+        assert_eq!(last.value(), 12);
+        assert_eq!(poll(&mut last, &waker), Poll::Pending);
+        notifier.signal(12);
+        assert_eq!(poll(&mut last, &waker), Poll::Ready(12));
+        assert_eq!(timeline.completed_value(), 12);
+    }
+}
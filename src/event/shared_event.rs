@@ -1,8 +1,10 @@
 use core::ptr::NonNull;
+use std::sync::{Mutex, OnceLock};
 
-use objc2::{extern_protocol, rc::Retained, runtime::ProtocolObject};
+use block2::RcBlock;
+use objc2::{Message, extern_protocol, rc::Retained, runtime::ProtocolObject};
 
-use super::{MTLEvent, MTLSharedEventHandle, MTLSharedEventListener};
+use super::{MTLEvent, MTLSharedEventHandle, MTLSharedEventListener, SignalFuture, SignalNotifier, SignalTimeline};
 
 pub type SharedEventNotificationBlock = *mut block2::DynBlock<dyn Fn(NonNull<ProtocolObject<dyn MTLSharedEvent>>, u64)>;
 
@@ -49,3 +51,94 @@ extern_protocol!(
         );
     }
 );
+
+/// [`SignalNotifier`] for an `MTLSharedEvent`, delivering notifications
+/// through an `MTLSharedEventListener`.
+#[derive(Clone)]
+pub struct SharedEventNotifier {
+    event: Retained<ProtocolObject<dyn MTLSharedEvent>>,
+    listener: Retained<MTLSharedEventListener>,
+}
+
+impl SharedEventNotifier {
+    /// Uses a listener shared by all notifiers in the process.
+    pub fn new(event: Retained<ProtocolObject<dyn MTLSharedEvent>>) -> Self {
+        static LISTENER: OnceLock<Retained<MTLSharedEventListener>> = OnceLock::new();
+        let listener = LISTENER.get_or_init(MTLSharedEventListener::new).clone();
+        Self::with_listener(event, listener)
+    }
+
+    pub fn with_listener(
+        event: Retained<ProtocolObject<dyn MTLSharedEvent>>,
+        listener: Retained<MTLSharedEventListener>,
+    ) -> Self {
+        Self {
+            event,
+            listener,
+        }
+    }
+
+    pub fn event(&self) -> &ProtocolObject<dyn MTLSharedEvent> {
+        &self.event
+    }
+}
+
+impl SignalNotifier for SharedEventNotifier {
+    fn signaled_value(&self) -> u64 {
+        self.event.signaled_value()
+    }
+
+    fn notify_at(
+        &self,
+        value: u64,
+        notify: Box<dyn FnOnce() + Send>,
+    ) {
+        let notify = Mutex::new(Some(notify));
+        let block = RcBlock::new(move |_: NonNull<ProtocolObject<dyn MTLSharedEvent>>, _: u64| {
+            if let Some(notify) = notify.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take() {
+                notify();
+            }
+        });
+        self.event.notify_listener_at_value_block(&self.listener, value, RcBlock::as_ptr(&block));
+    }
+}
+
+pub trait MTLSharedEventExt: MTLSharedEvent + Message {
+    /// Resolves once the event's signaled value reaches `value`.
+    fn wait_for(
+        &self,
+        value: u64,
+    ) -> SignalFuture<SharedEventNotifier>;
+
+    /// Like [`wait_for`](Self::wait_for), delivering the notification through
+    /// `listener`.
+    fn wait_for_with_listener(
+        &self,
+        value: u64,
+        listener: Retained<MTLSharedEventListener>,
+    ) -> SignalFuture<SharedEventNotifier>;
+
+    /// A timeline handing out values after the current signaled value.
+    fn timeline(&self) -> SignalTimeline<SharedEventNotifier>;
+}
+
+impl MTLSharedEventExt for ProtocolObject<dyn MTLSharedEvent> {
+    fn wait_for(
+        &self,
+        value: u64,
+    ) -> SignalFuture<SharedEventNotifier> {
+        SignalFuture::new(SharedEventNotifier::new(self.retain()), value)
+    }
+
+    fn wait_for_with_listener(
+        &self,
+        value: u64,
+        listener: Retained<MTLSharedEventListener>,
+    ) -> SignalFuture<SharedEventNotifier> {
+        SignalFuture::new(SharedEventNotifier::with_listener(self.retain(), listener), value)
+    }
+
+    fn timeline(&self) -> SignalTimeline<SharedEventNotifier> {
+        SignalTimeline::new(SharedEventNotifier::new(self.retain()))
+    }
+}
@@ -0,0 +1,607 @@
+use core::{fmt, time::Duration};
+use std::{collections::VecDeque, sync::Arc};
+
+use objc2::{Message, runtime::ProtocolObject};
+
+use super::{SharedEventNotifier, SignalFuture, SignalNotifier, SignalTimeline};
+use crate::MTLSharedEvent;
+
+/// A [`SignalNotifier`] that can also be signaled from the CPU and waited on
+/// synchronously.
+pub trait TimelineEvent: SignalNotifier {
+    /// Sets the signaled value from the CPU.
+    fn signal(
+        &self,
+        value: u64,
+    );
+
+    /// Blocks until the signaled value reaches `value` or `timeout` passes,
+    /// returning whether it was reached.
+    fn wait_until(
+        &self,
+        value: u64,
+        timeout: Duration,
+    ) -> bool;
+}
+
+impl<E: TimelineEvent + ?Sized> TimelineEvent for &E {
+    fn signal(
+        &self,
+        value: u64,
+    ) {
+        (**self).signal(value)
+    }
+
+    fn wait_until(
+        &self,
+        value: u64,
+        timeout: Duration,
+    ) -> bool {
+        (**self).wait_until(value, timeout)
+    }
+}
+
+impl<E: TimelineEvent + ?Sized> TimelineEvent for Arc<E> {
+    fn signal(
+        &self,
+        value: u64,
+    ) {
+        (**self).signal(value)
+    }
+
+    fn wait_until(
+        &self,
+        value: u64,
+        timeout: Duration,
+    ) -> bool {
+        (**self).wait_until(value, timeout)
+    }
+}
+
+impl TimelineEvent for SharedEventNotifier {
+    fn signal(
+        &self,
+        value: u64,
+    ) {
+        self.event().set_signaled_value(value);
+    }
+
+    fn wait_until(
+        &self,
+        value: u64,
+        timeout: Duration,
+    ) -> bool {
+        let milliseconds = u64::try_from(timeout.as_nanos().div_ceil(1_000_000)).unwrap_or(u64::MAX);
+        self.event().wait_until_signaled_value_timeout_ms(value, milliseconds)
+    }
+}
+
+/// Errors raised by [`Timeline`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TimelineError {
+    /// The wait timed out before the event reached `value`.
+    Timeout {
+        value: u64,
+        signaled: u64,
+    },
+    /// `value` was never handed out by the timeline.
+    Unallocated {
+        value: u64,
+        last_allocated: u64,
+    },
+    /// CPU signals can't move the event backwards.
+    NonMonotonicSignal {
+        value: u64,
+        signaled: u64,
+    },
+}
+
+impl fmt::Display for TimelineError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::Timeout {
+                value,
+                signaled,
+            } => {
+                write!(f, "timed out waiting for value {value}; the event is at {signaled}")
+            },
+            Self::Unallocated {
+                value,
+                last_allocated,
+            } => {
+                write!(f, "value {value} was not allocated; the last allocated value is {last_allocated}")
+            },
+            Self::NonMonotonicSignal {
+                value,
+                signaled,
+            } => {
+                write!(f, "cannot signal {value}; the event is already at {signaled}")
+            },
+        }
+    }
+}
+
+impl std::error::Error for TimelineError {}
+
+/// A frame started by [`TimelineState::begin_frame`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TimelineFrame {
+    /// Frames started before this one.
+    pub number: u64,
+    /// Index of the per-frame resources to use, `number % frames_in_flight`.
+    pub slot: usize,
+    /// Value to signal when the frame's GPU work completes.
+    pub value: u64,
+    /// Value of the previous frame that used `slot`, which must complete
+    /// before the slot is reused. `None` if it already has.
+    pub wait_value: Option<u64>,
+}
+
+/// The bookkeeping of a [`Timeline`], independent of any event: work pending
+/// on each value and frame pacing. Values are allocated by the timeline's
+/// [`SignalTimeline`].
+#[derive(Clone, Debug)]
+pub struct TimelineState<T> {
+    completed: u64,
+    pending: VecDeque<(u64, T)>,
+    frames_in_flight: usize,
+    recent_frames: VecDeque<u64>,
+    frame_count: u64,
+}
+
+impl<T> TimelineState<T> {
+    /// Starts with `initial_value` completed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frames_in_flight` is zero.
+    pub fn new(
+        initial_value: u64,
+        frames_in_flight: usize,
+    ) -> Self {
+        assert!(frames_in_flight > 0, "frames_in_flight must be at least 1");
+        Self {
+            completed: initial_value,
+            pending: VecDeque::new(),
+            frames_in_flight,
+            recent_frames: VecDeque::with_capacity(frames_in_flight),
+            frame_count: 0,
+        }
+    }
+
+    /// The highest value known to be complete.
+    pub fn completed_value(&self) -> u64 {
+        self.completed
+    }
+
+    pub fn is_complete(
+        &self,
+        value: u64,
+    ) -> bool {
+        value <= self.completed
+    }
+
+    pub fn frames_in_flight(&self) -> usize {
+        self.frames_in_flight
+    }
+
+    /// Keeps `work` until `value` completes. Work on an already completed
+    /// value is released by the next [`retire`](Self::retire).
+    pub fn track(
+        &mut self,
+        value: u64,
+        work: T,
+    ) {
+        let index = self.pending.partition_point(|&(pending, _)| pending <= value);
+        self.pending.insert(index, (value, work));
+    }
+
+    /// Values with pending work, oldest first.
+    pub fn pending_values(&self) -> impl Iterator<Item = u64> + '_ {
+        self.pending.iter().map(|&(value, _)| value)
+    }
+
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Records that the event reached `signaled` without releasing work.
+    pub fn observe(
+        &mut self,
+        signaled: u64,
+    ) {
+        self.completed = self.completed.max(signaled);
+    }
+
+    /// Records that the event reached `signaled` and releases the work on
+    /// every completed value, oldest first.
+    pub fn retire(
+        &mut self,
+        signaled: u64,
+    ) -> Vec<T> {
+        self.observe(signaled);
+        let count = self.pending.partition_point(|&(value, _)| value <= self.completed);
+        self.pending.drain(..count).map(|(_, work)| work).collect()
+    }
+
+    /// The value [`begin_frame`](Self::begin_frame) would report as
+    /// `wait_value`.
+    pub fn frame_wait_value(&self) -> Option<u64> {
+        if self.recent_frames.len() < self.frames_in_flight {
+            return None;
+        }
+        self.recent_frames.front().copied().filter(|&value| !self.is_complete(value))
+    }
+
+    /// Starts a frame that signals `value` and reports which earlier value
+    /// must complete before its slot is reused.
+    pub fn begin_frame(
+        &mut self,
+        value: u64,
+    ) -> TimelineFrame {
+        let wait_value = self.frame_wait_value();
+        if self.recent_frames.len() == self.frames_in_flight {
+            self.recent_frames.pop_front();
+        }
+        self.recent_frames.push_back(value);
+        let number = self.frame_count;
+        self.frame_count += 1;
+        TimelineFrame {
+            number,
+            slot: (number % self.frames_in_flight as u64) as usize,
+            value,
+            wait_value,
+        }
+    }
+}
+
+/// Signal values on an `MTLSharedEvent` (or any [`TimelineEvent`]) with work
+/// tracked per value and frame pacing.
+///
+/// Values are handed out by a [`SignalTimeline`] over the event, which
+/// [`values`](Self::values) exposes for code that only needs to wait.
+///
+/// GPU work signals the values it's handed with
+/// `encode_signal_event_value`; the CPU can signal with
+/// [`signal`](Self::signal). Work attached with [`track`](Self::track) is
+/// handed back by [`take_completed`](Self::take_completed) once its value is
+/// reached.
+pub struct Timeline<E: TimelineEvent, T = ()> {
+    values: SignalTimeline<E>,
+    state: TimelineState<T>,
+}
+
+impl<T> Timeline<SharedEventNotifier, T> {
+    /// Wraps `event`, continuing from its current signaled value.
+    pub fn with_shared_event(
+        event: &ProtocolObject<dyn MTLSharedEvent>,
+        frames_in_flight: usize,
+    ) -> Self {
+        Self::new(SharedEventNotifier::new(event.retain()), frames_in_flight)
+    }
+}
+
+impl<E: TimelineEvent, T> Timeline<E, T> {
+    /// Continues from the event's current signaled value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frames_in_flight` is zero.
+    pub fn new(
+        event: E,
+        frames_in_flight: usize,
+    ) -> Self {
+        let values = SignalTimeline::new(event);
+        let state = TimelineState::new(values.last_value(), frames_in_flight);
+        Self {
+            values,
+            state,
+        }
+    }
+
+    pub fn event(&self) -> &E {
+        self.values.notifier()
+    }
+
+    pub fn values(&self) -> &SignalTimeline<E> {
+        &self.values
+    }
+
+    pub fn state(&self) -> &TimelineState<T> {
+        &self.state
+    }
+
+    /// Hands out the next value to signal.
+    pub fn next_value(&mut self) -> u64 {
+        self.values.next_value()
+    }
+
+    pub fn last_value(&self) -> u64 {
+        self.values.last_value()
+    }
+
+    /// The event's current signaled value.
+    pub fn signaled_value(&self) -> u64 {
+        self.values.completed_value()
+    }
+
+    pub fn is_complete(
+        &self,
+        value: u64,
+    ) -> bool {
+        self.values.is_complete(value)
+    }
+
+    /// Keeps `work` until the event reaches `value`.
+    pub fn track(
+        &mut self,
+        value: u64,
+        work: T,
+    ) -> Result<(), TimelineError> {
+        self.check_allocated(value)?;
+        self.state.track(value, work);
+        Ok(())
+    }
+
+    /// Releases the work on every value the event has reached.
+    pub fn take_completed(&mut self) -> Vec<T> {
+        self.state.retire(self.signaled_value())
+    }
+
+    /// Signals an allocated `value` from the CPU.
+    pub fn signal(
+        &mut self,
+        value: u64,
+    ) -> Result<(), TimelineError> {
+        self.check_allocated(value)?;
+        let signaled = self.signaled_value();
+        if value < signaled {
+            return Err(TimelineError::NonMonotonicSignal {
+                value,
+                signaled,
+            });
+        }
+        self.event().signal(value);
+        Ok(())
+    }
+
+    /// Blocks until the event reaches `value`, for at most `timeout`.
+    pub fn wait(
+        &self,
+        value: u64,
+        timeout: Duration,
+    ) -> Result<(), TimelineError> {
+        self.check_allocated(value)?;
+        if !self.event().wait_until(value, timeout) {
+            return Err(TimelineError::Timeout {
+                value,
+                signaled: self.signaled_value(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Blocks until every allocated value is reached.
+    pub fn wait_idle(
+        &self,
+        timeout: Duration,
+    ) -> Result<(), TimelineError> {
+        self.wait(self.last_value(), timeout)
+    }
+
+    /// Resolves once the event reaches `value`.
+    pub fn wait_async(
+        &self,
+        value: u64,
+    ) -> SignalFuture<&E> {
+        self.values.wait_for(value)
+    }
+
+    /// Starts a frame, first waiting up to `timeout` until no more than
+    /// `frames_in_flight - 1` earlier frames are still executing, so the
+    /// returned frame's `wait_value` is always `None`.
+    ///
+    /// On timeout the frame is not started.
+    pub fn begin_frame(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<TimelineFrame, TimelineError> {
+        self.state.observe(self.signaled_value());
+        if let Some(value) = self.state.frame_wait_value() {
+            self.wait(value, timeout)?;
+            self.state.observe(value);
+        }
+        let value = self.values.next_value();
+        Ok(self.state.begin_frame(value))
+    }
+
+    fn check_allocated(
+        &self,
+        value: u64,
+    ) -> Result<(), TimelineError> {
+        let last_allocated = self.last_value();
+        if value > last_allocated {
+            return Err(TimelineError::Unallocated {
+                value,
+                last_allocated,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    type Callback = Box<dyn FnOnce() + Send>;
+
+    /// A CPU-only event; `wait_until` never blocks and reports whether the
+    /// value was already reached. Notifications wait in `pending` until a
+    /// signal reaches their value.
+    #[derive(Default)]
+    struct FakeEvent {
+        value: Mutex<u64>,
+        pending: Mutex<Vec<(u64, Callback)>>,
+    }
+
+    impl SignalNotifier for FakeEvent {
+        fn signaled_value(&self) -> u64 {
+            *self.value.lock().unwrap()
+        }
+
+        fn notify_at(
+            &self,
+            value: u64,
+            notify: Callback,
+        ) {
+            if self.signaled_value() >= value {
+                notify();
+            } else {
+                self.pending.lock().unwrap().push((value, notify));
+            }
+        }
+    }
+
+    impl TimelineEvent for FakeEvent {
+        fn signal(
+            &self,
+            value: u64,
+        ) {
+            *self.value.lock().unwrap() = value;
+            let ready: Vec<_> = {
+                let mut pending = self.pending.lock().unwrap();
+                let (ready, waiting) = pending.drain(..).partition(|(at, _)| *at <= value);
+                *pending = waiting;
+                ready
+            };
+            for (_, notify) in ready {
+                notify();
+            }
+        }
+
+        fn wait_until(
+            &self,
+            value: u64,
+            _timeout: Duration,
+        ) -> bool {
+            self.signaled_value() >= value
+        }
+    }
+
+    #[test]
+    fn track_and_retire_in_value_order() {
+        let mut state = TimelineState::new(0, 2);
+        state.track(3, "c");
+        state.track(1, "a");
+        state.track(2, "b");
+        state.track(1, "a2");
+        assert_eq!(state.pending_values().collect::<Vec<_>>(), [1, 1, 2, 3]);
+
+        assert_eq!(state.retire(1), ["a", "a2"]);
+        // Signals never move the completed value backwards.
+        assert!(state.retire(0).is_empty());
+        assert_eq!(state.completed_value(), 1);
+        state.track(1, "late");
+        assert_eq!(state.retire(3), ["late", "b", "c"]);
+        assert_eq!(state.pending_len(), 0);
+    }
+
+    #[test]
+    fn frames_cycle_slots() {
+        let mut state = TimelineState::<()>::new(10, 2);
+        let frames: Vec<_> = (11..=14).map(|value| state.begin_frame(value)).collect();
+        let summary: Vec<_> = frames.iter().map(|frame| (frame.number, frame.slot, frame.wait_value)).collect();
+        assert_eq!(summary, [(0, 0, None), (1, 1, None), (2, 0, Some(11)), (3, 1, Some(12))]);
+
+        state.observe(13);
+        assert_eq!(state.frame_wait_value(), None);
+        let frame = state.begin_frame(15);
+        assert_eq!((frame.slot, frame.value, frame.wait_value), (0, 15, None));
+        assert_eq!(state.frame_wait_value(), Some(14));
+    }
+
+    #[test]
+    fn signals_must_be_allocated_and_monotonic() {
+        let mut timeline = Timeline::<_, &str>::new(FakeEvent::default(), 2);
+        assert_eq!(
+            timeline.signal(1),
+            Err(TimelineError::Unallocated {
+                value: 1,
+                last_allocated: 0,
+            })
+        );
+        assert_eq!(
+            timeline.track(1, "work"),
+            Err(TimelineError::Unallocated {
+                value: 1,
+                last_allocated: 0,
+            })
+        );
+
+        let (first, second) = (timeline.next_value(), timeline.next_value());
+        timeline.track(second, "second").unwrap();
+        timeline.track(first, "first").unwrap();
+        timeline.signal(second).unwrap();
+        assert_eq!(
+            timeline.signal(first),
+            Err(TimelineError::NonMonotonicSignal {
+                value: 1,
+                signaled: 2,
+            })
+        );
+        assert_eq!(timeline.take_completed(), ["first", "second"]);
+    }
+
+    #[test]
+    fn begin_frame_paces() {
+        let mut timeline = Timeline::<_>::new(FakeEvent::default(), 2);
+        let timeout = Duration::from_millis(1);
+        let first = timeline.begin_frame(timeout).unwrap();
+        let second = timeline.begin_frame(timeout).unwrap();
+        assert_eq!((first.value, second.value), (1, 2));
+        assert_eq!(
+            timeline.begin_frame(timeout),
+            Err(TimelineError::Timeout {
+                value: 1,
+                signaled: 0,
+            })
+        );
+        // A timed out frame allocates nothing.
+        assert_eq!(timeline.last_value(), 2);
+
+        timeline.signal(first.value).unwrap();
+        let third = timeline.begin_frame(timeout).unwrap();
+        assert_eq!((third.number, third.slot, third.value, third.wait_value), (2, 0, 3, None));
+        assert_eq!(timeline.values().last_value(), 3);
+    }
+
+    #[test]
+    fn wait_async_resolves_on_signal() {
+        use core::{
+            future::Future,
+            pin::Pin,
+            task::{Context, Poll, Waker},
+        };
+
+        let mut timeline = Timeline::<_>::new(FakeEvent::default(), 2);
+        let (first, second) = (timeline.next_value(), timeline.next_value());
+        let mut future = timeline.wait_async(second);
+        let mut context = Context::from_waker(Waker::noop());
+        assert_eq!(Pin::new(&mut future).poll(&mut context), Poll::Pending);
+        assert_eq!(timeline.event().pending.lock().unwrap().len(), 1);
+
+        timeline.event().signal(first);
+        assert_eq!(timeline.event().pending.lock().unwrap().len(), 1);
+        assert_eq!(Pin::new(&mut future).poll(&mut context), Poll::Pending);
+
+        timeline.event().signal(second);
+        assert!(timeline.event().pending.lock().unwrap().is_empty());
+        assert_eq!(Pin::new(&mut future).poll(&mut context), Poll::Ready(second));
+    }
+}
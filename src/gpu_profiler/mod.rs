@@ -0,0 +1,8 @@
+mod profiler;
+mod timing;
+
+pub use profiler::GpuProfiler;
+pub use timing::{
+    GpuClockCorrelation, GpuFrameRecorder, GpuProfilerError, GpuScopeSamples, GpuTimingNode, GpuTimingScope,
+    GpuTimingTree, decode_timestamp_samples,
+};
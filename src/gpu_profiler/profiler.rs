@@ -0,0 +1,217 @@
+use objc2::{Message, rc::Retained, runtime::ProtocolObject};
+
+use super::{GpuClockCorrelation, GpuFrameRecorder, GpuProfilerError, GpuScopeSamples, GpuTimingTree};
+use crate::{
+    MTL_COUNTER_DONT_SAMPLE, MTLBlitPassDescriptor, MTLComputePassDescriptor, MTLCounterSampleBuffer,
+    MTLCounterSampleBufferDescriptor, MTLCounterSampleBufferExt, MTLCounterSamplingPoint, MTLCounterSetExt, MTLDevice,
+    MTLDeviceExt, MTLRenderPassDescriptor, MTLStorageMode, counters::MTLCommonCounterSetTimestamp,
+};
+
+#[derive(Debug)]
+struct FrameSlot {
+    sample_buffer: Retained<ProtocolObject<dyn MTLCounterSampleBuffer>>,
+    recorder: GpuFrameRecorder,
+    frame: Option<u64>,
+    clock_start: (u64, u64),
+    clock_end: Option<(u64, u64)>,
+}
+
+/// Scoped GPU timer built on `MTLCommonCounterSetTimestamp` sample buffers.
+///
+/// Each frame in flight gets its own sample buffer. Between
+/// [`begin_frame`][Self::begin_frame] and [`end_frame`][Self::end_frame],
+/// scopes are sampled either at pass boundaries, with the `attach_*_pass`
+/// helpers, or inside an encoder by passing the indices returned from
+/// [`begin_scope`][Self::begin_scope] to `sample_counters_in_buffer` along with
+/// [`sample_buffer`][Self::sample_buffer]. Once the frame's command buffers have
+/// completed, [`resolve_frame`][Self::resolve_frame] returns its timing tree.
+///
+/// Apple GPUs only sample at stage boundaries; check
+/// [`supports_sampling_point`][Self::supports_sampling_point] before sampling
+/// inside encoders.
+#[derive(Debug)]
+pub struct GpuProfiler {
+    device: Retained<ProtocolObject<dyn MTLDevice>>,
+    slots: Vec<FrameSlot>,
+    current: usize,
+    next_frame: u64,
+}
+
+impl GpuProfiler {
+    /// Creates sample buffers holding `max_scopes_per_frame` scopes for each of
+    /// `frames_in_flight` frames.
+    pub fn new(
+        device: &ProtocolObject<dyn MTLDevice>,
+        max_scopes_per_frame: usize,
+        frames_in_flight: usize,
+    ) -> Result<Self, GpuProfilerError> {
+        let timestamp_name = unsafe { MTLCommonCounterSetTimestamp }.to_string();
+        let counter_set = device
+            .counter_sets()
+            .and_then(|sets| sets.into_iter().find(|set| set.name() == timestamp_name))
+            .ok_or(GpuProfilerError::TimestampCounterSetUnavailable)?;
+
+        let capacity = max_scopes_per_frame * 2;
+        let descriptor = MTLCounterSampleBufferDescriptor::new();
+        descriptor.set_counter_set(Some(&*counter_set));
+        descriptor.set_storage_mode(MTLStorageMode::Shared);
+        descriptor.set_sample_count(capacity);
+
+        let slots = (0..frames_in_flight.max(1))
+            .map(|_| {
+                let sample_buffer = device
+                    .new_counter_sample_buffer_with_descriptor_error(&descriptor)
+                    .map_err(GpuProfilerError::SampleBufferCreation)?;
+                Ok(FrameSlot {
+                    sample_buffer,
+                    recorder: GpuFrameRecorder::new(capacity),
+                    frame: None,
+                    clock_start: (0, 0),
+                    clock_end: None,
+                })
+            })
+            .collect::<Result<_, GpuProfilerError>>()?;
+
+        Ok(Self {
+            device: device.retain(),
+            slots,
+            current: 0,
+            next_frame: 0,
+        })
+    }
+
+    pub fn supports_sampling_point(
+        &self,
+        sampling_point: MTLCounterSamplingPoint,
+    ) -> bool {
+        self.device.supports_counter_sampling(sampling_point)
+    }
+
+    /// Starts recording the next frame, reusing the oldest frame's sample
+    /// buffer, and returns the new frame's number.
+    ///
+    /// Fails without discarding anything if the current frame was begun but
+    /// not yet ended.
+    pub fn begin_frame(&mut self) -> Result<u64, GpuProfilerError> {
+        let current = &self.slots[self.current];
+        if let (Some(frame), None) = (current.frame, current.clock_end) {
+            return Err(GpuProfilerError::FrameInProgress(frame));
+        }
+        let frame = self.next_frame;
+        self.next_frame += 1;
+        self.current = (frame % self.slots.len() as u64) as usize;
+        let clock_start = self.sample_clocks();
+        let slot = &mut self.slots[self.current];
+        slot.recorder.clear();
+        slot.frame = Some(frame);
+        slot.clock_start = clock_start;
+        slot.clock_end = None;
+        Ok(frame)
+    }
+
+    /// Finishes recording the current frame and returns its number.
+    pub fn end_frame(&mut self) -> Result<u64, GpuProfilerError> {
+        let clock_end = self.sample_clocks();
+        let slot = &mut self.slots[self.current];
+        let frame = slot.frame.ok_or(GpuProfilerError::UnknownFrame(self.next_frame))?;
+        match slot.recorder.open_scope_count() {
+            0 => {
+                slot.clock_end = Some(clock_end);
+                Ok(frame)
+            },
+            open => Err(GpuProfilerError::UnclosedScopes(open)),
+        }
+    }
+
+    /// Sample buffer of the frame being recorded.
+    pub fn sample_buffer(&self) -> &ProtocolObject<dyn MTLCounterSampleBuffer> {
+        &self.slots[self.current].sample_buffer
+    }
+
+    /// Opens a scope nested in the innermost open scope.
+    pub fn begin_scope(
+        &mut self,
+        name: impl Into<String>,
+    ) -> Result<GpuScopeSamples, GpuProfilerError> {
+        self.slots[self.current].recorder.begin_scope(name)
+    }
+
+    /// Closes the innermost open scope.
+    pub fn end_scope(&mut self) -> Result<GpuScopeSamples, GpuProfilerError> {
+        self.slots[self.current].recorder.end_scope()
+    }
+
+    /// Times a render pass from the start of vertex to the end of fragment
+    /// processing through sample buffer attachment 0.
+    pub fn attach_render_pass(
+        &mut self,
+        descriptor: &MTLRenderPassDescriptor,
+        name: impl Into<String>,
+    ) -> Result<GpuScopeSamples, GpuProfilerError> {
+        let samples = self.slots[self.current].recorder.pass_scope(name)?;
+        let attachment = descriptor.sample_buffer_attachments().object_at_indexed_subscript(0);
+        attachment.set_sample_buffer(Some(self.sample_buffer()));
+        attachment.set_start_of_vertex_sample_index(samples.begin);
+        attachment.set_end_of_vertex_sample_index(MTL_COUNTER_DONT_SAMPLE);
+        attachment.set_start_of_fragment_sample_index(MTL_COUNTER_DONT_SAMPLE);
+        attachment.set_end_of_fragment_sample_index(samples.end);
+        Ok(samples)
+    }
+
+    /// Times a compute pass through sample buffer attachment 0.
+    pub fn attach_compute_pass(
+        &mut self,
+        descriptor: &MTLComputePassDescriptor,
+        name: impl Into<String>,
+    ) -> Result<GpuScopeSamples, GpuProfilerError> {
+        let samples = self.slots[self.current].recorder.pass_scope(name)?;
+        let attachment = descriptor.sample_buffer_attachments().object_at_indexed_subscript(0);
+        attachment.set_sample_buffer(Some(self.sample_buffer()));
+        attachment.set_start_of_encoder_sample_index(samples.begin);
+        attachment.set_end_of_encoder_sample_index(samples.end);
+        Ok(samples)
+    }
+
+    /// Times a blit pass through sample buffer attachment 0.
+    pub fn attach_blit_pass(
+        &mut self,
+        descriptor: &MTLBlitPassDescriptor,
+        name: impl Into<String>,
+    ) -> Result<GpuScopeSamples, GpuProfilerError> {
+        let samples = self.slots[self.current].recorder.pass_scope(name)?;
+        let attachment = descriptor.sample_buffer_attachments().object_at_indexed_subscript(0);
+        attachment.set_sample_buffer(Some(self.sample_buffer()));
+        attachment.set_start_of_encoder_sample_index(samples.begin);
+        attachment.set_end_of_encoder_sample_index(samples.end);
+        Ok(samples)
+    }
+
+    /// Resolves `frame`'s samples into a timing tree.
+    ///
+    /// Call once every command buffer that sampled into the frame has
+    /// completed, and before the frame's sample buffer is reused
+    /// `frames_in_flight` frames later.
+    pub fn resolve_frame(
+        &self,
+        frame: u64,
+    ) -> Result<GpuTimingTree, GpuProfilerError> {
+        let slot =
+            self.slots.iter().find(|slot| slot.frame == Some(frame)).ok_or(GpuProfilerError::UnknownFrame(frame))?;
+        let clock_end = slot.clock_end.ok_or(GpuProfilerError::UnknownFrame(frame))?;
+        let correlation = GpuClockCorrelation::new(slot.clock_start, clock_end);
+
+        let sample_count = slot.recorder.sample_count();
+        if sample_count == 0 {
+            return GpuTimingTree::from_samples(frame, &[], &[], &correlation);
+        }
+        let resolved =
+            slot.sample_buffer.resolve_counter_range_bytes(0..sample_count).ok_or(GpuProfilerError::ResolveFailed)?;
+        GpuTimingTree::from_resolved(frame, slot.recorder.scopes(), &resolved, &correlation)
+    }
+
+    fn sample_clocks(&self) -> (u64, u64) {
+        let (mut cpu, mut gpu) = (0, 0);
+        self.device.sample_timestamps_gpu_timestamp(&mut cpu, &mut gpu);
+        (cpu, gpu)
+    }
+}
@@ -0,0 +1,468 @@
+use core::fmt;
+
+use objc2::rc::Retained;
+use objc2_foundation::NSError;
+
+use crate::{MTLCounterResultTimestamp, decode_counter_results};
+
+/// Errors raised by [`GpuProfiler`][super::GpuProfiler] and the timing tree
+/// decoding.
+#[derive(Debug)]
+pub enum GpuProfilerError {
+    /// The device exposes no `MTLCommonCounterSetTimestamp` counter set.
+    TimestampCounterSetUnavailable,
+    /// `new_counter_sample_buffer_with_descriptor_error` failed.
+    SampleBufferCreation(Retained<NSError>),
+    /// A frame opened more scopes than its sample buffer has room for.
+    SampleBufferFull {
+        capacity: usize,
+    },
+    /// `end_scope` was called with no scope open.
+    NoOpenScope,
+    /// A frame was finished while scopes were still open.
+    UnclosedScopes(usize),
+    /// A frame was begun before the previous one was ended.
+    FrameInProgress(u64),
+    /// The frame being resolved was never recorded, or was overwritten.
+    UnknownFrame(u64),
+    /// `resolve_counter_range` returned no data.
+    ResolveFailed,
+    /// The resolved data is not a whole number of timestamp samples.
+    InvalidResolvedLength(usize),
+    /// A scope refers to a sample past the end of the resolved data.
+    SampleOutOfRange {
+        sample: usize,
+        sample_count: usize,
+    },
+}
+
+impl fmt::Display for GpuProfilerError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::TimestampCounterSetUnavailable => f.write_str("the device has no timestamp counter set"),
+            Self::SampleBufferCreation(error) => write!(f, "failed to create a counter sample buffer: {error}"),
+            Self::SampleBufferFull {
+                capacity,
+            } => {
+                write!(f, "the frame needs more than the {capacity} samples its sample buffer holds")
+            },
+            Self::NoOpenScope => f.write_str("no GPU timing scope is open"),
+            Self::UnclosedScopes(count) => write!(f, "{count} GPU timing scopes were never closed"),
+            Self::FrameInProgress(frame) => write!(f, "frame {frame} was never ended"),
+            Self::UnknownFrame(frame) => write!(f, "frame {frame} is not available for resolving"),
+            Self::ResolveFailed => f.write_str("resolving the counter sample buffer failed"),
+            Self::InvalidResolvedLength(length) => {
+                write!(f, "{length} bytes of resolved data is not a whole number of timestamp samples")
+            },
+            Self::SampleOutOfRange {
+                sample,
+                sample_count,
+            } => {
+                write!(f, "sample {sample} is out of range for {sample_count} resolved samples")
+            },
+        }
+    }
+}
+
+impl std::error::Error for GpuProfilerError {}
+
+/// Maps GPU timestamps to the CPU clock from two pairs of timestamps taken
+/// with `MTLDevice::sample_timestamps_gpu_timestamp`.
+///
+/// GPU ticks are nanoseconds on Apple silicon but not on every Mac GPU, so the
+/// scale is derived from how far both clocks advanced between the two samples.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GpuClockCorrelation {
+    pub cpu_start: u64,
+    pub gpu_start: u64,
+    pub cpu_end: u64,
+    pub gpu_end: u64,
+}
+
+impl GpuClockCorrelation {
+    pub const fn new(
+        (cpu_start, gpu_start): (u64, u64),
+        (cpu_end, gpu_end): (u64, u64),
+    ) -> Self {
+        Self {
+            cpu_start,
+            gpu_start,
+            cpu_end,
+            gpu_end,
+        }
+    }
+
+    /// A correlation for GPUs whose ticks are already CPU-clock nanoseconds.
+    pub const fn identity() -> Self {
+        Self::new((0, 0), (1, 1))
+    }
+
+    /// Nanoseconds per GPU tick, or 1.0 if the GPU clock didn't advance.
+    pub fn nanoseconds_per_tick(&self) -> f64 {
+        let gpu_delta = self.gpu_end.wrapping_sub(self.gpu_start);
+        if gpu_delta == 0 || self.gpu_end < self.gpu_start || self.cpu_end < self.cpu_start {
+            return 1.0;
+        }
+        (self.cpu_end - self.cpu_start) as f64 / gpu_delta as f64
+    }
+
+    /// Converts a tick delta to nanoseconds.
+    pub fn ticks_to_nanoseconds(
+        &self,
+        ticks: u64,
+    ) -> u64 {
+        (ticks as f64 * self.nanoseconds_per_tick()).round() as u64
+    }
+
+    /// Converts a GPU timestamp to the CPU clock, in nanoseconds.
+    pub fn gpu_to_cpu_nanoseconds(
+        &self,
+        gpu_timestamp: u64,
+    ) -> u64 {
+        let ticks = gpu_timestamp as f64 - self.gpu_start as f64;
+        (self.cpu_start as f64 + ticks * self.nanoseconds_per_tick()).max(0.0).round() as u64
+    }
+}
+
+/// Decodes resolved `MTLCommonCounterSetTimestamp` data into one timestamp per
+/// sample, with `None` for samples the GPU failed to take.
+pub fn decode_timestamp_samples(bytes: &[u8]) -> Result<Vec<Option<u64>>, GpuProfilerError> {
+    let samples = decode_counter_results::<MTLCounterResultTimestamp>(bytes)
+        .map_err(|_| GpuProfilerError::InvalidResolvedLength(bytes.len()))?;
+    // Some GPUs leave samples they didn't take zeroed rather than writing
+    // `MTLCounterErrorValue`.
+    Ok(samples.into_iter().map(|sample| sample.map(|s| s.timestamp).filter(|&t| t != 0)).collect())
+}
+
+/// Sample indices reserved for one timing scope.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GpuScopeSamples {
+    pub begin: usize,
+    pub end: usize,
+}
+
+/// One scope recorded by a [`GpuFrameRecorder`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GpuTimingScope {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub samples: GpuScopeSamples,
+}
+
+/// Records nested timing scopes for one frame and assigns each a pair of
+/// sample indices.
+///
+/// Both indices are reserved when the scope begins, so a pass's sample buffer
+/// attachment can be configured before the pass is encoded.
+#[derive(Clone, Debug, Default)]
+pub struct GpuFrameRecorder {
+    scopes: Vec<GpuTimingScope>,
+    open: Vec<usize>,
+    capacity: usize,
+}
+
+impl GpuFrameRecorder {
+    /// A recorder whose scopes use at most `capacity` samples.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            scopes: Vec::new(),
+            open: Vec::new(),
+            capacity,
+        }
+    }
+
+    pub fn scopes(&self) -> &[GpuTimingScope] {
+        &self.scopes
+    }
+
+    /// Samples used so far.
+    pub fn sample_count(&self) -> usize {
+        self.scopes.len() * 2
+    }
+
+    pub fn open_scope_count(&self) -> usize {
+        self.open.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.scopes.clear();
+        self.open.clear();
+    }
+
+    /// Opens a scope nested in the innermost open scope.
+    pub fn begin_scope(
+        &mut self,
+        name: impl Into<String>,
+    ) -> Result<GpuScopeSamples, GpuProfilerError> {
+        let begin = self.sample_count();
+        if begin + 2 > self.capacity {
+            return Err(GpuProfilerError::SampleBufferFull {
+                capacity: self.capacity,
+            });
+        }
+        let samples = GpuScopeSamples {
+            begin,
+            end: begin + 1,
+        };
+        self.open.push(self.scopes.len());
+        self.scopes.push(GpuTimingScope {
+            name: name.into(),
+            parent: self.open.iter().rev().nth(1).copied(),
+            samples,
+        });
+        Ok(samples)
+    }
+
+    /// Closes the innermost open scope and returns its samples.
+    pub fn end_scope(&mut self) -> Result<GpuScopeSamples, GpuProfilerError> {
+        let index = self.open.pop().ok_or(GpuProfilerError::NoOpenScope)?;
+        Ok(self.scopes[index].samples)
+    }
+
+    /// Opens and closes a scope whose samples are both taken by a pass's
+    /// sample buffer attachment.
+    pub fn pass_scope(
+        &mut self,
+        name: impl Into<String>,
+    ) -> Result<GpuScopeSamples, GpuProfilerError> {
+        self.begin_scope(name)?;
+        self.end_scope()
+    }
+}
+
+/// Timing of one scope, in CPU-clock nanoseconds relative to the frame's
+/// first valid sample.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GpuTimingNode {
+    pub name: String,
+    /// `None` if the GPU didn't record the begin sample.
+    pub begin_ns: Option<u64>,
+    /// `None` if the GPU didn't record the end sample.
+    pub end_ns: Option<u64>,
+    pub children: Vec<GpuTimingNode>,
+}
+
+impl GpuTimingNode {
+    pub fn duration_ns(&self) -> Option<u64> {
+        Some(self.end_ns?.saturating_sub(self.begin_ns?))
+    }
+
+    /// Duration not covered by children with valid timings.
+    pub fn self_duration_ns(&self) -> Option<u64> {
+        let children: u64 = self.children.iter().filter_map(Self::duration_ns).sum();
+        Some(self.duration_ns()?.saturating_sub(children))
+    }
+}
+
+/// Hierarchical GPU timings of one frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GpuTimingTree {
+    /// Frame the timings belong to, as counted by the profiler.
+    pub frame: u64,
+    /// CPU-clock time of the frame's first valid sample, which node times are
+    /// relative to.
+    pub origin_ns: u64,
+    pub roots: Vec<GpuTimingNode>,
+}
+
+impl GpuTimingTree {
+    /// Builds the tree for `scopes` from resolved timestamp data covering
+    /// samples `0..` of the frame.
+    pub fn from_resolved(
+        frame: u64,
+        scopes: &[GpuTimingScope],
+        resolved: &[u8],
+        correlation: &GpuClockCorrelation,
+    ) -> Result<Self, GpuProfilerError> {
+        let samples = decode_timestamp_samples(resolved)?;
+        Self::from_samples(frame, scopes, &samples, correlation)
+    }
+
+    /// Builds the tree for `scopes` from decoded GPU timestamps.
+    pub fn from_samples(
+        frame: u64,
+        scopes: &[GpuTimingScope],
+        samples: &[Option<u64>],
+        correlation: &GpuClockCorrelation,
+    ) -> Result<Self, GpuProfilerError> {
+        let sample = |index: usize| {
+            samples.get(index).copied().ok_or(GpuProfilerError::SampleOutOfRange {
+                sample: index,
+                sample_count: samples.len(),
+            })
+        };
+        let mut times = Vec::with_capacity(scopes.len());
+        for scope in scopes {
+            let begin = sample(scope.samples.begin)?.map(|t| correlation.gpu_to_cpu_nanoseconds(t));
+            let end = sample(scope.samples.end)?.map(|t| correlation.gpu_to_cpu_nanoseconds(t));
+            times.push((begin, end));
+        }
+        let origin_ns = times.iter().flat_map(|&(begin, end)| [begin, end]).flatten().min().unwrap_or(0);
+
+        // Scopes are recorded parent-first, so children can be attached by
+        // walking backwards.
+        let mut nodes: Vec<Option<GpuTimingNode>> = scopes
+            .iter()
+            .zip(&times)
+            .map(|(scope, &(begin, end))| {
+                Some(GpuTimingNode {
+                    name: scope.name.clone(),
+                    begin_ns: begin.map(|t| t - origin_ns),
+                    end_ns: end.map(|t| t - origin_ns),
+                    children: Vec::new(),
+                })
+            })
+            .collect();
+        let mut roots = Vec::new();
+        for index in (0..scopes.len()).rev() {
+            let node = nodes[index].take().unwrap();
+            match scopes[index].parent.and_then(|parent| nodes[parent].as_mut()) {
+                Some(parent) => parent.children.insert(0, node),
+                None => roots.insert(0, node),
+            }
+        }
+
+        Ok(Self {
+            frame,
+            origin_ns,
+            roots,
+        })
+    }
+
+    /// Duration from the earliest to the latest valid sample.
+    pub fn total_ns(&self) -> u64 {
+        self.iter().filter_map(|(_, node)| node.end_ns).max().unwrap_or(0)
+    }
+
+    /// Depth-first iterator over `(depth, node)`.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &GpuTimingNode)> {
+        let mut stack: Vec<(usize, &GpuTimingNode)> = self.roots.iter().rev().map(|node| (0, node)).collect();
+        core::iter::from_fn(move || {
+            let (depth, node) = stack.pop()?;
+            stack.extend(node.children.iter().rev().map(|child| (depth + 1, child)));
+            Some((depth, node))
+        })
+    }
+
+    /// Finds a node by the names along its path from the root.
+    pub fn find(
+        &self,
+        path: &[&str],
+    ) -> Option<&GpuTimingNode> {
+        let (first, rest) = path.split_first()?;
+        let mut node = self.roots.iter().find(|node| node.name == *first)?;
+        for name in rest {
+            node = node.children.iter().find(|child| child.name == *name)?;
+        }
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MTL_COUNTER_ERROR_VALUE;
+
+    fn resolved(timestamps: &[u64]) -> Vec<u8> {
+        timestamps.iter().flat_map(|timestamp| timestamp.to_le_bytes()).collect()
+    }
+
+    fn recorded_frame() -> GpuFrameRecorder {
+        let mut recorder = GpuFrameRecorder::new(8);
+        recorder.begin_scope("frame").unwrap();
+        recorder.pass_scope("shadow").unwrap();
+        recorder.begin_scope("main").unwrap();
+        recorder.pass_scope("opaque").unwrap();
+        recorder.end_scope().unwrap();
+        recorder.end_scope().unwrap();
+        recorder
+    }
+
+    #[test]
+    fn recorder_assigns_samples_and_parents() {
+        let recorder = recorded_frame();
+        assert_eq!(recorder.open_scope_count(), 0);
+        assert_eq!(recorder.sample_count(), 8);
+        let layout: Vec<_> =
+            recorder.scopes().iter().map(|scope| (scope.name.as_str(), scope.parent, scope.samples.begin)).collect();
+        assert_eq!(layout, [("frame", None, 0), ("shadow", Some(0), 2), ("main", Some(0), 4), ("opaque", Some(2), 6)]);
+    }
+
+    #[test]
+    fn recorder_errors() {
+        let mut recorder = GpuFrameRecorder::new(4);
+        assert!(matches!(recorder.end_scope(), Err(GpuProfilerError::NoOpenScope)));
+        recorder.pass_scope("a").unwrap();
+        recorder.pass_scope("b").unwrap();
+        assert!(matches!(
+            recorder.begin_scope("c"),
+            Err(GpuProfilerError::SampleBufferFull {
+                capacity: 4
+            })
+        ));
+        recorder.clear();
+        assert_eq!(recorder.sample_count(), 0);
+    }
+
+    #[test]
+    fn clock_correlation() {
+        let correlation = GpuClockCorrelation::new((1000, 100), (2000, 600));
+        assert_eq!(correlation.nanoseconds_per_tick(), 2.0);
+        assert_eq!(correlation.ticks_to_nanoseconds(150), 300);
+        assert_eq!(correlation.gpu_to_cpu_nanoseconds(350), 1500);
+        assert_eq!(correlation.gpu_to_cpu_nanoseconds(0), 800);
+        assert_eq!(GpuClockCorrelation::new((1000, 100), (10, 600)).gpu_to_cpu_nanoseconds(0), 900);
+        assert_eq!(GpuClockCorrelation::new((10, 100), (2000, 600)).gpu_to_cpu_nanoseconds(0), 0);
+
+        assert_eq!(GpuClockCorrelation::identity().ticks_to_nanoseconds(12345), 12345);
+        assert_eq!(GpuClockCorrelation::new((0, 5), (100, 5)).nanoseconds_per_tick(), 1.0);
+        assert_eq!(GpuClockCorrelation::new((0, 5), (100, 4)).nanoseconds_per_tick(), 1.0);
+    }
+
+    #[test]
+    fn decodes_timestamps() {
+        let bytes = resolved(&[42, 0, MTL_COUNTER_ERROR_VALUE, 7]);
+        assert_eq!(decode_timestamp_samples(&bytes).unwrap(), [Some(42), None, None, Some(7)]);
+        assert!(matches!(decode_timestamp_samples(&bytes[..12]), Err(GpuProfilerError::InvalidResolvedLength(12))));
+    }
+
+    #[test]
+    fn timing_tree_from_resolved_bytes() {
+        let recorder = recorded_frame();
+        let bytes = resolved(&[100, 400, 110, 200, 200, 390, 210, MTL_COUNTER_ERROR_VALUE]);
+        let correlation = GpuClockCorrelation::new((1000, 100), (2000, 600));
+        let tree = GpuTimingTree::from_resolved(3, recorder.scopes(), &bytes, &correlation).unwrap();
+
+        assert_eq!((tree.frame, tree.origin_ns, tree.total_ns()), (3, 1000, 600));
+        let frame = tree.find(&["frame"]).unwrap();
+        assert_eq!((frame.begin_ns, frame.end_ns), (Some(0), Some(600)));
+        assert_eq!(frame.self_duration_ns(), Some(40));
+        assert_eq!(tree.find(&["frame", "shadow"]).unwrap().duration_ns(), Some(180));
+        let main = tree.find(&["frame", "main"]).unwrap();
+        assert_eq!(main.duration_ns(), Some(380));
+        assert_eq!(main.self_duration_ns(), Some(380));
+        let opaque = tree.find(&["frame", "main", "opaque"]).unwrap();
+        assert_eq!((opaque.begin_ns, opaque.end_ns, opaque.duration_ns()), (Some(220), None, None));
+        assert!(tree.find(&["main"]).is_none());
+
+        let order: Vec<_> = tree.iter().map(|(depth, node)| (depth, node.name.as_str())).collect();
+        assert_eq!(order, [(0, "frame"), (1, "shadow"), (1, "main"), (2, "opaque")]);
+    }
+
+    #[test]
+    fn timing_tree_sample_out_of_range() {
+        let recorder = recorded_frame();
+        let bytes = resolved(&[1, 2, 3, 4, 5, 6]);
+        let result = GpuTimingTree::from_resolved(0, recorder.scopes(), &bytes, &GpuClockCorrelation::identity());
+        assert!(matches!(
+            result,
+            Err(GpuProfilerError::SampleOutOfRange {
+                sample: 6,
+                sample_count: 6
+            })
+        ));
+    }
+}
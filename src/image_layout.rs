@@ -0,0 +1,100 @@
+use core::fmt;
+
+/// Why a buffer can't hold an image's rows.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ImageLayoutError {
+    /// A row stride is smaller than a row of pixels.
+    RowTooShort {
+        bytes_per_row: usize,
+        required: usize,
+    },
+    /// A buffer is smaller than the image it holds.
+    DataTooShort {
+        required: usize,
+        actual: usize,
+    },
+    /// A size computation overflowed `usize`.
+    Overflow,
+}
+
+impl fmt::Display for ImageLayoutError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::RowTooShort {
+                bytes_per_row,
+                required,
+            } => write!(f, "row stride of {bytes_per_row} bytes is shorter than a {required} byte row"),
+            Self::DataTooShort {
+                required,
+                actual,
+            } => write!(f, "buffer of {actual} bytes is shorter than the {required} bytes required"),
+            Self::Overflow => f.write_str("image size overflows usize"),
+        }
+    }
+}
+
+impl std::error::Error for ImageLayoutError {}
+
+/// Checks that `len` bytes hold `height` rows of `width` pixels of
+/// `bytes_per_pixel` bytes, starting `bytes_per_row` apart. The last row
+/// doesn't need its padding. Returns the length of a row of pixels.
+pub(crate) fn check_image_layout(
+    bytes_per_pixel: usize,
+    width: usize,
+    height: usize,
+    bytes_per_row: usize,
+    len: usize,
+) -> Result<usize, ImageLayoutError> {
+    let row_len = width.checked_mul(bytes_per_pixel).ok_or(ImageLayoutError::Overflow)?;
+    if bytes_per_row < row_len {
+        return Err(ImageLayoutError::RowTooShort {
+            bytes_per_row,
+            required: row_len,
+        });
+    }
+    let required = match height {
+        0 => 0,
+        _ => bytes_per_row
+            .checked_mul(height - 1)
+            .and_then(|required| required.checked_add(row_len))
+            .ok_or(ImageLayoutError::Overflow)?,
+    };
+    if len < required {
+        return Err(ImageLayoutError::DataTooShort {
+            required,
+            actual: len,
+        });
+    }
+    Ok(row_len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layouts() {
+        assert_eq!(check_image_layout(4, 2, 2, 12, 20), Ok(8));
+        assert_eq!(check_image_layout(4, 2, 0, 8, 0), Ok(8));
+        assert_eq!(check_image_layout(4, 0, 3, 0, 0), Ok(0));
+        assert_eq!(
+            check_image_layout(4, 2, 2, 7, 20),
+            Err(ImageLayoutError::RowTooShort {
+                bytes_per_row: 7,
+                required: 8,
+            })
+        );
+        assert_eq!(
+            check_image_layout(4, 2, 2, 12, 19),
+            Err(ImageLayoutError::DataTooShort {
+                required: 20,
+                actual: 19,
+            })
+        );
+        assert_eq!(check_image_layout(16, usize::MAX / 8, 1, usize::MAX, 0), Err(ImageLayoutError::Overflow));
+        assert_eq!(check_image_layout(4, 1, 3, usize::MAX / 2 + 1, 0), Err(ImageLayoutError::Overflow));
+    }
+}
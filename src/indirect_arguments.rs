@@ -0,0 +1,236 @@
+use core::{fmt, marker::PhantomData, slice};
+
+use objc2::{Encode, runtime::ProtocolObject};
+
+use crate::{
+    MTLBuffer, MTLDispatchThreadgroupsIndirectArguments, MTLDispatchThreadsIndirectArguments,
+    MTLDrawIndexedPrimitivesIndirectArguments, MTLDrawPatchIndirectArguments, MTLDrawPrimitivesIndirectArguments,
+    MTLGPUAddress, MTLIndirectCommandBufferExecutionRange, MTLStageInRegionIndirectArguments,
+};
+
+/// Argument structs that Metal reads from a buffer for indirect draws,
+/// dispatches and command buffer execution.
+///
+/// # Safety
+///
+/// Implementors must be `#[repr(C)]`, contain no padding or pointers, and match
+/// the layout Metal expects in the indirect buffer.
+pub unsafe trait MTLIndirectArguments: Copy + Encode + 'static {
+    /// Required alignment of the argument's offset within its buffer.
+    const OFFSET_ALIGNMENT: usize = 4;
+
+    /// The argument's bytes, as written into an indirect buffer.
+    fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts((self as *const Self).cast(), size_of::<Self>()) }
+    }
+
+    /// The bytes of a tightly packed array of arguments.
+    fn slice_as_bytes(arguments: &[Self]) -> &[u8] {
+        unsafe { slice::from_raw_parts(arguments.as_ptr().cast(), size_of_val(arguments)) }
+    }
+}
+
+unsafe impl MTLIndirectArguments for MTLDrawPrimitivesIndirectArguments {}
+unsafe impl MTLIndirectArguments for MTLDrawIndexedPrimitivesIndirectArguments {}
+unsafe impl MTLIndirectArguments for MTLDrawPatchIndirectArguments {}
+unsafe impl MTLIndirectArguments for MTLDispatchThreadgroupsIndirectArguments {}
+unsafe impl MTLIndirectArguments for MTLDispatchThreadsIndirectArguments {}
+unsafe impl MTLIndirectArguments for MTLStageInRegionIndirectArguments {}
+unsafe impl MTLIndirectArguments for MTLIndirectCommandBufferExecutionRange {}
+
+/// Byte offset of a `T` within an indirect buffer.
+///
+/// Construction checks the offset alignment Metal requires for indirect
+/// buffers; the typed encoder helpers additionally check that the argument
+/// lies within the buffer.
+pub struct IndirectArgumentsOffset<T> {
+    bytes: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: MTLIndirectArguments> IndirectArgumentsOffset<T> {
+    /// Offset of the first argument in the buffer.
+    pub const ZERO: Self = Self {
+        bytes: 0,
+        _marker: PhantomData,
+    };
+
+    /// Offset of element `index` in a tightly packed array of `T` starting at
+    /// the beginning of the buffer, or `None` if it overflows `usize`.
+    pub const fn from_index(index: usize) -> Option<Self> {
+        match index.checked_mul(size_of::<T>()) {
+            Some(bytes) => Some(Self {
+                bytes,
+                _marker: PhantomData,
+            }),
+            None => None,
+        }
+    }
+
+    /// Offset of `bytes`, or `None` if it isn't aligned for `T`.
+    pub const fn from_bytes(bytes: usize) -> Option<Self> {
+        if !bytes.is_multiple_of(T::OFFSET_ALIGNMENT) {
+            return None;
+        }
+        Some(Self {
+            bytes,
+            _marker: PhantomData,
+        })
+    }
+
+    /// The offset in bytes, as passed to Metal.
+    pub const fn bytes(self) -> usize {
+        self.bytes
+    }
+
+    /// Offset of the `count`-th argument after this one, or `None` if it
+    /// overflows `usize`.
+    pub const fn offset_by(
+        self,
+        count: usize,
+    ) -> Option<Self> {
+        let Some(stride) = count.checked_mul(size_of::<T>()) else {
+            return None;
+        };
+        match self.bytes.checked_add(stride) {
+            Some(bytes) => Some(Self {
+                bytes,
+                _marker: PhantomData,
+            }),
+            None => None,
+        }
+    }
+
+    /// Whether a `T` at this offset lies entirely within `buffer_length` bytes.
+    pub const fn fits_in(
+        self,
+        buffer_length: usize,
+    ) -> bool {
+        match self.bytes.checked_add(size_of::<T>()) {
+            Some(end) => end <= buffer_length,
+            None => false,
+        }
+    }
+
+    /// GPU address of the argument in `buffer`, for the `MTL4` encoders.
+    pub fn gpu_address(
+        self,
+        buffer: &ProtocolObject<dyn MTLBuffer>,
+    ) -> MTLGPUAddress {
+        self.check_bounds(buffer);
+        buffer.gpu_address() + self.bytes as MTLGPUAddress
+    }
+
+    pub(crate) fn check_bounds(
+        self,
+        buffer: &ProtocolObject<dyn MTLBuffer>,
+    ) {
+        let length = buffer.length();
+        assert!(
+            self.fits_in(length),
+            "indirect arguments at offset {} ({} bytes) overrun a buffer of {} bytes",
+            self.bytes,
+            size_of::<T>(),
+            length
+        );
+    }
+}
+
+impl<T> Clone for IndirectArgumentsOffset<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for IndirectArgumentsOffset<T> {}
+
+impl<T> PartialEq for IndirectArgumentsOffset<T> {
+    fn eq(
+        &self,
+        other: &Self,
+    ) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl<T> Eq for IndirectArgumentsOffset<T> {}
+
+impl<T> fmt::Debug for IndirectArgumentsOffset<T> {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        f.debug_tuple("IndirectArgumentsOffset").field(&self.bytes).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Draw = MTLDrawPrimitivesIndirectArguments;
+    type DrawIndexed = MTLDrawIndexedPrimitivesIndirectArguments;
+
+    #[test]
+    fn argument_sizes() {
+        assert_eq!(size_of::<Draw>(), 16);
+        assert_eq!(size_of::<DrawIndexed>(), 20);
+        assert_eq!(size_of::<MTLDispatchThreadgroupsIndirectArguments>(), 12);
+        assert_eq!(size_of::<MTLIndirectCommandBufferExecutionRange>(), 8);
+    }
+
+    #[test]
+    fn index_and_stride() {
+        assert_eq!(IndirectArgumentsOffset::<Draw>::ZERO.bytes(), 0);
+        assert_eq!(IndirectArgumentsOffset::<Draw>::from_index(3).unwrap().bytes(), 48);
+        assert_eq!(IndirectArgumentsOffset::<DrawIndexed>::from_index(3).unwrap().bytes(), 60);
+
+        let offset = IndirectArgumentsOffset::<DrawIndexed>::from_bytes(8).unwrap();
+        assert_eq!(offset.offset_by(0), Some(offset));
+        assert_eq!(offset.offset_by(2).unwrap().bytes(), 48);
+        assert_eq!(offset.offset_by(2).unwrap().offset_by(1), offset.offset_by(3));
+        assert_eq!(IndirectArgumentsOffset::<DrawIndexed>::ZERO.offset_by(5), IndirectArgumentsOffset::from_index(5));
+    }
+
+    #[test]
+    fn overflow() {
+        assert_eq!(IndirectArgumentsOffset::<Draw>::from_index(usize::MAX / 16 + 1), None);
+        assert_eq!(IndirectArgumentsOffset::<Draw>::from_index(usize::MAX / 16).unwrap().bytes(), usize::MAX - 15);
+        let last = IndirectArgumentsOffset::<Draw>::from_bytes(usize::MAX - 15).unwrap();
+        assert_eq!(last.offset_by(1), None);
+        assert_eq!(last.offset_by(usize::MAX), None);
+        assert_eq!(IndirectArgumentsOffset::<Draw>::ZERO.offset_by(usize::MAX), None);
+        assert!(!last.fits_in(usize::MAX));
+    }
+
+    #[test]
+    fn alignment_and_bounds() {
+        assert_eq!(IndirectArgumentsOffset::<Draw>::from_bytes(6), None);
+        let offset = IndirectArgumentsOffset::<Draw>::from_bytes(4).unwrap();
+        assert!(offset.fits_in(20));
+        assert!(!offset.fits_in(19));
+        assert!(!IndirectArgumentsOffset::<Draw>::from_index(1).unwrap().fits_in(31));
+    }
+
+    #[test]
+    fn bytes() {
+        let arguments = [
+            Draw {
+                vertex_count: 3,
+                instance_count: 1,
+                vertex_start: 0,
+                base_instance: 2,
+            },
+            Draw {
+                vertex_count: 6,
+                instance_count: 4,
+                vertex_start: 3,
+                base_instance: 0,
+            },
+        ];
+        assert_eq!(arguments[0].as_bytes(), [3, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0]);
+        let bytes = Draw::slice_as_bytes(&arguments);
+        assert_eq!(bytes.len(), 32);
+        assert_eq!(bytes[16..], *arguments[1].as_bytes());
+    }
+}
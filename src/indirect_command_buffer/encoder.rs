@@ -0,0 +1,1253 @@
+use core::{fmt, ops::Range};
+
+use objc2::runtime::ProtocolObject;
+
+use super::{
+    MTLIndirectCommandBuffer, MTLIndirectCommandBufferDescriptor, MTLIndirectCommandBufferExecutionRange,
+    MTLIndirectCommandBufferExt, MTLIndirectCommandType,
+};
+use crate::{
+    MTLBuffer, MTLComputePipelineState, MTLIndexType, MTLIndirectComputeCommand, MTLIndirectRenderCommand,
+    MTLPrimitiveType, MTLRenderPipelineState,
+    types::{MTLRegion, MTLSize},
+};
+
+/// The limits and inheritance flags an indirect command buffer was created with.
+///
+/// This is a plain snapshot of [`MTLIndirectCommandBufferDescriptor`] plus the
+/// command count passed to `new_indirect_command_buffer_with_descriptor`, so
+/// commands can be validated without touching Metal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IndirectCommandBufferLimits {
+    pub command_types: MTLIndirectCommandType,
+    pub max_command_count: usize,
+    pub inherit_pipeline_state: bool,
+    pub inherit_buffers: bool,
+    pub max_vertex_buffer_bind_count: usize,
+    pub max_fragment_buffer_bind_count: usize,
+    pub max_kernel_buffer_bind_count: usize,
+    pub max_kernel_threadgroup_memory_bind_count: usize,
+    pub max_object_buffer_bind_count: usize,
+    pub max_mesh_buffer_bind_count: usize,
+    pub support_dynamic_attribute_stride: bool,
+}
+
+impl IndirectCommandBufferLimits {
+    pub fn from_descriptor(
+        descriptor: &MTLIndirectCommandBufferDescriptor,
+        max_command_count: usize,
+    ) -> Self {
+        Self {
+            command_types: descriptor.command_types(),
+            max_command_count,
+            inherit_pipeline_state: descriptor.inherit_pipeline_state(),
+            inherit_buffers: descriptor.inherit_buffers(),
+            max_vertex_buffer_bind_count: descriptor.max_vertex_buffer_bind_count(),
+            max_fragment_buffer_bind_count: descriptor.max_fragment_buffer_bind_count(),
+            max_kernel_buffer_bind_count: descriptor.max_kernel_buffer_bind_count(),
+            max_kernel_threadgroup_memory_bind_count: descriptor.max_kernel_threadgroup_memory_bind_count(),
+            max_object_buffer_bind_count: descriptor.max_object_buffer_bind_count(),
+            max_mesh_buffer_bind_count: descriptor.max_mesh_buffer_bind_count(),
+            support_dynamic_attribute_stride: descriptor.support_dynamic_attribute_stride(),
+        }
+    }
+
+    fn is_render(&self) -> bool {
+        self.command_types.intersects(
+            MTLIndirectCommandType::Draw
+                | MTLIndirectCommandType::DrawIndexed
+                | MTLIndirectCommandType::DrawPatches
+                | MTLIndirectCommandType::DrawIndexedPatches
+                | MTLIndirectCommandType::DrawMeshThreadgroups
+                | MTLIndirectCommandType::DrawMeshThreads,
+        )
+    }
+
+    fn is_compute(&self) -> bool {
+        self.command_types
+            .intersects(MTLIndirectCommandType::ConcurrentDispatch | MTLIndirectCommandType::ConcurrentDispatchThreads)
+    }
+}
+
+/// Shader stage a buffer binding targets, used in validation errors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IndirectBindingStage {
+    Vertex,
+    Fragment,
+    Object,
+    Mesh,
+    Kernel,
+    KernelThreadgroupMemory,
+}
+
+/// Reasons a typed indirect command is rejected before it reaches Metal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IndirectCommandError {
+    /// The command index is not below the buffer's command count.
+    IndexOutOfRange {
+        index: usize,
+        count: usize,
+    },
+    /// The range extends past the buffer's command count.
+    RangeOutOfBounds {
+        range: Range<usize>,
+        count: usize,
+    },
+    /// The descriptor's `command_types` does not include this command type.
+    CommandTypeNotAllowed {
+        required: MTLIndirectCommandType,
+        allowed: MTLIndirectCommandType,
+    },
+    /// Render commands were encoded into a compute buffer or the other way around.
+    WrongCommandKind,
+    /// The buffer inherits its pipeline, but the command sets one.
+    PipelineStateInherited,
+    /// The buffer does not inherit its pipeline, but the command doesn't set one.
+    PipelineStateMissing,
+    /// The buffer inherits its bindings, but the command sets buffers.
+    BuffersInherited,
+    /// A binding index is not below the descriptor's bind count for that stage.
+    BindIndexOutOfRange {
+        stage: IndirectBindingStage,
+        index: usize,
+        max_bind_count: usize,
+    },
+    /// An attribute stride was given, but the descriptor does not enable dynamic strides.
+    DynamicAttributeStrideUnsupported,
+}
+
+impl fmt::Display for IndirectCommandError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::IndexOutOfRange {
+                index,
+                count,
+            } => {
+                write!(f, "command index {index} is out of range for {count} commands")
+            },
+            Self::RangeOutOfBounds {
+                range,
+                count,
+            } => {
+                write!(f, "command range {range:?} is out of bounds for {count} commands")
+            },
+            Self::CommandTypeNotAllowed {
+                required,
+                allowed,
+            } => {
+                write!(f, "command type {required:?} is not in the allowed types {allowed:?}")
+            },
+            Self::WrongCommandKind => {
+                f.write_str("render and compute commands cannot share an indirect command buffer")
+            },
+            Self::PipelineStateInherited => {
+                f.write_str("the pipeline state is inherited and cannot be set per command")
+            },
+            Self::PipelineStateMissing => f.write_str("the pipeline state is not inherited and must be set"),
+            Self::BuffersInherited => f.write_str("buffers are inherited and cannot be set per command"),
+            Self::BindIndexOutOfRange {
+                stage,
+                index,
+                max_bind_count,
+            } => {
+                write!(f, "{stage:?} binding index {index} exceeds the bind count {max_bind_count}")
+            },
+            Self::DynamicAttributeStrideUnsupported => {
+                f.write_str("attribute strides require support_dynamic_attribute_stride")
+            },
+        }
+    }
+}
+
+impl std::error::Error for IndirectCommandError {}
+
+/// A buffer bound to one argument index of an indirect command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IndirectBufferBinding<B> {
+    pub buffer: B,
+    pub offset: usize,
+    pub index: usize,
+    /// Per-binding attribute stride; requires `support_dynamic_attribute_stride`.
+    pub attribute_stride: Option<usize>,
+}
+
+impl<B> IndirectBufferBinding<B> {
+    pub fn new(
+        buffer: B,
+        offset: usize,
+        index: usize,
+    ) -> Self {
+        Self {
+            buffer,
+            offset,
+            index,
+            attribute_stride: None,
+        }
+    }
+}
+
+/// The draw an indirect render command performs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndirectDraw<B> {
+    Primitives {
+        primitive_type: MTLPrimitiveType,
+        vertex_start: usize,
+        vertex_count: usize,
+        instance_count: usize,
+        base_instance: usize,
+    },
+    IndexedPrimitives {
+        primitive_type: MTLPrimitiveType,
+        index_count: usize,
+        index_type: MTLIndexType,
+        index_buffer: B,
+        index_buffer_offset: usize,
+        instance_count: usize,
+        base_vertex: isize,
+        base_instance: usize,
+    },
+    /// A tessellated draw; `patch_index_buffer` maps patches to patch IDs, or
+    /// patches are numbered consecutively without one.
+    Patches {
+        number_of_patch_control_points: usize,
+        patch_start: usize,
+        patch_count: usize,
+        patch_index_buffer: Option<B>,
+        patch_index_buffer_offset: usize,
+        instance_count: usize,
+        base_instance: usize,
+        tessellation_factor_buffer: B,
+        tessellation_factor_buffer_offset: usize,
+        tessellation_factor_buffer_instance_stride: usize,
+    },
+    /// A tessellated draw reading control points through an index buffer.
+    IndexedPatches {
+        number_of_patch_control_points: usize,
+        patch_start: usize,
+        patch_count: usize,
+        patch_index_buffer: Option<B>,
+        patch_index_buffer_offset: usize,
+        control_point_index_buffer: B,
+        control_point_index_buffer_offset: usize,
+        instance_count: usize,
+        base_instance: usize,
+        tessellation_factor_buffer: B,
+        tessellation_factor_buffer_offset: usize,
+        tessellation_factor_buffer_instance_stride: usize,
+    },
+    MeshThreadgroups {
+        threadgroups_per_grid: MTLSize,
+        threads_per_object_threadgroup: MTLSize,
+        threads_per_mesh_threadgroup: MTLSize,
+    },
+    MeshThreads {
+        threads_per_grid: MTLSize,
+        threads_per_object_threadgroup: MTLSize,
+        threads_per_mesh_threadgroup: MTLSize,
+    },
+}
+
+impl<B> IndirectDraw<B> {
+    /// The `MTLIndirectCommandType` bit an indirect command buffer needs for this draw.
+    pub fn command_type(&self) -> MTLIndirectCommandType {
+        match self {
+            Self::Primitives {
+                ..
+            } => MTLIndirectCommandType::Draw,
+            Self::IndexedPrimitives {
+                ..
+            } => MTLIndirectCommandType::DrawIndexed,
+            Self::Patches {
+                ..
+            } => MTLIndirectCommandType::DrawPatches,
+            Self::IndexedPatches {
+                ..
+            } => MTLIndirectCommandType::DrawIndexedPatches,
+            Self::MeshThreadgroups {
+                ..
+            } => MTLIndirectCommandType::DrawMeshThreadgroups,
+            Self::MeshThreads {
+                ..
+            } => MTLIndirectCommandType::DrawMeshThreads,
+        }
+    }
+}
+
+/// The dispatch an indirect compute command performs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndirectDispatch {
+    Threadgroups {
+        threadgroups_per_grid: MTLSize,
+        threads_per_threadgroup: MTLSize,
+    },
+    Threads {
+        threads_per_grid: MTLSize,
+        threads_per_threadgroup: MTLSize,
+    },
+}
+
+impl IndirectDispatch {
+    /// The `MTLIndirectCommandType` bit an indirect command buffer needs for this dispatch.
+    pub fn command_type(&self) -> MTLIndirectCommandType {
+        match self {
+            Self::Threadgroups {
+                ..
+            } => MTLIndirectCommandType::ConcurrentDispatch,
+            Self::Threads {
+                ..
+            } => MTLIndirectCommandType::ConcurrentDispatchThreads,
+        }
+    }
+}
+
+/// Everything one render command in an indirect command buffer holds.
+///
+/// `B` and `P` are buffer and render pipeline handles; for Metal these are
+/// `&ProtocolObject<dyn MTLBuffer>` and `&ProtocolObject<dyn MTLRenderPipelineState>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypedIndirectRenderCommand<B, P> {
+    pub pipeline_state: Option<P>,
+    pub vertex_buffers: Vec<IndirectBufferBinding<B>>,
+    pub fragment_buffers: Vec<IndirectBufferBinding<B>>,
+    pub object_buffers: Vec<IndirectBufferBinding<B>>,
+    pub mesh_buffers: Vec<IndirectBufferBinding<B>>,
+    pub barrier: bool,
+    pub draw: IndirectDraw<B>,
+}
+
+impl<B, P> TypedIndirectRenderCommand<B, P> {
+    pub fn new(draw: IndirectDraw<B>) -> Self {
+        Self {
+            pipeline_state: None,
+            vertex_buffers: Vec::new(),
+            fragment_buffers: Vec::new(),
+            object_buffers: Vec::new(),
+            mesh_buffers: Vec::new(),
+            barrier: false,
+            draw,
+        }
+    }
+
+    pub fn with_pipeline_state(
+        mut self,
+        pipeline_state: P,
+    ) -> Self {
+        self.pipeline_state = Some(pipeline_state);
+        self
+    }
+
+    pub fn with_vertex_buffer(
+        mut self,
+        binding: IndirectBufferBinding<B>,
+    ) -> Self {
+        self.vertex_buffers.push(binding);
+        self
+    }
+
+    pub fn with_fragment_buffer(
+        mut self,
+        binding: IndirectBufferBinding<B>,
+    ) -> Self {
+        self.fragment_buffers.push(binding);
+        self
+    }
+
+    pub fn with_object_buffer(
+        mut self,
+        binding: IndirectBufferBinding<B>,
+    ) -> Self {
+        self.object_buffers.push(binding);
+        self
+    }
+
+    pub fn with_mesh_buffer(
+        mut self,
+        binding: IndirectBufferBinding<B>,
+    ) -> Self {
+        self.mesh_buffers.push(binding);
+        self
+    }
+
+    pub fn with_barrier(mut self) -> Self {
+        self.barrier = true;
+        self
+    }
+
+    /// Checks the command against `limits` without encoding it.
+    pub fn validate(
+        &self,
+        limits: &IndirectCommandBufferLimits,
+    ) -> Result<(), IndirectCommandError> {
+        if !limits.is_render() {
+            return Err(IndirectCommandError::WrongCommandKind);
+        }
+        check_command_type(self.draw.command_type(), limits)?;
+        check_pipeline(self.pipeline_state.is_some(), limits)?;
+        check_bindings(
+            &self.vertex_buffers,
+            IndirectBindingStage::Vertex,
+            limits.max_vertex_buffer_bind_count,
+            limits,
+        )?;
+        check_bindings(
+            &self.fragment_buffers,
+            IndirectBindingStage::Fragment,
+            limits.max_fragment_buffer_bind_count,
+            limits,
+        )?;
+        check_bindings(
+            &self.object_buffers,
+            IndirectBindingStage::Object,
+            limits.max_object_buffer_bind_count,
+            limits,
+        )?;
+        check_bindings(&self.mesh_buffers, IndirectBindingStage::Mesh, limits.max_mesh_buffer_bind_count, limits)
+    }
+}
+
+/// Everything one compute command in an indirect command buffer holds.
+///
+/// `B` and `P` are buffer and compute pipeline handles; for Metal these are
+/// `&ProtocolObject<dyn MTLBuffer>` and `&ProtocolObject<dyn MTLComputePipelineState>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypedIndirectComputeCommand<B, P> {
+    pub pipeline_state: Option<P>,
+    pub kernel_buffers: Vec<IndirectBufferBinding<B>>,
+    /// `(length, index)` pairs for threadgroup memory arguments.
+    pub threadgroup_memory_lengths: Vec<(usize, usize)>,
+    pub stage_in_region: Option<MTLRegion>,
+    pub imageblock_size: Option<(usize, usize)>,
+    pub barrier: bool,
+    pub dispatch: IndirectDispatch,
+}
+
+impl<B, P> TypedIndirectComputeCommand<B, P> {
+    pub fn new(dispatch: IndirectDispatch) -> Self {
+        Self {
+            pipeline_state: None,
+            kernel_buffers: Vec::new(),
+            threadgroup_memory_lengths: Vec::new(),
+            stage_in_region: None,
+            imageblock_size: None,
+            barrier: false,
+            dispatch,
+        }
+    }
+
+    pub fn with_pipeline_state(
+        mut self,
+        pipeline_state: P,
+    ) -> Self {
+        self.pipeline_state = Some(pipeline_state);
+        self
+    }
+
+    pub fn with_kernel_buffer(
+        mut self,
+        binding: IndirectBufferBinding<B>,
+    ) -> Self {
+        self.kernel_buffers.push(binding);
+        self
+    }
+
+    pub fn with_threadgroup_memory_length(
+        mut self,
+        length: usize,
+        index: usize,
+    ) -> Self {
+        self.threadgroup_memory_lengths.push((length, index));
+        self
+    }
+
+    pub fn with_stage_in_region(
+        mut self,
+        region: MTLRegion,
+    ) -> Self {
+        self.stage_in_region = Some(region);
+        self
+    }
+
+    pub fn with_imageblock_size(
+        mut self,
+        width: usize,
+        height: usize,
+    ) -> Self {
+        self.imageblock_size = Some((width, height));
+        self
+    }
+
+    pub fn with_barrier(mut self) -> Self {
+        self.barrier = true;
+        self
+    }
+
+    /// Checks the command against `limits` without encoding it.
+    pub fn validate(
+        &self,
+        limits: &IndirectCommandBufferLimits,
+    ) -> Result<(), IndirectCommandError> {
+        if !limits.is_compute() {
+            return Err(IndirectCommandError::WrongCommandKind);
+        }
+        check_command_type(self.dispatch.command_type(), limits)?;
+        check_pipeline(self.pipeline_state.is_some(), limits)?;
+        check_bindings(
+            &self.kernel_buffers,
+            IndirectBindingStage::Kernel,
+            limits.max_kernel_buffer_bind_count,
+            limits,
+        )?;
+        for &(_, index) in &self.threadgroup_memory_lengths {
+            if index >= limits.max_kernel_threadgroup_memory_bind_count {
+                return Err(IndirectCommandError::BindIndexOutOfRange {
+                    stage: IndirectBindingStage::KernelThreadgroupMemory,
+                    index,
+                    max_bind_count: limits.max_kernel_threadgroup_memory_bind_count,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_command_type(
+    required: MTLIndirectCommandType,
+    limits: &IndirectCommandBufferLimits,
+) -> Result<(), IndirectCommandError> {
+    if limits.command_types.contains(required) {
+        Ok(())
+    } else {
+        Err(IndirectCommandError::CommandTypeNotAllowed {
+            required,
+            allowed: limits.command_types,
+        })
+    }
+}
+
+fn check_pipeline(
+    has_pipeline: bool,
+    limits: &IndirectCommandBufferLimits,
+) -> Result<(), IndirectCommandError> {
+    match (limits.inherit_pipeline_state, has_pipeline) {
+        (true, true) => Err(IndirectCommandError::PipelineStateInherited),
+        (false, false) => Err(IndirectCommandError::PipelineStateMissing),
+        _ => Ok(()),
+    }
+}
+
+fn check_bindings<B>(
+    bindings: &[IndirectBufferBinding<B>],
+    stage: IndirectBindingStage,
+    max_bind_count: usize,
+    limits: &IndirectCommandBufferLimits,
+) -> Result<(), IndirectCommandError> {
+    if bindings.is_empty() {
+        return Ok(());
+    }
+    if limits.inherit_buffers {
+        return Err(IndirectCommandError::BuffersInherited);
+    }
+    for binding in bindings {
+        if binding.index >= max_bind_count {
+            return Err(IndirectCommandError::BindIndexOutOfRange {
+                stage,
+                index: binding.index,
+                max_bind_count,
+            });
+        }
+        if binding.attribute_stride.is_some() && !limits.support_dynamic_attribute_stride {
+            return Err(IndirectCommandError::DynamicAttributeStrideUnsupported);
+        }
+    }
+    Ok(())
+}
+
+/// Destination for validated indirect commands.
+///
+/// Implemented by `ProtocolObject<dyn MTLIndirectCommandBuffer>` and by
+/// [`IndirectCommandRecorder`], which keeps the commands in memory for tests.
+pub trait IndirectCommandSink<B, RP, CP> {
+    fn encode_render_command(
+        &mut self,
+        index: usize,
+        command: &TypedIndirectRenderCommand<B, RP>,
+    );
+
+    fn encode_compute_command(
+        &mut self,
+        index: usize,
+        command: &TypedIndirectComputeCommand<B, CP>,
+    );
+
+    fn reset_range(
+        &mut self,
+        range: Range<usize>,
+    );
+}
+
+impl<'a>
+    IndirectCommandSink<
+        &'a ProtocolObject<dyn MTLBuffer>,
+        &'a ProtocolObject<dyn MTLRenderPipelineState>,
+        &'a ProtocolObject<dyn MTLComputePipelineState>,
+    > for &ProtocolObject<dyn MTLIndirectCommandBuffer>
+{
+    fn encode_render_command(
+        &mut self,
+        index: usize,
+        command: &TypedIndirectRenderCommand<
+            &'a ProtocolObject<dyn MTLBuffer>,
+            &'a ProtocolObject<dyn MTLRenderPipelineState>,
+        >,
+    ) {
+        let encoder = self.indirect_render_command_at_index(index);
+        encoder.reset();
+        if let Some(pipeline_state) = command.pipeline_state {
+            encoder.set_render_pipeline_state(pipeline_state);
+        }
+        for binding in &command.vertex_buffers {
+            match binding.attribute_stride {
+                Some(stride) => encoder.set_vertex_buffer_offset_attribute_stride_at_index(
+                    binding.buffer,
+                    binding.offset,
+                    stride,
+                    binding.index,
+                ),
+                None => encoder.set_vertex_buffer_offset_at_index(binding.buffer, binding.offset, binding.index),
+            }
+        }
+        for binding in &command.fragment_buffers {
+            encoder.set_fragment_buffer_offset_at_index(binding.buffer, binding.offset, binding.index);
+        }
+        for binding in &command.object_buffers {
+            encoder.set_object_buffer_offset_at_index(binding.buffer, binding.offset, binding.index);
+        }
+        for binding in &command.mesh_buffers {
+            encoder.set_mesh_buffer_offset_at_index(binding.buffer, binding.offset, binding.index);
+        }
+        if command.barrier {
+            encoder.set_barrier();
+        }
+        match command.draw {
+            IndirectDraw::Primitives {
+                primitive_type,
+                vertex_start,
+                vertex_count,
+                instance_count,
+                base_instance,
+            } => encoder.draw_primitives_vertex_start_vertex_count_instance_count_base_instance(
+                primitive_type,
+                vertex_start,
+                vertex_count,
+                instance_count,
+                base_instance,
+            ),
+            IndirectDraw::IndexedPrimitives {
+                primitive_type,
+                index_count,
+                index_type,
+                index_buffer,
+                index_buffer_offset,
+                instance_count,
+                base_vertex,
+                base_instance,
+            } => encoder
+                .draw_indexed_primitives_index_count_index_type_index_buffer_index_buffer_offset_instance_count_base_vertex_base_instance(
+                    primitive_type,
+                    index_count,
+                    index_type,
+                    index_buffer,
+                    index_buffer_offset,
+                    instance_count,
+                    base_vertex,
+                    base_instance,
+                ),
+            IndirectDraw::Patches {
+                number_of_patch_control_points,
+                patch_start,
+                patch_count,
+                patch_index_buffer,
+                patch_index_buffer_offset,
+                instance_count,
+                base_instance,
+                tessellation_factor_buffer,
+                tessellation_factor_buffer_offset,
+                tessellation_factor_buffer_instance_stride,
+            } => encoder
+                .draw_patches_patch_start_patch_count_patch_index_buffer_patch_index_buffer_offset_instance_count_base_instance_tessellation_factor_buffer_tessellation_factor_buffer_offset_tessellation_factor_buffer_instance_stride(
+                    number_of_patch_control_points,
+                    patch_start,
+                    patch_count,
+                    patch_index_buffer,
+                    patch_index_buffer_offset,
+                    instance_count,
+                    base_instance,
+                    tessellation_factor_buffer,
+                    tessellation_factor_buffer_offset,
+                    tessellation_factor_buffer_instance_stride,
+                ),
+            IndirectDraw::IndexedPatches {
+                number_of_patch_control_points,
+                patch_start,
+                patch_count,
+                patch_index_buffer,
+                patch_index_buffer_offset,
+                control_point_index_buffer,
+                control_point_index_buffer_offset,
+                instance_count,
+                base_instance,
+                tessellation_factor_buffer,
+                tessellation_factor_buffer_offset,
+                tessellation_factor_buffer_instance_stride,
+            } => encoder
+                .draw_indexed_patches_patch_start_patch_count_patch_index_buffer_patch_index_buffer_offset_control_point_index_buffer_control_point_index_buffer_offset_instance_count_base_instance_tessellation_factor_buffer_tessellation_factor_buffer_offset_tessellation_factor_buffer_instance_stride(
+                    number_of_patch_control_points,
+                    patch_start,
+                    patch_count,
+                    patch_index_buffer,
+                    patch_index_buffer_offset,
+                    control_point_index_buffer,
+                    control_point_index_buffer_offset,
+                    instance_count,
+                    base_instance,
+                    tessellation_factor_buffer,
+                    tessellation_factor_buffer_offset,
+                    tessellation_factor_buffer_instance_stride,
+                ),
+            IndirectDraw::MeshThreadgroups {
+                threadgroups_per_grid,
+                threads_per_object_threadgroup,
+                threads_per_mesh_threadgroup,
+            } => encoder.draw_mesh_threadgroups_threads_per_object_threadgroup_threads_per_mesh_threadgroup(
+                threadgroups_per_grid,
+                threads_per_object_threadgroup,
+                threads_per_mesh_threadgroup,
+            ),
+            IndirectDraw::MeshThreads {
+                threads_per_grid,
+                threads_per_object_threadgroup,
+                threads_per_mesh_threadgroup,
+            } => encoder.draw_mesh_threads_threads_per_object_threadgroup_threads_per_mesh_threadgroup(
+                threads_per_grid,
+                threads_per_object_threadgroup,
+                threads_per_mesh_threadgroup,
+            ),
+        }
+    }
+
+    fn encode_compute_command(
+        &mut self,
+        index: usize,
+        command: &TypedIndirectComputeCommand<
+            &'a ProtocolObject<dyn MTLBuffer>,
+            &'a ProtocolObject<dyn MTLComputePipelineState>,
+        >,
+    ) {
+        let encoder = self.indirect_compute_command_at_index(index);
+        encoder.reset();
+        if let Some(pipeline_state) = command.pipeline_state {
+            encoder.set_compute_pipeline_state(pipeline_state);
+        }
+        for binding in &command.kernel_buffers {
+            match binding.attribute_stride {
+                Some(stride) => encoder.set_kernel_buffer_offset_attribute_stride_at_index(
+                    binding.buffer,
+                    binding.offset,
+                    stride,
+                    binding.index,
+                ),
+                None => encoder.set_kernel_buffer_offset_at_index(binding.buffer, binding.offset, binding.index),
+            }
+        }
+        for &(length, index) in &command.threadgroup_memory_lengths {
+            encoder.set_threadgroup_memory_length_at_index(length, index);
+        }
+        if let Some(region) = command.stage_in_region {
+            encoder.set_stage_in_region(region);
+        }
+        if let Some((width, height)) = command.imageblock_size {
+            encoder.set_imageblock_width_height(width, height);
+        }
+        if command.barrier {
+            encoder.set_barrier();
+        }
+        match command.dispatch {
+            IndirectDispatch::Threadgroups {
+                threadgroups_per_grid,
+                threads_per_threadgroup,
+            } => encoder.concurrent_dispatch_threadgroups_threads_per_threadgroup(
+                threadgroups_per_grid,
+                threads_per_threadgroup,
+            ),
+            IndirectDispatch::Threads {
+                threads_per_grid,
+                threads_per_threadgroup,
+            } => encoder.concurrent_dispatch_threads_threads_per_threadgroup(threads_per_grid, threads_per_threadgroup),
+        }
+    }
+
+    fn reset_range(
+        &mut self,
+        range: Range<usize>,
+    ) {
+        (**self).reset_with_range(range);
+    }
+}
+
+/// One operation captured by [`IndirectCommandRecorder`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RecordedIndirectCommand<B, RP, CP> {
+    Render {
+        index: usize,
+        command: TypedIndirectRenderCommand<B, RP>,
+    },
+    Compute {
+        index: usize,
+        command: TypedIndirectComputeCommand<B, CP>,
+    },
+    Reset {
+        range: Range<usize>,
+    },
+}
+
+/// An [`IndirectCommandSink`] that records every operation in order.
+///
+/// Handles can be any cloneable value, such as integers standing in for
+/// buffers and pipelines in tests.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndirectCommandRecorder<B, RP, CP> {
+    pub operations: Vec<RecordedIndirectCommand<B, RP, CP>>,
+}
+
+impl<B, RP, CP> Default for IndirectCommandRecorder<B, RP, CP> {
+    fn default() -> Self {
+        Self {
+            operations: Vec::new(),
+        }
+    }
+}
+
+impl<B: Clone, RP: Clone, CP: Clone> IndirectCommandSink<B, RP, CP> for IndirectCommandRecorder<B, RP, CP> {
+    fn encode_render_command(
+        &mut self,
+        index: usize,
+        command: &TypedIndirectRenderCommand<B, RP>,
+    ) {
+        self.operations.push(RecordedIndirectCommand::Render {
+            index,
+            command: command.clone(),
+        });
+    }
+
+    fn encode_compute_command(
+        &mut self,
+        index: usize,
+        command: &TypedIndirectComputeCommand<B, CP>,
+    ) {
+        self.operations.push(RecordedIndirectCommand::Compute {
+            index,
+            command: command.clone(),
+        });
+    }
+
+    fn reset_range(
+        &mut self,
+        range: Range<usize>,
+    ) {
+        self.operations.push(RecordedIndirectCommand::Reset {
+            range,
+        });
+    }
+}
+
+/// Validates typed commands against [`IndirectCommandBufferLimits`] and writes
+/// them to a sink, tracking which command slots hold encoded commands.
+///
+/// For Metal, pass `&*icb` as the sink and build the limits from the same
+/// descriptor and command count the buffer was created with.
+#[derive(Debug)]
+pub struct IndirectCommandBufferEncoder<S> {
+    sink: S,
+    limits: IndirectCommandBufferLimits,
+    encoded: Vec<bool>,
+}
+
+impl<S> IndirectCommandBufferEncoder<S> {
+    pub fn new(
+        sink: S,
+        limits: IndirectCommandBufferLimits,
+    ) -> Self {
+        Self {
+            sink,
+            encoded: vec![false; limits.max_command_count],
+            limits,
+        }
+    }
+
+    pub fn limits(&self) -> &IndirectCommandBufferLimits {
+        &self.limits
+    }
+
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    pub fn into_sink(self) -> S {
+        self.sink
+    }
+
+    /// Whether the slot at `index` holds a command encoded since its last reset.
+    pub fn is_encoded(
+        &self,
+        index: usize,
+    ) -> bool {
+        self.encoded.get(index).copied().unwrap_or(false)
+    }
+
+    /// Number of slots holding encoded commands.
+    pub fn encoded_count(&self) -> usize {
+        self.encoded.iter().filter(|&&encoded| encoded).count()
+    }
+
+    pub fn encode_render<B, RP, CP>(
+        &mut self,
+        index: usize,
+        command: &TypedIndirectRenderCommand<B, RP>,
+    ) -> Result<(), IndirectCommandError>
+    where
+        S: IndirectCommandSink<B, RP, CP>,
+    {
+        self.check_index(index)?;
+        command.validate(&self.limits)?;
+        self.sink.encode_render_command(index, command);
+        self.encoded[index] = true;
+        Ok(())
+    }
+
+    pub fn encode_compute<B, RP, CP>(
+        &mut self,
+        index: usize,
+        command: &TypedIndirectComputeCommand<B, CP>,
+    ) -> Result<(), IndirectCommandError>
+    where
+        S: IndirectCommandSink<B, RP, CP>,
+    {
+        self.check_index(index)?;
+        command.validate(&self.limits)?;
+        self.sink.encode_compute_command(index, command);
+        self.encoded[index] = true;
+        Ok(())
+    }
+
+    /// Resets the commands in `range` so they do nothing when executed.
+    pub fn reset<B, RP, CP>(
+        &mut self,
+        range: Range<usize>,
+    ) -> Result<(), IndirectCommandError>
+    where
+        S: IndirectCommandSink<B, RP, CP>,
+    {
+        self.check_range(&range)?;
+        self.encoded[range.clone()].fill(false);
+        self.sink.reset_range(range);
+        Ok(())
+    }
+
+    /// Converts `range` into the struct `execute_commands_in_buffer_indirect`
+    /// reads from an indirect range buffer, checking it against the command count.
+    pub fn execution_range(
+        &self,
+        range: Range<usize>,
+    ) -> Result<MTLIndirectCommandBufferExecutionRange, IndirectCommandError> {
+        self.check_range(&range)?;
+        let out_of_bounds = || IndirectCommandError::RangeOutOfBounds {
+            range: range.clone(),
+            count: self.limits.max_command_count,
+        };
+        Ok(MTLIndirectCommandBufferExecutionRange {
+            location: u32::try_from(range.start).map_err(|_| out_of_bounds())?,
+            length: u32::try_from(range.len()).map_err(|_| out_of_bounds())?,
+        })
+    }
+
+    fn check_index(
+        &self,
+        index: usize,
+    ) -> Result<(), IndirectCommandError> {
+        if index < self.limits.max_command_count {
+            Ok(())
+        } else {
+            Err(IndirectCommandError::IndexOutOfRange {
+                index,
+                count: self.limits.max_command_count,
+            })
+        }
+    }
+
+    fn check_range(
+        &self,
+        range: &Range<usize>,
+    ) -> Result<(), IndirectCommandError> {
+        if range.start <= range.end && range.end <= self.limits.max_command_count {
+            Ok(())
+        } else {
+            Err(IndirectCommandError::RangeOutOfBounds {
+                range: range.clone(),
+                count: self.limits.max_command_count,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Recorder = IndirectCommandRecorder<u32, &'static str, &'static str>;
+
+    fn render_limits() -> IndirectCommandBufferLimits {
+        IndirectCommandBufferLimits {
+            command_types: MTLIndirectCommandType::Draw | MTLIndirectCommandType::DrawIndexed,
+            max_command_count: 4,
+            inherit_pipeline_state: false,
+            inherit_buffers: false,
+            max_vertex_buffer_bind_count: 2,
+            max_fragment_buffer_bind_count: 1,
+            max_kernel_buffer_bind_count: 0,
+            max_kernel_threadgroup_memory_bind_count: 0,
+            max_object_buffer_bind_count: 0,
+            max_mesh_buffer_bind_count: 0,
+            support_dynamic_attribute_stride: false,
+        }
+    }
+
+    fn compute_limits() -> IndirectCommandBufferLimits {
+        IndirectCommandBufferLimits {
+            command_types: MTLIndirectCommandType::ConcurrentDispatch,
+            max_kernel_buffer_bind_count: 4,
+            max_kernel_threadgroup_memory_bind_count: 1,
+            max_vertex_buffer_bind_count: 0,
+            max_fragment_buffer_bind_count: 0,
+            ..render_limits()
+        }
+    }
+
+    fn draw() -> TypedIndirectRenderCommand<u32, &'static str> {
+        TypedIndirectRenderCommand::new(IndirectDraw::Primitives {
+            primitive_type: MTLPrimitiveType::Triangle,
+            vertex_start: 0,
+            vertex_count: 3,
+            instance_count: 1,
+            base_instance: 0,
+        })
+        .with_pipeline_state("pipeline")
+    }
+
+    fn dispatch() -> TypedIndirectComputeCommand<u32, &'static str> {
+        TypedIndirectComputeCommand::new(IndirectDispatch::Threadgroups {
+            threadgroups_per_grid: MTLSize::new(8, 1, 1),
+            threads_per_threadgroup: MTLSize::new(64, 1, 1),
+        })
+        .with_pipeline_state("kernel")
+    }
+
+    fn patches(indexed: bool) -> IndirectDraw<u32> {
+        if indexed {
+            IndirectDraw::IndexedPatches {
+                number_of_patch_control_points: 4,
+                patch_start: 0,
+                patch_count: 16,
+                patch_index_buffer: None,
+                patch_index_buffer_offset: 0,
+                control_point_index_buffer: 2,
+                control_point_index_buffer_offset: 0,
+                instance_count: 1,
+                base_instance: 0,
+                tessellation_factor_buffer: 1,
+                tessellation_factor_buffer_offset: 0,
+                tessellation_factor_buffer_instance_stride: 0,
+            }
+        } else {
+            IndirectDraw::Patches {
+                number_of_patch_control_points: 4,
+                patch_start: 0,
+                patch_count: 16,
+                patch_index_buffer: Some(3),
+                patch_index_buffer_offset: 0,
+                instance_count: 1,
+                base_instance: 0,
+                tessellation_factor_buffer: 1,
+                tessellation_factor_buffer_offset: 0,
+                tessellation_factor_buffer_instance_stride: 0,
+            }
+        }
+    }
+
+    #[test]
+    fn records_valid_commands() {
+        let mut encoder = IndirectCommandBufferEncoder::new(Recorder::default(), render_limits());
+        let command = draw().with_vertex_buffer(IndirectBufferBinding::new(7, 0, 1)).with_barrier();
+        encoder.encode_render(2, &command).unwrap();
+        assert!(encoder.is_encoded(2));
+        assert_eq!(encoder.encoded_count(), 1);
+        assert_eq!(
+            encoder.sink().operations,
+            [RecordedIndirectCommand::Render {
+                index: 2,
+                command,
+            }]
+        );
+    }
+
+    #[test]
+    fn bind_count_overflow() {
+        let mut encoder = IndirectCommandBufferEncoder::new(Recorder::default(), render_limits());
+        let command = draw().with_vertex_buffer(IndirectBufferBinding::new(7, 0, 2));
+        assert_eq!(
+            encoder.encode_render(0, &command),
+            Err(IndirectCommandError::BindIndexOutOfRange {
+                stage: IndirectBindingStage::Vertex,
+                index: 2,
+                max_bind_count: 2,
+            })
+        );
+        let command = draw().with_fragment_buffer(IndirectBufferBinding::new(7, 0, 1));
+        assert_eq!(
+            encoder.encode_render(0, &command),
+            Err(IndirectCommandError::BindIndexOutOfRange {
+                stage: IndirectBindingStage::Fragment,
+                index: 1,
+                max_bind_count: 1,
+            })
+        );
+        let command = draw().with_vertex_buffer(IndirectBufferBinding {
+            attribute_stride: Some(16),
+            ..IndirectBufferBinding::new(7, 0, 0)
+        });
+        assert_eq!(encoder.encode_render(0, &command), Err(IndirectCommandError::DynamicAttributeStrideUnsupported));
+
+        let mut encoder = IndirectCommandBufferEncoder::new(Recorder::default(), compute_limits());
+        let command = dispatch().with_threadgroup_memory_length(1024, 1);
+        assert_eq!(
+            encoder.encode_compute(0, &command),
+            Err(IndirectCommandError::BindIndexOutOfRange {
+                stage: IndirectBindingStage::KernelThreadgroupMemory,
+                index: 1,
+                max_bind_count: 1,
+            })
+        );
+
+        // Rejected commands never reach the sink.
+        assert!(encoder.sink().operations.is_empty());
+        assert_eq!(encoder.encoded_count(), 0);
+    }
+
+    #[test]
+    fn disallowed_command_types() {
+        let mut encoder = IndirectCommandBufferEncoder::new(Recorder::default(), render_limits());
+        let command = TypedIndirectRenderCommand::new(patches(false)).with_pipeline_state("pipeline");
+        assert_eq!(
+            encoder.encode_render(0, &command),
+            Err(IndirectCommandError::CommandTypeNotAllowed {
+                required: MTLIndirectCommandType::DrawPatches,
+                allowed: render_limits().command_types,
+            })
+        );
+        assert_eq!(encoder.encode_compute(0, &dispatch()), Err(IndirectCommandError::WrongCommandKind));
+
+        let mut encoder = IndirectCommandBufferEncoder::new(Recorder::default(), compute_limits());
+        assert_eq!(encoder.encode_render(0, &draw()), Err(IndirectCommandError::WrongCommandKind));
+        let command = TypedIndirectComputeCommand::new(IndirectDispatch::Threads {
+            threads_per_grid: MTLSize::new(100, 1, 1),
+            threads_per_threadgroup: MTLSize::new(64, 1, 1),
+        })
+        .with_pipeline_state("kernel");
+        assert_eq!(
+            encoder.encode_compute(0, &command),
+            Err(IndirectCommandError::CommandTypeNotAllowed {
+                required: MTLIndirectCommandType::ConcurrentDispatchThreads,
+                allowed: MTLIndirectCommandType::ConcurrentDispatch,
+            })
+        );
+        assert!(encoder.sink().operations.is_empty());
+    }
+
+    #[test]
+    fn patch_draws() {
+        let limits = IndirectCommandBufferLimits {
+            command_types: MTLIndirectCommandType::DrawPatches | MTLIndirectCommandType::DrawIndexedPatches,
+            ..render_limits()
+        };
+        let mut encoder = IndirectCommandBufferEncoder::new(Recorder::default(), limits);
+        for (index, indexed) in [false, true].into_iter().enumerate() {
+            let command = TypedIndirectRenderCommand::new(patches(indexed)).with_pipeline_state("pipeline");
+            encoder.encode_render(index, &command).unwrap();
+        }
+        assert_eq!(encoder.encoded_count(), 2);
+        let command = TypedIndirectRenderCommand::new(draw().draw).with_pipeline_state("pipeline");
+        assert_eq!(
+            encoder.encode_render(2, &command),
+            Err(IndirectCommandError::CommandTypeNotAllowed {
+                required: MTLIndirectCommandType::Draw,
+                allowed: limits.command_types,
+            })
+        );
+    }
+
+    #[test]
+    fn inherit_flags() {
+        let limits = IndirectCommandBufferLimits {
+            inherit_pipeline_state: true,
+            inherit_buffers: true,
+            ..render_limits()
+        };
+        let mut encoder = IndirectCommandBufferEncoder::new(Recorder::default(), limits);
+        assert_eq!(encoder.encode_render(0, &draw()), Err(IndirectCommandError::PipelineStateInherited));
+        let inherited = TypedIndirectRenderCommand::new(draw().draw);
+        encoder.encode_render(0, &inherited).unwrap();
+        let command = inherited.clone().with_vertex_buffer(IndirectBufferBinding::new(7, 0, 0));
+        assert_eq!(encoder.encode_render(1, &command), Err(IndirectCommandError::BuffersInherited));
+
+        let mut encoder = IndirectCommandBufferEncoder::new(Recorder::default(), render_limits());
+        assert_eq!(encoder.encode_render(0, &inherited), Err(IndirectCommandError::PipelineStateMissing));
+    }
+
+    #[test]
+    fn range_resets() {
+        let mut encoder = IndirectCommandBufferEncoder::new(Recorder::default(), render_limits());
+        for index in 0..4 {
+            encoder.encode_render(index, &draw()).unwrap();
+        }
+        assert_eq!(
+            encoder.encode_render(4, &draw()),
+            Err(IndirectCommandError::IndexOutOfRange {
+                index: 4,
+                count: 4,
+            })
+        );
+
+        encoder.reset(1..3).unwrap();
+        assert_eq!((0..4).map(|index| encoder.is_encoded(index)).collect::<Vec<_>>(), [true, false, false, true]);
+        assert_eq!(
+            encoder.sink().operations.last(),
+            Some(&RecordedIndirectCommand::Reset {
+                range: 1..3,
+            })
+        );
+        assert_eq!(
+            encoder.reset(2..5),
+            Err(IndirectCommandError::RangeOutOfBounds {
+                range: 2..5,
+                count: 4,
+            })
+        );
+        assert_eq!(encoder.encoded_count(), 2);
+
+        let range = encoder.execution_range(1..4).unwrap();
+        assert_eq!((range.location, range.length), (1, 3));
+        assert!(encoder.execution_range(0..5).is_err());
+    }
+}
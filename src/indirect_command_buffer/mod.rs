@@ -1,7 +1,13 @@
 mod buffer;
 mod descriptor;
+mod encoder;
 mod types;
 
 pub use buffer::{MTLIndirectCommandBuffer, MTLIndirectCommandBufferExt};
 pub use descriptor::MTLIndirectCommandBufferDescriptor;
+pub use encoder::{
+    IndirectBindingStage, IndirectBufferBinding, IndirectCommandBufferEncoder, IndirectCommandBufferLimits,
+    IndirectCommandError, IndirectCommandRecorder, IndirectCommandSink, IndirectDispatch, IndirectDraw,
+    RecordedIndirectCommand, TypedIndirectComputeCommand, TypedIndirectRenderCommand,
+};
 pub use types::{MTLIndirectCommandBufferExecutionRange, MTLIndirectCommandType};
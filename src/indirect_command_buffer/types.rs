@@ -41,7 +41,7 @@ unsafe impl RefEncode for MTLIndirectCommandType {
 ///
 /// Availability: macOS 10.14+, Mac Catalyst 13.0+, iOS 13.0+
 #[repr(C)]
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct MTLIndirectCommandBufferExecutionRange {
     pub location: u32,
     pub length: u32,
@@ -54,3 +54,6 @@ unsafe impl Encode for MTLIndirectCommandBufferExecutionRange {
 unsafe impl RefEncode for MTLIndirectCommandBufferExecutionRange {
     const ENCODING_REF: Encoding = Encoding::Pointer(&Self::ENCODING);
 }
+
+const _: () = assert!(size_of::<MTLIndirectCommandBufferExecutionRange>() == 8);
+const _: () = assert!(align_of::<MTLIndirectCommandBufferExecutionRange>() == 4);
@@ -0,0 +1,377 @@
+//! Just enough JSON to read safetensors headers and device capability
+//! snapshots, and to write traces and snapshots.
+
+use std::io;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum JsonValue {
+    Null,
+    Bool(bool),
+    /// The number's source text, so 64-bit offsets survive unrounded.
+    Number(String),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    pub(crate) fn parse(text: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            bytes: text.as_bytes(),
+            position: 0,
+        };
+        let value = parser.value(0)?;
+        parser.skip_whitespace();
+        if parser.position != parser.bytes.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        Ok(value)
+    }
+
+    pub(crate) fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_u64(&self) -> Option<u64> {
+        match self {
+            Self::Number(text) => text.parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            Self::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_object(&self) -> Option<&[(String, JsonValue)]> {
+        match self {
+            Self::Object(members) => Some(members),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn get(
+        &self,
+        key: &str,
+    ) -> Option<&JsonValue> {
+        self.as_object()?.iter().find(|(name, _)| name == key).map(|(_, value)| value)
+    }
+}
+
+/// Nesting limit, so hostile headers can't overflow the stack.
+const MAX_DEPTH: usize = 64;
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(
+        &self,
+        message: &str,
+    ) -> String {
+        format!("{message} at byte {}", self.position)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.bytes.get(self.position) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(
+        &mut self,
+        byte: u8,
+    ) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.bytes.get(self.position) != Some(&byte) {
+            return Err(self.error(&format!("expected '{}'", byte as char)));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn literal(
+        &mut self,
+        literal: &str,
+        value: JsonValue,
+    ) -> Result<JsonValue, String> {
+        if !self.bytes[self.position..].starts_with(literal.as_bytes()) {
+            return Err(self.error("invalid literal"));
+        }
+        self.position += literal.len();
+        Ok(value)
+    }
+
+    fn value(
+        &mut self,
+        depth: usize,
+    ) -> Result<JsonValue, String> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nesting too deep"));
+        }
+        self.skip_whitespace();
+        match self.bytes.get(self.position) {
+            Some(b'{') => self.object(depth),
+            Some(b'[') => self.array(depth),
+            Some(b'"') => self.string().map(JsonValue::String),
+            Some(b't') => self.literal("true", JsonValue::Bool(true)),
+            Some(b'f') => self.literal("false", JsonValue::Bool(false)),
+            Some(b'n') => self.literal("null", JsonValue::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn object(
+        &mut self,
+        depth: usize,
+    ) -> Result<JsonValue, String> {
+        self.position += 1;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.bytes.get(self.position) == Some(&b'}') {
+            self.position += 1;
+            return Ok(JsonValue::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.bytes.get(self.position) != Some(&b'"') {
+                return Err(self.error("expected a string key"));
+            }
+            let key = self.string()?;
+            self.expect(b':')?;
+            members.push((key, self.value(depth + 1)?));
+            self.skip_whitespace();
+            match self.bytes.get(self.position) {
+                Some(b',') => self.position += 1,
+                Some(b'}') => {
+                    self.position += 1;
+                    return Ok(JsonValue::Object(members));
+                },
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(
+        &mut self,
+        depth: usize,
+    ) -> Result<JsonValue, String> {
+        self.position += 1;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.bytes.get(self.position) == Some(&b']') {
+            self.position += 1;
+            return Ok(JsonValue::Array(values));
+        }
+        loop {
+            values.push(self.value(depth + 1)?);
+            self.skip_whitespace();
+            match self.bytes.get(self.position) {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(JsonValue::Array(values));
+                },
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<JsonValue, String> {
+        let start = self.position;
+        while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') = self.bytes.get(self.position) {
+            self.position += 1;
+        }
+        let text = core::str::from_utf8(&self.bytes[start..self.position]).map_err(|_| self.error("invalid number"))?;
+        if text.parse::<f64>().is_err() {
+            return Err(self.error("invalid number"));
+        }
+        Ok(JsonValue::Number(text.to_owned()))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.position += 1;
+        let mut value = String::new();
+        loop {
+            let start = self.position;
+            while let Some(&byte) = self.bytes.get(self.position) {
+                if byte == b'"' || byte == b'\\' || byte < 0x20 {
+                    break;
+                }
+                self.position += 1;
+            }
+            value.push_str(
+                core::str::from_utf8(&self.bytes[start..self.position]).map_err(|_| self.error("invalid UTF-8"))?,
+            );
+            match self.bytes.get(self.position) {
+                Some(b'"') => {
+                    self.position += 1;
+                    return Ok(value);
+                },
+                Some(b'\\') => {
+                    self.position += 1;
+                    let escape = *self.bytes.get(self.position).ok_or_else(|| self.error("unterminated escape"))?;
+                    self.position += 1;
+                    match escape {
+                        b'"' => value.push('"'),
+                        b'\\' => value.push('\\'),
+                        b'/' => value.push('/'),
+                        b'b' => value.push('\u{8}'),
+                        b'f' => value.push('\u{c}'),
+                        b'n' => value.push('\n'),
+                        b'r' => value.push('\r'),
+                        b't' => value.push('\t'),
+                        b'u' => value.push(self.unicode_escape()?),
+                        _ => return Err(self.error("invalid escape")),
+                    }
+                },
+                Some(_) => return Err(self.error("control character in string")),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits = self.bytes.get(self.position..self.position + 4).ok_or_else(|| self.error("truncated escape"))?;
+        let digits = core::str::from_utf8(digits).map_err(|_| self.error("invalid escape"))?;
+        let value = u32::from_str_radix(digits, 16).map_err(|_| self.error("invalid escape"))?;
+        self.position += 4;
+        Ok(value)
+    }
+
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        let code_point = if (0xd800..0xdc00).contains(&high) {
+            if !self.bytes[self.position..].starts_with(b"\\u") {
+                return Err(self.error("unpaired surrogate"));
+            }
+            self.position += 2;
+            let low = self.hex4()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(self.error("unpaired surrogate"));
+            }
+            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+        } else {
+            high
+        };
+        char::from_u32(code_point).ok_or_else(|| self.error("invalid code point"))
+    }
+}
+
+/// Writes `value` as a JSON string, escaping quotes, backslashes and control
+/// characters.
+pub(crate) fn write_json_string(
+    writer: &mut impl io::Write,
+    value: &str,
+) -> io::Result<()> {
+    writer.write_all(b"\"")?;
+    let mut start = 0;
+    for (index, c) in value.char_indices() {
+        let escape: Option<&str> = match c {
+            '"' => Some("\\\""),
+            '\\' => Some("\\\\"),
+            '\n' => Some("\\n"),
+            '\r' => Some("\\r"),
+            '\t' => Some("\\t"),
+            _ => None,
+        };
+        if escape.is_none() && !c.is_control() {
+            continue;
+        }
+        writer.write_all(&value.as_bytes()[start..index])?;
+        match escape {
+            Some(escape) => writer.write_all(escape.as_bytes())?,
+            None => write!(writer, "\\u{:04x}", c as u32)?,
+        }
+        start = index + c.len_utf8();
+    }
+    writer.write_all(&value.as_bytes()[start..])?;
+    writer.write_all(b"\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json_string(value: &str) -> String {
+        let mut bytes = Vec::new();
+        write_json_string(&mut bytes, value).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn string_round_trip() {
+        let cases = [
+            ("", "\"\""),
+            ("plain", "\"plain\""),
+            ("say \"hi\"\\", "\"say \\\"hi\\\"\\\\\""),
+            ("line\nbreak\r\ttab", "\"line\\nbreak\\r\\ttab\""),
+            ("\u{1}\u{1f}\u{7f}", "\"\\u0001\\u001f\\u007f\""),
+            ("Apple M1 — 🍎", "\"Apple M1 — 🍎\""),
+        ];
+        for (value, written) in cases {
+            assert_eq!(json_string(value), written);
+            assert_eq!(JsonValue::parse(written), Ok(JsonValue::String(value.to_owned())));
+        }
+    }
+
+    #[test]
+    fn parses_values() {
+        let value = JsonValue::parse(
+            " {\"a\": [1, -2.5e3, true, false, null], \"b\": {}, \"c\": \"\\u00e9\\ud83c\\udf4e\\/\"} ",
+        )
+        .unwrap();
+        let array = value.get("a").and_then(JsonValue::as_array).unwrap();
+        assert_eq!(array[0].as_u64(), Some(1));
+        assert_eq!(array[1], JsonValue::Number("-2.5e3".to_owned()));
+        assert_eq!(array[1].as_u64(), None);
+        assert_eq!(array[2].as_bool(), Some(true));
+        assert_eq!(array[3].as_bool(), Some(false));
+        assert_eq!(array[4], JsonValue::Null);
+        assert_eq!(value.get("b").and_then(JsonValue::as_object), Some(&[][..]));
+        assert_eq!(value.get("c").and_then(JsonValue::as_str), Some("é🍎/"));
+        assert_eq!(value.get("d"), None);
+        // Offsets past 2^53 keep every digit.
+        assert_eq!(JsonValue::parse("18446744073709551615").unwrap().as_u64(), Some(u64::MAX));
+    }
+
+    #[test]
+    fn rejects_malformed_json() {
+        let cases = [
+            ("", "unexpected end of input at byte 0"),
+            ("{\"a\" 1}", "expected ':' at byte 5"),
+            ("{1: 2}", "expected a string key at byte 1"),
+            ("[1 2]", "expected ',' or ']' at byte 3"),
+            ("{\"a\": 1,}", "expected a string key at byte 8"),
+            ("tru", "invalid literal at byte 0"),
+            ("1.2.3", "invalid number at byte 5"),
+            ("\"open", "unterminated string at byte 5"),
+            ("\"\\x\"", "invalid escape at byte 3"),
+            ("\"\\ud83c\"", "unpaired surrogate at byte 7"),
+            ("\"a\nb\"", "control character in string at byte 2"),
+            ("{} {}", "trailing characters at byte 3"),
+        ];
+        for (text, error) in cases {
+            assert_eq!(JsonValue::parse(text), Err(error.to_owned()), "{text:?}");
+        }
+        let deep = "[".repeat(MAX_DEPTH + 2);
+        assert_eq!(JsonValue::parse(&deep), Err(format!("nesting too deep at byte {}", MAX_DEPTH + 1)));
+    }
+}
@@ -17,9 +17,11 @@ mod compute_command_encoder;
 mod compute_pass;
 mod compute_pipeline;
 mod counters;
+mod culling;
 mod data_type;
 mod depth_stencil;
 mod device;
+mod device_capabilities;
 mod device_certification;
 mod drawable;
 mod dynamic_library;
@@ -31,12 +33,16 @@ mod function_handle;
 mod function_log;
 mod function_stitching;
 mod gpu_address;
+mod gpu_profiler;
 mod heap;
+mod image_layout;
+mod indirect_arguments;
 mod indirect_command_buffer;
 mod indirect_command_encoder;
 mod intersection_function_table;
 mod io_command_buffer;
 mod io_compressor;
+pub(crate) mod json;
 mod library;
 mod linked_functions;
 mod log_state;
@@ -54,13 +60,25 @@ mod resource_state_command_encoder;
 mod resource_state_pass;
 mod resource_view_pool;
 mod sampler;
+mod sharing;
+mod sparse_buffer;
+mod sparse_texture;
 mod stage_input_output_descriptor;
+pub(crate) mod staging_buffer;
 mod tensor;
 mod texture;
+mod texture_container;
+mod texture_decompress;
+mod texture_readback;
+mod texture_swizzle;
+mod texture_upload;
+mod texture_validation;
+mod trace_export;
 mod types;
 pub(crate) mod util;
 mod vertex_descriptor;
 mod visible_function_table;
+mod weights;
 
 pub mod prelude;
 pub use acceleration_structure::*;
@@ -83,9 +101,11 @@ pub use compute_command_encoder::*;
 pub use compute_pass::*;
 pub use compute_pipeline::*;
 pub use counters::*;
+pub use culling::*;
 pub use data_type::*;
 pub use depth_stencil::*;
 pub use device::*;
+pub use device_capabilities::*;
 pub use device_certification::*;
 pub use drawable::*;
 pub use dynamic_library::*;
@@ -97,7 +117,10 @@ pub use function_handle::*;
 pub use function_log::*;
 pub use function_stitching::*;
 pub use gpu_address::*;
+pub use gpu_profiler::*;
 pub use heap::*;
+pub use image_layout::*;
+pub use indirect_arguments::*;
 pub use indirect_command_buffer::*;
 pub use indirect_command_encoder::*;
 pub use intersection_function_table::*;
@@ -120,9 +143,20 @@ pub use resource_state_command_encoder::*;
 pub use resource_state_pass::*;
 pub use resource_view_pool::*;
 pub use sampler::*;
+pub use sharing::*;
+pub use sparse_buffer::*;
+pub use sparse_texture::*;
 pub use stage_input_output_descriptor::*;
 pub use tensor::*;
 pub use texture::*;
+pub use texture_container::*;
+pub use texture_decompress::*;
+pub use texture_readback::*;
+pub use texture_swizzle::*;
+pub use texture_upload::*;
+pub use texture_validation::*;
+pub use trace_export::*;
 pub use types::*;
 pub use vertex_descriptor::*;
 pub use visible_function_table::*;
+pub use weights::*;
@@ -194,6 +194,333 @@ pub enum MTLPixelFormat {
     BGRA10_XR_sRGB = 553,
 }
 
+impl MTLPixelFormat {
+    /// Every pixel format, in ascending raw value order.
+    pub const ALL: &[Self] = &[
+        Self::Invalid,
+        Self::A8Unorm,
+        Self::R8Unorm,
+        Self::R8UnormSrgb,
+        Self::R8Snorm,
+        Self::R8Uint,
+        Self::R8Sint,
+        Self::R16Unorm,
+        Self::R16Snorm,
+        Self::R16Uint,
+        Self::R16Sint,
+        Self::R16Float,
+        Self::RG8Unorm,
+        Self::RG8UnormSrgb,
+        Self::RG8Snorm,
+        Self::RG8Uint,
+        Self::RG8Sint,
+        Self::B5G6R5Unorm,
+        Self::A1BGR5Unorm,
+        Self::ABGR4Unorm,
+        Self::BGR5A1Unorm,
+        Self::R32Uint,
+        Self::R32Sint,
+        Self::R32Float,
+        Self::RG16Unorm,
+        Self::RG16Snorm,
+        Self::RG16Uint,
+        Self::RG16Sint,
+        Self::RG16Float,
+        Self::RGBA8Unorm,
+        Self::RGBA8UnormSrgb,
+        Self::RGBA8Snorm,
+        Self::RGBA8Uint,
+        Self::RGBA8Sint,
+        Self::BGRA8Unorm,
+        Self::BGRA8UnormSrgb,
+        Self::RGB10A2Unorm,
+        Self::RGB10A2Uint,
+        Self::RG11B10Float,
+        Self::RGB9E5Float,
+        Self::BGR10A2Unorm,
+        Self::RG32Uint,
+        Self::RG32Sint,
+        Self::RG32Float,
+        Self::RGBA16Unorm,
+        Self::RGBA16Snorm,
+        Self::RGBA16Uint,
+        Self::RGBA16Sint,
+        Self::RGBA16Float,
+        Self::RGBA32Uint,
+        Self::RGBA32Sint,
+        Self::RGBA32Float,
+        Self::BC1_RGBA,
+        Self::BC1_RGBA_sRGB,
+        Self::BC2_RGBA,
+        Self::BC2_RGBA_sRGB,
+        Self::BC3_RGBA,
+        Self::BC3_RGBA_sRGB,
+        Self::BC4_RUnorm,
+        Self::BC4_RSnorm,
+        Self::BC5_RGUnorm,
+        Self::BC5_RGSnorm,
+        Self::BC6H_RGBFloat,
+        Self::BC6H_RGBUfloat,
+        Self::BC7_RGBAUnorm,
+        Self::BC7_RGBAUnorm_sRGB,
+        Self::PVRTC_RGB_2BPP,
+        Self::PVRTC_RGB_2BPP_sRGB,
+        Self::PVRTC_RGB_4BPP,
+        Self::PVRTC_RGB_4BPP_sRGB,
+        Self::PVRTC_RGBA_2BPP,
+        Self::PVRTC_RGBA_2BPP_sRGB,
+        Self::PVRTC_RGBA_4BPP,
+        Self::PVRTC_RGBA_4BPP_sRGB,
+        Self::EAC_R11Unorm,
+        Self::EAC_R11Snorm,
+        Self::EAC_RG11Unorm,
+        Self::EAC_RG11Snorm,
+        Self::EAC_RGBA8,
+        Self::EAC_RGBA8_sRGB,
+        Self::ETC2_RGB8,
+        Self::ETC2_RGB8_sRGB,
+        Self::ETC2_RGB8A1,
+        Self::ETC2_RGB8A1_sRGB,
+        Self::ASTC_4x4_sRGB,
+        Self::ASTC_5x4_sRGB,
+        Self::ASTC_5x5_sRGB,
+        Self::ASTC_6x5_sRGB,
+        Self::ASTC_6x6_sRGB,
+        Self::ASTC_8x5_sRGB,
+        Self::ASTC_8x6_sRGB,
+        Self::ASTC_8x8_sRGB,
+        Self::ASTC_10x5_sRGB,
+        Self::ASTC_10x6_sRGB,
+        Self::ASTC_10x8_sRGB,
+        Self::ASTC_10x10_sRGB,
+        Self::ASTC_12x10_sRGB,
+        Self::ASTC_12x12_sRGB,
+        Self::ASTC_4x4_LDR,
+        Self::ASTC_5x4_LDR,
+        Self::ASTC_5x5_LDR,
+        Self::ASTC_6x5_LDR,
+        Self::ASTC_6x6_LDR,
+        Self::ASTC_8x5_LDR,
+        Self::ASTC_8x6_LDR,
+        Self::ASTC_8x8_LDR,
+        Self::ASTC_10x5_LDR,
+        Self::ASTC_10x6_LDR,
+        Self::ASTC_10x8_LDR,
+        Self::ASTC_10x10_LDR,
+        Self::ASTC_12x10_LDR,
+        Self::ASTC_12x12_LDR,
+        Self::ASTC_4x4_HDR,
+        Self::ASTC_5x4_HDR,
+        Self::ASTC_5x5_HDR,
+        Self::ASTC_6x5_HDR,
+        Self::ASTC_6x6_HDR,
+        Self::ASTC_8x5_HDR,
+        Self::ASTC_8x6_HDR,
+        Self::ASTC_8x8_HDR,
+        Self::ASTC_10x5_HDR,
+        Self::ASTC_10x6_HDR,
+        Self::ASTC_10x8_HDR,
+        Self::ASTC_10x10_HDR,
+        Self::ASTC_12x10_HDR,
+        Self::ASTC_12x12_HDR,
+        Self::GBGR422,
+        Self::BGRG422,
+        Self::Depth16Unorm,
+        Self::Depth32Float,
+        Self::Stencil8,
+        Self::Depth24Unorm_Stencil8,
+        Self::Depth32Float_Stencil8,
+        Self::X32_Stencil8,
+        Self::X24_Stencil8,
+        Self::Unspecialized,
+        Self::BGRA10_XR,
+        Self::BGRA10_XR_sRGB,
+    ];
+
+    /// Whether pixels are stored in compressed blocks: BC, EAC/ETC2, ASTC or
+    /// PVRTC.
+    pub const fn is_compressed(self) -> bool {
+        matches!(self as u64, 130..=236)
+    }
+
+    /// Whether the format is one of the BC1 to BC7 formats.
+    pub const fn is_bc(self) -> bool {
+        matches!(self as u64, 130..=153)
+    }
+
+    pub const fn is_pvrtc(self) -> bool {
+        matches!(self as u64, 160..=167)
+    }
+
+    /// Whether the format is one of the EAC or ETC2 formats.
+    pub const fn is_eac_etc2(self) -> bool {
+        matches!(self as u64, 170..=183)
+    }
+
+    /// Whether the format is ASTC of any profile: sRGB, LDR or HDR.
+    pub const fn is_astc(self) -> bool {
+        matches!(self as u64, 186..=236)
+    }
+
+    pub const fn is_astc_hdr(self) -> bool {
+        matches!(self as u64, 222..=236)
+    }
+
+    /// Whether color is stored sRGB-encoded and linearized when sampled.
+    pub const fn is_srgb(self) -> bool {
+        matches!(
+            self,
+            Self::R8UnormSrgb
+                | Self::RG8UnormSrgb
+                | Self::RGBA8UnormSrgb
+                | Self::BGRA8UnormSrgb
+                | Self::BC1_RGBA_sRGB
+                | Self::BC2_RGBA_sRGB
+                | Self::BC3_RGBA_sRGB
+                | Self::BC7_RGBAUnorm_sRGB
+                | Self::PVRTC_RGB_2BPP_sRGB
+                | Self::PVRTC_RGB_4BPP_sRGB
+                | Self::PVRTC_RGBA_2BPP_sRGB
+                | Self::PVRTC_RGBA_4BPP_sRGB
+                | Self::EAC_RGBA8_sRGB
+                | Self::ETC2_RGB8_sRGB
+                | Self::ETC2_RGB8A1_sRGB
+                | Self::ASTC_4x4_sRGB
+                | Self::ASTC_5x4_sRGB
+                | Self::ASTC_5x5_sRGB
+                | Self::ASTC_6x5_sRGB
+                | Self::ASTC_6x6_sRGB
+                | Self::ASTC_8x5_sRGB
+                | Self::ASTC_8x6_sRGB
+                | Self::ASTC_8x8_sRGB
+                | Self::ASTC_10x5_sRGB
+                | Self::ASTC_10x6_sRGB
+                | Self::ASTC_10x8_sRGB
+                | Self::ASTC_10x10_sRGB
+                | Self::ASTC_12x10_sRGB
+                | Self::ASTC_12x12_sRGB
+                | Self::BGRA10_XR_sRGB
+        )
+    }
+
+    /// The format with the same layout, without sRGB encoding; the format
+    /// itself if it isn't sRGB.
+    pub fn linear_format(self) -> Self {
+        if !self.is_srgb() {
+            return self;
+        }
+        // The linear formats precede their sRGB counterparts, except ASTC,
+        // whose LDR range follows the sRGB one.
+        let linear = match self as u64 {
+            value if self.is_astc() => value + 18,
+            value => value - 1,
+        };
+        Self::try_from(linear).expect("every sRGB format has a linear counterpart")
+    }
+
+    /// Width and height in pixels of the blocks the format stores; `(1, 1)`
+    /// for formats that store individual pixels.
+    pub const fn block_dimensions(self) -> (usize, usize) {
+        match self {
+            Self::PVRTC_RGB_2BPP | Self::PVRTC_RGB_2BPP_sRGB | Self::PVRTC_RGBA_2BPP | Self::PVRTC_RGBA_2BPP_sRGB => {
+                (8, 4)
+            },
+            Self::ASTC_4x4_sRGB | Self::ASTC_4x4_LDR | Self::ASTC_4x4_HDR => (4, 4),
+            Self::ASTC_5x4_sRGB | Self::ASTC_5x4_LDR | Self::ASTC_5x4_HDR => (5, 4),
+            Self::ASTC_5x5_sRGB | Self::ASTC_5x5_LDR | Self::ASTC_5x5_HDR => (5, 5),
+            Self::ASTC_6x5_sRGB | Self::ASTC_6x5_LDR | Self::ASTC_6x5_HDR => (6, 5),
+            Self::ASTC_6x6_sRGB | Self::ASTC_6x6_LDR | Self::ASTC_6x6_HDR => (6, 6),
+            Self::ASTC_8x5_sRGB | Self::ASTC_8x5_LDR | Self::ASTC_8x5_HDR => (8, 5),
+            Self::ASTC_8x6_sRGB | Self::ASTC_8x6_LDR | Self::ASTC_8x6_HDR => (8, 6),
+            Self::ASTC_8x8_sRGB | Self::ASTC_8x8_LDR | Self::ASTC_8x8_HDR => (8, 8),
+            Self::ASTC_10x5_sRGB | Self::ASTC_10x5_LDR | Self::ASTC_10x5_HDR => (10, 5),
+            Self::ASTC_10x6_sRGB | Self::ASTC_10x6_LDR | Self::ASTC_10x6_HDR => (10, 6),
+            Self::ASTC_10x8_sRGB | Self::ASTC_10x8_LDR | Self::ASTC_10x8_HDR => (10, 8),
+            Self::ASTC_10x10_sRGB | Self::ASTC_10x10_LDR | Self::ASTC_10x10_HDR => (10, 10),
+            Self::ASTC_12x10_sRGB | Self::ASTC_12x10_LDR | Self::ASTC_12x10_HDR => (12, 10),
+            Self::ASTC_12x12_sRGB | Self::ASTC_12x12_LDR | Self::ASTC_12x12_HDR => (12, 12),
+            Self::GBGR422 | Self::BGRG422 => (2, 1),
+            _ if self.is_compressed() => (4, 4),
+            _ => (1, 1),
+        }
+    }
+
+    /// Bytes per block, or per pixel for formats that store individual
+    /// pixels. `None` for `Invalid` and `Unspecialized`.
+    pub const fn bytes_per_block(self) -> Option<usize> {
+        let bytes = match self {
+            Self::Invalid | Self::Unspecialized => return None,
+            Self::A8Unorm
+            | Self::R8Unorm
+            | Self::R8UnormSrgb
+            | Self::R8Snorm
+            | Self::R8Uint
+            | Self::R8Sint
+            | Self::Stencil8 => 1,
+            Self::R16Unorm
+            | Self::R16Snorm
+            | Self::R16Uint
+            | Self::R16Sint
+            | Self::R16Float
+            | Self::RG8Unorm
+            | Self::RG8UnormSrgb
+            | Self::RG8Snorm
+            | Self::RG8Uint
+            | Self::RG8Sint
+            | Self::B5G6R5Unorm
+            | Self::A1BGR5Unorm
+            | Self::ABGR4Unorm
+            | Self::BGR5A1Unorm
+            | Self::Depth16Unorm => 2,
+            Self::RG32Uint
+            | Self::RG32Sint
+            | Self::RG32Float
+            | Self::RGBA16Unorm
+            | Self::RGBA16Snorm
+            | Self::RGBA16Uint
+            | Self::RGBA16Sint
+            | Self::RGBA16Float
+            | Self::Depth32Float_Stencil8
+            | Self::X32_Stencil8
+            | Self::BGRA10_XR
+            | Self::BGRA10_XR_sRGB => 8,
+            Self::RGBA32Uint | Self::RGBA32Sint | Self::RGBA32Float => 16,
+            Self::BC1_RGBA
+            | Self::BC1_RGBA_sRGB
+            | Self::BC4_RUnorm
+            | Self::BC4_RSnorm
+            | Self::EAC_R11Unorm
+            | Self::EAC_R11Snorm
+            | Self::ETC2_RGB8
+            | Self::ETC2_RGB8_sRGB
+            | Self::ETC2_RGB8A1
+            | Self::ETC2_RGB8A1_sRGB
+            | Self::PVRTC_RGB_2BPP
+            | Self::PVRTC_RGB_2BPP_sRGB
+            | Self::PVRTC_RGB_4BPP
+            | Self::PVRTC_RGB_4BPP_sRGB
+            | Self::PVRTC_RGBA_2BPP
+            | Self::PVRTC_RGBA_2BPP_sRGB
+            | Self::PVRTC_RGBA_4BPP
+            | Self::PVRTC_RGBA_4BPP_sRGB => 8,
+            _ if self.is_compressed() => 16,
+            // The remaining 32-bit formats, including packed, 4:2:2 and
+            // 24-bit depth with stencil.
+            _ => 4,
+        };
+        Some(bytes)
+    }
+}
+
+impl TryFrom<u64> for MTLPixelFormat {
+    /// The unrecognized raw value.
+    type Error = u64;
+
+    fn try_from(value: u64) -> Result<Self, Self::Error> {
+        Self::ALL.binary_search_by_key(&value, |format| *format as u64).map(|index| Self::ALL[index]).map_err(|_| value)
+    }
+}
+
 unsafe impl Encode for MTLPixelFormat {
     const ENCODING: Encoding = u64::ENCODING;
 }
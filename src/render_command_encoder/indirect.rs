@@ -0,0 +1,72 @@
+use objc2::{Encode, Encoding, RefEncode};
+
+/// Indirect arguments for `drawPrimitives` (from `MTLDrawPrimitivesIndirectArguments`).
+///
+/// Availability: macOS 10.11+, iOS 9.0+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MTLDrawPrimitivesIndirectArguments {
+    pub vertex_count: u32,
+    pub instance_count: u32,
+    pub vertex_start: u32,
+    pub base_instance: u32,
+}
+
+unsafe impl Encode for MTLDrawPrimitivesIndirectArguments {
+    const ENCODING: Encoding = Encoding::Struct("?", &[u32::ENCODING, u32::ENCODING, u32::ENCODING, u32::ENCODING]);
+}
+
+unsafe impl RefEncode for MTLDrawPrimitivesIndirectArguments {
+    const ENCODING_REF: Encoding = Encoding::Pointer(&Self::ENCODING);
+}
+
+const _: () = assert!(size_of::<MTLDrawPrimitivesIndirectArguments>() == 16);
+const _: () = assert!(align_of::<MTLDrawPrimitivesIndirectArguments>() == 4);
+
+/// Indirect arguments for `drawIndexedPrimitives` (from `MTLDrawIndexedPrimitivesIndirectArguments`).
+///
+/// Availability: macOS 10.11+, iOS 9.0+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MTLDrawIndexedPrimitivesIndirectArguments {
+    pub index_count: u32,
+    pub instance_count: u32,
+    pub index_start: u32,
+    pub base_vertex: i32,
+    pub base_instance: u32,
+}
+
+unsafe impl Encode for MTLDrawIndexedPrimitivesIndirectArguments {
+    const ENCODING: Encoding =
+        Encoding::Struct("?", &[u32::ENCODING, u32::ENCODING, u32::ENCODING, i32::ENCODING, u32::ENCODING]);
+}
+
+unsafe impl RefEncode for MTLDrawIndexedPrimitivesIndirectArguments {
+    const ENCODING_REF: Encoding = Encoding::Pointer(&Self::ENCODING);
+}
+
+const _: () = assert!(size_of::<MTLDrawIndexedPrimitivesIndirectArguments>() == 20);
+const _: () = assert!(align_of::<MTLDrawIndexedPrimitivesIndirectArguments>() == 4);
+
+/// Indirect arguments for `drawPatches` and `drawIndexedPatches` (from `MTLDrawPatchIndirectArguments`).
+///
+/// Availability: macOS 10.12+, iOS 10.0+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MTLDrawPatchIndirectArguments {
+    pub patch_count: u32,
+    pub instance_count: u32,
+    pub patch_start: u32,
+    pub base_instance: u32,
+}
+
+unsafe impl Encode for MTLDrawPatchIndirectArguments {
+    const ENCODING: Encoding = Encoding::Struct("?", &[u32::ENCODING, u32::ENCODING, u32::ENCODING, u32::ENCODING]);
+}
+
+unsafe impl RefEncode for MTLDrawPatchIndirectArguments {
+    const ENCODING_REF: Encoding = Encoding::Pointer(&Self::ENCODING);
+}
+
+const _: () = assert!(size_of::<MTLDrawPatchIndirectArguments>() == 16);
+const _: () = assert!(align_of::<MTLDrawPatchIndirectArguments>() == 4);
@@ -1,7 +1,11 @@
+mod indirect;
 mod render_command_encoder;
 mod types;
 
-pub use render_command_encoder::MTLRenderCommandEncoder;
+pub use indirect::{
+    MTLDrawIndexedPrimitivesIndirectArguments, MTLDrawPatchIndirectArguments, MTLDrawPrimitivesIndirectArguments,
+};
+pub use render_command_encoder::{MTLRenderCommandEncoder, MTLRenderCommandEncoderExt};
 pub use types::{
     MTLCullMode, MTLDepthClipMode, MTLPrimitiveType, MTLScissorRect, MTLTriangleFillMode,
     MTLVertexAmplificationViewMapping, MTLViewport, MTLVisibilityResultMode, MTLWinding,
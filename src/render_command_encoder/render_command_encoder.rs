@@ -1,10 +1,14 @@
 use core::ptr::NonNull;
 
-use objc2::{extern_protocol, runtime::ProtocolObject};
+use objc2::{Message, extern_protocol, runtime::ProtocolObject};
 
-use super::{MTLCullMode, MTLDepthClipMode, MTLScissorRect, MTLTriangleFillMode, MTLViewport, MTLVisibilityResultMode};
+use super::{
+    MTLCullMode, MTLDepthClipMode, MTLDrawIndexedPrimitivesIndirectArguments, MTLDrawPatchIndirectArguments,
+    MTLDrawPrimitivesIndirectArguments, MTLScissorRect, MTLTriangleFillMode, MTLViewport, MTLVisibilityResultMode,
+};
 use crate::{
-    MTLBuffer, MTLCommandEncoder, MTLPrimitiveType, MTLTexture,
+    IndirectArgumentsOffset, MTLBuffer, MTLCommandEncoder, MTLDispatchThreadgroupsIndirectArguments, MTLIndexType,
+    MTLIndirectCommandBuffer, MTLIndirectCommandBufferExecutionRange, MTLPrimitiveType, MTLSize, MTLTexture,
     depth_stencil::MTLDepthStencilState,
     render_pipeline::{MTLLogicalToPhysicalColorAttachmentMap, MTLRenderPipelineState},
 };
@@ -199,5 +203,174 @@ extern_protocol!(
             instance_count: usize,
             base_instance: usize,
         );
+
+        /// Encodes a draw command whose arguments are read from an
+        /// `MTLDrawPrimitivesIndirectArguments` in `indirect_buffer`.
+        ///
+        /// Availability: macOS 10.11+, iOS 9.0+
+        #[unsafe(method(drawPrimitives:indirectBuffer:indirectBufferOffset:))]
+        #[unsafe(method_family = none)]
+        fn draw_primitives_indirect(
+            &self,
+            primitive_type: MTLPrimitiveType,
+            indirect_buffer: &ProtocolObject<dyn MTLBuffer>,
+            indirect_buffer_offset: usize,
+        );
+
+        /// Encodes an indexed draw command whose arguments are read from an
+        /// `MTLDrawIndexedPrimitivesIndirectArguments` in `indirect_buffer`.
+        ///
+        /// Availability: macOS 10.11+, iOS 9.0+
+        #[unsafe(method(drawIndexedPrimitives:indexType:indexBuffer:indexBufferOffset:indirectBuffer:indirectBufferOffset:))]
+        #[unsafe(method_family = none)]
+        fn draw_indexed_primitives_indirect(
+            &self,
+            primitive_type: MTLPrimitiveType,
+            index_type: MTLIndexType,
+            index_buffer: &ProtocolObject<dyn MTLBuffer>,
+            index_buffer_offset: usize,
+            indirect_buffer: &ProtocolObject<dyn MTLBuffer>,
+            indirect_buffer_offset: usize,
+        );
+
+        /// Encodes a tessellated draw command whose arguments are read from an
+        /// `MTLDrawPatchIndirectArguments` in `indirect_buffer`.
+        ///
+        /// Availability: macOS 10.12+, iOS 12.0+
+        #[unsafe(method(drawPatches:patchIndexBuffer:patchIndexBufferOffset:indirectBuffer:indirectBufferOffset:))]
+        #[unsafe(method_family = none)]
+        fn draw_patches_indirect(
+            &self,
+            number_of_patch_control_points: usize,
+            patch_index_buffer: Option<&ProtocolObject<dyn MTLBuffer>>,
+            patch_index_buffer_offset: usize,
+            indirect_buffer: &ProtocolObject<dyn MTLBuffer>,
+            indirect_buffer_offset: usize,
+        );
+
+        /// Encodes a mesh shader draw whose threadgroup count is read from an
+        /// `MTLDispatchThreadgroupsIndirectArguments` in `indirect_buffer`.
+        ///
+        /// Availability: macOS 13.0+, iOS 16.0+
+        #[unsafe(method(drawMeshThreadgroupsWithIndirectBuffer:indirectBufferOffset:threadsPerObjectThreadgroup:threadsPerMeshThreadgroup:))]
+        #[unsafe(method_family = none)]
+        fn draw_mesh_threadgroups_indirect(
+            &self,
+            indirect_buffer: &ProtocolObject<dyn MTLBuffer>,
+            indirect_buffer_offset: usize,
+            threads_per_object_threadgroup: MTLSize,
+            threads_per_mesh_threadgroup: MTLSize,
+        );
+
+        /// Executes the commands in `icb` over the range read from an
+        /// `MTLIndirectCommandBufferExecutionRange` in `indirect_range_buffer`.
+        ///
+        /// Availability: macOS 10.14+, iOS 13.0+
+        #[unsafe(method(executeCommandsInBuffer:indirectBuffer:indirectBufferOffset:))]
+        #[unsafe(method_family = none)]
+        fn execute_commands_in_buffer_indirect(
+            &self,
+            icb: &ProtocolObject<dyn MTLIndirectCommandBuffer>,
+            indirect_range_buffer: &ProtocolObject<dyn MTLBuffer>,
+            indirect_buffer_offset: usize,
+        );
     }
 );
+
+/// Typed indirect draw helpers for [`MTLRenderCommandEncoder`].
+///
+/// Each helper takes the indirect buffer together with an
+/// [`IndirectArgumentsOffset`] of the matching argument struct and panics if
+/// the arguments would overrun the buffer.
+pub trait MTLRenderCommandEncoderExt: MTLRenderCommandEncoder + Message {
+    fn encode_draw_primitives_indirect(
+        &self,
+        primitive_type: MTLPrimitiveType,
+        indirect_buffer: &ProtocolObject<dyn MTLBuffer>,
+        offset: IndirectArgumentsOffset<MTLDrawPrimitivesIndirectArguments>,
+    ) where
+        Self: Sized,
+    {
+        offset.check_bounds(indirect_buffer);
+        self.draw_primitives_indirect(primitive_type, indirect_buffer, offset.bytes());
+    }
+
+    fn encode_draw_indexed_primitives_indirect(
+        &self,
+        primitive_type: MTLPrimitiveType,
+        index_type: MTLIndexType,
+        index_buffer: &ProtocolObject<dyn MTLBuffer>,
+        index_buffer_offset: usize,
+        indirect_buffer: &ProtocolObject<dyn MTLBuffer>,
+        offset: IndirectArgumentsOffset<MTLDrawIndexedPrimitivesIndirectArguments>,
+    ) where
+        Self: Sized,
+    {
+        offset.check_bounds(indirect_buffer);
+        self.draw_indexed_primitives_indirect(
+            primitive_type,
+            index_type,
+            index_buffer,
+            index_buffer_offset,
+            indirect_buffer,
+            offset.bytes(),
+        );
+    }
+
+    fn encode_draw_patches_indirect(
+        &self,
+        number_of_patch_control_points: usize,
+        patch_index_buffer: Option<(&ProtocolObject<dyn MTLBuffer>, usize)>,
+        indirect_buffer: &ProtocolObject<dyn MTLBuffer>,
+        offset: IndirectArgumentsOffset<MTLDrawPatchIndirectArguments>,
+    ) where
+        Self: Sized,
+    {
+        offset.check_bounds(indirect_buffer);
+        let (patch_index_buffer, patch_index_buffer_offset) = match patch_index_buffer {
+            Some((buffer, buffer_offset)) => (Some(buffer), buffer_offset),
+            None => (None, 0),
+        };
+        self.draw_patches_indirect(
+            number_of_patch_control_points,
+            patch_index_buffer,
+            patch_index_buffer_offset,
+            indirect_buffer,
+            offset.bytes(),
+        );
+    }
+
+    /// Mesh draws read their threadgroup count from the same struct as
+    /// indirect compute dispatches.
+    fn encode_draw_mesh_threadgroups_indirect(
+        &self,
+        indirect_buffer: &ProtocolObject<dyn MTLBuffer>,
+        offset: IndirectArgumentsOffset<MTLDispatchThreadgroupsIndirectArguments>,
+        threads_per_object_threadgroup: MTLSize,
+        threads_per_mesh_threadgroup: MTLSize,
+    ) where
+        Self: Sized,
+    {
+        offset.check_bounds(indirect_buffer);
+        self.draw_mesh_threadgroups_indirect(
+            indirect_buffer,
+            offset.bytes(),
+            threads_per_object_threadgroup,
+            threads_per_mesh_threadgroup,
+        );
+    }
+
+    fn encode_execute_commands_in_buffer_indirect(
+        &self,
+        icb: &ProtocolObject<dyn MTLIndirectCommandBuffer>,
+        indirect_range_buffer: &ProtocolObject<dyn MTLBuffer>,
+        offset: IndirectArgumentsOffset<MTLIndirectCommandBufferExecutionRange>,
+    ) where
+        Self: Sized,
+    {
+        offset.check_bounds(indirect_range_buffer);
+        self.execute_commands_in_buffer_indirect(icb, indirect_range_buffer, offset.bytes());
+    }
+}
+
+impl<T: MTLRenderCommandEncoder + Message> MTLRenderCommandEncoderExt for T {}
@@ -0,0 +1,7 @@
+mod shared_texture;
+mod token;
+
+pub use shared_texture::{SharedTexture, SharedTextureError};
+pub use token::{
+    SHARED_TEXTURE_TOKEN_MAGIC, SHARED_TEXTURE_TOKEN_VERSION, SharedEventToken, SharedTextureToken, SharedTokenError,
+};
@@ -0,0 +1,180 @@
+use core::fmt;
+
+use objc2::{ClassType, rc::Retained, runtime::ProtocolObject};
+use objc2_foundation::{NSData, NSError, NSKeyedArchiver, NSKeyedUnarchiver};
+use objc2_io_surface::IOSurfaceRef;
+
+use super::{SharedEventToken, SharedTextureToken, SharedTokenError};
+use crate::{
+    MTLDevice, MTLDeviceExt, MTLSharedEvent, MTLSharedEventHandle, MTLTexture, MTLTextureDescriptor, MTLTextureType,
+};
+
+/// Errors raised while exporting or opening shared textures.
+#[derive(Debug)]
+pub enum SharedTextureError {
+    /// The token's bytes are malformed.
+    Token(SharedTokenError),
+    /// The texture isn't a 2D texture backed by an IOSurface.
+    NotSurfaceBacked,
+    /// No IOSurface with this ID is visible to the process.
+    SurfaceNotFound(u32),
+    /// The event handle couldn't be archived or unarchived.
+    EventHandle(Option<Retained<NSError>>),
+    /// Metal failed to create the texture or event.
+    ResourceCreation,
+}
+
+impl fmt::Display for SharedTextureError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::Token(error) => fmt::Display::fmt(error, f),
+            Self::NotSurfaceBacked => f.write_str("texture is not a 2D texture backed by an IOSurface"),
+            Self::SurfaceNotFound(id) => write!(f, "no IOSurface with ID {id}"),
+            Self::EventHandle(Some(error)) => {
+                write!(f, "failed to archive or unarchive the shared event handle: {error}")
+            },
+            Self::EventHandle(None) => f.write_str("failed to archive or unarchive the shared event handle"),
+            Self::ResourceCreation => f.write_str("failed to create a Metal resource"),
+        }
+    }
+}
+
+impl std::error::Error for SharedTextureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Token(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<SharedTokenError> for SharedTextureError {
+    fn from(error: SharedTokenError) -> Self {
+        Self::Token(error)
+    }
+}
+
+/// A texture opened from a [`SharedTextureToken`].
+#[derive(Debug)]
+pub struct SharedTexture {
+    pub texture: Retained<ProtocolObject<dyn MTLTexture>>,
+    /// The synchronizing event and the value signaled once the texture's
+    /// contents are ready, if the token carried one.
+    pub event: Option<(Retained<ProtocolObject<dyn MTLSharedEvent>>, u64)>,
+}
+
+impl SharedEventToken {
+    /// Archives a handle to `event`.
+    ///
+    /// The archive only resolves to the same event in another process if it
+    /// travels over a transport that carries Mach ports, such as XPC.
+    pub fn new(
+        event: &ProtocolObject<dyn MTLSharedEvent>,
+        value: u64,
+    ) -> Result<Self, SharedTextureError> {
+        let handle = event.new_shared_event_handle();
+        let data = unsafe { NSKeyedArchiver::archivedDataWithRootObject_requiringSecureCoding_error(&handle, true) }
+            .map_err(|error| SharedTextureError::EventHandle(Some(error)))?;
+        Ok(Self {
+            handle: data.to_vec(),
+            value,
+        })
+    }
+
+    /// Unarchives the handle and creates the event on `device`.
+    pub fn open(
+        &self,
+        device: &ProtocolObject<dyn MTLDevice>,
+    ) -> Result<Retained<ProtocolObject<dyn MTLSharedEvent>>, SharedTextureError> {
+        let data = NSData::with_bytes(&self.handle);
+        let object =
+            unsafe { NSKeyedUnarchiver::unarchivedObjectOfClass_fromData_error(MTLSharedEventHandle::class(), &data) }
+                .map_err(|error| SharedTextureError::EventHandle(Some(error)))?;
+        let handle = object.downcast::<MTLSharedEventHandle>().map_err(|_| SharedTextureError::EventHandle(None))?;
+        device.new_shared_event_with_handle(&handle).ok_or(SharedTextureError::ResourceCreation)
+    }
+}
+
+impl SharedTextureToken {
+    /// Describes an IOSurface-backed 2D texture, without an event.
+    pub fn from_texture(texture: &ProtocolObject<dyn MTLTexture>) -> Result<Self, SharedTextureError> {
+        if texture.texture_type() != MTLTextureType::Type2D {
+            return Err(SharedTextureError::NotSurfaceBacked);
+        }
+        let surface = texture.iosurface().ok_or(SharedTextureError::NotSurfaceBacked)?;
+        Ok(Self {
+            surface_id: surface.id(),
+            plane: texture.iosurface_plane() as u32,
+            width: texture.width() as u32,
+            height: texture.height() as u32,
+            pixel_format: texture.pixel_format(),
+            usage: texture.usage(),
+            event: None,
+        })
+    }
+
+    /// Attaches `event`, which the producer signals with `value` once the
+    /// texture's contents are ready.
+    pub fn with_event(
+        mut self,
+        event: &ProtocolObject<dyn MTLSharedEvent>,
+        value: u64,
+    ) -> Result<Self, SharedTextureError> {
+        self.event = Some(SharedEventToken::new(event, value)?);
+        Ok(self)
+    }
+
+    /// Looks up the surface by its global ID and opens the texture and event
+    /// on `device`.
+    ///
+    /// The producer must keep the surface alive until the consumer has opened
+    /// it. Processes that can't share global surface IDs, such as sandboxed
+    /// apps, should send the surface's Mach port and use
+    /// [`Self::open_with_surface`].
+    pub fn open(
+        &self,
+        device: &ProtocolObject<dyn MTLDevice>,
+    ) -> Result<SharedTexture, SharedTextureError> {
+        let surface =
+            IOSurfaceRef::lookup(self.surface_id).ok_or(SharedTextureError::SurfaceNotFound(self.surface_id))?;
+        self.open_with_surface(device, &surface)
+    }
+
+    /// Opens the texture on `surface`, which the caller resolved, and the
+    /// event on `device`.
+    pub fn open_with_surface(
+        &self,
+        device: &ProtocolObject<dyn MTLDevice>,
+        surface: &IOSurfaceRef,
+    ) -> Result<SharedTexture, SharedTextureError> {
+        let descriptor = MTLTextureDescriptor::texture_2d_descriptor_with_pixel_format_width_height_mipmapped(
+            self.pixel_format,
+            self.width as usize,
+            self.height as usize,
+            false,
+        );
+        descriptor.set_usage(self.usage);
+        let texture = device
+            .new_texture_with_descriptor_iosurface_plane(&descriptor, surface, self.plane as usize)
+            .ok_or(SharedTextureError::ResourceCreation)?;
+        let event = match &self.event {
+            Some(event) => Some((event.open(device)?, event.value)),
+            None => None,
+        };
+        Ok(SharedTexture {
+            texture,
+            event,
+        })
+    }
+
+    /// Decodes a token and opens it on `device`.
+    pub fn open_bytes(
+        device: &ProtocolObject<dyn MTLDevice>,
+        bytes: &[u8],
+    ) -> Result<SharedTexture, SharedTextureError> {
+        Self::from_bytes(bytes)?.open(device)
+    }
+}
@@ -0,0 +1,362 @@
+use core::fmt;
+
+use crate::{MTLPixelFormat, MTLTextureUsage};
+
+/// Leading bytes of an encoded [`SharedTextureToken`].
+pub const SHARED_TEXTURE_TOKEN_MAGIC: [u8; 4] = *b"MTLS";
+
+/// Wire format version written by [`SharedTextureToken::to_bytes`].
+///
+/// Fields may be appended to the body without a version bump: decoders skip
+/// body bytes they don't understand. The version only changes when existing
+/// fields change meaning, and decoders reject versions newer than their own.
+pub const SHARED_TEXTURE_TOKEN_VERSION: u16 = 1;
+
+/// Magic, version, flags and body length.
+const HEADER_LEN: usize = 12;
+/// Surface ID, plane, width, height, pixel format and usage.
+const TEXTURE_LEN: usize = 32;
+/// Signal value and handle length, followed by the handle.
+const EVENT_LEN: usize = 12;
+
+const FLAG_EVENT: u16 = 1 << 0;
+const KNOWN_FLAGS: u16 = FLAG_EVENT;
+
+/// Errors raised while decoding a [`SharedTextureToken`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SharedTokenError {
+    /// The bytes end before the structure they describe.
+    Truncated {
+        needed: usize,
+        available: usize,
+    },
+    /// The bytes don't start with [`SHARED_TEXTURE_TOKEN_MAGIC`].
+    InvalidMagic([u8; 4]),
+    /// The token was written by a newer, incompatible encoder.
+    UnsupportedVersion(u16),
+    /// The header sets flags this decoder doesn't know.
+    UnknownFlags(u16),
+    /// The raw pixel format isn't an `MTLPixelFormat`.
+    UnknownPixelFormat(u64),
+    /// Bytes follow the token's body.
+    TrailingBytes(usize),
+}
+
+impl fmt::Display for SharedTokenError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::Truncated {
+                needed,
+                available,
+            } => write!(f, "shared texture token is truncated: needed {needed} bytes, found {available}"),
+            Self::InvalidMagic(magic) => write!(f, "not a shared texture token (magic {magic:02x?})"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "shared texture token version {version} is newer than the supported version {SHARED_TEXTURE_TOKEN_VERSION}"
+            ),
+            Self::UnknownFlags(flags) => write!(f, "shared texture token has unknown flags {flags:#06x}"),
+            Self::UnknownPixelFormat(value) => write!(f, "unknown pixel format {value}"),
+            Self::TrailingBytes(count) => write!(f, "{count} bytes follow the shared texture token"),
+        }
+    }
+}
+
+impl std::error::Error for SharedTokenError {}
+
+/// A shared event carried by a [`SharedTextureToken`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SharedEventToken {
+    /// The `MTLSharedEventHandle`, archived with `NSKeyedArchiver`.
+    pub handle: Vec<u8>,
+    /// The value the producer signals once the texture's contents are ready.
+    pub value: u64,
+}
+
+/// A transferable description of an IOSurface-backed texture and the shared
+/// event that synchronizes access to it.
+///
+/// The token identifies the surface by its global `IOSurfaceID`, which the
+/// receiving process resolves with `IOSurfaceLookup`. The encoding is
+/// little-endian:
+///
+/// | Bytes | Field                                     |
+/// |-------|-------------------------------------------|
+/// | 4     | magic, `MTLS`                             |
+/// | 2     | version                                   |
+/// | 2     | flags, bit 0 set when an event follows    |
+/// | 4     | body length                               |
+/// | 4     | `IOSurfaceID`                             |
+/// | 4     | IOSurface plane                           |
+/// | 4     | width                                     |
+/// | 4     | height                                    |
+/// | 8     | `MTLPixelFormat`                          |
+/// | 8     | `MTLTextureUsage`                         |
+/// | 8     | event signal value, if flagged            |
+/// | 4     | event handle length, if flagged           |
+/// | n     | archived event handle, if flagged         |
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SharedTextureToken {
+    pub surface_id: u32,
+    pub plane: u32,
+    pub width: u32,
+    pub height: u32,
+    pub pixel_format: MTLPixelFormat,
+    pub usage: MTLTextureUsage,
+    pub event: Option<SharedEventToken>,
+}
+
+impl SharedTextureToken {
+    /// The length of [`Self::to_bytes`]'s output.
+    pub fn encoded_len(&self) -> usize {
+        HEADER_LEN + self.body_len()
+    }
+
+    fn body_len(&self) -> usize {
+        TEXTURE_LEN + self.event.as_ref().map_or(0, |event| EVENT_LEN + event.handle.len())
+    }
+
+    /// Encodes the token in the current wire format version.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the archived event handle is 4 GiB or larger.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let body_len = u32::try_from(self.body_len()).expect("shared texture token is too large");
+        let flags = if self.event.is_some() {
+            FLAG_EVENT
+        } else {
+            0
+        };
+
+        let mut bytes = Vec::with_capacity(self.encoded_len());
+        bytes.extend_from_slice(&SHARED_TEXTURE_TOKEN_MAGIC);
+        bytes.extend_from_slice(&SHARED_TEXTURE_TOKEN_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&flags.to_le_bytes());
+        bytes.extend_from_slice(&body_len.to_le_bytes());
+        bytes.extend_from_slice(&self.surface_id.to_le_bytes());
+        bytes.extend_from_slice(&self.plane.to_le_bytes());
+        bytes.extend_from_slice(&self.width.to_le_bytes());
+        bytes.extend_from_slice(&self.height.to_le_bytes());
+        bytes.extend_from_slice(&(self.pixel_format as u64).to_le_bytes());
+        bytes.extend_from_slice(&(self.usage.bits() as u64).to_le_bytes());
+        if let Some(event) = &self.event {
+            bytes.extend_from_slice(&event.value.to_le_bytes());
+            bytes.extend_from_slice(&(event.handle.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&event.handle);
+        }
+        bytes
+    }
+
+    /// Decodes a token written by this or an older wire format version.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SharedTokenError> {
+        let mut header = Reader::new(bytes);
+        let magic: [u8; 4] = header.array()?;
+        if magic != SHARED_TEXTURE_TOKEN_MAGIC {
+            return Err(SharedTokenError::InvalidMagic(magic));
+        }
+        let version = header.u16()?;
+        if version == 0 || version > SHARED_TEXTURE_TOKEN_VERSION {
+            return Err(SharedTokenError::UnsupportedVersion(version));
+        }
+        let flags = header.u16()?;
+        if flags & !KNOWN_FLAGS != 0 {
+            return Err(SharedTokenError::UnknownFlags(flags));
+        }
+        let body_len = header.u32()? as usize;
+        let body = header.take(body_len)?;
+        if !header.remaining().is_empty() {
+            return Err(SharedTokenError::TrailingBytes(header.remaining().len()));
+        }
+
+        let mut body = Reader::new(body);
+        let surface_id = body.u32()?;
+        let plane = body.u32()?;
+        let width = body.u32()?;
+        let height = body.u32()?;
+        let pixel_format = MTLPixelFormat::try_from(body.u64()?).map_err(SharedTokenError::UnknownPixelFormat)?;
+        let usage = MTLTextureUsage::from_bits_retain(body.u64()? as usize);
+        let event = if flags & FLAG_EVENT != 0 {
+            let value = body.u64()?;
+            let handle_len = body.u32()? as usize;
+            let handle = body.take(handle_len)?.to_vec();
+            Some(SharedEventToken {
+                handle,
+                value,
+            })
+        } else {
+            None
+        };
+
+        Ok(Self {
+            surface_id,
+            plane,
+            width,
+            height,
+            pixel_format,
+            usage,
+            event,
+        })
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    consumed: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            consumed: 0,
+        }
+    }
+
+    fn remaining(&self) -> &'a [u8] {
+        &self.bytes[self.consumed..]
+    }
+
+    fn take(
+        &mut self,
+        len: usize,
+    ) -> Result<&'a [u8], SharedTokenError> {
+        let remaining = self.remaining();
+        if remaining.len() < len {
+            return Err(SharedTokenError::Truncated {
+                needed: self.consumed + len,
+                available: self.bytes.len(),
+            });
+        }
+        self.consumed += len;
+        Ok(&remaining[..len])
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], SharedTokenError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn u16(&mut self) -> Result<u16, SharedTokenError> {
+        self.array().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, SharedTokenError> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64, SharedTokenError> {
+        self.array().map(u64::from_le_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(event: Option<SharedEventToken>) -> SharedTextureToken {
+        SharedTextureToken {
+            surface_id: 0x1234,
+            plane: 1,
+            width: 640,
+            height: 480,
+            pixel_format: MTLPixelFormat::BGRA8Unorm,
+            usage: MTLTextureUsage::SHADER_READ | MTLTextureUsage::RENDER_TARGET,
+            event,
+        }
+    }
+
+    fn event() -> SharedEventToken {
+        SharedEventToken {
+            handle: b"archived handle".to_vec(),
+            value: 42,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        for token in [token(None), token(Some(event()))] {
+            let bytes = token.to_bytes();
+            assert_eq!(bytes.len(), token.encoded_len());
+            assert_eq!(SharedTextureToken::from_bytes(&bytes), Ok(token));
+        }
+
+        let bytes = token(None).to_bytes();
+        assert_eq!(&bytes[..HEADER_LEN], b"MTLS\x01\x00\x00\x00\x20\x00\x00\x00");
+        assert_eq!(&bytes[HEADER_LEN..HEADER_LEN + 4], 0x1234u32.to_le_bytes());
+        assert_eq!(token(Some(event())).to_bytes()[6..8], FLAG_EVENT.to_le_bytes());
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = token(Some(event())).to_bytes();
+        assert_eq!(
+            SharedTextureToken::from_bytes(&bytes[..10]),
+            Err(SharedTokenError::Truncated {
+                needed: HEADER_LEN,
+                available: 10,
+            })
+        );
+        let cut = bytes.len() - 1;
+        assert_eq!(
+            SharedTextureToken::from_bytes(&bytes[..cut]),
+            Err(SharedTokenError::Truncated {
+                needed: bytes.len(),
+                available: cut,
+            })
+        );
+
+        // A body length that hides the event handle.
+        let mut short_body = bytes.clone();
+        short_body[8..12].copy_from_slice(&((TEXTURE_LEN + EVENT_LEN) as u32).to_le_bytes());
+        short_body.truncate(HEADER_LEN + TEXTURE_LEN + EVENT_LEN);
+        assert_eq!(
+            SharedTextureToken::from_bytes(&short_body),
+            Err(SharedTokenError::Truncated {
+                needed: TEXTURE_LEN + EVENT_LEN + event().handle.len(),
+                available: TEXTURE_LEN + EVENT_LEN,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let bytes = token(None).to_bytes();
+
+        let mut magic = bytes.clone();
+        magic[..4].copy_from_slice(b"MTLX");
+        assert_eq!(SharedTextureToken::from_bytes(&magic), Err(SharedTokenError::InvalidMagic(*b"MTLX")));
+
+        for version in [0, SHARED_TEXTURE_TOKEN_VERSION + 1] {
+            let mut future = bytes.clone();
+            future[4..6].copy_from_slice(&version.to_le_bytes());
+            assert_eq!(SharedTextureToken::from_bytes(&future), Err(SharedTokenError::UnsupportedVersion(version)));
+        }
+
+        let mut flags = bytes.clone();
+        flags[6..8].copy_from_slice(&0x0003u16.to_le_bytes());
+        assert_eq!(SharedTextureToken::from_bytes(&flags), Err(SharedTokenError::UnknownFlags(3)));
+
+        let mut format = bytes.clone();
+        format[HEADER_LEN + 16..HEADER_LEN + 24].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(SharedTextureToken::from_bytes(&format), Err(SharedTokenError::UnknownPixelFormat(u64::MAX)));
+
+        let mut trailing = bytes;
+        trailing.extend_from_slice(&[0; 3]);
+        assert_eq!(SharedTextureToken::from_bytes(&trailing), Err(SharedTokenError::TrailingBytes(3)));
+    }
+
+    #[test]
+    fn skips_appended_body_fields() {
+        for token in [token(None), token(Some(event()))] {
+            let mut bytes = token.to_bytes();
+            let body_len = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) + 5;
+            bytes[8..12].copy_from_slice(&body_len.to_le_bytes());
+            bytes.extend_from_slice(b"newer");
+            assert_eq!(SharedTextureToken::from_bytes(&bytes), Ok(token));
+        }
+    }
+}
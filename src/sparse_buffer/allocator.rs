@@ -0,0 +1,491 @@
+use core::{fmt, ops::Range};
+use std::collections::BTreeMap;
+
+use objc2::runtime::ProtocolObject;
+
+use super::SparseBufferMappingQueue;
+use crate::{
+    MTL4CopySparseBufferMappingOperation, MTL4UpdateSparseBufferMappingOperation, MTLDevice, MTLSparsePageSize,
+    MTLSparseTextureMappingMode,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SparseBufferError {
+    /// Allocations must be at least a byte.
+    ZeroLength,
+    /// No run of unallocated buffer pages is long enough; defragmenting may
+    /// make one.
+    BufferSpaceExhausted {
+        pages: usize,
+    },
+    /// The allocation needs more heap pages than are free.
+    OutOfHeapPages {
+        needed: usize,
+        available: usize,
+    },
+    /// No allocation starts at this byte offset.
+    UnknownAllocation(usize),
+    /// Mapping operations are waiting to be taken, and would apply to the
+    /// buffer before defragmenting.
+    PendingOperations,
+}
+
+impl fmt::Display for SparseBufferError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::ZeroLength => f.write_str("sparse buffer allocations can't be empty"),
+            Self::BufferSpaceExhausted {
+                pages,
+            } => write!(f, "no run of {pages} free pages in the sparse buffer"),
+            Self::OutOfHeapPages {
+                needed,
+                available,
+            } => write!(f, "allocation needs {needed} heap pages, but only {available} are free"),
+            Self::UnknownAllocation(offset) => write!(f, "no sparse buffer allocation at offset {offset}"),
+            Self::PendingOperations => f.write_str("sparse buffer has mapping operations that weren't applied"),
+        }
+    }
+}
+
+impl std::error::Error for SparseBufferError {}
+
+/// Where [`SparseBufferAllocator::defragment`] moved allocations, and the
+/// mapping copies that move them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SparseBufferDefragmentation {
+    /// Copies from the old buffer's mappings into the new buffer, for
+    /// `MTL4CommandQueueExt::copy_buffer_mappings`.
+    pub copies: Vec<MTL4CopySparseBufferMappingOperation>,
+    /// Each allocation's old offset and new byte range.
+    pub relocations: Vec<(usize, Range<usize>)>,
+}
+
+/// A mapping change to one buffer page.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct PageUpdate {
+    mode: MTLSparseTextureMappingMode,
+    buffer_page: usize,
+    heap_page: usize,
+}
+
+/// Allocates byte ranges of a placement sparse buffer and the heap pages
+/// backing them.
+///
+/// Allocations take a run of buffer pages, each mapped to any free heap
+/// page. The allocator only keeps the bookkeeping; the mapping operations
+/// to apply are collected by [`take_operations`](Self::take_operations), or
+/// applied to a [`SparseBufferMappingQueue`] by [`flush`](Self::flush).
+#[derive(Clone, Debug)]
+pub struct SparseBufferAllocator {
+    page_size: usize,
+    /// Whether each heap page is in use.
+    heap_pages: Vec<bool>,
+    free_heap_pages: usize,
+    /// The heap page each buffer page maps to.
+    buffer_pages: Vec<Option<usize>>,
+    /// Page counts of the allocations, by first buffer page.
+    allocations: BTreeMap<usize, usize>,
+    pending: Vec<PageUpdate>,
+}
+
+impl SparseBufferAllocator {
+    /// An allocator for a buffer of `buffer_length` bytes, backed by
+    /// `heap_page_count` heap pages of `page_size` bytes.
+    pub fn new(
+        buffer_length: usize,
+        heap_page_count: usize,
+        page_size: usize,
+    ) -> Self {
+        assert!(page_size > 0, "sparse pages can't be empty");
+        Self {
+            page_size,
+            heap_pages: vec![false; heap_page_count],
+            free_heap_pages: heap_page_count,
+            buffer_pages: vec![None; buffer_length / page_size],
+            allocations: BTreeMap::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// An allocator for a buffer of `buffer_length` bytes backed by a
+    /// `heap_size` byte heap, both with sparse pages of `page_size`.
+    pub fn for_device(
+        device: &ProtocolObject<dyn MTLDevice>,
+        page_size: MTLSparsePageSize,
+        buffer_length: usize,
+        heap_size: usize,
+    ) -> Self {
+        let page_size = device.sparse_tile_size_in_bytes_for_sparse_page_size(page_size);
+        Self::new(buffer_length, heap_size / page_size, page_size)
+    }
+
+    /// Bytes per page.
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    pub fn heap_page_count(&self) -> usize {
+        self.heap_pages.len()
+    }
+
+    pub fn free_heap_page_count(&self) -> usize {
+        self.free_heap_pages
+    }
+
+    /// Pages of the buffer, mapped or not.
+    pub fn buffer_page_count(&self) -> usize {
+        self.buffer_pages.len()
+    }
+
+    /// The heap page the page at byte `offset` of the buffer maps to.
+    pub fn heap_page(
+        &self,
+        offset: usize,
+    ) -> Option<usize> {
+        self.buffer_pages.get(offset / self.page_size).copied().flatten()
+    }
+
+    /// The byte ranges of the allocations, in buffer order.
+    pub fn allocations(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        self.allocations.iter().map(|(&start, &pages)| self.byte_range(start, pages))
+    }
+
+    /// Allocates at least `length` bytes, returning their range in the
+    /// buffer, which is page-aligned and rounded up to whole pages.
+    pub fn allocate(
+        &mut self,
+        length: usize,
+    ) -> Result<Range<usize>, SparseBufferError> {
+        if length == 0 {
+            return Err(SparseBufferError::ZeroLength);
+        }
+        let pages = length.div_ceil(self.page_size);
+        if pages > self.free_heap_pages {
+            return Err(SparseBufferError::OutOfHeapPages {
+                needed: pages,
+                available: self.free_heap_pages,
+            });
+        }
+        let start = self.find_buffer_pages(pages).ok_or(SparseBufferError::BufferSpaceExhausted {
+            pages,
+        })?;
+        for buffer_page in start..start + pages {
+            let heap_page = self.heap_pages.iter().position(|&used| !used).expect("free heap pages were counted");
+            self.heap_pages[heap_page] = true;
+            self.buffer_pages[buffer_page] = Some(heap_page);
+            self.pending.push(PageUpdate {
+                mode: MTLSparseTextureMappingMode::Map,
+                buffer_page,
+                heap_page,
+            });
+        }
+        self.free_heap_pages -= pages;
+        self.allocations.insert(start, pages);
+        Ok(self.byte_range(start, pages))
+    }
+
+    /// Frees the allocation starting at byte `offset`, unmapping its pages.
+    pub fn free(
+        &mut self,
+        offset: usize,
+    ) -> Result<(), SparseBufferError> {
+        let start = offset / self.page_size;
+        let pages = match self.allocations.get(&start) {
+            Some(&pages) if offset.is_multiple_of(self.page_size) => pages,
+            _ => return Err(SparseBufferError::UnknownAllocation(offset)),
+        };
+        self.allocations.remove(&start);
+        for buffer_page in start..start + pages {
+            let heap_page = self.buffer_pages[buffer_page].take().expect("allocated pages are mapped");
+            self.heap_pages[heap_page] = false;
+            self.pending.push(PageUpdate {
+                mode: MTLSparseTextureMappingMode::Unmap,
+                buffer_page,
+                heap_page,
+            });
+        }
+        self.free_heap_pages += pages;
+        Ok(())
+    }
+
+    /// Whether mapping operations are waiting to be taken.
+    pub fn has_pending_operations(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// The mapping operations since the last call, in order, for
+    /// `MTL4CommandQueueExt::update_buffer_mappings`. Runs of buffer pages
+    /// mapped to consecutive heap pages, and runs of unmapped pages, are
+    /// merged into one operation.
+    pub fn take_operations(&mut self) -> Vec<MTL4UpdateSparseBufferMappingOperation> {
+        let mut runs: Vec<(PageUpdate, usize)> = Vec::new();
+        for update in self.pending.drain(..) {
+            if let Some((first, pages)) = runs.last_mut() {
+                let continues = first.mode == update.mode
+                    && first.buffer_page + *pages == update.buffer_page
+                    && (update.mode == MTLSparseTextureMappingMode::Unmap
+                        || first.heap_page + *pages == update.heap_page);
+                if continues {
+                    *pages += 1;
+                    continue;
+                }
+            }
+            runs.push((update, 1));
+        }
+        let operation = |(first, pages): (PageUpdate, usize)| {
+            let heap_offset = match first.mode {
+                MTLSparseTextureMappingMode::Map => first.heap_page * self.page_size,
+                MTLSparseTextureMappingMode::Unmap => 0,
+            };
+            MTL4UpdateSparseBufferMappingOperation::new(
+                first.mode,
+                self.byte_range(first.buffer_page, pages),
+                heap_offset,
+            )
+        };
+        runs.into_iter().map(operation).collect()
+    }
+
+    /// Applies the pending mapping operations to `buffer` on `queue`.
+    pub fn flush<B, Q: SparseBufferMappingQueue<B> + ?Sized>(
+        &mut self,
+        queue: &mut Q,
+        buffer: B,
+    ) {
+        let operations = self.take_operations();
+        if !operations.is_empty() {
+            queue.update_buffer_mappings(buffer, &operations);
+        }
+    }
+
+    /// Packs the allocations, in order, at the start of a new buffer of the
+    /// same length, keeping their heap pages. The allocator then describes
+    /// the new buffer.
+    ///
+    /// The returned copies map the new buffer to the old one's heap pages;
+    /// the old buffer can be released once they're applied and nothing uses
+    /// it. Fails if operations are pending, since they'd apply to the old
+    /// buffer.
+    pub fn defragment(&mut self) -> Result<SparseBufferDefragmentation, SparseBufferError> {
+        if self.has_pending_operations() {
+            return Err(SparseBufferError::PendingOperations);
+        }
+        let mut buffer_pages = vec![None; self.buffer_pages.len()];
+        let mut allocations = BTreeMap::new();
+        let mut copies: Vec<MTL4CopySparseBufferMappingOperation> = Vec::new();
+        let mut relocations = Vec::new();
+        let mut next = 0;
+        for (&start, &pages) in &self.allocations {
+            buffer_pages[next..next + pages].copy_from_slice(&self.buffer_pages[start..start + pages]);
+            allocations.insert(next, pages);
+            let source = self.byte_range(start, pages);
+            let destination = self.byte_range(next, pages);
+            relocations.push((source.start, destination.clone()));
+
+            // Allocations that stay adjacent share a copy.
+            match copies.last_mut() {
+                Some(last)
+                    if last.source_range().end == source.start
+                        && last.destination_offset + last.source_range().len() == destination.start =>
+                {
+                    *last = MTL4CopySparseBufferMappingOperation::new(
+                        last.source_range().start..source.end,
+                        last.destination_offset,
+                    );
+                },
+                _ => copies.push(MTL4CopySparseBufferMappingOperation::new(source, destination.start)),
+            }
+            next += pages;
+        }
+        self.buffer_pages = buffer_pages;
+        self.allocations = allocations;
+        Ok(SparseBufferDefragmentation {
+            copies,
+            relocations,
+        })
+    }
+
+    /// [`defragment`](Self::defragment)s the allocator and applies the copies
+    /// from `source` to `destination` on `queue`.
+    pub fn defragment_into<B, Q: SparseBufferMappingQueue<B> + ?Sized>(
+        &mut self,
+        queue: &mut Q,
+        source: B,
+        destination: B,
+    ) -> Result<Vec<(usize, Range<usize>)>, SparseBufferError> {
+        let defragmentation = self.defragment()?;
+        if !defragmentation.copies.is_empty() {
+            queue.copy_buffer_mappings(source, destination, &defragmentation.copies);
+        }
+        Ok(defragmentation.relocations)
+    }
+
+    /// First-fit run of `pages` unallocated buffer pages.
+    fn find_buffer_pages(
+        &self,
+        pages: usize,
+    ) -> Option<usize> {
+        let mut gap_start = 0;
+        for (&start, &length) in &self.allocations {
+            if start - gap_start >= pages {
+                return Some(gap_start);
+            }
+            gap_start = start + length;
+        }
+        (self.buffer_pages.len() - gap_start >= pages).then_some(gap_start)
+    }
+
+    fn byte_range(
+        &self,
+        first_page: usize,
+        pages: usize,
+    ) -> Range<usize> {
+        first_page * self.page_size..(first_page + pages) * self.page_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RecordedSparseBufferOperation, SimulatedSparseBufferQueue};
+
+    const PAGE: usize = 16;
+
+    /// Checks `queue`'s page table for `buffer` matches the allocator's.
+    fn assert_matches(
+        allocator: &SparseBufferAllocator,
+        queue: &SimulatedSparseBufferQueue<u32>,
+        buffer: u32,
+    ) {
+        let expected: Vec<(usize, usize)> = (0..allocator.buffer_page_count())
+            .filter_map(|page| allocator.heap_page(page * PAGE).map(|heap_page| (page, heap_page)))
+            .collect();
+        assert_eq!(queue.mapped_pages(&buffer), expected);
+    }
+
+    #[test]
+    fn allocations_round_up_to_pages_and_coalesce() {
+        let mut allocator = SparseBufferAllocator::new(8 * PAGE, 8, PAGE);
+        assert_eq!(allocator.allocate(1), Ok(0..PAGE));
+        assert_eq!(allocator.allocate(2 * PAGE + 1), Ok(PAGE..4 * PAGE));
+        assert_eq!(allocator.free_heap_page_count(), 4);
+
+        // Both allocations map consecutive heap pages, so one operation covers them.
+        assert_eq!(
+            allocator.take_operations(),
+            [MTL4UpdateSparseBufferMappingOperation::new(MTLSparseTextureMappingMode::Map, 0..4 * PAGE, 0)]
+        );
+        assert!(!allocator.has_pending_operations());
+    }
+
+    #[test]
+    fn freed_pages_are_reused() {
+        let mut allocator = SparseBufferAllocator::new(8 * PAGE, 4, PAGE);
+        let mut queue = SimulatedSparseBufferQueue::new(PAGE);
+        let first = allocator.allocate(2 * PAGE).unwrap();
+        let second = allocator.allocate(2 * PAGE).unwrap();
+        allocator.flush(&mut queue, 0);
+        assert_eq!(
+            allocator.allocate(PAGE),
+            Err(SparseBufferError::OutOfHeapPages {
+                needed: 1,
+                available: 0,
+            })
+        );
+
+        allocator.free(first.start).unwrap();
+        assert_eq!(allocator.free(first.start), Err(SparseBufferError::UnknownAllocation(first.start)));
+        assert_eq!(allocator.allocate(PAGE), Ok(0..PAGE));
+        assert_eq!(allocator.heap_page(0), Some(0));
+        allocator.flush(&mut queue, 0);
+        assert_eq!(queue.operations.len(), 2);
+        assert_matches(&allocator, &queue, 0);
+        assert_eq!(allocator.allocations().collect::<Vec<_>>(), [0..PAGE, second]);
+    }
+
+    #[test]
+    fn unmaps_merge_without_consecutive_heap_pages() {
+        let mut allocator = SparseBufferAllocator::new(5 * PAGE, 4, PAGE);
+        let [_, second, third] = [PAGE; 3].map(|length| allocator.allocate(length).unwrap());
+        allocator.free(second.start).unwrap();
+        allocator.take_operations();
+
+        // The freed heap page and the last one back the new allocation, out
+        // of step with its buffer pages.
+        let fourth = allocator.allocate(2 * PAGE).unwrap();
+        assert_eq!(fourth, 3 * PAGE..5 * PAGE);
+        assert_eq!(
+            allocator.take_operations(),
+            [
+                MTL4UpdateSparseBufferMappingOperation::new(MTLSparseTextureMappingMode::Map, 3 * PAGE..4 * PAGE, PAGE),
+                MTL4UpdateSparseBufferMappingOperation::new(
+                    MTLSparseTextureMappingMode::Map,
+                    4 * PAGE..5 * PAGE,
+                    3 * PAGE
+                ),
+            ]
+        );
+
+        allocator.free(third.start).unwrap();
+        allocator.free(fourth.start).unwrap();
+        assert_eq!(
+            allocator.take_operations(),
+            [MTL4UpdateSparseBufferMappingOperation::new(MTLSparseTextureMappingMode::Unmap, 2 * PAGE..5 * PAGE, 0)]
+        );
+    }
+
+    #[test]
+    fn defragmenting_packs_allocations_into_a_new_buffer() {
+        let mut allocator = SparseBufferAllocator::new(6 * PAGE, 6, PAGE);
+        let mut queue = SimulatedSparseBufferQueue::new(PAGE);
+        let allocations: Vec<_> =
+            [PAGE, 2 * PAGE, PAGE, 2 * PAGE].map(|length| allocator.allocate(length).unwrap()).into();
+        allocator.free(allocations[0].start).unwrap();
+        allocator.free(allocations[2].start).unwrap();
+        allocator.flush(&mut queue, 0);
+        assert_eq!(
+            allocator.allocate(2 * PAGE),
+            Err(SparseBufferError::BufferSpaceExhausted {
+                pages: 2,
+            })
+        );
+
+        let heap_pages: Vec<_> = allocator.allocations().map(|range| allocator.heap_page(range.start)).collect();
+        let relocations = allocator.defragment_into(&mut queue, 0, 1).unwrap();
+        assert_eq!(relocations, [(allocations[1].start, 0..2 * PAGE), (allocations[3].start, 2 * PAGE..4 * PAGE)]);
+        assert_eq!(
+            allocator.allocations().map(|range| allocator.heap_page(range.start)).collect::<Vec<_>>(),
+            heap_pages
+        );
+        assert_matches(&allocator, &queue, 1);
+        assert_eq!(
+            queue.operations.last(),
+            Some(&RecordedSparseBufferOperation::Copy {
+                source: 0,
+                destination: 1,
+                operations: vec![
+                    MTL4CopySparseBufferMappingOperation::new(PAGE..3 * PAGE, 0),
+                    MTL4CopySparseBufferMappingOperation::new(4 * PAGE..6 * PAGE, 2 * PAGE),
+                ],
+            })
+        );
+
+        // The space the gaps took is now free at the end of the new buffer.
+        assert_eq!(allocator.allocate(2 * PAGE), Ok(4 * PAGE..6 * PAGE));
+        allocator.flush(&mut queue, 1);
+        assert_matches(&allocator, &queue, 1);
+    }
+
+    #[test]
+    fn defragmenting_needs_operations_applied() {
+        let mut allocator = SparseBufferAllocator::new(2 * PAGE, 2, PAGE);
+        allocator.allocate(PAGE).unwrap();
+        assert_eq!(allocator.defragment(), Err(SparseBufferError::PendingOperations));
+        allocator.take_operations();
+        let defragmentation = allocator.defragment().unwrap();
+        assert_eq!(defragmentation.copies, [MTL4CopySparseBufferMappingOperation::new(0..PAGE, 0)]);
+    }
+}
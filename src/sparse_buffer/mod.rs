@@ -0,0 +1,7 @@
+mod allocator;
+mod queue;
+
+pub use allocator::{SparseBufferAllocator, SparseBufferDefragmentation, SparseBufferError};
+pub use queue::{
+    RecordedSparseBufferOperation, SimulatedSparseBufferQueue, SparseBufferHeapQueue, SparseBufferMappingQueue,
+};
@@ -0,0 +1,190 @@
+use core::{hash::Hash, ops::Range};
+use std::collections::{BTreeMap, HashMap};
+
+use objc2::runtime::ProtocolObject;
+
+use crate::{
+    MTL4CommandQueue, MTL4CommandQueueExt, MTL4CopySparseBufferMappingOperation,
+    MTL4UpdateSparseBufferMappingOperation, MTLBuffer, MTLHeap, MTLSparseTextureMappingMode,
+};
+
+/// Applies mapping operations to placement sparse buffers.
+///
+/// Implemented by [`SparseBufferHeapQueue`], which submits them to a Metal
+/// queue, and by [`SimulatedSparseBufferQueue`], which applies them to page
+/// tables in memory for tests.
+pub trait SparseBufferMappingQueue<B> {
+    fn update_buffer_mappings(
+        &mut self,
+        buffer: B,
+        operations: &[MTL4UpdateSparseBufferMappingOperation],
+    );
+
+    fn copy_buffer_mappings(
+        &mut self,
+        source: B,
+        destination: B,
+        operations: &[MTL4CopySparseBufferMappingOperation],
+    );
+}
+
+/// A queue that maps buffers to pages of one heap.
+#[derive(Copy, Clone, Debug)]
+pub struct SparseBufferHeapQueue<'a> {
+    pub queue: &'a ProtocolObject<dyn MTL4CommandQueue>,
+    pub heap: &'a ProtocolObject<dyn MTLHeap>,
+}
+
+impl<'a> SparseBufferMappingQueue<&'a ProtocolObject<dyn MTLBuffer>> for SparseBufferHeapQueue<'_> {
+    fn update_buffer_mappings(
+        &mut self,
+        buffer: &'a ProtocolObject<dyn MTLBuffer>,
+        operations: &[MTL4UpdateSparseBufferMappingOperation],
+    ) {
+        self.queue.update_buffer_mappings(buffer, Some(self.heap), operations);
+    }
+
+    fn copy_buffer_mappings(
+        &mut self,
+        source: &'a ProtocolObject<dyn MTLBuffer>,
+        destination: &'a ProtocolObject<dyn MTLBuffer>,
+        operations: &[MTL4CopySparseBufferMappingOperation],
+    ) {
+        self.queue.copy_buffer_mappings(source, destination, operations);
+    }
+}
+
+/// One batch captured by [`SimulatedSparseBufferQueue`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecordedSparseBufferOperation<B> {
+    Update {
+        buffer: B,
+        operations: Vec<MTL4UpdateSparseBufferMappingOperation>,
+    },
+    Copy {
+        source: B,
+        destination: B,
+        operations: Vec<MTL4CopySparseBufferMappingOperation>,
+    },
+}
+
+/// A [`SparseBufferMappingQueue`] that records every batch in order and
+/// keeps the page table of each buffer it sees.
+///
+/// Buffers can be any hashable value, such as integers. Operations must be
+/// aligned to the page size, as Metal requires; misaligned ones panic.
+#[derive(Clone, Debug)]
+pub struct SimulatedSparseBufferQueue<B> {
+    pub operations: Vec<RecordedSparseBufferOperation<B>>,
+    page_size: usize,
+    /// The heap page each mapped buffer page maps to, by buffer.
+    pages: HashMap<B, BTreeMap<usize, usize>>,
+}
+
+impl<B: Clone + Eq + Hash> SimulatedSparseBufferQueue<B> {
+    pub fn new(page_size: usize) -> Self {
+        assert!(page_size > 0, "sparse pages can't be empty");
+        Self {
+            operations: Vec::new(),
+            page_size,
+            pages: HashMap::new(),
+        }
+    }
+
+    /// The heap page the page at byte `offset` of `buffer` maps to.
+    pub fn heap_page(
+        &self,
+        buffer: &B,
+        offset: usize,
+    ) -> Option<usize> {
+        self.pages.get(buffer)?.get(&(offset / self.page_size)).copied()
+    }
+
+    /// The mapped pages of `buffer` and their heap pages, in buffer order.
+    pub fn mapped_pages(
+        &self,
+        buffer: &B,
+    ) -> Vec<(usize, usize)> {
+        self.pages
+            .get(buffer)
+            .map(|pages| pages.iter().map(|(&page, &heap)| (page, heap)).collect())
+            .unwrap_or_default()
+    }
+
+    fn pages(
+        &self,
+        range: Range<usize>,
+    ) -> Range<usize> {
+        self.page(range.start)..self.page(range.end)
+    }
+
+    fn page(
+        &self,
+        offset: usize,
+    ) -> usize {
+        assert!(
+            offset.is_multiple_of(self.page_size),
+            "offset {offset} isn't aligned to {}-byte pages",
+            self.page_size
+        );
+        offset / self.page_size
+    }
+}
+
+impl<B: Clone + Eq + Hash> SparseBufferMappingQueue<B> for SimulatedSparseBufferQueue<B> {
+    fn update_buffer_mappings(
+        &mut self,
+        buffer: B,
+        operations: &[MTL4UpdateSparseBufferMappingOperation],
+    ) {
+        for operation in operations {
+            let pages = self.pages(operation.buffer_range());
+            let first_heap_page = self.page(operation.heap_offset);
+            let table = self.pages.entry(buffer.clone()).or_default();
+            match operation.mode {
+                MTLSparseTextureMappingMode::Map => {
+                    for (index, page) in pages.enumerate() {
+                        table.insert(page, first_heap_page + index);
+                    }
+                },
+                MTLSparseTextureMappingMode::Unmap => {
+                    for page in pages {
+                        table.remove(&page);
+                    }
+                },
+            }
+        }
+        self.operations.push(RecordedSparseBufferOperation::Update {
+            buffer,
+            operations: operations.to_vec(),
+        });
+    }
+
+    fn copy_buffer_mappings(
+        &mut self,
+        source: B,
+        destination: B,
+        operations: &[MTL4CopySparseBufferMappingOperation],
+    ) {
+        for operation in operations {
+            let pages = self.pages(operation.source_range());
+            let destination_start = self.page(operation.destination_offset);
+            let copied: Vec<Option<usize>> = {
+                let table = self.pages.get(&source);
+                pages.map(|page| table.and_then(|table| table.get(&page).copied())).collect()
+            };
+            let table = self.pages.entry(destination.clone()).or_default();
+            for (index, heap_page) in copied.into_iter().enumerate() {
+                match heap_page {
+                    Some(heap_page) => table.insert(destination_start + index, heap_page),
+                    None => table.remove(&(destination_start + index)),
+                };
+            }
+        }
+        self.operations.push(RecordedSparseBufferOperation::Copy {
+            source,
+            destination,
+            operations: operations.to_vec(),
+        });
+    }
+}
@@ -0,0 +1,103 @@
+use core::ptr::NonNull;
+
+use objc2::runtime::ProtocolObject;
+
+use super::{SparseTileGrid, SparseTileUpdate};
+use crate::{
+    MTL4CommandQueue, MTL4CommandQueueExt, MTL4UpdateSparseTextureMappingOperation, MTLDevice, MTLHeap, MTLRegion,
+    MTLResourceStateCommandEncoder, MTLSize, MTLSparsePageSize, MTLSparseTextureMappingMode, MTLTexture,
+    MTLTextureType,
+};
+
+impl SparseTileGrid {
+    /// The tile grid of sparse `texture` on `device`. `page_size` is the
+    /// texture's placement sparse page size, if it's placement sparse.
+    pub fn from_texture(
+        device: &ProtocolObject<dyn MTLDevice>,
+        texture: &ProtocolObject<dyn MTLTexture>,
+        page_size: Option<MTLSparsePageSize>,
+    ) -> Self {
+        let (texture_type, pixel_format, sample_count) =
+            (texture.texture_type(), texture.pixel_format(), texture.sample_count());
+        let (tile_size, tile_size_in_bytes) = match page_size {
+            Some(page_size) => (
+                device.sparse_tile_size_with_page_size(texture_type, pixel_format, sample_count, page_size),
+                device.sparse_tile_size_in_bytes_for_sparse_page_size(page_size),
+            ),
+            None => {
+                (device.sparse_tile_size(texture_type, pixel_format, sample_count), device.sparse_tile_size_in_bytes())
+            },
+        };
+        let slice_count = match texture_type {
+            MTLTextureType::Cube | MTLTextureType::CubeArray => texture.array_length() * 6,
+            _ => texture.array_length(),
+        };
+        Self::new(
+            MTLSize::new(texture.width(), texture.height(), texture.depth()),
+            tile_size,
+            texture.mipmap_level_count(),
+            slice_count,
+            texture.first_mipmap_in_tail(),
+            texture.tail_size_in_bytes().div_ceil(tile_size_in_bytes.max(1)),
+        )
+    }
+}
+
+impl SparseTileUpdate {
+    /// The update as an operation for `MTL4CommandQueueExt::update_texture_mappings`,
+    /// with heap tiles `tile_size_in_bytes` apart.
+    pub fn mtl4_operation(
+        &self,
+        tile_size_in_bytes: usize,
+    ) -> MTL4UpdateSparseTextureMappingOperation {
+        MTL4UpdateSparseTextureMappingOperation {
+            mode: self.mode,
+            texture_region: self.tile_region,
+            texture_level: self.level,
+            texture_slice: self.slice,
+            heap_offset: self.heap_tile * tile_size_in_bytes,
+        }
+    }
+}
+
+/// Applies `updates` to placement sparse `texture` on `queue`, mapping tiles
+/// to `heap`, whose tiles are `tile_size_in_bytes` apart.
+pub fn update_sparse_tile_mappings(
+    queue: &ProtocolObject<dyn MTL4CommandQueue>,
+    texture: &ProtocolObject<dyn MTLTexture>,
+    heap: &ProtocolObject<dyn MTLHeap>,
+    updates: &[SparseTileUpdate],
+    tile_size_in_bytes: usize,
+) {
+    if updates.is_empty() {
+        return;
+    }
+    let operations: Vec<_> = updates.iter().map(|update| update.mtl4_operation(tile_size_in_bytes)).collect();
+    queue.update_texture_mappings(texture, Some(heap), &operations);
+}
+
+/// Encodes `updates` to sparse `texture`, which allocates its tiles from its
+/// sparse heap, so heap tiles are ignored.
+pub fn encode_sparse_tile_updates(
+    encoder: &ProtocolObject<dyn MTLResourceStateCommandEncoder>,
+    texture: &ProtocolObject<dyn MTLTexture>,
+    updates: &[SparseTileUpdate],
+) {
+    for mode in [MTLSparseTextureMappingMode::Unmap, MTLSparseTextureMappingMode::Map] {
+        let updates = updates.iter().filter(|update| update.mode == mode);
+        let regions: Vec<MTLRegion> = updates.clone().map(|update| update.tile_region).collect();
+        if regions.is_empty() {
+            continue;
+        }
+        let levels: Vec<usize> = updates.clone().map(|update| update.level).collect();
+        let slices: Vec<usize> = updates.map(|update| update.slice).collect();
+        encoder.update_texture_mappings(
+            texture,
+            mode,
+            NonNull::from(regions.as_slice()).cast(),
+            NonNull::from(levels.as_slice()).cast(),
+            NonNull::from(slices.as_slice()).cast(),
+            regions.len(),
+        );
+    }
+}
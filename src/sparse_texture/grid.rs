@@ -0,0 +1,465 @@
+use core::fmt;
+
+use crate::{MTLOrigin, MTLRegion, MTLSize, MTLSparseTextureRegionAlignmentMode};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SparseTextureError {
+    LevelOutOfRange {
+        level: usize,
+        mipmap_level_count: usize,
+    },
+    SliceOutOfRange {
+        slice: usize,
+        slice_count: usize,
+    },
+    /// The region extends past the level's tiles, or pixels for
+    /// [`SparseTileGrid::tile_region`].
+    RegionOutOfBounds {
+        level: usize,
+        region: MTLRegion,
+    },
+    /// Mapping needs more heap tiles than remain in the budget.
+    OutOfHeapTiles {
+        needed: usize,
+        available: usize,
+    },
+}
+
+impl fmt::Display for SparseTextureError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::LevelOutOfRange {
+                level,
+                mipmap_level_count,
+            } => write!(f, "mipmap level {level} is out of range for {mipmap_level_count} levels"),
+            Self::SliceOutOfRange {
+                slice,
+                slice_count,
+            } => write!(f, "slice {slice} is out of range for {slice_count} slices"),
+            Self::RegionOutOfBounds {
+                level,
+                region,
+            } => write!(f, "region {region:?} is out of bounds for mipmap level {level}"),
+            Self::OutOfHeapTiles {
+                needed,
+                available,
+            } => write!(f, "mapping needs {needed} heap tiles, but only {available} are free"),
+        }
+    }
+}
+
+impl std::error::Error for SparseTextureError {}
+
+/// The sparse tiles of a texture: how many tiles each mipmap level spans,
+/// and the mip tail that holds the levels smaller than a tile.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SparseTileGrid {
+    size: MTLSize,
+    tile_size: MTLSize,
+    mipmap_level_count: usize,
+    slice_count: usize,
+    first_mipmap_in_tail: usize,
+    tail_tile_count: usize,
+}
+
+impl SparseTileGrid {
+    /// A grid of tiles of `tile_size` pixels over a `size` texture.
+    ///
+    /// Levels from `first_mipmap_in_tail` on share the mip tail, which is
+    /// mapped as a whole per slice and takes `tail_tile_count` heap tiles.
+    pub fn new(
+        size: MTLSize,
+        tile_size: MTLSize,
+        mipmap_level_count: usize,
+        slice_count: usize,
+        first_mipmap_in_tail: usize,
+        tail_tile_count: usize,
+    ) -> Self {
+        assert!(tile_size.width > 0 && tile_size.height > 0 && tile_size.depth > 0, "tiles must not be empty");
+        Self {
+            size,
+            tile_size,
+            mipmap_level_count,
+            slice_count,
+            first_mipmap_in_tail: first_mipmap_in_tail.min(mipmap_level_count),
+            tail_tile_count,
+        }
+    }
+
+    pub fn tile_size(&self) -> MTLSize {
+        self.tile_size
+    }
+
+    pub fn mipmap_level_count(&self) -> usize {
+        self.mipmap_level_count
+    }
+
+    /// Array elements, or faces for cube textures.
+    pub fn slice_count(&self) -> usize {
+        self.slice_count
+    }
+
+    /// The first level in the mip tail; [`mipmap_level_count`](Self::mipmap_level_count)
+    /// if there is no tail.
+    pub fn first_mipmap_in_tail(&self) -> usize {
+        self.first_mipmap_in_tail
+    }
+
+    /// Heap tiles the mip tail of one slice takes.
+    pub fn tail_tile_count(&self) -> usize {
+        if self.has_tail() {
+            self.tail_tile_count
+        } else {
+            0
+        }
+    }
+
+    pub fn has_tail(&self) -> bool {
+        self.first_mipmap_in_tail < self.mipmap_level_count
+    }
+
+    /// Whether `level` is in the mip tail.
+    pub fn is_in_tail(
+        &self,
+        level: usize,
+    ) -> bool {
+        level >= self.first_mipmap_in_tail
+    }
+
+    /// Size of `level` in pixels. Levels past the last one are 1x1x1.
+    pub fn level_size(
+        &self,
+        level: usize,
+    ) -> MTLSize {
+        let shift = u32::try_from(level).unwrap_or(u32::MAX);
+        let halve = |extent: usize| extent.checked_shr(shift).unwrap_or(0).max(1);
+        MTLSize {
+            width: halve(self.size.width),
+            height: halve(self.size.height),
+            depth: halve(self.size.depth),
+        }
+    }
+
+    /// Size of `level` in tiles, rounding partial tiles up. Levels in the
+    /// mip tail are one tile.
+    pub fn level_tiles(
+        &self,
+        level: usize,
+    ) -> MTLSize {
+        if self.is_in_tail(level) {
+            return MTLSize::new(1, 1, 1);
+        }
+        let size = self.level_size(level);
+        MTLSize {
+            width: size.width.div_ceil(self.tile_size.width),
+            height: size.height.div_ceil(self.tile_size.height),
+            depth: size.depth.div_ceil(self.tile_size.depth),
+        }
+    }
+
+    /// Tiles tracked per slice: every tile of the levels before the mip
+    /// tail, and one for the tail.
+    pub(crate) fn tiles_per_slice(&self) -> usize {
+        let levels = 0..self.first_mipmap_in_tail;
+        let tiles: usize = levels.map(|level| volume(self.level_tiles(level))).sum();
+        tiles + usize::from(self.has_tail())
+    }
+
+    /// Index of the first tile of `level` among a slice's tiles; levels in
+    /// the mip tail share the tail's tile.
+    pub(crate) fn level_tile_offset(
+        &self,
+        level: usize,
+    ) -> usize {
+        let levels = 0..level.min(self.first_mipmap_in_tail);
+        levels.map(|level| volume(self.level_tiles(level))).sum()
+    }
+
+    /// The tiles covering `region` of `level`, in pixels, like
+    /// `MTLDevice`'s `convertSparsePixelRegions`. `Outward` includes partly
+    /// covered tiles and `Inward` only those entirely covered, so the result
+    /// may be empty. Levels in the mip tail always give the tail's tile.
+    pub fn tile_region(
+        &self,
+        level: usize,
+        region: MTLRegion,
+        mode: MTLSparseTextureRegionAlignmentMode,
+    ) -> Result<MTLRegion, SparseTextureError> {
+        self.check_level(level)?;
+        let size = self.level_size(level);
+        let fits =
+            |origin: usize, extent: usize, size: usize| origin.checked_add(extent).is_some_and(|end| end <= size);
+        let valid = fits(region.origin.x, region.size.width, size.width)
+            && fits(region.origin.y, region.size.height, size.height)
+            && fits(region.origin.z, region.size.depth, size.depth);
+        if !valid {
+            return Err(SparseTextureError::RegionOutOfBounds {
+                level,
+                region,
+            });
+        }
+        if self.is_in_tail(level) {
+            return Ok(MTLRegion {
+                origin: MTLOrigin {
+                    x: 0,
+                    y: 0,
+                    z: 0,
+                },
+                size: MTLSize::new(1, 1, 1),
+            });
+        }
+
+        // A partial tile at the end of the level counts as covered when the
+        // region reaches the level's edge.
+        let span = |origin: usize, extent: usize, size: usize, tile: usize| {
+            let end = origin + extent;
+            let (start, end) = match mode {
+                MTLSparseTextureRegionAlignmentMode::Outward => (origin / tile, end.div_ceil(tile)),
+                MTLSparseTextureRegionAlignmentMode::Inward => {
+                    let end = if end == size {
+                        end.div_ceil(tile)
+                    } else {
+                        end / tile
+                    };
+                    (origin.div_ceil(tile), end)
+                },
+            };
+            (start, end.saturating_sub(start))
+        };
+        let (x, width) = span(region.origin.x, region.size.width, size.width, self.tile_size.width);
+        let (y, height) = span(region.origin.y, region.size.height, size.height, self.tile_size.height);
+        let (z, depth) = span(region.origin.z, region.size.depth, size.depth, self.tile_size.depth);
+        Ok(MTLRegion {
+            origin: MTLOrigin {
+                x,
+                y,
+                z,
+            },
+            size: MTLSize {
+                width,
+                height,
+                depth,
+            },
+        })
+    }
+
+    /// The pixels `region` of `level`'s tiles covers, clamped to the level,
+    /// like `MTLDevice`'s `convertSparseTileRegions`.
+    pub fn pixel_region(
+        &self,
+        level: usize,
+        region: MTLRegion,
+    ) -> Result<MTLRegion, SparseTextureError> {
+        self.check_tile_region(level, region)?;
+        let size = self.level_size(level);
+        if self.is_in_tail(level) {
+            return Ok(MTLRegion {
+                origin: MTLOrigin {
+                    x: 0,
+                    y: 0,
+                    z: 0,
+                },
+                size,
+            });
+        }
+        let span = |origin: usize, extent: usize, size: usize, tile: usize| {
+            let start = (origin * tile).min(size);
+            (start, ((origin + extent) * tile).min(size) - start)
+        };
+        let (x, width) = span(region.origin.x, region.size.width, size.width, self.tile_size.width);
+        let (y, height) = span(region.origin.y, region.size.height, size.height, self.tile_size.height);
+        let (z, depth) = span(region.origin.z, region.size.depth, size.depth, self.tile_size.depth);
+        Ok(MTLRegion {
+            origin: MTLOrigin {
+                x,
+                y,
+                z,
+            },
+            size: MTLSize {
+                width,
+                height,
+                depth,
+            },
+        })
+    }
+
+    pub(crate) fn check_level(
+        &self,
+        level: usize,
+    ) -> Result<(), SparseTextureError> {
+        if level >= self.mipmap_level_count {
+            return Err(SparseTextureError::LevelOutOfRange {
+                level,
+                mipmap_level_count: self.mipmap_level_count,
+            });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_slice(
+        &self,
+        slice: usize,
+    ) -> Result<(), SparseTextureError> {
+        if slice >= self.slice_count {
+            return Err(SparseTextureError::SliceOutOfRange {
+                slice,
+                slice_count: self.slice_count,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks `region`, in tiles, lies within `level`.
+    pub(crate) fn check_tile_region(
+        &self,
+        level: usize,
+        region: MTLRegion,
+    ) -> Result<(), SparseTextureError> {
+        self.check_level(level)?;
+        let tiles = self.level_tiles(level);
+        let fits =
+            |origin: usize, extent: usize, size: usize| origin.checked_add(extent).is_some_and(|end| end <= size);
+        let valid = fits(region.origin.x, region.size.width, tiles.width)
+            && fits(region.origin.y, region.size.height, tiles.height)
+            && fits(region.origin.z, region.size.depth, tiles.depth);
+        if !valid {
+            return Err(SparseTextureError::RegionOutOfBounds {
+                level,
+                region,
+            });
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn volume(size: MTLSize) -> usize {
+    size.width * size.height * size.depth
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 100x70 texture with 16x16 tiles: levels 0 to 2 span 7x5, 4x3 and
+    /// 2x2 tiles, and levels 3 and 4 share a two-tile mip tail.
+    fn grid() -> SparseTileGrid {
+        SparseTileGrid::new(MTLSize::new(100, 70, 1), MTLSize::new(16, 16, 1), 5, 2, 3, 2)
+    }
+
+    fn region(
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> MTLRegion {
+        MTLRegion {
+            origin: MTLOrigin {
+                x,
+                y,
+                z: 0,
+            },
+            size: MTLSize::new(width, height, 1),
+        }
+    }
+
+    #[test]
+    fn levels_and_tail() {
+        let grid = grid();
+        assert_eq!(grid.level_tiles(0), MTLSize::new(7, 5, 1));
+        assert_eq!(grid.level_tiles(1), MTLSize::new(4, 3, 1));
+        assert_eq!(grid.level_tiles(2), MTLSize::new(2, 2, 1));
+        assert_eq!(grid.level_tiles(4), MTLSize::new(1, 1, 1));
+        assert_eq!(grid.level_size(4), MTLSize::new(6, 4, 1));
+        assert!(grid.has_tail() && grid.is_in_tail(3) && !grid.is_in_tail(2));
+        assert_eq!(grid.tail_tile_count(), 2);
+        assert_eq!(grid.tiles_per_slice(), 35 + 12 + 4 + 1);
+        assert_eq!(grid.level_tile_offset(2), 47);
+        assert_eq!(grid.level_tile_offset(4), 51);
+
+        let no_tail = SparseTileGrid::new(MTLSize::new(64, 64, 1), MTLSize::new(16, 16, 1), 3, 1, 9, 2);
+        assert_eq!(no_tail.first_mipmap_in_tail(), 3);
+        assert!(!no_tail.has_tail());
+        assert_eq!(no_tail.tail_tile_count(), 0);
+        assert_eq!(no_tail.tiles_per_slice(), 16 + 4 + 1);
+    }
+
+    #[test]
+    fn out_of_range_level_size() {
+        let grid = grid();
+        assert_eq!(grid.level_size(7), MTLSize::new(1, 1, 1));
+        // Shifts of the word size or more would overflow.
+        assert_eq!(grid.level_size(usize::BITS as usize), MTLSize::new(1, 1, 1));
+        assert_eq!(grid.level_size(usize::MAX), MTLSize::new(1, 1, 1));
+        assert_eq!(grid.level_tiles(usize::MAX), MTLSize::new(1, 1, 1));
+    }
+
+    #[test]
+    fn tile_region_alignment() {
+        use MTLSparseTextureRegionAlignmentMode::{Inward, Outward};
+
+        let grid = grid();
+        let cases = [
+            // Interior pixels: outward grows to partly covered tiles, inward
+            // shrinks to entirely covered ones.
+            (region(10, 0, 30, 16), Outward, region(0, 0, 3, 1)),
+            (region(10, 0, 30, 16), Inward, region(1, 0, 1, 1)),
+            (region(20, 20, 10, 10), Outward, region(1, 1, 1, 1)),
+            (region(20, 20, 10, 10), Inward, region(2, 2, 0, 0)),
+            // The partial tiles at the right and bottom edges count as
+            // covered when the region reaches the edge.
+            (region(90, 60, 10, 10), Outward, region(5, 3, 2, 2)),
+            (region(90, 60, 10, 10), Inward, region(6, 4, 1, 1)),
+            (region(96, 64, 4, 6), Inward, region(6, 4, 1, 1)),
+            (region(0, 0, 100, 70), Inward, region(0, 0, 7, 5)),
+            (region(0, 0, 99, 69), Inward, region(0, 0, 6, 4)),
+        ];
+        for (pixels, mode, tiles) in cases {
+            assert_eq!(grid.tile_region(0, pixels, mode), Ok(tiles), "{pixels:?} {mode:?}");
+        }
+        // Level 2 is 25x17 pixels.
+        assert_eq!(grid.tile_region(2, region(16, 16, 9, 1), Inward), Ok(region(1, 1, 1, 1)));
+        assert_eq!(grid.tile_region(2, region(16, 15, 9, 1), Inward), Ok(region(1, 1, 1, 0)));
+        // Levels in the tail are its single tile.
+        assert_eq!(grid.tile_region(4, region(1, 1, 2, 2), Inward), Ok(region(0, 0, 1, 1)));
+
+        assert_eq!(
+            grid.tile_region(0, region(90, 0, 11, 1), Outward),
+            Err(SparseTextureError::RegionOutOfBounds {
+                level: 0,
+                region: region(90, 0, 11, 1),
+            })
+        );
+        assert_eq!(
+            grid.tile_region(3, region(0, 0, 13, 1), Outward),
+            Err(SparseTextureError::RegionOutOfBounds {
+                level: 3,
+                region: region(0, 0, 13, 1),
+            })
+        );
+        assert_eq!(
+            grid.tile_region(5, region(0, 0, 1, 1), Outward),
+            Err(SparseTextureError::LevelOutOfRange {
+                level: 5,
+                mipmap_level_count: 5,
+            })
+        );
+    }
+
+    #[test]
+    fn pixel_region() {
+        let grid = grid();
+        assert_eq!(grid.pixel_region(0, region(1, 1, 2, 1)), Ok(region(16, 16, 32, 16)));
+        assert_eq!(grid.pixel_region(0, region(6, 4, 1, 1)), Ok(region(96, 64, 4, 6)));
+        assert_eq!(grid.pixel_region(4, region(0, 0, 1, 1)), Ok(region(0, 0, 6, 4)));
+        assert_eq!(
+            grid.pixel_region(1, region(3, 0, 2, 1)),
+            Err(SparseTextureError::RegionOutOfBounds {
+                level: 1,
+                region: region(3, 0, 2, 1),
+            })
+        );
+    }
+}
@@ -0,0 +1,7 @@
+mod encode;
+mod grid;
+mod residency;
+
+pub use encode::{encode_sparse_tile_updates, update_sparse_tile_mappings};
+pub use grid::{SparseTextureError, SparseTileGrid};
+pub use residency::{SparseTextureResidency, SparseTileUpdate};
@@ -0,0 +1,525 @@
+use std::collections::BTreeSet;
+
+use super::{SparseTextureError, SparseTileGrid, grid::volume};
+use crate::{MTLOrigin, MTLRegion, MTLSize, MTLSparseTextureMappingMode, MTLSparseTextureRegionAlignmentMode};
+
+/// A change to a sparse texture's tile mappings.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SparseTileUpdate {
+    pub mode: MTLSparseTextureMappingMode,
+    pub level: usize,
+    pub slice: usize,
+    /// The tiles to update, a run along one row.
+    pub tile_region: MTLRegion,
+    /// The heap tile the region's first tile maps to; the rest map to the
+    /// tiles after it. 0 for unmaps.
+    pub heap_tile: usize,
+}
+
+/// Tracks which tiles of a sparse texture are mapped, and to which tiles of
+/// a heap with a fixed budget.
+///
+/// Mapping and unmapping only change the bookkeeping;
+/// [`take_updates`](Self::take_updates) returns the changes since it was
+/// last called, to encode in one batch.
+#[derive(Clone, Debug)]
+pub struct SparseTextureResidency {
+    grid: SparseTileGrid,
+    /// Whether each heap tile is in use.
+    heap_tiles: Vec<bool>,
+    free_heap_tiles: usize,
+    /// The heap tile each texture tile maps to, by slice, level and tile.
+    mapped: Vec<Option<usize>>,
+    /// `mapped` as of the last `take_updates`.
+    committed: Vec<Option<usize>>,
+    /// Slices and levels whose mappings changed since the last
+    /// `take_updates`.
+    dirty: BTreeSet<(usize, usize)>,
+}
+
+impl SparseTextureResidency {
+    /// Tracks a texture with tiles `grid`, backed by `heap_tile_count` heap
+    /// tiles.
+    pub fn new(
+        grid: SparseTileGrid,
+        heap_tile_count: usize,
+    ) -> Self {
+        let tile_count = grid.tiles_per_slice() * grid.slice_count();
+        Self {
+            grid,
+            heap_tiles: vec![false; heap_tile_count],
+            free_heap_tiles: heap_tile_count,
+            mapped: vec![None; tile_count],
+            committed: vec![None; tile_count],
+            dirty: BTreeSet::new(),
+        }
+    }
+
+    pub fn grid(&self) -> &SparseTileGrid {
+        &self.grid
+    }
+
+    pub fn heap_tile_count(&self) -> usize {
+        self.heap_tiles.len()
+    }
+
+    pub fn free_heap_tile_count(&self) -> usize {
+        self.free_heap_tiles
+    }
+
+    /// The heap tile `tile` of `level` and `slice` maps to, if it's mapped.
+    pub fn heap_tile(
+        &self,
+        level: usize,
+        slice: usize,
+        tile: MTLOrigin,
+    ) -> Result<Option<usize>, SparseTextureError> {
+        let region = MTLRegion {
+            origin: tile,
+            size: MTLSize::new(1, 1, 1),
+        };
+        self.check(level, slice, region)?;
+        Ok(self.mapped[self.tile_index(level, slice, tile)])
+    }
+
+    pub fn is_mapped(
+        &self,
+        level: usize,
+        slice: usize,
+        tile: MTLOrigin,
+    ) -> Result<bool, SparseTextureError> {
+        self.heap_tile(level, slice, tile).map(|heap_tile| heap_tile.is_some())
+    }
+
+    /// Maps the unmapped tiles of `tile_region`, returning how many heap
+    /// tiles that took. Nothing is mapped if the budget can't cover them all.
+    pub fn map(
+        &mut self,
+        level: usize,
+        slice: usize,
+        tile_region: MTLRegion,
+    ) -> Result<usize, SparseTextureError> {
+        self.check(level, slice, tile_region)?;
+        let unmapped: Vec<usize> =
+            self.tile_indices(level, slice, tile_region).filter(|&index| self.mapped[index].is_none()).collect();
+        let tile_cost = self.tile_cost(level);
+        let needed = unmapped.len() * tile_cost;
+        if needed > self.free_heap_tiles {
+            return Err(SparseTextureError::OutOfHeapTiles {
+                needed,
+                available: self.free_heap_tiles,
+            });
+        }
+        for index in unmapped {
+            // Only the tail, a single tile, needs more than one heap tile, so
+            // fragmentation can only fail before anything is mapped.
+            let heap_tile = self.allocate(tile_cost).ok_or(SparseTextureError::OutOfHeapTiles {
+                needed: tile_cost,
+                available: self.free_heap_tiles,
+            })?;
+            self.mapped[index] = Some(heap_tile);
+            self.dirty.insert((slice, self.tracked_level(level)));
+        }
+        Ok(needed)
+    }
+
+    /// Unmaps the mapped tiles of `tile_region`, returning how many heap
+    /// tiles that freed.
+    pub fn unmap(
+        &mut self,
+        level: usize,
+        slice: usize,
+        tile_region: MTLRegion,
+    ) -> Result<usize, SparseTextureError> {
+        self.check(level, slice, tile_region)?;
+        let tile_cost = self.tile_cost(level);
+        let tracked_level = self.tracked_level(level);
+        let indices: Vec<usize> = self.tile_indices(level, slice, tile_region).collect();
+        let mut freed = 0;
+        for index in indices {
+            if let Some(heap_tile) = self.mapped[index].take() {
+                self.heap_tiles[heap_tile..heap_tile + tile_cost].fill(false);
+                self.free_heap_tiles += tile_cost;
+                freed += tile_cost;
+                self.dirty.insert((slice, tracked_level));
+            }
+        }
+        Ok(freed)
+    }
+
+    /// Maps every tile `region` of `level` touches, in pixels.
+    pub fn map_pixels(
+        &mut self,
+        level: usize,
+        slice: usize,
+        region: MTLRegion,
+    ) -> Result<usize, SparseTextureError> {
+        let tile_region = self.grid.tile_region(level, region, MTLSparseTextureRegionAlignmentMode::Outward)?;
+        self.map(level, slice, tile_region)
+    }
+
+    /// Unmaps the tiles `region` of `level` entirely covers, in pixels.
+    pub fn unmap_pixels(
+        &mut self,
+        level: usize,
+        slice: usize,
+        region: MTLRegion,
+    ) -> Result<usize, SparseTextureError> {
+        let tile_region = self.grid.tile_region(level, region, MTLSparseTextureRegionAlignmentMode::Inward)?;
+        self.unmap(level, slice, tile_region)
+    }
+
+    /// Whether mappings changed since the last [`take_updates`](Self::take_updates).
+    pub fn has_updates(&self) -> bool {
+        !self.dirty.is_empty()
+    }
+
+    /// The mapping changes since the last call: unmaps first, so heap tiles
+    /// they free can be reused by the maps, then maps. Runs of tiles along a
+    /// row are merged, and tiles mapped and unmapped again in between are
+    /// left out.
+    pub fn take_updates(&mut self) -> Vec<SparseTileUpdate> {
+        let mut unmaps = Vec::new();
+        let mut maps = Vec::new();
+        for (slice, level) in std::mem::take(&mut self.dirty) {
+            let tiles = self.grid.level_tiles(level);
+            for z in 0..tiles.depth {
+                for y in 0..tiles.height {
+                    for x in 0..tiles.width {
+                        let index = self.tile_index(
+                            level,
+                            slice,
+                            MTLOrigin {
+                                x,
+                                y,
+                                z,
+                            },
+                        );
+                        let (before, after) = (self.committed[index], self.mapped[index]);
+                        if before == after {
+                            continue;
+                        }
+                        let origin = MTLOrigin {
+                            x,
+                            y,
+                            z,
+                        };
+                        if before.is_some() {
+                            push_run(&mut unmaps, MTLSparseTextureMappingMode::Unmap, level, slice, origin, 0);
+                        }
+                        if let Some(heap_tile) = after {
+                            push_run(&mut maps, MTLSparseTextureMappingMode::Map, level, slice, origin, heap_tile);
+                        }
+                        self.committed[index] = after;
+                    }
+                }
+            }
+        }
+        unmaps.extend(maps);
+        unmaps
+    }
+
+    fn check(
+        &self,
+        level: usize,
+        slice: usize,
+        tile_region: MTLRegion,
+    ) -> Result<(), SparseTextureError> {
+        self.grid.check_slice(slice)?;
+        self.grid.check_tile_region(level, tile_region)
+    }
+
+    /// The level tiles of `level` are tracked under: the first in the tail
+    /// for levels in the tail.
+    fn tracked_level(
+        &self,
+        level: usize,
+    ) -> usize {
+        level.min(self.grid.first_mipmap_in_tail())
+    }
+
+    /// Heap tiles each tile of `level` takes.
+    fn tile_cost(
+        &self,
+        level: usize,
+    ) -> usize {
+        if self.grid.is_in_tail(level) {
+            self.grid.tail_tile_count().max(1)
+        } else {
+            1
+        }
+    }
+
+    fn tile_index(
+        &self,
+        level: usize,
+        slice: usize,
+        tile: MTLOrigin,
+    ) -> usize {
+        let tiles = self.grid.level_tiles(level);
+        let within_level = (tile.z * tiles.height + tile.y) * tiles.width + tile.x;
+        slice * self.grid.tiles_per_slice() + self.grid.level_tile_offset(level) + within_level
+    }
+
+    fn tile_indices(
+        &self,
+        level: usize,
+        slice: usize,
+        region: MTLRegion,
+    ) -> impl Iterator<Item = usize> + '_ {
+        let MTLRegion {
+            origin,
+            size,
+        } = region;
+        (0..volume(size)).map(move |offset| {
+            let tile = MTLOrigin {
+                x: origin.x + offset % size.width,
+                y: origin.y + offset / size.width % size.height,
+                z: origin.z + offset / (size.width * size.height),
+            };
+            self.tile_index(level, slice, tile)
+        })
+    }
+
+    /// Takes the first run of `count` free heap tiles.
+    fn allocate(
+        &mut self,
+        count: usize,
+    ) -> Option<usize> {
+        let mut run = 0;
+        for index in 0..self.heap_tiles.len() {
+            run = if self.heap_tiles[index] {
+                0
+            } else {
+                run + 1
+            };
+            if run == count {
+                let start = index + 1 - count;
+                self.heap_tiles[start..=index].fill(true);
+                self.free_heap_tiles -= count;
+                return Some(start);
+            }
+        }
+        None
+    }
+}
+
+/// Appends a one-tile update at `origin`, extending the last update instead
+/// when the tile continues its run.
+fn push_run(
+    updates: &mut Vec<SparseTileUpdate>,
+    mode: MTLSparseTextureMappingMode,
+    level: usize,
+    slice: usize,
+    origin: MTLOrigin,
+    heap_tile: usize,
+) {
+    if let Some(last) = updates.last_mut() {
+        let region = last.tile_region;
+        let continues = last.level == level
+            && last.slice == slice
+            && region.origin.y == origin.y
+            && region.origin.z == origin.z
+            && region.origin.x + region.size.width == origin.x
+            && (mode == MTLSparseTextureMappingMode::Unmap || last.heap_tile + region.size.width == heap_tile);
+        if continues {
+            last.tile_region.size.width += 1;
+            return;
+        }
+    }
+    updates.push(SparseTileUpdate {
+        mode,
+        level,
+        slice,
+        tile_region: MTLRegion {
+            origin,
+            size: MTLSize::new(1, 1, 1),
+        },
+        heap_tile,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 100x70 texture with 16x16 tiles in 2 slices: levels 0 to 2 span
+    /// 7x5, 4x3 and 2x2 tiles, and levels 3 and 4 share a two-tile mip tail.
+    fn grid() -> SparseTileGrid {
+        SparseTileGrid::new(MTLSize::new(100, 70, 1), MTLSize::new(16, 16, 1), 5, 2, 3, 2)
+    }
+
+    fn region(
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> MTLRegion {
+        MTLRegion {
+            origin: tile(x, y),
+            size: MTLSize::new(width, height, 1),
+        }
+    }
+
+    fn tile(
+        x: usize,
+        y: usize,
+    ) -> MTLOrigin {
+        MTLOrigin {
+            x,
+            y,
+            z: 0,
+        }
+    }
+
+    fn update(
+        mode: MTLSparseTextureMappingMode,
+        level: usize,
+        slice: usize,
+        tile_region: MTLRegion,
+        heap_tile: usize,
+    ) -> SparseTileUpdate {
+        SparseTileUpdate {
+            mode,
+            level,
+            slice,
+            tile_region,
+            heap_tile,
+        }
+    }
+
+    #[test]
+    fn mip_tail_costs_its_heap_tiles() {
+        let mut residency = SparseTextureResidency::new(grid(), 8);
+        assert_eq!(residency.map(4, 1, region(0, 0, 1, 1)), Ok(2));
+        assert_eq!(residency.free_heap_tile_count(), 6);
+        // The tail's levels share its mapping.
+        assert_eq!(residency.map(3, 1, region(0, 0, 1, 1)), Ok(0));
+        assert_eq!(residency.heap_tile(3, 1, tile(0, 0)), Ok(Some(0)));
+        assert_eq!(residency.is_mapped(3, 0, tile(0, 0)), Ok(false));
+        assert_eq!(residency.map_pixels(4, 0, region(5, 3, 1, 1)), Ok(2));
+        assert_eq!(
+            residency.take_updates(),
+            [
+                update(MTLSparseTextureMappingMode::Map, 3, 0, region(0, 0, 1, 1), 2),
+                update(MTLSparseTextureMappingMode::Map, 3, 1, region(0, 0, 1, 1), 0),
+            ]
+        );
+        assert_eq!(residency.unmap(3, 1, region(0, 0, 1, 1)), Ok(2));
+        assert_eq!(residency.unmap(4, 1, region(0, 0, 1, 1)), Ok(0));
+        assert_eq!(residency.free_heap_tile_count(), 6);
+    }
+
+    #[test]
+    fn exhausted_budget_leaves_state_untouched() {
+        let mut residency = SparseTextureResidency::new(grid(), 4);
+        assert_eq!(residency.map(0, 0, region(0, 0, 3, 1)), Ok(3));
+        residency.take_updates();
+
+        assert_eq!(
+            residency.map(0, 0, region(0, 1, 2, 1)),
+            Err(SparseTextureError::OutOfHeapTiles {
+                needed: 2,
+                available: 1,
+            })
+        );
+        assert_eq!(residency.free_heap_tile_count(), 1);
+        assert_eq!(residency.is_mapped(0, 0, tile(0, 1)), Ok(false));
+        assert!(!residency.has_updates());
+        // Already mapped tiles don't count against the budget.
+        assert_eq!(residency.map(0, 0, region(0, 0, 4, 1)), Ok(1));
+        assert_eq!(residency.free_heap_tile_count(), 0);
+
+        // Two heap tiles are free, but not two adjacent ones for the tail.
+        assert_eq!(residency.unmap(0, 0, region(1, 0, 1, 1)), Ok(1));
+        assert_eq!(residency.unmap(0, 0, region(3, 0, 1, 1)), Ok(1));
+        residency.take_updates();
+        assert_eq!(
+            residency.map(3, 0, region(0, 0, 1, 1)),
+            Err(SparseTextureError::OutOfHeapTiles {
+                needed: 2,
+                available: 2,
+            })
+        );
+        assert_eq!(residency.free_heap_tile_count(), 2);
+        assert_eq!(residency.is_mapped(3, 0, tile(0, 0)), Ok(false));
+        assert!(!residency.has_updates());
+
+        assert_eq!(
+            residency.map(0, 2, region(0, 0, 1, 1)),
+            Err(SparseTextureError::SliceOutOfRange {
+                slice: 2,
+                slice_count: 2,
+            })
+        );
+        assert_eq!(
+            residency.map(1, 0, region(3, 2, 2, 1)),
+            Err(SparseTextureError::RegionOutOfBounds {
+                level: 1,
+                region: region(3, 2, 2, 1),
+            })
+        );
+    }
+
+    #[test]
+    fn take_updates_merges_runs() {
+        use MTLSparseTextureMappingMode::{Map, Unmap};
+
+        let mut residency = SparseTextureResidency::new(grid(), 16);
+        assert_eq!(residency.map(0, 0, region(0, 0, 4, 2)), Ok(8));
+        assert_eq!(residency.map(1, 1, region(2, 1, 2, 1)), Ok(2));
+        // Mapped and unmapped again before the updates are taken.
+        assert_eq!(residency.map(2, 0, region(0, 0, 1, 1)), Ok(1));
+        assert_eq!(residency.unmap(2, 0, region(0, 0, 1, 1)), Ok(1));
+        assert!(residency.has_updates());
+        assert_eq!(
+            residency.take_updates(),
+            [
+                update(Map, 0, 0, region(0, 0, 4, 1), 0),
+                update(Map, 0, 0, region(0, 1, 4, 1), 4),
+                update(Map, 1, 1, region(2, 1, 2, 1), 8),
+            ]
+        );
+        assert!(!residency.has_updates());
+        assert_eq!(residency.take_updates(), []);
+
+        // Freed heap tiles are reused in order, so tiles 5 and 6 map to
+        // consecutive heap tiles 1 and 2.
+        assert_eq!(residency.unmap(0, 0, region(1, 0, 2, 1)), Ok(2));
+        assert_eq!(residency.map(0, 0, region(5, 0, 2, 1)), Ok(2));
+        assert_eq!(
+            residency.take_updates(),
+            [update(Unmap, 0, 0, region(1, 0, 2, 1), 0), update(Map, 0, 0, region(5, 0, 2, 1), 1)]
+        );
+    }
+
+    #[test]
+    fn take_updates_remaps() {
+        use MTLSparseTextureMappingMode::{Map, Unmap};
+
+        let mut residency = SparseTextureResidency::new(grid(), 16);
+        assert_eq!(residency.map(0, 0, region(0, 0, 4, 1)), Ok(4));
+        residency.take_updates();
+
+        // Swap the heap tiles of tiles 0 and 3.
+        assert_eq!(residency.unmap(0, 0, region(0, 0, 1, 1)), Ok(1));
+        assert_eq!(residency.unmap(0, 0, region(3, 0, 1, 1)), Ok(1));
+        assert_eq!(residency.map(0, 0, region(3, 0, 1, 1)), Ok(1));
+        assert_eq!(residency.map(0, 0, region(0, 0, 1, 1)), Ok(1));
+        assert_eq!(residency.heap_tile(0, 0, tile(0, 0)), Ok(Some(3)));
+        assert_eq!(
+            residency.take_updates(),
+            [
+                update(Unmap, 0, 0, region(0, 0, 1, 1), 0),
+                update(Unmap, 0, 0, region(3, 0, 1, 1), 0),
+                update(Map, 0, 0, region(0, 0, 1, 1), 3),
+                update(Map, 0, 0, region(3, 0, 1, 1), 0),
+            ]
+        );
+
+        // Remapping to the same heap tile changes nothing.
+        assert_eq!(residency.unmap(0, 0, region(1, 0, 1, 1)), Ok(1));
+        assert_eq!(residency.map(0, 0, region(1, 0, 1, 1)), Ok(1));
+        assert_eq!(residency.take_updates(), []);
+    }
+}
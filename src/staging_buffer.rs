@@ -0,0 +1,17 @@
+//! Buffers that host data passes through on its way to or from the GPU.
+
+use objc2::{rc::Retained, runtime::ProtocolObject};
+
+use crate::{MTLBuffer, MTLDevice, MTLDeviceExt, MTLResourceOptions};
+
+/// Creates a buffer holding at least `length` bytes.
+///
+/// Metal rejects zero-length buffers, so an empty staging area still gets one
+/// byte. Callers size their copies by `length`, not by the buffer's length.
+pub(crate) fn new_staging_buffer(
+    device: &ProtocolObject<dyn MTLDevice>,
+    length: usize,
+    options: MTLResourceOptions,
+) -> Option<Retained<ProtocolObject<dyn MTLBuffer>>> {
+    device.new_buffer(length.max(1), options)
+}
@@ -0,0 +1,58 @@
+use core::slice;
+
+use super::{BFloat16, Float16, MTLTensorDataType};
+use crate::MTLDataType;
+
+/// A Rust type with the same in-memory representation as a tensor element of
+/// [`Self::DATA_TYPE`] and a shader value of [`Self::MTL_DATA_TYPE`].
+///
+/// # Safety
+///
+/// `Self` must have the size, alignment and bit layout of `DATA_TYPE`, and
+/// every bit pattern must be a valid `Self`.
+pub unsafe trait TensorElement: Copy + 'static {
+    const DATA_TYPE: MTLTensorDataType;
+    const MTL_DATA_TYPE: MTLDataType;
+
+    /// The raw bytes of `elements`, for copying into a buffer.
+    fn slice_as_bytes(elements: &[Self]) -> &[u8] {
+        unsafe { slice::from_raw_parts(elements.as_ptr().cast(), size_of_val(elements)) }
+    }
+}
+
+macro_rules! tensor_element {
+    ($type:ty, $data_type:ident, $mtl_data_type:ident) => {
+        unsafe impl TensorElement for $type {
+            const DATA_TYPE: MTLTensorDataType = MTLTensorDataType::$data_type;
+            const MTL_DATA_TYPE: MTLDataType = MTLDataType::$mtl_data_type;
+        }
+    };
+}
+
+tensor_element!(f32, Float32, Float);
+tensor_element!(Float16, Float16, Half);
+tensor_element!(BFloat16, BFloat16, BFloat);
+tensor_element!(i8, Int8, Char);
+tensor_element!(u8, UInt8, UChar);
+tensor_element!(i16, Int16, Short);
+tensor_element!(u16, UInt16, UShort);
+tensor_element!(i32, Int32, Int);
+tensor_element!(u32, UInt32, UInt);
+#[cfg(feature = "half")]
+tensor_element!(half::f16, Float16, Half);
+#[cfg(feature = "half")]
+tensor_element!(half::bf16, BFloat16, BFloat);
+
+/// The element size in bytes of `data_type`, or `None` for
+/// `MTLTensorDataType::None`.
+pub const fn tensor_data_type_size(data_type: MTLTensorDataType) -> Option<usize> {
+    match data_type {
+        MTLTensorDataType::None => None,
+        MTLTensorDataType::Int8 | MTLTensorDataType::UInt8 => Some(1),
+        MTLTensorDataType::Float16
+        | MTLTensorDataType::BFloat16
+        | MTLTensorDataType::Int16
+        | MTLTensorDataType::UInt16 => Some(2),
+        MTLTensorDataType::Float32 | MTLTensorDataType::Int32 | MTLTensorDataType::UInt32 => Some(4),
+    }
+}
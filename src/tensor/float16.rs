@@ -0,0 +1,470 @@
+use core::{cmp::Ordering, fmt};
+
+/// IEEE 754 binary16 value, the layout of Metal's `half` and
+/// `MTLTensorDataType::Float16`.
+///
+/// Conversions from `f32` round to nearest, ties to even. Comparisons follow
+/// `f32` semantics, so `NaN != NaN` and `-0.0 == 0.0`.
+#[repr(transparent)]
+#[derive(Copy, Clone, Default)]
+pub struct Float16(u16);
+
+impl Float16 {
+    pub const ZERO: Self = Self(0x0000);
+    pub const ONE: Self = Self(0x3c00);
+    pub const INFINITY: Self = Self(0x7c00);
+    pub const NEG_INFINITY: Self = Self(0xfc00);
+    pub const NAN: Self = Self(0x7e00);
+    /// Largest finite value, 65504.
+    pub const MAX: Self = Self(0x7bff);
+    /// Smallest positive normal value, 2⁻¹⁴.
+    pub const MIN_POSITIVE: Self = Self(0x0400);
+
+    pub const fn from_bits(bits: u16) -> Self {
+        Self(bits)
+    }
+
+    pub const fn to_bits(self) -> u16 {
+        self.0
+    }
+
+    pub const fn from_f32(value: f32) -> Self {
+        let bits = value.to_bits();
+        let sign = ((bits >> 16) & 0x8000) as u16;
+        let exponent = ((bits >> 23) & 0xff) as i32;
+        let mantissa = bits & 0x7f_ffff;
+
+        if exponent == 0xff {
+            // Keep NaNs quiet and preserve the top payload bits.
+            let nan = if mantissa != 0 {
+                0x0200 | (mantissa >> 13) as u16
+            } else {
+                0
+            };
+            return Self(sign | 0x7c00 | nan);
+        }
+        let exponent = exponent - 127 + 15;
+        if exponent >= 0x1f {
+            return Self(sign | 0x7c00);
+        }
+        if exponent <= 0 {
+            // Subnormal, or below half the smallest subnormal.
+            if exponent < -10 {
+                return Self(sign);
+            }
+            let mantissa = mantissa | 0x80_0000;
+            let shift = (14 - exponent) as u32;
+            return Self(sign | round_shifted(mantissa, shift) as u16);
+        }
+        let rounded = round_shifted((exponent as u32) << 23 | mantissa, 13);
+        Self(sign | rounded as u16)
+    }
+
+    pub const fn to_f32(self) -> f32 {
+        let bits = self.0 as u32;
+        let sign = (bits & 0x8000) << 16;
+        let exponent = (bits >> 10) & 0x1f;
+        let mantissa = bits & 0x3ff;
+        let bits = match exponent {
+            0 if mantissa == 0 => sign,
+            0 => {
+                let shift = mantissa.leading_zeros() - 21;
+                sign | (113 - shift) << 23 | ((mantissa << shift) & 0x3ff) << 13
+            },
+            0x1f if mantissa == 0 => sign | 0x7f80_0000,
+            // Quiet NaNs, as `from_f32` does.
+            0x1f => sign | 0x7fc0_0000 | mantissa << 13,
+            _ => sign | (exponent + 112) << 23 | mantissa << 13,
+        };
+        f32::from_bits(bits)
+    }
+
+    pub const fn is_nan(self) -> bool {
+        self.0 & 0x7fff > 0x7c00
+    }
+
+    pub const fn is_finite(self) -> bool {
+        self.0 & 0x7c00 != 0x7c00
+    }
+
+    /// Converts `source` into `destination`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slices have different lengths.
+    pub fn convert_from_f32_slice(
+        destination: &mut [Self],
+        source: &[f32],
+    ) {
+        assert_eq!(destination.len(), source.len(), "slice lengths differ");
+        for (destination, &source) in destination.iter_mut().zip(source) {
+            *destination = Self::from_f32(source);
+        }
+    }
+
+    /// Converts `source` into `destination`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slices have different lengths.
+    pub fn convert_to_f32_slice(
+        destination: &mut [f32],
+        source: &[Self],
+    ) {
+        assert_eq!(destination.len(), source.len(), "slice lengths differ");
+        for (destination, &source) in destination.iter_mut().zip(source) {
+            *destination = source.to_f32();
+        }
+    }
+
+    pub fn vec_from_f32_slice(source: &[f32]) -> Vec<Self> {
+        source.iter().copied().map(Self::from_f32).collect()
+    }
+
+    pub fn vec_to_f32(source: &[Self]) -> Vec<f32> {
+        source.iter().copied().map(Self::to_f32).collect()
+    }
+}
+
+/// bfloat16 value: the upper half of an `f32`, the layout of Metal's `bfloat`
+/// and `MTLTensorDataType::BFloat16`.
+///
+/// Conversions from `f32` round to nearest, ties to even. Comparisons follow
+/// `f32` semantics, so `NaN != NaN` and `-0.0 == 0.0`.
+#[repr(transparent)]
+#[derive(Copy, Clone, Default)]
+pub struct BFloat16(u16);
+
+impl BFloat16 {
+    pub const ZERO: Self = Self(0x0000);
+    pub const ONE: Self = Self(0x3f80);
+    pub const INFINITY: Self = Self(0x7f80);
+    pub const NEG_INFINITY: Self = Self(0xff80);
+    pub const NAN: Self = Self(0x7fc0);
+    /// Largest finite value, about 3.39e38.
+    pub const MAX: Self = Self(0x7f7f);
+    /// Smallest positive normal value, 2⁻¹²⁶.
+    pub const MIN_POSITIVE: Self = Self(0x0080);
+
+    pub const fn from_bits(bits: u16) -> Self {
+        Self(bits)
+    }
+
+    pub const fn to_bits(self) -> u16 {
+        self.0
+    }
+
+    pub const fn from_f32(value: f32) -> Self {
+        let bits = value.to_bits();
+        if value.is_nan() {
+            return Self((bits >> 16) as u16 | 0x0040);
+        }
+        Self(round_shifted(bits, 16) as u16)
+    }
+
+    pub const fn to_f32(self) -> f32 {
+        // Quiet NaNs, as `from_f32` does.
+        let bits = if self.is_nan() {
+            self.0 | 0x0040
+        } else {
+            self.0
+        };
+        f32::from_bits((bits as u32) << 16)
+    }
+
+    pub const fn is_nan(self) -> bool {
+        self.0 & 0x7fff > 0x7f80
+    }
+
+    pub const fn is_finite(self) -> bool {
+        self.0 & 0x7f80 != 0x7f80
+    }
+
+    /// Converts `source` into `destination`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slices have different lengths.
+    pub fn convert_from_f32_slice(
+        destination: &mut [Self],
+        source: &[f32],
+    ) {
+        assert_eq!(destination.len(), source.len(), "slice lengths differ");
+        for (destination, &source) in destination.iter_mut().zip(source) {
+            *destination = Self::from_f32(source);
+        }
+    }
+
+    /// Converts `source` into `destination`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slices have different lengths.
+    pub fn convert_to_f32_slice(
+        destination: &mut [f32],
+        source: &[Self],
+    ) {
+        assert_eq!(destination.len(), source.len(), "slice lengths differ");
+        for (destination, &source) in destination.iter_mut().zip(source) {
+            *destination = source.to_f32();
+        }
+    }
+
+    pub fn vec_from_f32_slice(source: &[f32]) -> Vec<Self> {
+        source.iter().copied().map(Self::from_f32).collect()
+    }
+
+    pub fn vec_to_f32(source: &[Self]) -> Vec<f32> {
+        source.iter().copied().map(Self::to_f32).collect()
+    }
+}
+
+/// Shifts `value` right by `shift` bits, rounding to nearest, ties to even.
+/// A carry out of the mantissa correctly bumps the exponent.
+const fn round_shifted(
+    value: u32,
+    shift: u32,
+) -> u32 {
+    let halfway = 1 << (shift - 1);
+    let remainder = value & ((halfway << 1) - 1);
+    let shifted = value >> shift;
+    if remainder > halfway || (remainder == halfway && shifted & 1 == 1) {
+        shifted + 1
+    } else {
+        shifted
+    }
+}
+
+macro_rules! float_impls {
+    ($type:ident) => {
+        const _: () = assert!(size_of::<$type>() == 2 && align_of::<$type>() == 2);
+
+        impl From<$type> for f32 {
+            fn from(value: $type) -> Self {
+                value.to_f32()
+            }
+        }
+
+        impl From<$type> for f64 {
+            fn from(value: $type) -> Self {
+                value.to_f32().into()
+            }
+        }
+
+        impl PartialEq for $type {
+            fn eq(
+                &self,
+                other: &Self,
+            ) -> bool {
+                self.to_f32() == other.to_f32()
+            }
+        }
+
+        impl PartialOrd for $type {
+            fn partial_cmp(
+                &self,
+                other: &Self,
+            ) -> Option<Ordering> {
+                self.to_f32().partial_cmp(&other.to_f32())
+            }
+        }
+
+        impl fmt::Debug for $type {
+            fn fmt(
+                &self,
+                f: &mut fmt::Formatter<'_>,
+            ) -> fmt::Result {
+                fmt::Debug::fmt(&self.to_f32(), f)
+            }
+        }
+
+        impl fmt::Display for $type {
+            fn fmt(
+                &self,
+                f: &mut fmt::Formatter<'_>,
+            ) -> fmt::Result {
+                fmt::Display::fmt(&self.to_f32(), f)
+            }
+        }
+    };
+}
+
+float_impls!(Float16);
+float_impls!(BFloat16);
+
+#[cfg(feature = "half")]
+mod half_interop {
+    use super::{BFloat16, Float16};
+
+    impl From<half::f16> for Float16 {
+        fn from(value: half::f16) -> Self {
+            Self::from_bits(value.to_bits())
+        }
+    }
+
+    impl From<Float16> for half::f16 {
+        fn from(value: Float16) -> Self {
+            Self::from_bits(value.to_bits())
+        }
+    }
+
+    impl From<half::bf16> for BFloat16 {
+        fn from(value: half::bf16) -> Self {
+            Self::from_bits(value.to_bits())
+        }
+    }
+
+    impl From<BFloat16> for half::bf16 {
+        fn from(value: BFloat16) -> Self {
+            Self::from_bits(value.to_bits())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn half_bits(value: f32) -> u16 {
+        Float16::from_f32(value).to_bits()
+    }
+
+    fn bfloat_bits(value: f32) -> u16 {
+        BFloat16::from_f32(value).to_bits()
+    }
+
+    #[test]
+    fn float16_rounding() {
+        assert_eq!(half_bits(1.0), 0x3c00);
+        assert_eq!(half_bits(65504.0), Float16::MAX.to_bits());
+        // Ties between 1 and the next value, 1 + 2⁻¹⁰, go to the even one.
+        assert_eq!(half_bits(1.0 + 2f32.powi(-11)), 0x3c00);
+        assert_eq!(half_bits(1.0 + 3.0 * 2f32.powi(-11)), 0x3c02);
+        assert_eq!(half_bits(-(1.0 + 3.0 * 2f32.powi(-11))), 0xbc02);
+        // Just above the tie rounds up.
+        assert_eq!(half_bits(f32::from_bits((1.0 + 2f32.powi(-11)).to_bits() + 1)), 0x3c01);
+        // Rounding up out of the mantissa bumps the exponent.
+        assert_eq!(half_bits(2.0 - 2f32.powi(-12)), 0x4000);
+    }
+
+    #[test]
+    fn float16_subnormals() {
+        let smallest = 2f32.powi(-24);
+        assert_eq!(half_bits(smallest), 0x0001);
+        assert_eq!(half_bits(-smallest), 0x8001);
+        assert_eq!(half_bits(1023.0 * smallest), 0x03ff);
+        assert_eq!(Float16::from_bits(0x0001).to_f32(), smallest);
+        assert_eq!(Float16::from_bits(0x03ff).to_f32(), 1023.0 * smallest);
+        assert_eq!(Float16::from_bits(0x8200).to_f32(), -2f32.powi(-15));
+        // Half the smallest subnormal is a tie with zero; anything above it
+        // rounds up.
+        assert_eq!(half_bits(smallest / 2.0), 0x0000);
+        assert_eq!(half_bits(smallest * 0.75), 0x0001);
+        assert_eq!(half_bits(smallest * 1.5), 0x0002);
+        assert_eq!(half_bits(smallest * 2.5), 0x0002);
+        assert_eq!(half_bits(f32::MIN_POSITIVE), 0x0000);
+        // The largest subnormal rounds up into the smallest normal.
+        assert_eq!(half_bits(1023.75 * smallest), Float16::MIN_POSITIVE.to_bits());
+        for bits in 1..0x0400 {
+            assert_eq!(half_bits(Float16::from_bits(bits).to_f32()), bits);
+        }
+    }
+
+    #[test]
+    fn float16_special_values() {
+        assert_eq!(half_bits(0.0), 0x0000);
+        assert_eq!(half_bits(-0.0), 0x8000);
+        assert!(Float16::from_bits(0x8000).to_f32().is_sign_negative());
+        assert_eq!(Float16::from_bits(0x8000), Float16::ZERO);
+        assert_eq!(half_bits(f32::INFINITY), Float16::INFINITY.to_bits());
+        assert_eq!(half_bits(f32::NEG_INFINITY), Float16::NEG_INFINITY.to_bits());
+        assert_eq!(Float16::INFINITY.to_f32(), f32::INFINITY);
+        assert_eq!(Float16::NEG_INFINITY.to_f32(), f32::NEG_INFINITY);
+        // Overflow, including rounding past the largest finite value.
+        assert_eq!(half_bits(65520.0), 0x7c00);
+        assert_eq!(half_bits(65519.0), 0x7bff);
+        assert_eq!(half_bits(-1e10), 0xfc00);
+        assert_eq!(half_bits(f32::MAX), 0x7c00);
+        assert!(!Float16::INFINITY.is_finite() && Float16::MAX.is_finite());
+    }
+
+    #[test]
+    fn float16_nan() {
+        assert!(Float16::from_f32(f32::NAN).is_nan());
+        assert!(Float16::NAN.to_f32().is_nan());
+        assert_ne!(Float16::NAN, Float16::NAN);
+        // Signaling NaNs become quiet, keeping the sign and top payload bits.
+        assert_eq!(half_bits(f32::from_bits(0x7f80_2000)), 0x7e01);
+        assert_eq!(half_bits(f32::from_bits(0xff80_0001)), 0xfe00);
+        assert_eq!(half_bits(f32::from_bits(0x7fd5_6000)), 0x7eab);
+        assert_eq!(Float16::from_bits(0x7eab).to_f32().to_bits(), 0x7fd5_6000);
+        assert_eq!(Float16::from_bits(0xfc01).to_f32().to_bits(), 0xffc0_2000);
+    }
+
+    #[test]
+    fn bfloat16_conversions() {
+        assert_eq!(bfloat_bits(1.0), 0x3f80);
+        // Ties between 1 and 1 + 2⁻⁷ go to the even one.
+        assert_eq!(bfloat_bits(1.0 + 2f32.powi(-8)), 0x3f80);
+        assert_eq!(bfloat_bits(1.0 + 3.0 * 2f32.powi(-8)), 0x3f82);
+        assert_eq!(bfloat_bits(f32::from_bits(0x3f80_8001)), 0x3f81);
+        // Subnormals use the same shift.
+        assert_eq!(bfloat_bits(f32::from_bits(0x0000_8000)), 0x0000);
+        assert_eq!(bfloat_bits(f32::from_bits(0x0001_8000)), 0x0002);
+        assert_eq!(bfloat_bits(f32::from_bits(0x8001_0000)), 0x8001);
+        assert_eq!(BFloat16::from_bits(0x0001).to_f32(), f32::from_bits(0x0001_0000));
+        assert_eq!(bfloat_bits(f32::from_bits(0x007f_ffff)), BFloat16::MIN_POSITIVE.to_bits());
+        // Zeros, infinities and overflow.
+        assert_eq!(bfloat_bits(0.0), 0x0000);
+        assert_eq!(bfloat_bits(-0.0), 0x8000);
+        assert_eq!(bfloat_bits(f32::INFINITY), BFloat16::INFINITY.to_bits());
+        assert_eq!(bfloat_bits(f32::NEG_INFINITY), BFloat16::NEG_INFINITY.to_bits());
+        assert_eq!(bfloat_bits(f32::MAX), 0x7f80);
+        assert_eq!(bfloat_bits(f32::from_bits(0x7f7f_7fff)), BFloat16::MAX.to_bits());
+        // NaNs stay NaN, even when only low payload bits are set.
+        assert_eq!(bfloat_bits(f32::from_bits(0x7f80_0001)), 0x7fc0);
+        assert_eq!(bfloat_bits(f32::from_bits(0xffa0_0000)), 0xffe0);
+        assert!(BFloat16::NAN.to_f32().is_nan());
+        assert_eq!(BFloat16::from_bits(0x7f81).to_f32().to_bits(), 0x7fc1_0000);
+        assert_ne!(BFloat16::NAN, BFloat16::NAN);
+    }
+
+    #[test]
+    fn slices() {
+        let values = [0.5, -2.0, 65504.0];
+        let halves = Float16::vec_from_f32_slice(&values);
+        assert_eq!(Float16::vec_to_f32(&halves), values);
+        let mut bfloats = [BFloat16::ZERO; 3];
+        BFloat16::convert_from_f32_slice(&mut bfloats, &values);
+        let mut round_trip = [0.0; 3];
+        BFloat16::convert_to_f32_slice(&mut round_trip, &bfloats);
+        assert_eq!(round_trip, [0.5, -2.0, 65536.0]);
+    }
+
+    #[cfg(feature = "half")]
+    #[test]
+    fn matches_half() {
+        for bits in 0..=u16::MAX {
+            let expected = half::f16::from_bits(bits).to_f32().to_bits();
+            assert_eq!(Float16::from_bits(bits).to_f32().to_bits(), expected, "{bits:#06x}");
+            let expected = half::bf16::from_bits(bits).to_f32().to_bits();
+            assert_eq!(BFloat16::from_bits(bits).to_f32().to_bits(), expected, "{bits:#06x}");
+        }
+        // Every exponent with a spread of mantissas, including ties.
+        for bits in (0..=u32::MAX).step_by(0x1001).chain((0..=u32::MAX).step_by(0x0001_0000).map(|bits| bits | 0x1000))
+        {
+            let value = f32::from_bits(bits);
+            assert_eq!(
+                half::f16::from(Float16::from_f32(value)).to_bits(),
+                half::f16::from_f32(value).to_bits(),
+                "{bits:#010x}"
+            );
+            assert_eq!(
+                half::bf16::from(BFloat16::from_f32(value)).to_bits(),
+                half::bf16::from_f32(value).to_bits(),
+                "{bits:#010x}"
+            );
+            assert_eq!(Float16::from(half::f16::from_f32(value)).to_bits(), half::f16::from_f32(value).to_bits());
+        }
+    }
+}
@@ -0,0 +1,659 @@
+use core::fmt;
+
+use objc2::rc::Retained;
+
+use super::{MTLTensorDataType, MTLTensorExtents, TENSOR_MAX_RANK};
+
+/// Errors raised by [`TensorShape`], [`TensorLayout`] and the typed tensor
+/// slice accessors.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TensorLayoutError {
+    /// More than [`TENSOR_MAX_RANK`] dimensions.
+    RankTooLarge(usize),
+    /// Two shapes, indices or stride lists have different ranks.
+    RankMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    /// Dimension `dimension` of the two shapes is neither equal nor 1.
+    IncompatibleBroadcast {
+        dimension: usize,
+        lhs: usize,
+        rhs: usize,
+    },
+    /// A slice or index extends past the end of dimension `dimension`.
+    OutOfBounds {
+        dimension: usize,
+        end: usize,
+        extent: usize,
+    },
+    /// The axes don't form a permutation of `0..rank`.
+    InvalidPermutation,
+    /// The strides don't satisfy Metal's ordering requirements for tensor copies.
+    UnsupportedStrides,
+    /// The element count or byte size overflows `usize`.
+    Overflow,
+    /// The tensor's data type doesn't match the Rust element type.
+    DataTypeMismatch {
+        expected: MTLTensorDataType,
+        actual: MTLTensorDataType,
+    },
+    /// The host slice is shorter than the layout spans.
+    SliceTooShort {
+        required: usize,
+        actual: usize,
+    },
+    /// Metal couldn't create an `MTLTensorExtents`.
+    ExtentsCreation,
+}
+
+impl fmt::Display for TensorLayoutError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::RankTooLarge(rank) => write!(f, "rank {rank} exceeds the maximum of {TENSOR_MAX_RANK}"),
+            Self::RankMismatch {
+                expected,
+                actual,
+            } => write!(f, "expected rank {expected}, got {actual}"),
+            Self::IncompatibleBroadcast {
+                dimension,
+                lhs,
+                rhs,
+            } => {
+                write!(f, "cannot broadcast extents {lhs} and {rhs} in dimension {dimension}")
+            },
+            Self::OutOfBounds {
+                dimension,
+                end,
+                extent,
+            } => {
+                write!(f, "range ending at {end} is out of bounds for dimension {dimension} of extent {extent}")
+            },
+            Self::InvalidPermutation => f.write_str("axes are not a permutation of the tensor's dimensions"),
+            Self::UnsupportedStrides => f.write_str("strides are not innermost-first and non-overlapping"),
+            Self::Overflow => f.write_str("tensor size overflows usize"),
+            Self::DataTypeMismatch {
+                expected,
+                actual,
+            } => {
+                write!(f, "tensor data type is {actual:?} but the element type is {expected:?}")
+            },
+            Self::SliceTooShort {
+                required,
+                actual,
+            } => {
+                write!(f, "layout spans {required} elements but the slice holds {actual}")
+            },
+            Self::ExtentsCreation => f.write_str("failed to create MTLTensorExtents"),
+        }
+    }
+}
+
+impl std::error::Error for TensorLayoutError {}
+
+/// Tensor dimensions, innermost first as in `MTLTensorExtents`.
+///
+/// A rank-0 shape is a scalar with one element.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TensorShape {
+    rank: usize,
+    dimensions: [usize; TENSOR_MAX_RANK],
+}
+
+impl TensorShape {
+    pub const SCALAR: Self = Self {
+        rank: 0,
+        dimensions: [0; TENSOR_MAX_RANK],
+    };
+
+    /// Shape with `dimensions`, innermost first.
+    pub fn new(dimensions: &[usize]) -> Result<Self, TensorLayoutError> {
+        if dimensions.len() > TENSOR_MAX_RANK {
+            return Err(TensorLayoutError::RankTooLarge(dimensions.len()));
+        }
+        let mut shape = Self::SCALAR;
+        shape.rank = dimensions.len();
+        shape.dimensions[..dimensions.len()].copy_from_slice(dimensions);
+        Ok(shape)
+    }
+
+    /// Reads the shape of an `MTLTensorExtents`.
+    pub fn from_extents(extents: &MTLTensorExtents) -> Result<Self, TensorLayoutError> {
+        let rank = extents.rank();
+        if rank > TENSOR_MAX_RANK {
+            return Err(TensorLayoutError::RankTooLarge(rank));
+        }
+        let mut shape = Self::SCALAR;
+        shape.rank = rank;
+        for (index, dimension) in shape.dimensions[..rank].iter_mut().enumerate() {
+            *dimension = extents.extent_at_dimension_index(index).max(0) as usize;
+        }
+        Ok(shape)
+    }
+
+    pub fn to_extents(&self) -> Result<Retained<MTLTensorExtents>, TensorLayoutError> {
+        extents_from(self.dimensions())
+    }
+
+    pub fn rank(&self) -> usize {
+        self.rank
+    }
+
+    pub fn dimensions(&self) -> &[usize] {
+        &self.dimensions[..self.rank]
+    }
+
+    /// Extent of `dimension`, or 1 past the rank, which is how broadcasting
+    /// treats missing outer dimensions.
+    pub fn dimension(
+        &self,
+        dimension: usize,
+    ) -> usize {
+        if dimension < self.rank {
+            self.dimensions[dimension]
+        } else {
+            1
+        }
+    }
+
+    pub fn element_count(&self) -> usize {
+        self.dimensions().iter().product()
+    }
+
+    pub fn checked_element_count(&self) -> Option<usize> {
+        self.dimensions().iter().try_fold(1usize, |count, &dimension| count.checked_mul(dimension))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.element_count() == 0
+    }
+
+    /// The shape both operands broadcast to, aligning innermost dimensions
+    /// and treating missing outer dimensions as 1.
+    pub fn broadcast_with(
+        &self,
+        other: &Self,
+    ) -> Result<Self, TensorLayoutError> {
+        let mut shape = Self::SCALAR;
+        shape.rank = self.rank.max(other.rank);
+        for dimension in 0..shape.rank {
+            let (lhs, rhs) = (self.dimension(dimension), other.dimension(dimension));
+            shape.dimensions[dimension] = match (lhs, rhs) {
+                _ if lhs == rhs => lhs,
+                (1, _) => rhs,
+                (_, 1) => lhs,
+                _ => {
+                    return Err(TensorLayoutError::IncompatibleBroadcast {
+                        dimension,
+                        lhs,
+                        rhs,
+                    });
+                },
+            };
+        }
+        Ok(shape)
+    }
+}
+
+impl fmt::Debug for TensorShape {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        f.debug_tuple("TensorShape").field(&self.dimensions()).finish()
+    }
+}
+
+impl TryFrom<&[usize]> for TensorShape {
+    type Error = TensorLayoutError;
+
+    fn try_from(dimensions: &[usize]) -> Result<Self, Self::Error> {
+        Self::new(dimensions)
+    }
+}
+
+/// A shape plus per-dimension strides and a base offset, all in elements,
+/// describing where each element of a (possibly strided) tensor view lives.
+///
+/// Dimensions are innermost first, so a contiguous layout has stride 1 in
+/// dimension 0. Broadcast dimensions have stride 0.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TensorLayout {
+    shape: TensorShape,
+    strides: [usize; TENSOR_MAX_RANK],
+    offset: usize,
+}
+
+impl TensorLayout {
+    /// Densely packed layout for `shape`.
+    pub fn contiguous(shape: TensorShape) -> Self {
+        let mut strides = [0; TENSOR_MAX_RANK];
+        let mut stride = 1;
+        for (dimension, &extent) in shape.dimensions().iter().enumerate() {
+            strides[dimension] = stride;
+            stride *= extent.max(1);
+        }
+        Self {
+            shape,
+            strides,
+            offset: 0,
+        }
+    }
+
+    /// Layout with explicit strides, one per dimension.
+    pub fn with_strides(
+        shape: TensorShape,
+        strides: &[usize],
+    ) -> Result<Self, TensorLayoutError> {
+        if strides.len() != shape.rank() {
+            return Err(TensorLayoutError::RankMismatch {
+                expected: shape.rank(),
+                actual: strides.len(),
+            });
+        }
+        let mut layout = Self::contiguous(shape);
+        layout.strides[..strides.len()].copy_from_slice(strides);
+        Ok(layout)
+    }
+
+    pub fn with_offset(
+        mut self,
+        offset: usize,
+    ) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn shape(&self) -> &TensorShape {
+        &self.shape
+    }
+
+    pub fn strides(&self) -> &[usize] {
+        &self.strides[..self.shape.rank()]
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn rank(&self) -> usize {
+        self.shape.rank()
+    }
+
+    /// Whether elements are densely packed innermost first.
+    pub fn is_contiguous(&self) -> bool {
+        *self == Self::contiguous(self.shape).with_offset(self.offset)
+    }
+
+    /// Whether Metal's tensor copy methods accept these strides: stride 1
+    /// innermost and each stride covering the dimensions inside it.
+    pub fn is_metal_compatible(&self) -> bool {
+        let strides = self.strides();
+        let dimensions = self.shape.dimensions();
+        strides.first().is_none_or(|&stride| stride == 1)
+            && (1..strides.len()).all(|i| strides[i] >= strides[i - 1].saturating_mul(dimensions[i - 1]))
+    }
+
+    /// Element offset of `index`, or `None` if it is out of bounds.
+    pub fn offset_of(
+        &self,
+        index: &[usize],
+    ) -> Option<usize> {
+        if index.len() != self.rank() {
+            return None;
+        }
+        index
+            .iter()
+            .zip(self.shape.dimensions())
+            .zip(self.strides())
+            .try_fold(self.offset, |offset, ((&i, &extent), &stride)| (i < extent).then(|| offset + i * stride))
+    }
+
+    /// Number of elements a buffer needs to hold every element of the layout,
+    /// counting from offset 0.
+    pub fn required_len(&self) -> Result<usize, TensorLayoutError> {
+        if self.shape.is_empty() {
+            return Ok(0);
+        }
+        self.shape
+            .dimensions()
+            .iter()
+            .zip(self.strides())
+            .try_fold(self.offset, |last, (&extent, &stride)| last.checked_add((extent - 1).checked_mul(stride)?))
+            .and_then(|last| last.checked_add(1))
+            .ok_or(TensorLayoutError::Overflow)
+    }
+
+    /// View of the `dimensions`-sized region starting at `origin`.
+    pub fn slice(
+        &self,
+        origin: &[usize],
+        dimensions: &[usize],
+    ) -> Result<Self, TensorLayoutError> {
+        check_region(self.shape.dimensions(), origin, dimensions)?;
+        let offset = origin.iter().zip(self.strides()).map(|(&o, &stride)| o * stride).sum::<usize>();
+        Ok(Self {
+            shape: TensorShape::new(dimensions)?,
+            strides: self.strides,
+            offset: self.offset + offset,
+        })
+    }
+
+    /// Swaps dimensions `a` and `b`.
+    pub fn transpose(
+        &self,
+        a: usize,
+        b: usize,
+    ) -> Result<Self, TensorLayoutError> {
+        let mut axes: [usize; TENSOR_MAX_RANK] = core::array::from_fn(|i| i);
+        if a >= self.rank() || b >= self.rank() {
+            return Err(TensorLayoutError::InvalidPermutation);
+        }
+        axes.swap(a, b);
+        self.permute(&axes[..self.rank()])
+    }
+
+    /// Reorders dimensions so dimension `i` of the result is dimension
+    /// `axes[i]` of `self`.
+    pub fn permute(
+        &self,
+        axes: &[usize],
+    ) -> Result<Self, TensorLayoutError> {
+        if axes.len() != self.rank() {
+            return Err(TensorLayoutError::RankMismatch {
+                expected: self.rank(),
+                actual: axes.len(),
+            });
+        }
+        let mut seen = [false; TENSOR_MAX_RANK];
+        let mut layout = *self;
+        for (dimension, &axis) in axes.iter().enumerate() {
+            if axis >= self.rank() || seen[axis] {
+                return Err(TensorLayoutError::InvalidPermutation);
+            }
+            seen[axis] = true;
+            layout.shape.dimensions[dimension] = self.shape.dimensions[axis];
+            layout.strides[dimension] = self.strides[axis];
+        }
+        Ok(layout)
+    }
+
+    /// View of `self` broadcast to `shape`: extent-1 and missing outer
+    /// dimensions repeat with stride 0. Unlike
+    /// [`TensorShape::broadcast_with`], only `self` is stretched.
+    pub fn broadcast_to(
+        &self,
+        shape: TensorShape,
+    ) -> Result<Self, TensorLayoutError> {
+        if self.rank() > shape.rank() {
+            return Err(TensorLayoutError::RankMismatch {
+                expected: shape.rank(),
+                actual: self.rank(),
+            });
+        }
+        for dimension in 0..self.rank() {
+            let (lhs, rhs) = (self.shape.dimensions[dimension], shape.dimensions[dimension]);
+            if lhs != rhs && lhs != 1 {
+                return Err(TensorLayoutError::IncompatibleBroadcast {
+                    dimension,
+                    lhs,
+                    rhs,
+                });
+            }
+        }
+        let mut strides = [0; TENSOR_MAX_RANK];
+        for (dimension, stride) in strides[..shape.rank()].iter_mut().enumerate() {
+            if dimension < self.rank() && self.shape.dimensions[dimension] == shape.dimensions[dimension] {
+                *stride = self.strides[dimension];
+            }
+        }
+        Ok(Self {
+            shape,
+            strides,
+            offset: self.offset,
+        })
+    }
+
+    /// The strides as `MTLTensorExtents`, for the tensor copy methods.
+    pub fn strides_to_extents(&self) -> Result<Retained<MTLTensorExtents>, TensorLayoutError> {
+        extents_from(self.strides())
+    }
+
+    /// Visits the element offsets of the layout, innermost dimension fastest.
+    pub fn for_each_offset(
+        &self,
+        mut f: impl FnMut(usize),
+    ) {
+        if self.shape.is_empty() {
+            return;
+        }
+        let rank = self.rank();
+        let mut index = [0usize; TENSOR_MAX_RANK];
+        let mut offset = self.offset;
+        loop {
+            f(offset);
+            let mut dimension = 0;
+            loop {
+                if dimension == rank {
+                    return;
+                }
+                index[dimension] += 1;
+                offset += self.strides[dimension];
+                if index[dimension] < self.shape.dimensions[dimension] {
+                    break;
+                }
+                offset -= index[dimension] * self.strides[dimension];
+                index[dimension] = 0;
+                dimension += 1;
+            }
+        }
+    }
+}
+
+impl fmt::Debug for TensorLayout {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        f.debug_struct("TensorLayout")
+            .field("dimensions", &self.shape.dimensions())
+            .field("strides", &self.strides())
+            .field("offset", &self.offset)
+            .finish()
+    }
+}
+
+pub(crate) fn check_region(
+    extents: &[usize],
+    origin: &[usize],
+    dimensions: &[usize],
+) -> Result<(), TensorLayoutError> {
+    for actual in [origin.len(), dimensions.len()] {
+        if actual != extents.len() {
+            return Err(TensorLayoutError::RankMismatch {
+                expected: extents.len(),
+                actual,
+            });
+        }
+    }
+    for (dimension, ((&o, &size), &extent)) in origin.iter().zip(dimensions).zip(extents).enumerate() {
+        let end = o.checked_add(size).ok_or(TensorLayoutError::Overflow)?;
+        if end > extent {
+            return Err(TensorLayoutError::OutOfBounds {
+                dimension,
+                end,
+                extent,
+            });
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn extents_from(values: &[usize]) -> Result<Retained<MTLTensorExtents>, TensorLayoutError> {
+    if values.len() > TENSOR_MAX_RANK {
+        return Err(TensorLayoutError::RankTooLarge(values.len()));
+    }
+    let mut signed = [0isize; TENSOR_MAX_RANK];
+    for (signed, &value) in signed.iter_mut().zip(values) {
+        *signed = isize::try_from(value).map_err(|_| TensorLayoutError::Overflow)?;
+    }
+    let values = (!values.is_empty()).then(|| &signed[..values.len()]);
+    MTLTensorExtents::new_with_rank_values(values.map_or(0, <[isize]>::len), values)
+        .ok_or(TensorLayoutError::ExtentsCreation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape(dimensions: &[usize]) -> TensorShape {
+        TensorShape::new(dimensions).unwrap()
+    }
+
+    fn offsets(layout: &TensorLayout) -> Vec<usize> {
+        let mut offsets = Vec::new();
+        layout.for_each_offset(|offset| offsets.push(offset));
+        offsets
+    }
+
+    #[test]
+    fn shapes() {
+        assert_eq!(TensorShape::new(&[1; TENSOR_MAX_RANK + 1]), Err(TensorLayoutError::RankTooLarge(17)));
+        assert_eq!(TensorShape::SCALAR.element_count(), 1);
+        let shape = shape(&[4, 3, 2]);
+        assert_eq!((shape.rank(), shape.element_count(), shape.dimension(5)), (3, 24, 1));
+        assert!(self::shape(&[4, 0]).is_empty());
+        assert_eq!(self::shape(&[usize::MAX, 2]).checked_element_count(), None);
+    }
+
+    #[test]
+    fn broadcast_shapes() {
+        assert_eq!(shape(&[4, 1]).broadcast_with(&shape(&[1, 3, 2])), Ok(shape(&[4, 3, 2])));
+        assert_eq!(TensorShape::SCALAR.broadcast_with(&shape(&[5])), Ok(shape(&[5])));
+        assert_eq!(
+            shape(&[4, 3]).broadcast_with(&shape(&[4, 2])),
+            Err(TensorLayoutError::IncompatibleBroadcast {
+                dimension: 1,
+                lhs: 3,
+                rhs: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn contiguous_layout() {
+        let layout = TensorLayout::contiguous(shape(&[4, 3, 2]));
+        assert_eq!(layout.strides(), [1, 4, 12]);
+        assert!(layout.is_contiguous() && layout.is_metal_compatible());
+        assert_eq!(layout.offset_of(&[3, 2, 1]), Some(23));
+        assert_eq!(layout.offset_of(&[4, 0, 0]), None);
+        assert_eq!(layout.offset_of(&[0, 0]), None);
+        assert_eq!(layout.required_len(), Ok(24));
+        assert_eq!(offsets(&layout), (0..24).collect::<Vec<_>>());
+        assert_eq!(TensorLayout::contiguous(shape(&[4, 0])).required_len(), Ok(0));
+        assert!(offsets(&TensorLayout::contiguous(shape(&[4, 0]))).is_empty());
+    }
+
+    #[test]
+    fn strided_layout() {
+        assert_eq!(
+            TensorLayout::with_strides(shape(&[4, 3]), &[1]),
+            Err(TensorLayoutError::RankMismatch {
+                expected: 2,
+                actual: 1,
+            })
+        );
+        let layout = TensorLayout::with_strides(shape(&[2, 3]), &[1, 8]).unwrap().with_offset(5);
+        assert!(!layout.is_contiguous() && layout.is_metal_compatible());
+        assert_eq!(layout.required_len(), Ok(23));
+        assert_eq!(offsets(&layout), [5, 6, 13, 14, 21, 22]);
+        assert!(!TensorLayout::with_strides(shape(&[2, 3]), &[2, 8]).unwrap().is_metal_compatible());
+        assert!(!TensorLayout::with_strides(shape(&[2, 3]), &[1, 1]).unwrap().is_metal_compatible());
+        let huge = TensorLayout::with_strides(shape(&[2, 2]), &[1, usize::MAX]).unwrap();
+        assert_eq!(huge.required_len(), Err(TensorLayoutError::Overflow));
+    }
+
+    #[test]
+    fn slices() {
+        let layout = TensorLayout::contiguous(shape(&[4, 3, 2]));
+        let slice = layout.slice(&[1, 1, 1], &[2, 2, 1]).unwrap();
+        assert_eq!((slice.offset(), slice.strides()), (17, [1, 4, 12].as_slice()));
+        assert_eq!(offsets(&slice), [17, 18, 21, 22]);
+        assert_eq!(
+            layout.slice(&[3, 0, 0], &[2, 1, 1]),
+            Err(TensorLayoutError::OutOfBounds {
+                dimension: 0,
+                end: 5,
+                extent: 4,
+            })
+        );
+        assert_eq!(
+            layout.slice(&[0, 0], &[1, 1, 1]),
+            Err(TensorLayoutError::RankMismatch {
+                expected: 3,
+                actual: 2,
+            })
+        );
+        assert_eq!(layout.slice(&[usize::MAX, 0, 0], &[1, 1, 1]), Err(TensorLayoutError::Overflow));
+    }
+
+    #[test]
+    fn permutations() {
+        let layout = TensorLayout::contiguous(shape(&[4, 3, 2]));
+        let transposed = layout.transpose(0, 1).unwrap();
+        assert_eq!((transposed.shape(), transposed.strides()), (&shape(&[3, 4, 2]), [4, 1, 12].as_slice()));
+        assert!(!transposed.is_metal_compatible());
+        assert_eq!(offsets(&transposed)[..5], [0, 4, 8, 1, 5]);
+
+        let permuted = layout.permute(&[2, 0, 1]).unwrap();
+        assert_eq!((permuted.shape(), permuted.strides()), (&shape(&[2, 4, 3]), [12, 1, 4].as_slice()));
+        assert_eq!(layout.permute(&[0, 0, 1]), Err(TensorLayoutError::InvalidPermutation));
+        assert_eq!(layout.permute(&[0, 1, 3]), Err(TensorLayoutError::InvalidPermutation));
+        assert_eq!(layout.transpose(0, 3), Err(TensorLayoutError::InvalidPermutation));
+        assert_eq!(
+            layout.permute(&[0, 1]),
+            Err(TensorLayoutError::RankMismatch {
+                expected: 3,
+                actual: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn broadcast_layouts() {
+        let layout = TensorLayout::contiguous(shape(&[4, 1])).with_offset(2);
+        let broadcast = layout.broadcast_to(shape(&[4, 3, 2])).unwrap();
+        assert_eq!((broadcast.strides(), broadcast.offset()), ([1, 0, 0].as_slice(), 2));
+        assert_eq!(offsets(&broadcast)[..8], [2, 3, 4, 5, 2, 3, 4, 5]);
+        assert_eq!(broadcast.required_len(), Ok(6));
+
+        assert_eq!(
+            layout.broadcast_to(shape(&[3, 5])),
+            Err(TensorLayoutError::IncompatibleBroadcast {
+                dimension: 0,
+                lhs: 4,
+                rhs: 3,
+            })
+        );
+        // Broadcasting never shrinks `self`, even where the shapes themselves
+        // would broadcast.
+        assert_eq!(
+            TensorLayout::contiguous(shape(&[4, 3])).broadcast_to(shape(&[4, 1])),
+            Err(TensorLayoutError::IncompatibleBroadcast {
+                dimension: 1,
+                lhs: 3,
+                rhs: 1,
+            })
+        );
+        assert_eq!(
+            layout.broadcast_to(shape(&[4])),
+            Err(TensorLayoutError::RankMismatch {
+                expected: 1,
+                actual: 2,
+            })
+        );
+    }
+}
@@ -1,4 +1,7 @@
 mod constants;
+mod element;
+mod float16;
+mod layout;
 mod tensor;
 mod tensor_data_type;
 mod tensor_descriptor;
@@ -8,7 +11,10 @@ mod tensor_reference_type;
 mod tensor_usage;
 
 pub use constants::TENSOR_MAX_RANK;
-pub use tensor::MTLTensor;
+pub use element::{TensorElement, tensor_data_type_size};
+pub use float16::{BFloat16, Float16};
+pub use layout::{TensorLayout, TensorLayoutError, TensorShape};
+pub use tensor::{MTLTensor, MTLTensorExt};
 pub use tensor_data_type::MTLTensorDataType;
 pub use tensor_descriptor::MTLTensorDescriptor;
 pub use tensor_error::MTLTensorError;
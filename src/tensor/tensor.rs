@@ -1,8 +1,11 @@
-use core::{ffi::c_void, ptr::NonNull};
+use core::{ffi::c_void, mem::MaybeUninit, ptr::NonNull};
 
-use objc2::{extern_protocol, rc::Retained, runtime::ProtocolObject};
+use objc2::{Message, extern_protocol, rc::Retained, runtime::ProtocolObject};
 
-use super::{MTLTensorDataType, MTLTensorExtents, MTLTensorUsage};
+use super::{
+    MTLTensorDataType, MTLTensorExtents, MTLTensorUsage, TensorElement, TensorLayout, TensorLayoutError, TensorShape,
+    layout::{check_region, extents_from},
+};
 use crate::{MTLBuffer, MTLResource, MTLResourceID};
 
 extern_protocol!(
@@ -86,3 +89,133 @@ extern_protocol!(
         );
     }
 );
+
+pub trait MTLTensorExt: MTLTensor + Message {
+    /// The tensor's dimensions, innermost first.
+    fn shape(&self) -> Result<TensorShape, TensorLayoutError>
+    where
+        Self: Sized,
+    {
+        TensorShape::from_extents(&self.dimensions())
+    }
+
+    /// The tensor's element layout, using its strides when it has them and a
+    /// contiguous layout otherwise.
+    fn layout(&self) -> Result<TensorLayout, TensorLayoutError>
+    where
+        Self: Sized,
+    {
+        let shape = self.shape()?;
+        let Some(strides) = self.strides() else {
+            return Ok(TensorLayout::contiguous(shape));
+        };
+        let strides = TensorShape::from_extents(&strides)?;
+        TensorLayout::with_strides(shape, strides.dimensions())
+    }
+
+    /// Fails unless the tensor's data type is `T::DATA_TYPE`.
+    fn check_element_type<T: TensorElement>(&self) -> Result<(), TensorLayoutError>
+    where
+        Self: Sized,
+    {
+        let actual = self.data_type();
+        if actual != T::DATA_TYPE {
+            return Err(TensorLayoutError::DataTypeMismatch {
+                expected: T::DATA_TYPE,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    /// Copies the `dimensions`-sized slice at `origin` into a contiguous
+    /// vector, innermost dimension fastest.
+    fn read_slice<T: TensorElement>(
+        &self,
+        origin: &[usize],
+        dimensions: &[usize],
+    ) -> Result<Vec<T>, TensorLayoutError>
+    where
+        Self: Sized,
+    {
+        let layout = TensorLayout::contiguous(TensorShape::new(dimensions)?);
+        let mut elements = Vec::with_capacity(layout.required_len()?);
+        self.read_slice_into(origin, &layout, elements.spare_capacity_mut())?;
+        unsafe { elements.set_len(layout.required_len()?) };
+        Ok(elements)
+    }
+
+    /// Copies the slice at `origin` with the dimensions of `layout` into
+    /// `elements`, placing each element where `layout` says.
+    ///
+    /// `layout` must be [Metal-compatible](TensorLayout::is_metal_compatible).
+    fn read_slice_into<T: TensorElement>(
+        &self,
+        origin: &[usize],
+        layout: &TensorLayout,
+        elements: &mut [MaybeUninit<T>],
+    ) -> Result<(), TensorLayoutError>
+    where
+        Self: Sized,
+    {
+        let Some((origin, dimensions, strides)) = slice_arguments::<T>(self, origin, layout, elements.len())? else {
+            return Ok(());
+        };
+        // Derived from the whole slice, not one element, so the copy may touch
+        // every element the layout spans; `slice_arguments` checked they're in bounds.
+        let bytes = unsafe { NonNull::new_unchecked(elements.as_mut_ptr().add(layout.offset())) }.cast();
+        self.get_bytes_strides_from_slice_origin_slice_dimensions(bytes, &strides, &origin, &dimensions);
+        Ok(())
+    }
+
+    /// Replaces the slice at `origin` with the dimensions of `layout` by
+    /// elements read from `elements` as `layout` describes.
+    ///
+    /// `layout` must be [Metal-compatible](TensorLayout::is_metal_compatible).
+    fn write_slice<T: TensorElement>(
+        &self,
+        origin: &[usize],
+        layout: &TensorLayout,
+        elements: &[T],
+    ) -> Result<(), TensorLayoutError>
+    where
+        Self: Sized,
+    {
+        let Some((origin, dimensions, strides)) = slice_arguments::<T>(self, origin, layout, elements.len())? else {
+            return Ok(());
+        };
+        let bytes = unsafe { NonNull::new_unchecked(elements.as_ptr().add(layout.offset()).cast_mut()) }.cast();
+        self.replace_slice_origin_slice_dimensions_with_bytes_strides(&origin, &dimensions, bytes, &strides);
+        Ok(())
+    }
+}
+
+impl<T: MTLTensor + Message> MTLTensorExt for T {}
+
+type SliceArguments = (Retained<MTLTensorExtents>, Retained<MTLTensorExtents>, Retained<MTLTensorExtents>);
+
+/// Validates a slice copy and builds its origin, dimension and stride
+/// extents, or returns `None` if the slice is empty.
+fn slice_arguments<T: TensorElement>(
+    tensor: &impl MTLTensorExt,
+    origin: &[usize],
+    layout: &TensorLayout,
+    len: usize,
+) -> Result<Option<SliceArguments>, TensorLayoutError> {
+    tensor.check_element_type::<T>()?;
+    check_region(tensor.shape()?.dimensions(), origin, layout.shape().dimensions())?;
+    if !layout.is_metal_compatible() {
+        return Err(TensorLayoutError::UnsupportedStrides);
+    }
+    let required = layout.required_len()?;
+    if required > len {
+        return Err(TensorLayoutError::SliceTooShort {
+            required,
+            actual: len,
+        });
+    }
+    if layout.shape().is_empty() {
+        return Ok(None);
+    }
+    Ok(Some((extents_from(origin)?, layout.shape().to_extents()?, layout.strides_to_extents()?)))
+}
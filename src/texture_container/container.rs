@@ -0,0 +1,724 @@
+use core::{fmt, ops::Range};
+use std::{io, path::Path};
+
+use objc2::rc::Retained;
+use objc2_foundation::NSError;
+
+use super::{dds, ktx2};
+use crate::{MTLOrigin, MTLPixelFormat, MTLSize, MTLTextureType, StagingCopy, TextureUploadTarget};
+
+/// Alignment of each image in a staging buffer.
+const STAGING_ALIGNMENT: usize = 256;
+
+/// Errors raised while parsing, writing or loading texture containers.
+#[derive(Debug)]
+pub enum TextureContainerError {
+    Io(io::Error),
+    /// The file ends before the structure it describes.
+    Truncated,
+    /// The file's header is malformed or describes an invalid texture.
+    InvalidHeader(String),
+    /// The container's pixel format has no `MTLPixelFormat`.
+    UnsupportedFormat(String),
+    /// The KTX2 file's levels use this supercompression scheme.
+    UnsupportedSupercompression(u32),
+    /// The pixel format can't be stored in the container or have its mipmaps
+    /// generated.
+    UnsupportedPixelFormat(MTLPixelFormat),
+    /// Image data isn't the size the texture's dimensions require.
+    DataLengthMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    /// Metal failed to create the texture, staging buffer or command objects.
+    ResourceCreation,
+    /// The upload's command buffer failed.
+    CommandBuffer(Option<Retained<NSError>>),
+}
+
+impl fmt::Display for TextureContainerError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "failed to read texture container: {error}"),
+            Self::Truncated => f.write_str("texture container is truncated"),
+            Self::InvalidHeader(message) => write!(f, "invalid texture container header: {message}"),
+            Self::UnsupportedFormat(format) => write!(f, "unsupported texture container format {format}"),
+            Self::UnsupportedSupercompression(scheme) => write!(f, "unsupported KTX2 supercompression scheme {scheme}"),
+            Self::UnsupportedPixelFormat(format) => write!(f, "unsupported pixel format {format:?}"),
+            Self::DataLengthMismatch {
+                expected,
+                actual,
+            } => write!(f, "texture image needs {expected} bytes of data but has {actual}"),
+            Self::ResourceCreation => f.write_str("failed to create a Metal resource"),
+            Self::CommandBuffer(Some(error)) => write!(f, "upload command buffer failed: {error}"),
+            Self::CommandBuffer(None) => f.write_str("upload command buffer failed"),
+        }
+    }
+}
+
+impl std::error::Error for TextureContainerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TextureContainerError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// The file format of a [`TextureContainer`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TextureContainerFormat {
+    Ktx2,
+    Dds,
+}
+
+/// One image of a [`TextureContainer`]: an array slice or cube face of a
+/// mipmap level, or all depth planes of a 3D texture's level.
+///
+/// Rows and images are tightly packed; compressed formats have a row of
+/// blocks per row.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ContainerImage {
+    pub level: usize,
+    /// The array slice or cube face (`6 * cube + face`); 0 for 3D textures.
+    pub slice: usize,
+    /// Size in pixels, which may not be a whole number of blocks.
+    pub size: MTLSize,
+    pub bytes_per_row: usize,
+    /// Bytes per depth plane.
+    pub bytes_per_image: usize,
+    /// The image's bytes in [`TextureContainer::data`].
+    pub range: Range<usize>,
+}
+
+impl ContainerImage {
+    pub(crate) fn new(
+        level: usize,
+        slice: usize,
+        layout: &LevelLayout,
+        start: usize,
+    ) -> Self {
+        Self {
+            level,
+            slice,
+            size: layout.size,
+            bytes_per_row: layout.bytes_per_row,
+            bytes_per_image: layout.bytes_per_image,
+            range: start..start + layout.image_len,
+        }
+    }
+
+    /// The blit copy of this image from `offset` in a staging buffer.
+    pub fn staging_copy(
+        &self,
+        offset: usize,
+    ) -> StagingCopy {
+        StagingCopy {
+            offset,
+            bytes_per_row: self.bytes_per_row,
+            bytes_per_image: self.bytes_per_image,
+            size: self.size,
+            level: self.level,
+            slice: self.slice,
+            origin: MTLOrigin {
+                x: 0,
+                y: 0,
+                z: 0,
+            },
+        }
+    }
+}
+
+/// The size and tightly packed strides of a mipmap level.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct LevelLayout {
+    pub(crate) size: MTLSize,
+    pub(crate) bytes_per_row: usize,
+    pub(crate) bytes_per_image: usize,
+    /// Bytes per [`ContainerImage`], covering every depth plane.
+    pub(crate) image_len: usize,
+    /// [`ContainerImage`]s per level.
+    pub(crate) image_count: usize,
+}
+
+impl LevelLayout {
+    pub(crate) fn new(
+        target: &TextureUploadTarget,
+        level: usize,
+    ) -> Result<Self, TextureContainerError> {
+        let overflow = || TextureContainerError::InvalidHeader("texture size overflows usize".to_owned());
+        let (block_width, block_height) = target.pixel_format.block_dimensions();
+        let bytes_per_block = target
+            .pixel_format
+            .bytes_per_block()
+            .ok_or(TextureContainerError::UnsupportedPixelFormat(target.pixel_format))?;
+        let size = target.level_size(level);
+        let bytes_per_row = size.width.div_ceil(block_width).checked_mul(bytes_per_block).ok_or_else(overflow)?;
+        let bytes_per_image = size.height.div_ceil(block_height).checked_mul(bytes_per_row).ok_or_else(overflow)?;
+        let image_len = bytes_per_image.checked_mul(size.depth).ok_or_else(overflow)?;
+        let image_count = match target.texture_type {
+            MTLTextureType::Type3D => 1,
+            _ => target.slice_count(level),
+        };
+        Ok(Self {
+            size,
+            bytes_per_row,
+            bytes_per_image,
+            image_len,
+            image_count,
+        })
+    }
+
+    /// Bytes of every image of the level.
+    pub(crate) fn level_len(&self) -> Result<usize, TextureContainerError> {
+        self.image_len
+            .checked_mul(self.image_count)
+            .ok_or_else(|| TextureContainerError::InvalidHeader("texture size overflows usize".to_owned()))
+    }
+}
+
+/// The number of levels in a full mipmap chain of `target`.
+pub(crate) fn full_mipmap_level_count(target: &TextureUploadTarget) -> usize {
+    let depth = match target.texture_type {
+        MTLTextureType::Type3D => target.depth,
+        _ => 1,
+    };
+    let extent = target.width.max(target.height).max(depth).max(1);
+    (usize::BITS - extent.leading_zeros()) as usize
+}
+
+/// Checks that a container can describe `target`.
+pub(crate) fn validate_target(target: &TextureUploadTarget) -> Result<(), TextureContainerError> {
+    let invalid = |message: String| Err(TextureContainerError::InvalidHeader(message));
+    if target.pixel_format.bytes_per_block().is_none() {
+        return Err(TextureContainerError::UnsupportedPixelFormat(target.pixel_format));
+    }
+    if target.width == 0 || target.height == 0 || target.depth == 0 || target.array_length == 0 {
+        return invalid(format!(
+            "texture is {}x{}x{} with {} slices",
+            target.width, target.height, target.depth, target.array_length
+        ));
+    }
+    let single_slice = matches!(
+        target.texture_type,
+        MTLTextureType::Type1D | MTLTextureType::Type2D | MTLTextureType::Cube | MTLTextureType::Type3D
+    );
+    if single_slice && target.array_length != 1 {
+        return invalid(format!("{:?} textures have one slice", target.texture_type));
+    }
+    let valid_extents = match target.texture_type {
+        MTLTextureType::Type1D | MTLTextureType::Type1DArray => target.height == 1 && target.depth == 1,
+        MTLTextureType::Type2D | MTLTextureType::Type2DArray => target.depth == 1,
+        MTLTextureType::Cube | MTLTextureType::CubeArray => target.depth == 1 && target.width == target.height,
+        MTLTextureType::Type3D => true,
+        texture_type => return invalid(format!("{texture_type:?} textures can't be stored in containers")),
+    };
+    if !valid_extents {
+        return invalid(format!(
+            "{:?} textures can't be {}x{}x{}",
+            target.texture_type, target.width, target.height, target.depth
+        ));
+    }
+    let full_count = full_mipmap_level_count(target);
+    if target.mipmap_level_count == 0 || target.mipmap_level_count > full_count {
+        return invalid(format!("{} mipmap levels for a texture with at most {full_count}", target.mipmap_level_count));
+    }
+    Ok(())
+}
+
+/// Reads a little-endian `u32` at `offset`.
+pub(crate) fn read_u32(
+    bytes: &[u8],
+    offset: usize,
+) -> Result<u32, TextureContainerError> {
+    let bytes = bytes.get(offset..offset + 4).ok_or(TextureContainerError::Truncated)?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Reads a little-endian `u64` at `offset`.
+pub(crate) fn read_u64(
+    bytes: &[u8],
+    offset: usize,
+) -> Result<u64, TextureContainerError> {
+    let bytes = bytes.get(offset..offset + 8).ok_or(TextureContainerError::Truncated)?;
+    Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// A texture parsed from a `.ktx2` or `.dds` file, or assembled in memory.
+///
+/// The file is kept in memory and images index into it. When
+/// [`generates_mipmaps`](Self::generates_mipmaps) is set, only level 0 is
+/// stored and loaders generate the rest of the target's levels.
+#[derive(Clone, Debug)]
+pub struct TextureContainer {
+    format: Option<TextureContainerFormat>,
+    target: TextureUploadTarget,
+    generate_mipmaps: bool,
+    key_values: Vec<(String, Vec<u8>)>,
+    images: Vec<ContainerImage>,
+    data: Vec<u8>,
+}
+
+impl TextureContainer {
+    /// Wraps the images of every level of `target`, tightly packed and
+    /// ordered by level, then slice or cube face.
+    pub fn new(
+        target: TextureUploadTarget,
+        data: Vec<u8>,
+    ) -> Result<Self, TextureContainerError> {
+        validate_target(&target)?;
+        let mut images = Vec::new();
+        let mut offset = 0usize;
+        for level in 0..target.mipmap_level_count {
+            let layout = LevelLayout::new(&target, level)?;
+            for slice in 0..layout.image_count {
+                images.push(ContainerImage::new(level, slice, &layout, offset));
+                offset = offset
+                    .checked_add(layout.image_len)
+                    .ok_or_else(|| TextureContainerError::InvalidHeader("texture size overflows usize".to_owned()))?;
+            }
+        }
+        if offset != data.len() {
+            return Err(TextureContainerError::DataLengthMismatch {
+                expected: offset,
+                actual: data.len(),
+            });
+        }
+        Ok(Self::from_parts(None, target, false, Vec::new(), images, data))
+    }
+
+    /// Assembles a parsed container, ordering `images` by level and slice.
+    pub(crate) fn from_parts(
+        format: Option<TextureContainerFormat>,
+        target: TextureUploadTarget,
+        generate_mipmaps: bool,
+        key_values: Vec<(String, Vec<u8>)>,
+        mut images: Vec<ContainerImage>,
+        data: Vec<u8>,
+    ) -> Self {
+        images.sort_by_key(|image| (image.level, image.slice));
+        Self {
+            format,
+            target,
+            generate_mipmaps,
+            key_values,
+            images,
+            data,
+        }
+    }
+
+    /// Reads and parses a texture container, detecting the format from its
+    /// contents.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, TextureContainerError> {
+        Self::from_bytes(std::fs::read(path)?)
+    }
+
+    /// Parses a texture container, detecting the format from its contents.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, TextureContainerError> {
+        if bytes.starts_with(ktx2::IDENTIFIER) {
+            Self::from_ktx2(bytes)
+        } else if bytes.starts_with(dds::MAGIC) {
+            Self::from_dds(bytes)
+        } else {
+            Err(TextureContainerError::InvalidHeader("not a KTX2 or DDS file".to_owned()))
+        }
+    }
+
+    /// Parses a KTX2 file without supercompression.
+    pub fn from_ktx2(bytes: Vec<u8>) -> Result<Self, TextureContainerError> {
+        ktx2::parse(bytes)
+    }
+
+    /// Parses a DDS file, with or without the DX10 header extension.
+    pub fn from_dds(bytes: Vec<u8>) -> Result<Self, TextureContainerError> {
+        dds::parse(bytes)
+    }
+
+    /// Keeps only level 0 and asks loaders to generate the remaining levels
+    /// of a full mipmap chain. Block-compressed formats can't be generated.
+    pub fn with_generated_mipmaps(mut self) -> Result<Self, TextureContainerError> {
+        if self.target.pixel_format.is_compressed() {
+            return Err(TextureContainerError::UnsupportedPixelFormat(self.target.pixel_format));
+        }
+        self.target.mipmap_level_count = full_mipmap_level_count(&self.target);
+        self.generate_mipmaps = true;
+        self.images.retain(|image| image.level == 0);
+        Ok(self)
+    }
+
+    /// Adds a KTX2 key/value pair, replacing any with the same key.
+    pub fn with_key_value(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<Vec<u8>>,
+    ) -> Self {
+        let key = key.into();
+        self.key_values.retain(|(existing, _)| *existing != key);
+        self.key_values.push((key, value.into()));
+        self
+    }
+
+    /// The file format the container was parsed from, if any.
+    pub fn format(&self) -> Option<TextureContainerFormat> {
+        self.format
+    }
+
+    /// The texture the container describes, including generated levels.
+    pub fn target(&self) -> &TextureUploadTarget {
+        &self.target
+    }
+
+    pub fn pixel_format(&self) -> MTLPixelFormat {
+        self.target.pixel_format
+    }
+
+    pub fn texture_type(&self) -> MTLTextureType {
+        self.target.texture_type
+    }
+
+    /// Whether levels past 0 are generated rather than stored.
+    pub fn generates_mipmaps(&self) -> bool {
+        self.generate_mipmaps
+    }
+
+    /// The KTX2 key/value data, in file order. Values are raw, so string
+    /// values keep their NUL terminator.
+    pub fn key_values(&self) -> &[(String, Vec<u8>)] {
+        &self.key_values
+    }
+
+    /// The value of KTX2 key `key`.
+    pub fn key_value(
+        &self,
+        key: &str,
+    ) -> Option<&[u8]> {
+        self.key_values.iter().find(|(existing, _)| existing == key).map(|(_, value)| value.as_slice())
+    }
+
+    /// Stored images, ordered by level, then slice.
+    pub fn images(&self) -> &[ContainerImage] {
+        &self.images
+    }
+
+    pub fn image(
+        &self,
+        level: usize,
+        slice: usize,
+    ) -> Option<&ContainerImage> {
+        self.images.iter().find(|image| image.level == level && image.slice == slice)
+    }
+
+    /// The bytes of `image`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `image` isn't one of this container's images.
+    pub fn image_data(
+        &self,
+        image: &ContainerImage,
+    ) -> &[u8] {
+        &self.data[image.range.clone()]
+    }
+
+    /// The file, or the data passed to [`new`](Self::new).
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The blit copies that upload every image from a staging buffer filled
+    /// by [`write_staging`](Self::write_staging), and that buffer's length.
+    /// Each image starts at a 256-byte aligned offset.
+    pub fn staging_copies(&self) -> (Vec<StagingCopy>, usize) {
+        let mut copies = Vec::with_capacity(self.images.len());
+        let mut staging_len = 0usize;
+        for image in &self.images {
+            let offset = staging_len.next_multiple_of(STAGING_ALIGNMENT);
+            copies.push(image.staging_copy(offset));
+            staging_len = offset + image.range.len();
+        }
+        (copies, staging_len)
+    }
+
+    /// Copies every image to its offset in `staging`.
+    pub fn write_staging(
+        &self,
+        staging: &mut [u8],
+    ) -> Result<(), TextureContainerError> {
+        let (copies, staging_len) = self.staging_copies();
+        if staging.len() < staging_len {
+            return Err(TextureContainerError::DataLengthMismatch {
+                expected: staging_len,
+                actual: staging.len(),
+            });
+        }
+        for (image, copy) in self.images.iter().zip(&copies) {
+            staging[copy.offset..copy.offset + image.range.len()].copy_from_slice(self.image_data(image));
+        }
+        Ok(())
+    }
+
+    /// Encodes the container as a KTX2 file without supercompression.
+    pub fn to_ktx2_bytes(&self) -> Result<Vec<u8>, TextureContainerError> {
+        ktx2::write(self)
+    }
+
+    /// Encodes the container as a DDS file with the DX10 header extension.
+    /// DDS has no key/value data, and generated levels aren't recorded.
+    pub fn to_dds_bytes(&self) -> Result<Vec<u8>, TextureContainerError> {
+        dds::write(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> Vec<u8> {
+        std::fs::read(format!("{}/tests/fixtures/texture_container/{name}", env!("CARGO_MANIFEST_DIR"))).unwrap()
+    }
+
+    fn parse(name: &str) -> TextureContainer {
+        TextureContainer::from_bytes(fixture(name)).unwrap()
+    }
+
+    /// The bytes the fixture generator fills each level or image with.
+    fn pattern(
+        len: usize,
+        seed: usize,
+    ) -> Vec<u8> {
+        (0..len).map(|i| ((seed * 37 + i) % 251) as u8).collect()
+    }
+
+    fn patch_u32(
+        bytes: &mut [u8],
+        offset: usize,
+        value: u32,
+    ) {
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn summary(container: &TextureContainer) -> (MTLTextureType, MTLPixelFormat, [usize; 5], usize) {
+        let target = container.target();
+        let extents = [target.width, target.height, target.depth, target.mipmap_level_count, target.array_length];
+        (target.texture_type, target.pixel_format, extents, container.images().len())
+    }
+
+    const KTX2_FIXTURES: [&str; 6] =
+        ["rgba8_mips.ktx2", "r8_array.ktx2", "bc1_cube.ktx2", "rg8_3d.ktx2", "astc_6x6.ktx2", "rgba8_generated.ktx2"];
+    const DDS_FIXTURES: [&str; 5] = ["bc3_mips.dds", "bgra8.dds", "rgba8_volume.dds", "bc7_array.dds", "r8_cube.dds"];
+
+    #[test]
+    fn ktx2_fixtures() {
+        let mips = parse("rgba8_mips.ktx2");
+        assert_eq!(mips.format(), Some(TextureContainerFormat::Ktx2));
+        assert_eq!(summary(&mips), (MTLTextureType::Type2D, MTLPixelFormat::RGBA8Unorm, [4, 4, 1, 3, 1], 3));
+        let level = mips.image(1, 0).unwrap();
+        assert_eq!((level.size, level.bytes_per_row, level.bytes_per_image), (MTLSize::new(2, 2, 1), 8, 16));
+        assert_eq!(mips.image_data(level), pattern(16, 2));
+        assert_eq!(mips.key_value("KTXorientation"), Some(b"rd\0".as_slice()));
+        let keys: Vec<_> = mips.key_values().iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, ["KTXorientation", "KTXwriter"]);
+
+        let array = parse("r8_array.ktx2");
+        assert_eq!(summary(&array), (MTLTextureType::Type2DArray, MTLPixelFormat::R8Unorm, [2, 2, 1, 1, 3], 3));
+        assert_eq!(array.image_data(array.image(0, 2).unwrap()), &pattern(12, 1)[8..]);
+
+        let cube = parse("bc1_cube.ktx2");
+        assert_eq!(summary(&cube), (MTLTextureType::Cube, MTLPixelFormat::BC1_RGBA, [8, 8, 1, 2, 1], 12));
+        let face = cube.image(0, 5).unwrap();
+        assert_eq!((face.bytes_per_row, face.bytes_per_image), (16, 32));
+        assert_eq!(cube.image_data(cube.image(1, 3).unwrap()), &pattern(48, 2)[24..32]);
+
+        let volume = parse("rg8_3d.ktx2");
+        assert_eq!(summary(&volume), (MTLTextureType::Type3D, MTLPixelFormat::RG8Unorm, [2, 2, 2, 2, 1], 2));
+        let level = volume.image(0, 0).unwrap();
+        assert_eq!((level.size, level.bytes_per_image, level.range.len()), (MTLSize::new(2, 2, 2), 8, 16));
+        assert_eq!(volume.image(1, 0).unwrap().size, MTLSize::new(1, 1, 1));
+
+        let astc = parse("astc_6x6.ktx2");
+        assert_eq!(summary(&astc), (MTLTextureType::Type2D, MTLPixelFormat::ASTC_6x6_LDR, [8, 8, 1, 1, 1], 1));
+        assert_eq!((astc.images()[0].bytes_per_row, astc.images()[0].range.len()), (32, 64));
+
+        let generated = parse("rgba8_generated.ktx2");
+        assert!(generated.generates_mipmaps());
+        assert_eq!(summary(&generated), (MTLTextureType::Type2D, MTLPixelFormat::RGBA8Unorm, [4, 2, 1, 3, 1], 1));
+    }
+
+    #[test]
+    fn dds_fixtures() {
+        let bc3 = parse("bc3_mips.dds");
+        assert_eq!(bc3.format(), Some(TextureContainerFormat::Dds));
+        assert_eq!(summary(&bc3), (MTLTextureType::Type2D, MTLPixelFormat::BC3_RGBA, [8, 8, 1, 2, 1], 2));
+        assert_eq!(bc3.image_data(bc3.image(1, 0).unwrap()), pattern(16, 2));
+
+        let bgra = parse("bgra8.dds");
+        assert_eq!(summary(&bgra), (MTLTextureType::Type2D, MTLPixelFormat::BGRA8Unorm, [2, 2, 1, 1, 1], 1));
+
+        let volume = parse("rgba8_volume.dds");
+        assert_eq!(summary(&volume), (MTLTextureType::Type3D, MTLPixelFormat::RGBA8Unorm, [2, 2, 2, 2, 1], 2));
+        assert_eq!(volume.image(0, 0).unwrap().range.len(), 32);
+        assert_eq!(volume.image_data(volume.image(1, 0).unwrap()), pattern(4, 2));
+
+        // Each slice holds its whole mipmap chain.
+        let array = parse("bc7_array.dds");
+        assert_eq!(summary(&array), (MTLTextureType::Type2DArray, MTLPixelFormat::BC7_RGBAUnorm, [4, 4, 1, 2, 2], 4));
+        assert_eq!(array.image_data(array.image(0, 1).unwrap()), pattern(16, 3));
+        assert_eq!(array.image_data(array.image(1, 0).unwrap()), pattern(16, 2));
+
+        let cube = parse("r8_cube.dds");
+        assert_eq!(summary(&cube), (MTLTextureType::Cube, MTLPixelFormat::R8Unorm, [2, 2, 1, 1, 1], 6));
+        assert_eq!(cube.image_data(cube.image(0, 4).unwrap()), pattern(4, 5));
+    }
+
+    fn assert_same_images(
+        name: &str,
+        original: &TextureContainer,
+        written: &TextureContainer,
+    ) {
+        assert_eq!(written.target(), original.target(), "{name}");
+        assert_eq!(written.images().len(), original.images().len(), "{name}");
+        for (a, b) in original.images().iter().zip(written.images()) {
+            assert_eq!((a.level, a.slice, a.size), (b.level, b.slice, b.size), "{name}");
+            assert_eq!(original.image_data(a), written.image_data(b), "{name}");
+        }
+    }
+
+    #[test]
+    fn ktx2_round_trip() {
+        for name in KTX2_FIXTURES.iter().chain(&DDS_FIXTURES) {
+            let original = parse(name);
+            let bytes = original.to_ktx2_bytes().unwrap();
+            let written = TextureContainer::from_ktx2(bytes.clone()).unwrap();
+            assert_same_images(name, &original, &written);
+            assert_eq!(written.generates_mipmaps(), original.generates_mipmaps(), "{name}");
+            assert_eq!(written.key_values(), original.key_values(), "{name}");
+            assert_eq!(written.to_ktx2_bytes().unwrap(), bytes, "{name}");
+        }
+    }
+
+    #[test]
+    fn dds_round_trip() {
+        for name in KTX2_FIXTURES.iter().chain(&DDS_FIXTURES).filter(|name| **name != "rgba8_generated.ktx2") {
+            let original = parse(name);
+            let bytes = original.to_dds_bytes().unwrap();
+            let written = TextureContainer::from_dds(bytes.clone()).unwrap();
+            assert_same_images(name, &original, &written);
+            assert_eq!(written.to_dds_bytes().unwrap(), bytes, "{name}");
+        }
+
+        // DDS can't record generated levels, so only level 0 is written.
+        let generated = parse("rgba8_generated.ktx2");
+        let written = TextureContainer::from_dds(generated.to_dds_bytes().unwrap()).unwrap();
+        assert_eq!((written.target().mipmap_level_count, written.generates_mipmaps()), (1, false));
+        assert_eq!(written.image_data(&written.images()[0]), generated.image_data(&generated.images()[0]));
+    }
+
+    #[test]
+    fn new_matches_parsed_layout() {
+        let parsed = parse("bc1_cube.ktx2");
+        let data: Vec<u8> = parsed.images().iter().flat_map(|image| parsed.image_data(image).to_vec()).collect();
+        let built = TextureContainer::new(*parsed.target(), data.clone()).unwrap();
+        assert_same_images("bc1_cube.ktx2", &parsed, &built);
+        assert_eq!(built.to_ktx2_bytes().unwrap(), parsed.to_ktx2_bytes().unwrap());
+
+        assert!(matches!(
+            TextureContainer::new(*parsed.target(), data[1..].to_vec()),
+            Err(TextureContainerError::DataLengthMismatch {
+                expected: 240,
+                actual: 239,
+            })
+        ));
+    }
+
+    #[test]
+    fn malformed_ktx2() {
+        let ktx2 = |patch: &dyn Fn(&mut Vec<u8>)| {
+            let mut bytes = fixture("rgba8_mips.ktx2");
+            patch(&mut bytes);
+            TextureContainer::from_bytes(bytes).unwrap_err()
+        };
+        let invalid = |error: TextureContainerError| matches!(error, TextureContainerError::InvalidHeader(_));
+
+        assert!(invalid(TextureContainer::from_bytes(b"PNG".to_vec()).unwrap_err()));
+        assert!(matches!(ktx2(&|bytes| bytes.truncate(40)), TextureContainerError::Truncated));
+        // Level 0 is stored last.
+        assert!(matches!(ktx2(&|bytes| bytes.truncate(bytes.len() - 1)), TextureContainerError::Truncated));
+        assert!(matches!(ktx2(&|bytes| bytes.truncate(90)), TextureContainerError::Truncated));
+        assert!(matches!(
+            ktx2(&|bytes| patch_u32(bytes, 44, 1)),
+            TextureContainerError::UnsupportedSupercompression(1)
+        ));
+        assert!(matches!(
+            ktx2(&|bytes| patch_u32(bytes, 12, 0)),
+            TextureContainerError::UnsupportedFormat(format) if format == "vkFormat 0"
+        ));
+        assert!(invalid(ktx2(&|bytes| patch_u32(bytes, 36, 3))));
+        // 3D cubes.
+        assert!(invalid(ktx2(&|bytes| {
+            patch_u32(bytes, 28, 4);
+            patch_u32(bytes, 36, 6);
+        })));
+        // More levels than a 4x4 mipmap chain has.
+        assert!(invalid(ktx2(&|bytes| patch_u32(bytes, 40, 4))));
+        assert!(matches!(
+            ktx2(&|bytes| patch_u32(bytes, 88, 63)),
+            TextureContainerError::DataLengthMismatch {
+                expected: 64,
+                actual: 63,
+            }
+        ));
+        assert!(invalid(ktx2(&|bytes| {
+            let start = bytes.windows(18).position(|pair| pair == b"KTXorientation\0rd\0").unwrap();
+            bytes[start + 14] = b'-';
+            bytes[start + 17] = b'-';
+        })));
+
+        let mut cube = fixture("bc1_cube.ktx2");
+        patch_u32(&mut cube, 40, 0);
+        assert!(invalid(TextureContainer::from_bytes(cube).unwrap_err()));
+    }
+
+    #[test]
+    fn malformed_dds() {
+        let dds = |name: &str, patch: &dyn Fn(&mut Vec<u8>)| {
+            let mut bytes = fixture(name);
+            patch(&mut bytes);
+            TextureContainer::from_bytes(bytes).unwrap_err()
+        };
+        let invalid = |error: TextureContainerError| matches!(error, TextureContainerError::InvalidHeader(_));
+
+        assert!(invalid(dds("bgra8.dds", &|bytes| patch_u32(bytes, 4, 100))));
+        assert!(invalid(dds("bgra8.dds", &|bytes| patch_u32(bytes, 16, 0))));
+        assert!(matches!(dds("bgra8.dds", &|bytes| bytes.truncate(60)), TextureContainerError::Truncated));
+        assert!(matches!(
+            dds("bc3_mips.dds", &|bytes| bytes.truncate(bytes.len() - 1)),
+            TextureContainerError::Truncated
+        ));
+        // A cube map without all six faces.
+        assert!(invalid(dds("bgra8.dds", &|bytes| patch_u32(bytes, 112, 0x600))));
+        assert!(matches!(
+            dds("bc3_mips.dds", &|bytes| patch_u32(bytes, 84, u32::from_le_bytes(*b"ABCD"))),
+            TextureContainerError::UnsupportedFormat(format) if format == "FourCC \"ABCD\""
+        ));
+        assert!(matches!(
+            dds("bgra8.dds", &|bytes| patch_u32(bytes, 92, 0xF00)),
+            TextureContainerError::UnsupportedFormat(_)
+        ));
+        assert!(matches!(
+            dds("bc7_array.dds", &|bytes| patch_u32(bytes, 128, 1)),
+            TextureContainerError::UnsupportedFormat(format) if format == "DXGI_FORMAT 1"
+        ));
+        // Arrays of 3D textures.
+        assert!(invalid(dds("bc7_array.dds", &|bytes| patch_u32(bytes, 132, 4))));
+        assert!(matches!(dds("bc7_array.dds", &|bytes| bytes.truncate(140)), TextureContainerError::Truncated));
+    }
+}
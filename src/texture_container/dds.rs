@@ -0,0 +1,298 @@
+use super::{
+    TextureContainer, TextureContainerError, TextureContainerFormat,
+    container::{ContainerImage, LevelLayout, read_u32, validate_target},
+    formats::{dds_dxgi_format, dds_pixel_format},
+};
+use crate::{MTLPixelFormat, MTLTextureType, TextureUploadTarget};
+
+pub(crate) const MAGIC: &[u8] = b"DDS ";
+
+const HEADER_LEN: usize = 124;
+const PIXEL_FORMAT_LEN: u32 = 32;
+/// Offset of the pixel format within the header.
+const PIXEL_FORMAT_OFFSET: usize = 72;
+const DX10_HEADER_LEN: usize = 20;
+const DX10: u32 = u32::from_le_bytes(*b"DX10");
+
+const DDSD_CAPS: u32 = 0x1;
+const DDSD_HEIGHT: u32 = 0x2;
+const DDSD_WIDTH: u32 = 0x4;
+const DDSD_PITCH: u32 = 0x8;
+const DDSD_PIXELFORMAT: u32 = 0x1000;
+const DDSD_MIPMAPCOUNT: u32 = 0x2_0000;
+const DDSD_LINEARSIZE: u32 = 0x8_0000;
+const DDSD_DEPTH: u32 = 0x80_0000;
+
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_ALPHA: u32 = 0x2;
+const DDPF_FOURCC: u32 = 0x4;
+
+const DDSCAPS_COMPLEX: u32 = 0x8;
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+const DDSCAPS_MIPMAP: u32 = 0x40_0000;
+
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_CUBEMAP_ALLFACES: u32 = 0xFC00;
+const DDSCAPS2_VOLUME: u32 = 0x20_0000;
+
+const DIMENSION_TEXTURE1D: u32 = 2;
+const DIMENSION_TEXTURE2D: u32 = 3;
+const DIMENSION_TEXTURE3D: u32 = 4;
+const MISC_TEXTURECUBE: u32 = 0x4;
+
+fn invalid(message: impl Into<String>) -> TextureContainerError {
+    TextureContainerError::InvalidHeader(message.into())
+}
+
+pub(crate) fn parse(bytes: Vec<u8>) -> Result<TextureContainer, TextureContainerError> {
+    if !bytes.starts_with(MAGIC) {
+        return Err(invalid("missing DDS magic"));
+    }
+    let field = |offset: usize| read_u32(&bytes, MAGIC.len() + offset);
+    let header_len = field(0)?;
+    if header_len as usize != HEADER_LEN {
+        return Err(invalid(format!("header size is {header_len}")));
+    }
+    let flags = field(4)?;
+    let height = field(8)? as usize;
+    let width = field(12)? as usize;
+    let depth = field(20)? as usize;
+    let mipmap_level_count = match flags & DDSD_MIPMAPCOUNT {
+        0 => 1,
+        _ => (field(24)? as usize).max(1),
+    };
+    let pixel_format_flags = field(PIXEL_FORMAT_OFFSET + 4)?;
+    let four_cc = field(PIXEL_FORMAT_OFFSET + 8)?;
+    let caps2 = field(108)?;
+
+    let header_end = MAGIC.len() + HEADER_LEN;
+    let (pixel_format, texture_type, array_length, data_start) = if pixel_format_flags & DDPF_FOURCC != 0
+        && four_cc == DX10
+    {
+        let dxgi_format = read_u32(&bytes, header_end)?;
+        let dimension = read_u32(&bytes, header_end + 4)?;
+        let misc_flags = read_u32(&bytes, header_end + 8)?;
+        let array_size = (read_u32(&bytes, header_end + 12)? as usize).max(1);
+        let pixel_format = dds_pixel_format(dxgi_format)
+            .ok_or_else(|| TextureContainerError::UnsupportedFormat(format!("DXGI_FORMAT {dxgi_format}")))?;
+        // Cube arrays count cubes rather than faces.
+        let texture_type = match (dimension, misc_flags & MISC_TEXTURECUBE != 0, array_size > 1) {
+            (DIMENSION_TEXTURE1D, false, false) => MTLTextureType::Type1D,
+            (DIMENSION_TEXTURE1D, false, true) => MTLTextureType::Type1DArray,
+            (DIMENSION_TEXTURE2D, false, false) => MTLTextureType::Type2D,
+            (DIMENSION_TEXTURE2D, false, true) => MTLTextureType::Type2DArray,
+            (DIMENSION_TEXTURE2D, true, false) => MTLTextureType::Cube,
+            (DIMENSION_TEXTURE2D, true, true) => MTLTextureType::CubeArray,
+            (DIMENSION_TEXTURE3D, false, false) => MTLTextureType::Type3D,
+            _ => {
+                return Err(invalid(format!(
+                    "resource dimension {dimension} with misc flags {misc_flags:#x} and {array_size} elements"
+                )));
+            },
+        };
+        (pixel_format, texture_type, array_size, header_end + DX10_HEADER_LEN)
+    } else {
+        let masks = [
+            field(PIXEL_FORMAT_OFFSET + 16)?,
+            field(PIXEL_FORMAT_OFFSET + 20)?,
+            field(PIXEL_FORMAT_OFFSET + 24)?,
+            field(PIXEL_FORMAT_OFFSET + 28)?,
+        ];
+        let pixel_format = legacy_pixel_format(pixel_format_flags, four_cc, field(PIXEL_FORMAT_OFFSET + 12)?, masks)?;
+        let texture_type = if caps2 & DDSCAPS2_VOLUME != 0 {
+            MTLTextureType::Type3D
+        } else if caps2 & DDSCAPS2_CUBEMAP != 0 {
+            if caps2 & DDSCAPS2_CUBEMAP_ALLFACES != DDSCAPS2_CUBEMAP_ALLFACES {
+                return Err(invalid("cube maps must have all six faces"));
+            }
+            MTLTextureType::Cube
+        } else {
+            MTLTextureType::Type2D
+        };
+        (pixel_format, texture_type, 1, header_end)
+    };
+
+    let target = TextureUploadTarget {
+        texture_type,
+        pixel_format,
+        width,
+        height,
+        depth: match texture_type {
+            MTLTextureType::Type3D => depth.max(1),
+            _ => 1,
+        },
+        mipmap_level_count,
+        array_length,
+    };
+    validate_target(&target)?;
+
+    // Each slice or face holds its whole mipmap chain.
+    let layouts =
+        (0..mipmap_level_count).map(|level| LevelLayout::new(&target, level)).collect::<Result<Vec<_>, _>>()?;
+    let mut images = Vec::new();
+    let mut offset = data_start;
+    for slice in 0..layouts[0].image_count {
+        for (level, layout) in layouts.iter().enumerate() {
+            images.push(ContainerImage::new(level, slice, layout, offset));
+            offset = offset.checked_add(layout.image_len).ok_or(TextureContainerError::Truncated)?;
+        }
+    }
+    if offset > bytes.len() {
+        return Err(TextureContainerError::Truncated);
+    }
+
+    Ok(TextureContainer::from_parts(Some(TextureContainerFormat::Dds), target, false, Vec::new(), images, bytes))
+}
+
+/// Maps a pre-DX10 pixel format: a FourCC, a numeric `D3DFORMAT` or channel
+/// masks.
+fn legacy_pixel_format(
+    flags: u32,
+    four_cc: u32,
+    bit_count: u32,
+    masks: [u32; 4],
+) -> Result<MTLPixelFormat, TextureContainerError> {
+    use MTLPixelFormat as F;
+
+    if flags & DDPF_FOURCC != 0 {
+        let format = match &four_cc.to_le_bytes() {
+            b"DXT1" => F::BC1_RGBA,
+            b"DXT2" | b"DXT3" => F::BC2_RGBA,
+            b"DXT4" | b"DXT5" => F::BC3_RGBA,
+            b"ATI1" | b"BC4U" => F::BC4_RUnorm,
+            b"BC4S" => F::BC4_RSnorm,
+            b"ATI2" | b"BC5U" => F::BC5_RGUnorm,
+            b"BC5S" => F::BC5_RGSnorm,
+            code => match four_cc {
+                36 => F::RGBA16Unorm,
+                110 => F::RGBA16Snorm,
+                111 => F::R16Float,
+                112 => F::RG16Float,
+                113 => F::RGBA16Float,
+                114 => F::R32Float,
+                115 => F::RG32Float,
+                116 => F::RGBA32Float,
+                _ if code.iter().all(u8::is_ascii_graphic) => {
+                    let code = String::from_utf8_lossy(code);
+                    return Err(TextureContainerError::UnsupportedFormat(format!("FourCC {code:?}")));
+                },
+                _ => return Err(TextureContainerError::UnsupportedFormat(format!("D3DFORMAT {four_cc}"))),
+            },
+        };
+        return Ok(format);
+    }
+
+    // The alpha mask only counts when the header says alpha is present.
+    let [red, green, blue, alpha] = masks;
+    let alpha = match flags & (DDPF_ALPHAPIXELS | DDPF_ALPHA) {
+        0 => 0,
+        _ => alpha,
+    };
+    let format = match (bit_count, [red, green, blue, alpha]) {
+        (32, [0xFF, 0xFF00, 0xFF_0000, 0xFF00_0000]) => F::RGBA8Unorm,
+        (32, [0xFF_0000, 0xFF00, 0xFF, 0xFF00_0000]) => F::BGRA8Unorm,
+        (32, [0x3FF, 0xF_FC00, 0x3FF0_0000, 0xC000_0000]) => F::RGB10A2Unorm,
+        (32, [0xFFFF, 0xFFFF_0000, 0, 0]) => F::RG16Unorm,
+        (16, [0xFF, 0xFF00, 0, 0]) => F::RG8Unorm,
+        (16, [0xFFFF, 0, 0, 0]) => F::R16Unorm,
+        (8, [0xFF, 0, 0, 0]) => F::R8Unorm,
+        (8, [0, 0, 0, 0xFF]) => F::A8Unorm,
+        _ => {
+            return Err(TextureContainerError::UnsupportedFormat(format!(
+                "{bit_count}-bit masks {red:#x}, {green:#x}, {blue:#x}, {alpha:#x}"
+            )));
+        },
+    };
+    Ok(format)
+}
+
+pub(crate) fn write(container: &TextureContainer) -> Result<Vec<u8>, TextureContainerError> {
+    let target = container.target();
+    let dxgi_format = dds_dxgi_format(target.pixel_format)
+        .ok_or(TextureContainerError::UnsupportedPixelFormat(target.pixel_format))?;
+    let (dimension, misc_flags, array_size) = match target.texture_type {
+        MTLTextureType::Type1D | MTLTextureType::Type1DArray => (DIMENSION_TEXTURE1D, 0, target.array_length),
+        MTLTextureType::Type2D | MTLTextureType::Type2DArray => (DIMENSION_TEXTURE2D, 0, target.array_length),
+        MTLTextureType::Cube | MTLTextureType::CubeArray => {
+            (DIMENSION_TEXTURE2D, MISC_TEXTURECUBE, target.array_length)
+        },
+        MTLTextureType::Type3D => (DIMENSION_TEXTURE3D, 0, 1),
+        texture_type => return Err(invalid(format!("{texture_type:?} textures can't be stored in containers"))),
+    };
+    let stored_levels = if container.generates_mipmaps() {
+        1
+    } else {
+        target.mipmap_level_count
+    };
+    let top = LevelLayout::new(target, 0)?;
+    let compressed = target.pixel_format.is_compressed();
+    let volume = target.texture_type == MTLTextureType::Type3D;
+    let cube = misc_flags & MISC_TEXTURECUBE != 0;
+
+    let mut flags = DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT;
+    flags |= if compressed {
+        DDSD_LINEARSIZE
+    } else {
+        DDSD_PITCH
+    };
+    if stored_levels > 1 {
+        flags |= DDSD_MIPMAPCOUNT;
+    }
+    if volume {
+        flags |= DDSD_DEPTH;
+    }
+    let pitch = if compressed {
+        top.bytes_per_image
+    } else {
+        top.bytes_per_row
+    };
+    let mut caps = DDSCAPS_TEXTURE;
+    if stored_levels > 1 {
+        caps |= DDSCAPS_COMPLEX | DDSCAPS_MIPMAP;
+    }
+    if cube || volume || array_size > 1 {
+        caps |= DDSCAPS_COMPLEX;
+    }
+    let caps2 = if cube {
+        DDSCAPS2_CUBEMAP | DDSCAPS2_CUBEMAP_ALLFACES
+    } else if volume {
+        DDSCAPS2_VOLUME
+    } else {
+        0
+    };
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    let depth = if volume {
+        target.depth
+    } else {
+        0
+    };
+    for value in [
+        HEADER_LEN as u32,
+        flags,
+        target.height as u32,
+        target.width as u32,
+        pitch as u32,
+        depth as u32,
+        stored_levels as u32,
+    ] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes.extend_from_slice(&[0; 44]);
+    for value in [PIXEL_FORMAT_LEN, DDPF_FOURCC, DX10, 0, 0, 0, 0, 0, caps, caps2, 0, 0, 0] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    // The alpha mode is left unknown.
+    for value in [dxgi_format, dimension, misc_flags, array_size as u32, 0] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    for slice in 0..top.image_count {
+        for level in 0..stored_levels {
+            let image = container.image(level, slice).expect("containers hold every stored image");
+            bytes.extend_from_slice(container.image_data(image));
+        }
+    }
+    Ok(bytes)
+}
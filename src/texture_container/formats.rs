@@ -0,0 +1,237 @@
+use crate::MTLPixelFormat;
+
+/// How a format's channels encode values, for KTX2 data format descriptors.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ChannelKind {
+    Unorm,
+    UnormSrgb,
+    Snorm,
+    Uint,
+    Sint,
+    Ufloat,
+    Sfloat,
+}
+
+/// The basic data format descriptor KTX2 writers emit for a format.
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum Dfd {
+    /// Byte-aligned channels of the format's size per channel, in memory
+    /// order.
+    Channels(&'static [u8], ChannelKind),
+    /// Channels packed into one little-endian word, as
+    /// `(channel, bit offset, bit length)`.
+    Packed(&'static [(u8, u16, u8)], ChannelKind),
+    /// A block-compressed color model and its samples.
+    Compressed(u8, &'static [(u8, u16, u8)], ChannelKind),
+    /// No descriptor is written; the format can be read but not written.
+    Unsupported,
+}
+
+/// Color models of block-compressed formats.
+pub(crate) const BC1A: u8 = 128;
+pub(crate) const BC2: u8 = 129;
+pub(crate) const BC3: u8 = 130;
+pub(crate) const BC4: u8 = 131;
+pub(crate) const BC5: u8 = 132;
+pub(crate) const BC6H: u8 = 133;
+pub(crate) const BC7: u8 = 134;
+pub(crate) const ETC2: u8 = 161;
+pub(crate) const ASTC: u8 = 162;
+
+/// Channel identifiers of the RGBSDA color model.
+pub(crate) const R: u8 = 0;
+pub(crate) const G: u8 = 1;
+pub(crate) const B: u8 = 2;
+pub(crate) const S: u8 = 13;
+pub(crate) const D: u8 = 14;
+pub(crate) const A: u8 = 15;
+/// Channel identifiers of compressed color models.
+const COLOR: u8 = 0;
+const BC1_ALPHA_PRESENT: u8 = 1;
+const ETC2_COLOR: u8 = 2;
+const ASTC_DATA: u8 = 0;
+
+/// A pixel format's codes in each container.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct FormatEntry {
+    pub(crate) vk_format: u32,
+    /// Zero if DXGI has no such format.
+    pub(crate) dxgi_format: u32,
+    pub(crate) pixel_format: MTLPixelFormat,
+    /// The KTX2 `typeSize`: the size of the format's data type, for byte
+    /// swapping.
+    pub(crate) type_size: u32,
+    pub(crate) dfd: Dfd,
+}
+
+const fn entry(
+    vk_format: u32,
+    dxgi_format: u32,
+    pixel_format: MTLPixelFormat,
+    type_size: u32,
+    dfd: Dfd,
+) -> FormatEntry {
+    FormatEntry {
+        vk_format,
+        dxgi_format,
+        pixel_format,
+        type_size,
+        dfd,
+    }
+}
+
+#[rustfmt::skip]
+pub(crate) const FORMATS: &[FormatEntry] = &[
+    entry(9, 61, MTLPixelFormat::R8Unorm, 1, Dfd::Channels(&[R], ChannelKind::Unorm)),
+    entry(10, 63, MTLPixelFormat::R8Snorm, 1, Dfd::Channels(&[R], ChannelKind::Snorm)),
+    entry(13, 62, MTLPixelFormat::R8Uint, 1, Dfd::Channels(&[R], ChannelKind::Uint)),
+    entry(14, 64, MTLPixelFormat::R8Sint, 1, Dfd::Channels(&[R], ChannelKind::Sint)),
+    entry(15, 0, MTLPixelFormat::R8UnormSrgb, 1, Dfd::Channels(&[R], ChannelKind::UnormSrgb)),
+    entry(1000470001, 65, MTLPixelFormat::A8Unorm, 1, Dfd::Channels(&[A], ChannelKind::Unorm)),
+    entry(16, 49, MTLPixelFormat::RG8Unorm, 1, Dfd::Channels(&[R, G], ChannelKind::Unorm)),
+    entry(17, 51, MTLPixelFormat::RG8Snorm, 1, Dfd::Channels(&[R, G], ChannelKind::Snorm)),
+    entry(20, 50, MTLPixelFormat::RG8Uint, 1, Dfd::Channels(&[R, G], ChannelKind::Uint)),
+    entry(21, 52, MTLPixelFormat::RG8Sint, 1, Dfd::Channels(&[R, G], ChannelKind::Sint)),
+    entry(22, 0, MTLPixelFormat::RG8UnormSrgb, 1, Dfd::Channels(&[R, G], ChannelKind::UnormSrgb)),
+    entry(37, 28, MTLPixelFormat::RGBA8Unorm, 1, Dfd::Channels(&[R, G, B, A], ChannelKind::Unorm)),
+    entry(38, 31, MTLPixelFormat::RGBA8Snorm, 1, Dfd::Channels(&[R, G, B, A], ChannelKind::Snorm)),
+    entry(41, 30, MTLPixelFormat::RGBA8Uint, 1, Dfd::Channels(&[R, G, B, A], ChannelKind::Uint)),
+    entry(42, 32, MTLPixelFormat::RGBA8Sint, 1, Dfd::Channels(&[R, G, B, A], ChannelKind::Sint)),
+    entry(43, 29, MTLPixelFormat::RGBA8UnormSrgb, 1, Dfd::Channels(&[R, G, B, A], ChannelKind::UnormSrgb)),
+    entry(44, 87, MTLPixelFormat::BGRA8Unorm, 1, Dfd::Channels(&[B, G, R, A], ChannelKind::Unorm)),
+    entry(50, 91, MTLPixelFormat::BGRA8UnormSrgb, 1, Dfd::Channels(&[B, G, R, A], ChannelKind::UnormSrgb)),
+    entry(58, 0, MTLPixelFormat::BGR10A2Unorm, 4, Dfd::Packed(&[(B, 0, 10), (G, 10, 10), (R, 20, 10), (A, 30, 2)], ChannelKind::Unorm)),
+    entry(64, 24, MTLPixelFormat::RGB10A2Unorm, 4, Dfd::Packed(&[(R, 0, 10), (G, 10, 10), (B, 20, 10), (A, 30, 2)], ChannelKind::Unorm)),
+    entry(68, 25, MTLPixelFormat::RGB10A2Uint, 4, Dfd::Packed(&[(R, 0, 10), (G, 10, 10), (B, 20, 10), (A, 30, 2)], ChannelKind::Uint)),
+    entry(70, 56, MTLPixelFormat::R16Unorm, 2, Dfd::Channels(&[R], ChannelKind::Unorm)),
+    entry(71, 58, MTLPixelFormat::R16Snorm, 2, Dfd::Channels(&[R], ChannelKind::Snorm)),
+    entry(74, 57, MTLPixelFormat::R16Uint, 2, Dfd::Channels(&[R], ChannelKind::Uint)),
+    entry(75, 59, MTLPixelFormat::R16Sint, 2, Dfd::Channels(&[R], ChannelKind::Sint)),
+    entry(76, 54, MTLPixelFormat::R16Float, 2, Dfd::Channels(&[R], ChannelKind::Sfloat)),
+    entry(77, 35, MTLPixelFormat::RG16Unorm, 2, Dfd::Channels(&[R, G], ChannelKind::Unorm)),
+    entry(78, 37, MTLPixelFormat::RG16Snorm, 2, Dfd::Channels(&[R, G], ChannelKind::Snorm)),
+    entry(81, 36, MTLPixelFormat::RG16Uint, 2, Dfd::Channels(&[R, G], ChannelKind::Uint)),
+    entry(82, 38, MTLPixelFormat::RG16Sint, 2, Dfd::Channels(&[R, G], ChannelKind::Sint)),
+    entry(83, 34, MTLPixelFormat::RG16Float, 2, Dfd::Channels(&[R, G], ChannelKind::Sfloat)),
+    entry(91, 11, MTLPixelFormat::RGBA16Unorm, 2, Dfd::Channels(&[R, G, B, A], ChannelKind::Unorm)),
+    entry(92, 13, MTLPixelFormat::RGBA16Snorm, 2, Dfd::Channels(&[R, G, B, A], ChannelKind::Snorm)),
+    entry(95, 12, MTLPixelFormat::RGBA16Uint, 2, Dfd::Channels(&[R, G, B, A], ChannelKind::Uint)),
+    entry(96, 14, MTLPixelFormat::RGBA16Sint, 2, Dfd::Channels(&[R, G, B, A], ChannelKind::Sint)),
+    entry(97, 10, MTLPixelFormat::RGBA16Float, 2, Dfd::Channels(&[R, G, B, A], ChannelKind::Sfloat)),
+    entry(98, 42, MTLPixelFormat::R32Uint, 4, Dfd::Channels(&[R], ChannelKind::Uint)),
+    entry(99, 43, MTLPixelFormat::R32Sint, 4, Dfd::Channels(&[R], ChannelKind::Sint)),
+    entry(100, 41, MTLPixelFormat::R32Float, 4, Dfd::Channels(&[R], ChannelKind::Sfloat)),
+    entry(101, 17, MTLPixelFormat::RG32Uint, 4, Dfd::Channels(&[R, G], ChannelKind::Uint)),
+    entry(102, 18, MTLPixelFormat::RG32Sint, 4, Dfd::Channels(&[R, G], ChannelKind::Sint)),
+    entry(103, 16, MTLPixelFormat::RG32Float, 4, Dfd::Channels(&[R, G], ChannelKind::Sfloat)),
+    entry(107, 3, MTLPixelFormat::RGBA32Uint, 4, Dfd::Channels(&[R, G, B, A], ChannelKind::Uint)),
+    entry(108, 4, MTLPixelFormat::RGBA32Sint, 4, Dfd::Channels(&[R, G, B, A], ChannelKind::Sint)),
+    entry(109, 2, MTLPixelFormat::RGBA32Float, 4, Dfd::Channels(&[R, G, B, A], ChannelKind::Sfloat)),
+    entry(122, 26, MTLPixelFormat::RG11B10Float, 4, Dfd::Packed(&[(R, 0, 11), (G, 11, 11), (B, 22, 10)], ChannelKind::Ufloat)),
+    entry(123, 67, MTLPixelFormat::RGB9E5Float, 4, Dfd::Unsupported),
+    entry(124, 55, MTLPixelFormat::Depth16Unorm, 2, Dfd::Channels(&[D], ChannelKind::Unorm)),
+    entry(126, 40, MTLPixelFormat::Depth32Float, 4, Dfd::Channels(&[D], ChannelKind::Sfloat)),
+    entry(127, 0, MTLPixelFormat::Stencil8, 1, Dfd::Channels(&[S], ChannelKind::Uint)),
+    entry(129, 45, MTLPixelFormat::Depth24Unorm_Stencil8, 4, Dfd::Unsupported),
+    entry(133, 71, MTLPixelFormat::BC1_RGBA, 1, Dfd::Compressed(BC1A, &[(BC1_ALPHA_PRESENT, 0, 64)], ChannelKind::Unorm)),
+    entry(134, 72, MTLPixelFormat::BC1_RGBA_sRGB, 1, Dfd::Compressed(BC1A, &[(BC1_ALPHA_PRESENT, 0, 64)], ChannelKind::UnormSrgb)),
+    entry(135, 74, MTLPixelFormat::BC2_RGBA, 1, Dfd::Compressed(BC2, &[(A, 0, 64), (COLOR, 64, 64)], ChannelKind::Unorm)),
+    entry(136, 75, MTLPixelFormat::BC2_RGBA_sRGB, 1, Dfd::Compressed(BC2, &[(A, 0, 64), (COLOR, 64, 64)], ChannelKind::UnormSrgb)),
+    entry(137, 77, MTLPixelFormat::BC3_RGBA, 1, Dfd::Compressed(BC3, &[(A, 0, 64), (COLOR, 64, 64)], ChannelKind::Unorm)),
+    entry(138, 78, MTLPixelFormat::BC3_RGBA_sRGB, 1, Dfd::Compressed(BC3, &[(A, 0, 64), (COLOR, 64, 64)], ChannelKind::UnormSrgb)),
+    entry(139, 80, MTLPixelFormat::BC4_RUnorm, 1, Dfd::Compressed(BC4, &[(R, 0, 64)], ChannelKind::Unorm)),
+    entry(140, 81, MTLPixelFormat::BC4_RSnorm, 1, Dfd::Compressed(BC4, &[(R, 0, 64)], ChannelKind::Snorm)),
+    entry(141, 83, MTLPixelFormat::BC5_RGUnorm, 1, Dfd::Compressed(BC5, &[(R, 0, 64), (G, 64, 64)], ChannelKind::Unorm)),
+    entry(142, 84, MTLPixelFormat::BC5_RGSnorm, 1, Dfd::Compressed(BC5, &[(R, 0, 64), (G, 64, 64)], ChannelKind::Snorm)),
+    entry(143, 95, MTLPixelFormat::BC6H_RGBUfloat, 1, Dfd::Compressed(BC6H, &[(COLOR, 0, 128)], ChannelKind::Ufloat)),
+    entry(144, 96, MTLPixelFormat::BC6H_RGBFloat, 1, Dfd::Compressed(BC6H, &[(COLOR, 0, 128)], ChannelKind::Sfloat)),
+    entry(145, 98, MTLPixelFormat::BC7_RGBAUnorm, 1, Dfd::Compressed(BC7, &[(COLOR, 0, 128)], ChannelKind::Unorm)),
+    entry(146, 99, MTLPixelFormat::BC7_RGBAUnorm_sRGB, 1, Dfd::Compressed(BC7, &[(COLOR, 0, 128)], ChannelKind::UnormSrgb)),
+    entry(147, 0, MTLPixelFormat::ETC2_RGB8, 1, Dfd::Compressed(ETC2, &[(ETC2_COLOR, 0, 64)], ChannelKind::Unorm)),
+    entry(148, 0, MTLPixelFormat::ETC2_RGB8_sRGB, 1, Dfd::Compressed(ETC2, &[(ETC2_COLOR, 0, 64)], ChannelKind::UnormSrgb)),
+    entry(149, 0, MTLPixelFormat::ETC2_RGB8A1, 1, Dfd::Compressed(ETC2, &[(ETC2_COLOR, 0, 64)], ChannelKind::Unorm)),
+    entry(150, 0, MTLPixelFormat::ETC2_RGB8A1_sRGB, 1, Dfd::Compressed(ETC2, &[(ETC2_COLOR, 0, 64)], ChannelKind::UnormSrgb)),
+    entry(151, 0, MTLPixelFormat::EAC_RGBA8, 1, Dfd::Compressed(ETC2, &[(A, 0, 64), (ETC2_COLOR, 64, 64)], ChannelKind::Unorm)),
+    entry(152, 0, MTLPixelFormat::EAC_RGBA8_sRGB, 1, Dfd::Compressed(ETC2, &[(A, 0, 64), (ETC2_COLOR, 64, 64)], ChannelKind::UnormSrgb)),
+    entry(153, 0, MTLPixelFormat::EAC_R11Unorm, 1, Dfd::Compressed(ETC2, &[(R, 0, 64)], ChannelKind::Unorm)),
+    entry(154, 0, MTLPixelFormat::EAC_R11Snorm, 1, Dfd::Compressed(ETC2, &[(R, 0, 64)], ChannelKind::Snorm)),
+    entry(155, 0, MTLPixelFormat::EAC_RG11Unorm, 1, Dfd::Compressed(ETC2, &[(R, 0, 64), (G, 64, 64)], ChannelKind::Unorm)),
+    entry(156, 0, MTLPixelFormat::EAC_RG11Snorm, 1, Dfd::Compressed(ETC2, &[(R, 0, 64), (G, 64, 64)], ChannelKind::Snorm)),
+    entry(157, 134, MTLPixelFormat::ASTC_4x4_LDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Unorm)),
+    entry(158, 135, MTLPixelFormat::ASTC_4x4_sRGB, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::UnormSrgb)),
+    entry(159, 138, MTLPixelFormat::ASTC_5x4_LDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Unorm)),
+    entry(160, 139, MTLPixelFormat::ASTC_5x4_sRGB, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::UnormSrgb)),
+    entry(161, 142, MTLPixelFormat::ASTC_5x5_LDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Unorm)),
+    entry(162, 143, MTLPixelFormat::ASTC_5x5_sRGB, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::UnormSrgb)),
+    entry(163, 146, MTLPixelFormat::ASTC_6x5_LDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Unorm)),
+    entry(164, 147, MTLPixelFormat::ASTC_6x5_sRGB, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::UnormSrgb)),
+    entry(165, 150, MTLPixelFormat::ASTC_6x6_LDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Unorm)),
+    entry(166, 151, MTLPixelFormat::ASTC_6x6_sRGB, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::UnormSrgb)),
+    entry(167, 154, MTLPixelFormat::ASTC_8x5_LDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Unorm)),
+    entry(168, 155, MTLPixelFormat::ASTC_8x5_sRGB, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::UnormSrgb)),
+    entry(169, 158, MTLPixelFormat::ASTC_8x6_LDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Unorm)),
+    entry(170, 159, MTLPixelFormat::ASTC_8x6_sRGB, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::UnormSrgb)),
+    entry(171, 162, MTLPixelFormat::ASTC_8x8_LDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Unorm)),
+    entry(172, 163, MTLPixelFormat::ASTC_8x8_sRGB, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::UnormSrgb)),
+    entry(173, 166, MTLPixelFormat::ASTC_10x5_LDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Unorm)),
+    entry(174, 167, MTLPixelFormat::ASTC_10x5_sRGB, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::UnormSrgb)),
+    entry(175, 170, MTLPixelFormat::ASTC_10x6_LDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Unorm)),
+    entry(176, 171, MTLPixelFormat::ASTC_10x6_sRGB, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::UnormSrgb)),
+    entry(177, 174, MTLPixelFormat::ASTC_10x8_LDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Unorm)),
+    entry(178, 175, MTLPixelFormat::ASTC_10x8_sRGB, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::UnormSrgb)),
+    entry(179, 178, MTLPixelFormat::ASTC_10x10_LDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Unorm)),
+    entry(180, 179, MTLPixelFormat::ASTC_10x10_sRGB, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::UnormSrgb)),
+    entry(181, 182, MTLPixelFormat::ASTC_12x10_LDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Unorm)),
+    entry(182, 183, MTLPixelFormat::ASTC_12x10_sRGB, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::UnormSrgb)),
+    entry(183, 186, MTLPixelFormat::ASTC_12x12_LDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Unorm)),
+    entry(184, 187, MTLPixelFormat::ASTC_12x12_sRGB, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::UnormSrgb)),
+    entry(1000066000, 0, MTLPixelFormat::ASTC_4x4_HDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Sfloat)),
+    entry(1000066001, 0, MTLPixelFormat::ASTC_5x4_HDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Sfloat)),
+    entry(1000066002, 0, MTLPixelFormat::ASTC_5x5_HDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Sfloat)),
+    entry(1000066003, 0, MTLPixelFormat::ASTC_6x5_HDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Sfloat)),
+    entry(1000066004, 0, MTLPixelFormat::ASTC_6x6_HDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Sfloat)),
+    entry(1000066005, 0, MTLPixelFormat::ASTC_8x5_HDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Sfloat)),
+    entry(1000066006, 0, MTLPixelFormat::ASTC_8x6_HDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Sfloat)),
+    entry(1000066007, 0, MTLPixelFormat::ASTC_8x8_HDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Sfloat)),
+    entry(1000066008, 0, MTLPixelFormat::ASTC_10x5_HDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Sfloat)),
+    entry(1000066009, 0, MTLPixelFormat::ASTC_10x6_HDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Sfloat)),
+    entry(1000066010, 0, MTLPixelFormat::ASTC_10x8_HDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Sfloat)),
+    entry(1000066011, 0, MTLPixelFormat::ASTC_10x10_HDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Sfloat)),
+    entry(1000066012, 0, MTLPixelFormat::ASTC_12x10_HDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Sfloat)),
+    entry(1000066013, 0, MTLPixelFormat::ASTC_12x12_HDR, 1, Dfd::Compressed(ASTC, &[(ASTC_DATA, 0, 128)], ChannelKind::Sfloat)),
+];
+
+pub(crate) fn format_entry(pixel_format: MTLPixelFormat) -> Option<&'static FormatEntry> {
+    FORMATS.iter().find(|entry| entry.pixel_format == pixel_format)
+}
+
+/// Maps a KTX2 `vkFormat` to the pixel format with the same layout.
+///
+/// BC1 without alpha maps to `BC1_RGBA`, so its transparent-black texels read
+/// as transparent rather than opaque black.
+pub fn ktx2_pixel_format(vk_format: u32) -> Option<MTLPixelFormat> {
+    let vk_format = match vk_format {
+        131 => 133,
+        132 => 134,
+        vk_format => vk_format,
+    };
+    FORMATS.iter().find(|entry| entry.vk_format == vk_format).map(|entry| entry.pixel_format)
+}
+
+/// The KTX2 `vkFormat` of `pixel_format`.
+pub fn ktx2_vk_format(pixel_format: MTLPixelFormat) -> Option<u32> {
+    format_entry(pixel_format).map(|entry| entry.vk_format)
+}
+
+/// Maps a DDS `DXGI_FORMAT` to the pixel format with the same layout.
+pub fn dds_pixel_format(dxgi_format: u32) -> Option<MTLPixelFormat> {
+    if dxgi_format == 0 {
+        return None;
+    }
+    FORMATS.iter().find(|entry| entry.dxgi_format == dxgi_format).map(|entry| entry.pixel_format)
+}
+
+/// The DDS `DXGI_FORMAT` of `pixel_format`.
+pub fn dds_dxgi_format(pixel_format: MTLPixelFormat) -> Option<u32> {
+    format_entry(pixel_format).map(|entry| entry.dxgi_format).filter(|&format| format != 0)
+}
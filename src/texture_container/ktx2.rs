@@ -0,0 +1,312 @@
+use super::{
+    TextureContainer, TextureContainerError, TextureContainerFormat,
+    container::{ContainerImage, LevelLayout, full_mipmap_level_count, read_u32, read_u64, validate_target},
+    formats::{A, ChannelKind, Dfd, FormatEntry, format_entry, ktx2_pixel_format},
+};
+use crate::{MTLTextureType, TextureUploadTarget};
+
+pub(crate) const IDENTIFIER: &[u8] = &[0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n'];
+
+/// Identifier, nine format and size fields, and the DFD, KVD and SGD index.
+const HEADER_LEN: usize = 80;
+/// Byte offset, byte length and uncompressed byte length.
+const LEVEL_INDEX_ENTRY_LEN: usize = 24;
+/// Descriptor type, version and size, then the color model through the
+/// plane sizes.
+const DFD_BLOCK_HEADER_LEN: usize = 24;
+const DFD_SAMPLE_LEN: usize = 16;
+const DFD_VERSION: u32 = 2;
+
+const PRIMARIES_BT709: u8 = 1;
+const TRANSFER_LINEAR: u8 = 1;
+const TRANSFER_SRGB: u8 = 2;
+
+const QUALIFIER_LINEAR: u8 = 0x10;
+const QUALIFIER_SIGNED: u8 = 0x40;
+const QUALIFIER_FLOAT: u8 = 0x80;
+
+/// The RGBSDA color model of uncompressed formats.
+const MODEL_RGBSDA: u8 = 1;
+
+fn invalid(message: impl Into<String>) -> TextureContainerError {
+    TextureContainerError::InvalidHeader(message.into())
+}
+
+fn to_usize(value: u64) -> Result<usize, TextureContainerError> {
+    usize::try_from(value).map_err(|_| TextureContainerError::Truncated)
+}
+
+pub(crate) fn parse(bytes: Vec<u8>) -> Result<TextureContainer, TextureContainerError> {
+    if !bytes.starts_with(IDENTIFIER) {
+        return Err(invalid("missing KTX2 identifier"));
+    }
+    let field = |index: usize| read_u32(&bytes, IDENTIFIER.len() + 4 * index).map(|value| value as usize);
+    let vk_format = read_u32(&bytes, IDENTIFIER.len())?;
+    let width = field(2)?;
+    let height = field(3)?;
+    let depth = field(4)?;
+    let layer_count = field(5)?;
+    let face_count = field(6)?;
+    let level_count = field(7)?;
+    let supercompression = read_u32(&bytes, IDENTIFIER.len() + 32)?;
+    let kvd_offset = read_u32(&bytes, 56)? as usize;
+    let kvd_len = read_u32(&bytes, 60)? as usize;
+
+    if supercompression != 0 {
+        return Err(TextureContainerError::UnsupportedSupercompression(supercompression));
+    }
+    let pixel_format = ktx2_pixel_format(vk_format)
+        .ok_or_else(|| TextureContainerError::UnsupportedFormat(format!("vkFormat {vk_format}")))?;
+    let cube = match face_count {
+        1 => false,
+        6 => true,
+        faces => return Err(invalid(format!("{faces} faces"))),
+    };
+    let texture_type = match (height, depth, cube, layer_count > 0) {
+        (0, 0, false, false) => MTLTextureType::Type1D,
+        (0, 0, false, true) => MTLTextureType::Type1DArray,
+        (0, ..) => return Err(invalid("1D textures can't be cubes or have depth")),
+        (_, 0, false, false) => MTLTextureType::Type2D,
+        (_, 0, false, true) => MTLTextureType::Type2DArray,
+        (_, 0, true, false) => MTLTextureType::Cube,
+        (_, 0, true, true) => MTLTextureType::CubeArray,
+        (_, _, false, false) => MTLTextureType::Type3D,
+        _ => return Err(invalid("3D textures can't be cubes or arrays")),
+    };
+
+    let generate_mipmaps = level_count == 0;
+    let mut target = TextureUploadTarget {
+        texture_type,
+        pixel_format,
+        width,
+        height: height.max(1),
+        depth: depth.max(1),
+        mipmap_level_count: level_count.max(1),
+        array_length: layer_count.max(1),
+    };
+    if generate_mipmaps {
+        if pixel_format.is_compressed() {
+            return Err(invalid("block-compressed textures can't generate mipmaps"));
+        }
+        target.mipmap_level_count = full_mipmap_level_count(&target);
+    }
+    validate_target(&target)?;
+
+    let stored_levels = level_count.max(1);
+    let mut images = Vec::new();
+    for level in 0..stored_levels {
+        let entry = HEADER_LEN + LEVEL_INDEX_ENTRY_LEN * level;
+        let offset = to_usize(read_u64(&bytes, entry)?)?;
+        let length = to_usize(read_u64(&bytes, entry + 8)?)?;
+        let layout = LevelLayout::new(&target, level)?;
+        let expected = layout.level_len()?;
+        if length != expected {
+            return Err(TextureContainerError::DataLengthMismatch {
+                expected,
+                actual: length,
+            });
+        }
+        if offset.checked_add(length).is_none_or(|end| end > bytes.len()) {
+            return Err(TextureContainerError::Truncated);
+        }
+        // Images are ordered by layer, then face; 3D levels are one image.
+        for slice in 0..layout.image_count {
+            images.push(ContainerImage::new(level, slice, &layout, offset + slice * layout.image_len));
+        }
+    }
+
+    let key_values = match kvd_len {
+        0 => Vec::new(),
+        _ => {
+            let end = kvd_offset.checked_add(kvd_len).ok_or(TextureContainerError::Truncated)?;
+            parse_key_values(bytes.get(kvd_offset..end).ok_or(TextureContainerError::Truncated)?)?
+        },
+    };
+
+    Ok(TextureContainer::from_parts(
+        Some(TextureContainerFormat::Ktx2),
+        target,
+        generate_mipmaps,
+        key_values,
+        images,
+        bytes,
+    ))
+}
+
+fn parse_key_values(data: &[u8]) -> Result<Vec<(String, Vec<u8>)>, TextureContainerError> {
+    let mut key_values = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        let len = read_u32(data, offset)? as usize;
+        let pair = data.get(offset + 4..offset + 4 + len).ok_or(TextureContainerError::Truncated)?;
+        let nul = pair.iter().position(|&byte| byte == 0).ok_or_else(|| invalid("key without a NUL terminator"))?;
+        let key = core::str::from_utf8(&pair[..nul]).map_err(|_| invalid("key isn't UTF-8"))?;
+        key_values.push((key.to_owned(), pair[nul + 1..].to_vec()));
+        offset = (offset + 4 + len).next_multiple_of(4);
+    }
+    Ok(key_values)
+}
+
+pub(crate) fn write(container: &TextureContainer) -> Result<Vec<u8>, TextureContainerError> {
+    let target = container.target();
+    let entry = format_entry(target.pixel_format)
+        .filter(|entry| !matches!(entry.dfd, Dfd::Unsupported))
+        .ok_or(TextureContainerError::UnsupportedPixelFormat(target.pixel_format))?;
+    let layers = target.array_length as u32;
+    let (height, depth, layer_count, face_count) = match target.texture_type {
+        MTLTextureType::Type1D => (0, 0, 0, 1),
+        MTLTextureType::Type1DArray => (0, 0, layers, 1),
+        MTLTextureType::Type2D => (target.height as u32, 0, 0, 1),
+        MTLTextureType::Type2DArray => (target.height as u32, 0, layers, 1),
+        MTLTextureType::Cube => (target.height as u32, 0, 0, 6),
+        MTLTextureType::CubeArray => (target.height as u32, 0, layers, 6),
+        MTLTextureType::Type3D => (target.height as u32, target.depth as u32, 0, 1),
+        texture_type => return Err(invalid(format!("{texture_type:?} textures can't be stored in containers"))),
+    };
+    let (stored_levels, level_count) = if container.generates_mipmaps() {
+        (1, 0)
+    } else {
+        (target.mipmap_level_count, target.mipmap_level_count as u32)
+    };
+
+    let dfd = data_format_descriptor(entry);
+    let mut key_values: Vec<_> = container.key_values().iter().collect();
+    key_values.sort_by(|(a, _), (b, _)| a.as_bytes().cmp(b.as_bytes()));
+    let mut kvd = Vec::new();
+    for (key, value) in key_values {
+        kvd.extend_from_slice(&((key.len() + 1 + value.len()) as u32).to_le_bytes());
+        kvd.extend_from_slice(key.as_bytes());
+        kvd.push(0);
+        kvd.extend_from_slice(value);
+        kvd.resize(kvd.len().next_multiple_of(4), 0);
+    }
+
+    let dfd_offset = HEADER_LEN + LEVEL_INDEX_ENTRY_LEN * stored_levels;
+    let kvd_offset = dfd_offset + dfd.len();
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(IDENTIFIER);
+    for value in [
+        entry.vk_format,
+        entry.type_size,
+        target.width as u32,
+        height,
+        depth,
+        layer_count,
+        face_count,
+        level_count,
+        0,
+        dfd_offset as u32,
+        dfd.len() as u32,
+        if kvd.is_empty() {
+            0
+        } else {
+            kvd_offset as u32
+        },
+        kvd.len() as u32,
+    ] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    // No supercompression global data.
+    bytes.extend_from_slice(&[0; 16]);
+    bytes.resize(dfd_offset, 0);
+    bytes.extend_from_slice(&dfd);
+    bytes.extend_from_slice(&kvd);
+
+    // Levels are stored smallest first, each aligned to the least common
+    // multiple of the block size and 4; block sizes are powers of two.
+    let alignment = target.pixel_format.bytes_per_block().unwrap_or(1).max(4);
+    for level in (0..stored_levels).rev() {
+        let start = bytes.len().next_multiple_of(alignment);
+        bytes.resize(start, 0);
+        for image in container.images().iter().filter(|image| image.level == level) {
+            bytes.extend_from_slice(container.image_data(image));
+        }
+        let length = (bytes.len() - start) as u64;
+        let expected = LevelLayout::new(target, level)?.level_len()?;
+        if length != expected as u64 {
+            return Err(TextureContainerError::DataLengthMismatch {
+                expected,
+                actual: length as usize,
+            });
+        }
+        let index = HEADER_LEN + LEVEL_INDEX_ENTRY_LEN * level;
+        bytes[index..index + 8].copy_from_slice(&(start as u64).to_le_bytes());
+        bytes[index + 8..index + 16].copy_from_slice(&length.to_le_bytes());
+        bytes[index + 16..index + 24].copy_from_slice(&length.to_le_bytes());
+    }
+    Ok(bytes)
+}
+
+/// A data format descriptor holding one basic descriptor block.
+fn data_format_descriptor(entry: &FormatEntry) -> Vec<u8> {
+    let pixel_format = entry.pixel_format;
+    let bytes_per_block = pixel_format.bytes_per_block().unwrap_or(0);
+    let (block_width, block_height) = pixel_format.block_dimensions();
+
+    let mut samples: Vec<(u8, u16, u8)> = Vec::new();
+    let (model, kind) = match entry.dfd {
+        Dfd::Channels(channels, kind) => {
+            let bits = (bytes_per_block / channels.len() * 8) as u8;
+            samples.extend(channels.iter().enumerate().map(|(i, &channel)| (channel, i as u16 * bits as u16, bits)));
+            (MODEL_RGBSDA, kind)
+        },
+        Dfd::Packed(channels, kind) => {
+            samples.extend_from_slice(channels);
+            (MODEL_RGBSDA, kind)
+        },
+        Dfd::Compressed(model, channels, kind) => {
+            samples.extend_from_slice(channels);
+            (model, kind)
+        },
+        Dfd::Unsupported => unreachable!("formats without a descriptor aren't written"),
+    };
+    let transfer = match kind {
+        ChannelKind::UnormSrgb => TRANSFER_SRGB,
+        _ => TRANSFER_LINEAR,
+    };
+
+    let block_len = DFD_BLOCK_HEADER_LEN + DFD_SAMPLE_LEN * samples.len();
+    let mut dfd = Vec::with_capacity(4 + block_len);
+    dfd.extend_from_slice(&((4 + block_len) as u32).to_le_bytes());
+    // Khronos vendor, basic descriptor type.
+    dfd.extend_from_slice(&0u32.to_le_bytes());
+    dfd.extend_from_slice(&(DFD_VERSION | (block_len as u32) << 16).to_le_bytes());
+    // Straight alpha.
+    dfd.extend_from_slice(&[model, PRIMARIES_BT709, transfer, 0]);
+    dfd.extend_from_slice(&[block_width as u8 - 1, block_height as u8 - 1, 0, 0]);
+    dfd.extend_from_slice(&[bytes_per_block as u8, 0, 0, 0, 0, 0, 0, 0]);
+    for (channel, bit_offset, bit_length) in samples {
+        let (qualifiers, lower, upper) = sample_range(kind, bit_length);
+        // sRGB formats store alpha linearly.
+        let qualifiers = match (kind, channel) {
+            (ChannelKind::UnormSrgb, A) if model == MODEL_RGBSDA => qualifiers | QUALIFIER_LINEAR,
+            _ => qualifiers,
+        };
+        dfd.extend_from_slice(&bit_offset.to_le_bytes());
+        dfd.extend_from_slice(&[bit_length - 1, channel | qualifiers]);
+        dfd.extend_from_slice(&[0; 4]);
+        dfd.extend_from_slice(&lower.to_le_bytes());
+        dfd.extend_from_slice(&upper.to_le_bytes());
+    }
+    dfd
+}
+
+/// The qualifiers and lower and upper sample values of a channel. Samples of
+/// 32 bits or more, including compressed ones, use the full 32-bit range.
+fn sample_range(
+    kind: ChannelKind,
+    bits: u8,
+) -> (u8, u32, u32) {
+    let bits = u32::from(bits.min(32));
+    match kind {
+        ChannelKind::Unorm | ChannelKind::UnormSrgb => (0, 0, u32::MAX >> (32 - bits)),
+        ChannelKind::Snorm => {
+            let max = i32::MAX >> (32 - bits);
+            (QUALIFIER_SIGNED, (-max) as u32, max as u32)
+        },
+        ChannelKind::Uint => (0, 0, 1),
+        ChannelKind::Sint => (QUALIFIER_SIGNED, -1i32 as u32, 1),
+        ChannelKind::Ufloat => (QUALIFIER_FLOAT, 0.0f32.to_bits(), 1.0f32.to_bits()),
+        ChannelKind::Sfloat => (QUALIFIER_FLOAT | QUALIFIER_SIGNED, (-1.0f32).to_bits(), 1.0f32.to_bits()),
+    }
+}
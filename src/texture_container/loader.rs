@@ -0,0 +1,94 @@
+use core::slice;
+
+use objc2::{rc::Retained, runtime::ProtocolObject};
+
+use super::{TextureContainer, TextureContainerError};
+use crate::{
+    MTLBlitCommandEncoder, MTLBuffer, MTLCommandBuffer, MTLCommandBufferStatus, MTLCommandEncoder, MTLCommandQueue,
+    MTLDevice, MTLDeviceExt, MTLResourceOptions, MTLStorageMode, MTLTexture, MTLTextureDescriptor, MTLTextureUsage,
+    staging_buffer::new_staging_buffer,
+};
+
+impl TextureContainer {
+    /// A descriptor for a shader-read texture of the container's type, format,
+    /// size and mipmap levels, including generated ones.
+    pub fn texture_descriptor(&self) -> Retained<MTLTextureDescriptor> {
+        let target = self.target();
+        let descriptor = MTLTextureDescriptor::new();
+        descriptor.set_texture_type(target.texture_type);
+        descriptor.set_pixel_format(target.pixel_format);
+        descriptor.set_width(target.width);
+        descriptor.set_height(target.height);
+        descriptor.set_depth(target.depth);
+        descriptor.set_mipmap_level_count(target.mipmap_level_count);
+        descriptor.set_array_length(target.array_length);
+        descriptor.set_usage(MTLTextureUsage::SHADER_READ);
+        descriptor
+    }
+
+    /// Copies the images into a new staging buffer and encodes their copies
+    /// into `texture`, then mipmap generation if the container asks for it.
+    ///
+    /// Returns the staging buffer, which must outlive the encoder's command
+    /// buffer unless that retains its references.
+    pub fn encode_upload(
+        &self,
+        device: &ProtocolObject<dyn MTLDevice>,
+        encoder: &ProtocolObject<dyn MTLBlitCommandEncoder>,
+        texture: &ProtocolObject<dyn MTLTexture>,
+    ) -> Result<Retained<ProtocolObject<dyn MTLBuffer>>, TextureContainerError> {
+        let (copies, staging_len) = self.staging_copies();
+        let staging = new_staging_buffer(
+            device,
+            staging_len,
+            MTLResourceOptions::STORAGE_MODE_SHARED | MTLResourceOptions::CPU_CACHE_MODE_WRITE_COMBINED,
+        )
+        .ok_or(TextureContainerError::ResourceCreation)?;
+        let contents = unsafe { slice::from_raw_parts_mut(staging.contents().as_ptr().cast::<u8>(), staging_len) };
+        self.write_staging(contents)?;
+
+        for copy in &copies {
+            encoder.copy_from_buffer_to_texture(
+                &staging,
+                copy.offset,
+                copy.bytes_per_row,
+                copy.bytes_per_image,
+                copy.size,
+                texture,
+                copy.slice,
+                copy.level,
+                copy.origin,
+            );
+        }
+        if self.generates_mipmaps() && texture.mipmap_level_count() > 1 {
+            encoder.generate_mipmaps_for_texture(texture);
+        }
+        Ok(staging)
+    }
+
+    /// Creates a private texture from [`texture_descriptor`](Self::texture_descriptor),
+    /// uploads the images on a new command buffer from `command_queue` and
+    /// waits for it to complete.
+    pub fn new_texture(
+        &self,
+        device: &ProtocolObject<dyn MTLDevice>,
+        command_queue: &ProtocolObject<dyn MTLCommandQueue>,
+    ) -> Result<Retained<ProtocolObject<dyn MTLTexture>>, TextureContainerError> {
+        let descriptor = self.texture_descriptor();
+        descriptor.set_storage_mode(MTLStorageMode::Private);
+        let texture = device.new_texture_with_descriptor(&descriptor).ok_or(TextureContainerError::ResourceCreation)?;
+
+        let command_buffer = command_queue.command_buffer().ok_or(TextureContainerError::ResourceCreation)?;
+        let encoder = command_buffer.blit_command_encoder().ok_or(TextureContainerError::ResourceCreation)?;
+        let result = self.encode_upload(device, &encoder, &texture);
+        encoder.end_encoding();
+        let _staging = result?;
+
+        command_buffer.commit();
+        command_buffer.wait_until_completed();
+        match command_buffer.status() {
+            MTLCommandBufferStatus::Error => Err(TextureContainerError::CommandBuffer(command_buffer.error())),
+            _ => Ok(texture),
+        }
+    }
+}
@@ -0,0 +1,8 @@
+mod container;
+mod dds;
+mod formats;
+mod ktx2;
+mod loader;
+
+pub use container::{ContainerImage, TextureContainer, TextureContainerError, TextureContainerFormat};
+pub use formats::{dds_dxgi_format, dds_pixel_format, ktx2_pixel_format, ktx2_vk_format};
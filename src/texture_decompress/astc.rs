@@ -0,0 +1,830 @@
+//! ASTC blocks of every 2D footprint, in the LDR and HDR profiles.
+
+use crate::Float16;
+
+/// How an ASTC format's decoded values are interpreted.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum AstcProfile {
+    Ldr,
+    /// LDR with 8-bit sRGB-encoded results.
+    LdrSrgb,
+    Hdr,
+}
+
+/// The color of blocks that are malformed or that the profile can't decode.
+const ERROR_COLOR: [f32; 4] = [1.0, 0.0, 1.0, 1.0];
+
+/// Decodes a 16-byte block into the first `block_width * block_height`
+/// texels, row by row.
+pub(crate) fn decode_astc(
+    block: &[u8],
+    block_width: usize,
+    block_height: usize,
+    profile: AstcProfile,
+    texels: &mut [[f32; 4]],
+) {
+    let texels = &mut texels[..block_width * block_height];
+    let bits = u128::from_le_bytes(block[..16].try_into().unwrap());
+    if decode(bits, block_width, block_height, profile, texels).is_none() {
+        texels.fill(ERROR_COLOR);
+    }
+}
+
+fn field(
+    bits: u128,
+    shift: usize,
+    len: usize,
+) -> u32 {
+    ((bits >> shift) & ((1 << len) - 1)) as u32
+}
+
+/// Decodes a block, or returns `None` if it's an error block.
+fn decode(
+    bits: u128,
+    block_width: usize,
+    block_height: usize,
+    profile: AstcProfile,
+    texels: &mut [[f32; 4]],
+) -> Option<()> {
+    if field(bits, 0, 9) == 0x1FC {
+        return decode_void_extent(bits, profile, texels);
+    }
+
+    let mode = BlockMode::new(field(bits, 0, 11))?;
+    let planes = 1 + mode.dual_plane as usize;
+    let weight_count = mode.width * mode.height * planes;
+    if mode.width > block_width || mode.height > block_height || weight_count > 64 {
+        return None;
+    }
+    let weight_bits = ise_bit_count(weight_count, mode.weight_levels);
+    if !(24..=96).contains(&weight_bits) {
+        return None;
+    }
+
+    let partition_count = field(bits, 11, 2) as usize + 1;
+    if mode.dual_plane && partition_count == 4 {
+        return None;
+    }
+    let mut modes = [0u32; 4];
+    let (partition_index, color_start, extra_bits) = if partition_count == 1 {
+        modes[0] = field(bits, 13, 4);
+        (0, 17, 0)
+    } else {
+        let selector = field(bits, 23, 2);
+        if selector == 0 {
+            modes.fill(field(bits, 25, 4));
+            (field(bits, 13, 10), 29, 0)
+        } else {
+            // Partitions choose between two adjacent classes of modes, with
+            // the bits that don't fit stored below the weights.
+            let extra_bits = 3 * partition_count - 4;
+            let extra = field(bits, 128 - weight_bits - extra_bits, extra_bits);
+            let value = field(bits, 25, 4) | extra << 4;
+            for (partition, mode) in modes[..partition_count].iter_mut().enumerate() {
+                let class = selector - 1 + ((value >> partition) & 1);
+                *mode = class << 2 | ((value >> (partition_count + 2 * partition)) & 3);
+            }
+            (field(bits, 13, 10), 29, extra_bits)
+        }
+    };
+    let color_end = (128 - weight_bits - extra_bits).checked_sub(2 * mode.dual_plane as usize)?;
+    let plane_channel = mode.dual_plane.then(|| field(bits, color_end, 2) as usize);
+
+    let modes = &modes[..partition_count];
+    let value_count = modes.iter().map(|&mode| 2 * ((mode >> 2) as usize + 1)).sum::<usize>();
+    if value_count > 18 {
+        return None;
+    }
+    let color_levels = color_levels(value_count, color_end.checked_sub(color_start)?)?;
+    let mut values = [0u32; 18];
+    decode_ise(bits >> color_start, color_levels, &mut values[..value_count]);
+    for value in &mut values[..value_count] {
+        *value = unquantize_color(*value, color_levels);
+    }
+    let mut endpoints = [Endpoints::default(); 4];
+    let mut offset = 0;
+    for (endpoints, &mode) in endpoints.iter_mut().zip(modes) {
+        let count = 2 * ((mode >> 2) as usize + 1);
+        *endpoints = Endpoints::new(mode, &values[offset..offset + count], profile)?;
+        offset += count;
+    }
+
+    // Weights are stored bit-reversed from the top of the block.
+    let mut weights = [0u32; 64];
+    decode_ise(bits.reverse_bits() & ((1 << weight_bits) - 1), mode.weight_levels, &mut weights[..weight_count]);
+    for weight in &mut weights[..weight_count] {
+        *weight = unquantize_weight(*weight, mode.weight_levels);
+    }
+
+    let texel_count = block_width * block_height;
+    let scale = |extent: usize| (1024 + extent / 2) / (extent - 1);
+    let (scale_s, scale_t) = (scale(block_width), scale(block_height));
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let (x, y) = (i % block_width, i / block_width);
+        let partition = match partition_count {
+            1 => 0,
+            _ => select_partition(partition_index, x, y, partition_count, texel_count < 31),
+        };
+        let endpoints = &endpoints[partition];
+        // Bilinearly infill the weight grid at the texel.
+        let grid_s = (scale_s * x * (mode.width - 1) + 32) >> 6;
+        let grid_t = (scale_t * y * (mode.height - 1) + 32) >> 6;
+        let (fraction_s, fraction_t) = (grid_s & 0xF, grid_t & 0xF);
+        let origin = (grid_s >> 4) + (grid_t >> 4) * mode.width;
+        let w11 = (fraction_s * fraction_t + 8) >> 4;
+        let factors = [16 + w11 - fraction_s - fraction_t, fraction_s - w11, fraction_t - w11, w11];
+        let grid_weight = |plane: usize| {
+            let neighbors = [origin, origin + 1, origin + mode.width, origin + mode.width + 1];
+            let sum = neighbors
+                .iter()
+                .zip(factors)
+                .filter(|&(_, factor)| factor > 0)
+                .map(|(&index, factor)| weights[index * planes + plane] * factor as u32)
+                .sum::<u32>();
+            (sum + 8) >> 4
+        };
+        let (weight0, weight1) = (grid_weight(0), plane_channel.map(|_| grid_weight(1)));
+        for (channel, value) in texel.iter_mut().enumerate() {
+            let weight = match weight1 {
+                Some(weight1) if plane_channel == Some(channel) => weight1,
+                _ => weight0,
+            };
+            let [low, high] = endpoints.colors.map(|color| color[channel]);
+            let color = (low * (64 - weight) + high * weight + 32) >> 6;
+            *value = match profile {
+                AstcProfile::LdrSrgb => (color >> 8) as f32 / 255.0,
+                AstcProfile::Hdr if endpoints.hdr[channel] => lns_to_float16(color).to_f32(),
+                _ => color as f32 / 65535.0,
+            };
+        }
+    }
+    Some(())
+}
+
+/// Decodes a void-extent block, a single color across the block.
+fn decode_void_extent(
+    bits: u128,
+    profile: AstcProfile,
+    texels: &mut [[f32; 4]],
+) -> Option<()> {
+    let hdr = field(bits, 9, 1) == 1;
+    if field(bits, 10, 2) != 3 || (hdr && profile != AstcProfile::Hdr) {
+        return None;
+    }
+    // Extents are optional, but must be ordered when present.
+    let extent = |i: usize| field(bits, 12 + 13 * i, 13);
+    let all_ones = (0..4).all(|i| extent(i) == 0x1FFF);
+    if !all_ones && (extent(0) >= extent(1) || extent(2) >= extent(3)) {
+        return None;
+    }
+    let color: [f32; 4] = core::array::from_fn(|channel| {
+        let value = field(bits, 64 + 16 * channel, 16);
+        match profile {
+            _ if hdr => Float16::from_bits(value as u16).to_f32(),
+            AstcProfile::LdrSrgb => (value >> 8) as f32 / 255.0,
+            _ => value as f32 / 65535.0,
+        }
+    });
+    texels.fill(color);
+    Some(())
+}
+
+/// The weight grid a block mode describes.
+struct BlockMode {
+    width: usize,
+    height: usize,
+    dual_plane: bool,
+    weight_levels: u32,
+}
+
+impl BlockMode {
+    fn new(mode: u32) -> Option<Self> {
+        let bit = |i: u32| (mode >> i) & 1;
+        let (a, b) = ((mode >> 5) & 3, (mode >> 7) & 3);
+        let mut dual_plane = bit(10) == 1;
+        let mut high_precision = bit(9) == 1;
+        let (range, width, height) = if mode & 3 != 0 {
+            let (width, height) = match (mode >> 2) & 3 {
+                0 => (b + 4, a + 2),
+                1 => (b + 8, a + 2),
+                2 => (a + 2, b + 8),
+                _ if bit(8) == 0 => (a + 2, bit(7) + 6),
+                _ => (bit(7) + 2, a + 2),
+            };
+            (bit(4) | (mode & 3) << 1, width, height)
+        } else {
+            let range = bit(4) | ((mode >> 2) & 3) << 1;
+            let (width, height) = match b {
+                0 => (12, a + 2),
+                1 => (a + 2, 12),
+                2 => {
+                    dual_plane = false;
+                    high_precision = false;
+                    (a + 6, ((mode >> 9) & 3) + 6)
+                },
+                _ => match a {
+                    0 => (6, 10),
+                    1 => (10, 6),
+                    _ => return None,
+                },
+            };
+            (range, width, height)
+        };
+        if range < 2 {
+            return None;
+        }
+        let levels = if high_precision {
+            [10, 12, 16, 20, 24, 32]
+        } else {
+            [2, 3, 4, 5, 6, 8]
+        };
+        Some(Self {
+            width: width as usize,
+            height: height as usize,
+            dual_plane,
+            weight_levels: levels[range as usize - 2],
+        })
+    }
+}
+
+/// Splits a range of `levels` values into its low bits and a trit (3) or
+/// quint (5) above them, or neither (1).
+fn ise_parts(levels: u32) -> (u32, u32) {
+    let base = if levels.is_multiple_of(3) {
+        3
+    } else if levels.is_multiple_of(5) {
+        5
+    } else {
+        1
+    };
+    ((levels / base).trailing_zeros(), base)
+}
+
+/// Bits taken by `count` integers of `levels` values.
+fn ise_bit_count(
+    count: usize,
+    levels: u32,
+) -> usize {
+    let (bits, base) = ise_parts(levels);
+    let packed = match base {
+        3 => (8 * count).div_ceil(5),
+        5 => (7 * count).div_ceil(3),
+        _ => 0,
+    };
+    count * bits as usize + packed
+}
+
+/// The finest color endpoint quantization whose `count` values fit in
+/// `available` bits.
+fn color_levels(
+    count: usize,
+    available: usize,
+) -> Option<u32> {
+    const LEVELS: [u32; 17] = [256, 192, 160, 128, 96, 80, 64, 48, 40, 32, 24, 20, 16, 12, 10, 8, 6];
+    LEVELS.into_iter().find(|&levels| ise_bit_count(count, levels) <= available)
+}
+
+/// Decodes an integer sequence of `levels` values from the low bits of
+/// `bits`, which must be zero past the sequence.
+fn decode_ise(
+    bits: u128,
+    levels: u32,
+    values: &mut [u32],
+) {
+    let (bit_count, base) = ise_parts(levels);
+    let mut position = 0;
+    let mut read = |len: u32| {
+        let value = field(bits.checked_shr(position).unwrap_or(0), 0, len as usize);
+        position += len;
+        value
+    };
+    match base {
+        3 => {
+            for group in values.chunks_mut(5) {
+                let mut low = [0; 5];
+                let mut packed = 0;
+                for (i, len) in [2, 2, 1, 2, 1].into_iter().enumerate() {
+                    low[i] = read(bit_count);
+                    packed |= read(len) << [0, 2, 4, 5, 7][i];
+                }
+                for (value, (trit, low)) in group.iter_mut().zip(decode_trits(packed).into_iter().zip(low)) {
+                    *value = trit << bit_count | low;
+                }
+            }
+        },
+        5 => {
+            for group in values.chunks_mut(3) {
+                let mut low = [0; 3];
+                let mut packed = 0;
+                for (i, len) in [3, 2, 2].into_iter().enumerate() {
+                    low[i] = read(bit_count);
+                    packed |= read(len) << [0, 3, 5][i];
+                }
+                for (value, (quint, low)) in group.iter_mut().zip(decode_quints(packed).into_iter().zip(low)) {
+                    *value = quint << bit_count | low;
+                }
+            }
+        },
+        _ => values.iter_mut().for_each(|value| *value = read(bit_count)),
+    }
+}
+
+/// Unpacks five trits from 8 bits.
+fn decode_trits(packed: u32) -> [u32; 5] {
+    let bit = |value: u32, i: u32| (value >> i) & 1;
+    let (c, t3, t4) = if (packed >> 2) & 7 == 7 {
+        ((packed >> 5) << 2 | (packed & 3), 2, 2)
+    } else if (packed >> 5) & 3 == 3 {
+        (packed & 0x1F, bit(packed, 7), 2)
+    } else {
+        (packed & 0x1F, (packed >> 5) & 3, bit(packed, 7))
+    };
+    let (t0, t1, t2) = if c & 3 == 3 {
+        (bit(c, 3) << 1 | (bit(c, 2) & !bit(c, 3) & 1), bit(c, 4), 2)
+    } else if (c >> 2) & 3 == 3 {
+        (c & 3, 2, 2)
+    } else {
+        (bit(c, 1) << 1 | (bit(c, 0) & !bit(c, 1) & 1), (c >> 2) & 3, bit(c, 4))
+    };
+    [t0, t1, t2, t3, t4]
+}
+
+/// Unpacks three quints from 7 bits.
+fn decode_quints(packed: u32) -> [u32; 3] {
+    let bit = |i: u32| (packed >> i) & 1;
+    if (packed >> 1) & 3 == 3 && (packed >> 5) & 3 == 0 {
+        let q2 = bit(0) << 2 | (bit(4) & !bit(0) & 1) << 1 | (bit(3) & !bit(0) & 1);
+        return [4, 4, q2];
+    }
+    let (c, q2) = if (packed >> 1) & 3 == 3 {
+        (((packed >> 3) & 3) << 3 | (!(packed >> 5) & 3) << 1 | bit(0), 4)
+    } else {
+        (packed & 0x1F, (packed >> 5) & 3)
+    };
+    let (q0, q1) = if c & 7 == 5 {
+        ((c >> 3) & 3, 4)
+    } else {
+        (c & 7, (c >> 3) & 3)
+    };
+    [q0, q1, q2]
+}
+
+/// Widens `bits` bits to `to` bits by repeating them.
+fn replicate(
+    value: u32,
+    bits: u32,
+    to: u32,
+) -> u32 {
+    let mut result = 0;
+    let mut filled = 0;
+    while filled < to {
+        let shift = to as i32 - filled as i32 - bits as i32;
+        result |= if shift >= 0 {
+            value << shift
+        } else {
+            value >> -shift
+        };
+        filled += bits;
+    }
+    result
+}
+
+/// Maps a trit or quint and its low bits onto a range, as the color and
+/// weight unquantization tables do: `bit_layout` ORs each low bit, from bit 1
+/// up, into the positions it lists.
+fn unquantize_packed(
+    value: u32,
+    bits: u32,
+    multiplier: u32,
+    bit_layout: &[&[u32]],
+    top_bit: u32,
+) -> u32 {
+    let low = value & ((1 << bits) - 1);
+    let mask = if low & 1 == 1 {
+        (top_bit << 2) - 1
+    } else {
+        0
+    };
+    let offset = bit_layout
+        .iter()
+        .enumerate()
+        .flat_map(|(i, positions)| positions.iter().map(move |&position| ((low >> (i + 1)) & 1) << position))
+        .fold(0, |offset, bit| offset | bit);
+    let scaled = ((value >> bits) * multiplier + offset) ^ mask;
+    (mask & top_bit) | scaled >> 2
+}
+
+/// Unquantizes a color endpoint value to 8 bits.
+fn unquantize_color(
+    value: u32,
+    levels: u32,
+) -> u32 {
+    let (bits, base) = ise_parts(levels);
+    let (multiplier, layout): (u32, &[&[u32]]) = match (base, bits) {
+        (1, _) => return replicate(value, bits, 8),
+        (3, 1) => (204, &[]),
+        (3, 2) => (93, &[&[8, 4, 2, 1]]),
+        (3, 3) => (44, &[&[7, 2, 0], &[8, 3, 1]]),
+        (3, 4) => (22, &[&[6, 0], &[7, 1], &[8, 2]]),
+        (3, 5) => (11, &[&[5], &[6], &[7, 0], &[8, 1]]),
+        (3, _) => (5, &[&[4], &[5], &[6], &[7], &[8, 0]]),
+        (_, 1) => (113, &[]),
+        (_, 2) => (54, &[&[8, 3, 2]]),
+        (_, 3) => (26, &[&[7, 1], &[8, 2, 0]]),
+        (_, 4) => (13, &[&[6], &[7, 0], &[8, 1]]),
+        (_, _) => (6, &[&[5], &[6], &[7], &[8, 0]]),
+    };
+    unquantize_packed(value, bits, multiplier, layout, 0x80)
+}
+
+/// Unquantizes a weight to `0..=64`.
+fn unquantize_weight(
+    value: u32,
+    levels: u32,
+) -> u32 {
+    let (bits, base) = ise_parts(levels);
+    let weight = match (base, bits) {
+        (1, _) => replicate(value, bits, 6),
+        (3, 0) => [0, 32, 63][value as usize],
+        (5, 0) => [0, 16, 32, 47, 63][value as usize],
+        (3, 1) => unquantize_packed(value, bits, 50, &[], 0x20),
+        (3, 2) => unquantize_packed(value, bits, 23, &[&[6, 2, 0]], 0x20),
+        (3, _) => unquantize_packed(value, bits, 11, &[&[5, 0], &[6, 1]], 0x20),
+        (_, 1) => unquantize_packed(value, bits, 28, &[], 0x20),
+        (_, _) => unquantize_packed(value, bits, 13, &[&[6, 1]], 0x20),
+    };
+    if weight > 32 {
+        weight + 1
+    } else {
+        weight
+    }
+}
+
+/// The partition of texel `(x, y)` in a block of `partition_count`
+/// partitions, from the hash the partition index seeds.
+fn select_partition(
+    index: u32,
+    x: usize,
+    y: usize,
+    partition_count: usize,
+    small_block: bool,
+) -> usize {
+    let (x, y) = if small_block {
+        (x as u32 * 2, y as u32 * 2)
+    } else {
+        (x as u32, y as u32)
+    };
+    let seed = index + (partition_count as u32 - 1) * 1024;
+    let mut hash = seed;
+    hash ^= hash >> 15;
+    hash = hash.wrapping_sub(hash << 17);
+    hash = hash.wrapping_add(hash << 7);
+    hash = hash.wrapping_add(hash << 4);
+    hash ^= hash >> 5;
+    hash = hash.wrapping_add(hash << 16);
+    hash ^= hash >> 7;
+    hash ^= hash >> 3;
+    hash ^= hash << 6;
+    hash ^= hash >> 17;
+
+    let nibble = |shift: u32| {
+        let value = hash.rotate_right(shift) & 0xF;
+        value * value
+    };
+    let seeds = [0, 4, 8, 12, 16, 20, 24, 28].map(nibble);
+    let (shift1, shift2) = match (seed & 1 == 1, seed & 2 == 2, partition_count == 3) {
+        (true, high, three) => (
+            if high {
+                4
+            } else {
+                5
+            },
+            if three {
+                6
+            } else {
+                5
+            },
+        ),
+        (false, high, three) => (
+            if three {
+                6
+            } else {
+                5
+            },
+            if high {
+                4
+            } else {
+                5
+            },
+        ),
+    };
+    let shifts = [shift1, shift2, shift1, shift2, shift1, shift2, shift1, shift2];
+    let [s1, s2, s3, s4, s5, s6, s7, s8] = core::array::from_fn(|i| seeds[i] >> shifts[i]);
+    let a = (s1 * x + s2 * y + (hash >> 14)) & 0x3F;
+    let b = (s3 * x + s4 * y + (hash >> 10)) & 0x3F;
+    let c = if partition_count >= 3 {
+        (s5 * x + s6 * y + (hash >> 6)) & 0x3F
+    } else {
+        0
+    };
+    let d = if partition_count >= 4 {
+        (s7 * x + s8 * y + (hash >> 2)) & 0x3F
+    } else {
+        0
+    };
+    if a >= b && a >= c && a >= d {
+        0
+    } else if b >= c && b >= d {
+        1
+    } else if c >= d {
+        2
+    } else {
+        3
+    }
+}
+
+/// A partition's two endpoint colors as 16-bit values, and which channels
+/// hold HDR logarithmic values rather than UNORM16.
+#[derive(Copy, Clone, Default)]
+struct Endpoints {
+    colors: [[u32; 4]; 2],
+    hdr: [bool; 4],
+}
+
+impl Endpoints {
+    /// Decodes the endpoints of color endpoint `mode` from its unquantized
+    /// values, or returns `None` for HDR modes in an LDR profile.
+    fn new(
+        mode: u32,
+        values: &[u32],
+        profile: AstcProfile,
+    ) -> Option<Self> {
+        let v: [i32; 8] = core::array::from_fn(|i| values.get(i).copied().unwrap_or(0) as i32);
+        let ldr = match mode {
+            0 => [[v[0], v[0], v[0], 255], [v[1], v[1], v[1], 255]],
+            1 => {
+                let low = (v[0] >> 2) | (v[1] & 0xC0);
+                let high = (low + (v[1] & 0x3F)).min(255);
+                [[low, low, low, 255], [high, high, high, 255]]
+            },
+            4 => [[v[0], v[0], v[0], v[2]], [v[1], v[1], v[1], v[3]]],
+            5 => {
+                let (luminance, luminance_delta) = bit_transfer_signed(v[1], v[0]);
+                let (alpha, alpha_delta) = bit_transfer_signed(v[3], v[2]);
+                let high = luminance + luminance_delta;
+                [[luminance, luminance, luminance, alpha], [high, high, high, alpha + alpha_delta]]
+            },
+            6 => [[(v[0] * v[3]) >> 8, (v[1] * v[3]) >> 8, (v[2] * v[3]) >> 8, 255], [v[0], v[1], v[2], 255]],
+            8 | 12 => {
+                let (alpha0, alpha1) = if mode == 12 {
+                    (v[6], v[7])
+                } else {
+                    (255, 255)
+                };
+                if v[1] + v[3] + v[5] >= v[0] + v[2] + v[4] {
+                    [[v[0], v[2], v[4], alpha0], [v[1], v[3], v[5], alpha1]]
+                } else {
+                    [blue_contract([v[1], v[3], v[5], alpha1]), blue_contract([v[0], v[2], v[4], alpha0])]
+                }
+            },
+            9 | 13 => {
+                let (r, r_delta) = bit_transfer_signed(v[1], v[0]);
+                let (g, g_delta) = bit_transfer_signed(v[3], v[2]);
+                let (b, b_delta) = bit_transfer_signed(v[5], v[4]);
+                let (a, a_delta) = if mode == 13 {
+                    bit_transfer_signed(v[7], v[6])
+                } else {
+                    (255, 0)
+                };
+                let base = [r, g, b, a];
+                let offset = [r + r_delta, g + g_delta, b + b_delta, a + a_delta];
+                if r_delta + g_delta + b_delta >= 0 {
+                    [base, offset]
+                } else {
+                    [blue_contract(offset), blue_contract(base)]
+                }
+            },
+            10 => [[(v[0] * v[3]) >> 8, (v[1] * v[3]) >> 8, (v[2] * v[3]) >> 8, v[4]], [v[0], v[1], v[2], v[5]]],
+            _ => return Self::new_hdr(mode, &v, profile),
+        };
+        let expand = |value: i32| {
+            let value = value.clamp(0, 255) as u32;
+            match profile {
+                AstcProfile::LdrSrgb => value << 8 | 0x80,
+                _ => value << 8 | value,
+            }
+        };
+        Some(Self {
+            colors: ldr.map(|color| color.map(expand)),
+            hdr: [false; 4],
+        })
+    }
+
+    fn new_hdr(
+        mode: u32,
+        v: &[i32; 8],
+        profile: AstcProfile,
+    ) -> Option<Self> {
+        if profile != AstcProfile::Hdr {
+            return None;
+        }
+        // 12-bit logarithmic values, opaque unless the mode has alpha.
+        let (mut low, mut high) = match mode {
+            2 => {
+                let (y0, y1) = if v[1] >= v[0] {
+                    (v[0] << 4, v[1] << 4)
+                } else {
+                    ((v[1] << 4) + 8, (v[0] << 4) - 8)
+                };
+                ([y0, y0, y0, 0x780], [y1, y1, y1, 0x780])
+            },
+            3 => {
+                let (y0, delta) = if v[0] & 0x80 != 0 {
+                    ((v[1] & 0xE0) << 4 | (v[0] & 0x7F) << 2, (v[1] & 0x1F) << 2)
+                } else {
+                    ((v[1] & 0xF0) << 4 | (v[0] & 0x7F) << 1, (v[1] & 0xF) << 1)
+                };
+                let y1 = (y0 + delta).min(0xFFF);
+                ([y0, y0, y0, 0x780], [y1, y1, y1, 0x780])
+            },
+            7 => hdr_rgb_scale(v),
+            _ => hdr_rgb(v),
+        };
+        let mut hdr = [true; 4];
+        match mode {
+            14 => {
+                (low[3], high[3]) = (v[6], v[7]);
+                hdr[3] = false;
+            },
+            15 => (low[3], high[3]) = hdr_alpha(v[6], v[7]),
+            _ => {},
+        }
+        let to_16_bits = |value: i32, hdr: bool| {
+            if hdr {
+                (value as u32) << 4
+            } else {
+                (value as u32) << 8 | value as u32
+            }
+        };
+        let mut colors = [[0; 4]; 2];
+        for channel in 0..4 {
+            colors[0][channel] = to_16_bits(low[channel], hdr[channel]);
+            colors[1][channel] = to_16_bits(high[channel], hdr[channel]);
+        }
+        Some(Self {
+            colors,
+            hdr,
+        })
+    }
+}
+
+/// Moves the top bit of `a` into `b`'s and makes `a` a signed 6-bit offset,
+/// returning `(b, a)`.
+fn bit_transfer_signed(
+    a: i32,
+    b: i32,
+) -> (i32, i32) {
+    let b = (b >> 1) | (a & 0x80);
+    let a = (a >> 1) & 0x3F;
+    let a = if a & 0x20 != 0 {
+        a - 0x40
+    } else {
+        a
+    };
+    (b, a)
+}
+
+fn blue_contract(color: [i32; 4]) -> [i32; 4] {
+    let [r, g, b, a] = color;
+    [(r + b) >> 1, (g + b) >> 1, b, a]
+}
+
+/// HDR mode 7: a color and a scale down from it.
+fn hdr_rgb_scale(v: &[i32; 8]) -> ([i32; 4], [i32; 4]) {
+    let mode_value = (v[0] & 0xC0) >> 6 | (v[1] & 0x80) >> 5 | (v[2] & 0x80) >> 4;
+    let (major, mode) = if mode_value & 0xC != 0xC {
+        (mode_value >> 2, mode_value & 3)
+    } else if mode_value != 0xF {
+        (mode_value & 3, 4)
+    } else {
+        (0, 5)
+    };
+    let (mut red, mut green, mut blue, mut scale) = (v[0] & 0x3F, v[1] & 0x1F, v[2] & 0x1F, v[3] & 0x1F);
+    let x = [
+        (v[1] >> 6) & 1,
+        (v[1] >> 5) & 1,
+        (v[2] >> 6) & 1,
+        (v[2] >> 5) & 1,
+        (v[3] >> 7) & 1,
+        (v[3] >> 6) & 1,
+        (v[3] >> 5) & 1,
+    ];
+    let modes = 1 << mode;
+    let when = |mask: i32, bit: i32, shift: i32| {
+        if modes & mask != 0 {
+            bit << shift
+        } else {
+            0
+        }
+    };
+    green |= when(0x30, x[0], 6) | when(0x3A, x[1], 5);
+    blue |= when(0x30, x[2], 6) | when(0x3A, x[3], 5);
+    scale |= when(0x3D, x[6], 5) | when(0x2D, x[5], 6) | when(0x04, x[4], 7);
+    red |= when(0x3B, x[4], 6) | when(0x04, x[3], 6) | when(0x10, x[5], 7) | when(0x0F, x[2], 7);
+    red |= when(0x05, x[1], 8) | when(0x0A, x[0], 8) | when(0x05, x[0], 9) | when(0x02, x[6], 9);
+    red |= when(0x01, x[3], 10) | when(0x02, x[5], 10);
+    let shift = [1, 1, 2, 3, 4, 5][mode as usize];
+    let (mut red, mut green, mut blue, scale) = (red << shift, green << shift, blue << shift, scale << shift);
+    if mode != 5 {
+        green = red - green;
+        blue = red - blue;
+    }
+    match major {
+        1 => (red, green) = (green, red),
+        2 => (red, blue) = (blue, red),
+        _ => {},
+    }
+    let clamp = |value: i32| value.clamp(0, 0xFFF);
+    (
+        [clamp(red - scale), clamp(green - scale), clamp(blue - scale), 0x780],
+        [clamp(red), clamp(green), clamp(blue), 0x780],
+    )
+}
+
+/// HDR mode 11: two colors, stored as a base and differences from it, or
+/// directly when both major-component bits are set.
+fn hdr_rgb(v: &[i32; 8]) -> ([i32; 4], [i32; 4]) {
+    let major = (v[4] & 0x80) >> 7 | (v[5] & 0x80) >> 6;
+    if major == 3 {
+        return ([v[0] << 4, v[2] << 4, (v[4] & 0x7F) << 5, 0x780], [v[1] << 4, v[3] << 4, (v[5] & 0x7F) << 5, 0x780]);
+    }
+    let mode = (v[1] & 0x80) >> 7 | (v[2] & 0x80) >> 6 | (v[3] & 0x80) >> 5;
+    let mut a = v[0] | (v[1] & 0x40) << 2;
+    let (mut b0, mut b1, mut c, mut d0, mut d1) = (v[2] & 0x3F, v[3] & 0x3F, v[1] & 0x3F, v[4] & 0x1F, v[5] & 0x1F);
+    let x = [(v[2] >> 6) & 1, (v[3] >> 6) & 1, (v[4] >> 6) & 1, (v[5] >> 6) & 1, (v[4] >> 5) & 1, (v[5] >> 5) & 1];
+    let modes = 1 << mode;
+    let when = |mask: i32, bit: i32, shift: i32| {
+        if modes & mask != 0 {
+            bit << shift
+        } else {
+            0
+        }
+    };
+    a |= when(0xA4, x[0], 9) | when(0x08, x[2], 9) | when(0x50, x[4], 9);
+    a |= when(0x50, x[5], 10) | when(0xA0, x[1], 10) | when(0xC0, x[2], 11);
+    c |= when(0x04, x[1], 6) | when(0xE8, x[3], 6) | when(0x20, x[2], 7);
+    b0 |= when(0x5B, x[0], 6) | when(0x12, x[2], 7);
+    b1 |= when(0x5B, x[1], 6) | when(0x12, x[3], 7);
+    d0 |= when(0xAF, x[4], 5) | when(0x05, x[2], 6);
+    d1 |= when(0xAF, x[5], 5) | when(0x05, x[3], 6);
+    let delta_bits = [7, 6, 7, 6, 5, 6, 5, 6][mode as usize];
+    let sign_extend = |value: i32| (value << (32 - delta_bits)) >> (32 - delta_bits);
+    let (d0, d1) = (sign_extend(d0), sign_extend(d1));
+    let shift = (mode >> 1) ^ 3;
+    let (a, b0, b1, c, d0, d1) = (a << shift, b0 << shift, b1 << shift, c << shift, d0 << shift, d1 << shift);
+    let clamp = |value: i32| value.clamp(0, 0xFFF);
+    let mut low = [clamp(a - c), clamp(a - b0 - c - d0), clamp(a - b1 - c - d1), 0x780];
+    let mut high = [clamp(a), clamp(a - b0), clamp(a - b1), 0x780];
+    match major {
+        1 => {
+            low.swap(0, 1);
+            high.swap(0, 1);
+        },
+        2 => {
+            low.swap(0, 2);
+            high.swap(0, 2);
+        },
+        _ => {},
+    }
+    (low, high)
+}
+
+/// HDR alpha of mode 15, as 12-bit logarithmic values.
+fn hdr_alpha(
+    v6: i32,
+    v7: i32,
+) -> (i32, i32) {
+    let mode = (v6 >> 7) & 1 | (v7 >> 6) & 2;
+    let (v6, v7) = (v6 & 0x7F, v7 & 0x7F);
+    if mode == 3 {
+        return (v6 << 5, v7 << 5);
+    }
+    let v6 = v6 | (v7 << (mode + 1)) & 0x780;
+    let v7 = v7 & (0x3F >> mode);
+    let v7 = (v7 ^ (0x20 >> mode)) - (0x20 >> mode);
+    let (v6, v7) = (v6 << (4 - mode), v7 << (4 - mode));
+    (v6, (v6 + v7).clamp(0, 0xFFF))
+}
+
+/// Converts an interpolated 16-bit logarithmic value to a half float.
+fn lns_to_float16(value: u32) -> Float16 {
+    let (exponent, mantissa) = (value >> 11, value & 0x7FF);
+    let mantissa = if mantissa < 512 {
+        3 * mantissa
+    } else if mantissa >= 1536 {
+        5 * mantissa - 2048
+    } else {
+        4 * mantissa - 512
+    };
+    Float16::from_bits(((exponent << 10) + (mantissa >> 3)).min(0x7BFF) as u16)
+}
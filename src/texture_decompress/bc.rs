@@ -0,0 +1,119 @@
+//! BC1–BC5: the S3TC color and alpha blocks and the RGTC one- and two-channel
+//! blocks built from them.
+
+/// Decoded texels of a 4x4 block, row by row.
+pub(crate) type Texels = [[f32; 4]; 16];
+
+pub(crate) fn decode_bc1(
+    block: &[u8],
+    texels: &mut Texels,
+) {
+    decode_color(block, false, texels);
+}
+
+/// BC2: explicit 4-bit alpha, then a four-color BC1 block.
+pub(crate) fn decode_bc2(
+    block: &[u8],
+    texels: &mut Texels,
+) {
+    decode_color(&block[8..], true, texels);
+    let alpha = u64::from_le_bytes(block[..8].try_into().unwrap());
+    for (i, texel) in texels.iter_mut().enumerate() {
+        texel[3] = ((alpha >> (4 * i)) & 0xF) as f32 / 15.0;
+    }
+}
+
+/// BC3: interpolated alpha, then a four-color BC1 block.
+pub(crate) fn decode_bc3(
+    block: &[u8],
+    texels: &mut Texels,
+) {
+    decode_color(&block[8..], true, texels);
+    let alpha = decode_channel(&block[..8], false);
+    for (texel, alpha) in texels.iter_mut().zip(alpha) {
+        texel[3] = alpha;
+    }
+}
+
+pub(crate) fn decode_bc4(
+    block: &[u8],
+    signed: bool,
+    texels: &mut Texels,
+) {
+    let red = decode_channel(block, signed);
+    for (texel, red) in texels.iter_mut().zip(red) {
+        *texel = [red, 0.0, 0.0, 1.0];
+    }
+}
+
+pub(crate) fn decode_bc5(
+    block: &[u8],
+    signed: bool,
+    texels: &mut Texels,
+) {
+    let red = decode_channel(&block[..8], signed);
+    let green = decode_channel(&block[8..], signed);
+    for (texel, (red, green)) in texels.iter_mut().zip(red.into_iter().zip(green)) {
+        *texel = [red, green, 0.0, 1.0];
+    }
+}
+
+/// Decodes an 8-byte BC1 color block. Blocks whose first endpoint isn't
+/// greater than the second have three colors and transparent black, unless
+/// `four_color` is set, as it is within BC2 and BC3 blocks.
+fn decode_color(
+    block: &[u8],
+    four_color: bool,
+    texels: &mut Texels,
+) {
+    let endpoint = |i: usize| u16::from_le_bytes([block[2 * i], block[2 * i + 1]]);
+    let (color0, color1) = (endpoint(0), endpoint(1));
+    let expand = |color: u16| {
+        let (r, g, b) = (color >> 11, (color >> 5) & 0x3F, color & 0x1F);
+        [(r << 3 | r >> 2) as u32, (g << 2 | g >> 4) as u32, (b << 3 | b >> 2) as u32]
+    };
+    let (c0, c1) = (expand(color0), expand(color1));
+    let mix = |weight0: u32, weight1: u32| {
+        let total = weight0 + weight1;
+        let channel = |i: usize| ((c0[i] * weight0 + c1[i] * weight1 + total / 2) / total) as f32 / 255.0;
+        [channel(0), channel(1), channel(2), 1.0]
+    };
+    let palette = if four_color || color0 > color1 {
+        [mix(1, 0), mix(0, 1), mix(2, 1), mix(1, 2)]
+    } else {
+        [mix(1, 0), mix(0, 1), mix(1, 1), [0.0; 4]]
+    };
+    let indices = u32::from_le_bytes(block[4..8].try_into().unwrap());
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = palette[((indices >> (2 * i)) & 3) as usize];
+    }
+}
+
+/// Decodes an 8-byte BC4 block, the alpha block of BC3, to one channel.
+fn decode_channel(
+    block: &[u8],
+    signed: bool,
+) -> [f32; 16] {
+    let (value0, value1, low, high) = if signed {
+        // -128 decodes as -127, so both map to -1.
+        let endpoint = |byte: u8| (byte as i8).max(-127) as f32 / 127.0;
+        (endpoint(block[0]), endpoint(block[1]), -1.0, 1.0)
+    } else {
+        (block[0] as f32 / 255.0, block[1] as f32 / 255.0, 0.0, 1.0)
+    };
+    let greater = if signed {
+        (block[0] as i8) > (block[1] as i8)
+    } else {
+        block[0] > block[1]
+    };
+    let mix = |weight1: f32, steps: f32| (value0 * (steps - weight1) + value1 * weight1) / steps;
+    let palette = if greater {
+        [value0, value1, mix(1.0, 7.0), mix(2.0, 7.0), mix(3.0, 7.0), mix(4.0, 7.0), mix(5.0, 7.0), mix(6.0, 7.0)]
+    } else {
+        [value0, value1, mix(1.0, 5.0), mix(2.0, 5.0), mix(3.0, 5.0), mix(4.0, 5.0), low, high]
+    };
+    let mut indices = [0; 8];
+    indices[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(indices);
+    core::array::from_fn(|i| palette[((indices >> (3 * i)) & 7) as usize])
+}
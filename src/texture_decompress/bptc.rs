@@ -0,0 +1,529 @@
+//! BC6H and BC7, the BPTC formats: blocks of up to three subsets, each
+//! interpolating between its own endpoints.
+
+use super::bc::Texels;
+use crate::Float16;
+
+/// Reads a 128-bit block least significant bit first.
+struct BitReader {
+    bits: u128,
+}
+
+impl BitReader {
+    fn new(block: &[u8]) -> Self {
+        Self {
+            bits: u128::from_le_bytes(block[..16].try_into().unwrap()),
+        }
+    }
+
+    fn read(
+        &mut self,
+        count: u32,
+    ) -> u32 {
+        let value = (self.bits & ((1 << count) - 1)) as u32;
+        self.bits = self.bits.checked_shr(count).unwrap_or(0);
+        value
+    }
+}
+
+/// Texel subsets of the two-subset partitions, one bit per texel.
+#[rustfmt::skip]
+const PARTITIONS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80,
+    0xC800, 0xFFEC, 0xFE80, 0xE800, 0xFFE8, 0xFF00, 0xFFF0, 0xF000,
+    0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C,
+    0xAAAA, 0xF0F0, 0x5A5A, 0x33CC, 0x3C3C, 0x55AA, 0x9696, 0xA55A,
+    0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C,
+    0x9336, 0x9CC6, 0x817E, 0xE718, 0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+/// Texel subsets of the three-subset partitions.
+#[rustfmt::skip]
+const PARTITIONS_3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2], [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1], [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2], [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1], [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2], [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2], [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2], [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2], [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2], [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2], [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2], [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2], [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0], [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0], [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2], [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1], [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2], [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2], [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0], [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0], [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1], [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1], [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1], [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1], [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2], [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2], [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2], [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2], [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2], [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2], [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1], [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2], [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+/// Anchor texel of the second subset of each two-subset partition.
+#[rustfmt::skip]
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+    15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2,
+    15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6,
+    6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Anchor texels of the second and third subsets of each three-subset
+/// partition.
+#[rustfmt::skip]
+const ANCHORS_3: [[u8; 2]; 64] = [
+    [3, 15], [3, 8], [15, 8], [15, 3], [8, 15], [3, 15], [15, 3], [15, 8],
+    [8, 15], [8, 15], [6, 15], [6, 15], [6, 15], [5, 15], [3, 15], [3, 8],
+    [3, 15], [3, 8], [8, 15], [15, 3], [3, 15], [3, 8], [6, 15], [10, 8],
+    [5, 3], [8, 15], [8, 6], [6, 10], [8, 15], [5, 15], [15, 10], [15, 8],
+    [8, 15], [15, 3], [3, 15], [5, 10], [6, 10], [10, 8], [8, 9], [15, 10],
+    [15, 6], [3, 15], [15, 8], [5, 15], [15, 3], [15, 6], [15, 6], [15, 8],
+    [3, 15], [15, 3], [5, 15], [5, 15], [5, 15], [8, 15], [5, 15], [10, 15],
+    [5, 15], [10, 15], [8, 15], [13, 15], [15, 3], [12, 15], [3, 15], [3, 8],
+];
+
+/// Interpolation weights out of 64 for 2-, 3- and 4-bit indices.
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+fn weights(index_bits: u32) -> &'static [u32] {
+    match index_bits {
+        2 => &WEIGHTS_2,
+        3 => &WEIGHTS_3,
+        _ => &WEIGHTS_4,
+    }
+}
+
+/// The subset of texel `i` in `partition` of a block of `subsets` subsets.
+fn subset(
+    subsets: usize,
+    partition: usize,
+    i: usize,
+) -> usize {
+    match subsets {
+        1 => 0,
+        2 => (PARTITIONS_2[partition] >> i) as usize & 1,
+        _ => PARTITIONS_3[partition][i] as usize,
+    }
+}
+
+/// Whether texel `i` is a subset's anchor, whose index drops its top bit.
+fn is_anchor(
+    subsets: usize,
+    partition: usize,
+    i: usize,
+) -> bool {
+    i == 0
+        || match subsets {
+            1 => false,
+            2 => i == ANCHORS_2[partition] as usize,
+            _ => ANCHORS_3[partition].contains(&(i as u8)),
+        }
+}
+
+/// Reads the index of each texel, `index_bits` wide except at anchors.
+fn read_indices(
+    bits: &mut BitReader,
+    subsets: usize,
+    partition: usize,
+    index_bits: u32,
+) -> [u32; 16] {
+    core::array::from_fn(|i| bits.read(index_bits - is_anchor(subsets, partition, i) as u32))
+}
+
+/// A BC7 mode's field widths.
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    /// Each endpoint has its own p-bit.
+    endpoint_p_bits: bool,
+    /// Both endpoints of a subset share a p-bit.
+    shared_p_bits: bool,
+    index_bits: u32,
+    secondary_index_bits: u32,
+}
+
+#[rustfmt::skip]
+const BC7_MODES: [Bc7Mode; 8] = [
+    Bc7Mode { subsets: 3, partition_bits: 4, rotation_bits: 0, index_selection_bits: 0, color_bits: 4, alpha_bits: 0, endpoint_p_bits: true, shared_p_bits: false, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 6, alpha_bits: 0, endpoint_p_bits: false, shared_p_bits: true, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 3, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 0, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 0, endpoint_p_bits: true, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 1, color_bits: 5, alpha_bits: 6, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 3 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 0, color_bits: 7, alpha_bits: 8, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 2 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 7, endpoint_p_bits: true, shared_p_bits: false, index_bits: 4, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 5, endpoint_p_bits: true, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+];
+
+pub(crate) fn decode_bc7(
+    block: &[u8],
+    texels: &mut Texels,
+) {
+    let mode_index = block[0].trailing_zeros() as usize;
+    // Blocks without a mode bit are reserved and decode as transparent black.
+    let Some(mode) = BC7_MODES.get(mode_index) else {
+        *texels = [[0.0; 4]; 16];
+        return;
+    };
+    let mut bits = BitReader::new(block);
+    bits.read(mode_index as u32 + 1);
+    let partition = bits.read(mode.partition_bits) as usize;
+    let rotation = bits.read(mode.rotation_bits);
+    let index_selection = bits.read(mode.index_selection_bits);
+
+    let endpoint_count = 2 * mode.subsets;
+    let mut endpoints = [[0u32; 4]; 6];
+    for channel in 0..3 {
+        for endpoint in &mut endpoints[..endpoint_count] {
+            endpoint[channel] = bits.read(mode.color_bits);
+        }
+    }
+    if mode.alpha_bits > 0 {
+        for endpoint in &mut endpoints[..endpoint_count] {
+            endpoint[3] = bits.read(mode.alpha_bits);
+        }
+    }
+    let mut p_bits = [None; 6];
+    if mode.endpoint_p_bits {
+        for p_bit in &mut p_bits[..endpoint_count] {
+            *p_bit = Some(bits.read(1));
+        }
+    } else if mode.shared_p_bits {
+        for pair in p_bits[..endpoint_count].chunks_exact_mut(2) {
+            let p_bit = bits.read(1);
+            pair.fill(Some(p_bit));
+        }
+    }
+    // Append each p-bit below its endpoint's channels, then widen them to 8
+    // bits by replicating their top bits.
+    let expand = |value: u32, bits: u32, p_bit: Option<u32>| {
+        let (value, bits) = match p_bit {
+            Some(p_bit) => (value << 1 | p_bit, bits + 1),
+            None => (value, bits),
+        };
+        let value = value << (8 - bits);
+        value | value >> bits
+    };
+    for (endpoint, p_bit) in endpoints[..endpoint_count].iter_mut().zip(p_bits) {
+        for channel in &mut endpoint[..3] {
+            *channel = expand(*channel, mode.color_bits, p_bit);
+        }
+        endpoint[3] = match mode.alpha_bits {
+            0 => 255,
+            alpha_bits => expand(endpoint[3], alpha_bits, p_bit),
+        };
+    }
+
+    let indices = read_indices(&mut bits, mode.subsets, partition, mode.index_bits);
+    let secondary_indices = match mode.secondary_index_bits {
+        0 => None,
+        index_bits => Some(read_indices(&mut bits, 1, 0, index_bits)),
+    };
+
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let subset = subset(mode.subsets, partition, i);
+        let (endpoint0, endpoint1) = (endpoints[2 * subset], endpoints[2 * subset + 1]);
+        let (color_weight, alpha_weight) = match secondary_indices {
+            None => {
+                let weight = weights(mode.index_bits)[indices[i] as usize];
+                (weight, weight)
+            },
+            Some(secondary) => {
+                let primary = weights(mode.index_bits)[indices[i] as usize];
+                let secondary = weights(mode.secondary_index_bits)[secondary[i] as usize];
+                if index_selection == 0 {
+                    (primary, secondary)
+                } else {
+                    (secondary, primary)
+                }
+            },
+        };
+        let mut color: [u32; 4] = core::array::from_fn(|channel| {
+            let weight = if channel < 3 {
+                color_weight
+            } else {
+                alpha_weight
+            };
+            (endpoint0[channel] * (64 - weight) + endpoint1[channel] * weight + 32) >> 6
+        });
+        if rotation > 0 {
+            color.swap(rotation as usize - 1, 3);
+        }
+        *texel = color.map(|channel| channel as f32 / 255.0);
+    }
+}
+
+/// Fields of BC6H endpoints: the red, green and blue of endpoints w and x of
+/// the first subset and y and z of the second, then the partition.
+const RW: u8 = 0;
+const GW: u8 = 1;
+const BW: u8 = 2;
+const RX: u8 = 3;
+const GX: u8 = 4;
+const BX: u8 = 5;
+const RY: u8 = 6;
+const GY: u8 = 7;
+const BY: u8 = 8;
+const RZ: u8 = 9;
+const GZ: u8 = 10;
+const BZ: u8 = 11;
+const D: u8 = 12;
+
+/// A BC6H mode's field widths and the order its header stores field bits
+/// in.
+struct Bc6hMode {
+    /// The mode bits, least significant first.
+    code: u32,
+    subsets: usize,
+    /// Whether endpoints other than w are stored as deltas from w.
+    transformed: bool,
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    /// Runs of `(field, first bit, last bit)` in stream order. Runs whose
+    /// first bit is above their last are stored most significant bit first.
+    layout: &'static [(u8, u8, u8)],
+}
+
+#[rustfmt::skip]
+const BC6H_MODES: [Bc6hMode; 14] = [
+    Bc6hMode { code: 0x00, subsets: 2, transformed: true, endpoint_bits: 10, delta_bits: [5, 5, 5], layout: &[
+        (GY, 4, 4), (BY, 4, 4), (BZ, 4, 4), (RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 4), (GZ, 4, 4), (GY, 0, 3),
+        (GX, 0, 4), (BZ, 0, 0), (GZ, 0, 3), (BX, 0, 4), (BZ, 1, 1), (BY, 0, 3), (RY, 0, 4), (BZ, 2, 2), (RZ, 0, 4),
+        (BZ, 3, 3), (D, 0, 4),
+    ] },
+    Bc6hMode { code: 0x01, subsets: 2, transformed: true, endpoint_bits: 7, delta_bits: [6, 6, 6], layout: &[
+        (GY, 5, 5), (GZ, 4, 5), (RW, 0, 6), (BZ, 0, 1), (BY, 4, 4), (GW, 0, 6), (BY, 5, 5), (BZ, 2, 2), (GY, 4, 4),
+        (BW, 0, 6), (BZ, 3, 3), (BZ, 5, 5), (BZ, 4, 4), (RX, 0, 5), (GY, 0, 3), (GX, 0, 5), (GZ, 0, 3), (BX, 0, 5),
+        (BY, 0, 3), (RY, 0, 5), (RZ, 0, 5), (D, 0, 4),
+    ] },
+    Bc6hMode { code: 0x02, subsets: 2, transformed: true, endpoint_bits: 11, delta_bits: [5, 4, 4], layout: &[
+        (RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 4), (RW, 10, 10), (GY, 0, 3), (GX, 0, 3), (GW, 10, 10),
+        (BZ, 0, 0), (GZ, 0, 3), (BX, 0, 3), (BW, 10, 10), (BZ, 1, 1), (BY, 0, 3), (RY, 0, 4), (BZ, 2, 2), (RZ, 0, 4),
+        (BZ, 3, 3), (D, 0, 4),
+    ] },
+    Bc6hMode { code: 0x06, subsets: 2, transformed: true, endpoint_bits: 11, delta_bits: [4, 5, 4], layout: &[
+        (RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 3), (RW, 10, 10), (GZ, 4, 4), (GY, 0, 3), (GX, 0, 4),
+        (GW, 10, 10), (GZ, 0, 3), (BX, 0, 3), (BW, 10, 10), (BZ, 1, 1), (BY, 0, 3), (RY, 0, 3), (BZ, 0, 0),
+        (BZ, 2, 2), (RZ, 0, 3), (GY, 4, 4), (BZ, 3, 3), (D, 0, 4),
+    ] },
+    Bc6hMode { code: 0x0A, subsets: 2, transformed: true, endpoint_bits: 11, delta_bits: [4, 4, 5], layout: &[
+        (RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 3), (RW, 10, 10), (BY, 4, 4), (GY, 0, 3), (GX, 0, 3),
+        (GW, 10, 10), (BZ, 0, 0), (GZ, 0, 3), (BX, 0, 4), (BW, 10, 10), (BY, 0, 3), (RY, 0, 3), (BZ, 1, 2),
+        (RZ, 0, 3), (BZ, 4, 4), (BZ, 3, 3), (D, 0, 4),
+    ] },
+    Bc6hMode { code: 0x0E, subsets: 2, transformed: true, endpoint_bits: 9, delta_bits: [5, 5, 5], layout: &[
+        (RW, 0, 8), (BY, 4, 4), (GW, 0, 8), (GY, 4, 4), (BW, 0, 8), (BZ, 4, 4), (RX, 0, 4), (GZ, 4, 4), (GY, 0, 3),
+        (GX, 0, 4), (BZ, 0, 0), (GZ, 0, 3), (BX, 0, 4), (BZ, 1, 1), (BY, 0, 3), (RY, 0, 4), (BZ, 2, 2), (RZ, 0, 4),
+        (BZ, 3, 3), (D, 0, 4),
+    ] },
+    Bc6hMode { code: 0x12, subsets: 2, transformed: true, endpoint_bits: 8, delta_bits: [6, 5, 5], layout: &[
+        (RW, 0, 7), (GZ, 4, 4), (BY, 4, 4), (GW, 0, 7), (BZ, 2, 2), (GY, 4, 4), (BW, 0, 7), (BZ, 3, 4), (RX, 0, 5),
+        (GY, 0, 3), (GX, 0, 4), (BZ, 0, 0), (GZ, 0, 3), (BX, 0, 4), (BZ, 1, 1), (BY, 0, 3), (RY, 0, 5), (RZ, 0, 5),
+        (D, 0, 4),
+    ] },
+    Bc6hMode { code: 0x16, subsets: 2, transformed: true, endpoint_bits: 8, delta_bits: [5, 6, 5], layout: &[
+        (RW, 0, 7), (BZ, 0, 0), (BY, 4, 4), (GW, 0, 7), (GY, 5, 5), (GY, 4, 4), (BW, 0, 7), (GZ, 5, 5), (BZ, 4, 4),
+        (RX, 0, 4), (GZ, 4, 4), (GY, 0, 3), (GX, 0, 5), (GZ, 0, 3), (BX, 0, 4), (BZ, 1, 1), (BY, 0, 3), (RY, 0, 4),
+        (BZ, 2, 2), (RZ, 0, 4), (BZ, 3, 3), (D, 0, 4),
+    ] },
+    Bc6hMode { code: 0x1A, subsets: 2, transformed: true, endpoint_bits: 8, delta_bits: [5, 5, 6], layout: &[
+        (RW, 0, 7), (BZ, 1, 1), (BY, 4, 4), (GW, 0, 7), (BY, 5, 5), (GY, 4, 4), (BW, 0, 7), (BZ, 5, 5), (BZ, 4, 4),
+        (RX, 0, 4), (GZ, 4, 4), (GY, 0, 3), (GX, 0, 4), (BZ, 0, 0), (GZ, 0, 3), (BX, 0, 5), (BY, 0, 3), (RY, 0, 4),
+        (BZ, 2, 2), (RZ, 0, 4), (BZ, 3, 3), (D, 0, 4),
+    ] },
+    Bc6hMode { code: 0x1E, subsets: 2, transformed: false, endpoint_bits: 6, delta_bits: [6, 6, 6], layout: &[
+        (RW, 0, 5), (GZ, 4, 4), (BZ, 0, 1), (BY, 4, 4), (GW, 0, 5), (GY, 5, 5), (BY, 5, 5), (BZ, 2, 2), (GY, 4, 4),
+        (BW, 0, 5), (GZ, 5, 5), (BZ, 3, 3), (BZ, 5, 5), (BZ, 4, 4), (RX, 0, 5), (GY, 0, 3), (GX, 0, 5), (GZ, 0, 3),
+        (BX, 0, 5), (BY, 0, 3), (RY, 0, 5), (RZ, 0, 5), (D, 0, 4),
+    ] },
+    Bc6hMode { code: 0x03, subsets: 1, transformed: false, endpoint_bits: 10, delta_bits: [10, 10, 10], layout: &[
+        (RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 9), (GX, 0, 9), (BX, 0, 9),
+    ] },
+    Bc6hMode { code: 0x07, subsets: 1, transformed: true, endpoint_bits: 11, delta_bits: [9, 9, 9], layout: &[
+        (RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 8), (RW, 10, 10), (GX, 0, 8), (GW, 10, 10), (BX, 0, 8),
+        (BW, 10, 10),
+    ] },
+    Bc6hMode { code: 0x0B, subsets: 1, transformed: true, endpoint_bits: 12, delta_bits: [8, 8, 8], layout: &[
+        (RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 7), (RW, 11, 10), (GX, 0, 7), (GW, 11, 10), (BX, 0, 7),
+        (BW, 11, 10),
+    ] },
+    Bc6hMode { code: 0x0F, subsets: 1, transformed: true, endpoint_bits: 16, delta_bits: [4, 4, 4], layout: &[
+        (RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 3), (RW, 15, 10), (GX, 0, 3), (GW, 15, 10), (BX, 0, 3),
+        (BW, 15, 10),
+    ] },
+];
+
+fn sign_extend(
+    value: i32,
+    bits: u32,
+) -> i32 {
+    let shift = 32 - bits;
+    (value << shift) >> shift
+}
+
+/// Decodes a BC6H block to half-float RGB with opaque alpha. `signed`
+/// selects `BC6H_RGBFloat` over `BC6H_RGBUfloat`.
+pub(crate) fn decode_bc6h(
+    block: &[u8],
+    signed: bool,
+    texels: &mut Texels,
+) {
+    let mut bits = BitReader::new(block);
+    let mut code = bits.read(2);
+    if code > 1 {
+        code |= bits.read(3) << 2;
+    }
+    // Reserved modes decode as black.
+    let Some(mode) = BC6H_MODES.iter().find(|mode| mode.code == code) else {
+        *texels = [[0.0, 0.0, 0.0, 1.0]; 16];
+        return;
+    };
+
+    let mut fields = [0i32; 13];
+    for &(field, first, last) in mode.layout {
+        if first <= last {
+            for bit in first..=last {
+                fields[field as usize] |= (bits.read(1) as i32) << bit;
+            }
+        } else {
+            for bit in (last..=first).rev() {
+                fields[field as usize] |= (bits.read(1) as i32) << bit;
+            }
+        }
+    }
+    let partition = fields[D as usize] as usize;
+
+    // Endpoints w, x, y and z, each red, green and blue.
+    let mut endpoints = [[0i32; 3]; 4];
+    for (endpoint, values) in endpoints.iter_mut().enumerate() {
+        for (channel, value) in values.iter_mut().enumerate() {
+            *value = fields[3 * endpoint + channel];
+        }
+    }
+    let endpoint_count = 2 * mode.subsets;
+    for channel in 0..3 {
+        if signed {
+            endpoints[0][channel] = sign_extend(endpoints[0][channel], mode.endpoint_bits);
+        }
+        for endpoint in &mut endpoints[1..endpoint_count] {
+            if signed || mode.transformed {
+                endpoint[channel] = sign_extend(endpoint[channel], mode.delta_bits[channel]);
+            }
+        }
+    }
+    if mode.transformed {
+        let base = endpoints[0];
+        let mask = (1 << mode.endpoint_bits) - 1;
+        for endpoint in &mut endpoints[1..endpoint_count] {
+            for channel in 0..3 {
+                let value = (base[channel] + endpoint[channel]) & mask;
+                endpoint[channel] = if signed {
+                    sign_extend(value, mode.endpoint_bits)
+                } else {
+                    value
+                };
+            }
+        }
+    }
+
+    let unquantize = |value: i32| {
+        if signed {
+            unquantize_signed(value, mode.endpoint_bits)
+        } else {
+            unquantize_unsigned(value, mode.endpoint_bits)
+        }
+    };
+    let endpoints = endpoints.map(|endpoint| endpoint.map(unquantize));
+    let index_bits = if mode.subsets == 1 {
+        4
+    } else {
+        3
+    };
+    let indices = read_indices(&mut bits, mode.subsets, partition, index_bits);
+
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let subset = subset(mode.subsets, partition, i);
+        let weight = weights(index_bits)[indices[i] as usize] as i32;
+        let (endpoint0, endpoint1) = (endpoints[2 * subset], endpoints[2 * subset + 1]);
+        for channel in 0..3 {
+            let value = (endpoint0[channel] * (64 - weight) + endpoint1[channel] * weight + 32) >> 6;
+            texel[channel] = finish(value, signed).to_f32();
+        }
+        texel[3] = 1.0;
+    }
+}
+
+fn unquantize_unsigned(
+    value: i32,
+    bits: u32,
+) -> i32 {
+    if bits >= 15 {
+        value
+    } else if value == 0 {
+        0
+    } else if value == (1 << bits) - 1 {
+        0xFFFF
+    } else {
+        ((value << 16) + 0x8000) >> bits
+    }
+}
+
+fn unquantize_signed(
+    value: i32,
+    bits: u32,
+) -> i32 {
+    if bits >= 16 {
+        return value;
+    }
+    let magnitude = value.abs();
+    let magnitude = if magnitude == 0 {
+        0
+    } else if magnitude >= (1 << (bits - 1)) - 1 {
+        0x7FFF
+    } else {
+        ((magnitude << 15) + 0x4000) >> (bits - 1)
+    };
+    if value < 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+/// Scales an interpolated value to the half-float range and reinterprets its
+/// bits.
+fn finish(
+    value: i32,
+    signed: bool,
+) -> Float16 {
+    let bits = if !signed {
+        (value * 31) >> 6
+    } else if value < 0 {
+        0x8000 | ((-value * 31) >> 5)
+    } else {
+        (value * 31) >> 5
+    };
+    Float16::from_bits(bits as u16)
+}
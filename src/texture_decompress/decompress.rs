@@ -0,0 +1,526 @@
+use core::fmt;
+
+use super::{
+    astc::{self, AstcProfile},
+    bc, bptc,
+    etc::{self, EacChannel},
+};
+use crate::{Float16, MTLPixelFormat, RgbaImage, TextureContainerError, texture_upload::srgb_to_linear};
+
+/// Errors raised while decompressing block-compressed images.
+#[derive(Debug)]
+pub enum TextureDecompressError {
+    /// The pixel format isn't block-compressed, or has no software decoder.
+    UnsupportedFormat(MTLPixelFormat),
+    /// A buffer is smaller than the blocks of the image it holds.
+    DataTooShort {
+        required: usize,
+        actual: usize,
+    },
+    /// A size computation overflowed `usize`.
+    Overflow,
+    /// The decompressed images couldn't be assembled into a container.
+    Container(TextureContainerError),
+}
+
+impl fmt::Display for TextureDecompressError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::UnsupportedFormat(format) => write!(f, "can't decompress {format:?} images"),
+            Self::DataTooShort {
+                required,
+                actual,
+            } => write!(f, "buffer of {actual} bytes is shorter than the {required} bytes required"),
+            Self::Overflow => f.write_str("image size overflows usize"),
+            Self::Container(error) => write!(f, "failed to assemble decompressed texture: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for TextureDecompressError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Container(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<TextureContainerError> for TextureDecompressError {
+    fn from(error: TextureContainerError) -> Self {
+        Self::Container(error)
+    }
+}
+
+/// Uncompressed RGBA layouts that compressed images decompress to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DecompressedFormat {
+    /// 8-bit unsigned normalized channels, sRGB-encoded for sRGB sources.
+    /// Negative values of signed formats clamp to zero.
+    Rgba8,
+    Rgba16Float,
+    Rgba32Float,
+}
+
+impl DecompressedFormat {
+    pub const fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::Rgba8 => 4,
+            Self::Rgba16Float => 8,
+            Self::Rgba32Float => 16,
+        }
+    }
+
+    /// The pixel format of `source` images decompressed to this layout.
+    pub const fn pixel_format(
+        self,
+        source: MTLPixelFormat,
+    ) -> MTLPixelFormat {
+        match self {
+            Self::Rgba8 if source.is_srgb() => MTLPixelFormat::RGBA8UnormSrgb,
+            Self::Rgba8 => MTLPixelFormat::RGBA8Unorm,
+            Self::Rgba16Float => MTLPixelFormat::RGBA16Float,
+            Self::Rgba32Float => MTLPixelFormat::RGBA32Float,
+        }
+    }
+
+    /// The layout that holds `format`'s decoded values without losing range
+    /// or precision: half floats for HDR, signed and 11-bit formats and 8-bit
+    /// channels for the rest. `None` if `format` can't be decompressed.
+    pub const fn fallback_for(format: MTLPixelFormat) -> Option<Self> {
+        use MTLPixelFormat as F;
+
+        if !can_decompress(format) {
+            return None;
+        }
+        let layout = match format {
+            F::BC4_RSnorm
+            | F::BC5_RGSnorm
+            | F::BC6H_RGBFloat
+            | F::BC6H_RGBUfloat
+            | F::EAC_R11Unorm
+            | F::EAC_R11Snorm
+            | F::EAC_RG11Unorm
+            | F::EAC_RG11Snorm => Self::Rgba16Float,
+            _ if format.is_astc_hdr() => Self::Rgba16Float,
+            _ => Self::Rgba8,
+        };
+        Some(layout)
+    }
+}
+
+/// Whether `format` has a software decoder: the BC, EAC/ETC2 and ASTC
+/// formats. PVRTC isn't supported.
+pub const fn can_decompress(format: MTLPixelFormat) -> bool {
+    format.is_compressed() && !format.is_pvrtc()
+}
+
+/// Decodes one block of `format` to texels in the format's own encoding, so
+/// sRGB values stay sRGB-encoded. `texels` receives the block's pixels row
+/// by row and must hold a whole block.
+fn decode_block(
+    format: MTLPixelFormat,
+    block: &[u8],
+    texels: &mut [[f32; 4]],
+) {
+    use MTLPixelFormat as F;
+
+    let Ok(texels_4x4) = <&mut bc::Texels>::try_from(&mut texels[..]) else {
+        return decode_astc(format, block, texels);
+    };
+    match format {
+        F::BC1_RGBA | F::BC1_RGBA_sRGB => bc::decode_bc1(block, texels_4x4),
+        F::BC2_RGBA | F::BC2_RGBA_sRGB => bc::decode_bc2(block, texels_4x4),
+        F::BC3_RGBA | F::BC3_RGBA_sRGB => bc::decode_bc3(block, texels_4x4),
+        F::BC4_RUnorm | F::BC4_RSnorm => bc::decode_bc4(block, format == F::BC4_RSnorm, texels_4x4),
+        F::BC5_RGUnorm | F::BC5_RGSnorm => bc::decode_bc5(block, format == F::BC5_RGSnorm, texels_4x4),
+        F::BC6H_RGBFloat | F::BC6H_RGBUfloat => bptc::decode_bc6h(block, format == F::BC6H_RGBFloat, texels_4x4),
+        F::BC7_RGBAUnorm | F::BC7_RGBAUnorm_sRGB => bptc::decode_bc7(block, texels_4x4),
+        F::ETC2_RGB8 | F::ETC2_RGB8_sRGB => etc::decode_etc2(block, false, texels_4x4),
+        F::ETC2_RGB8A1 | F::ETC2_RGB8A1_sRGB => etc::decode_etc2(block, true, texels_4x4),
+        F::EAC_RGBA8 | F::EAC_RGBA8_sRGB => {
+            etc::decode_etc2(&block[8..], false, texels_4x4);
+            let alpha = etc::decode_eac(block, EacChannel::Alpha);
+            for (texel, alpha) in texels_4x4.iter_mut().zip(alpha) {
+                texel[3] = alpha;
+            }
+        },
+        F::EAC_R11Unorm | F::EAC_R11Snorm | F::EAC_RG11Unorm | F::EAC_RG11Snorm => {
+            let channel = match format {
+                F::EAC_R11Snorm | F::EAC_RG11Snorm => EacChannel::Snorm11,
+                _ => EacChannel::Unorm11,
+            };
+            let red = etc::decode_eac(block, channel);
+            let green = match format {
+                F::EAC_RG11Unorm | F::EAC_RG11Snorm => etc::decode_eac(&block[8..], channel),
+                _ => [0.0; 16],
+            };
+            for (i, texel) in texels_4x4.iter_mut().enumerate() {
+                *texel = [red[i], green[i], 0.0, 1.0];
+            }
+        },
+        _ => decode_astc(format, block, texels_4x4),
+    }
+}
+
+fn decode_astc(
+    format: MTLPixelFormat,
+    block: &[u8],
+    texels: &mut [[f32; 4]],
+) {
+    let (block_width, block_height) = format.block_dimensions();
+    let profile = if format.is_srgb() {
+        AstcProfile::LdrSrgb
+    } else if format.is_astc_hdr() {
+        AstcProfile::Hdr
+    } else {
+        AstcProfile::Ldr
+    };
+    astc::decode_astc(block, block_width, block_height, profile, texels);
+}
+
+/// Decodes a `width` by `height` image of `format` blocks, stored row by row
+/// and tightly packed, calling `write` with each pixel's coordinates and
+/// texel.
+fn decode_blocks(
+    format: MTLPixelFormat,
+    width: usize,
+    height: usize,
+    data: &[u8],
+    mut write: impl FnMut(usize, usize, [f32; 4]),
+) -> Result<(), TextureDecompressError> {
+    let bytes_per_block = match format.bytes_per_block() {
+        Some(bytes) if can_decompress(format) => bytes,
+        _ => return Err(TextureDecompressError::UnsupportedFormat(format)),
+    };
+    let (block_width, block_height) = format.block_dimensions();
+    let (blocks_wide, blocks_high) = (width.div_ceil(block_width), height.div_ceil(block_height));
+    let required = blocks_wide
+        .checked_mul(blocks_high)
+        .and_then(|blocks| blocks.checked_mul(bytes_per_block))
+        .ok_or(TextureDecompressError::Overflow)?;
+    if data.len() < required {
+        return Err(TextureDecompressError::DataTooShort {
+            required,
+            actual: data.len(),
+        });
+    }
+
+    let mut texels = vec![[0.0; 4]; block_width * block_height];
+    for (i, block) in data[..required].chunks_exact(bytes_per_block).enumerate() {
+        decode_block(format, block, &mut texels);
+        let (x0, y0) = ((i % blocks_wide) * block_width, (i / blocks_wide) * block_height);
+        for y in y0..(y0 + block_height).min(height) {
+            for x in x0..(x0 + block_width).min(width) {
+                write(x, y, texels[(y - y0) * block_width + x - x0]);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Decompresses a `width` by `height` image of `format` blocks, stored row by
+/// row and tightly packed, to tightly packed `output` pixels.
+///
+/// Values keep the source's encoding: sRGB formats produce sRGB-encoded
+/// [`DecompressedFormat::Rgba8`] pixels, to be stored as
+/// [`pixel_format`](DecompressedFormat::pixel_format) says. Formats without
+/// green, blue or alpha decode them as 0, 0 and 1. Malformed ASTC blocks, and
+/// HDR blocks in LDR formats, decode as magenta.
+pub fn decompress_image(
+    format: MTLPixelFormat,
+    width: usize,
+    height: usize,
+    data: &[u8],
+    output: DecompressedFormat,
+) -> Result<Vec<u8>, TextureDecompressError> {
+    let bytes_per_pixel = output.bytes_per_pixel();
+    let len = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(bytes_per_pixel))
+        .ok_or(TextureDecompressError::Overflow)?;
+    let mut pixels = vec![0; len];
+    decode_blocks(format, width, height, data, |x, y, texel| {
+        let offset = (y * width + x) * bytes_per_pixel;
+        let pixel = &mut pixels[offset..offset + bytes_per_pixel];
+        for (channel, value) in texel.into_iter().enumerate() {
+            match output {
+                DecompressedFormat::Rgba8 => pixel[channel] = (value.clamp(0.0, 1.0) * 255.0).round() as u8,
+                DecompressedFormat::Rgba16Float => pixel[2 * channel..2 * channel + 2]
+                    .copy_from_slice(&Float16::from_f32(value).to_bits().to_le_bytes()),
+                DecompressedFormat::Rgba32Float => {
+                    pixel[4 * channel..4 * channel + 4].copy_from_slice(&value.to_le_bytes())
+                },
+            }
+        }
+    })?;
+    Ok(pixels)
+}
+
+/// Decompresses an image, as [`decompress_image`] does, to linear RGBA for
+/// comparison with decoded readbacks. sRGB formats are decoded to linear.
+pub fn decompress_to_rgba(
+    format: MTLPixelFormat,
+    width: usize,
+    height: usize,
+    data: &[u8],
+) -> Result<RgbaImage, TextureDecompressError> {
+    let mut image = RgbaImage::new(width, height);
+    let srgb = format.is_srgb();
+    let pixels = image.pixels_mut();
+    decode_blocks(format, width, height, data, |x, y, mut texel| {
+        if srgb {
+            for channel in &mut texel[..3] {
+                *channel = srgb_to_linear(*channel);
+            }
+        }
+        pixels[y * width + x] = texel;
+    })?;
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decodes one block of `format` to rows of RGBA8 texels.
+    fn decode_rgba8(
+        format: MTLPixelFormat,
+        block: &[u8],
+    ) -> Vec<[u8; 4]> {
+        let (width, height) = format.block_dimensions();
+        let pixels = decompress_image(format, width, height, block, DecompressedFormat::Rgba8).unwrap();
+        pixels.chunks_exact(4).map(|pixel| pixel.try_into().unwrap()).collect()
+    }
+
+    fn decode_f32(
+        format: MTLPixelFormat,
+        block: &[u8],
+    ) -> Vec<[f32; 4]> {
+        let (width, height) = format.block_dimensions();
+        let pixels = decompress_image(format, width, height, block, DecompressedFormat::Rgba32Float).unwrap();
+        let values: Vec<f32> =
+            pixels.chunks_exact(4).map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap())).collect();
+        values.chunks_exact(4).map(|texel| texel.try_into().unwrap()).collect()
+    }
+
+    fn decode_half_bits(
+        format: MTLPixelFormat,
+        block: &[u8],
+    ) -> Vec<[u16; 4]> {
+        let pixels = decompress_image(format, 4, 4, block, DecompressedFormat::Rgba16Float).unwrap();
+        let bits: Vec<u16> = pixels.chunks_exact(2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]])).collect();
+        bits.chunks_exact(4).map(|texel| texel.try_into().unwrap()).collect()
+    }
+
+    fn assert_close(
+        actual: f32,
+        expected: f32,
+    ) {
+        assert!((actual - expected).abs() < 1e-6, "{actual} != {expected}");
+    }
+
+    /// Red then blue 565 endpoints, and texel indices 0, 1, 2, 3 in each row.
+    const RED_BLUE: [u8; 8] = [0x00, 0xf8, 0x1f, 0x00, 0xe4, 0xe4, 0xe4, 0xe4];
+    /// Blue then red, which selects BC1's three-color mode.
+    const BLUE_RED: [u8; 8] = [0x1f, 0x00, 0x00, 0xf8, 0xe4, 0xe4, 0xe4, 0xe4];
+    /// Texel `i` uses index `i % 8`.
+    const CHANNEL_INDICES: [u8; 6] = [0x88, 0xc6, 0xfa, 0x88, 0xc6, 0xfa];
+
+    #[test]
+    fn bc1() {
+        let four_color = [[255, 0, 0, 255], [0, 0, 255, 255], [170, 0, 85, 255], [85, 0, 170, 255]];
+        assert_eq!(decode_rgba8(MTLPixelFormat::BC1_RGBA, &RED_BLUE), four_color.repeat(4));
+        let three_color = [[0, 0, 255, 255], [255, 0, 0, 255], [128, 0, 128, 255], [0, 0, 0, 0]];
+        assert_eq!(decode_rgba8(MTLPixelFormat::BC1_RGBA, &BLUE_RED), three_color.repeat(4));
+        // sRGB values stay encoded.
+        assert_eq!(decode_rgba8(MTLPixelFormat::BC1_RGBA_sRGB, &RED_BLUE), four_color.repeat(4));
+    }
+
+    #[test]
+    fn bc2_and_bc3() {
+        // Texel `i` has 4-bit alpha `i`; the color block always has four colors.
+        let mut bc2 = 0xfedc_ba98_7654_3210u64.to_le_bytes().to_vec();
+        bc2.extend_from_slice(&BLUE_RED);
+        let colors = [[0, 0, 255], [255, 0, 0], [85, 0, 170], [170, 0, 85]];
+        let expected: Vec<_> =
+            (0..16).map(|i| [colors[i % 4][0], colors[i % 4][1], colors[i % 4][2], 17 * i as u8]).collect();
+        assert_eq!(decode_rgba8(MTLPixelFormat::BC2_RGBA, &bc2), expected);
+
+        let mut bc3 = vec![255, 0];
+        bc3.extend_from_slice(&CHANNEL_INDICES);
+        bc3.extend_from_slice(&RED_BLUE[..4]);
+        bc3.extend_from_slice(&[0; 4]);
+        let alpha = [255, 0, 219, 182, 146, 109, 73, 36];
+        let expected: Vec<_> = (0..16).map(|i| [255, 0, 0, alpha[i % 8]]).collect();
+        assert_eq!(decode_rgba8(MTLPixelFormat::BC3_RGBA, &bc3), expected);
+    }
+
+    #[test]
+    fn bc4_and_bc5() {
+        // Endpoints in increasing order select the six-value mode with 0 and 1.
+        let mut six_value = vec![0, 255];
+        six_value.extend_from_slice(&CHANNEL_INDICES);
+        let red = [0, 255, 51, 102, 153, 204, 0, 255];
+        let expected: Vec<_> = (0..16).map(|i| [red[i % 8], 0, 0, 255]).collect();
+        assert_eq!(decode_rgba8(MTLPixelFormat::BC4_RUnorm, &six_value), expected);
+
+        let mut bc5 = six_value.clone();
+        bc5.extend_from_slice(&[255, 0]);
+        bc5.extend_from_slice(&CHANNEL_INDICES);
+        let green = [255, 0, 219, 182, 146, 109, 73, 36];
+        let expected: Vec<_> = (0..16).map(|i| [red[i % 8], green[i % 8], 0, 255]).collect();
+        assert_eq!(decode_rgba8(MTLPixelFormat::BC5_RGUnorm, &bc5), expected);
+
+        // Signed endpoints 127 and -127, where -128 also decodes as -1.
+        for low in [0x81, 0x80] {
+            let mut signed = vec![0x7f, low];
+            signed.extend_from_slice(&CHANNEL_INDICES);
+            let texels = decode_f32(MTLPixelFormat::BC4_RSnorm, &signed);
+            let red = [7.0, -7.0, 5.0, 3.0, 1.0, -1.0, -3.0, -5.0].map(|value| value / 7.0);
+            for (i, texel) in texels.iter().enumerate() {
+                assert_close(texel[0], red[i % 8]);
+                assert_eq!(texel[1..], [0.0, 0.0, 1.0]);
+            }
+        }
+    }
+
+    #[test]
+    fn bc6h() {
+        // Mode 11: one region with unquantized 10-bit endpoints 0 and 1023,
+        // and texel `i` using index `i`.
+        let block = [0x03, 0x00, 0x00, 0x00, 0xf8, 0xff, 0xff, 0xff, 0x11, 0x32, 0x54, 0x76, 0x98, 0xba, 0xdc, 0xfe];
+        let red = [
+            0x0000, 0x07c0, 0x1170, 0x1930, 0x20f0, 0x28b0, 0x3260, 0x3a20, 0x41df, 0x499f, 0x534f, 0x5b0f, 0x62cf,
+            0x6a8f, 0x743f, 0x7bff,
+        ];
+        let expected: Vec<_> = red.iter().map(|&value| [value, value, value, 0x3c00]).collect();
+        assert_eq!(decode_half_bits(MTLPixelFormat::BC6H_RGBUfloat, &block), expected);
+    }
+
+    #[test]
+    fn bc7() {
+        // Mode 6: red and green from 255 to 0, blue 0 and alpha 255, with
+        // texel `i` using index `i`. The endpoints' p-bits, 1 then 0, also
+        // set blue and alpha's low bits.
+        let block = [0xc0, 0x3f, 0xe0, 0x0f, 0x00, 0x00, 0xfe, 0xff, 0x10, 0x32, 0x54, 0x76, 0x98, 0xba, 0xdc, 0xfe];
+        let red = [255, 239, 219, 203, 187, 171, 151, 135, 120, 104, 84, 68, 52, 36, 16, 0];
+        let expected: Vec<_> = red
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| match i {
+                ..8 => [value, value, 1, 255],
+                _ => [value, value, 0, 254],
+            })
+            .collect();
+        assert_eq!(decode_rgba8(MTLPixelFormat::BC7_RGBAUnorm, &block), expected);
+    }
+
+    /// ETC2 individual mode: red 8 then 4 in the left and right halves,
+    /// modifier table 0, every pixel `+2` but pixel (0, 0) `-8`.
+    const ETC2_INDIVIDUAL: [u8; 8] = [0x84, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01];
+
+    #[test]
+    fn etc2() {
+        let expected: Vec<_> = (0..16)
+            .map(|i| match (i % 4, i / 4) {
+                (0, 0) => [128, 0, 0, 255],
+                (0 | 1, _) => [138, 2, 2, 255],
+                _ => [70, 2, 2, 255],
+            })
+            .collect();
+        assert_eq!(decode_rgba8(MTLPixelFormat::ETC2_RGB8, &ETC2_INDIVIDUAL), expected);
+
+        // A differential, non-opaque punch-through block with red 16 and each
+        // row's pixels using the row's index.
+        let punch_through = [0x80, 0x00, 0x00, 0x00, 0xcc, 0xcc, 0xaa, 0xaa];
+        let rows = [[132, 0, 0, 255], [140, 8, 8, 255], [0, 0, 0, 0], [124, 0, 0, 255]];
+        let expected: Vec<_> = (0..16).map(|i| rows[i / 4]).collect();
+        assert_eq!(decode_rgba8(MTLPixelFormat::ETC2_RGB8A1, &punch_through), expected);
+
+        // Base 128, multiplier 2 and modifier `+2` everywhere.
+        let mut eac_rgba = vec![128, 0x20, 0x92, 0x49, 0x24, 0x92, 0x49, 0x24];
+        eac_rgba.extend_from_slice(&ETC2_INDIVIDUAL);
+        let alpha: Vec<_> = decode_rgba8(MTLPixelFormat::EAC_RGBA8, &eac_rgba).iter().map(|texel| texel[3]).collect();
+        assert_eq!(alpha, [132; 16]);
+    }
+
+    #[test]
+    fn eac_r11() {
+        // Multiplier 1 and modifier `+2` everywhere, from base 128, -64 and 64.
+        let unorm = [128, 0x10, 0x92, 0x49, 0x24, 0x92, 0x49, 0x24];
+        for texel in decode_f32(MTLPixelFormat::EAC_R11Unorm, &unorm) {
+            assert_close(texel[0], (128.0 * 8.0 + 4.0 + 16.0) / 2047.0);
+            assert_eq!(texel[1..], [0.0, 0.0, 1.0]);
+        }
+        let snorm = [0xc0, 0x10, 0x92, 0x49, 0x24, 0x92, 0x49, 0x24];
+        let mut rg = snorm.to_vec();
+        rg.extend_from_slice(&[0x40, 0x10, 0x92, 0x49, 0x24, 0x92, 0x49, 0x24]);
+        for texel in decode_f32(MTLPixelFormat::EAC_RG11Snorm, &rg) {
+            assert_close(texel[0], (-64.0 * 8.0 + 16.0) / 1023.0);
+            assert_close(texel[1], (64.0 * 8.0 + 16.0) / 1023.0);
+        }
+        // Base -128 decodes as -127.
+        let mut minimum = snorm;
+        minimum[0] = 0x80;
+        assert_close(decode_f32(MTLPixelFormat::EAC_R11Snorm, &minimum)[0][0], (-127.0 * 8.0 + 16.0) / 1023.0);
+    }
+
+    #[test]
+    fn astc() {
+        // A 4x4 grid of two-bit weights, luminance endpoints 0 and 255, and odd
+        // texels weighted to the second endpoint.
+        let checkerboard =
+            [0x42, 0x00, 0x00, 0xfe, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x33, 0x33, 0x33, 0x33];
+        let expected: Vec<_> = (0..16)
+            .map(|i| {
+                if i % 2 == 0 {
+                    [0, 0, 0, 255]
+                } else {
+                    [255; 4]
+                }
+            })
+            .collect();
+        assert_eq!(decode_rgba8(MTLPixelFormat::ASTC_4x4_LDR, &checkerboard), expected);
+
+        let mut void_extent = vec![0xfc, 0xfd, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        void_extent.extend([0xffffu16, 0x8000, 0x0000, 0xffff].iter().flat_map(|value| value.to_le_bytes()));
+        assert_eq!(decode_rgba8(MTLPixelFormat::ASTC_4x4_LDR, &void_extent), [[255, 128, 0, 255]; 16]);
+        assert_eq!(decode_rgba8(MTLPixelFormat::ASTC_6x5_LDR, &void_extent), [[255, 128, 0, 255]; 30]);
+
+        let mut hdr_void_extent = vec![0xfc, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        hdr_void_extent.extend([0x3c00u16, 0x4000, 0x0000, 0x3c00].iter().flat_map(|value| value.to_le_bytes()));
+        assert_eq!(
+            decode_half_bits(MTLPixelFormat::ASTC_4x4_HDR, &hdr_void_extent),
+            [[0x3c00, 0x4000, 0x0000, 0x3c00]; 16]
+        );
+
+        // HDR blocks in LDR formats and reserved block modes decode as magenta.
+        let magenta = [[255, 0, 255, 255]; 16];
+        assert_eq!(decode_rgba8(MTLPixelFormat::ASTC_4x4_LDR, &hdr_void_extent), magenta);
+        assert_eq!(decode_rgba8(MTLPixelFormat::ASTC_4x4_LDR, &[0; 16]), magenta);
+    }
+
+    #[test]
+    fn unsupported_and_short_data() {
+        assert!(matches!(
+            decompress_image(MTLPixelFormat::PVRTC_RGBA_4BPP, 4, 4, &[0; 8], DecompressedFormat::Rgba8),
+            Err(TextureDecompressError::UnsupportedFormat(MTLPixelFormat::PVRTC_RGBA_4BPP))
+        ));
+        assert!(matches!(
+            decompress_image(MTLPixelFormat::BC1_RGBA, 5, 4, &[0; 8], DecompressedFormat::Rgba8),
+            Err(TextureDecompressError::DataTooShort {
+                required: 16,
+                actual: 8,
+            })
+        ));
+        assert_eq!(
+            DecompressedFormat::fallback_for(MTLPixelFormat::ASTC_4x4_HDR),
+            Some(DecompressedFormat::Rgba16Float)
+        );
+        assert_eq!(DecompressedFormat::fallback_for(MTLPixelFormat::PVRTC_RGB_2BPP), None);
+    }
+}
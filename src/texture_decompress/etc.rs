@@ -0,0 +1,209 @@
+//! ETC2 color blocks, with the ETC1 modes they extend, and EAC blocks of a
+//! single 8- or 11-bit channel.
+
+use super::bc::Texels;
+
+/// Luminance modifiers `a` and `b` of the individual and differential modes,
+/// applied as `a`, `b`, `-a` and `-b`.
+const MODIFIERS: [[i32; 2]; 8] = [[2, 8], [5, 17], [9, 29], [13, 42], [18, 60], [24, 80], [33, 106], [47, 183]];
+
+/// Paint color distances of the T and H modes.
+const DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+#[rustfmt::skip]
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+/// How an EAC block's values are scaled.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum EacChannel {
+    /// The 8-bit alpha of `EAC_RGBA8`.
+    Alpha,
+    Unorm11,
+    Snorm11,
+}
+
+/// Decodes an 8-byte ETC2 block. `punch_through` selects `ETC2_RGB8A1`,
+/// whose blocks have no individual mode and use the differential bit to mark
+/// them opaque.
+pub(crate) fn decode_etc2(
+    block: &[u8],
+    punch_through: bool,
+    texels: &mut Texels,
+) {
+    let bits = u64::from_be_bytes(block[..8].try_into().unwrap());
+    let field = |shift: u32, len: u32| ((bits >> shift) & ((1 << len) - 1)) as i32;
+    let differential = punch_through || field(33, 1) == 1;
+    let opaque = !punch_through || field(33, 1) == 1;
+    let expand4 = |value: i32| value * 17;
+    let expand5 = |value: i32| value << 3 | value >> 2;
+
+    if !differential {
+        let base = |shift: u32| [field(shift, 4), field(shift - 8, 4), field(shift - 16, 4)].map(expand4);
+        return decode_subblocks(bits, [base(60), base(56)], opaque, texels);
+    }
+
+    // Differences that overflow the second base color select the T, H and
+    // planar modes, by channel.
+    let base = [field(59, 5), field(51, 5), field(43, 5)];
+    let delta = [field(56, 3), field(48, 3), field(40, 3)].map(|delta| (delta << 29) >> 29);
+    let second: [i32; 3] = core::array::from_fn(|channel| base[channel] + delta[channel]);
+    let overflow = |channel: usize| !(0..32).contains(&second[channel]);
+    if !overflow(0) && !overflow(1) {
+        if overflow(2) {
+            return decode_planar(bits, texels);
+        }
+        return decode_subblocks(bits, [base.map(expand5), second.map(expand5)], opaque, texels);
+    }
+
+    let paint = if overflow(0) {
+        // T mode: one color, and another with two colors a distance from it.
+        let color1 = [field(59, 2) << 2 | field(56, 2), field(52, 4), field(48, 4)].map(expand4);
+        let color2 = [field(44, 4), field(40, 4), field(36, 4)].map(expand4);
+        let distance = DISTANCES[(field(34, 2) << 1 | field(32, 1)) as usize];
+        [color1, offset(color2, distance), color2, offset(color2, -distance)]
+    } else {
+        // H mode: two colors, each with two colors a distance either side.
+        let color1 = [field(59, 4), field(56, 3) << 1 | field(52, 1), field(51, 1) << 3 | field(47, 3)];
+        let color2 = [field(43, 4), field(39, 4), field(35, 4)];
+        let packed = |color: [i32; 3]| color[0] << 8 | color[1] << 4 | color[2];
+        let ordering = (packed(color1) >= packed(color2)) as i32;
+        let distance = DISTANCES[(field(34, 1) << 2 | field(32, 1) << 1 | ordering) as usize];
+        let (color1, color2) = (color1.map(expand4), color2.map(expand4));
+        [offset(color1, distance), offset(color1, -distance), offset(color2, distance), offset(color2, -distance)]
+    };
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let index = pixel_index(bits, i) as usize;
+        *texel = if !opaque && index == 2 {
+            [0.0; 4]
+        } else {
+            rgba(paint[index])
+        };
+    }
+}
+
+/// The 2-bit index of texel `i`. Indices run down columns, with their high
+/// and low bits in separate halves of the block.
+fn pixel_index(
+    bits: u64,
+    i: usize,
+) -> u64 {
+    let pixel = (i % 4) * 4 + i / 4;
+    ((bits >> (16 + pixel)) & 1) << 1 | (bits >> pixel) & 1
+}
+
+/// Decodes planar mode, a gradient between colors at the block's origin and
+/// its horizontal and vertical edges. It's always opaque.
+fn decode_planar(
+    bits: u64,
+    texels: &mut Texels,
+) {
+    let field = |shift: u32, len: u32| ((bits >> shift) & ((1 << len) - 1)) as i32;
+    let expand6 = |value: i32| value << 2 | value >> 4;
+    let expand7 = |value: i32| value << 1 | value >> 6;
+    let origin = [
+        expand6(field(57, 6)),
+        expand7(field(56, 1) << 6 | field(49, 6)),
+        expand6(field(48, 1) << 5 | field(43, 2) << 3 | field(39, 3)),
+    ];
+    let horizontal = [expand6(field(34, 5) << 1 | field(32, 1)), expand7(field(25, 7)), expand6(field(19, 6))];
+    let vertical = [expand6(field(13, 6)), expand7(field(6, 7)), expand6(field(0, 6))];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let (x, y) = ((i % 4) as i32, (i / 4) as i32);
+        let channel = |c: usize| {
+            let value = x * (horizontal[c] - origin[c]) + y * (vertical[c] - origin[c]) + 4 * origin[c] + 2;
+            (value >> 2).clamp(0, 255)
+        };
+        *texel = rgba([channel(0), channel(1), channel(2)]);
+    }
+}
+
+/// Decodes the individual and differential modes, which split the block
+/// into two halves, each a base color with luminance modifiers.
+fn decode_subblocks(
+    bits: u64,
+    bases: [[i32; 3]; 2],
+    opaque: bool,
+    texels: &mut Texels,
+) {
+    let tables = [(bits >> 37) & 7, (bits >> 34) & 7];
+    let flipped = (bits >> 32) & 1 == 1;
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let (x, y) = (i % 4, i / 4);
+        let half = if flipped {
+            y / 2
+        } else {
+            x / 2
+        };
+        let index = pixel_index(bits, i);
+        let [a, b] = MODIFIERS[tables[half] as usize];
+        // Without the opaque bit, index 2 is transparent and index 0 keeps
+        // the base color.
+        let modifier = match (index, opaque) {
+            (0, true) => a,
+            (0, false) => 0,
+            (1, _) => b,
+            (2, _) => -a,
+            _ => -b,
+        };
+        *texel = if !opaque && index == 2 {
+            [0.0; 4]
+        } else {
+            rgba(offset(bases[half], modifier))
+        };
+    }
+}
+
+fn offset(
+    color: [i32; 3],
+    amount: i32,
+) -> [i32; 3] {
+    color.map(|channel| (channel + amount).clamp(0, 255))
+}
+
+fn rgba(color: [i32; 3]) -> [f32; 4] {
+    let [r, g, b] = color.map(|channel| channel as f32 / 255.0);
+    [r, g, b, 1.0]
+}
+
+/// Decodes an 8-byte EAC block to one channel, row by row.
+pub(crate) fn decode_eac(
+    block: &[u8],
+    channel: EacChannel,
+) -> [f32; 16] {
+    let bits = u64::from_be_bytes(block[..8].try_into().unwrap());
+    let base = (bits >> 56) as u8;
+    let multiplier = ((bits >> 52) & 0xF) as i32;
+    let modifiers = EAC_MODIFIERS[((bits >> 48) & 0xF) as usize];
+    core::array::from_fn(|i| {
+        let pixel = (i % 4) * 4 + i / 4;
+        let modifier = modifiers[((bits >> (45 - 3 * pixel)) & 7) as usize];
+        // 11-bit blocks scale modifiers by eight, or by one with a zero
+        // multiplier.
+        let scaled = match multiplier {
+            0 => modifier,
+            _ => modifier * multiplier * 8,
+        };
+        match channel {
+            EacChannel::Alpha => (base as i32 + modifier * multiplier).clamp(0, 255) as f32 / 255.0,
+            EacChannel::Unorm11 => (base as i32 * 8 + 4 + scaled).clamp(0, 2047) as f32 / 2047.0,
+            EacChannel::Snorm11 => ((base as i8).max(-127) as i32 * 8 + scaled).clamp(-1023, 1023) as f32 / 1023.0,
+        }
+    })
+}
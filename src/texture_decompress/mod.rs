@@ -0,0 +1,11 @@
+mod astc;
+mod bc;
+mod bptc;
+mod decompress;
+mod etc;
+mod transcode;
+
+pub use decompress::{
+    DecompressedFormat, TextureDecompressError, can_decompress, decompress_image, decompress_to_rgba,
+};
+pub use transcode::supports_compressed_format;
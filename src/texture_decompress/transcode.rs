@@ -0,0 +1,65 @@
+use std::borrow::Cow;
+
+use objc2::runtime::ProtocolObject;
+
+use super::{DecompressedFormat, TextureDecompressError, decompress_image};
+use crate::{
+    MTLDevice, MTLPixelFormat, TextureContainer, TextureUploadTarget, device_capabilities::compressed_format_supported,
+};
+
+/// Whether `device` can sample textures of block-compressed `format`.
+/// Uncompressed formats are always reported as supported.
+pub fn supports_compressed_format(
+    device: &ProtocolObject<dyn MTLDevice>,
+    format: MTLPixelFormat,
+) -> bool {
+    compressed_format_supported(
+        device.supports_bc_texture_compression(),
+        |family| device.supports_family(family),
+        format,
+    )
+}
+
+impl TextureContainer {
+    /// Decompresses every image to `output`, keeping the texture's type,
+    /// size, levels and key/value data.
+    pub fn decompressed(
+        &self,
+        output: DecompressedFormat,
+    ) -> Result<TextureContainer, TextureDecompressError> {
+        let source = self.target();
+        let format = source.pixel_format;
+        let mut data = Vec::new();
+        for image in self.images() {
+            let bytes = self.image_data(image);
+            for plane in 0..image.size.depth {
+                let plane = &bytes[plane * image.bytes_per_image..][..image.bytes_per_image];
+                data.extend(decompress_image(format, image.size.width, image.size.height, plane, output)?);
+            }
+        }
+        let target = TextureUploadTarget {
+            pixel_format: output.pixel_format(format),
+            ..*source
+        };
+        let mut container = TextureContainer::new(target, data)?;
+        for (key, value) in self.key_values() {
+            container = container.with_key_value(key.as_str(), value.as_slice());
+        }
+        Ok(container)
+    }
+
+    /// The container as is if `device` supports its pixel format, otherwise
+    /// decompressed to the format's [fallback](DecompressedFormat::fallback_for).
+    pub fn for_device(
+        &self,
+        device: &ProtocolObject<dyn MTLDevice>,
+    ) -> Result<Cow<'_, TextureContainer>, TextureDecompressError> {
+        let format = self.pixel_format();
+        if supports_compressed_format(device, format) {
+            return Ok(Cow::Borrowed(self));
+        }
+        let output =
+            DecompressedFormat::fallback_for(format).ok_or(TextureDecompressError::UnsupportedFormat(format))?;
+        self.decompressed(output).map(Cow::Owned)
+    }
+}
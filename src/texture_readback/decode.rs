@@ -0,0 +1,390 @@
+use core::fmt;
+
+use objc2::rc::Retained;
+use objc2_foundation::NSError;
+
+use super::RgbaImage;
+use crate::{
+    Float16, ImageLayoutError, MTLPixelFormat, MTLTextureType, image_layout::check_image_layout,
+    texture_upload::srgb_to_linear,
+};
+
+/// Errors raised while reading back or decoding texture images.
+#[derive(Debug)]
+pub enum TextureReadbackError {
+    /// Images of this pixel format can't be decoded.
+    UnsupportedFormat(MTLPixelFormat),
+    /// Readback can't copy from multisample textures or texture buffers.
+    UnsupportedTextureType(MTLTextureType),
+    /// A buffer can't hold an image's rows.
+    Layout(ImageLayoutError),
+    /// An image's pixel count doesn't match its width and height.
+    PixelCountMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    /// The texture has no such mipmap level.
+    LevelOutOfRange {
+        level: usize,
+        count: usize,
+    },
+    /// The texture has no such slice or cube face.
+    SliceOutOfRange {
+        slice: usize,
+        count: usize,
+    },
+    /// The region isn't a single image within the mipmap level.
+    RegionOutOfBounds {
+        level: usize,
+    },
+    /// Metal failed to create the staging buffer or command objects.
+    ResourceCreation,
+    /// The readback's command buffer failed.
+    CommandBuffer(Option<Retained<NSError>>),
+}
+
+impl fmt::Display for TextureReadbackError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::UnsupportedFormat(format) => write!(f, "can't decode {format:?} images"),
+            Self::UnsupportedTextureType(texture_type) => write!(f, "can't read back {texture_type:?} textures"),
+            Self::Layout(error) => write!(f, "{error}"),
+            Self::PixelCountMismatch {
+                expected,
+                actual,
+            } => write!(f, "image needs {expected} pixels but has {actual}"),
+            Self::LevelOutOfRange {
+                level,
+                count,
+            } => write!(f, "mipmap level {level} is out of range for {count} levels"),
+            Self::SliceOutOfRange {
+                slice,
+                count,
+            } => write!(f, "slice {slice} is out of range for {count} slices"),
+            Self::RegionOutOfBounds {
+                level,
+            } => write!(f, "region isn't a single image within mipmap level {level}"),
+            Self::ResourceCreation => f.write_str("failed to create a Metal resource"),
+            Self::CommandBuffer(Some(error)) => write!(f, "readback command buffer failed: {error}"),
+            Self::CommandBuffer(None) => f.write_str("readback command buffer failed"),
+        }
+    }
+}
+
+impl std::error::Error for TextureReadbackError {}
+
+impl From<ImageLayoutError> for TextureReadbackError {
+    fn from(error: ImageLayoutError) -> Self {
+        Self::Layout(error)
+    }
+}
+
+/// The size of one read back pixel of `format`, or `None` if it can't be
+/// decoded.
+pub const fn readback_bytes_per_pixel(format: MTLPixelFormat) -> Option<usize> {
+    use MTLPixelFormat as F;
+
+    let bytes = match format {
+        F::R8Unorm => 1,
+        F::RG8Unorm | F::R16Float => 2,
+        F::RGBA8Unorm
+        | F::RGBA8UnormSrgb
+        | F::BGRA8Unorm
+        | F::BGRA8UnormSrgb
+        | F::RGB10A2Unorm
+        | F::BGR10A2Unorm
+        | F::RG11B10Float
+        | F::RGB9E5Float
+        | F::RG16Float
+        | F::R32Float
+        | F::Depth32Float
+        | F::Depth24Unorm_Stencil8 => 4,
+        F::RGBA16Float | F::RG32Float => 8,
+        F::RGBA32Float => 16,
+        _ => return None,
+    };
+    Some(bytes)
+}
+
+/// Bytes read back from one texture image, in the layout [`decode_image`]
+/// expects.
+#[derive(Clone, Debug)]
+pub struct TextureReadback {
+    pixel_format: MTLPixelFormat,
+    width: usize,
+    height: usize,
+    bytes_per_row: usize,
+    data: Vec<u8>,
+}
+
+impl TextureReadback {
+    /// Wraps `data`, whose rows start `bytes_per_row` bytes apart.
+    pub fn new(
+        pixel_format: MTLPixelFormat,
+        width: usize,
+        height: usize,
+        bytes_per_row: usize,
+        data: Vec<u8>,
+    ) -> Result<Self, TextureReadbackError> {
+        check_layout(pixel_format, width, height, bytes_per_row, data.len())?;
+        Ok(Self {
+            pixel_format,
+            width,
+            height,
+            bytes_per_row,
+            data,
+        })
+    }
+
+    pub fn pixel_format(&self) -> MTLPixelFormat {
+        self.pixel_format
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn bytes_per_row(&self) -> usize {
+        self.bytes_per_row
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn decode(&self) -> Result<RgbaImage, TextureReadbackError> {
+        decode_image(self.pixel_format, self.width, self.height, self.bytes_per_row, &self.data)
+    }
+}
+
+/// Checks that `len` bytes hold `height` rows of `format` pixels
+/// `bytes_per_row` apart, returning the length of a row of pixels.
+fn check_layout(
+    format: MTLPixelFormat,
+    width: usize,
+    height: usize,
+    bytes_per_row: usize,
+    len: usize,
+) -> Result<usize, TextureReadbackError> {
+    let bytes_per_pixel = readback_bytes_per_pixel(format).ok_or(TextureReadbackError::UnsupportedFormat(format))?;
+    Ok(check_image_layout(bytes_per_pixel, width, height, bytes_per_row, len)?)
+}
+
+/// Decodes `height` rows of `format` pixels, `bytes_per_row` apart, to linear
+/// RGBA.
+///
+/// sRGB formats are decoded to linear. Formats without green, blue or alpha
+/// read them as 0, 0 and 1, as shaders do. Depth lands in red, and the stencil
+/// of `Depth24Unorm_Stencil8`, packed above 24 bits of depth, in green as
+/// `stencil / 255`.
+pub fn decode_image(
+    format: MTLPixelFormat,
+    width: usize,
+    height: usize,
+    bytes_per_row: usize,
+    data: &[u8],
+) -> Result<RgbaImage, TextureReadbackError> {
+    let row_len = check_layout(format, width, height, bytes_per_row, data.len())?;
+    let bytes_per_pixel = readback_bytes_per_pixel(format).ok_or(TextureReadbackError::UnsupportedFormat(format))?;
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let row = &data[y * bytes_per_row..y * bytes_per_row + row_len];
+        pixels.extend(row.chunks_exact(bytes_per_pixel).map(|pixel| decode_pixel(format, pixel)));
+    }
+    RgbaImage::from_pixels(width, height, pixels)
+}
+
+fn decode_pixel(
+    format: MTLPixelFormat,
+    bytes: &[u8],
+) -> [f32; 4] {
+    use MTLPixelFormat as F;
+
+    let unorm8 = |i: usize| bytes[i] as f32 / 255.0;
+    let half = |i: usize| Float16::from_bits(u16::from_le_bytes([bytes[2 * i], bytes[2 * i + 1]])).to_f32();
+    let float = |i: usize| f32::from_le_bytes(bytes[4 * i..4 * i + 4].try_into().unwrap());
+    let word = || u32::from_le_bytes(bytes[..4].try_into().unwrap());
+    let bits = |value: u32, shift: u32, len: u32| (value >> shift) & ((1 << len) - 1);
+
+    match format {
+        F::R8Unorm => [unorm8(0), 0.0, 0.0, 1.0],
+        F::RG8Unorm => [unorm8(0), unorm8(1), 0.0, 1.0],
+        F::RGBA8Unorm => [unorm8(0), unorm8(1), unorm8(2), unorm8(3)],
+        F::BGRA8Unorm => [unorm8(2), unorm8(1), unorm8(0), unorm8(3)],
+        F::RGBA8UnormSrgb | F::BGRA8UnormSrgb => {
+            let [r, b] = match format {
+                F::RGBA8UnormSrgb => [0, 2],
+                _ => [2, 0],
+            };
+            let color = |i: usize| srgb_to_linear(unorm8(i));
+            [color(r), color(1), color(b), unorm8(3)]
+        },
+        F::RGB10A2Unorm | F::BGR10A2Unorm => {
+            let value = word();
+            let channel = |shift: u32| bits(value, shift, 10) as f32 / 1023.0;
+            let alpha = bits(value, 30, 2) as f32 / 3.0;
+            match format {
+                F::RGB10A2Unorm => [channel(0), channel(10), channel(20), alpha],
+                _ => [channel(20), channel(10), channel(0), alpha],
+            }
+        },
+        F::RG11B10Float => {
+            let value = word();
+            [
+                unsigned_float(bits(value, 0, 11), 6),
+                unsigned_float(bits(value, 11, 11), 6),
+                unsigned_float(bits(value, 22, 10), 5),
+                1.0,
+            ]
+        },
+        F::RGB9E5Float => {
+            let value = word();
+            // Mantissas have no implicit leading one.
+            let scale = 2f32.powi(bits(value, 27, 5) as i32 - 15 - 9);
+            [bits(value, 0, 9) as f32 * scale, bits(value, 9, 9) as f32 * scale, bits(value, 18, 9) as f32 * scale, 1.0]
+        },
+        F::R16Float => [half(0), 0.0, 0.0, 1.0],
+        F::RG16Float => [half(0), half(1), 0.0, 1.0],
+        F::RGBA16Float => [half(0), half(1), half(2), half(3)],
+        F::R32Float | F::Depth32Float => [float(0), 0.0, 0.0, 1.0],
+        F::RG32Float => [float(0), float(1), 0.0, 1.0],
+        F::RGBA32Float => [float(0), float(1), float(2), float(3)],
+        F::Depth24Unorm_Stencil8 => {
+            let value = word();
+            [bits(value, 0, 24) as f32 / 16_777_215.0, bits(value, 24, 8) as f32 / 255.0, 0.0, 1.0]
+        },
+        _ => unreachable!("{format:?} has no readback size"),
+    }
+}
+
+/// Decodes an unsigned float with a 5-bit exponent and a
+/// `mantissa_bits`-bit mantissa, as packed by `RG11B10Float`.
+fn unsigned_float(
+    value: u32,
+    mantissa_bits: u32,
+) -> f32 {
+    let exponent = value >> mantissa_bits;
+    let mantissa = (value & ((1 << mantissa_bits) - 1)) as f32 / (1 << mantissa_bits) as f32;
+    match exponent {
+        0 => mantissa * 2f32.powi(-14),
+        31 if mantissa == 0.0 => f32::INFINITY,
+        31 => f32::NAN,
+        _ => (1.0 + mantissa) * 2f32.powi(exponent as i32 - 15),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_word(
+        format: MTLPixelFormat,
+        word: u32,
+    ) -> [f32; 4] {
+        decode_image(format, 1, 1, 4, &word.to_le_bytes()).unwrap().pixel(0, 0)
+    }
+
+    #[test]
+    fn packed_formats() {
+        let word = 1023 | 512 << 20 | 2 << 30;
+        assert_eq!(decode_word(MTLPixelFormat::RGB10A2Unorm, word), [1.0, 0.0, 512.0 / 1023.0, 2.0 / 3.0]);
+        assert_eq!(decode_word(MTLPixelFormat::BGR10A2Unorm, word), [512.0 / 1023.0, 0.0, 1.0, 2.0 / 3.0]);
+
+        // 1.0 and 0.5 in 11 bits, infinity in 10.
+        let word = 15 << 6 | (14 << 6) << 11 | (31 << 5) << 22;
+        assert_eq!(decode_word(MTLPixelFormat::RG11B10Float, word), [1.0, 0.5, f32::INFINITY, 1.0]);
+        // The smallest subnormal, 1.75 and NaN.
+        let word = 1 | (15 << 6 | 48) << 11 | (31 << 5 | 1) << 22;
+        let [r, g, b, _] = decode_word(MTLPixelFormat::RG11B10Float, word);
+        assert_eq!((r, g), (2f32.powi(-20), 1.75));
+        assert!(b.is_nan());
+
+        // Shared exponent 16 scales 9-bit mantissas by 2^-8.
+        let word = 256 | 128 << 9 | 3 << 18 | 16 << 27;
+        assert_eq!(decode_word(MTLPixelFormat::RGB9E5Float, word), [1.0, 0.5, 3.0 / 256.0, 1.0]);
+        assert_eq!(decode_word(MTLPixelFormat::RGB9E5Float, 511), [511.0 * 2f32.powi(-24), 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn depth_and_stencil() {
+        assert_eq!(
+            decode_word(MTLPixelFormat::Depth24Unorm_Stencil8, 0x00FF_FFFF | 128 << 24),
+            [1.0, 128.0 / 255.0, 0.0, 1.0]
+        );
+        assert_eq!(
+            decode_word(MTLPixelFormat::Depth24Unorm_Stencil8, 0x0080_0000),
+            [8_388_608.0 / 16_777_215.0, 0.0, 0.0, 1.0]
+        );
+        assert_eq!(decode_word(MTLPixelFormat::Depth32Float, 0.25f32.to_bits()), [0.25, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn srgb_to_linear_decoding() {
+        let close = |actual: [f32; 4], expected: [f32; 4]| {
+            actual.iter().zip(expected).all(|(actual, expected)| (actual - expected).abs() < 1e-4)
+        };
+        let data = [188, 10, 255, 128];
+        // 188 is on the power curve, 10 on the linear segment.
+        let expected = [0.502_886, 0.003_035, 1.0, 128.0 / 255.0];
+        let rgba = decode_image(MTLPixelFormat::RGBA8UnormSrgb, 1, 1, 4, &data).unwrap().pixel(0, 0);
+        assert!(close(rgba, expected), "{rgba:?}");
+        let bgra = decode_image(MTLPixelFormat::BGRA8UnormSrgb, 1, 1, 4, &data).unwrap().pixel(0, 0);
+        assert!(close(bgra, [expected[2], expected[1], expected[0], expected[3]]), "{bgra:?}");
+        // Without sRGB, values stay as stored.
+        let linear = decode_image(MTLPixelFormat::RGBA8Unorm, 1, 1, 4, &data).unwrap().pixel(0, 0);
+        assert_eq!(linear, [188.0 / 255.0, 10.0 / 255.0, 1.0, 128.0 / 255.0]);
+    }
+
+    #[test]
+    fn half_and_single_floats() {
+        let data = [0x00, 0x3c, 0x00, 0xc0];
+        assert_eq!(decode_image(MTLPixelFormat::RG16Float, 1, 1, 4, &data).unwrap().pixel(0, 0), [1.0, -2.0, 0.0, 1.0]);
+        let data: Vec<u8> = [0.5f32, -1.0].iter().flat_map(|value| value.to_le_bytes()).collect();
+        assert_eq!(decode_image(MTLPixelFormat::RG32Float, 1, 1, 8, &data).unwrap().pixel(0, 0), [0.5, -1.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn padded_rows_and_errors() {
+        // Two 2x1 rows of R8, 3 bytes apart, with the last row's padding
+        // omitted.
+        let data = [0, 255, 0xaa, 51, 102];
+        let image = decode_image(MTLPixelFormat::R8Unorm, 2, 2, 3, &data).unwrap();
+        let red: Vec<f32> = image.pixels().iter().map(|pixel| pixel[0]).collect();
+        assert_eq!(red, [0.0, 1.0, 0.2, 0.4]);
+        let readback = TextureReadback::new(MTLPixelFormat::R8Unorm, 2, 2, 3, data.to_vec()).unwrap();
+        assert_eq!(readback.decode().unwrap(), image);
+
+        assert!(matches!(
+            decode_image(MTLPixelFormat::R8Unorm, 2, 2, 3, &data[..4]),
+            Err(TextureReadbackError::Layout(ImageLayoutError::DataTooShort {
+                required: 5,
+                actual: 4,
+            }))
+        ));
+        assert!(matches!(
+            decode_image(MTLPixelFormat::RGBA8Unorm, 2, 1, 7, &[0; 8]),
+            Err(TextureReadbackError::Layout(ImageLayoutError::RowTooShort {
+                bytes_per_row: 7,
+                required: 8,
+            }))
+        ));
+        assert!(matches!(
+            decode_image(MTLPixelFormat::BC1_RGBA, 4, 4, 8, &[0; 8]),
+            Err(TextureReadbackError::UnsupportedFormat(MTLPixelFormat::BC1_RGBA))
+        ));
+        assert!(matches!(
+            RgbaImage::from_pixels(2, 2, vec![[0.0; 4]; 3]),
+            Err(TextureReadbackError::PixelCountMismatch {
+                expected: 4,
+                actual: 3,
+            })
+        ));
+    }
+}
@@ -0,0 +1,156 @@
+use std::{io, path::Path};
+
+use super::RgbaImage;
+
+const MAGIC: u32 = 20_000_630;
+/// Single-part scanline image.
+const VERSION: u32 = 2;
+const PIXEL_TYPE_FLOAT: u32 = 2;
+/// Channels are stored in alphabetical order.
+const CHANNELS: [(&str, usize); 4] = [("A", 3), ("B", 2), ("G", 1), ("R", 0)];
+
+impl RgbaImage {
+    /// Encodes the image as an uncompressed OpenEXR file of linear 32-bit
+    /// float RGBA.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image is empty or wider or taller than `i32::MAX`.
+    pub fn to_exr(&self) -> Vec<u8> {
+        assert!(self.width() > 0 && self.height() > 0, "OpenEXR images can't be empty");
+        let max_x = i32::try_from(self.width() - 1).expect("image is too wide for OpenEXR");
+        let max_y = i32::try_from(self.height() - 1).expect("image is too tall for OpenEXR");
+
+        let mut exr = Vec::new();
+        exr.extend_from_slice(&MAGIC.to_le_bytes());
+        exr.extend_from_slice(&VERSION.to_le_bytes());
+
+        let mut channels = Vec::new();
+        for (name, _) in CHANNELS {
+            channels.extend_from_slice(name.as_bytes());
+            channels.push(0);
+            channels.extend_from_slice(&PIXEL_TYPE_FLOAT.to_le_bytes());
+            // Perceptually logarithmic, reserved bytes, then x and y sampling.
+            channels.extend_from_slice(&[0; 4]);
+            channels.extend_from_slice(&1i32.to_le_bytes());
+            channels.extend_from_slice(&1i32.to_le_bytes());
+        }
+        channels.push(0);
+        let mut window = Vec::with_capacity(16);
+        for value in [0, 0, max_x, max_y] {
+            window.extend_from_slice(&value.to_le_bytes());
+        }
+        write_attribute(&mut exr, "channels", "chlist", &channels);
+        // No compression.
+        write_attribute(&mut exr, "compression", "compression", &[0]);
+        write_attribute(&mut exr, "dataWindow", "box2i", &window);
+        write_attribute(&mut exr, "displayWindow", "box2i", &window);
+        // Increasing y.
+        write_attribute(&mut exr, "lineOrder", "lineOrder", &[0]);
+        write_attribute(&mut exr, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+        write_attribute(&mut exr, "screenWindowCenter", "v2f", &[0; 8]);
+        write_attribute(&mut exr, "screenWindowWidth", "float", &1f32.to_le_bytes());
+        exr.push(0);
+
+        // One scanline per chunk, each with its y and data size, then each
+        // channel's row of values.
+        let row_len = self.width() * CHANNELS.len() * 4;
+        let offsets_start = exr.len();
+        let first_chunk = offsets_start + self.height() * 8;
+        for y in 0..self.height() {
+            let offset = (first_chunk + y * (8 + row_len)) as u64;
+            exr.extend_from_slice(&offset.to_le_bytes());
+        }
+        for y in 0..self.height() {
+            exr.extend_from_slice(&(y as i32).to_le_bytes());
+            exr.extend_from_slice(&(row_len as i32).to_le_bytes());
+            let row = self.row(y);
+            for (_, channel) in CHANNELS {
+                for pixel in row {
+                    exr.extend_from_slice(&pixel[channel].to_le_bytes());
+                }
+            }
+        }
+        exr
+    }
+
+    /// Writes [`to_exr`](Self::to_exr) to `path`.
+    pub fn write_exr(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> io::Result<()> {
+        std::fs::write(path, self.to_exr())
+    }
+}
+
+fn write_attribute(
+    exr: &mut Vec<u8>,
+    name: &str,
+    attribute_type: &str,
+    value: &[u8],
+) {
+    exr.extend_from_slice(name.as_bytes());
+    exr.push(0);
+    exr.extend_from_slice(attribute_type.as_bytes());
+    exr.push(0);
+    exr.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    exr.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attribute(
+        name: &str,
+        attribute_type: &str,
+        value: &[u8],
+    ) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_attribute(&mut bytes, name, attribute_type, value);
+        bytes
+    }
+
+    #[test]
+    fn exr_bytes() {
+        let pixels = vec![[1.0, 2.0, 3.0, 4.0], [5.0, 6.0, 7.0, 8.0], [-1.0, 0.5, 0.25, 0.0], [9.0, 10.0, 11.0, 12.0]];
+        let image = RgbaImage::from_pixels(2, 2, pixels).unwrap();
+        let exr = image.to_exr();
+
+        assert_eq!(exr[..8], [0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+        let channels = attribute("channels", "chlist", &[0; 73]);
+        assert_eq!(exr[8..8 + 16], *b"channels\0chlist\0");
+        assert_eq!(exr[24..28], 73u32.to_le_bytes());
+        assert_eq!(exr[28..31], *b"A\0\x02");
+        assert_eq!(exr[28 + 18..28 + 21], *b"B\0\x02");
+        let mut position = 8 + channels.len();
+        let window = [0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0];
+        for expected in [
+            attribute("compression", "compression", &[0]),
+            attribute("dataWindow", "box2i", &window),
+            attribute("displayWindow", "box2i", &window),
+            attribute("lineOrder", "lineOrder", &[0]),
+            attribute("pixelAspectRatio", "float", &1f32.to_le_bytes()),
+            attribute("screenWindowCenter", "v2f", &[0; 8]),
+            attribute("screenWindowWidth", "float", &1f32.to_le_bytes()),
+        ] {
+            assert_eq!(exr[position..position + expected.len()], expected);
+            position += expected.len();
+        }
+        assert_eq!(exr[position], 0);
+        position += 1;
+
+        // Two scanline offsets, then chunks of y, size and the A, B, G and R
+        // rows.
+        let row_len = 2 * 4 * 4;
+        let offset = |y: usize| u64::from_le_bytes(exr[position + 8 * y..position + 8 * y + 8].try_into().unwrap());
+        let first = position + 16;
+        assert_eq!((offset(0), offset(1)), (first as u64, (first + 8 + row_len) as u64));
+        assert_eq!(exr.len(), first + 2 * (8 + row_len));
+        let chunk = &exr[first + 8 + row_len..];
+        assert_eq!(chunk[..8], [1, 0, 0, 0, 32, 0, 0, 0]);
+        let values: Vec<f32> =
+            chunk[8..].chunks_exact(4).map(|value| f32::from_le_bytes(value.try_into().unwrap())).collect();
+        assert_eq!(values, [0.0, 12.0, 0.25, 11.0, 0.5, 10.0, -1.0, 9.0]);
+    }
+}
@@ -0,0 +1,11 @@
+mod decode;
+#[cfg(feature = "exr")]
+mod exr;
+#[cfg(feature = "png")]
+mod png;
+mod reader;
+mod rgba_image;
+
+pub use decode::{TextureReadback, TextureReadbackError, decode_image, readback_bytes_per_pixel};
+pub use reader::{PendingReadback, TextureReader};
+pub use rgba_image::RgbaImage;
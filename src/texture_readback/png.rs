@@ -0,0 +1,197 @@
+use std::{io, path::Path};
+
+use super::RgbaImage;
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// The largest payload of a stored deflate block.
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+impl RgbaImage {
+    /// Encodes the image as an 8-bit sRGB RGBA PNG. Pixel data is stored
+    /// without compression.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image is empty or wider or taller than `u32::MAX`.
+    pub fn to_png(&self) -> Vec<u8> {
+        assert!(self.width() > 0 && self.height() > 0, "PNG images can't be empty");
+        let width = u32::try_from(self.width()).expect("image is too wide for PNG");
+        let height = u32::try_from(self.height()).expect("image is too tall for PNG");
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&width.to_be_bytes());
+        header.extend_from_slice(&height.to_be_bytes());
+        // 8 bits per channel, RGBA, deflate, adaptive filtering, no interlace.
+        header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+        // Each row starts with its filter type, none.
+        let rgba = self.to_rgba8(true);
+        let mut scanlines = Vec::with_capacity(rgba.len() + self.height());
+        for row in rgba.chunks_exact(self.width() * 4) {
+            scanlines.push(0);
+            scanlines.extend_from_slice(row);
+        }
+
+        let mut png = SIGNATURE.to_vec();
+        write_chunk(&mut png, b"IHDR", &header);
+        // Perceptual rendering intent.
+        write_chunk(&mut png, b"sRGB", &[0]);
+        write_chunk(&mut png, b"IDAT", &zlib_stored(&scanlines));
+        write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    /// Writes [`to_png`](Self::to_png) to `path`.
+    pub fn write_png(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> io::Result<()> {
+        std::fs::write(path, self.to_png())
+    }
+}
+
+fn write_chunk(
+    png: &mut Vec<u8>,
+    chunk_type: &[u8; 4],
+    data: &[u8],
+) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(chunk_type);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// A zlib stream of stored deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // Deflate with a 32 KiB window and no preset dictionary; the check bits
+    // make the header a multiple of 31.
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        stream.push(last as u8);
+        stream.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        stream.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MODULUS: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 bytes is the most that can be summed before `b` may overflow.
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= MODULUS;
+        b %= MODULUS;
+    }
+    b << 16 | a
+}
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| CRC_TABLE[((crc ^ u32::from(byte)) & 0xFF) as usize] ^ (crc >> 8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Inflates a zlib stream of stored blocks, checking each block's length
+    /// complement and the Adler-32 trailer.
+    fn inflate_stored(stream: &[u8]) -> Vec<u8> {
+        assert_eq!(stream[..2], [0x78, 0x01]);
+        assert_eq!(u16::from_be_bytes([stream[0], stream[1]]) % 31, 0);
+        let mut data = Vec::new();
+        let mut position = 2;
+        loop {
+            let header = stream[position];
+            assert_eq!(header & !1, 0, "only stored blocks are written");
+            let len = u16::from_le_bytes([stream[position + 1], stream[position + 2]]);
+            let complement = u16::from_le_bytes([stream[position + 3], stream[position + 4]]);
+            assert_eq!(len, !complement);
+            position += 5;
+            data.extend_from_slice(&stream[position..position + len as usize]);
+            position += len as usize;
+            if header == 1 {
+                break;
+            }
+        }
+        assert_eq!(stream[position..], adler32(&data).to_be_bytes());
+        data
+    }
+
+    #[test]
+    fn checksums() {
+        // Reference values from zlib.
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+        // Long enough for the sums to need reducing.
+        assert_eq!(adler32(&[0xFF; 100_000]), 0x149A_302C);
+    }
+
+    #[test]
+    fn stored_blocks() {
+        assert_eq!(zlib_stored(&[]), [0x78, 0x01, 1, 0, 0, 0xFF, 0xFF, 0, 0, 0, 1]);
+
+        let data: Vec<u8> = (0..70_000).map(|i| (i * 7 % 256) as u8).collect();
+        let stream = zlib_stored(&data);
+        assert_eq!(stream.len(), 2 + 5 + 65_535 + 5 + 4_465 + 4);
+        assert_eq!(stream[2..7], [0, 0xFF, 0xFF, 0, 0]);
+        assert_eq!(stream[65_542..65_547], [1, 0x71, 0x11, 0x8E, 0xEE]);
+        assert_eq!(stream[stream.len() - 4..], 0x385E_3671u32.to_be_bytes());
+        assert_eq!(inflate_stored(&stream), data);
+
+        // Exactly one full block.
+        let full = vec![9; MAX_STORED_BLOCK];
+        let stream = zlib_stored(&full);
+        assert_eq!(stream[2..7], [1, 0xFF, 0xFF, 0, 0]);
+        assert_eq!(inflate_stored(&stream), full);
+    }
+
+    #[test]
+    fn png_bytes() {
+        let image = RgbaImage::from_pixels(2, 1, vec![[1.0, 0.0, 0.0, 1.0], [0.0, 0.0, 0.0, 0.5]]).unwrap();
+        // Written independently with Python's zlib.
+        let expected = [
+            0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52, 0x00, 0x00,
+            0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0xf4, 0x22, 0x7f, 0x8a, 0x00, 0x00, 0x00,
+            0x01, 0x73, 0x52, 0x47, 0x42, 0x00, 0xae, 0xce, 0x1c, 0xe9, 0x00, 0x00, 0x00, 0x14, 0x49, 0x44, 0x41, 0x54,
+            0x78, 0x01, 0x01, 0x09, 0x00, 0xf6, 0xff, 0x00, 0xff, 0x00, 0x00, 0xff, 0x00, 0x00, 0x00, 0x80, 0x0d, 0x7c,
+            0x02, 0x7f, 0x6e, 0x32, 0x6e, 0x2e, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+        ];
+        assert_eq!(image.to_png(), expected);
+    }
+}
@@ -0,0 +1,202 @@
+use core::slice;
+
+use objc2::{rc::Retained, runtime::ProtocolObject};
+
+use super::{TextureReadback, TextureReadbackError, readback_bytes_per_pixel};
+use crate::{
+    ImageLayoutError, MTLBlitCommandEncoder, MTLBlitOption, MTLBuffer, MTLCommandBuffer, MTLCommandBufferStatus,
+    MTLCommandEncoder, MTLCommandQueue, MTLDevice, MTLPixelFormat, MTLRegion, MTLResourceOptions, MTLTexture,
+    MTLTextureType, TextureUploadTarget, staging_buffer::new_staging_buffer,
+};
+
+/// Copies texture images into shared staging buffers for the CPU to read.
+pub struct TextureReader {
+    device: Retained<ProtocolObject<dyn MTLDevice>>,
+}
+
+impl TextureReader {
+    pub fn new(device: Retained<ProtocolObject<dyn MTLDevice>>) -> Self {
+        Self {
+            device,
+        }
+    }
+
+    pub fn device(&self) -> &ProtocolObject<dyn MTLDevice> {
+        &self.device
+    }
+
+    /// Encodes a copy of `region` of `texture`'s mipmap `level` and array
+    /// slice or cube face `slice` into a new staging buffer. The region must
+    /// be one image deep; 3D textures select their depth plane with
+    /// `region.origin.z`.
+    ///
+    /// Read the staging buffer with [`PendingReadback::finish`] once the
+    /// encoder's command buffer has completed.
+    pub fn encode_readback(
+        &self,
+        encoder: &ProtocolObject<dyn MTLBlitCommandEncoder>,
+        texture: &ProtocolObject<dyn MTLTexture>,
+        level: usize,
+        slice: usize,
+        region: MTLRegion,
+    ) -> Result<PendingReadback, TextureReadbackError> {
+        let pixel_format = texture.pixel_format();
+        let bytes_per_pixel =
+            readback_bytes_per_pixel(pixel_format).ok_or(TextureReadbackError::UnsupportedFormat(pixel_format))?;
+        let target = TextureUploadTarget::from_texture(texture);
+        if level >= target.mipmap_level_count {
+            return Err(TextureReadbackError::LevelOutOfRange {
+                level,
+                count: target.mipmap_level_count,
+            });
+        }
+        let slice_count = match target.texture_type {
+            MTLTextureType::Type3D => 1,
+            texture_type => match target.slice_count(level) {
+                0 => return Err(TextureReadbackError::UnsupportedTextureType(texture_type)),
+                count => count,
+            },
+        };
+        if slice >= slice_count {
+            return Err(TextureReadbackError::SliceOutOfRange {
+                slice,
+                count: slice_count,
+            });
+        }
+        let level_size = target.level_size(level);
+        let fits =
+            |origin: usize, size: usize, extent: usize| origin.checked_add(size).is_some_and(|end| end <= extent);
+        if region.size.depth != 1
+            || !fits(region.origin.x, region.size.width, level_size.width)
+            || !fits(region.origin.y, region.size.height, level_size.height)
+            || !fits(region.origin.z, 1, level_size.depth)
+        {
+            return Err(TextureReadbackError::RegionOutOfBounds {
+                level,
+            });
+        }
+
+        let (width, height) = (region.size.width, region.size.height);
+        let bytes_per_row = width.checked_mul(bytes_per_pixel).ok_or(ImageLayoutError::Overflow)?;
+        let image_len = bytes_per_row.checked_mul(height).ok_or(ImageLayoutError::Overflow)?;
+        // Combined depth and stencil is copied one aspect at a time, with the
+        // one-byte stencil after the depth.
+        let stencil_offset = (pixel_format == MTLPixelFormat::Depth24Unorm_Stencil8).then_some(image_len);
+        let staging_len = match stencil_offset {
+            Some(offset) => width * height + offset,
+            None => image_len,
+        };
+        let buffer = new_staging_buffer(&self.device, staging_len, MTLResourceOptions::STORAGE_MODE_SHARED)
+            .ok_or(TextureReadbackError::ResourceCreation)?;
+
+        match stencil_offset {
+            Some(stencil_offset) => {
+                encoder.copy_from_texture_to_buffer_with_options(
+                    texture,
+                    slice,
+                    level,
+                    region.origin,
+                    region.size,
+                    &buffer,
+                    0,
+                    bytes_per_row,
+                    image_len,
+                    MTLBlitOption::DepthFromDepthStencil,
+                );
+                encoder.copy_from_texture_to_buffer_with_options(
+                    texture,
+                    slice,
+                    level,
+                    region.origin,
+                    region.size,
+                    &buffer,
+                    stencil_offset,
+                    width,
+                    width * height,
+                    MTLBlitOption::StencilFromDepthStencil,
+                );
+            },
+            None => encoder.copy_from_texture_to_buffer(
+                texture,
+                slice,
+                level,
+                region.origin,
+                region.size,
+                &buffer,
+                0,
+                bytes_per_row,
+                image_len,
+            ),
+        }
+
+        Ok(PendingReadback {
+            buffer,
+            pixel_format,
+            width,
+            height,
+            stencil_offset,
+        })
+    }
+
+    /// Reads back an image on a new command buffer from `command_queue` and
+    /// waits for it to complete. See [`encode_readback`](Self::encode_readback).
+    pub fn read(
+        &self,
+        command_queue: &ProtocolObject<dyn MTLCommandQueue>,
+        texture: &ProtocolObject<dyn MTLTexture>,
+        level: usize,
+        slice: usize,
+        region: MTLRegion,
+    ) -> Result<TextureReadback, TextureReadbackError> {
+        let command_buffer = command_queue.command_buffer().ok_or(TextureReadbackError::ResourceCreation)?;
+        let encoder = command_buffer.blit_command_encoder().ok_or(TextureReadbackError::ResourceCreation)?;
+        let result = self.encode_readback(&encoder, texture, level, slice, region);
+        encoder.end_encoding();
+        let pending = result?;
+
+        command_buffer.commit();
+        command_buffer.wait_until_completed();
+        match command_buffer.status() {
+            MTLCommandBufferStatus::Error => Err(TextureReadbackError::CommandBuffer(command_buffer.error())),
+            _ => Ok(pending.finish()),
+        }
+    }
+}
+
+/// A staging buffer that an encoded readback copies into.
+pub struct PendingReadback {
+    buffer: Retained<ProtocolObject<dyn MTLBuffer>>,
+    pixel_format: MTLPixelFormat,
+    width: usize,
+    height: usize,
+    stencil_offset: Option<usize>,
+}
+
+impl PendingReadback {
+    pub fn buffer(&self) -> &ProtocolObject<dyn MTLBuffer> {
+        &self.buffer
+    }
+
+    /// Copies the image out of the staging buffer. Call once the command
+    /// buffer holding the copy has completed.
+    pub fn finish(self) -> TextureReadback {
+        let pixel_count = self.width * self.height;
+        let bytes_per_row = self.width * readback_bytes_per_pixel(self.pixel_format).unwrap_or(0);
+        let len = bytes_per_row * self.height + self.stencil_offset.map_or(0, |_| pixel_count);
+        let contents = unsafe { slice::from_raw_parts(self.buffer.contents().as_ptr().cast::<u8>(), len) };
+        let data = match self.stencil_offset {
+            // Pack 24 bits of depth with the stencil above them.
+            Some(offset) => contents[..offset]
+                .chunks_exact(4)
+                .zip(&contents[offset..])
+                .flat_map(|(depth, &stencil)| {
+                    let depth = u32::from_le_bytes(depth.try_into().unwrap()) & 0x00FF_FFFF;
+                    (depth | u32::from(stencil) << 24).to_le_bytes()
+                })
+                .collect(),
+            None => contents.to_vec(),
+        };
+        TextureReadback::new(self.pixel_format, self.width, self.height, bytes_per_row, data)
+            .expect("staging buffers hold a whole image")
+    }
+}
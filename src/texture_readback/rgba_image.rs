@@ -0,0 +1,108 @@
+use super::TextureReadbackError;
+use crate::{ImageLayoutError, texture_upload::linear_to_srgb};
+
+/// An image of linear `f32` RGBA pixels, stored row by row from the top.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RgbaImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<[f32; 4]>,
+}
+
+impl RgbaImage {
+    /// An image of transparent black pixels.
+    pub fn new(
+        width: usize,
+        height: usize,
+    ) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![[0.0; 4]; width * height],
+        }
+    }
+
+    pub fn from_pixels(
+        width: usize,
+        height: usize,
+        pixels: Vec<[f32; 4]>,
+    ) -> Result<Self, TextureReadbackError> {
+        let expected = width.checked_mul(height).ok_or(ImageLayoutError::Overflow)?;
+        if pixels.len() != expected {
+            return Err(TextureReadbackError::PixelCountMismatch {
+                expected,
+                actual: pixels.len(),
+            });
+        }
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[[f32; 4]] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [[f32; 4]] {
+        &mut self.pixels
+    }
+
+    /// The pixels of row `y`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `y` is out of bounds.
+    pub fn row(
+        &self,
+        y: usize,
+    ) -> &[[f32; 4]] {
+        assert!(y < self.height, "row {y} is out of bounds for height {}", self.height);
+        &self.pixels[y * self.width..(y + 1) * self.width]
+    }
+
+    /// The pixel at `(x, y)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `(x, y)` is out of bounds.
+    pub fn pixel(
+        &self,
+        x: usize,
+        y: usize,
+    ) -> [f32; 4] {
+        assert!(x < self.width, "column {x} is out of bounds for width {}", self.width);
+        self.row(y)[x]
+    }
+
+    /// Quantizes the pixels to packed 8-bit RGBA, sRGB-encoding color if
+    /// `srgb` is set. Alpha is always linear and values are clamped to
+    /// `[0, 1]`.
+    pub fn to_rgba8(
+        &self,
+        srgb: bool,
+    ) -> Vec<u8> {
+        let quantize = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let mut bytes = Vec::with_capacity(self.pixels.len() * 4);
+        for pixel in &self.pixels {
+            for (channel, &value) in pixel.iter().enumerate() {
+                let value = if srgb && channel < 3 {
+                    linear_to_srgb(value.max(0.0))
+                } else {
+                    value
+                };
+                bytes.push(quantize(value));
+            }
+        }
+        bytes
+    }
+}
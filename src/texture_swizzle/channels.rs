@@ -0,0 +1,252 @@
+use crate::{MTLTextureSwizzle, MTLTextureSwizzleChannels};
+
+impl MTLTextureSwizzle {
+    /// The component this swizzle reads from `pixel`, in RGBA order, with
+    /// `Zero` as `T::default()` and `One` as `one`.
+    pub fn select<T: Copy + Default>(
+        self,
+        pixel: [T; 4],
+        one: T,
+    ) -> T {
+        match self {
+            Self::Zero => T::default(),
+            Self::One => one,
+            Self::Red => pixel[0],
+            Self::Green => pixel[1],
+            Self::Blue => pixel[2],
+            Self::Alpha => pixel[3],
+        }
+    }
+}
+
+impl MTLTextureSwizzleChannels {
+    /// Each channel reads itself.
+    pub const IDENTITY: Self = Self::from_array([
+        MTLTextureSwizzle::Red,
+        MTLTextureSwizzle::Green,
+        MTLTextureSwizzle::Blue,
+        MTLTextureSwizzle::Alpha,
+    ]);
+    /// Swaps red and blue, between RGBA and BGRA.
+    pub const BGRA: Self = Self::from_array([
+        MTLTextureSwizzle::Blue,
+        MTLTextureSwizzle::Green,
+        MTLTextureSwizzle::Red,
+        MTLTextureSwizzle::Alpha,
+    ]);
+    /// Reads alpha-first data, stored ARGB in RGBA channels.
+    pub const ARGB: Self = Self::from_array([
+        MTLTextureSwizzle::Green,
+        MTLTextureSwizzle::Blue,
+        MTLTextureSwizzle::Alpha,
+        MTLTextureSwizzle::Red,
+    ]);
+    /// Ignores alpha, reading it as one.
+    pub const OPAQUE: Self = Self::from_array([
+        MTLTextureSwizzle::Red,
+        MTLTextureSwizzle::Green,
+        MTLTextureSwizzle::Blue,
+        MTLTextureSwizzle::One,
+    ]);
+    /// Black with red's value as alpha, like `A8Unorm`.
+    pub const RED_AS_ALPHA: Self = Self::from_array([
+        MTLTextureSwizzle::Zero,
+        MTLTextureSwizzle::Zero,
+        MTLTextureSwizzle::Zero,
+        MTLTextureSwizzle::Red,
+    ]);
+    /// Grey from red, opaque.
+    pub const LUMINANCE: Self = Self::from_array([
+        MTLTextureSwizzle::Red,
+        MTLTextureSwizzle::Red,
+        MTLTextureSwizzle::Red,
+        MTLTextureSwizzle::One,
+    ]);
+    /// Grey from red, with green as alpha.
+    pub const LUMINANCE_ALPHA: Self = Self::from_array([
+        MTLTextureSwizzle::Red,
+        MTLTextureSwizzle::Red,
+        MTLTextureSwizzle::Red,
+        MTLTextureSwizzle::Green,
+    ]);
+    /// Red in every channel.
+    pub const INTENSITY: Self = Self::from_array([
+        MTLTextureSwizzle::Red,
+        MTLTextureSwizzle::Red,
+        MTLTextureSwizzle::Red,
+        MTLTextureSwizzle::Red,
+    ]);
+
+    /// The swizzles of red, green, blue and alpha.
+    pub const fn from_array(channels: [MTLTextureSwizzle; 4]) -> Self {
+        let [red, green, blue, alpha] = channels;
+        Self {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+
+    pub const fn to_array(self) -> [MTLTextureSwizzle; 4] {
+        [self.red, self.green, self.blue, self.alpha]
+    }
+
+    pub fn is_identity(self) -> bool {
+        self == Self::IDENTITY
+    }
+
+    /// The swizzle that reads like applying `self`, then `next` to the
+    /// result, as a view with swizzle `next` of a texture with swizzle
+    /// `self` does.
+    pub fn then(
+        self,
+        next: Self,
+    ) -> Self {
+        let channels = self.to_array();
+        Self::from_array(next.to_array().map(|swizzle| match swizzle {
+            MTLTextureSwizzle::Zero | MTLTextureSwizzle::One => swizzle,
+            MTLTextureSwizzle::Red => channels[0],
+            MTLTextureSwizzle::Green => channels[1],
+            MTLTextureSwizzle::Blue => channels[2],
+            MTLTextureSwizzle::Alpha => channels[3],
+        }))
+    }
+
+    /// The swizzle undoing `self`, so `self.then(inverse)` is the identity.
+    /// Only swizzles that read each channel exactly once have one.
+    pub fn inverse(self) -> Option<Self> {
+        let mut inverse = [MTLTextureSwizzle::Zero; 4];
+        let outputs =
+            [MTLTextureSwizzle::Red, MTLTextureSwizzle::Green, MTLTextureSwizzle::Blue, MTLTextureSwizzle::Alpha];
+        for (output, swizzle) in outputs.into_iter().zip(self.to_array()) {
+            let source = match swizzle {
+                MTLTextureSwizzle::Zero | MTLTextureSwizzle::One => return None,
+                MTLTextureSwizzle::Red => 0,
+                MTLTextureSwizzle::Green => 1,
+                MTLTextureSwizzle::Blue => 2,
+                MTLTextureSwizzle::Alpha => 3,
+            };
+            if inverse[source] != MTLTextureSwizzle::Zero {
+                return None;
+            }
+            inverse[source] = output;
+        }
+        Some(Self::from_array(inverse))
+    }
+
+    /// `pixel`, in RGBA order, as sampling with this swizzle reads it. `Zero`
+    /// reads `T::default()` and `One` reads `one`, such as `255` for
+    /// normalized bytes or `1` for integer formats.
+    pub fn apply<T: Copy + Default>(
+        self,
+        pixel: [T; 4],
+        one: T,
+    ) -> [T; 4] {
+        self.to_array().map(|swizzle| swizzle.select(pixel, one))
+    }
+
+    /// [`apply`](Self::apply)s the swizzle to each of `pixels`.
+    pub fn apply_to_pixels<T: Copy + Default>(
+        self,
+        pixels: &mut [[T; 4]],
+        one: T,
+    ) {
+        if self.is_identity() {
+            return;
+        }
+        for pixel in pixels {
+            *pixel = self.apply(*pixel, one);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRESETS: [MTLTextureSwizzleChannels; 8] = [
+        MTLTextureSwizzleChannels::IDENTITY,
+        MTLTextureSwizzleChannels::BGRA,
+        MTLTextureSwizzleChannels::ARGB,
+        MTLTextureSwizzleChannels::OPAQUE,
+        MTLTextureSwizzleChannels::RED_AS_ALPHA,
+        MTLTextureSwizzleChannels::LUMINANCE,
+        MTLTextureSwizzleChannels::LUMINANCE_ALPHA,
+        MTLTextureSwizzleChannels::INTENSITY,
+    ];
+
+    #[test]
+    fn identity_is_default() {
+        assert_eq!(MTLTextureSwizzleChannels::default(), MTLTextureSwizzleChannels::IDENTITY);
+        for preset in PRESETS {
+            assert_eq!(preset.then(MTLTextureSwizzleChannels::IDENTITY), preset);
+            assert_eq!(MTLTextureSwizzleChannels::IDENTITY.then(preset), preset);
+        }
+    }
+
+    #[test]
+    fn apply() {
+        let pixel = [10u8, 20, 30, 40];
+        assert_eq!(MTLTextureSwizzleChannels::BGRA.apply(pixel, 255), [30, 20, 10, 40]);
+        assert_eq!(MTLTextureSwizzleChannels::ARGB.apply(pixel, 255), [20, 30, 40, 10]);
+        assert_eq!(MTLTextureSwizzleChannels::OPAQUE.apply(pixel, 255), [10, 20, 30, 255]);
+        assert_eq!(MTLTextureSwizzleChannels::RED_AS_ALPHA.apply(pixel, 255), [0, 0, 0, 10]);
+        assert_eq!(MTLTextureSwizzleChannels::LUMINANCE_ALPHA.apply(pixel, 255), [10, 10, 10, 20]);
+        assert_eq!(MTLTextureSwizzleChannels::LUMINANCE.apply([0.5f32, 0.0, 0.0, 0.0], 1.0), [0.5, 0.5, 0.5, 1.0]);
+
+        let mut pixels = [[1u8, 2, 3, 4], [5, 6, 7, 8]];
+        MTLTextureSwizzleChannels::BGRA.apply_to_pixels(&mut pixels, 255);
+        assert_eq!(pixels, [[3, 2, 1, 4], [7, 6, 5, 8]]);
+    }
+
+    #[test]
+    fn composition_matches_applying_in_turn() {
+        let pixel = [10u8, 20, 30, 40];
+        for first in PRESETS {
+            for second in PRESETS {
+                assert_eq!(
+                    first.then(second).apply(pixel, 255),
+                    second.apply(first.apply(pixel, 255), 255),
+                    "{first:?} then {second:?}"
+                );
+            }
+        }
+        assert_eq!(
+            MTLTextureSwizzleChannels::BGRA.then(MTLTextureSwizzleChannels::BGRA),
+            MTLTextureSwizzleChannels::IDENTITY
+        );
+        assert_eq!(
+            MTLTextureSwizzleChannels::BGRA.then(MTLTextureSwizzleChannels::OPAQUE),
+            MTLTextureSwizzleChannels::from_array([
+                MTLTextureSwizzle::Blue,
+                MTLTextureSwizzle::Green,
+                MTLTextureSwizzle::Red,
+                MTLTextureSwizzle::One,
+            ])
+        );
+    }
+
+    #[test]
+    fn inverse() {
+        for preset in PRESETS {
+            let Some(inverse) = preset.inverse() else {
+                continue;
+            };
+            assert_eq!(preset.then(inverse), MTLTextureSwizzleChannels::IDENTITY, "{preset:?}");
+            assert_eq!(inverse.then(preset), MTLTextureSwizzleChannels::IDENTITY, "{preset:?}");
+        }
+        assert_eq!(MTLTextureSwizzleChannels::BGRA.inverse(), Some(MTLTextureSwizzleChannels::BGRA));
+        assert_eq!(
+            MTLTextureSwizzleChannels::ARGB.inverse(),
+            Some(MTLTextureSwizzleChannels::from_array([
+                MTLTextureSwizzle::Alpha,
+                MTLTextureSwizzle::Red,
+                MTLTextureSwizzle::Green,
+                MTLTextureSwizzle::Blue,
+            ]))
+        );
+        assert_eq!(MTLTextureSwizzleChannels::OPAQUE.inverse(), None);
+        assert_eq!(MTLTextureSwizzleChannels::LUMINANCE_ALPHA.inverse(), None);
+    }
+}
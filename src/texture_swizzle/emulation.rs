@@ -0,0 +1,99 @@
+use crate::{MTLPixelFormat, MTLTextureSwizzleChannels};
+
+/// Channel layouts that a swizzled texture of another pixel format can stand
+/// in for, such as legacy luminance formats Metal lacks, or formats that
+/// can't be written or rendered to on some devices.
+///
+/// Texels keep their bytes: data in the emulated layout uploads unchanged to
+/// a texture of the emulating format, and sampling it through the swizzle
+/// reads what the emulated format would.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum EmulatedTextureFormat {
+    /// `A8Unorm`.
+    Alpha8,
+    /// One normalized byte of grey, opaque.
+    Luminance8,
+    /// Normalized bytes of grey and alpha.
+    LuminanceAlpha8,
+    /// One normalized byte read into every channel.
+    Intensity8,
+    /// `BGRA8Unorm`.
+    BGRA8Unorm,
+    /// `BGRA8UnormSrgb`.
+    BGRA8UnormSrgb,
+    /// Normalized bytes of alpha, red, green and blue.
+    ARGB8Unorm,
+    /// Normalized bytes of red, green and blue, with a fourth byte ignored.
+    RGBX8Unorm,
+}
+
+impl EmulatedTextureFormat {
+    /// The pixel format to create textures with, and the swizzle that makes
+    /// them read as this format.
+    pub fn emulation(self) -> (MTLPixelFormat, MTLTextureSwizzleChannels) {
+        match self {
+            Self::Alpha8 => (MTLPixelFormat::R8Unorm, MTLTextureSwizzleChannels::RED_AS_ALPHA),
+            Self::Luminance8 => (MTLPixelFormat::R8Unorm, MTLTextureSwizzleChannels::LUMINANCE),
+            Self::LuminanceAlpha8 => (MTLPixelFormat::RG8Unorm, MTLTextureSwizzleChannels::LUMINANCE_ALPHA),
+            Self::Intensity8 => (MTLPixelFormat::R8Unorm, MTLTextureSwizzleChannels::INTENSITY),
+            Self::BGRA8Unorm => (MTLPixelFormat::RGBA8Unorm, MTLTextureSwizzleChannels::BGRA),
+            Self::BGRA8UnormSrgb => (MTLPixelFormat::RGBA8UnormSrgb, MTLTextureSwizzleChannels::BGRA),
+            Self::ARGB8Unorm => (MTLPixelFormat::RGBA8Unorm, MTLTextureSwizzleChannels::ARGB),
+            Self::RGBX8Unorm => (MTLPixelFormat::RGBA8Unorm, MTLTextureSwizzleChannels::OPAQUE),
+        }
+    }
+
+    /// The emulation of `format`, for the pixel formats this can emulate.
+    pub fn from_pixel_format(format: MTLPixelFormat) -> Option<Self> {
+        match format {
+            MTLPixelFormat::A8Unorm => Some(Self::Alpha8),
+            MTLPixelFormat::BGRA8Unorm => Some(Self::BGRA8Unorm),
+            MTLPixelFormat::BGRA8UnormSrgb => Some(Self::BGRA8UnormSrgb),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emulations_read_like_the_emulated_format() {
+        // Bytes of one texel, and the RGBA the emulated format samples them as.
+        let cases = [
+            (EmulatedTextureFormat::Alpha8, &[200u8][..], [0, 0, 0, 200]),
+            (EmulatedTextureFormat::Luminance8, &[200], [200, 200, 200, 255]),
+            (EmulatedTextureFormat::LuminanceAlpha8, &[200, 100], [200, 200, 200, 100]),
+            (EmulatedTextureFormat::Intensity8, &[200], [200, 200, 200, 200]),
+            (EmulatedTextureFormat::BGRA8Unorm, &[1, 2, 3, 4], [3, 2, 1, 4]),
+            (EmulatedTextureFormat::BGRA8UnormSrgb, &[1, 2, 3, 4], [3, 2, 1, 4]),
+            (EmulatedTextureFormat::ARGB8Unorm, &[4, 1, 2, 3], [1, 2, 3, 4]),
+            (EmulatedTextureFormat::RGBX8Unorm, &[1, 2, 3, 99], [1, 2, 3, 255]),
+        ];
+        for (emulated, bytes, expected) in cases {
+            let (format, swizzle) = emulated.emulation();
+            assert_eq!(format.bytes_per_block(), Some(bytes.len()), "{emulated:?}");
+
+            // Channels missing from the format read as zero, and alpha as one.
+            let mut texel = [0, 0, 0, 255];
+            texel[..bytes.len()].copy_from_slice(bytes);
+            assert_eq!(swizzle.apply(texel, 255), expected, "{emulated:?}");
+        }
+    }
+
+    #[test]
+    fn pixel_formats() {
+        for format in [MTLPixelFormat::A8Unorm, MTLPixelFormat::BGRA8Unorm, MTLPixelFormat::BGRA8UnormSrgb] {
+            let emulated = EmulatedTextureFormat::from_pixel_format(format).unwrap();
+            let (emulating, _) = emulated.emulation();
+            assert_ne!(emulating, format);
+            assert_eq!(emulating.bytes_per_block(), format.bytes_per_block());
+        }
+        assert_eq!(
+            EmulatedTextureFormat::from_pixel_format(MTLPixelFormat::BGRA8UnormSrgb).unwrap().emulation().0,
+            MTLPixelFormat::RGBA8UnormSrgb
+        );
+        assert_eq!(EmulatedTextureFormat::from_pixel_format(MTLPixelFormat::RGBA8Unorm), None);
+    }
+}
@@ -0,0 +1,4 @@
+mod channels;
+mod emulation;
+
+pub use emulation::EmulatedTextureFormat;
@@ -0,0 +1,386 @@
+use super::{HostImage, HostImageFormat, TextureUploadError};
+use crate::{Float16, MTLPixelFormat, image_layout::check_image_layout};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum ChannelEncoding {
+    Unorm8,
+    Snorm8,
+    Unorm16,
+    Snorm16,
+    Float16,
+    Float32,
+}
+
+impl ChannelEncoding {
+    const fn size(self) -> usize {
+        match self {
+            Self::Unorm8 | Self::Snorm8 => 1,
+            Self::Unorm16 | Self::Snorm16 | Self::Float16 => 2,
+            Self::Float32 => 4,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Packing {
+    /// One value per channel, the `i`th taken from source channel `order[i]`.
+    Channels {
+        encoding: ChannelEncoding,
+        count: usize,
+        order: [usize; 4],
+    },
+    /// Three 10-bit color channels from the low bits, taken from source
+    /// channels `order`, then 2 bits of alpha.
+    Rgb10A2 {
+        order: [usize; 3],
+    },
+}
+
+#[derive(Copy, Clone, Debug)]
+struct UploadFormat {
+    packing: Packing,
+    srgb: bool,
+}
+
+impl UploadFormat {
+    const fn bytes_per_pixel(&self) -> usize {
+        match self.packing {
+            Packing::Channels {
+                encoding,
+                count,
+                ..
+            } => encoding.size() * count,
+            Packing::Rgb10A2 {
+                ..
+            } => 4,
+        }
+    }
+}
+
+const RGBA: [usize; 4] = [0, 1, 2, 3];
+const BGRA: [usize; 4] = [2, 1, 0, 3];
+
+const fn upload_format(format: MTLPixelFormat) -> Option<UploadFormat> {
+    use ChannelEncoding::*;
+    use MTLPixelFormat as F;
+
+    let (encoding, count, order, srgb) = match format {
+        F::R8Unorm => (Unorm8, 1, RGBA, false),
+        F::R8UnormSrgb => (Unorm8, 1, RGBA, true),
+        F::R8Snorm => (Snorm8, 1, RGBA, false),
+        F::RG8Unorm => (Unorm8, 2, RGBA, false),
+        F::RG8UnormSrgb => (Unorm8, 2, RGBA, true),
+        F::RG8Snorm => (Snorm8, 2, RGBA, false),
+        F::RGBA8Unorm => (Unorm8, 4, RGBA, false),
+        F::RGBA8UnormSrgb => (Unorm8, 4, RGBA, true),
+        F::RGBA8Snorm => (Snorm8, 4, RGBA, false),
+        F::BGRA8Unorm => (Unorm8, 4, BGRA, false),
+        F::BGRA8UnormSrgb => (Unorm8, 4, BGRA, true),
+        F::R16Unorm => (Unorm16, 1, RGBA, false),
+        F::R16Snorm => (Snorm16, 1, RGBA, false),
+        F::RG16Unorm => (Unorm16, 2, RGBA, false),
+        F::RG16Snorm => (Snorm16, 2, RGBA, false),
+        F::RGBA16Unorm => (Unorm16, 4, RGBA, false),
+        F::RGBA16Snorm => (Snorm16, 4, RGBA, false),
+        F::R16Float => (Float16, 1, RGBA, false),
+        F::RG16Float => (Float16, 2, RGBA, false),
+        F::RGBA16Float => (Float16, 4, RGBA, false),
+        F::R32Float => (Float32, 1, RGBA, false),
+        F::RG32Float => (Float32, 2, RGBA, false),
+        F::RGBA32Float => (Float32, 4, RGBA, false),
+        F::RGB10A2Unorm => {
+            return Some(UploadFormat {
+                packing: Packing::Rgb10A2 {
+                    order: [0, 1, 2],
+                },
+                srgb: false,
+            });
+        },
+        F::BGR10A2Unorm => {
+            return Some(UploadFormat {
+                packing: Packing::Rgb10A2 {
+                    order: [2, 1, 0],
+                },
+                srgb: false,
+            });
+        },
+        _ => return None,
+    };
+    Some(UploadFormat {
+        packing: Packing::Channels {
+            encoding,
+            count,
+            order,
+        },
+        srgb,
+    })
+}
+
+/// The size of one pixel of `format`, or `None` if host images can't be
+/// converted to it.
+pub const fn upload_bytes_per_pixel(format: MTLPixelFormat) -> Option<usize> {
+    match upload_format(format) {
+        Some(format) => Some(format.bytes_per_pixel()),
+        None => None,
+    }
+}
+
+/// Converts `image` to `format`, writing rows `bytes_per_row` apart into
+/// `destination`.
+///
+/// Color is sRGB-encoded when only `format` is sRGB and decoded when only the
+/// image is; alpha is always linear. Values are
+/// clamped to the range of normalized formats and rounded to nearest.
+pub fn convert_image(
+    image: &HostImage<'_>,
+    format: MTLPixelFormat,
+    destination: &mut [u8],
+    bytes_per_row: usize,
+) -> Result<(), TextureUploadError> {
+    let target = upload_format(format).ok_or(TextureUploadError::UnsupportedFormat(format))?;
+    let row_len =
+        check_image_layout(target.bytes_per_pixel(), image.width(), image.height(), bytes_per_row, destination.len())?;
+    for y in 0..image.height() {
+        let start = y * bytes_per_row;
+        convert_row(image.row(y), image.format(), &target, &mut destination[start..start + row_len]);
+    }
+    Ok(())
+}
+
+fn convert_row(
+    source: &[u8],
+    source_format: HostImageFormat,
+    target: &UploadFormat,
+    destination: &mut [u8],
+) {
+    let source_pixels = source.chunks_exact(source_format.bytes_per_pixel());
+    let destination_pixels = destination.chunks_exact_mut(target.bytes_per_pixel());
+
+    // 8-bit sources going to 8-bit formats of the same encoding only need
+    // their channels reordered.
+    let reorder = match target.packing {
+        Packing::Channels {
+            encoding: ChannelEncoding::Unorm8,
+            order,
+            ..
+        } if source_format.bytes_per_pixel() == 4 && source_format.is_srgb() == target.srgb => Some(order),
+        _ => None,
+    };
+    if let Some(order) = reorder {
+        for (source, destination) in source_pixels.zip(destination_pixels) {
+            for (destination, &channel) in destination.iter_mut().zip(&order) {
+                *destination = source[channel];
+            }
+        }
+        return;
+    }
+
+    for (source, destination) in source_pixels.zip(destination_pixels) {
+        let mut pixel = read_pixel(source_format, source);
+        if source_format.is_srgb() != target.srgb {
+            let transfer = if target.srgb {
+                linear_to_srgb
+            } else {
+                srgb_to_linear
+            };
+            for channel in &mut pixel[..3] {
+                *channel = transfer(*channel);
+            }
+        }
+        write_pixel(target.packing, &pixel, destination);
+    }
+}
+
+fn read_pixel(
+    format: HostImageFormat,
+    bytes: &[u8],
+) -> [f32; 4] {
+    match format {
+        HostImageFormat::Rgba8Unorm | HostImageFormat::Rgba8UnormSrgb => {
+            core::array::from_fn(|i| bytes[i] as f32 / 255.0)
+        },
+        HostImageFormat::Rgba16Float => {
+            core::array::from_fn(|i| Float16::from_bits(u16::from_le_bytes([bytes[2 * i], bytes[2 * i + 1]])).to_f32())
+        },
+        HostImageFormat::Rgba32Float => {
+            core::array::from_fn(|i| f32::from_le_bytes(bytes[4 * i..4 * i + 4].try_into().unwrap()))
+        },
+    }
+}
+
+fn write_pixel(
+    packing: Packing,
+    pixel: &[f32; 4],
+    destination: &mut [u8],
+) {
+    match packing {
+        Packing::Channels {
+            encoding,
+            count,
+            order,
+        } => {
+            let size = encoding.size();
+            for (destination, &channel) in destination.chunks_exact_mut(size).zip(&order[..count]) {
+                let value = pixel[channel];
+                match encoding {
+                    ChannelEncoding::Unorm8 => destination[0] = unorm(value, 255.0) as u8,
+                    ChannelEncoding::Snorm8 => destination[0] = snorm(value, 127.0) as i8 as u8,
+                    ChannelEncoding::Unorm16 => {
+                        destination.copy_from_slice(&(unorm(value, 65535.0) as u16).to_le_bytes())
+                    },
+                    ChannelEncoding::Snorm16 => {
+                        destination.copy_from_slice(&(snorm(value, 32767.0) as i16).to_le_bytes())
+                    },
+                    ChannelEncoding::Float16 => {
+                        destination.copy_from_slice(&Float16::from_f32(value).to_bits().to_le_bytes())
+                    },
+                    ChannelEncoding::Float32 => destination.copy_from_slice(&value.to_le_bytes()),
+                }
+            }
+        },
+        Packing::Rgb10A2 {
+            order,
+        } => {
+            let packed = unorm(pixel[order[0]], 1023.0)
+                | unorm(pixel[order[1]], 1023.0) << 10
+                | unorm(pixel[order[2]], 1023.0) << 20
+                | unorm(pixel[3], 3.0) << 30;
+            destination.copy_from_slice(&packed.to_le_bytes());
+        },
+    }
+}
+
+fn unorm(
+    value: f32,
+    max: f32,
+) -> u32 {
+    (value.clamp(0.0, 1.0) * max).round() as u32
+}
+
+fn snorm(
+    value: f32,
+    max: f32,
+) -> i32 {
+    (value.clamp(-1.0, 1.0) * max).round() as i32
+}
+
+/// Encodes a linear value with the sRGB transfer function.
+pub(crate) fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Decodes an sRGB-encoded value to linear.
+pub(crate) fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.040_45 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ImageLayoutError;
+
+    fn convert(
+        format: HostImageFormat,
+        data: &[u8],
+        target: MTLPixelFormat,
+    ) -> Vec<u8> {
+        let image = HostImage::packed(format, data.len() / format.bytes_per_pixel(), 1, data).unwrap();
+        let row_len = image.width() * upload_bytes_per_pixel(target).unwrap();
+        let mut destination = vec![0; row_len];
+        convert_image(&image, target, &mut destination, row_len).unwrap();
+        destination
+    }
+
+    #[test]
+    fn srgb_transfer() {
+        assert!((linear_to_srgb(0.5) - 0.735_357).abs() < 1e-5);
+        assert!((srgb_to_linear(0.5) - 0.214_041).abs() < 1e-5);
+        // The linear segment near black.
+        assert_eq!(linear_to_srgb(0.001), 0.001 * 12.92);
+        assert_eq!(srgb_to_linear(0.02), 0.02 / 12.92);
+        for value in [0.0, 0.002, 0.1, 0.5, 0.9, 1.0] {
+            assert!((srgb_to_linear(linear_to_srgb(value)) - value).abs() < 1e-6);
+        }
+
+        // Color is encoded or decoded, alpha is left linear.
+        let encoded = convert(HostImageFormat::Rgba8Unorm, &[128, 255, 0, 64], MTLPixelFormat::RGBA8UnormSrgb);
+        assert_eq!(encoded, [188, 255, 0, 64]);
+        let decoded = convert(HostImageFormat::Rgba8UnormSrgb, &encoded, MTLPixelFormat::RGBA8Unorm);
+        assert_eq!(decoded, [128, 255, 0, 64]);
+        // Matching encodings only reorder.
+        let swizzled = convert(HostImageFormat::Rgba8UnormSrgb, &encoded, MTLPixelFormat::BGRA8UnormSrgb);
+        assert_eq!(swizzled, [0, 255, 188, 64]);
+    }
+
+    #[test]
+    fn half_packing() {
+        let pixel: Vec<u8> = [1.0f32, 0.5, -2.0, 65504.0].iter().flat_map(|value| value.to_le_bytes()).collect();
+        let half = convert(HostImageFormat::Rgba32Float, &pixel, MTLPixelFormat::RGBA16Float);
+        let bits: Vec<u16> = half.chunks_exact(2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]])).collect();
+        assert_eq!(bits, [0x3c00, 0x3800, 0xc000, 0x7bff]);
+
+        assert_eq!(convert(HostImageFormat::Rgba32Float, &pixel, MTLPixelFormat::RG16Float), half[..4]);
+        let float = convert(HostImageFormat::Rgba16Float, &half, MTLPixelFormat::RGBA32Float);
+        assert_eq!(float, pixel);
+    }
+
+    #[test]
+    fn ten_bit_packing() {
+        let pixel = [255, 0, 128, 128];
+        let rgb = convert(HostImageFormat::Rgba8Unorm, &pixel, MTLPixelFormat::RGB10A2Unorm);
+        assert_eq!(rgb, (1023u32 | 514 << 20 | 2 << 30).to_le_bytes());
+        let bgr = convert(HostImageFormat::Rgba8Unorm, &pixel, MTLPixelFormat::BGR10A2Unorm);
+        assert_eq!(bgr, (514u32 | 1023 << 20 | 2 << 30).to_le_bytes());
+
+        // Out of range floats clamp.
+        let pixel: Vec<u8> = [2.0f32, -1.0, 0.0, 1.0].iter().flat_map(|value| value.to_le_bytes()).collect();
+        let clamped = convert(HostImageFormat::Rgba32Float, &pixel, MTLPixelFormat::RGB10A2Unorm);
+        assert_eq!(clamped, (1023u32 | 3 << 30).to_le_bytes());
+    }
+
+    #[test]
+    fn padded_rows() {
+        // Two 2x1 rows, 12 bytes apart, with the last row's padding omitted.
+        let data = [1, 2, 3, 4, 5, 6, 7, 8, 0xaa, 0xaa, 0xaa, 0xaa, 9, 10, 11, 12, 13, 14, 15, 16];
+        let image = HostImage::new(HostImageFormat::Rgba8Unorm, 2, 2, 12, &data).unwrap();
+        assert_eq!(image.row(1), [9, 10, 11, 12, 13, 14, 15, 16]);
+
+        let mut destination = [0xee; 18];
+        convert_image(&image, MTLPixelFormat::BGRA8Unorm, &mut destination, 10).unwrap();
+        assert_eq!(destination, [3, 2, 1, 4, 7, 6, 5, 8, 0xee, 0xee, 11, 10, 9, 12, 15, 14, 13, 16]);
+
+        assert!(matches!(
+            HostImage::new(HostImageFormat::Rgba8Unorm, 2, 2, 7, &data),
+            Err(TextureUploadError::Layout(ImageLayoutError::RowTooShort {
+                bytes_per_row: 7,
+                required: 8,
+            }))
+        ));
+        assert!(matches!(
+            HostImage::new(HostImageFormat::Rgba8Unorm, 2, 2, 12, &data[..19]),
+            Err(TextureUploadError::Layout(ImageLayoutError::DataTooShort {
+                required: 20,
+                actual: 19,
+            }))
+        ));
+        assert!(matches!(
+            convert_image(&image, MTLPixelFormat::BGRA8Unorm, &mut destination[..17], 10),
+            Err(TextureUploadError::Layout(ImageLayoutError::DataTooShort {
+                required: 18,
+                actual: 17,
+            }))
+        ));
+        assert!(matches!(
+            convert_image(&image, MTLPixelFormat::Depth32Float, &mut destination, 10),
+            Err(TextureUploadError::UnsupportedFormat(MTLPixelFormat::Depth32Float))
+        ));
+    }
+}
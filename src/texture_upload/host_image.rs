@@ -0,0 +1,106 @@
+use super::TextureUploadError;
+use crate::{ImageLayoutError, image_layout::check_image_layout};
+
+/// Pixel layout of a [`HostImage`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum HostImageFormat {
+    /// Four `u8` channels holding linear values.
+    Rgba8Unorm,
+    /// Four `u8` channels with sRGB-encoded color and linear alpha.
+    Rgba8UnormSrgb,
+    /// Four IEEE binary16 channels holding linear values.
+    Rgba16Float,
+    /// Four `f32` channels holding linear values.
+    Rgba32Float,
+}
+
+impl HostImageFormat {
+    pub const fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::Rgba8Unorm | Self::Rgba8UnormSrgb => 4,
+            Self::Rgba16Float => 8,
+            Self::Rgba32Float => 16,
+        }
+    }
+
+    pub const fn is_srgb(self) -> bool {
+        matches!(self, Self::Rgba8UnormSrgb)
+    }
+}
+
+/// A borrowed CPU image whose rows may be padded. Multi-byte channels are
+/// little-endian.
+#[derive(Copy, Clone, Debug)]
+pub struct HostImage<'a> {
+    format: HostImageFormat,
+    width: usize,
+    height: usize,
+    bytes_per_row: usize,
+    data: &'a [u8],
+}
+
+impl<'a> HostImage<'a> {
+    /// Wraps `data`, whose rows start `bytes_per_row` bytes apart. The last
+    /// row doesn't need its padding.
+    pub fn new(
+        format: HostImageFormat,
+        width: usize,
+        height: usize,
+        bytes_per_row: usize,
+        data: &'a [u8],
+    ) -> Result<Self, TextureUploadError> {
+        check_image_layout(format.bytes_per_pixel(), width, height, bytes_per_row, data.len())?;
+        Ok(Self {
+            format,
+            width,
+            height,
+            bytes_per_row,
+            data,
+        })
+    }
+
+    /// Wraps `data` with tightly packed rows.
+    pub fn packed(
+        format: HostImageFormat,
+        width: usize,
+        height: usize,
+        data: &'a [u8],
+    ) -> Result<Self, TextureUploadError> {
+        let bytes_per_row = width.checked_mul(format.bytes_per_pixel()).ok_or(ImageLayoutError::Overflow)?;
+        Self::new(format, width, height, bytes_per_row, data)
+    }
+
+    pub fn format(&self) -> HostImageFormat {
+        self.format
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn bytes_per_row(&self) -> usize {
+        self.bytes_per_row
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// The pixels of row `y`, without padding.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `y` is out of bounds.
+    pub fn row(
+        &self,
+        y: usize,
+    ) -> &'a [u8] {
+        assert!(y < self.height, "row {y} is out of bounds for height {}", self.height);
+        let start = y * self.bytes_per_row;
+        &self.data[start..start + self.width * self.format.bytes_per_pixel()]
+    }
+}
@@ -0,0 +1,10 @@
+mod convert;
+mod host_image;
+mod plan;
+mod uploader;
+
+pub use convert::{convert_image, upload_bytes_per_pixel};
+pub(crate) use convert::{linear_to_srgb, srgb_to_linear};
+pub use host_image::{HostImage, HostImageFormat};
+pub use plan::{StagingCopy, TextureUploadError, TextureUploadTarget, UploadImage, UploadPlan};
+pub use uploader::TextureUploader;
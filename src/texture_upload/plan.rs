@@ -0,0 +1,420 @@
+use core::fmt;
+
+use objc2::rc::Retained;
+use objc2_foundation::NSError;
+
+use super::{HostImage, convert_image, upload_bytes_per_pixel};
+use crate::{ImageLayoutError, MTLOrigin, MTLPixelFormat, MTLSize, MTLTextureType};
+
+/// Alignment of each image in a staging buffer.
+const STAGING_ALIGNMENT: usize = 256;
+
+/// Errors raised while planning or performing texture uploads.
+#[derive(Debug)]
+pub enum TextureUploadError {
+    /// Host images can't be converted to this pixel format.
+    UnsupportedFormat(MTLPixelFormat),
+    /// Uploads can't target multisample textures or texture buffers.
+    UnsupportedTextureType(MTLTextureType),
+    /// A buffer can't hold an image's rows.
+    Layout(ImageLayoutError),
+    /// The texture has no such mipmap level.
+    LevelOutOfRange {
+        level: usize,
+        count: usize,
+    },
+    /// The mipmap level has no such slice, face or depth plane.
+    SliceOutOfRange {
+        level: usize,
+        slice: usize,
+        count: usize,
+    },
+    /// An image's width and height differ from its mipmap level's.
+    SizeMismatch {
+        level: usize,
+        expected: (usize, usize),
+        actual: (usize, usize),
+    },
+    /// Metal failed to create the staging buffer or command objects.
+    ResourceCreation,
+    /// The upload's command buffer failed.
+    CommandBuffer(Option<Retained<NSError>>),
+}
+
+impl fmt::Display for TextureUploadError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::UnsupportedFormat(format) => write!(f, "can't upload host images to {format:?} textures"),
+            Self::UnsupportedTextureType(texture_type) => {
+                write!(f, "can't upload host images to {texture_type:?} textures")
+            },
+            Self::Layout(error) => write!(f, "{error}"),
+            Self::LevelOutOfRange {
+                level,
+                count,
+            } => write!(f, "mipmap level {level} is out of range for {count} levels"),
+            Self::SliceOutOfRange {
+                level,
+                slice,
+                count,
+            } => write!(f, "slice {slice} is out of range for {count} slices at mipmap level {level}"),
+            Self::SizeMismatch {
+                level,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "image is {}x{} but mipmap level {level} is {}x{}",
+                actual.0, actual.1, expected.0, expected.1
+            ),
+            Self::ResourceCreation => f.write_str("failed to create a Metal resource"),
+            Self::CommandBuffer(Some(error)) => write!(f, "upload command buffer failed: {error}"),
+            Self::CommandBuffer(None) => f.write_str("upload command buffer failed"),
+        }
+    }
+}
+
+impl std::error::Error for TextureUploadError {}
+
+impl From<ImageLayoutError> for TextureUploadError {
+    fn from(error: ImageLayoutError) -> Self {
+        Self::Layout(error)
+    }
+}
+
+/// The properties of a texture that uploads are planned against.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct TextureUploadTarget {
+    pub texture_type: MTLTextureType,
+    pub pixel_format: MTLPixelFormat,
+    pub width: usize,
+    pub height: usize,
+    pub depth: usize,
+    pub mipmap_level_count: usize,
+    pub array_length: usize,
+}
+
+impl TextureUploadTarget {
+    /// The size of mipmap `level`.
+    pub fn level_size(
+        &self,
+        level: usize,
+    ) -> MTLSize {
+        let mip = |extent: usize| extent.checked_shr(level as u32).unwrap_or(0).max(1);
+        let depth = match self.texture_type {
+            MTLTextureType::Type3D => mip(self.depth),
+            _ => 1,
+        };
+        MTLSize::new(mip(self.width), mip(self.height), depth)
+    }
+
+    /// The number of images at mipmap `level`: array slices, cube faces
+    /// (six per cube) or, for 3D textures, depth planes.
+    pub fn slice_count(
+        &self,
+        level: usize,
+    ) -> usize {
+        match self.texture_type {
+            MTLTextureType::Type1D | MTLTextureType::Type2D => 1,
+            MTLTextureType::Type1DArray | MTLTextureType::Type2DArray => self.array_length,
+            MTLTextureType::Cube => 6,
+            MTLTextureType::CubeArray => 6 * self.array_length,
+            MTLTextureType::Type3D => self.level_size(level).depth,
+            MTLTextureType::Type2DMultisample
+            | MTLTextureType::Type2DMultisampleArray
+            | MTLTextureType::TextureBuffer => 0,
+        }
+    }
+}
+
+/// A host image and the texture image it replaces.
+#[derive(Copy, Clone, Debug)]
+pub struct UploadImage<'a> {
+    pub level: usize,
+    /// The array slice or cube face (`6 * cube + face`), or the depth plane of
+    /// a 3D texture.
+    pub slice: usize,
+    pub image: HostImage<'a>,
+}
+
+/// A blit copy from a staging buffer into one texture image.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct StagingCopy {
+    pub offset: usize,
+    pub bytes_per_row: usize,
+    pub bytes_per_image: usize,
+    pub size: MTLSize,
+    pub level: usize,
+    pub slice: usize,
+    pub origin: MTLOrigin,
+}
+
+/// The staging buffer layout and blit copies that upload images into a
+/// texture.
+#[derive(Clone, Debug)]
+pub struct UploadPlan<'a> {
+    pixel_format: MTLPixelFormat,
+    images: &'a [UploadImage<'a>],
+    copies: Vec<StagingCopy>,
+    staging_len: usize,
+}
+
+impl<'a> UploadPlan<'a> {
+    /// Plans one copy per image, each from its own 256-byte aligned region of
+    /// the staging buffer.
+    pub fn new(
+        target: &TextureUploadTarget,
+        images: &'a [UploadImage<'a>],
+    ) -> Result<Self, TextureUploadError> {
+        if target.slice_count(0) == 0 {
+            return Err(TextureUploadError::UnsupportedTextureType(target.texture_type));
+        }
+        let bytes_per_pixel = upload_bytes_per_pixel(target.pixel_format)
+            .ok_or(TextureUploadError::UnsupportedFormat(target.pixel_format))?;
+
+        let mut copies = Vec::with_capacity(images.len());
+        let mut staging_len = 0usize;
+        for image in images {
+            if image.level >= target.mipmap_level_count {
+                return Err(TextureUploadError::LevelOutOfRange {
+                    level: image.level,
+                    count: target.mipmap_level_count,
+                });
+            }
+            let slice_count = target.slice_count(image.level);
+            if image.slice >= slice_count {
+                return Err(TextureUploadError::SliceOutOfRange {
+                    level: image.level,
+                    slice: image.slice,
+                    count: slice_count,
+                });
+            }
+            let level_size = target.level_size(image.level);
+            let expected = (level_size.width, level_size.height);
+            let actual = (image.image.width(), image.image.height());
+            if actual != expected {
+                return Err(TextureUploadError::SizeMismatch {
+                    level: image.level,
+                    expected,
+                    actual,
+                });
+            }
+
+            let offset = staging_len.checked_next_multiple_of(STAGING_ALIGNMENT).ok_or(ImageLayoutError::Overflow)?;
+            let bytes_per_row = level_size.width * bytes_per_pixel;
+            let bytes_per_image = bytes_per_row * level_size.height;
+            staging_len = offset.checked_add(bytes_per_image).ok_or(ImageLayoutError::Overflow)?;
+
+            let (slice, z) = match target.texture_type {
+                MTLTextureType::Type3D => (0, image.slice),
+                _ => (image.slice, 0),
+            };
+            copies.push(StagingCopy {
+                offset,
+                bytes_per_row,
+                bytes_per_image,
+                size: MTLSize::new(level_size.width, level_size.height, 1),
+                level: image.level,
+                slice,
+                origin: MTLOrigin {
+                    x: 0,
+                    y: 0,
+                    z,
+                },
+            });
+        }
+
+        Ok(Self {
+            pixel_format: target.pixel_format,
+            images,
+            copies,
+            staging_len,
+        })
+    }
+
+    pub fn pixel_format(&self) -> MTLPixelFormat {
+        self.pixel_format
+    }
+
+    pub fn images(&self) -> &'a [UploadImage<'a>] {
+        self.images
+    }
+
+    /// The copies, in the order of the images.
+    pub fn copies(&self) -> &[StagingCopy] {
+        &self.copies
+    }
+
+    /// The staging buffer length the copies need.
+    pub fn staging_len(&self) -> usize {
+        self.staging_len
+    }
+
+    /// Converts every image into its region of `staging`.
+    pub fn write_staging(
+        &self,
+        staging: &mut [u8],
+    ) -> Result<(), TextureUploadError> {
+        if staging.len() < self.staging_len {
+            return Err(ImageLayoutError::DataTooShort {
+                required: self.staging_len,
+                actual: staging.len(),
+            }
+            .into());
+        }
+        for (image, copy) in self.images.iter().zip(&self.copies) {
+            let region = &mut staging[copy.offset..copy.offset + copy.bytes_per_image];
+            convert_image(&image.image, self.pixel_format, region, copy.bytes_per_row)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture_upload::HostImageFormat;
+
+    const PIXELS: [u8; 4 * 16] = [0x7f; 4 * 16];
+
+    fn target(
+        texture_type: MTLTextureType,
+        (width, height, depth): (usize, usize, usize),
+        mipmap_level_count: usize,
+        array_length: usize,
+    ) -> TextureUploadTarget {
+        TextureUploadTarget {
+            texture_type,
+            pixel_format: MTLPixelFormat::RGBA8Unorm,
+            width,
+            height,
+            depth,
+            mipmap_level_count,
+            array_length,
+        }
+    }
+
+    fn image(
+        level: usize,
+        slice: usize,
+        (width, height): (usize, usize),
+    ) -> UploadImage<'static> {
+        UploadImage {
+            level,
+            slice,
+            image: HostImage::packed(HostImageFormat::Rgba8Unorm, width, height, &PIXELS).unwrap(),
+        }
+    }
+
+    fn layout(plan: &UploadPlan<'_>) -> Vec<(usize, usize, usize, usize, usize)> {
+        plan.copies()
+            .iter()
+            .map(|copy| (copy.offset, copy.bytes_per_row, copy.level, copy.slice, copy.origin.z))
+            .collect()
+    }
+
+    #[test]
+    fn mip_chain_offsets() {
+        let target = target(MTLTextureType::Type2D, (5, 3, 1), 3, 1);
+        assert_eq!(target.level_size(1), MTLSize::new(2, 1, 1));
+        assert_eq!(target.level_size(7), MTLSize::new(1, 1, 1));
+
+        let images = [image(0, 0, (5, 3)), image(1, 0, (2, 1)), image(2, 0, (1, 1))];
+        let plan = UploadPlan::new(&target, &images).unwrap();
+        assert_eq!(layout(&plan), [(0, 20, 0, 0, 0), (256, 8, 1, 0, 0), (512, 4, 2, 0, 0)]);
+        assert_eq!(plan.copies()[0].bytes_per_image, 60);
+        assert_eq!(plan.copies()[0].size, MTLSize::new(5, 3, 1));
+        assert_eq!(plan.staging_len(), 516);
+
+        let mut staging = vec![0; plan.staging_len()];
+        plan.write_staging(&mut staging).unwrap();
+        assert!(staging[..60].iter().all(|&byte| byte == 0x7f));
+        assert!(staging[60..256].iter().all(|&byte| byte == 0));
+        assert!(matches!(
+            plan.write_staging(&mut staging[..515]),
+            Err(TextureUploadError::Layout(ImageLayoutError::DataTooShort {
+                required: 516,
+                actual: 515,
+            }))
+        ));
+    }
+
+    #[test]
+    fn slice_offsets() {
+        let target = target(MTLTextureType::CubeArray, (2, 2, 1), 2, 2);
+        assert_eq!(target.slice_count(1), 12);
+        let images = [image(0, 11, (2, 2)), image(1, 7, (1, 1)), image(0, 0, (2, 2))];
+        let plan = UploadPlan::new(&target, &images).unwrap();
+        assert_eq!(layout(&plan), [(0, 8, 0, 11, 0), (256, 4, 1, 7, 0), (512, 8, 0, 0, 0)]);
+        assert_eq!(plan.staging_len(), 528);
+    }
+
+    #[test]
+    fn depth_plane_offsets() {
+        let target = target(MTLTextureType::Type3D, (4, 2, 4), 2, 1);
+        assert_eq!((target.slice_count(0), target.slice_count(1)), (4, 2));
+        assert_eq!(target.level_size(1), MTLSize::new(2, 1, 2));
+
+        let images = [image(0, 0, (4, 2)), image(0, 3, (4, 2)), image(1, 1, (2, 1))];
+        let plan = UploadPlan::new(&target, &images).unwrap();
+        // Depth planes are copied into slice 0 at their z origin, one plane at
+        // a time.
+        assert_eq!(layout(&plan), [(0, 16, 0, 0, 0), (256, 16, 0, 0, 3), (512, 8, 1, 0, 1)]);
+        assert!(plan.copies().iter().all(|copy| copy.size.depth == 1));
+        assert_eq!(plan.staging_len(), 520);
+
+        assert!(matches!(
+            UploadPlan::new(&target, &[image(1, 2, (2, 1))]),
+            Err(TextureUploadError::SliceOutOfRange {
+                level: 1,
+                slice: 2,
+                count: 2,
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_images() {
+        let target_2d = target(MTLTextureType::Type2D, (4, 4, 1), 2, 1);
+        assert!(matches!(
+            UploadPlan::new(&target_2d, &[image(2, 0, (1, 1))]),
+            Err(TextureUploadError::LevelOutOfRange {
+                level: 2,
+                count: 2,
+            })
+        ));
+        assert!(matches!(
+            UploadPlan::new(&target_2d, &[image(0, 1, (4, 4))]),
+            Err(TextureUploadError::SliceOutOfRange {
+                level: 0,
+                slice: 1,
+                count: 1,
+            })
+        ));
+        assert!(matches!(
+            UploadPlan::new(&target_2d, &[image(1, 0, (4, 4))]),
+            Err(TextureUploadError::SizeMismatch {
+                level: 1,
+                expected: (2, 2),
+                actual: (4, 4),
+            })
+        ));
+
+        let multisample = target(MTLTextureType::Type2DMultisample, (4, 4, 1), 1, 1);
+        assert!(matches!(
+            UploadPlan::new(&multisample, &[]),
+            Err(TextureUploadError::UnsupportedTextureType(MTLTextureType::Type2DMultisample))
+        ));
+        let depth = TextureUploadTarget {
+            pixel_format: MTLPixelFormat::Depth32Float,
+            ..target_2d
+        };
+        assert!(matches!(
+            UploadPlan::new(&depth, &[]),
+            Err(TextureUploadError::UnsupportedFormat(MTLPixelFormat::Depth32Float))
+        ));
+    }
+}
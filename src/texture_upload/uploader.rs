@@ -0,0 +1,108 @@
+use core::slice;
+
+use objc2::{rc::Retained, runtime::ProtocolObject};
+
+use super::{TextureUploadError, TextureUploadTarget, UploadImage, UploadPlan};
+use crate::{
+    MTLBlitCommandEncoder, MTLBuffer, MTLCommandBuffer, MTLCommandBufferStatus, MTLCommandEncoder, MTLCommandQueue,
+    MTLDevice, MTLResourceOptions, MTLTexture, staging_buffer::new_staging_buffer,
+};
+
+impl TextureUploadTarget {
+    pub fn from_texture(texture: &ProtocolObject<dyn MTLTexture>) -> Self {
+        Self {
+            texture_type: texture.texture_type(),
+            pixel_format: texture.pixel_format(),
+            width: texture.width(),
+            height: texture.height(),
+            depth: texture.depth(),
+            mipmap_level_count: texture.mipmap_level_count(),
+            array_length: texture.array_length(),
+        }
+    }
+}
+
+/// Uploads host images into textures through staging buffers and blit
+/// copies.
+pub struct TextureUploader {
+    device: Retained<ProtocolObject<dyn MTLDevice>>,
+    staging_options: MTLResourceOptions,
+}
+
+impl TextureUploader {
+    /// Staging buffers are shared and write-combined, since the CPU only
+    /// writes them.
+    pub fn new(device: Retained<ProtocolObject<dyn MTLDevice>>) -> Self {
+        Self {
+            device,
+            staging_options: MTLResourceOptions::STORAGE_MODE_SHARED
+                | MTLResourceOptions::CPU_CACHE_MODE_WRITE_COMBINED,
+        }
+    }
+
+    pub fn device(&self) -> &ProtocolObject<dyn MTLDevice> {
+        &self.device
+    }
+
+    /// Converts `images` into a new staging buffer and encodes their copies
+    /// into `texture`, then mipmap generation if `generate_mipmaps` is set and
+    /// the texture has more than one level.
+    ///
+    /// Returns the staging buffer, which must outlive the encoder's command
+    /// buffer unless that retains its references.
+    pub fn encode_upload(
+        &self,
+        encoder: &ProtocolObject<dyn MTLBlitCommandEncoder>,
+        texture: &ProtocolObject<dyn MTLTexture>,
+        images: &[UploadImage<'_>],
+        generate_mipmaps: bool,
+    ) -> Result<Retained<ProtocolObject<dyn MTLBuffer>>, TextureUploadError> {
+        let plan = UploadPlan::new(&TextureUploadTarget::from_texture(texture), images)?;
+        let staging = new_staging_buffer(&self.device, plan.staging_len(), self.staging_options)
+            .ok_or(TextureUploadError::ResourceCreation)?;
+        let contents =
+            unsafe { slice::from_raw_parts_mut(staging.contents().as_ptr().cast::<u8>(), plan.staging_len()) };
+        plan.write_staging(contents)?;
+
+        for copy in plan.copies() {
+            encoder.copy_from_buffer_to_texture(
+                &staging,
+                copy.offset,
+                copy.bytes_per_row,
+                copy.bytes_per_image,
+                copy.size,
+                texture,
+                copy.slice,
+                copy.level,
+                copy.origin,
+            );
+        }
+        if generate_mipmaps && texture.mipmap_level_count() > 1 {
+            encoder.generate_mipmaps_for_texture(texture);
+        }
+        Ok(staging)
+    }
+
+    /// Uploads `images` on a new command buffer from `command_queue` and
+    /// waits for it to complete.
+    pub fn upload(
+        &self,
+        command_queue: &ProtocolObject<dyn MTLCommandQueue>,
+        texture: &ProtocolObject<dyn MTLTexture>,
+        images: &[UploadImage<'_>],
+        generate_mipmaps: bool,
+    ) -> Result<(), TextureUploadError> {
+        let command_buffer = command_queue.command_buffer().ok_or(TextureUploadError::ResourceCreation)?;
+        let encoder = command_buffer.blit_command_encoder().ok_or(TextureUploadError::ResourceCreation)?;
+        let result = self.encode_upload(&encoder, texture, images, generate_mipmaps);
+        encoder.end_encoding();
+        let _staging = result?;
+
+        command_buffer.commit();
+        command_buffer.wait_until_completed();
+        match command_buffer.status() {
+            MTLCommandBufferStatus::Error => Err(TextureUploadError::CommandBuffer(command_buffer.error())),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use objc2::{msg_send, runtime::ProtocolObject};
+
+use super::{TextureDescriptorError, rules};
+use crate::{
+    DeviceCapabilities, MTLHeapType, MTLPixelFormat, MTLResource, MTLSparsePageSize, MTLStorageMode, MTLTexture,
+    MTLTextureDescriptor, MTLTextureType, MTLTextureUsage,
+};
+
+/// Where a texture is allocated from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TextureAllocation {
+    /// The device, with `new_texture_with_descriptor`.
+    Device,
+    /// A heap of the given type.
+    Heap(MTLHeapType),
+}
+
+/// The properties of a texture descriptor that validation checks.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextureDescription {
+    pub texture_type: MTLTextureType,
+    pub pixel_format: MTLPixelFormat,
+    pub width: usize,
+    pub height: usize,
+    pub depth: usize,
+    pub mipmap_level_count: usize,
+    pub sample_count: usize,
+    pub array_length: usize,
+    pub storage_mode: MTLStorageMode,
+    pub usage: MTLTextureUsage,
+    /// The page size of a placement sparse texture.
+    pub placement_sparse_page_size: Option<MTLSparsePageSize>,
+    /// Whether shaders read and write the texture in the same function,
+    /// which descriptors don't record. The device's read-write tier must
+    /// cover the pixel format.
+    pub read_write: bool,
+}
+
+impl TextureDescription {
+    /// Reads the properties of `descriptor`. [`read_write`](Self::read_write)
+    /// is left unset.
+    pub fn new(descriptor: &MTLTextureDescriptor) -> Self {
+        // The page size is 0 unless the texture is placement sparse, which
+        // `MTLSparsePageSize` can't represent.
+        let page_size: isize = unsafe { msg_send![descriptor, placementSparsePageSize] };
+        let placement_sparse_page_size = match page_size {
+            101 => Some(MTLSparsePageSize::KB16),
+            102 => Some(MTLSparsePageSize::KB64),
+            103 => Some(MTLSparsePageSize::KB256),
+            _ => None,
+        };
+        Self {
+            texture_type: descriptor.texture_type(),
+            pixel_format: descriptor.pixel_format(),
+            width: descriptor.width(),
+            height: descriptor.height(),
+            depth: descriptor.depth(),
+            mipmap_level_count: descriptor.mipmap_level_count(),
+            sample_count: descriptor.sample_count(),
+            array_length: descriptor.array_length(),
+            storage_mode: descriptor.storage_mode(),
+            usage: descriptor.usage(),
+            placement_sparse_page_size,
+            read_write: false,
+        }
+    }
+
+    /// Reads the properties of `texture`, to validate views of it.
+    /// [`placement_sparse_page_size`](Self::placement_sparse_page_size) and
+    /// [`read_write`](Self::read_write) are left unset.
+    pub fn from_texture(texture: &ProtocolObject<dyn MTLTexture>) -> Self {
+        Self {
+            texture_type: texture.texture_type(),
+            pixel_format: texture.pixel_format(),
+            width: texture.width(),
+            height: texture.height(),
+            depth: texture.depth(),
+            mipmap_level_count: texture.mipmap_level_count(),
+            sample_count: texture.sample_count(),
+            array_length: texture.array_length(),
+            storage_mode: texture.storage_mode(),
+            usage: texture.usage(),
+            placement_sparse_page_size: None,
+            read_write: false,
+        }
+    }
+
+    /// Every rule the texture breaks when allocated from `allocation` on a
+    /// device with `capabilities`.
+    pub fn violations(
+        &self,
+        allocation: TextureAllocation,
+        capabilities: &DeviceCapabilities,
+    ) -> Vec<TextureDescriptorError> {
+        rules::RULES.iter().filter_map(|rule| rule(self, allocation, capabilities)).collect()
+    }
+
+    /// Checks the texture can be allocated from `allocation` on a device with
+    /// `capabilities`, returning the first rule it breaks.
+    pub fn validate(
+        &self,
+        allocation: TextureAllocation,
+        capabilities: &DeviceCapabilities,
+    ) -> Result<(), TextureDescriptorError> {
+        match rules::RULES.iter().find_map(|rule| rule(self, allocation, capabilities)) {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
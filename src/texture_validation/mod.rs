@@ -0,0 +1,7 @@
+mod description;
+mod rules;
+mod view;
+
+pub use description::{TextureAllocation, TextureDescription};
+pub use rules::TextureDescriptorError;
+pub use view::{TextureViewDescription, TextureViewError};
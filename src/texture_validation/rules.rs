@@ -0,0 +1,962 @@
+use core::fmt;
+
+use super::{TextureAllocation, TextureDescription};
+use crate::{
+    DeviceCapabilities, MTLGPUFamily, MTLHeapType, MTLPixelFormat, MTLReadWriteTextureTier, MTLStorageMode,
+    MTLTextureType, MTLTextureUsage,
+};
+
+/// A rule a texture descriptor breaks.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TextureDescriptorError {
+    /// The device can't create textures of this pixel format.
+    UnsupportedPixelFormat(MTLPixelFormat),
+    /// The device doesn't support this texture type.
+    UnsupportedTextureType(MTLTextureType),
+    /// The width, height, depth or array length is zero.
+    EmptyTexture,
+    /// The size doesn't fit the texture type, such as a 2D texture with depth
+    /// or a cube with unequal width and height.
+    InvalidSize {
+        texture_type: MTLTextureType,
+        width: usize,
+        height: usize,
+        depth: usize,
+    },
+    /// The texture type has one slice, but the array length isn't 1.
+    InvalidArrayLength {
+        texture_type: MTLTextureType,
+        array_length: usize,
+    },
+    /// The texture type, such as a multisample texture, has no mipmaps.
+    MipmapsUnsupported {
+        texture_type: MTLTextureType,
+        mipmap_level_count: usize,
+    },
+    /// The width, height or depth is larger than the device's GPU families
+    /// allow for the texture type.
+    TextureTooLarge {
+        texture_type: MTLTextureType,
+        extent: usize,
+        max: usize,
+    },
+    /// The array length is longer than the device's GPU families allow.
+    ArrayTooLong {
+        array_length: usize,
+        max: usize,
+    },
+    /// More mipmap levels than a full chain for the size, or none.
+    InvalidMipmapLevelCount {
+        mipmap_level_count: usize,
+        max: usize,
+    },
+    /// The sample count is greater than 1 for a type that isn't multisample.
+    SampleCountMismatch {
+        texture_type: MTLTextureType,
+        sample_count: usize,
+    },
+    /// The device doesn't support this texture sample count.
+    UnsupportedSampleCount(usize),
+    /// Block-compressed pixel formats can't be used with the texture type.
+    CompressedTextureType {
+        texture_type: MTLTextureType,
+        pixel_format: MTLPixelFormat,
+    },
+    /// The device doesn't support memoryless storage.
+    MemorylessUnsupported,
+    /// Memoryless textures can only be render targets.
+    MemorylessUsage(MTLTextureUsage),
+    /// The device can't allocate multisample textures of 32-bit float
+    /// formats.
+    Multisample32BitFloat(MTLPixelFormat),
+    /// Read-write access needs both shader-read and shader-write usage.
+    ReadWriteUsage(MTLTextureUsage),
+    /// The device's read-write tier doesn't cover the pixel format.
+    ReadWriteUnsupported {
+        pixel_format: MTLPixelFormat,
+        tier: MTLReadWriteTextureTier,
+    },
+    /// The device doesn't support placement sparse textures.
+    PlacementSparseUnsupported,
+    /// Placement sparse textures can't be allocated from this type of heap.
+    PlacementSparseHeap(MTLHeapType),
+}
+
+impl fmt::Display for TextureDescriptorError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::UnsupportedPixelFormat(format) => write!(f, "device doesn't support {format:?} textures"),
+            Self::UnsupportedTextureType(texture_type) => write!(f, "device doesn't support {texture_type:?} textures"),
+            Self::EmptyTexture => f.write_str("texture has a zero width, height, depth or array length"),
+            Self::InvalidSize {
+                texture_type,
+                width,
+                height,
+                depth,
+            } => write!(f, "{texture_type:?} textures can't be {width}x{height}x{depth}"),
+            Self::InvalidArrayLength {
+                texture_type,
+                array_length,
+            } => write!(f, "{texture_type:?} textures have one slice, not {array_length}"),
+            Self::MipmapsUnsupported {
+                texture_type,
+                mipmap_level_count,
+            } => write!(f, "{texture_type:?} textures can't have {mipmap_level_count} mipmap levels"),
+            Self::TextureTooLarge {
+                texture_type,
+                extent,
+                max,
+            } => write!(f, "{texture_type:?} textures are at most {max} texels across, not {extent}"),
+            Self::ArrayTooLong {
+                array_length,
+                max,
+            } => write!(f, "texture arrays have at most {max} slices, not {array_length}"),
+            Self::InvalidMipmapLevelCount {
+                mipmap_level_count,
+                max,
+            } => write!(f, "{mipmap_level_count} mipmap levels for a texture with at most {max}"),
+            Self::SampleCountMismatch {
+                texture_type,
+                sample_count,
+            } => write!(f, "{texture_type:?} textures can't have {sample_count} samples"),
+            Self::UnsupportedSampleCount(sample_count) => {
+                write!(f, "device doesn't support textures with {sample_count} samples")
+            },
+            Self::CompressedTextureType {
+                texture_type,
+                pixel_format,
+            } => write!(f, "{texture_type:?} textures can't use compressed format {pixel_format:?}"),
+            Self::MemorylessUnsupported => f.write_str("device doesn't support memoryless textures"),
+            Self::MemorylessUsage(usage) => write!(f, "memoryless textures can only be render targets, not {usage:?}"),
+            Self::Multisample32BitFloat(format) => {
+                write!(f, "device doesn't support multisample {format:?} textures")
+            },
+            Self::ReadWriteUsage(usage) => {
+                write!(f, "read-write textures need shader read and write usage, not {usage:?}")
+            },
+            Self::ReadWriteUnsupported {
+                pixel_format,
+                tier,
+            } => write!(f, "read-write {pixel_format:?} textures aren't supported at {tier:?}"),
+            Self::PlacementSparseUnsupported => f.write_str("device doesn't support placement sparse textures"),
+            Self::PlacementSparseHeap(heap_type) => {
+                write!(f, "placement sparse textures can't be allocated from {heap_type:?} heaps")
+            },
+        }
+    }
+}
+
+impl std::error::Error for TextureDescriptorError {}
+
+/// The constraints a texture type places on descriptors.
+struct TextureTypeRules {
+    texture_type: MTLTextureType,
+    /// 1, 2 or 3; extents past these must be 1.
+    dimensions: u8,
+    square: bool,
+    arrayed: bool,
+    multisample: bool,
+    mipmapped: bool,
+    compressed: bool,
+    /// Families of which the device must support one; empty for all devices.
+    families: &'static [MTLGPUFamily],
+}
+
+impl TextureTypeRules {
+    const fn new(
+        texture_type: MTLTextureType,
+        dimensions: u8,
+    ) -> Self {
+        Self {
+            texture_type,
+            dimensions,
+            square: false,
+            arrayed: false,
+            multisample: false,
+            mipmapped: false,
+            compressed: false,
+            families: &[],
+        }
+    }
+
+    const fn square(mut self) -> Self {
+        self.square = true;
+        self
+    }
+
+    const fn arrayed(mut self) -> Self {
+        self.arrayed = true;
+        self
+    }
+
+    const fn multisample(mut self) -> Self {
+        self.multisample = true;
+        self
+    }
+
+    const fn mipmapped(mut self) -> Self {
+        self.mipmapped = true;
+        self
+    }
+
+    const fn compressed(mut self) -> Self {
+        self.compressed = true;
+        self
+    }
+
+    const fn families(
+        mut self,
+        families: &'static [MTLGPUFamily],
+    ) -> Self {
+        self.families = families;
+        self
+    }
+}
+
+const TEXTURE_TYPE_RULES: [TextureTypeRules; 10] = [
+    TextureTypeRules::new(MTLTextureType::Type1D, 1).mipmapped(),
+    TextureTypeRules::new(MTLTextureType::Type1DArray, 1).arrayed().mipmapped(),
+    TextureTypeRules::new(MTLTextureType::Type2D, 2).mipmapped().compressed(),
+    TextureTypeRules::new(MTLTextureType::Type2DArray, 2).arrayed().mipmapped().compressed(),
+    TextureTypeRules::new(MTLTextureType::Type2DMultisample, 2).multisample(),
+    TextureTypeRules::new(MTLTextureType::Cube, 2).square().mipmapped().compressed(),
+    TextureTypeRules::new(MTLTextureType::CubeArray, 2)
+        .square()
+        .arrayed()
+        .mipmapped()
+        .compressed()
+        .families(&[MTLGPUFamily::Apple4, MTLGPUFamily::Mac2]),
+    TextureTypeRules::new(MTLTextureType::Type3D, 3).mipmapped().compressed(),
+    TextureTypeRules::new(MTLTextureType::Type2DMultisampleArray, 2).arrayed().multisample(),
+    TextureTypeRules::new(MTLTextureType::TextureBuffer, 1),
+];
+
+fn type_rules(texture_type: MTLTextureType) -> &'static TextureTypeRules {
+    let rules = TEXTURE_TYPE_RULES.iter().find(|rules| rules.texture_type == texture_type);
+    rules.expect("every texture type has rules")
+}
+
+/// A rule, returning how `TextureDescription` breaks it, if it does.
+type Rule = fn(&TextureDescription, TextureAllocation, &DeviceCapabilities) -> Option<TextureDescriptorError>;
+
+/// Rules checked in order; earlier rules cover more fundamental mistakes.
+pub(crate) const RULES: &[Rule] = &[
+    pixel_format,
+    texture_type,
+    size,
+    array_length,
+    size_limits,
+    mipmap_level_count,
+    sample_count,
+    compressed_texture_type,
+    memoryless,
+    multisample_32bit_float,
+    read_write,
+    placement_sparse,
+];
+
+fn pixel_format(
+    texture: &TextureDescription,
+    _: TextureAllocation,
+    capabilities: &DeviceCapabilities,
+) -> Option<TextureDescriptorError> {
+    let supported = capabilities.supports_pixel_format(texture.pixel_format);
+    (!supported).then_some(TextureDescriptorError::UnsupportedPixelFormat(texture.pixel_format))
+}
+
+fn texture_type(
+    texture: &TextureDescription,
+    _: TextureAllocation,
+    capabilities: &DeviceCapabilities,
+) -> Option<TextureDescriptorError> {
+    let families = type_rules(texture.texture_type).families;
+    let supported = families.is_empty() || families.iter().any(|&family| capabilities.supports_family(family));
+    (!supported).then_some(TextureDescriptorError::UnsupportedTextureType(texture.texture_type))
+}
+
+fn size(
+    texture: &TextureDescription,
+    _: TextureAllocation,
+    _: &DeviceCapabilities,
+) -> Option<TextureDescriptorError> {
+    if texture.width == 0 || texture.height == 0 || texture.depth == 0 || texture.array_length == 0 {
+        return Some(TextureDescriptorError::EmptyTexture);
+    }
+    let rules = type_rules(texture.texture_type);
+    let valid = (rules.dimensions >= 2 || texture.height == 1)
+        && (rules.dimensions >= 3 || texture.depth == 1)
+        && (!rules.square || texture.width == texture.height);
+    (!valid).then_some(TextureDescriptorError::InvalidSize {
+        texture_type: texture.texture_type,
+        width: texture.width,
+        height: texture.height,
+        depth: texture.depth,
+    })
+}
+
+fn array_length(
+    texture: &TextureDescription,
+    _: TextureAllocation,
+    _: &DeviceCapabilities,
+) -> Option<TextureDescriptorError> {
+    let valid = type_rules(texture.texture_type).arrayed || texture.array_length <= 1;
+    (!valid).then_some(TextureDescriptorError::InvalidArrayLength {
+        texture_type: texture.texture_type,
+        array_length: texture.array_length,
+    })
+}
+
+fn size_limits(
+    texture: &TextureDescription,
+    _: TextureAllocation,
+    capabilities: &DeviceCapabilities,
+) -> Option<TextureDescriptorError> {
+    // Texture buffers are limited by the buffer length instead.
+    if texture.texture_type == MTLTextureType::TextureBuffer {
+        return None;
+    }
+    let limits = capabilities.family_capabilities()?;
+    let max = match texture.texture_type {
+        MTLTextureType::Type3D => limits.max_3d_texture_size,
+        _ => limits.max_texture_size,
+    };
+    let extent = texture.width.max(texture.height).max(texture.depth);
+    if extent > max {
+        return Some(TextureDescriptorError::TextureTooLarge {
+            texture_type: texture.texture_type,
+            extent,
+            max,
+        });
+    }
+    let max = limits.max_texture_array_length;
+    (texture.array_length > max).then_some(TextureDescriptorError::ArrayTooLong {
+        array_length: texture.array_length,
+        max,
+    })
+}
+
+fn mipmap_level_count(
+    texture: &TextureDescription,
+    _: TextureAllocation,
+    _: &DeviceCapabilities,
+) -> Option<TextureDescriptorError> {
+    let rules = type_rules(texture.texture_type);
+    if !rules.mipmapped && texture.mipmap_level_count > 1 {
+        return Some(TextureDescriptorError::MipmapsUnsupported {
+            texture_type: texture.texture_type,
+            mipmap_level_count: texture.mipmap_level_count,
+        });
+    }
+    let depth = match rules.dimensions {
+        3 => texture.depth,
+        _ => 1,
+    };
+    let extent = texture.width.max(texture.height).max(depth).max(1);
+    let max = (usize::BITS - extent.leading_zeros()) as usize;
+    let valid = (1..=max).contains(&texture.mipmap_level_count);
+    (!valid).then_some(TextureDescriptorError::InvalidMipmapLevelCount {
+        mipmap_level_count: texture.mipmap_level_count,
+        max,
+    })
+}
+
+fn sample_count(
+    texture: &TextureDescription,
+    _: TextureAllocation,
+    capabilities: &DeviceCapabilities,
+) -> Option<TextureDescriptorError> {
+    if !type_rules(texture.texture_type).multisample && texture.sample_count > 1 {
+        return Some(TextureDescriptorError::SampleCountMismatch {
+            texture_type: texture.texture_type,
+            sample_count: texture.sample_count,
+        });
+    }
+    let supported = capabilities.supports_texture_sample_count(texture.sample_count);
+    (!supported).then_some(TextureDescriptorError::UnsupportedSampleCount(texture.sample_count))
+}
+
+fn compressed_texture_type(
+    texture: &TextureDescription,
+    _: TextureAllocation,
+    _: &DeviceCapabilities,
+) -> Option<TextureDescriptorError> {
+    let valid = type_rules(texture.texture_type).compressed || !texture.pixel_format.is_compressed();
+    (!valid).then_some(TextureDescriptorError::CompressedTextureType {
+        texture_type: texture.texture_type,
+        pixel_format: texture.pixel_format,
+    })
+}
+
+fn memoryless(
+    texture: &TextureDescription,
+    _: TextureAllocation,
+    capabilities: &DeviceCapabilities,
+) -> Option<TextureDescriptorError> {
+    if texture.storage_mode != MTLStorageMode::Memoryless {
+        return None;
+    }
+    if !capabilities.supports_family(MTLGPUFamily::Apple1) {
+        return Some(TextureDescriptorError::MemorylessUnsupported);
+    }
+    let valid = MTLTextureUsage::RENDER_TARGET.contains(texture.usage);
+    (!valid).then_some(TextureDescriptorError::MemorylessUsage(texture.usage))
+}
+
+fn multisample_32bit_float(
+    texture: &TextureDescription,
+    _: TextureAllocation,
+    capabilities: &DeviceCapabilities,
+) -> Option<TextureDescriptorError> {
+    let float32 = matches!(
+        texture.pixel_format,
+        MTLPixelFormat::R32Float | MTLPixelFormat::RG32Float | MTLPixelFormat::RGBA32Float
+    );
+    let valid = texture.sample_count <= 1 || !float32 || capabilities.supports_32bit_msaa;
+    (!valid).then_some(TextureDescriptorError::Multisample32BitFloat(texture.pixel_format))
+}
+
+fn read_write(
+    texture: &TextureDescription,
+    _: TextureAllocation,
+    capabilities: &DeviceCapabilities,
+) -> Option<TextureDescriptorError> {
+    if !texture.read_write {
+        return None;
+    }
+    if !texture.usage.contains(MTLTextureUsage::SHADER_READ | MTLTextureUsage::SHADER_WRITE) {
+        return Some(TextureDescriptorError::ReadWriteUsage(texture.usage));
+    }
+    let supported = capabilities.supports_read_write_texture(texture.pixel_format);
+    (!supported).then_some(TextureDescriptorError::ReadWriteUnsupported {
+        pixel_format: texture.pixel_format,
+        tier: capabilities.read_write_texture_tier,
+    })
+}
+
+fn placement_sparse(
+    texture: &TextureDescription,
+    allocation: TextureAllocation,
+    capabilities: &DeviceCapabilities,
+) -> Option<TextureDescriptorError> {
+    texture.placement_sparse_page_size?;
+    if !capabilities.supports_placement_sparse {
+        return Some(TextureDescriptorError::PlacementSparseUnsupported);
+    }
+    match allocation {
+        TextureAllocation::Heap(heap_type) if heap_type != MTLHeapType::Placement => {
+            Some(TextureDescriptorError::PlacementSparseHeap(heap_type))
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MTLArgumentBuffersTier, MTLDeviceLocation, MTLSize, MTLSparsePageSize};
+
+    fn capabilities(families: &[MTLGPUFamily]) -> DeviceCapabilities {
+        DeviceCapabilities {
+            name: "Test GPU".to_owned(),
+            architecture: "test".to_owned(),
+            location: MTLDeviceLocation::BuiltIn,
+            location_number: 0,
+            is_low_power: false,
+            is_headless: false,
+            is_removable: false,
+            has_unified_memory: true,
+            recommended_max_working_set_size: 1 << 32,
+            max_transfer_rate: 0,
+            peer_group_id: 0,
+            peer_index: 0,
+            peer_count: 0,
+            families: families.to_vec(),
+            texture_sample_counts: vec![1, 2, 4],
+            read_write_texture_tier: MTLReadWriteTextureTier::Tier1,
+            argument_buffers_tier: MTLArgumentBuffersTier::Tier2,
+            max_threads_per_threadgroup: MTLSize {
+                width: 1024,
+                height: 1024,
+                depth: 1024,
+            },
+            max_threadgroup_memory_length: 32768,
+            max_buffer_length: 1 << 30,
+            max_argument_buffer_sampler_count: 1024,
+            max_vertex_amplification_count: 2,
+            max_rasterization_rate_map_layer_count: 2,
+            sparse_tile_size_in_bytes: 16384,
+            placement_sparse_tile_sizes_in_bytes: Vec::new(),
+            supports_bc_texture_compression: false,
+            supports_32bit_float_filtering: true,
+            supports_32bit_msaa: false,
+            supports_depth24_stencil8: false,
+            supports_query_texture_lod: true,
+            supports_pull_model_interpolation: true,
+            supports_shader_barycentric_coordinates: true,
+            supports_programmable_sample_positions: true,
+            supports_raster_order_groups: true,
+            supports_dynamic_libraries: true,
+            supports_render_dynamic_libraries: true,
+            supports_function_pointers: true,
+            supports_function_pointers_from_render: true,
+            supports_raytracing: true,
+            supports_raytracing_from_render: true,
+            supports_primitive_motion_blur: true,
+            supports_placement_sparse: false,
+        }
+    }
+
+    /// An Apple GPU reporting every family up to Apple7.
+    fn apple7() -> DeviceCapabilities {
+        use MTLGPUFamily::*;
+        capabilities(&[Apple1, Apple2, Apple3, Apple4, Apple5, Apple6, Apple7, Common1, Common2, Common3])
+    }
+
+    /// A GPU in the Mac family only.
+    fn mac2() -> DeviceCapabilities {
+        use MTLGPUFamily::*;
+        DeviceCapabilities {
+            supports_bc_texture_compression: true,
+            ..capabilities(&[Mac2, Common1, Common2, Common3])
+        }
+    }
+
+    fn texture() -> TextureDescription {
+        TextureDescription {
+            texture_type: MTLTextureType::Type2D,
+            pixel_format: MTLPixelFormat::RGBA8Unorm,
+            width: 256,
+            height: 128,
+            depth: 1,
+            mipmap_level_count: 1,
+            sample_count: 1,
+            array_length: 1,
+            storage_mode: MTLStorageMode::Private,
+            usage: MTLTextureUsage::SHADER_READ,
+            placement_sparse_page_size: None,
+            read_write: false,
+        }
+    }
+
+    fn validate(
+        texture: TextureDescription,
+        capabilities: &DeviceCapabilities,
+    ) -> Result<(), TextureDescriptorError> {
+        texture.validate(TextureAllocation::Device, capabilities)
+    }
+
+    #[test]
+    fn valid_textures() {
+        let apple7 = apple7();
+        let mac2 = mac2();
+        assert_eq!(validate(texture(), &apple7), Ok(()));
+        assert_eq!(validate(texture(), &mac2), Ok(()));
+        let mipmapped = TextureDescription {
+            mipmap_level_count: 9,
+            ..texture()
+        };
+        assert_eq!(validate(mipmapped, &apple7), Ok(()));
+        let cube_array = TextureDescription {
+            texture_type: MTLTextureType::CubeArray,
+            height: 256,
+            array_length: 4,
+            ..texture()
+        };
+        assert_eq!(validate(cube_array, &apple7), Ok(()));
+        assert_eq!(validate(cube_array, &mac2), Ok(()));
+        let volume = TextureDescription {
+            texture_type: MTLTextureType::Type3D,
+            depth: 64,
+            ..texture()
+        };
+        assert_eq!(validate(volume, &apple7), Ok(()));
+        let multisample = TextureDescription {
+            texture_type: MTLTextureType::Type2DMultisample,
+            sample_count: 4,
+            usage: MTLTextureUsage::RENDER_TARGET,
+            ..texture()
+        };
+        assert_eq!(validate(multisample, &apple7), Ok(()));
+        let memoryless = TextureDescription {
+            storage_mode: MTLStorageMode::Memoryless,
+            ..multisample
+        };
+        assert_eq!(validate(memoryless, &apple7), Ok(()));
+        assert_eq!(texture().violations(TextureAllocation::Device, &apple7), []);
+    }
+
+    #[test]
+    fn compressed_formats_follow_families() {
+        use MTLGPUFamily::*;
+        use MTLPixelFormat as F;
+
+        let apple1 = capabilities(&[Apple1, Common1]);
+        let apple4 = capabilities(&[Apple1, Apple2, Apple3, Apple4, Common1, Common2]);
+        let apple7 = apple7();
+        let mac2 = mac2();
+        let cases = [
+            (F::PVRTC_RGBA_4BPP, [true, true, true, false]),
+            (F::EAC_R11Unorm, [true, true, true, false]),
+            (F::ETC2_RGB8, [true, true, true, false]),
+            (F::ASTC_4x4_LDR, [false, true, true, false]),
+            (F::ASTC_4x4_HDR, [false, false, true, false]),
+            (F::BC1_RGBA, [false, false, false, true]),
+            (F::RGBA8Unorm, [true, true, true, true]),
+        ];
+        for (format, supported) in cases {
+            let texture = TextureDescription {
+                pixel_format: format,
+                ..texture()
+            };
+            for (capabilities, supported) in [&apple1, &apple4, &apple7, &mac2].into_iter().zip(supported) {
+                let expected = if supported {
+                    Ok(())
+                } else {
+                    Err(TextureDescriptorError::UnsupportedPixelFormat(format))
+                };
+                assert_eq!(validate(texture, capabilities), expected, "{format:?} on {:?}", capabilities.families);
+            }
+        }
+        let invalid = TextureDescription {
+            pixel_format: F::Invalid,
+            ..texture()
+        };
+        assert_eq!(validate(invalid, &apple7), Err(TextureDescriptorError::UnsupportedPixelFormat(F::Invalid)));
+        let depth24 = TextureDescription {
+            pixel_format: F::Depth24Unorm_Stencil8,
+            ..texture()
+        };
+        assert_eq!(
+            validate(depth24, &apple7),
+            Err(TextureDescriptorError::UnsupportedPixelFormat(F::Depth24Unorm_Stencil8))
+        );
+        let mac2 = DeviceCapabilities {
+            supports_depth24_stencil8: true,
+            ..mac2
+        };
+        assert_eq!(validate(depth24, &mac2), Ok(()));
+    }
+
+    #[test]
+    fn texture_types() {
+        let apple3 = capabilities(&[MTLGPUFamily::Apple1, MTLGPUFamily::Apple2, MTLGPUFamily::Apple3]);
+        let cube_array = TextureDescription {
+            texture_type: MTLTextureType::CubeArray,
+            height: 256,
+            array_length: 2,
+            ..texture()
+        };
+        assert_eq!(
+            validate(cube_array, &apple3),
+            Err(TextureDescriptorError::UnsupportedTextureType(MTLTextureType::CubeArray))
+        );
+        let cube = TextureDescription {
+            texture_type: MTLTextureType::Cube,
+            ..texture()
+        };
+        assert_eq!(
+            validate(cube, &apple3),
+            Err(TextureDescriptorError::InvalidSize {
+                texture_type: MTLTextureType::Cube,
+                width: 256,
+                height: 128,
+                depth: 1,
+            })
+        );
+        let line = TextureDescription {
+            texture_type: MTLTextureType::Type1D,
+            ..texture()
+        };
+        assert_eq!(
+            validate(line, &apple3),
+            Err(TextureDescriptorError::InvalidSize {
+                texture_type: MTLTextureType::Type1D,
+                width: 256,
+                height: 128,
+                depth: 1,
+            })
+        );
+        let deep = TextureDescription {
+            depth: 2,
+            ..texture()
+        };
+        assert!(matches!(
+            validate(deep, &apple3),
+            Err(TextureDescriptorError::InvalidSize {
+                depth: 2,
+                ..
+            })
+        ));
+        let empty = TextureDescription {
+            width: 0,
+            ..texture()
+        };
+        assert_eq!(validate(empty, &apple3), Err(TextureDescriptorError::EmptyTexture));
+        let no_slices = TextureDescription {
+            array_length: 0,
+            ..texture()
+        };
+        assert_eq!(validate(no_slices, &apple3), Err(TextureDescriptorError::EmptyTexture));
+        let arrayed = TextureDescription {
+            array_length: 3,
+            ..texture()
+        };
+        assert_eq!(
+            validate(arrayed, &apple3),
+            Err(TextureDescriptorError::InvalidArrayLength {
+                texture_type: MTLTextureType::Type2D,
+                array_length: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn size_limits() {
+        let apple1 = capabilities(&[MTLGPUFamily::Apple1]);
+        let apple7 = apple7();
+        let wide = TextureDescription {
+            width: 16384,
+            ..texture()
+        };
+        assert_eq!(
+            validate(wide, &apple1),
+            Err(TextureDescriptorError::TextureTooLarge {
+                texture_type: MTLTextureType::Type2D,
+                extent: 16384,
+                max: 8192,
+            })
+        );
+        assert_eq!(validate(wide, &apple7), Ok(()));
+        let volume = TextureDescription {
+            texture_type: MTLTextureType::Type3D,
+            depth: 4096,
+            ..texture()
+        };
+        assert_eq!(
+            validate(volume, &apple7),
+            Err(TextureDescriptorError::TextureTooLarge {
+                texture_type: MTLTextureType::Type3D,
+                extent: 4096,
+                max: 2048,
+            })
+        );
+        let array = TextureDescription {
+            texture_type: MTLTextureType::Type2DArray,
+            array_length: 4096,
+            ..texture()
+        };
+        assert_eq!(
+            validate(array, &apple7),
+            Err(TextureDescriptorError::ArrayTooLong {
+                array_length: 4096,
+                max: 2048,
+            })
+        );
+        // Without documented families, only the device checks the size.
+        let common = capabilities(&[MTLGPUFamily::Common1]);
+        let huge = TextureDescription {
+            width: 1 << 20,
+            ..texture()
+        };
+        assert_eq!(validate(huge, &common), Ok(()));
+    }
+
+    #[test]
+    fn mipmaps_and_samples() {
+        let apple7 = apple7();
+        let too_many = TextureDescription {
+            mipmap_level_count: 10,
+            ..texture()
+        };
+        assert_eq!(
+            validate(too_many, &apple7),
+            Err(TextureDescriptorError::InvalidMipmapLevelCount {
+                mipmap_level_count: 10,
+                max: 9,
+            })
+        );
+        let none = TextureDescription {
+            mipmap_level_count: 0,
+            ..texture()
+        };
+        assert_eq!(
+            validate(none, &apple7),
+            Err(TextureDescriptorError::InvalidMipmapLevelCount {
+                mipmap_level_count: 0,
+                max: 9,
+            })
+        );
+        let multisample = TextureDescription {
+            texture_type: MTLTextureType::Type2DMultisample,
+            sample_count: 4,
+            ..texture()
+        };
+        let mipmapped = TextureDescription {
+            mipmap_level_count: 2,
+            ..multisample
+        };
+        assert_eq!(
+            validate(mipmapped, &apple7),
+            Err(TextureDescriptorError::MipmapsUnsupported {
+                texture_type: MTLTextureType::Type2DMultisample,
+                mipmap_level_count: 2,
+            })
+        );
+        let single_sampled = TextureDescription {
+            sample_count: 4,
+            ..texture()
+        };
+        assert_eq!(
+            validate(single_sampled, &apple7),
+            Err(TextureDescriptorError::SampleCountMismatch {
+                texture_type: MTLTextureType::Type2D,
+                sample_count: 4,
+            })
+        );
+        let eight = TextureDescription {
+            sample_count: 8,
+            ..multisample
+        };
+        assert_eq!(validate(eight, &apple7), Err(TextureDescriptorError::UnsupportedSampleCount(8)));
+        let float32 = TextureDescription {
+            pixel_format: MTLPixelFormat::RGBA32Float,
+            ..multisample
+        };
+        assert_eq!(
+            validate(float32, &apple7),
+            Err(TextureDescriptorError::Multisample32BitFloat(MTLPixelFormat::RGBA32Float))
+        );
+        let apple7 = DeviceCapabilities {
+            supports_32bit_msaa: true,
+            ..apple7
+        };
+        assert_eq!(validate(float32, &apple7), Ok(()));
+    }
+
+    #[test]
+    fn compressed_texture_types() {
+        let apple7 = apple7();
+        let line = TextureDescription {
+            texture_type: MTLTextureType::Type1D,
+            pixel_format: MTLPixelFormat::ASTC_4x4_LDR,
+            height: 1,
+            ..texture()
+        };
+        assert_eq!(
+            validate(line, &apple7),
+            Err(TextureDescriptorError::CompressedTextureType {
+                texture_type: MTLTextureType::Type1D,
+                pixel_format: MTLPixelFormat::ASTC_4x4_LDR,
+            })
+        );
+        let volume = TextureDescription {
+            texture_type: MTLTextureType::Type3D,
+            pixel_format: MTLPixelFormat::ASTC_4x4_LDR,
+            depth: 4,
+            ..texture()
+        };
+        assert_eq!(validate(volume, &apple7), Ok(()));
+    }
+
+    #[test]
+    fn memoryless() {
+        let memoryless = TextureDescription {
+            storage_mode: MTLStorageMode::Memoryless,
+            usage: MTLTextureUsage::RENDER_TARGET,
+            ..texture()
+        };
+        assert_eq!(validate(memoryless, &mac2()), Err(TextureDescriptorError::MemorylessUnsupported));
+        let sampled = TextureDescription {
+            usage: MTLTextureUsage::RENDER_TARGET | MTLTextureUsage::SHADER_READ,
+            ..memoryless
+        };
+        assert_eq!(validate(sampled, &apple7()), Err(TextureDescriptorError::MemorylessUsage(sampled.usage)));
+    }
+
+    #[test]
+    fn read_write() {
+        let apple7 = apple7();
+        let read_only = TextureDescription {
+            pixel_format: MTLPixelFormat::R32Float,
+            read_write: true,
+            ..texture()
+        };
+        assert_eq!(
+            validate(read_only, &apple7),
+            Err(TextureDescriptorError::ReadWriteUsage(MTLTextureUsage::SHADER_READ))
+        );
+        let tier1 = TextureDescription {
+            usage: MTLTextureUsage::SHADER_READ | MTLTextureUsage::SHADER_WRITE,
+            ..read_only
+        };
+        assert_eq!(validate(tier1, &apple7), Ok(()));
+        let tier2 = TextureDescription {
+            pixel_format: MTLPixelFormat::RGBA8Unorm,
+            ..tier1
+        };
+        assert_eq!(
+            validate(tier2, &apple7),
+            Err(TextureDescriptorError::ReadWriteUnsupported {
+                pixel_format: MTLPixelFormat::RGBA8Unorm,
+                tier: MTLReadWriteTextureTier::Tier1,
+            })
+        );
+        let apple7 = DeviceCapabilities {
+            read_write_texture_tier: MTLReadWriteTextureTier::Tier2,
+            ..apple7
+        };
+        assert_eq!(validate(tier2, &apple7), Ok(()));
+    }
+
+    #[test]
+    fn placement_sparse() {
+        let sparse = TextureDescription {
+            placement_sparse_page_size: Some(MTLSparsePageSize::KB16),
+            ..texture()
+        };
+        let apple7 = apple7();
+        assert_eq!(
+            sparse.validate(TextureAllocation::Heap(MTLHeapType::Placement), &apple7),
+            Err(TextureDescriptorError::PlacementSparseUnsupported)
+        );
+        let apple7 = DeviceCapabilities {
+            supports_placement_sparse: true,
+            ..apple7
+        };
+        assert_eq!(sparse.validate(TextureAllocation::Heap(MTLHeapType::Placement), &apple7), Ok(()));
+        assert_eq!(
+            sparse.validate(TextureAllocation::Heap(MTLHeapType::Automatic), &apple7),
+            Err(TextureDescriptorError::PlacementSparseHeap(MTLHeapType::Automatic))
+        );
+    }
+
+    #[test]
+    fn violations_are_in_rule_order() {
+        let texture = TextureDescription {
+            texture_type: MTLTextureType::CubeArray,
+            pixel_format: MTLPixelFormat::ASTC_4x4_LDR,
+            sample_count: 2,
+            ..texture()
+        };
+        let violations = texture.violations(TextureAllocation::Device, &mac2());
+        assert_eq!(
+            violations,
+            [
+                TextureDescriptorError::UnsupportedPixelFormat(MTLPixelFormat::ASTC_4x4_LDR),
+                TextureDescriptorError::InvalidSize {
+                    texture_type: MTLTextureType::CubeArray,
+                    width: 256,
+                    height: 128,
+                    depth: 1,
+                },
+                TextureDescriptorError::SampleCountMismatch {
+                    texture_type: MTLTextureType::CubeArray,
+                    sample_count: 2,
+                },
+            ]
+        );
+    }
+}
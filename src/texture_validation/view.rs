@@ -0,0 +1,480 @@
+use core::fmt;
+use std::ops::Range;
+
+use super::TextureDescription;
+use crate::{MTLPixelFormat, MTLTextureType, MTLTextureUsage, MTLTextureViewDescriptor};
+
+/// Why a texture view can't be created.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TextureViewError {
+    /// The view's pixel format isn't in the texture format's compatibility
+    /// class.
+    IncompatiblePixelFormat {
+        texture: MTLPixelFormat,
+        view: MTLPixelFormat,
+    },
+    /// Reinterpreting the pixel format needs the texture to have
+    /// `MTLTextureUsage::PIXEL_FORMAT_VIEW`.
+    PixelFormatViewUsage {
+        texture: MTLPixelFormat,
+        view: MTLPixelFormat,
+    },
+    /// Views of textures of this type can't have the view's type.
+    IncompatibleTextureType {
+        texture: MTLTextureType,
+        view: MTLTextureType,
+    },
+    /// The levels are empty or past the texture's mipmap levels.
+    InvalidLevelRange {
+        levels: Range<usize>,
+        mipmap_level_count: usize,
+    },
+    /// The slices are empty or past the texture's slices.
+    InvalidSliceRange {
+        slices: Range<usize>,
+        slice_count: usize,
+    },
+    /// The view's type needs a different number of slices, such as 6 for a
+    /// cube or a multiple of 6 for a cube array.
+    InvalidSliceCount {
+        texture_type: MTLTextureType,
+        slice_count: usize,
+    },
+}
+
+impl fmt::Display for TextureViewError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::IncompatiblePixelFormat {
+                texture,
+                view,
+            } => write!(f, "{texture:?} textures can't be viewed as {view:?}"),
+            Self::PixelFormatViewUsage {
+                texture,
+                view,
+            } => write!(f, "viewing {texture:?} textures as {view:?} needs pixel format view usage"),
+            Self::IncompatibleTextureType {
+                texture,
+                view,
+            } => write!(f, "{texture:?} textures can't be viewed as {view:?}"),
+            Self::InvalidLevelRange {
+                levels,
+                mipmap_level_count,
+            } => write!(f, "levels {levels:?} aren't within a texture's {mipmap_level_count} mipmap levels"),
+            Self::InvalidSliceRange {
+                slices,
+                slice_count,
+            } => write!(f, "slices {slices:?} aren't within a texture's {slice_count} slices"),
+            Self::InvalidSliceCount {
+                texture_type,
+                slice_count,
+            } => write!(f, "{texture_type:?} views can't have {slice_count} slices"),
+        }
+    }
+}
+
+impl std::error::Error for TextureViewError {}
+
+/// The properties of a texture view that validation checks.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextureViewDescription {
+    pub pixel_format: MTLPixelFormat,
+    pub texture_type: MTLTextureType,
+    pub levels: Range<usize>,
+    /// Array elements, or faces for cube textures, with 6 per cube.
+    pub slices: Range<usize>,
+}
+
+impl TextureViewDescription {
+    /// Reads the properties of `descriptor`.
+    pub fn new(descriptor: &MTLTextureViewDescriptor) -> Self {
+        Self {
+            pixel_format: descriptor.pixel_format(),
+            texture_type: descriptor.texture_type(),
+            levels: descriptor.level_range(),
+            slices: descriptor.slice_range(),
+        }
+    }
+
+    /// A view of all of `texture` with a different pixel format, as
+    /// `new_texture_view_with_pixel_format` creates.
+    pub fn with_pixel_format(
+        texture: &TextureDescription,
+        pixel_format: MTLPixelFormat,
+    ) -> Self {
+        Self {
+            pixel_format,
+            texture_type: texture.texture_type,
+            levels: 0..texture.mipmap_level_count,
+            slices: 0..slice_count(texture),
+        }
+    }
+
+    /// Checks the view can be created from `texture`.
+    pub fn validate(
+        &self,
+        texture: &TextureDescription,
+    ) -> Result<(), TextureViewError> {
+        self.validate_pixel_format(texture)?;
+        if !view_types(texture.texture_type).contains(&self.texture_type) {
+            return Err(TextureViewError::IncompatibleTextureType {
+                texture: texture.texture_type,
+                view: self.texture_type,
+            });
+        }
+        if self.levels.is_empty() || self.levels.end > texture.mipmap_level_count {
+            return Err(TextureViewError::InvalidLevelRange {
+                levels: self.levels.clone(),
+                mipmap_level_count: texture.mipmap_level_count,
+            });
+        }
+        let slice_count = slice_count(texture);
+        if self.slices.is_empty() || self.slices.end > slice_count {
+            return Err(TextureViewError::InvalidSliceRange {
+                slices: self.slices.clone(),
+                slice_count,
+            });
+        }
+        let view_slices = self.slices.len();
+        let valid = match self.texture_type {
+            MTLTextureType::Cube => view_slices == 6,
+            MTLTextureType::CubeArray => view_slices.is_multiple_of(6),
+            MTLTextureType::Type1DArray | MTLTextureType::Type2DArray | MTLTextureType::Type2DMultisampleArray => true,
+            _ => view_slices == 1,
+        };
+        if !valid {
+            return Err(TextureViewError::InvalidSliceCount {
+                texture_type: self.texture_type,
+                slice_count: view_slices,
+            });
+        }
+        Ok(())
+    }
+
+    fn validate_pixel_format(
+        &self,
+        texture: &TextureDescription,
+    ) -> Result<(), TextureViewError> {
+        let (source, view) = (texture.pixel_format, self.pixel_format);
+        // Switching between sRGB and linear encoding is always allowed.
+        if source.linear_format() == view.linear_format() {
+            return Ok(());
+        }
+        let compatible = match (source, view) {
+            (MTLPixelFormat::Depth32Float_Stencil8, MTLPixelFormat::X32_Stencil8)
+            | (MTLPixelFormat::Depth24Unorm_Stencil8, MTLPixelFormat::X24_Stencil8) => true,
+            _ => {
+                is_reinterpretable(source)
+                    && is_reinterpretable(view)
+                    && source.bytes_per_block() == view.bytes_per_block()
+            },
+        };
+        if !compatible {
+            return Err(TextureViewError::IncompatiblePixelFormat {
+                texture: source,
+                view,
+            });
+        }
+        if !texture.usage.contains(MTLTextureUsage::PIXEL_FORMAT_VIEW) {
+            return Err(TextureViewError::PixelFormatViewUsage {
+                texture: source,
+                view,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Whether views can reinterpret the format's bits as another format of the
+/// same size: uncompressed color formats other than 4:2:2.
+fn is_reinterpretable(format: MTLPixelFormat) -> bool {
+    use MTLPixelFormat as F;
+
+    let excluded = matches!(
+        format,
+        F::Invalid
+            | F::Unspecialized
+            | F::GBGR422
+            | F::BGRG422
+            | F::Depth16Unorm
+            | F::Depth32Float
+            | F::Stencil8
+            | F::Depth24Unorm_Stencil8
+            | F::Depth32Float_Stencil8
+            | F::X32_Stencil8
+            | F::X24_Stencil8
+    );
+    !excluded && !format.is_compressed()
+}
+
+/// The types views of a texture of `texture_type` can have.
+fn view_types(texture_type: MTLTextureType) -> &'static [MTLTextureType] {
+    use MTLTextureType as T;
+
+    match texture_type {
+        T::Type1D | T::Type1DArray => &[T::Type1D, T::Type1DArray],
+        T::Type2D => &[T::Type2D, T::Type2DArray],
+        T::Type2DArray | T::Cube | T::CubeArray => &[T::Type2D, T::Type2DArray, T::Cube, T::CubeArray],
+        T::Type2DMultisample | T::Type2DMultisampleArray => &[T::Type2DMultisample, T::Type2DMultisampleArray],
+        T::Type3D => &[T::Type3D],
+        T::TextureBuffer => &[T::TextureBuffer],
+    }
+}
+
+/// Slices of `texture` that views can select, with a slice per cube face.
+fn slice_count(texture: &TextureDescription) -> usize {
+    match texture.texture_type {
+        MTLTextureType::Cube | MTLTextureType::CubeArray => texture.array_length * 6,
+        _ => texture.array_length,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MTLStorageMode;
+
+    fn texture(
+        texture_type: MTLTextureType,
+        pixel_format: MTLPixelFormat,
+        usage: MTLTextureUsage,
+    ) -> TextureDescription {
+        let height = match texture_type {
+            MTLTextureType::Type1D | MTLTextureType::Type1DArray => 1,
+            MTLTextureType::Cube | MTLTextureType::CubeArray => 64,
+            _ => 32,
+        };
+        TextureDescription {
+            texture_type,
+            pixel_format,
+            width: 64,
+            height,
+            depth: 1,
+            mipmap_level_count: 4,
+            sample_count: 1,
+            array_length: 1,
+            storage_mode: MTLStorageMode::Private,
+            usage,
+            placement_sparse_page_size: None,
+            read_write: false,
+        }
+    }
+
+    fn view(
+        pixel_format: MTLPixelFormat,
+        texture_type: MTLTextureType,
+        slices: Range<usize>,
+    ) -> TextureViewDescription {
+        TextureViewDescription {
+            pixel_format,
+            texture_type,
+            levels: 0..4,
+            slices,
+        }
+    }
+
+    #[test]
+    fn srgb_views_need_no_usage() {
+        use MTLPixelFormat as F;
+
+        for (linear, srgb) in [
+            (F::RGBA8Unorm, F::RGBA8UnormSrgb),
+            (F::BGRA10_XR, F::BGRA10_XR_sRGB),
+            (F::BC1_RGBA, F::BC1_RGBA_sRGB),
+            (F::ETC2_RGB8, F::ETC2_RGB8_sRGB),
+            (F::ASTC_4x4_LDR, F::ASTC_4x4_sRGB),
+        ] {
+            let linear_texture = texture(MTLTextureType::Type2D, linear, MTLTextureUsage::SHADER_READ);
+            assert_eq!(
+                TextureViewDescription::with_pixel_format(&linear_texture, srgb).validate(&linear_texture),
+                Ok(())
+            );
+            let srgb_texture = texture(MTLTextureType::Type2D, srgb, MTLTextureUsage::SHADER_READ);
+            assert_eq!(
+                TextureViewDescription::with_pixel_format(&srgb_texture, linear).validate(&srgb_texture),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    fn reinterpreting_needs_pixel_format_view_usage() {
+        use MTLPixelFormat as F;
+
+        let sampled = texture(MTLTextureType::Type2D, F::RGBA8Unorm, MTLTextureUsage::SHADER_READ);
+        let as_float = TextureViewDescription::with_pixel_format(&sampled, F::R32Float);
+        assert_eq!(
+            as_float.validate(&sampled),
+            Err(TextureViewError::PixelFormatViewUsage {
+                texture: F::RGBA8Unorm,
+                view: F::R32Float,
+            })
+        );
+        let viewable = texture(
+            MTLTextureType::Type2D,
+            F::RGBA8Unorm,
+            MTLTextureUsage::SHADER_READ | MTLTextureUsage::PIXEL_FORMAT_VIEW,
+        );
+        assert_eq!(as_float.validate(&viewable), Ok(()));
+        // Reinterpreting sRGB formats isn't an sRGB toggle.
+        assert_eq!(TextureViewDescription::with_pixel_format(&viewable, F::BGRA8UnormSrgb).validate(&viewable), Ok(()));
+
+        for (source, view) in [
+            (F::RGBA8Unorm, F::RG8Unorm),
+            (F::RGBA8Unorm, F::BC1_RGBA),
+            (F::BC1_RGBA, F::BC4_RUnorm),
+            (F::Depth32Float, F::R32Float),
+            (F::Depth32Float_Stencil8, F::Depth32Float),
+            (F::Depth32Float_Stencil8, F::X24_Stencil8),
+            (F::GBGR422, F::RG8Unorm),
+        ] {
+            let texture = texture(MTLTextureType::Type2D, source, MTLTextureUsage::PIXEL_FORMAT_VIEW);
+            assert_eq!(
+                TextureViewDescription::with_pixel_format(&texture, view).validate(&texture),
+                Err(TextureViewError::IncompatiblePixelFormat {
+                    texture: source,
+                    view,
+                }),
+                "{source:?} as {view:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn stencil_views() {
+        use MTLPixelFormat as F;
+
+        for (source, view) in [(F::Depth32Float_Stencil8, F::X32_Stencil8), (F::Depth24Unorm_Stencil8, F::X24_Stencil8)]
+        {
+            let texture = texture(
+                MTLTextureType::Type2D,
+                source,
+                MTLTextureUsage::RENDER_TARGET | MTLTextureUsage::PIXEL_FORMAT_VIEW,
+            );
+            assert_eq!(TextureViewDescription::with_pixel_format(&texture, view).validate(&texture), Ok(()));
+        }
+    }
+
+    #[test]
+    fn texture_types() {
+        use MTLTextureType as T;
+
+        let format = MTLPixelFormat::RGBA8Unorm;
+        let usage = MTLTextureUsage::SHADER_READ;
+        let plane = texture(T::Type2D, format, usage);
+        assert_eq!(view(format, T::Type2DArray, 0..1).validate(&plane), Ok(()));
+        assert_eq!(
+            view(format, T::Cube, 0..1).validate(&plane),
+            Err(TextureViewError::IncompatibleTextureType {
+                texture: T::Type2D,
+                view: T::Cube,
+            })
+        );
+        assert_eq!(
+            view(format, T::Type3D, 0..1).validate(&plane),
+            Err(TextureViewError::IncompatibleTextureType {
+                texture: T::Type2D,
+                view: T::Type3D,
+            })
+        );
+
+        let cubes = TextureDescription {
+            array_length: 2,
+            ..texture(T::CubeArray, format, usage)
+        };
+        assert_eq!(view(format, T::CubeArray, 0..12).validate(&cubes), Ok(()));
+        assert_eq!(view(format, T::Cube, 6..12).validate(&cubes), Ok(()));
+        assert_eq!(view(format, T::Type2DArray, 3..9).validate(&cubes), Ok(()));
+        assert_eq!(view(format, T::Type2D, 11..12).validate(&cubes), Ok(()));
+        assert_eq!(
+            view(format, T::CubeArray, 0..9).validate(&cubes),
+            Err(TextureViewError::InvalidSliceCount {
+                texture_type: T::CubeArray,
+                slice_count: 9,
+            })
+        );
+        assert_eq!(
+            view(format, T::Cube, 0..12).validate(&cubes),
+            Err(TextureViewError::InvalidSliceCount {
+                texture_type: T::Cube,
+                slice_count: 12,
+            })
+        );
+
+        let cube = texture(T::Cube, format, usage);
+        assert_eq!(view(format, T::CubeArray, 0..6).validate(&cube), Ok(()));
+        assert_eq!(
+            view(format, T::CubeArray, 0..12).validate(&cube),
+            Err(TextureViewError::InvalidSliceRange {
+                slices: 0..12,
+                slice_count: 6,
+            })
+        );
+        assert_eq!(
+            view(format, T::Type2D, 0..2).validate(&cube),
+            Err(TextureViewError::InvalidSliceCount {
+                texture_type: T::Type2D,
+                slice_count: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn level_and_slice_ranges() {
+        let texture = TextureDescription {
+            array_length: 3,
+            ..texture(MTLTextureType::Type2DArray, MTLPixelFormat::RGBA8Unorm, MTLTextureUsage::SHADER_READ)
+        };
+        let levels = |levels: Range<usize>| TextureViewDescription {
+            levels,
+            ..view(MTLPixelFormat::RGBA8Unorm, MTLTextureType::Type2DArray, 0..3)
+        };
+        assert_eq!(levels(1..4).validate(&texture), Ok(()));
+        for range in [2..2, 3..5] {
+            assert_eq!(
+                levels(range.clone()).validate(&texture),
+                Err(TextureViewError::InvalidLevelRange {
+                    levels: range,
+                    mipmap_level_count: 4,
+                })
+            );
+        }
+        for range in [1..1, 2..4] {
+            assert_eq!(
+                view(MTLPixelFormat::RGBA8Unorm, MTLTextureType::Type2DArray, range.clone()).validate(&texture),
+                Err(TextureViewError::InvalidSliceRange {
+                    slices: range,
+                    slice_count: 3,
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn linear_format_covers_every_srgb_format() {
+        let srgb = (0..=u16::MAX as u64)
+            .filter_map(|value| MTLPixelFormat::try_from(value).ok())
+            .filter(|format| format.is_srgb());
+        let mut count = 0;
+        for format in srgb {
+            let linear = format.linear_format();
+            let name = format!("{format:?}");
+            let expected = match name.strip_suffix("_sRGB") {
+                Some(prefix) if format.is_astc() => format!("{prefix}_LDR"),
+                Some(prefix) => prefix.to_owned(),
+                None => name.replace("UnormSrgb", "Unorm"),
+            };
+            assert_eq!(format!("{linear:?}"), expected);
+            assert!(!linear.is_srgb());
+            assert_eq!(linear.bytes_per_block(), format.bytes_per_block());
+            assert_eq!(linear.block_dimensions(), format.block_dimensions());
+            count += 1;
+        }
+        assert_eq!(count, 30);
+        assert_eq!(MTLPixelFormat::RGBA16Float.linear_format(), MTLPixelFormat::RGBA16Float);
+        assert_eq!(MTLPixelFormat::ASTC_4x4_HDR.linear_format(), MTLPixelFormat::ASTC_4x4_HDR);
+    }
+}
@@ -0,0 +1,107 @@
+use std::io;
+
+use super::{TraceArg, TraceTimeline, TraceTrackId, TraceTrackKind};
+use crate::json::write_json_string;
+
+/// Process id used for every track; each track is its own thread.
+const PROCESS_ID: usize = 1;
+
+/// Writes `timeline` in the Chrome Trace Event JSON format, readable by
+/// `chrome://tracing`, Perfetto UI and Speedscope.
+///
+/// Each track becomes a named thread of a single process, CPU tracks first.
+/// Spans are complete (`"X"`) events with microsecond timestamps.
+pub fn write_chrome_trace<W: io::Write>(
+    timeline: &TraceTimeline,
+    mut writer: W,
+) -> io::Result<()> {
+    writer.write_all(b"{\"displayTimeUnit\":\"ns\",\"traceEvents\":[")?;
+    let mut first = true;
+    let mut separator = |writer: &mut W| -> io::Result<()> {
+        if !first {
+            writer.write_all(b",")?;
+        }
+        first = false;
+        writer.write_all(b"\n")
+    };
+
+    separator(&mut writer)?;
+    write!(writer, "{{\"ph\":\"M\",\"pid\":{PROCESS_ID},\"name\":\"process_name\",\"args\":{{\"name\":\"Metal\"}}}}")?;
+    for (index, track) in timeline.tracks.iter().enumerate() {
+        let tid = index + 1;
+        separator(&mut writer)?;
+        write!(
+            writer,
+            "{{\"ph\":\"M\",\"pid\":{PROCESS_ID},\"tid\":{tid},\"name\":\"thread_name\",\"args\":{{\"name\":"
+        )?;
+        write_json_string(&mut writer, &track.name)?;
+        writer.write_all(b"}}")?;
+
+        let sort_index = match track.kind {
+            TraceTrackKind::Cpu => index,
+            TraceTrackKind::Gpu => timeline.tracks.len() + index,
+        };
+        separator(&mut writer)?;
+        write!(writer, "{{\"ph\":\"M\",\"pid\":{PROCESS_ID},\"tid\":{tid},\"name\":\"thread_sort_index\",")?;
+        write!(writer, "\"args\":{{\"sort_index\":{sort_index}}}}}")?;
+    }
+
+    for (index, _) in timeline.tracks.iter().enumerate() {
+        for span in timeline.track_spans(TraceTrackId(index)) {
+            separator(&mut writer)?;
+            writer.write_all(b"{\"ph\":\"X\",\"name\":")?;
+            write_json_string(&mut writer, &span.name)?;
+            writer.write_all(b",\"cat\":")?;
+            write_json_string(&mut writer, span.category)?;
+            write!(writer, ",\"pid\":{PROCESS_ID},\"tid\":{},\"ts\":", index + 1)?;
+            write_microseconds(&mut writer, span.start_ns)?;
+            writer.write_all(b",\"dur\":")?;
+            write_microseconds(&mut writer, span.duration_ns)?;
+            if !span.args.is_empty() {
+                writer.write_all(b",\"args\":{")?;
+                for (arg_index, (name, value)) in span.args.iter().enumerate() {
+                    if arg_index > 0 {
+                        writer.write_all(b",")?;
+                    }
+                    write_json_string(&mut writer, name)?;
+                    writer.write_all(b":")?;
+                    write_json_arg(&mut writer, value)?;
+                }
+                writer.write_all(b"}")?;
+            }
+            writer.write_all(b"}")?;
+        }
+    }
+
+    writer.write_all(b"\n]}\n")
+}
+
+/// [`write_chrome_trace`] into a string.
+pub fn chrome_trace_json(timeline: &TraceTimeline) -> String {
+    let mut bytes = Vec::new();
+    write_chrome_trace(timeline, &mut bytes).expect("writing to a Vec cannot fail");
+    String::from_utf8(bytes).expect("the Chrome trace writer only emits UTF-8")
+}
+
+/// Writes nanoseconds as microseconds with three decimals, without going
+/// through floating point.
+fn write_microseconds(
+    writer: &mut impl io::Write,
+    nanoseconds: u64,
+) -> io::Result<()> {
+    write!(writer, "{}.{:03}", nanoseconds / 1000, nanoseconds % 1000)
+}
+
+fn write_json_arg(
+    writer: &mut impl io::Write,
+    value: &TraceArg,
+) -> io::Result<()> {
+    match value {
+        TraceArg::Bool(value) => write!(writer, "{value}"),
+        TraceArg::Int(value) => write!(writer, "{value}"),
+        TraceArg::UInt(value) => write!(writer, "{value}"),
+        TraceArg::Float(value) if value.is_finite() => write!(writer, "{value}"),
+        TraceArg::Float(_) => writer.write_all(b"null"),
+        TraceArg::String(value) => write_json_string(writer, value),
+    }
+}
@@ -0,0 +1,103 @@
+mod chrome;
+mod perfetto;
+mod timeline;
+
+pub use chrome::{chrome_trace_json, write_chrome_trace};
+pub use perfetto::{perfetto_trace_bytes, write_perfetto_trace};
+pub use timeline::{
+    CommandBufferTimes, TraceArg, TraceSpan, TraceTimeline, TraceTrack, TraceTrackId, TraceTrackKind,
+    seconds_to_nanoseconds,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GpuTimingNode, GpuTimingTree};
+
+    const CHROME_GOLDEN: &str =
+        include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/trace_export/timeline.json"));
+    /// Encoded with prost from message definitions transcribed from
+    /// Perfetto's `protos/perfetto/trace/`, independently of this writer.
+    const PERFETTO_GOLDEN: &[u8] =
+        include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/trace_export/timeline.perfetto-trace"));
+
+    fn node(
+        name: &str,
+        begin_ns: Option<u64>,
+        end_ns: Option<u64>,
+        children: Vec<GpuTimingNode>,
+    ) -> GpuTimingNode {
+        GpuTimingNode {
+            name: name.to_owned(),
+            begin_ns,
+            end_ns,
+            children,
+        }
+    }
+
+    /// A frame with nested CPU spans, every argument type, a command buffer
+    /// and a GPU timing tree with one unresolved scope.
+    fn fixture_timeline() -> TraceTimeline {
+        let mut timeline = TraceTimeline::new();
+        let gpu = timeline.add_track("GPU", TraceTrackKind::Gpu);
+        let cpu = timeline.add_track("Main \"render\" thread", TraceTrackKind::Cpu);
+
+        let frame = timeline.add_span(cpu, "frame", "cpu", 1_000, 9_500);
+        frame.args.push(("index".to_owned(), TraceArg::UInt(3)));
+        frame.args.push(("vsync".to_owned(), TraceArg::Bool(true)));
+        frame.args.push(("offset".to_owned(), TraceArg::Int(-2)));
+        frame.args.push(("load".to_owned(), TraceArg::Float(0.5)));
+        frame.args.push(("drift".to_owned(), TraceArg::Float(f64::NAN)));
+        frame.args.push(("note".to_owned(), TraceArg::String("line\nbreak\u{1}".to_owned())));
+        timeline.add_span(cpu, "encode", "cpu", 1_250, 4_000);
+
+        timeline.add_command_buffer(
+            gpu,
+            Some(cpu),
+            "scene",
+            &CommandBufferTimes {
+                kernel_start_time: Some(0.000_004),
+                kernel_end_time: Some(0.000_005),
+                gpu_start_time: 0.000_005,
+                gpu_end_time: 0.000_009,
+            },
+        );
+        timeline.add_gpu_timing_tree(
+            gpu,
+            &GpuTimingTree {
+                frame: 7,
+                origin_ns: 5_200,
+                roots: vec![
+                    node("shadow", Some(0), Some(1_000), vec![node("cascade 0", Some(100), Some(600), Vec::new())]),
+                    node("main", Some(1_200), Some(3_500), Vec::new()),
+                    node("post", None, Some(3_700), Vec::new()),
+                ],
+            },
+        );
+        timeline
+    }
+
+    #[test]
+    fn chrome_trace_matches_golden() {
+        assert_eq!(chrome_trace_json(&fixture_timeline()), CHROME_GOLDEN);
+    }
+
+    #[test]
+    fn perfetto_trace_matches_golden() {
+        assert_eq!(perfetto_trace_bytes(&fixture_timeline()), PERFETTO_GOLDEN);
+        let mut written = Vec::new();
+        write_perfetto_trace(&fixture_timeline(), &mut written).unwrap();
+        assert_eq!(written, PERFETTO_GOLDEN);
+    }
+
+    #[test]
+    fn empty_timeline() {
+        let timeline = TraceTimeline::new();
+        assert_eq!(
+            chrome_trace_json(&timeline),
+            "{\"displayTimeUnit\":\"ns\",\"traceEvents\":[\n{\"ph\":\"M\",\"pid\":1,\"name\":\"process_name\",\"args\":{\"name\":\"Metal\"}}\n]}\n"
+        );
+        // Just the CPU and GPU group descriptors.
+        assert_eq!(perfetto_trace_bytes(&timeline), PERFETTO_GOLDEN[..30]);
+    }
+}
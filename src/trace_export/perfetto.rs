@@ -0,0 +1,369 @@
+use std::io;
+
+use super::{TraceArg, TraceSpan, TraceTimeline, TraceTrackId, TraceTrackKind};
+
+// Field numbers from Perfetto's `protos/perfetto/trace/`.
+const TRACE_PACKET: u32 = 1;
+const PACKET_TIMESTAMP: u32 = 8;
+const PACKET_SEQUENCE_ID: u32 = 10;
+const PACKET_TRACK_EVENT: u32 = 11;
+const PACKET_SEQUENCE_FLAGS: u32 = 13;
+const PACKET_TRACK_DESCRIPTOR: u32 = 60;
+const TRACK_DESCRIPTOR_UUID: u32 = 1;
+const TRACK_DESCRIPTOR_NAME: u32 = 2;
+const TRACK_DESCRIPTOR_PARENT_UUID: u32 = 5;
+const TRACK_EVENT_DEBUG_ANNOTATIONS: u32 = 4;
+const TRACK_EVENT_TYPE: u32 = 9;
+const TRACK_EVENT_TRACK_UUID: u32 = 11;
+const TRACK_EVENT_CATEGORIES: u32 = 22;
+const TRACK_EVENT_NAME: u32 = 23;
+const DEBUG_ANNOTATION_BOOL: u32 = 2;
+const DEBUG_ANNOTATION_UINT: u32 = 3;
+const DEBUG_ANNOTATION_INT: u32 = 4;
+const DEBUG_ANNOTATION_DOUBLE: u32 = 5;
+const DEBUG_ANNOTATION_STRING: u32 = 6;
+const DEBUG_ANNOTATION_NAME: u32 = 10;
+
+const TYPE_SLICE_BEGIN: u64 = 1;
+const TYPE_SLICE_END: u64 = 2;
+const SEQ_INCREMENTAL_STATE_CLEARED: u64 = 1;
+const SEQUENCE_ID: u64 = 1;
+
+/// Parent tracks grouping the CPU and GPU tracks in the viewer.
+const CPU_GROUP_UUID: u64 = 1;
+const GPU_GROUP_UUID: u64 = 2;
+
+/// Writes `timeline` as a Perfetto `Trace` protobuf, readable by
+/// ui.perfetto.dev and `trace_processor`.
+///
+/// Tracks become `TrackDescriptor`s grouped under "CPU" and "GPU" parent
+/// tracks; spans become nested `TYPE_SLICE_BEGIN`/`TYPE_SLICE_END` pairs of
+/// `TrackEvent`s on a single packet sequence, in timestamp order. Spans on one
+/// track must nest; a span that outlasts its parent is cut off at the parent's
+/// end.
+pub fn write_perfetto_trace<W: io::Write>(
+    timeline: &TraceTimeline,
+    mut writer: W,
+) -> io::Result<()> {
+    writer.write_all(&perfetto_trace_bytes(timeline))
+}
+
+/// [`write_perfetto_trace`] into a byte vector.
+pub fn perfetto_trace_bytes(timeline: &TraceTimeline) -> Vec<u8> {
+    let mut trace = ProtoWriter::default();
+    let mut sequence_flags = SEQ_INCREMENTAL_STATE_CLEARED;
+
+    for (uuid, name) in [(CPU_GROUP_UUID, "CPU"), (GPU_GROUP_UUID, "GPU")] {
+        let mut descriptor = ProtoWriter::default();
+        descriptor.varint(TRACK_DESCRIPTOR_UUID, uuid);
+        descriptor.string(TRACK_DESCRIPTOR_NAME, name);
+        trace.message(TRACE_PACKET, &descriptor_packet(&descriptor, &mut sequence_flags));
+    }
+    for (index, track) in timeline.tracks.iter().enumerate() {
+        let mut descriptor = ProtoWriter::default();
+        descriptor.varint(TRACK_DESCRIPTOR_UUID, track_uuid(TraceTrackId(index)));
+        descriptor.string(TRACK_DESCRIPTOR_NAME, &track.name);
+        let parent = match track.kind {
+            TraceTrackKind::Cpu => CPU_GROUP_UUID,
+            TraceTrackKind::Gpu => GPU_GROUP_UUID,
+        };
+        descriptor.varint(TRACK_DESCRIPTOR_PARENT_UUID, parent);
+        trace.message(TRACE_PACKET, &descriptor_packet(&descriptor, &mut sequence_flags));
+    }
+
+    // Each track's events are already in time order; a stable sort merges the
+    // tracks and keeps every slice end after its begin.
+    let mut events: Vec<(u64, ProtoWriter)> = Vec::new();
+    for index in 0..timeline.tracks.len() {
+        let track = TraceTrackId(index);
+        // End times of the open slices, clamped to their parent's.
+        let mut open: Vec<u64> = Vec::new();
+        for span in timeline.track_spans(track) {
+            while let Some(end) = open.last().copied().filter(|&end| end <= span.start_ns) {
+                events.push((end, slice_end_packet(track, end)));
+                open.pop();
+            }
+            let end = open.last().map_or(span.end_ns(), |&parent_end| span.end_ns().min(parent_end));
+            events.push((span.start_ns, slice_begin_packet(track, span)));
+            open.push(end);
+        }
+        while let Some(end) = open.pop() {
+            events.push((end, slice_end_packet(track, end)));
+        }
+    }
+    events.sort_by_key(|(timestamp, _)| *timestamp);
+    for (_, packet) in &events {
+        trace.message(TRACE_PACKET, packet);
+    }
+
+    trace.bytes
+}
+
+fn track_uuid(track: TraceTrackId) -> u64 {
+    GPU_GROUP_UUID + 1 + track.0 as u64
+}
+
+fn descriptor_packet(
+    descriptor: &ProtoWriter,
+    sequence_flags: &mut u64,
+) -> ProtoWriter {
+    let mut packet = ProtoWriter::default();
+    packet.varint(PACKET_SEQUENCE_ID, SEQUENCE_ID);
+    if *sequence_flags != 0 {
+        packet.varint(PACKET_SEQUENCE_FLAGS, *sequence_flags);
+        *sequence_flags = 0;
+    }
+    packet.message(PACKET_TRACK_DESCRIPTOR, descriptor);
+    packet
+}
+
+fn slice_begin_packet(
+    track: TraceTrackId,
+    span: &TraceSpan,
+) -> ProtoWriter {
+    let mut event = ProtoWriter::default();
+    for (name, value) in &span.args {
+        let mut annotation = ProtoWriter::default();
+        match value {
+            TraceArg::Bool(value) => annotation.varint(DEBUG_ANNOTATION_BOOL, *value as u64),
+            TraceArg::Int(value) => annotation.varint(DEBUG_ANNOTATION_INT, *value as u64),
+            TraceArg::UInt(value) => annotation.varint(DEBUG_ANNOTATION_UINT, *value),
+            TraceArg::Float(value) => annotation.fixed64(DEBUG_ANNOTATION_DOUBLE, value.to_bits()),
+            TraceArg::String(value) => annotation.string(DEBUG_ANNOTATION_STRING, value),
+        }
+        annotation.string(DEBUG_ANNOTATION_NAME, name);
+        event.message(TRACK_EVENT_DEBUG_ANNOTATIONS, &annotation);
+    }
+    event.varint(TRACK_EVENT_TYPE, TYPE_SLICE_BEGIN);
+    event.varint(TRACK_EVENT_TRACK_UUID, track_uuid(track));
+    event.string(TRACK_EVENT_CATEGORIES, span.category);
+    event.string(TRACK_EVENT_NAME, &span.name);
+    event_packet(span.start_ns, &event)
+}
+
+fn slice_end_packet(
+    track: TraceTrackId,
+    timestamp: u64,
+) -> ProtoWriter {
+    let mut event = ProtoWriter::default();
+    event.varint(TRACK_EVENT_TYPE, TYPE_SLICE_END);
+    event.varint(TRACK_EVENT_TRACK_UUID, track_uuid(track));
+    event_packet(timestamp, &event)
+}
+
+fn event_packet(
+    timestamp: u64,
+    event: &ProtoWriter,
+) -> ProtoWriter {
+    let mut packet = ProtoWriter::default();
+    packet.varint(PACKET_TIMESTAMP, timestamp);
+    packet.varint(PACKET_SEQUENCE_ID, SEQUENCE_ID);
+    packet.message(PACKET_TRACK_EVENT, event);
+    packet
+}
+
+/// Minimal protobuf wire-format writer.
+///
+/// Callers write fields in field-number order, as protobuf's own serializers
+/// do, so the output matches theirs byte for byte.
+#[derive(Default)]
+struct ProtoWriter {
+    bytes: Vec<u8>,
+}
+
+impl ProtoWriter {
+    const WIRE_VARINT: u32 = 0;
+    const WIRE_FIXED64: u32 = 1;
+    const WIRE_LENGTH_DELIMITED: u32 = 2;
+
+    fn raw_varint(
+        &mut self,
+        mut value: u64,
+    ) {
+        while value >= 0x80 {
+            self.bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+
+    fn key(
+        &mut self,
+        field: u32,
+        wire_type: u32,
+    ) {
+        self.raw_varint(u64::from(field << 3 | wire_type));
+    }
+
+    fn varint(
+        &mut self,
+        field: u32,
+        value: u64,
+    ) {
+        self.key(field, Self::WIRE_VARINT);
+        self.raw_varint(value);
+    }
+
+    fn fixed64(
+        &mut self,
+        field: u32,
+        value: u64,
+    ) {
+        self.key(field, Self::WIRE_FIXED64);
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn length_delimited(
+        &mut self,
+        field: u32,
+        payload: &[u8],
+    ) {
+        self.key(field, Self::WIRE_LENGTH_DELIMITED);
+        self.raw_varint(payload.len() as u64);
+        self.bytes.extend_from_slice(payload);
+    }
+
+    fn string(
+        &mut self,
+        field: u32,
+        value: &str,
+    ) {
+        self.length_delimited(field, value.as_bytes());
+    }
+
+    fn message(
+        &mut self,
+        field: u32,
+        message: &ProtoWriter,
+    ) {
+        self.length_delimited(field, &message.bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    enum Field<'a> {
+        Varint(u64),
+        Fixed64,
+        Bytes(&'a [u8]),
+    }
+
+    fn read_varint(
+        bytes: &[u8],
+        position: &mut usize,
+    ) -> u64 {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = bytes[*position];
+            *position += 1;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte < 0x80 {
+                break;
+            }
+        }
+        value
+    }
+
+    fn fields(bytes: &[u8]) -> Vec<(u32, Field<'_>)> {
+        let mut fields = Vec::new();
+        let mut position = 0;
+        while position < bytes.len() {
+            let key = read_varint(bytes, &mut position);
+            let field = match key & 7 {
+                0 => Field::Varint(read_varint(bytes, &mut position)),
+                1 => {
+                    position += 8;
+                    Field::Fixed64
+                },
+                2 => {
+                    let len = read_varint(bytes, &mut position) as usize;
+                    position += len;
+                    Field::Bytes(&bytes[position - len..position])
+                },
+                wire_type => panic!("unexpected wire type {wire_type}"),
+            };
+            fields.push(((key >> 3) as u32, field));
+        }
+        fields
+    }
+
+    fn varint(
+        message: &[u8],
+        number: u32,
+    ) -> Option<u64> {
+        fields(message).into_iter().find_map(|(field, value)| match value {
+            Field::Varint(value) if field == number => Some(value),
+            _ => None,
+        })
+    }
+
+    /// `(timestamp, type, track uuid)` of every track event, in file order.
+    fn track_events(trace: &[u8]) -> Vec<(u64, u64, u64)> {
+        fields(trace)
+            .into_iter()
+            .filter_map(|(_, packet)| {
+                let Field::Bytes(packet) = packet else {
+                    panic!("packets are messages")
+                };
+                fields(packet).into_iter().find_map(|(field, value)| match value {
+                    Field::Bytes(event) if field == PACKET_TRACK_EVENT => Some((
+                        varint(packet, PACKET_TIMESTAMP).unwrap(),
+                        varint(event, TRACK_EVENT_TYPE).unwrap(),
+                        varint(event, TRACK_EVENT_TRACK_UUID).unwrap(),
+                    )),
+                    _ => None,
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn packets_in_timestamp_order() {
+        let mut timeline = TraceTimeline::new();
+        let a = timeline.add_track("a", TraceTrackKind::Cpu);
+        let b = timeline.add_track("b", TraceTrackKind::Gpu);
+        timeline.add_span(a, "a1", "cpu", 0, 100);
+        timeline.add_span(a, "a2", "cpu", 300, 400);
+        timeline.add_span(b, "b1", "gpu", 50, 350);
+        timeline.add_span(b, "b2", "gpu", 60, 70);
+
+        let (a, b) = (track_uuid(a), track_uuid(b));
+        assert_eq!(
+            track_events(&perfetto_trace_bytes(&timeline)),
+            [
+                (0, TYPE_SLICE_BEGIN, a),
+                (50, TYPE_SLICE_BEGIN, b),
+                (60, TYPE_SLICE_BEGIN, b),
+                (70, TYPE_SLICE_END, b),
+                (100, TYPE_SLICE_END, a),
+                (300, TYPE_SLICE_BEGIN, a),
+                (350, TYPE_SLICE_END, b),
+                (400, TYPE_SLICE_END, a),
+            ]
+        );
+    }
+
+    #[test]
+    fn overlapping_spans_end_with_parent() {
+        let mut timeline = TraceTimeline::new();
+        let track = timeline.add_track("a", TraceTrackKind::Cpu);
+        timeline.add_span(track, "parent", "cpu", 0, 100);
+        timeline.add_span(track, "child", "cpu", 50, 150);
+        timeline.add_span(track, "next", "cpu", 120, 130);
+
+        let track = track_uuid(track);
+        assert_eq!(
+            track_events(&perfetto_trace_bytes(&timeline)),
+            [
+                (0, TYPE_SLICE_BEGIN, track),
+                (50, TYPE_SLICE_BEGIN, track),
+                (100, TYPE_SLICE_END, track),
+                (100, TYPE_SLICE_END, track),
+                (120, TYPE_SLICE_BEGIN, track),
+                (130, TYPE_SLICE_END, track),
+            ]
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use objc2::runtime::ProtocolObject;
+
+use crate::{GpuTimingNode, GpuTimingTree, MTLCommandBuffer, MTLCommandBufferExt};
+
+/// Identifies a track within a [`TraceTimeline`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TraceTrackId(pub(crate) usize);
+
+/// Whether a track holds CPU or GPU work.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TraceTrackKind {
+    Cpu,
+    Gpu,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceTrack {
+    pub name: String,
+    pub kind: TraceTrackKind,
+}
+
+/// Value of a span argument, shown in the viewer's details panel.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TraceArg {
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    String(String),
+}
+
+/// A named interval on one track. Times are nanoseconds on the host clock.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceSpan {
+    pub track: TraceTrackId,
+    pub name: String,
+    pub category: &'static str,
+    pub start_ns: u64,
+    pub duration_ns: u64,
+    pub args: Vec<(String, TraceArg)>,
+}
+
+impl TraceSpan {
+    pub fn end_ns(&self) -> u64 {
+        self.start_ns + self.duration_ns
+    }
+}
+
+/// Host-clock times of one `MTLCommandBuffer`, in seconds.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CommandBufferTimes {
+    pub kernel_start_time: Option<f64>,
+    pub kernel_end_time: Option<f64>,
+    pub gpu_start_time: f64,
+    pub gpu_end_time: f64,
+}
+
+impl CommandBufferTimes {
+    /// Reads the times of a completed command buffer.
+    pub fn from_command_buffer(command_buffer: &ProtocolObject<dyn MTLCommandBuffer>) -> Self {
+        Self {
+            kernel_start_time: command_buffer.kernel_start_time(),
+            kernel_end_time: command_buffer.kernel_end_time(),
+            gpu_start_time: command_buffer.gpu_start_time(),
+            gpu_end_time: command_buffer.gpu_end_time(),
+        }
+    }
+}
+
+/// CPU and GPU spans collected for export with
+/// [`write_chrome_trace`][super::write_chrome_trace] or
+/// [`write_perfetto_trace`][super::write_perfetto_trace].
+///
+/// GPU pass timings from [`GpuTimingTree`], command buffer times and CPU
+/// spans share the host clock, so they line up in the viewer.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TraceTimeline {
+    pub(crate) tracks: Vec<TraceTrack>,
+    pub(crate) spans: Vec<TraceSpan>,
+}
+
+impl TraceTimeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_track(
+        &mut self,
+        name: impl Into<String>,
+        kind: TraceTrackKind,
+    ) -> TraceTrackId {
+        self.tracks.push(TraceTrack {
+            name: name.into(),
+            kind,
+        });
+        TraceTrackId(self.tracks.len() - 1)
+    }
+
+    pub fn tracks(&self) -> &[TraceTrack] {
+        &self.tracks
+    }
+
+    pub fn track(
+        &self,
+        id: TraceTrackId,
+    ) -> &TraceTrack {
+        &self.tracks[id.0]
+    }
+
+    pub fn spans(&self) -> &[TraceSpan] {
+        &self.spans
+    }
+
+    /// Adds a span; `end_ns` before `start_ns` gives an empty span.
+    pub fn add_span(
+        &mut self,
+        track: TraceTrackId,
+        name: impl Into<String>,
+        category: &'static str,
+        start_ns: u64,
+        end_ns: u64,
+    ) -> &mut TraceSpan {
+        assert!(track.0 < self.tracks.len(), "unknown trace track {track:?}");
+        self.spans.push(TraceSpan {
+            track,
+            name: name.into(),
+            category,
+            start_ns,
+            duration_ns: end_ns.saturating_sub(start_ns),
+            args: Vec::new(),
+        });
+        self.spans.last_mut().unwrap()
+    }
+
+    /// Adds the command buffer's GPU execution to `gpu_track` and, when the
+    /// times are known, its scheduling to `cpu_track`.
+    pub fn add_command_buffer(
+        &mut self,
+        gpu_track: TraceTrackId,
+        cpu_track: Option<TraceTrackId>,
+        label: &str,
+        times: &CommandBufferTimes,
+    ) {
+        self.add_span(
+            gpu_track,
+            label,
+            "command_buffer",
+            seconds_to_nanoseconds(times.gpu_start_time),
+            seconds_to_nanoseconds(times.gpu_end_time),
+        );
+        if let (Some(track), Some(start), Some(end)) = (cpu_track, times.kernel_start_time, times.kernel_end_time) {
+            self.add_span(
+                track,
+                format!("{label} (scheduling)"),
+                "command_buffer",
+                seconds_to_nanoseconds(start),
+                seconds_to_nanoseconds(end),
+            );
+        }
+    }
+
+    /// Adds every node of a GPU timing tree with valid begin and end samples
+    /// to `track`, nested as in the tree.
+    pub fn add_gpu_timing_tree(
+        &mut self,
+        track: TraceTrackId,
+        tree: &GpuTimingTree,
+    ) {
+        fn add_node(
+            timeline: &mut TraceTimeline,
+            track: TraceTrackId,
+            tree: &GpuTimingTree,
+            node: &GpuTimingNode,
+        ) {
+            if let (Some(begin), Some(end)) = (node.begin_ns, node.end_ns) {
+                timeline
+                    .add_span(track, node.name.clone(), "gpu", tree.origin_ns + begin, tree.origin_ns + end)
+                    .args
+                    .push(("frame".to_owned(), TraceArg::UInt(tree.frame)));
+            }
+            for child in &node.children {
+                add_node(timeline, track, tree, child);
+            }
+        }
+        for root in &tree.roots {
+            add_node(self, track, tree, root);
+        }
+    }
+
+    /// Spans on `track` ordered so enclosing spans come before the spans they
+    /// contain.
+    pub(crate) fn track_spans(
+        &self,
+        track: TraceTrackId,
+    ) -> Vec<&TraceSpan> {
+        let mut spans: Vec<&TraceSpan> = self.spans.iter().filter(|span| span.track == track).collect();
+        spans.sort_by(|a, b| a.start_ns.cmp(&b.start_ns).then(b.duration_ns.cmp(&a.duration_ns)));
+        spans
+    }
+}
+
+/// Converts a host time in seconds, as reported by `MTLCommandBuffer`, to
+/// nanoseconds.
+pub fn seconds_to_nanoseconds(seconds: f64) -> u64 {
+    (seconds * 1e9).max(0.0).round() as u64
+}
@@ -0,0 +1,141 @@
+use core::{ptr, ptr::NonNull};
+
+use objc2::{rc::Retained, runtime::ProtocolObject};
+
+use super::{WeightError, WeightFile, WeightTensor};
+use crate::{
+    MTLBuffer, MTLDevice, MTLDeviceExt, MTLResourceOptions, MTLTensor, MTLTensorDescriptor, MTLTensorExtents,
+    MTLTensorUsage, TENSOR_MAX_RANK, TensorLayout, TensorLayoutError, TensorShape, staging_buffer::new_staging_buffer,
+};
+
+impl WeightTensor<'_> {
+    /// The shape of the Metal tensor holding these weights. Metal tensors have
+    /// at least one dimension, so scalars become `[1]`.
+    pub fn tensor_shape(&self) -> TensorShape {
+        if self.shape.rank() == 0 {
+            TensorShape::new(&[1]).unwrap()
+        } else {
+            self.shape
+        }
+    }
+
+    /// Fails unless `extents` equal [`tensor_shape`](Self::tensor_shape).
+    pub fn check_extents(
+        &self,
+        extents: &MTLTensorExtents,
+    ) -> Result<(), WeightError> {
+        let actual = TensorShape::from_extents(extents).map_err(|error| self.layout_error(error))?;
+        if actual != self.tensor_shape() {
+            return Err(WeightError::ShapeMismatch {
+                name: self.name.to_owned(),
+                expected: self.tensor_shape().dimensions().to_vec(),
+                actual: actual.dimensions().to_vec(),
+            });
+        }
+        Ok(())
+    }
+
+    /// A descriptor for a contiguous tensor of these weights.
+    pub fn tensor_descriptor(
+        &self,
+        usage: MTLTensorUsage,
+    ) -> Result<Retained<MTLTensorDescriptor>, WeightError> {
+        let layout = TensorLayout::contiguous(self.tensor_shape());
+        let dimensions = layout.shape().to_extents().map_err(|error| self.layout_error(error))?;
+        let strides = layout.strides_to_extents().map_err(|error| self.layout_error(error))?;
+        let descriptor = MTLTensorDescriptor::new();
+        descriptor.set_dimensions(&dimensions);
+        descriptor.set_strides(Some(&strides));
+        descriptor.set_data_type(self.data_type);
+        descriptor.set_usage(usage);
+        Ok(descriptor)
+    }
+
+    /// Creates a device tensor and copies the weights into it.
+    pub fn new_tensor(
+        &self,
+        device: &ProtocolObject<dyn MTLDevice>,
+        usage: MTLTensorUsage,
+    ) -> Result<Retained<ProtocolObject<dyn MTLTensor>>, WeightError> {
+        let descriptor = self.tensor_descriptor(usage)?;
+        let tensor = device
+            .new_tensor_with_descriptor(&descriptor)
+            .map_err(|error| WeightError::ResourceCreation(Some(error)))?;
+        self.fill_tensor(&tensor)?;
+        Ok(tensor)
+    }
+
+    /// Copies the weights into `tensor`, which must have the same data type
+    /// and shape.
+    pub fn fill_tensor(
+        &self,
+        tensor: &ProtocolObject<dyn MTLTensor>,
+    ) -> Result<(), WeightError> {
+        if tensor.data_type() != self.data_type {
+            return Err(WeightError::DataTypeMismatch {
+                name: self.name.to_owned(),
+                expected: self.data_type,
+                actual: tensor.data_type(),
+            });
+        }
+        self.check_extents(&tensor.dimensions())?;
+        if self.data.is_empty() {
+            return Ok(());
+        }
+        let layout = TensorLayout::contiguous(self.tensor_shape());
+        let origin = TensorShape::new(&[0; TENSOR_MAX_RANK][..layout.rank()])
+            .and_then(|origin| origin.to_extents())
+            .map_err(|error| self.layout_error(error))?;
+        let dimensions = layout.shape().to_extents().map_err(|error| self.layout_error(error))?;
+        let strides = layout.strides_to_extents().map_err(|error| self.layout_error(error))?;
+        let bytes = NonNull::from(self.data).cast();
+        tensor.replace_slice_origin_slice_dimensions_with_bytes_strides(&origin, &dimensions, bytes, &strides);
+        Ok(())
+    }
+
+    /// Copies the weights into a new buffer and creates a tensor viewing it;
+    /// the buffer is available through [`MTLTensor::buffer`].
+    ///
+    /// `options` must allow CPU access, since the buffer is initialized with
+    /// the weights.
+    pub fn new_buffer_tensor(
+        &self,
+        device: &ProtocolObject<dyn MTLDevice>,
+        options: MTLResourceOptions,
+        usage: MTLTensorUsage,
+    ) -> Result<Retained<ProtocolObject<dyn MTLTensor>>, WeightError> {
+        let buffer = if self.data.is_empty() {
+            new_staging_buffer(device, 0, options)
+        } else {
+            device.new_buffer_with_data(self.data, options)
+        }
+        .ok_or(WeightError::ResourceCreation(None))?;
+        let descriptor = self.tensor_descriptor(usage)?;
+        descriptor.set_resource_options(options);
+        let mut error = ptr::null_mut();
+        let tensor = buffer.new_tensor_with_descriptor_offset_error(&descriptor, 0, &mut error);
+        tensor.ok_or_else(|| WeightError::ResourceCreation(unsafe { Retained::retain(error) }))
+    }
+
+    fn layout_error(
+        &self,
+        error: TensorLayoutError,
+    ) -> WeightError {
+        WeightError::Layout {
+            name: self.name.to_owned(),
+            error,
+        }
+    }
+}
+
+impl WeightFile {
+    /// Creates and fills a device tensor for every tensor in the file, in
+    /// file order.
+    pub fn new_tensors(
+        &self,
+        device: &ProtocolObject<dyn MTLDevice>,
+        usage: MTLTensorUsage,
+    ) -> Result<Vec<(String, Retained<ProtocolObject<dyn MTLTensor>>)>, WeightError> {
+        self.tensors().map(|weights| Ok((weights.name.to_owned(), weights.new_tensor(device, usage)?))).collect()
+    }
+}
@@ -0,0 +1,9 @@
+mod loader;
+mod npy;
+mod npz;
+mod safetensors;
+mod weight_file;
+
+pub use npy::npy_data_type;
+pub use safetensors::safetensors_data_type;
+pub use weight_file::{WeightError, WeightFile, WeightFormat, WeightTensor};
@@ -0,0 +1,112 @@
+use core::ops::Range;
+
+use super::{WeightError, weight_file::WeightEntry};
+use crate::MTLTensorDataType;
+
+pub(crate) const MAGIC: &[u8] = b"\x93NUMPY";
+
+/// Maps a NumPy `descr` string such as `"<f4"` to the tensor data type with
+/// the same layout. Big-endian multi-byte types have no match.
+pub fn npy_data_type(descr: &str) -> Option<MTLTensorDataType> {
+    let (byte_order, kind) = descr.split_at_checked(1)?;
+    let data_type = match kind {
+        "f4" => MTLTensorDataType::Float32,
+        "f2" => MTLTensorDataType::Float16,
+        "i1" => return Some(MTLTensorDataType::Int8),
+        "u1" => return Some(MTLTensorDataType::UInt8),
+        "i2" => MTLTensorDataType::Int16,
+        "u2" => MTLTensorDataType::UInt16,
+        "i4" => MTLTensorDataType::Int32,
+        "u4" => MTLTensorDataType::UInt32,
+        _ => return None,
+    };
+    matches!(byte_order, "<" | "=" | "|").then_some(data_type)
+}
+
+/// Parses the `.npy` array occupying `range` of `bytes`.
+pub(crate) fn parse(
+    name: String,
+    bytes: &[u8],
+    range: Range<usize>,
+) -> Result<WeightEntry, WeightError> {
+    let file = &bytes[range.clone()];
+    if !file.starts_with(MAGIC) {
+        return Err(WeightError::InvalidHeader(format!("{name:?} is not an .npy array")));
+    }
+    let (header_length, header_start) = match file.get(MAGIC.len()) {
+        Some(1) => (file.get(8..10).map(|length| u16::from_le_bytes([length[0], length[1]]) as usize), 10),
+        Some(2 | 3) => (file.get(8..12).map(|length| u32::from_le_bytes(length.try_into().unwrap()) as usize), 12),
+        Some(version) => return Err(WeightError::InvalidHeader(format!("unsupported .npy version {version}"))),
+        None => return Err(WeightError::Truncated),
+    };
+    let data_start = header_length
+        .and_then(|length| length.checked_add(header_start))
+        .filter(|&start| start <= file.len())
+        .ok_or(WeightError::Truncated)?;
+    let header = core::str::from_utf8(&file[header_start..data_start])
+        .map_err(|_| WeightError::InvalidHeader(format!("{name:?} has a non-UTF-8 header")))?;
+
+    let descr = dict_value(header, "descr")
+        .and_then(|value| value.strip_prefix('\'')?.strip_suffix('\''))
+        .ok_or_else(|| invalid_header(&name, "descr"))?;
+    let data_type = npy_data_type(descr).ok_or_else(|| WeightError::UnsupportedDataType {
+        name: name.clone(),
+        dtype: descr.to_owned(),
+    })?;
+    let fortran_order = match dict_value(header, "fortran_order") {
+        Some("False") => false,
+        Some("True") => true,
+        _ => return Err(invalid_header(&name, "fortran_order")),
+    };
+    let mut shape = dict_value(header, "shape")
+        .and_then(|value| value.strip_prefix('(')?.strip_suffix(')'))
+        .and_then(|tuple| {
+            tuple
+                .split(',')
+                .map(str::trim)
+                .filter(|extent| !extent.is_empty())
+                .map(|extent| extent.trim_end_matches('L').parse().ok())
+                .collect::<Option<Vec<usize>>>()
+        })
+        .ok_or_else(|| invalid_header(&name, "shape"))?;
+    if fortran_order {
+        // Column-major data is already innermost first.
+        shape.reverse();
+    }
+    WeightEntry::new(name, data_type, &shape, range.start + data_start..range.end)
+}
+
+/// Finds the raw value of `key` in the Python dict literal of an `.npy`
+/// header. Values are strings, booleans or tuples, so the value ends at the
+/// first `,` or `}` outside parentheses.
+fn dict_value<'a>(
+    header: &'a str,
+    key: &str,
+) -> Option<&'a str> {
+    let key_start = header.find(&format!("'{key}'"))? + key.len() + 2;
+    let rest = header[key_start..].trim_start().strip_prefix(':')?.trim_start();
+    let mut depth = 0usize;
+    let end = rest
+        .char_indices()
+        .find(|&(_, c)| match c {
+            '(' => {
+                depth += 1;
+                false
+            },
+            ')' => {
+                depth = depth.saturating_sub(1);
+                false
+            },
+            ',' | '}' => depth == 0,
+            _ => false,
+        })
+        .map_or(rest.len(), |(index, _)| index);
+    Some(rest[..end].trim())
+}
+
+fn invalid_header(
+    name: &str,
+    field: &str,
+) -> WeightError {
+    WeightError::InvalidHeader(format!("{name:?} has a missing or invalid {field:?}"))
+}
@@ -0,0 +1,150 @@
+use super::{WeightError, npy, weight_file::WeightEntry};
+
+pub(crate) const LOCAL_HEADER_SIGNATURE: &[u8] = b"PK\x03\x04";
+pub(crate) const EMPTY_ARCHIVE_SIGNATURE: &[u8] = b"PK\x05\x06";
+const CENTRAL_HEADER_SIGNATURE: &[u8] = b"PK\x01\x02";
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: &[u8] = EMPTY_ARCHIVE_SIGNATURE;
+const ZIP64_END_OF_CENTRAL_DIRECTORY_SIGNATURE: &[u8] = b"PK\x06\x06";
+const ZIP64_LOCATOR_SIGNATURE: &[u8] = b"PK\x06\x07";
+const ZIP64_EXTRA_FIELD: u16 = 0x0001;
+const METHOD_STORED: u16 = 0;
+pub(crate) const METHOD_DEFLATED: u16 = 8;
+
+/// Parses the `.npy` members of a zip archive written by `numpy.savez`.
+/// Only stored (uncompressed) members are supported, so arrays are read in
+/// place. `numpy.savez_compressed` archives deflate their members and are
+/// rejected with [`WeightError::UnsupportedCompression`]; there is no inflater.
+pub(crate) fn parse(bytes: &[u8]) -> Result<Vec<WeightEntry>, WeightError> {
+    let (entry_count, mut position) = central_directory(bytes)?;
+    let mut entries = Vec::new();
+    for _ in 0..entry_count {
+        let header = bytes.get(position..position + 46).ok_or(WeightError::Truncated)?;
+        if !header.starts_with(CENTRAL_HEADER_SIGNATURE) {
+            return Err(invalid("bad central directory entry"));
+        }
+        let method = read_u16(header, 10);
+        let mut compressed_size = u64::from(read_u32(header, 20));
+        let mut uncompressed_size = u64::from(read_u32(header, 24));
+        let name_length = read_u16(header, 28) as usize;
+        let extra_length = read_u16(header, 30) as usize;
+        let comment_length = read_u16(header, 32) as usize;
+        let mut local_header_offset = u64::from(read_u32(header, 42));
+
+        let name_start = position + 46;
+        let name = bytes.get(name_start..name_start + name_length).ok_or(WeightError::Truncated)?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        let extra = bytes
+            .get(name_start + name_length..name_start + name_length + extra_length)
+            .ok_or(WeightError::Truncated)?;
+        // ZIP64 sizes replace the 32-bit fields set to 0xffffffff, in this order.
+        if let Some(mut zip64) = extra_field(extra, ZIP64_EXTRA_FIELD) {
+            for field in [&mut uncompressed_size, &mut compressed_size, &mut local_header_offset] {
+                if *field == u64::from(u32::MAX) {
+                    *field = zip64
+                        .first_chunk::<8>()
+                        .map(|value| u64::from_le_bytes(*value))
+                        .ok_or(WeightError::Truncated)?;
+                    zip64 = &zip64[8..];
+                }
+            }
+        }
+        position = name_start + name_length + extra_length + comment_length;
+
+        let Some(array_name) = name.strip_suffix(".npy") else {
+            continue;
+        };
+        if method != METHOD_STORED || compressed_size != uncompressed_size {
+            return Err(WeightError::UnsupportedCompression {
+                name,
+                method,
+            });
+        }
+        let data = member_data(bytes, local_header_offset, compressed_size)?;
+        entries.push(npy::parse(array_name.to_owned(), bytes, data)?);
+    }
+    Ok(entries)
+}
+
+/// The entry count and offset of the central directory.
+fn central_directory(bytes: &[u8]) -> Result<(u64, usize), WeightError> {
+    // The end record is 22 bytes plus a comment of up to 65535 bytes.
+    let search_start = bytes.len().saturating_sub(22 + u16::MAX as usize);
+    let end = (search_start..=bytes.len().saturating_sub(22))
+        .rev()
+        .find(|&offset| bytes[offset..].starts_with(END_OF_CENTRAL_DIRECTORY_SIGNATURE))
+        .ok_or_else(|| invalid("missing end of central directory"))?;
+    let record = bytes.get(end..end + 22).ok_or(WeightError::Truncated)?;
+    let mut entry_count = u64::from(read_u16(record, 10));
+    let mut directory_offset = u64::from(read_u32(record, 16));
+
+    let locator = end.checked_sub(20).map(|start| &bytes[start..end]);
+    if let Some(locator) = locator.filter(|locator| locator.starts_with(ZIP64_LOCATOR_SIGNATURE)) {
+        let record_offset = usize::try_from(read_u64(locator, 8)).map_err(|_| WeightError::Truncated)?;
+        let record_end = record_offset.checked_add(56).ok_or(WeightError::Truncated)?;
+        let record = bytes.get(record_offset..record_end).ok_or(WeightError::Truncated)?;
+        if !record.starts_with(ZIP64_END_OF_CENTRAL_DIRECTORY_SIGNATURE) {
+            return Err(invalid("bad ZIP64 end of central directory"));
+        }
+        entry_count = read_u64(record, 32);
+        directory_offset = read_u64(record, 48);
+    }
+    let directory_offset =
+        usize::try_from(directory_offset).ok().filter(|&offset| offset <= bytes.len()).ok_or(WeightError::Truncated)?;
+    Ok((entry_count, directory_offset))
+}
+
+/// The byte range of a member's data, after its local header.
+fn member_data(
+    bytes: &[u8],
+    local_header_offset: u64,
+    size: u64,
+) -> Result<core::ops::Range<usize>, WeightError> {
+    let offset = usize::try_from(local_header_offset).map_err(|_| WeightError::Truncated)?;
+    let header = bytes.get(offset..offset.saturating_add(30)).ok_or(WeightError::Truncated)?;
+    if !header.starts_with(LOCAL_HEADER_SIGNATURE) {
+        return Err(invalid("bad local file header"));
+    }
+    let start = offset + 30 + read_u16(header, 26) as usize + read_u16(header, 28) as usize;
+    let end = usize::try_from(size).ok().and_then(|size| start.checked_add(size)).filter(|&end| end <= bytes.len());
+    end.map(|end| start..end).ok_or(WeightError::Truncated)
+}
+
+fn extra_field(
+    mut extra: &[u8],
+    id: u16,
+) -> Option<&[u8]> {
+    while extra.len() >= 4 {
+        let (field_id, length) = (read_u16(extra, 0), read_u16(extra, 2) as usize);
+        let data = extra.get(4..4 + length)?;
+        if field_id == id {
+            return Some(data);
+        }
+        extra = &extra[4 + length..];
+    }
+    None
+}
+
+fn read_u16(
+    bytes: &[u8],
+    offset: usize,
+) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(
+    bytes: &[u8],
+    offset: usize,
+) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(
+    bytes: &[u8],
+    offset: usize,
+) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+fn invalid(message: &str) -> WeightError {
+    WeightError::InvalidHeader(message.to_owned())
+}
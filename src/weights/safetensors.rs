@@ -0,0 +1,87 @@
+use super::{WeightError, weight_file::WeightEntry};
+use crate::{MTLTensorDataType, json::JsonValue};
+
+/// Maps a safetensors `dtype` to the tensor data type with the same layout.
+pub fn safetensors_data_type(dtype: &str) -> Option<MTLTensorDataType> {
+    Some(match dtype {
+        "F32" => MTLTensorDataType::Float32,
+        "F16" => MTLTensorDataType::Float16,
+        "BF16" => MTLTensorDataType::BFloat16,
+        "I8" => MTLTensorDataType::Int8,
+        "U8" => MTLTensorDataType::UInt8,
+        "I16" => MTLTensorDataType::Int16,
+        "U16" => MTLTensorDataType::UInt16,
+        "I32" => MTLTensorDataType::Int32,
+        "U32" => MTLTensorDataType::UInt32,
+        _ => return None,
+    })
+}
+
+type Metadata = Vec<(String, String)>;
+
+/// Parses the 8-byte header length, the JSON header and each tensor's data
+/// range, returning the tensors in data order.
+pub(crate) fn parse(bytes: &[u8]) -> Result<(Vec<WeightEntry>, Metadata), WeightError> {
+    let header_length =
+        bytes.first_chunk::<8>().map(|length| u64::from_le_bytes(*length)).ok_or(WeightError::Truncated)?;
+    let data_start = usize::try_from(header_length)
+        .ok()
+        .and_then(|length| length.checked_add(8))
+        .filter(|&start| start <= bytes.len())
+        .ok_or(WeightError::Truncated)?;
+    let header = core::str::from_utf8(&bytes[8..data_start])
+        .map_err(|_| WeightError::InvalidHeader("header is not UTF-8".to_owned()))?;
+    let header = JsonValue::parse(header).map_err(WeightError::InvalidHeader)?;
+    let members = header.as_object().ok_or_else(|| invalid("header is not a JSON object"))?;
+    let data_length = bytes.len() - data_start;
+
+    let mut entries = Vec::with_capacity(members.len());
+    let mut metadata = Vec::new();
+    for (name, value) in members {
+        if name == "__metadata__" {
+            for (key, value) in value.as_object().ok_or_else(|| invalid("__metadata__ is not an object"))? {
+                let value = value.as_str().ok_or_else(|| invalid("__metadata__ values must be strings"))?;
+                metadata.push((key.clone(), value.to_owned()));
+            }
+            continue;
+        }
+        let dtype = value.get("dtype").and_then(JsonValue::as_str).ok_or_else(|| invalid_tensor(name, "dtype"))?;
+        let data_type = safetensors_data_type(dtype).ok_or_else(|| WeightError::UnsupportedDataType {
+            name: name.clone(),
+            dtype: dtype.to_owned(),
+        })?;
+        let shape = value
+            .get("shape")
+            .and_then(JsonValue::as_array)
+            .and_then(|shape| {
+                shape.iter().map(|extent| usize::try_from(extent.as_u64()?).ok()).collect::<Option<Vec<_>>>()
+            })
+            .ok_or_else(|| invalid_tensor(name, "shape"))?;
+        let (begin, end) = match value.get("data_offsets").and_then(JsonValue::as_array) {
+            Some([begin, end]) => (begin.as_u64(), end.as_u64()),
+            _ => (None, None),
+        };
+        let (begin, end) = begin
+            .zip(end)
+            .and_then(|(begin, end)| Some((usize::try_from(begin).ok()?, usize::try_from(end).ok()?)))
+            .filter(|&(begin, end)| begin <= end)
+            .ok_or_else(|| invalid_tensor(name, "data_offsets"))?;
+        if end > data_length {
+            return Err(WeightError::Truncated);
+        }
+        entries.push(WeightEntry::new(name.clone(), data_type, &shape, data_start + begin..data_start + end)?);
+    }
+    entries.sort_by_key(|entry| entry.data.start);
+    Ok((entries, metadata))
+}
+
+fn invalid(message: &str) -> WeightError {
+    WeightError::InvalidHeader(message.to_owned())
+}
+
+fn invalid_tensor(
+    name: &str,
+    field: &str,
+) -> WeightError {
+    WeightError::InvalidHeader(format!("tensor {name:?} has a missing or invalid {field:?}"))
+}
@@ -0,0 +1,476 @@
+use core::{fmt, ops::Range};
+use std::{io, path::Path};
+
+use objc2::rc::Retained;
+use objc2_foundation::NSError;
+
+use super::{npy, npz, safetensors};
+use crate::{MTLTensorDataType, TensorElement, TensorLayoutError, TensorShape, tensor_data_type_size};
+
+/// Errors raised while parsing weight files or loading them into tensors.
+#[derive(Debug)]
+pub enum WeightError {
+    Io(io::Error),
+    /// The file ends before the structure it describes.
+    Truncated,
+    /// The file's header is malformed.
+    InvalidHeader(String),
+    /// The tensor's element type has no `MTLTensorDataType`.
+    UnsupportedDataType {
+        name: String,
+        dtype: String,
+    },
+    /// An `.npz` member uses a compression method other than stored.
+    UnsupportedCompression {
+        name: String,
+        method: u16,
+    },
+    /// The tensor's data isn't `element count * element size` bytes.
+    DataLengthMismatch {
+        name: String,
+        expected: usize,
+        actual: usize,
+    },
+    /// The tensor's shape doesn't fit a Metal tensor.
+    Layout {
+        name: String,
+        error: TensorLayoutError,
+    },
+    /// No tensor with this name.
+    NotFound(String),
+    /// A destination tensor or extents don't match the weights. Dimensions
+    /// are innermost first.
+    ShapeMismatch {
+        name: String,
+        expected: Vec<usize>,
+        actual: Vec<usize>,
+    },
+    /// A destination tensor or element type doesn't match the weights.
+    DataTypeMismatch {
+        name: String,
+        expected: MTLTensorDataType,
+        actual: MTLTensorDataType,
+    },
+    /// Metal failed to create a buffer or tensor.
+    ResourceCreation(Option<Retained<NSError>>),
+}
+
+impl fmt::Display for WeightError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "failed to read weights: {error}"),
+            Self::Truncated => f.write_str("weight file is truncated"),
+            Self::InvalidHeader(message) => write!(f, "invalid weight file header: {message}"),
+            Self::UnsupportedDataType {
+                name,
+                dtype,
+            } => write!(f, "tensor {name:?} has unsupported element type {dtype:?}"),
+            Self::UnsupportedCompression {
+                name,
+                method: npz::METHOD_DEFLATED,
+            } => write!(f, "archive member {name:?} is deflated; numpy.savez_compressed archives are not supported"),
+            Self::UnsupportedCompression {
+                name,
+                method,
+            } => write!(f, "archive member {name:?} uses unsupported compression method {method}"),
+            Self::DataLengthMismatch {
+                name,
+                expected,
+                actual,
+            } => write!(f, "tensor {name:?} needs {expected} bytes of data but has {actual}"),
+            Self::Layout {
+                name,
+                error,
+            } => write!(f, "tensor {name:?}: {error}"),
+            Self::NotFound(name) => write!(f, "no tensor named {name:?}"),
+            Self::ShapeMismatch {
+                name,
+                expected,
+                actual,
+            } => write!(f, "tensor {name:?} has shape {expected:?} but the destination has {actual:?}"),
+            Self::DataTypeMismatch {
+                name,
+                expected,
+                actual,
+            } => write!(f, "tensor {name:?} has data type {expected:?} but the destination has {actual:?}"),
+            Self::ResourceCreation(Some(error)) => write!(f, "failed to create a Metal resource: {error}"),
+            Self::ResourceCreation(None) => f.write_str("failed to create a Metal resource"),
+        }
+    }
+}
+
+impl std::error::Error for WeightError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Layout {
+                error,
+                ..
+            } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for WeightError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// The container format of a [`WeightFile`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum WeightFormat {
+    SafeTensors,
+    Npy,
+    Npz,
+}
+
+/// One tensor of a [`WeightFile`], borrowing its data.
+///
+/// `shape` is innermost first, as Metal expects; the file's row-major shape is
+/// reversed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WeightTensor<'a> {
+    pub name: &'a str,
+    pub data_type: MTLTensorDataType,
+    pub shape: TensorShape,
+    /// Contiguous little-endian element data. May be unaligned.
+    pub data: &'a [u8],
+}
+
+impl WeightTensor<'_> {
+    pub fn element_count(&self) -> usize {
+        self.shape.element_count()
+    }
+
+    /// Copies the data out as `T`, which must match the data type.
+    pub fn to_vec<T: TensorElement>(&self) -> Result<Vec<T>, WeightError> {
+        if T::DATA_TYPE != self.data_type {
+            return Err(WeightError::DataTypeMismatch {
+                name: self.name.to_owned(),
+                expected: self.data_type,
+                actual: T::DATA_TYPE,
+            });
+        }
+        Ok(self
+            .data
+            .chunks_exact(size_of::<T>())
+            .map(|chunk| unsafe { chunk.as_ptr().cast::<T>().read_unaligned() })
+            .collect())
+    }
+}
+
+/// A parsed entry; `data` indexes [`WeightFile::bytes`].
+#[derive(Clone, Debug)]
+pub(crate) struct WeightEntry {
+    pub(crate) name: String,
+    pub(crate) data_type: MTLTensorDataType,
+    pub(crate) shape: TensorShape,
+    pub(crate) data: Range<usize>,
+}
+
+impl WeightEntry {
+    /// Builds an entry from a row-major `shape`, checking the rank and that
+    /// `data` holds exactly the tensor's bytes.
+    pub(crate) fn new(
+        name: String,
+        data_type: MTLTensorDataType,
+        row_major_shape: &[usize],
+        data: Range<usize>,
+    ) -> Result<Self, WeightError> {
+        let mut dimensions = row_major_shape.to_vec();
+        dimensions.reverse();
+        let shape = TensorShape::new(&dimensions).map_err(|error| WeightError::Layout {
+            name: name.clone(),
+            error,
+        })?;
+        let expected = shape
+            .checked_element_count()
+            .and_then(|count| count.checked_mul(tensor_data_type_size(data_type)?))
+            .ok_or_else(|| WeightError::Layout {
+                name: name.clone(),
+                error: TensorLayoutError::Overflow,
+            })?;
+        if expected != data.len() {
+            return Err(WeightError::DataLengthMismatch {
+                name,
+                expected,
+                actual: data.len(),
+            });
+        }
+        Ok(Self {
+            name,
+            data_type,
+            shape,
+            data,
+        })
+    }
+}
+
+/// Tensors parsed from a `.safetensors`, `.npy` or `.npz` file.
+///
+/// The file is kept in memory and tensors borrow their data from it.
+#[derive(Clone, Debug)]
+pub struct WeightFile {
+    format: WeightFormat,
+    bytes: Vec<u8>,
+    entries: Vec<WeightEntry>,
+    metadata: Vec<(String, String)>,
+}
+
+impl WeightFile {
+    /// Reads and parses a weight file, detecting the format from its
+    /// contents. A lone `.npy` array is named after the file stem.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, WeightError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)?;
+        if bytes.starts_with(npy::MAGIC) {
+            let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+            return Self::from_npy(name, bytes);
+        }
+        Self::from_bytes(bytes)
+    }
+
+    /// Parses a weight file, detecting the format from its contents. A lone
+    /// `.npy` array gets an empty name.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, WeightError> {
+        if bytes.starts_with(npy::MAGIC) {
+            Self::from_npy(String::new(), bytes)
+        } else if bytes.starts_with(npz::LOCAL_HEADER_SIGNATURE) || bytes.starts_with(npz::EMPTY_ARCHIVE_SIGNATURE) {
+            Self::from_npz(bytes)
+        } else {
+            Self::from_safetensors(bytes)
+        }
+    }
+
+    pub fn from_safetensors(bytes: Vec<u8>) -> Result<Self, WeightError> {
+        let (entries, metadata) = safetensors::parse(&bytes)?;
+        Ok(Self {
+            format: WeightFormat::SafeTensors,
+            bytes,
+            entries,
+            metadata,
+        })
+    }
+
+    pub fn from_npy(
+        name: impl Into<String>,
+        bytes: Vec<u8>,
+    ) -> Result<Self, WeightError> {
+        let entry = npy::parse(name.into(), &bytes, 0..bytes.len())?;
+        Ok(Self {
+            format: WeightFormat::Npy,
+            bytes,
+            entries: vec![entry],
+            metadata: Vec::new(),
+        })
+    }
+
+    /// Parses an uncompressed (`numpy.savez`) archive. Members are named
+    /// without their `.npy` extension. Compressed (`numpy.savez_compressed`)
+    /// archives fail with [`WeightError::UnsupportedCompression`].
+    pub fn from_npz(bytes: Vec<u8>) -> Result<Self, WeightError> {
+        let entries = npz::parse(&bytes)?;
+        Ok(Self {
+            format: WeightFormat::Npz,
+            bytes,
+            entries,
+            metadata: Vec::new(),
+        })
+    }
+
+    pub fn format(&self) -> WeightFormat {
+        self.format
+    }
+
+    /// The safetensors `__metadata__` entries, in file order.
+    pub fn metadata(&self) -> &[(String, String)] {
+        &self.metadata
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Tensors in file order.
+    pub fn tensors(&self) -> impl ExactSizeIterator<Item = WeightTensor<'_>> {
+        self.entries.iter().map(|entry| self.view(entry))
+    }
+
+    pub fn get(
+        &self,
+        name: &str,
+    ) -> Option<WeightTensor<'_>> {
+        self.entries.iter().find(|entry| entry.name == name).map(|entry| self.view(entry))
+    }
+
+    /// Like [`get`](Self::get), but with an error naming the missing tensor.
+    pub fn tensor(
+        &self,
+        name: &str,
+    ) -> Result<WeightTensor<'_>, WeightError> {
+        self.get(name).ok_or_else(|| WeightError::NotFound(name.to_owned()))
+    }
+
+    fn view<'a>(
+        &'a self,
+        entry: &'a WeightEntry,
+    ) -> WeightTensor<'a> {
+        WeightTensor {
+            name: &entry.name,
+            data_type: entry.data_type,
+            shape: entry.shape,
+            data: &self.bytes[entry.data.clone()],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture_path(name: &str) -> String {
+        format!("{}/tests/fixtures/weights/{name}", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    fn fixture(name: &str) -> Result<WeightFile, WeightError> {
+        WeightFile::from_bytes(std::fs::read(fixture_path(name)).unwrap())
+    }
+
+    fn shape(dimensions: &[usize]) -> TensorShape {
+        TensorShape::new(dimensions).unwrap()
+    }
+
+    #[test]
+    fn safetensors() {
+        let file = fixture("model.safetensors").unwrap();
+        assert_eq!(file.format(), WeightFormat::SafeTensors);
+        assert_eq!(
+            file.metadata(),
+            [("format".to_owned(), "pt".to_owned()), ("source".to_owned(), "fixture".to_owned())]
+        );
+        let names: Vec<_> = file.tensors().map(|tensor| tensor.name).collect();
+        assert_eq!(names, ["bias", "weight"]);
+
+        let bias = file.tensor("bias").unwrap();
+        assert_eq!((bias.data_type, bias.shape), (MTLTensorDataType::Float32, shape(&[3])));
+        assert_eq!(bias.to_vec::<f32>().unwrap(), [0.5, -1.0, 2.0]);
+        let weight = file.tensor("weight").unwrap();
+        assert_eq!((weight.data_type, weight.shape), (MTLTensorDataType::Float16, shape(&[2, 2])));
+        assert_eq!(weight.data, [0x00, 0x3C, 0x00, 0x40, 0x00, 0x42, 0x00, 0x44]);
+
+        assert!(matches!(bias.to_vec::<i32>(), Err(WeightError::DataTypeMismatch { .. })));
+        assert!(matches!(file.tensor("missing"), Err(WeightError::NotFound(name)) if name == "missing"));
+    }
+
+    #[test]
+    fn malformed_safetensors() {
+        assert!(matches!(fixture("truncated.safetensors"), Err(WeightError::Truncated)));
+        assert!(matches!(fixture("truncated_header.safetensors"), Err(WeightError::Truncated)));
+        assert!(matches!(WeightFile::from_safetensors(vec![1, 0]), Err(WeightError::Truncated)));
+        assert!(matches!(fixture("malformed.safetensors"), Err(WeightError::InvalidHeader(_))));
+        assert!(matches!(
+            fixture("bad_offsets.safetensors"),
+            Err(WeightError::InvalidHeader(message)) if message.contains("data_offsets")
+        ));
+        assert!(matches!(
+            fixture("unsupported_dtype.safetensors"),
+            Err(WeightError::UnsupportedDataType { dtype, .. }) if dtype == "F64"
+        ));
+    }
+
+    #[test]
+    fn npy() {
+        let file = WeightFile::open(fixture_path("matrix.npy")).unwrap();
+        assert_eq!(file.format(), WeightFormat::Npy);
+        let matrix = file.tensor("matrix").unwrap();
+        assert_eq!((matrix.data_type, matrix.shape), (MTLTensorDataType::Float32, shape(&[3, 2])));
+        assert_eq!(matrix.to_vec::<f32>().unwrap(), [0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+
+        // Column-major data is already innermost first.
+        let fortran = fixture("fortran.npy").unwrap();
+        let fortran = fortran.tensor("").unwrap();
+        assert_eq!(fortran.shape, shape(&[2, 3]));
+        assert_eq!(fortran.to_vec::<i32>().unwrap(), [0, 3, 1, 4, 2, 5]);
+
+        let version2 = fixture("version2.npy").unwrap();
+        let version2 = version2.tensor("").unwrap();
+        assert_eq!((version2.data_type, version2.data), (MTLTensorDataType::UInt8, [1, 2, 3, 4].as_slice()));
+    }
+
+    #[test]
+    fn malformed_npy() {
+        assert!(matches!(
+            fixture("big_endian.npy"),
+            Err(WeightError::UnsupportedDataType { dtype, .. }) if dtype == ">f4"
+        ));
+        assert!(matches!(
+            fixture("truncated_data.npy"),
+            Err(WeightError::DataLengthMismatch {
+                expected: 24,
+                actual: 20,
+                ..
+            })
+        ));
+        assert!(matches!(fixture("truncated_header.npy"), Err(WeightError::Truncated)));
+        assert!(matches!(WeightFile::from_npy("", b"\x93NUMPY".to_vec()), Err(WeightError::Truncated)));
+        assert!(matches!(WeightFile::from_npy("", b"\x93NUMPY\x04\x00".to_vec()), Err(WeightError::InvalidHeader(_))));
+    }
+
+    #[test]
+    fn npz() {
+        for name in ["arrays.npz", "zip64.npz"] {
+            let file = fixture(name).unwrap();
+            assert_eq!(file.format(), WeightFormat::Npz);
+            let names: Vec<_> = file.tensors().map(|tensor| tensor.name).collect();
+            assert_eq!(names, ["a", "b"], "{name}");
+            assert_eq!(file.tensor("a").unwrap().to_vec::<f32>().unwrap(), [1.0, 2.0, 3.0]);
+            let b = file.tensor("b").unwrap();
+            assert_eq!(
+                (b.data_type, b.shape, b.data),
+                (MTLTensorDataType::UInt8, shape(&[2, 2]), [1, 2, 3, 4].as_slice())
+            );
+        }
+        assert!(fixture("empty.npz").unwrap().is_empty());
+    }
+
+    #[test]
+    fn malformed_npz() {
+        let error = fixture("compressed.npz").unwrap_err();
+        assert!(matches!(&error, WeightError::UnsupportedCompression { name, method: 8 } if name == "a.npy"));
+        assert!(error.to_string().contains("savez_compressed"));
+        assert!(matches!(fixture("truncated.npz"), Err(WeightError::InvalidHeader(_))));
+
+        // Point the central directory past the end of the file.
+        let mut bytes = std::fs::read(fixture_path("arrays.npz")).unwrap();
+        let end = bytes.len() - 22;
+        bytes[end + 16..end + 20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(WeightFile::from_npz(bytes), Err(WeightError::Truncated)));
+    }
+
+    #[test]
+    fn malformed_zip64_locator() {
+        let bytes = std::fs::read(fixture_path("zip64.npz")).unwrap();
+        // The locator sits right before the 22-byte end record and holds the
+        // ZIP64 end record's offset at byte 8.
+        let locator = bytes.len() - 22 - 20;
+        assert_eq!(bytes[locator..locator + 4], *b"PK\x06\x07");
+        let with_record_offset = |offset: u64| {
+            let mut bytes = bytes.clone();
+            bytes[locator + 8..locator + 16].copy_from_slice(&offset.to_le_bytes());
+            WeightFile::from_npz(bytes)
+        };
+        assert!(matches!(with_record_offset(u64::MAX), Err(WeightError::Truncated)));
+        assert!(matches!(with_record_offset(usize::MAX as u64 - 55), Err(WeightError::Truncated)));
+        assert!(matches!(with_record_offset(bytes.len() as u64 - 55), Err(WeightError::Truncated)));
+        assert!(matches!(with_record_offset(0), Err(WeightError::InvalidHeader(message)) if message.contains("ZIP64")));
+    }
+}